@@ -0,0 +1,220 @@
+//! BGPKIT Broker-style index entries for finalized segments, so a focl
+//! archive can be indexed by bgpkit-broker without crawling.
+
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use chrono::DateTime;
+use reqwest::header::CONTENT_TYPE;
+use serde::{Deserialize, Serialize};
+
+use crate::archive::types::{ArchiveStream, FinalizedSegment};
+use crate::config::{ArchiveConfig, BrokerIndexConfig};
+
+const PUSH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Mirrors bgpkit-broker's `BrokerItem`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BrokerItem {
+    pub ts_start: String,
+    pub ts_end: String,
+    pub collector_id: String,
+    pub data_type: String,
+    pub url: String,
+    pub rough_size: i64,
+    pub exact_size: i64,
+}
+
+pub struct BrokerIndex {
+    cfg: BrokerIndexConfig,
+    updates_interval_secs: u32,
+    index_path: PathBuf,
+    /// Shared by every push; set when `endpoint` is.
+    client: Option<reqwest::Client>,
+}
+
+impl BrokerIndex {
+    /// `None` unless `[archive.broker]` is enabled.
+    pub fn new(cfg: &ArchiveConfig) -> Result<Option<Self>> {
+        if !cfg.broker.enabled {
+            return Ok(None);
+        }
+        let client = match &cfg.broker.endpoint {
+            Some(_) => Some(
+                reqwest::Client::builder()
+                    .timeout(PUSH_TIMEOUT)
+                    .build()
+                    .context("failed building HTTP client for [archive.broker]")?,
+            ),
+            None => None,
+        };
+        Ok(Some(Self {
+            cfg: cfg.broker.clone(),
+            updates_interval_secs: cfg.updates_interval_secs,
+            index_path: cfg
+                .broker
+                .index_path
+                .clone()
+                .unwrap_or_else(|| cfg.root.join("broker-index.jsonl")),
+            client,
+        }))
+    }
+
+    /// Broker convention: an updates file covers its whole interval, a RIB
+    /// dump is a single instant.
+    pub fn item(&self, segment: &FinalizedSegment) -> BrokerItem {
+        let (data_type, ts_end) = match segment.stream {
+            ArchiveStream::Updates => (
+                "updates",
+                segment.start_ts + i64::from(self.updates_interval_secs),
+            ),
+            ArchiveStream::Ribs => ("rib", segment.start_ts),
+        };
+        let relative = segment.relative_path.to_string_lossy();
+        let url = match &self.cfg.url_base {
+            Some(base) => format!("{}/{}", base.trim_end_matches('/'), relative),
+            None => relative.to_string(),
+        };
+        BrokerItem {
+            ts_start: broker_ts(segment.start_ts),
+            ts_end: broker_ts(ts_end),
+            collector_id: segment.collector_id.clone(),
+            data_type: data_type.to_string(),
+            url,
+            rough_size: segment.bytes as i64,
+            exact_size: segment.bytes as i64,
+        }
+    }
+
+    /// Append the segment to the index file and, if configured, push it to
+    /// the endpoint in the background. Failures are logged; the segment
+    /// itself is already safely on disk.
+    pub fn publish(&self, segment: &FinalizedSegment) {
+        let item = self.item(segment);
+        if let Err(err) = self.append(&item) {
+            tracing::error!(path=%self.index_path.display(), error=%err, "failed writing broker index");
+        }
+        if let (Some(endpoint), Some(client)) = (self.cfg.endpoint.clone(), self.client.clone()) {
+            tokio::spawn(async move {
+                if let Err(err) = post_item(&client, &endpoint, &item).await {
+                    tracing::warn!(endpoint=%endpoint, url=%item.url, error=%err, "failed pushing broker item");
+                }
+            });
+        }
+    }
+
+    fn append(&self, item: &BrokerItem) -> Result<()> {
+        if let Some(parent) = self.index_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let mut line = serde_json::to_vec(item)?;
+        line.push(b'\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.index_path)?
+            .write_all(&line)?;
+        Ok(())
+    }
+}
+
+fn broker_ts(ts: i64) -> String {
+    DateTime::from_timestamp(ts, 0)
+        .unwrap_or_default()
+        .format("%Y-%m-%dT%H:%M:%S")
+        .to_string()
+}
+
+async fn post_item(client: &reqwest::Client, endpoint: &str, item: &BrokerItem) -> Result<()> {
+    client
+        .post(endpoint)
+        .header(CONTENT_TYPE, "application/json")
+        .body(serde_json::to_vec(item)?)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CompressionKind;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn indexes_and_pushes_segments() {
+        let dir = tempfile::tempdir().unwrap();
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let endpoint = format!("http://{}/items", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut buf = vec![0u8; 4096];
+            // Headers and body may arrive in separate writes; the body is
+            // one JSON object.
+            while !request.ends_with(b"}") {
+                let read = stream.read(&mut buf).await.unwrap();
+                assert!(read > 0, "connection closed mid-request");
+                request.extend_from_slice(&buf[..read]);
+            }
+            stream
+                .write_all(b"HTTP/1.1 201 Created\r\nContent-Length: 0\r\n\r\n")
+                .await
+                .unwrap();
+            String::from_utf8_lossy(&request).to_string()
+        });
+
+        let mut cfg = ArchiveConfig {
+            root: dir.path().to_path_buf(),
+            ..Default::default()
+        };
+        cfg.broker = BrokerIndexConfig {
+            enabled: true,
+            url_base: Some("https://archive.example.net/".to_string()),
+            index_path: None,
+            endpoint: Some(endpoint.clone()),
+        };
+        let index = BrokerIndex::new(&cfg).unwrap().unwrap();
+        let segment = FinalizedSegment {
+            stream: ArchiveStream::Updates,
+            start_ts: 1_700_000_100,
+            end_ts: 1_700_001_000,
+            record_count: 10,
+            bytes: 2048,
+            compression: CompressionKind::Gzip,
+            final_path: dir.path().join("x.gz"),
+            relative_path: PathBuf::from("focl01/2023.11/UPDATES/updates.20231114.2215.gz"),
+            manifest_path: dir.path().join("x.gz.json"),
+            collector_id: "focl01".to_string(),
+            family: None,
+        };
+
+        let item = index.item(&segment);
+        assert_eq!(item.ts_start, "2023-11-14T22:15:00");
+        assert_eq!(item.ts_end, "2023-11-14T22:30:00");
+        assert_eq!(item.data_type, "updates");
+        assert_eq!(
+            item.url,
+            "https://archive.example.net/focl01/2023.11/UPDATES/updates.20231114.2215.gz"
+        );
+
+        index.append(&item).unwrap();
+        let written = fs::read_to_string(dir.path().join("broker-index.jsonl")).unwrap();
+        assert_eq!(
+            serde_json::from_str::<BrokerItem>(written.trim()).unwrap(),
+            item
+        );
+
+        post_item(index.client.as_ref().unwrap(), &endpoint, &item)
+            .await
+            .unwrap();
+        let request = server.await.unwrap();
+        assert!(request.starts_with("POST /items HTTP/1.1\r\n"));
+        assert!(request.contains("\"collector_id\":\"focl01\""));
+    }
+}
@@ -0,0 +1,166 @@
+//! `[archive.disk_guard]`: watch free space on the archive root and back off
+//! before a write fails with ENOSPC half way through a segment.
+
+use std::fs;
+use std::path::Path;
+
+use anyhow::Result;
+
+use crate::archive::object_lock::ensure_deletable;
+use crate::archive::peers::sidecar_path;
+use crate::archive::queue::ReplicationQueue;
+use crate::archive::reconcile::{local_segments, LocalSegment};
+use crate::config::{DiskGuardConfig, ObjectLockConfig, PrunePolicy};
+use crate::types::DiskLevel;
+
+impl DiskLevel {
+    pub fn for_free_mb(cfg: &DiskGuardConfig, free_mb: u64) -> Self {
+        let below = |threshold: u64| threshold > 0 && free_mb < threshold;
+        if below(cfg.pause_updates_free_mb) {
+            DiskLevel::PauseUpdates
+        } else if below(cfg.pause_ribs_free_mb) {
+            DiskLevel::PauseRibs
+        } else if below(cfg.warn_free_mb) {
+            DiskLevel::Warn
+        } else {
+            DiskLevel::Ok
+        }
+    }
+}
+
+/// Delete the oldest finalized segments the policy allows until `free_mb`
+/// reports at least `[archive.disk_guard].prune_free_mb`. `replicas` are the
+/// keys of the async replica destinations. Segments inside the retention
+/// window of `lock` are kept. Returns how many segments were removed.
+pub fn prune(
+    cfg: &DiskGuardConfig,
+    root: &Path,
+    queue: Option<&ReplicationQueue>,
+    replicas: &[String],
+    lock: Option<&ObjectLockConfig>,
+    free_mb: impl Fn() -> Result<u64>,
+) -> Result<usize> {
+    let mut candidates = local_segments(root)?;
+    candidates.sort_by_key(|segment| segment.manifest.end_ts);
+    let mut pruned = 0;
+    for segment in candidates {
+        if free_mb()? >= cfg.prune_free_mb {
+            break;
+        }
+        if !prunable(cfg.prune_policy, &segment, queue, replicas)?
+            || ensure_deletable(&segment.segment_path, lock).is_err()
+        {
+            continue;
+        }
+        for path in [
+            &segment.segment_path,
+            &segment.manifest_path,
+            &sidecar_path(&segment.segment_path),
+        ] {
+            match fs::remove_file(path) {
+                Ok(()) => {}
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        if let Some(queue) = queue {
+            queue.forget_segment(&segment.segment_path)?;
+        }
+        tracing::warn!(path=%segment.segment_path.display(), "pruned archive segment for disk space");
+        pruned += 1;
+    }
+    Ok(pruned)
+}
+
+fn prunable(
+    policy: PrunePolicy,
+    segment: &LocalSegment,
+    queue: Option<&ReplicationQueue>,
+    replicas: &[String],
+) -> Result<bool> {
+    Ok(match policy {
+        PrunePolicy::Oldest => true,
+        // Only once every replica holds a copy; never without replicas.
+        PrunePolicy::Replicated => match queue {
+            Some(queue) => queue.replicated_to_all(&segment.segment_path, replicas)?,
+            None => false,
+        },
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::queue::ReplicationPriority;
+
+    #[test]
+    fn escalates_and_prunes_only_replicated_segments() {
+        let cfg: DiskGuardConfig = toml::from_str(
+            "warn_free_mb = 1000\npause_ribs_free_mb = 500\npause_updates_free_mb = 100\nprune_free_mb = 300",
+        )
+        .unwrap();
+        cfg.validate().unwrap();
+        assert_eq!(DiskLevel::for_free_mb(&cfg, 2000), DiskLevel::Ok);
+        assert_eq!(DiskLevel::for_free_mb(&cfg, 900), DiskLevel::Warn);
+        assert_eq!(DiskLevel::for_free_mb(&cfg, 400), DiskLevel::PauseRibs);
+        assert_eq!(DiskLevel::for_free_mb(&cfg, 50), DiskLevel::PauseUpdates);
+
+        let root = tempfile::tempdir().unwrap();
+        let queue = ReplicationQueue::new(root.path()).unwrap();
+        let mut segments = Vec::new();
+        for (i, end_ts) in [(0, 300), (1, 100), (2, 200)] {
+            let segment = root.path().join(format!("updates.{i}.gz"));
+            fs::write(&segment, b"x").unwrap();
+            let manifest = serde_json::json!({
+                "collector_id": "focl01", "stream": "updates", "start_ts": 0,
+                "end_ts": end_ts, "record_count": 1, "bytes": 1, "sha256": "",
+                "compression": "gzip", "layout_profile": "routeviews",
+                "relative_path": format!("updates.{i}.gz"),
+            });
+            fs::write(format!("{}.json", segment.display()), manifest.to_string()).unwrap();
+            segments.push(segment);
+        }
+        // Each pruned segment frees 100 MiB.
+        let free_mb = || Ok(200 + 100 * segments.iter().filter(|s| !s.exists()).count() as u64);
+
+        // Without replica destinations nothing has a second copy.
+        assert_eq!(
+            prune(&cfg, root.path(), Some(&queue), &[], None, free_mb).unwrap(),
+            0
+        );
+        assert!(segments.iter().all(|s| s.exists()));
+
+        // updates.2 and updates.0 reached the replica; updates.1 (oldest)
+        // still waits for it.
+        let replicas = ["local:/mnt/replica".to_string()];
+        for segment in &segments {
+            queue
+                .enqueue(
+                    segment,
+                    Path::new("m"),
+                    &replicas[0],
+                    0,
+                    ReplicationPriority::Normal,
+                )
+                .unwrap();
+        }
+        for job in queue.claim_ready(10, 60).unwrap() {
+            if job.segment_path != segments[1] {
+                queue.mark_success(job.id).unwrap();
+            }
+        }
+        // Replicas that were never copied to keep every segment.
+        let two = [replicas[0].clone(), "local:/mnt/other".to_string()];
+        assert_eq!(
+            prune(&cfg, root.path(), Some(&queue), &two, None, free_mb).unwrap(),
+            0
+        );
+
+        let pruned = prune(&cfg, root.path(), Some(&queue), &replicas, None, free_mb).unwrap();
+        assert_eq!(pruned, 1);
+        assert!(segments[1].exists());
+        assert!(!segments[2].exists());
+        assert!(segments[0].exists());
+        assert!(!queue.replicated_to_all(&segments[2], &replicas).unwrap());
+    }
+}
@@ -0,0 +1,167 @@
+//! Reading a finalized segment back for `archive_fetch`, in chunks: as
+//! stored, decompressed to plain MRT, or parsed into JSONL BGP elems.
+
+use std::fs::File;
+use std::io::{ErrorKind, Read};
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use bgpkit_parser::BgpkitParser;
+use bzip2::read::MultiBzDecoder;
+use flate2::read::MultiGzDecoder;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FetchFormat {
+    /// The segment file as stored, still compressed.
+    #[default]
+    Raw,
+    /// Decompressed MRT records.
+    Mrt,
+    /// One JSON object per BGP elem, newline-delimited.
+    Elems,
+}
+
+/// Chunks of at most `chunk_size` bytes of `path` in `format`. Elems are never
+/// split across chunks, so an elem chunk may run past `chunk_size` by one line.
+pub fn read_chunks(
+    path: &Path,
+    format: FetchFormat,
+    chunk_size: usize,
+) -> Result<Box<dyn Iterator<Item = Result<Vec<u8>>> + Send>> {
+    let file = File::open(path).with_context(|| format!("failed opening {}", path.display()))?;
+    Ok(match format {
+        FetchFormat::Raw => Box::new(ReaderChunks::new(file, chunk_size)),
+        FetchFormat::Mrt => Box::new(ReaderChunks::new(decompressed(path, file)?, chunk_size)),
+        FetchFormat::Elems => {
+            let parser = BgpkitParser::from_reader(decompressed(path, file)?);
+            Box::new(ElemChunks {
+                elems: Box::new(parser.into_elem_iter().map(|elem| {
+                    let mut line = serde_json::to_vec(&elem)?;
+                    line.push(b'\n');
+                    Ok(line)
+                })),
+                chunk_size,
+            })
+        }
+    })
+}
+
+/// A decoder for `file` by the extension of `path`.
+fn decompressed(path: &Path, file: File) -> Result<Box<dyn Read + Send>> {
+    Ok(match path.extension().and_then(|ext| ext.to_str()) {
+        Some("gz") => Box::new(MultiGzDecoder::new(file)),
+        Some("bz2") => Box::new(MultiBzDecoder::new(file)),
+        Some("zst") => Box::new(zstd::Decoder::new(file)?),
+        _ => Box::new(file),
+    })
+}
+
+struct ReaderChunks<R> {
+    reader: R,
+    chunk_size: usize,
+    done: bool,
+}
+
+impl<R: Read> ReaderChunks<R> {
+    fn new(reader: R, chunk_size: usize) -> Self {
+        Self {
+            reader,
+            chunk_size,
+            done: false,
+        }
+    }
+
+    fn fill(&mut self) -> Result<Vec<u8>> {
+        let mut chunk = vec![0; self.chunk_size];
+        let mut filled = 0;
+        while filled < chunk.len() {
+            match self.reader.read(&mut chunk[filled..]) {
+                Ok(0) => {
+                    self.done = true;
+                    break;
+                }
+                Ok(n) => filled += n,
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => return Err(err.into()),
+            }
+        }
+        chunk.truncate(filled);
+        Ok(chunk)
+    }
+}
+
+impl<R: Read> Iterator for ReaderChunks<R> {
+    type Item = Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.done {
+            return None;
+        }
+        match self.fill() {
+            Ok(chunk) if chunk.is_empty() => None,
+            Ok(chunk) => Some(Ok(chunk)),
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            }
+        }
+    }
+}
+
+struct ElemChunks {
+    elems: Box<dyn Iterator<Item = Result<Vec<u8>>> + Send>,
+    chunk_size: usize,
+}
+
+impl Iterator for ElemChunks {
+    type Item = Result<Vec<u8>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut chunk = Vec::new();
+        while chunk.len() < self.chunk_size {
+            match self.elems.next() {
+                Some(Ok(line)) => chunk.extend_from_slice(&line),
+                Some(Err(err)) => return Some(Err(err)),
+                None => break,
+            }
+        }
+        (!chunk.is_empty()).then_some(Ok(chunk))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    #[test]
+    fn reads_segments_as_stored_or_decompressed() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("updates.20260221.1200.gz");
+        let body = (0..10_000u32)
+            .flat_map(u32::to_be_bytes)
+            .collect::<Vec<_>>();
+        let mut encoder = GzEncoder::new(File::create(&path).unwrap(), Compression::default());
+        encoder.write_all(&body).unwrap();
+        encoder.finish().unwrap();
+
+        let collect = |format| {
+            read_chunks(&path, format, 4096)
+                .unwrap()
+                .map(Result::unwrap)
+                .collect::<Vec<_>>()
+        };
+        let raw = collect(FetchFormat::Raw).concat();
+        assert_eq!(raw, std::fs::read(&path).unwrap());
+
+        let chunks = collect(FetchFormat::Mrt);
+        assert_eq!(chunks.len(), 10);
+        assert!(chunks[..9].iter().all(|chunk| chunk.len() == 4096));
+        assert_eq!(chunks.concat(), body);
+    }
+}
@@ -0,0 +1,171 @@
+//! RIB buckets the archive has no snapshot for. The last bucket the scheduler
+//! accounted for is kept under the archive root, so buckets that passed while
+//! focld was down are recorded in `rib-gaps.jsonl` on startup instead of
+//! being skipped silently.
+
+use std::fs::{self, OpenOptions};
+use std::io::{ErrorKind, Write};
+use std::path::PathBuf;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::config::ArchiveConfig;
+
+const GAPS_FILE: &str = "rib-gaps.jsonl";
+const LAST_BUCKET_FILE: &str = ".ribs-last-bucket";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RibGapReason {
+    /// focld was not running.
+    Downtime,
+    /// `[archive.disk_guard]` paused RIB snapshots.
+    DiskPressure,
+}
+
+impl RibGapReason {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            RibGapReason::Downtime => "downtime",
+            RibGapReason::DiskPressure => "disk_pressure",
+        }
+    }
+}
+
+/// A run of consecutive RIB buckets without a snapshot.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RibGap {
+    pub collector_id: String,
+    /// Start of the first and last missed bucket, inclusive.
+    pub first_bucket: i64,
+    pub last_bucket: i64,
+    pub buckets: u64,
+    pub reason: RibGapReason,
+    pub recorded_at: i64,
+}
+
+pub struct RibGapIndex {
+    collector_id: String,
+    interval_secs: u32,
+    state_path: PathBuf,
+    index_path: PathBuf,
+}
+
+impl RibGapIndex {
+    pub fn new(cfg: &ArchiveConfig) -> Self {
+        Self {
+            collector_id: cfg.collector_id.clone(),
+            interval_secs: cfg.ribs_interval_secs,
+            state_path: cfg.root.join(LAST_BUCKET_FILE),
+            index_path: cfg.root.join(GAPS_FILE),
+        }
+    }
+
+    /// The last bucket snapshotted or recorded as a gap, if any.
+    pub fn last_bucket(&self) -> Result<Option<i64>> {
+        match fs::read_to_string(&self.state_path) {
+            Ok(raw) => raw
+                .trim()
+                .parse()
+                .map(Some)
+                .with_context(|| format!("invalid RIB bucket in {}", self.state_path.display())),
+            Err(err) if err.kind() == ErrorKind::NotFound => Ok(None),
+            Err(err) => {
+                Err(err).with_context(|| format!("failed reading {}", self.state_path.display()))
+            }
+        }
+    }
+
+    pub fn set_last_bucket(&self, bucket: i64) -> Result<()> {
+        let tmp = self.state_path.with_extension("tmp");
+        fs::write(&tmp, format!("{bucket}\n"))
+            .with_context(|| format!("failed writing {}", tmp.display()))?;
+        fs::rename(&tmp, &self.state_path)
+            .with_context(|| format!("failed writing {}", self.state_path.display()))?;
+        Ok(())
+    }
+
+    /// The buckets strictly between `previous` and `current`, if any.
+    pub fn missed(&self, previous: i64, current: i64, reason: RibGapReason) -> Option<RibGap> {
+        let interval = i64::from(self.interval_secs);
+        let first_bucket = previous + interval;
+        let last_bucket = current - interval;
+        (first_bucket <= last_bucket).then(|| self.gap(first_bucket, last_bucket, reason))
+    }
+
+    /// `first_bucket..=last_bucket` as a gap recorded now.
+    pub fn gap(&self, first_bucket: i64, last_bucket: i64, reason: RibGapReason) -> RibGap {
+        RibGap {
+            collector_id: self.collector_id.clone(),
+            first_bucket,
+            last_bucket,
+            buckets: ((last_bucket - first_bucket) / i64::from(self.interval_secs) + 1) as u64,
+            reason,
+            recorded_at: chrono::Utc::now().timestamp(),
+        }
+    }
+
+    pub fn record(&self, gap: &RibGap) -> Result<()> {
+        let mut line = serde_json::to_vec(gap)?;
+        line.push(b'\n');
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.index_path)
+            .with_context(|| format!("failed opening {}", self.index_path.display()))?
+            .write_all(&line)?;
+        Ok(())
+    }
+
+    pub fn load(&self) -> Result<Vec<RibGap>> {
+        let raw = match fs::read_to_string(&self.index_path) {
+            Ok(raw) => raw,
+            Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(err) => return Err(err.into()),
+        };
+        raw.lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(|line| serde_json::from_str(line).map_err(Into::into))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn records_buckets_missed_between_runs() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cfg = ArchiveConfig {
+            root: tmp.path().to_path_buf(),
+            ribs_interval_secs: 3600,
+            ..ArchiveConfig::default()
+        };
+        let index = RibGapIndex::new(&cfg);
+
+        assert_eq!(index.last_bucket().unwrap(), None);
+        index.set_last_bucket(7200).unwrap();
+        assert_eq!(index.last_bucket().unwrap(), Some(7200));
+
+        // The next bucket is not a gap.
+        assert!(index.missed(7200, 10_800, RibGapReason::Downtime).is_none());
+
+        let gap = index.missed(7200, 21_600, RibGapReason::Downtime).unwrap();
+        assert_eq!(
+            (gap.first_bucket, gap.last_bucket, gap.buckets),
+            (10_800, 18_000, 3)
+        );
+        index.record(&gap).unwrap();
+        index
+            .record(&index.gap(25_200, 25_200, RibGapReason::DiskPressure))
+            .unwrap();
+
+        let loaded = index.load().unwrap();
+        assert_eq!(loaded.len(), 2);
+        assert_eq!(loaded[0], gap);
+        assert_eq!(loaded[1].reason, RibGapReason::DiskPressure);
+        assert_eq!(loaded[1].buckets, 1);
+    }
+}
@@ -3,7 +3,7 @@ use std::path::{Path, PathBuf};
 use anyhow::{bail, Result};
 use chrono::{Datelike, TimeZone, Timelike, Utc};
 
-use crate::archive::types::{ArchiveStream, SegmentPaths};
+use crate::archive::types::{AddressFamily, ArchiveStream, SegmentPaths};
 use crate::config::{ArchiveConfig, LayoutProfile};
 
 pub fn aligned_epoch(timestamp: i64, interval_secs: u32) -> i64 {
@@ -11,9 +11,28 @@ pub fn aligned_epoch(timestamp: i64, interval_secs: u32) -> i64 {
     timestamp - (timestamp.rem_euclid(interval))
 }
 
+/// Seconds past each RIB bucket boundary at which the periodic snapshot is
+/// taken: `rib_offset_secs` plus this collector's share of `rib_spread_secs`.
+/// Always less than `ribs_interval_secs`, so the snapshot stays in its bucket.
+pub fn rib_snapshot_delay(cfg: &ArchiveConfig) -> i64 {
+    let mut delay = i64::from(cfg.rib_offset_secs);
+    if cfg.rib_spread_secs > 0 {
+        // FNV-1a: stable across builds and restarts, unlike `DefaultHasher`.
+        let key = format!("{}\0{}", cfg.collector_id, cfg.rib_view_name);
+        let hash = key.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+            (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+        });
+        delay += (hash % u64::from(cfg.rib_spread_secs)) as i64;
+    }
+    delay
+}
+
+/// `family` is set when address families are archived as separate series;
+/// built-in profiles then name files `updates.v4.*`, `rib.v6.*`, and so on.
 pub fn segment_paths(
     cfg: &ArchiveConfig,
     stream: ArchiveStream,
+    family: Option<AddressFamily>,
     timestamp: i64,
 ) -> Result<SegmentPaths> {
     let aligned = match stream {
@@ -31,26 +50,29 @@ pub fn segment_paths(
     let hhmm = format!("{:02}{:02}", dt.hour(), dt.minute());
 
     let ext = cfg.compression.extension();
+    let family_token = family
+        .map(|f| format!("{}.", f.token()))
+        .unwrap_or_default();
 
     let relative_path = match cfg.layout_profile {
         LayoutProfile::RouteViews => match stream {
             ArchiveStream::Updates => PathBuf::from(format!(
-                "{}/{}/UPDATES/updates.{}.{}.{}",
-                cfg.collector_id, year_month, yyyymmdd, hhmm, ext
+                "{}/{}/UPDATES/updates.{}{}.{}.{}",
+                cfg.collector_id, year_month, family_token, yyyymmdd, hhmm, ext
             )),
             ArchiveStream::Ribs => PathBuf::from(format!(
-                "{}/{}/RIBS/rib.{}.{}.{}",
-                cfg.collector_id, year_month, yyyymmdd, hhmm, ext
+                "{}/{}/RIBS/rib.{}{}.{}.{}",
+                cfg.collector_id, year_month, family_token, yyyymmdd, hhmm, ext
             )),
         },
         LayoutProfile::Ris => match stream {
             ArchiveStream::Updates => PathBuf::from(format!(
-                "{}/{}/updates.{}.{}.{}",
-                cfg.collector_id, year_month, yyyymmdd, hhmm, ext
+                "{}/{}/updates.{}{}.{}.{}",
+                cfg.collector_id, year_month, family_token, yyyymmdd, hhmm, ext
             )),
             ArchiveStream::Ribs => PathBuf::from(format!(
-                "{}/{}/bview.{}.{}.{}",
-                cfg.collector_id, year_month, yyyymmdd, hhmm, ext
+                "{}/{}/bview.{}{}.{}.{}",
+                cfg.collector_id, year_month, family_token, yyyymmdd, hhmm, ext
             )),
         },
         LayoutProfile::Custom => {
@@ -64,6 +86,7 @@ pub fn segment_paths(
             build_custom_relative_path(
                 template,
                 &cfg.collector_id,
+                family,
                 dt.year(),
                 dt.month(),
                 dt.day(),
@@ -96,6 +119,7 @@ pub fn segment_paths(
         tmp_path,
         final_path,
         relative_path,
+        family,
     })
 }
 
@@ -103,6 +127,7 @@ pub fn segment_paths(
 fn build_custom_relative_path(
     template: &str,
     collector: &str,
+    family: Option<AddressFamily>,
     year: i32,
     month: u32,
     day: u32,
@@ -127,6 +152,10 @@ fn build_custom_relative_path(
         .replace("{dd}", &format!("{:02}", day))
         .replace("{yyyymmdd}", &yyyymmdd)
         .replace("{hhmm}", &hhmm)
+        .replace(
+            "{family}",
+            family.map(AddressFamily::token).unwrap_or_default(),
+        )
         .replace("{ext}", ext);
 
     let mut path = PathBuf::from(rendered);
@@ -147,9 +176,34 @@ fn build_custom_relative_path(
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeSet;
+
     use super::*;
     use crate::config::{ArchiveConfig, LayoutProfile};
 
+    #[test]
+    fn rib_snapshot_delay_is_offset_plus_a_stable_spread() {
+        let mut cfg = ArchiveConfig {
+            rib_offset_secs: 300,
+            ..ArchiveConfig::default()
+        };
+        assert_eq!(rib_snapshot_delay(&cfg), 300);
+
+        cfg.rib_spread_secs = 600;
+        let delays: Vec<i64> = ["rv01", "rv02", "rv03", "rv04"]
+            .iter()
+            .map(|id| {
+                cfg.collector_id = id.to_string();
+                rib_snapshot_delay(&cfg)
+            })
+            .collect();
+        assert!(delays.iter().all(|delay| (300..900).contains(delay)));
+        let distinct: BTreeSet<i64> = delays.iter().copied().collect();
+        assert!(distinct.len() > 1, "collectors were not spread: {delays:?}");
+        // The same collector always dumps at the same time.
+        assert_eq!(rib_snapshot_delay(&cfg), delays[3]);
+    }
+
     #[test]
     fn routeviews_layout_matches_expected_convention() {
         let cfg = ArchiveConfig {
@@ -161,7 +215,7 @@ mod tests {
             .with_ymd_and_hms(2026, 2, 21, 13, 43, 0)
             .unwrap()
             .timestamp();
-        let paths = segment_paths(&cfg, ArchiveStream::Updates, ts).unwrap();
+        let paths = segment_paths(&cfg, ArchiveStream::Updates, None, ts).unwrap();
         assert_eq!(
             paths.relative_path.to_string_lossy(),
             "focl01/2026.02/UPDATES/updates.20260221.1330.gz"
@@ -180,7 +234,7 @@ mod tests {
             .with_ymd_and_hms(2026, 2, 21, 13, 43, 0)
             .unwrap()
             .timestamp();
-        let paths = segment_paths(&cfg, ArchiveStream::Ribs, ts).unwrap();
+        let paths = segment_paths(&cfg, ArchiveStream::Ribs, None, ts).unwrap();
         assert_eq!(
             paths.relative_path.to_string_lossy(),
             "rrc00/2026.02/bview.20260221.1200.gz"
@@ -203,13 +257,43 @@ mod tests {
             .with_ymd_and_hms(2026, 2, 21, 13, 43, 0)
             .unwrap()
             .timestamp();
-        let paths = segment_paths(&cfg, ArchiveStream::Updates, ts).unwrap();
+        let paths = segment_paths(&cfg, ArchiveStream::Updates, None, ts).unwrap();
         assert_eq!(
             paths.relative_path.to_string_lossy(),
             "focl01/2026/02/updates.20260221.1330.gz"
         );
     }
 
+    #[test]
+    fn split_families_get_distinct_names() {
+        let mut cfg = ArchiveConfig {
+            enabled: true,
+            split_address_families: true,
+            ..ArchiveConfig::default()
+        };
+        let ts = Utc
+            .with_ymd_and_hms(2026, 2, 21, 13, 43, 0)
+            .unwrap()
+            .timestamp();
+        let v6 = segment_paths(&cfg, ArchiveStream::Ribs, Some(AddressFamily::Ipv6), ts).unwrap();
+        assert_eq!(
+            v6.relative_path.to_string_lossy(),
+            "focl01/2026.02/RIBS/rib.v6.20260221.1200.gz"
+        );
+
+        cfg.layout_profile = LayoutProfile::Custom;
+        cfg.custom_templates = Some(crate::config::CustomLayoutTemplates {
+            updates: "{collector}/{family}/updates.{yyyymmdd}.{hhmm}.{ext}".to_string(),
+            ribs: "{collector}/{family}/ribs.{yyyymmdd}.{hhmm}.{ext}".to_string(),
+        });
+        let v4 =
+            segment_paths(&cfg, ArchiveStream::Updates, Some(AddressFamily::Ipv4), ts).unwrap();
+        assert_eq!(
+            v4.relative_path.to_string_lossy(),
+            "focl01/v4/updates.20260221.1330.gz"
+        );
+    }
+
     #[test]
     fn aligns_epoch_boundaries() {
         assert_eq!(aligned_epoch(1_700_000_001, 900), 1_699_999_200);
@@ -0,0 +1,127 @@
+//! S3 lifecycle integration: with `[archive.destinations.lifecycle]`, every
+//! uploaded object is tagged with its kind and stream so bucket rules can
+//! tier old segments while their manifests stay hot, and
+//! `focl archive lifecycle` prints the rules matching that config.
+
+use serde_json::{json, Value};
+
+use crate::archive::replicator::object_key;
+use crate::config::{ArchiveDestinationConfig, S3LifecycleConfig};
+
+pub const KIND_TAG: &str = "focl-kind";
+pub const STREAM_TAG: &str = "focl-stream";
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ObjectKind {
+    Segment,
+    Manifest,
+    Peers,
+}
+
+impl ObjectKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            ObjectKind::Segment => "segment",
+            ObjectKind::Manifest => "manifest",
+            ObjectKind::Peers => "peers",
+        }
+    }
+}
+
+/// URL-encoded `x-amz-tagging` value for an object of `kind` in `stream`.
+pub fn object_tagging(lifecycle: &S3LifecycleConfig, kind: ObjectKind, stream: &str) -> String {
+    [(KIND_TAG, kind.as_str()), (STREAM_TAG, stream)]
+        .into_iter()
+        .chain(lifecycle.tags.iter().map(|(k, v)| (k.as_str(), v.as_str())))
+        .map(|(key, value)| format!("{}={}", encode(key), encode(value)))
+        .collect::<Vec<_>>()
+        .join("&")
+}
+
+/// Bucket lifecycle configuration, in the JSON accepted by
+/// `aws s3api put-bucket-lifecycle-configuration`, for an S3 destination.
+pub fn recommended_rules(destination: &ArchiveDestinationConfig) -> Option<Value> {
+    let lifecycle = destination.lifecycle.as_ref()?;
+    let prefix = object_key(destination.prefix.as_deref().unwrap_or_default(), "");
+    let mut rules = Vec::new();
+    for (stream, days) in [
+        ("updates", lifecycle.updates_transition_days),
+        ("ribs", lifecycle.ribs_transition_days),
+    ] {
+        let Some(days) = days else {
+            continue;
+        };
+        rules.push(json!({
+            "ID": format!("focl-{stream}-transition"),
+            "Status": "Enabled",
+            "Filter": {"And": {
+                "Prefix": prefix,
+                "Tags": [
+                    {"Key": KIND_TAG, "Value": ObjectKind::Segment.as_str()},
+                    {"Key": STREAM_TAG, "Value": stream},
+                ],
+            }},
+            "Transitions": [{"Days": days, "StorageClass": lifecycle.storage_class}],
+        }));
+    }
+    if let Some(days) = lifecycle.expire_days {
+        rules.push(json!({
+            "ID": "focl-expire",
+            "Status": "Enabled",
+            "Filter": {"Prefix": prefix},
+            "Expiration": {"Days": days},
+        }));
+    }
+    Some(json!({ "Rules": rules }))
+}
+
+fn encode(raw: &str) -> String {
+    let mut out = String::with_capacity(raw.len());
+    for byte in raw.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-_.~".contains(&byte) {
+            out.push(byte as char);
+        } else {
+            out.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tags_objects_and_recommends_segment_only_transitions() {
+        let destination: ArchiveDestinationConfig = toml::from_str(
+            r#"
+type = "s3"
+mode = "async_replica"
+endpoint = "https://s3.example.net"
+bucket = "mrt"
+prefix = "/focl01/"
+
+[lifecycle]
+tags = { team = "routing ops" }
+updates_transition_days = 30
+expire_days = 365
+"#,
+        )
+        .unwrap();
+        destination.validate().unwrap();
+        let lifecycle = destination.lifecycle.as_ref().unwrap();
+
+        assert_eq!(
+            object_tagging(lifecycle, ObjectKind::Manifest, "updates"),
+            "focl-kind=manifest&focl-stream=updates&team=routing%20ops"
+        );
+
+        let rules = recommended_rules(&destination).unwrap();
+        let rules = rules["Rules"].as_array().unwrap();
+        assert_eq!(rules.len(), 2);
+        assert_eq!(rules[0]["Filter"]["And"]["Prefix"], "focl01/");
+        assert_eq!(rules[0]["Filter"]["And"]["Tags"][0]["Value"], "segment");
+        assert_eq!(rules[0]["Transitions"][0]["StorageClass"], "GLACIER");
+        assert_eq!(rules[1]["Expiration"]["Days"], 365);
+    }
+}
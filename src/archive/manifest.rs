@@ -6,7 +6,7 @@ use anyhow::{Context, Result};
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
 
-use crate::archive::types::ArchiveStream;
+use crate::archive::types::{default_sample_rate, AddressFamily, ArchiveStream};
 use crate::config::CompressionKind;
 use crate::config::LayoutProfile;
 
@@ -22,6 +22,12 @@ pub struct SegmentManifest {
     pub compression: CompressionKind,
     pub layout_profile: LayoutProfile,
     pub relative_path: String,
+    /// 1-in-N UPDATE sampling applied to this segment; 1 means unsampled.
+    #[serde(default = "default_sample_rate")]
+    pub sample_rate: u32,
+    /// Set when address families are archived as separate series.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub family: Option<AddressFamily>,
 }
 
 impl SegmentManifest {
@@ -54,6 +60,8 @@ impl SegmentManifest {
             compression,
             layout_profile,
             relative_path: relative_path.to_string_lossy().to_string(),
+            sample_rate: default_sample_rate(),
+            family: None,
         })
     }
 
@@ -66,7 +74,7 @@ impl SegmentManifest {
     }
 }
 
-fn compute_sha256(path: &Path) -> Result<String> {
+pub(crate) fn compute_sha256(path: &Path) -> Result<String> {
     let mut file = fs::File::open(path)
         .with_context(|| format!("failed to open segment for hashing {}", path.display()))?;
     let mut hasher = Sha256::new();
@@ -1,46 +1,144 @@
+pub mod broker;
+pub mod disk_guard;
+pub mod fetch;
+pub mod gaps;
 pub mod layout;
+pub mod lifecycle;
 pub mod manifest;
+pub mod object_lock;
+pub mod peer_filter;
+pub mod peers;
+pub mod pool;
 pub mod queue;
+pub mod reconcile;
+pub mod recovery;
 pub mod replicator;
 pub mod snapshot;
+pub mod tap;
+pub mod throughput;
 pub mod types;
 pub mod writer;
 
-use std::net::Ipv4Addr;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
 use std::sync::Arc;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use chrono::Utc;
-use tokio::sync::{broadcast, Mutex};
+use tokio::net::UnixListener;
+use tokio::sync::{mpsc, Mutex, Notify};
 
-use crate::archive::layout::{aligned_epoch, segment_paths};
+use crate::archive::broker::BrokerIndex;
+use crate::archive::gaps::{RibGap, RibGapIndex, RibGapReason};
+use crate::archive::layout::{aligned_epoch, rib_snapshot_delay, segment_paths};
+use crate::archive::peer_filter::PeerFilter;
+use crate::archive::peers::{PeerDirectory, PeersSidecar, SegmentPeer};
+use crate::archive::pool::BufferPool;
+use crate::archive::reconcile::ReconcileReport;
+use crate::archive::recovery::{quarantine, WriterHealth};
 use crate::archive::replicator::Replicator;
 use crate::archive::snapshot::{
-    build_table_dump_v2, encode_bgp4mp_message_as4, encode_bgp4mp_state_change_as4,
+    build_table_dump_v2, write_bgp4mp_message_as4, write_bgp4mp_state_change_as4,
 };
+use crate::archive::tap::Tap;
+use crate::archive::throughput::ThroughputMeter;
 use crate::archive::types::{
-    ArchiveStatus, ArchiveStream, FinalizedSegment, PeerStateRecordInput, RibSnapshotInput,
-    UpdateRecordInput,
+    default_sample_rate, AddressFamily, ArchiveStatus, ArchiveStream, FinalizedSegment,
+    PeerStateRecordInput, RibSnapshotInput, SnapshotRoute, StreamStatus, UpdateRecordInput,
 };
 use crate::archive::writer::SegmentWriter;
-use crate::config::{ArchiveConfig, DestinationMode};
-use crate::types::{Event, EventEnvelope};
+use crate::config::{ArchiveConfig, DestinationMode, IngestOverflow, PeerConfig};
+use crate::preflight::free_space_mb;
+use crate::types::DiskLevel;
+use crate::types::{Event, EventBus};
+
+/// Idle record buffers kept for reuse; roughly the number of sessions
+/// encoding concurrently.
+const RECORD_BUFFER_POOL: usize = 64;
+
+/// A record handed to the archive writer task by [`ArchiveService::submit`].
+#[derive(Debug)]
+pub enum IngestRecord {
+    Update(UpdateRecordInput),
+    PeerState(PeerStateRecordInput),
+}
+
+/// An updates series: the peer stream's collector ID (`None` for
+/// `[archive].collector_id`) and the address family, `None` unless split.
+type SeriesKey = (Option<String>, Option<AddressFamily>);
+
+/// What `archive_status` reports about a stream beyond its open segment.
+#[derive(Debug, Default)]
+struct StreamHistory {
+    last_finalized: Option<FinalizedSegment>,
+    last_error: Option<(i64, String)>,
+    health: WriterHealth,
+}
+
+impl StreamHistory {
+    fn status(&self) -> StreamStatus {
+        let last = self.last_finalized.as_ref();
+        StreamStatus {
+            consecutive_write_failures: self.health.consecutive_failures(),
+            write_failures: self.health.failures(),
+            quarantined_segments: self.health.quarantined(),
+            records_dropped_backoff: self.health.dropped(),
+            retry_in_secs: self.health.retry_in_secs(Instant::now()),
+            last_finalized_path: last.map(|s| s.final_path.clone()),
+            last_finalized_ts: last.map(|s| s.end_ts),
+            last_finalized_record_count: last.map(|s| s.record_count).unwrap_or(0),
+            last_error: self.last_error.as_ref().map(|(_, err)| err.clone()),
+            last_error_ts: self.last_error.as_ref().map(|(ts, _)| *ts),
+            sample_rate: default_sample_rate(),
+            ..StreamStatus::default()
+        }
+    }
+}
 
 pub struct ArchiveService {
     cfg: ArchiveConfig,
     collector_bgp_id: Ipv4Addr,
-    updates_writer: Mutex<Option<SegmentWriter>>,
-    ribs_last: Mutex<Option<FinalizedSegment>>,
+    /// Open updates segment per series.
+    updates_writers: Mutex<BTreeMap<SeriesKey, SegmentWriter>>,
+    updates_history: std::sync::Mutex<StreamHistory>,
+    ribs_history: std::sync::Mutex<StreamHistory>,
     last_rib_bucket: Mutex<Option<i64>>,
+    rib_gaps: RibGapIndex,
     replicator: Option<Arc<Replicator>>,
-    event_tx: broadcast::Sender<EventEnvelope>,
+    broker: Option<BrokerIndex>,
+    tap: Option<Tap>,
+    record_buffers: BufferPool,
+    ingest_tx: mpsc::Sender<IngestRecord>,
+    ingest_rx: Mutex<mpsc::Receiver<IngestRecord>>,
+    /// Tells the ingest task to drain the queue and end.
+    ingest_stop: Notify,
+    ingest_task: std::sync::Mutex<Option<tokio::task::JoinHandle<()>>>,
+    records_shed: AtomicU64,
+    duplicates_suppressed: AtomicU64,
+    sample_seq: AtomicU64,
+    sampled_out: AtomicU64,
+    disk_level: std::sync::Mutex<DiskLevel>,
+    disk_free_mb: std::sync::Mutex<Option<u64>>,
+    disk_records_dropped: AtomicU64,
+    peers: PeerDirectory,
+    peer_filter: PeerFilter,
+    updates_throughput: std::sync::Mutex<ThroughputMeter>,
+    events: EventBus,
+    stopping: AtomicBool,
+    tasks: std::sync::Mutex<Vec<tokio::task::JoinHandle<()>>>,
 }
 
 impl ArchiveService {
-    pub async fn new(cfg: ArchiveConfig, collector_bgp_id: Ipv4Addr) -> Result<Arc<Self>> {
-        let (event_tx, _event_rx) = broadcast::channel(512);
-
+    /// Archive events are published on `events`.
+    pub async fn new(
+        cfg: ArchiveConfig,
+        collector_bgp_id: Ipv4Addr,
+        events: EventBus,
+    ) -> Result<Arc<Self>> {
         let replicator = if cfg.enabled {
             std::fs::create_dir_all(&cfg.root)
                 .with_context(|| format!("failed creating archive root {}", cfg.root.display()))?;
@@ -54,43 +152,63 @@ impl ArchiveService {
                 .with_context(|| format!("failed cleaning tmp root {}", cfg.tmp_root.display()))?;
 
             let queue = crate::archive::queue::ReplicationQueue::new(&cfg.root)?;
-            Some(Arc::new(Replicator::new(
-                &cfg,
-                queue,
-                Some(event_tx.clone()),
-            )))
+            Some(Arc::new(Replicator::new(&cfg, queue, Some(events.clone()))))
         } else {
             None
         };
 
+        let (tap, tap_listener) = match &cfg.tap.socket {
+            Some(path) if cfg.enabled => {
+                let (tap, listener) = Tap::bind(path, cfg.tap.buffer_records)?;
+                (Some(tap), Some(listener))
+            }
+            _ => (None, None),
+        };
+        let broker = BrokerIndex::new(&cfg)?;
+        let rib_gaps = RibGapIndex::new(&cfg);
+        let peer_filter = PeerFilter::new(&cfg.peer_filter)?;
+        let (ingest_tx, ingest_rx) = mpsc::channel(cfg.ingest_queue_len.max(1));
         let service = Arc::new(Self {
             cfg,
             collector_bgp_id,
-            updates_writer: Mutex::new(None),
-            ribs_last: Mutex::new(None),
+            updates_writers: Mutex::new(BTreeMap::new()),
+            updates_history: std::sync::Mutex::default(),
+            ribs_history: std::sync::Mutex::default(),
             last_rib_bucket: Mutex::new(None),
+            rib_gaps,
             replicator,
-            event_tx,
+            broker,
+            tap,
+            record_buffers: BufferPool::new(RECORD_BUFFER_POOL),
+            ingest_tx,
+            ingest_rx: Mutex::new(ingest_rx),
+            ingest_stop: Notify::new(),
+            ingest_task: std::sync::Mutex::new(None),
+            records_shed: AtomicU64::new(0),
+            duplicates_suppressed: AtomicU64::new(0),
+            sample_seq: AtomicU64::new(0),
+            sampled_out: AtomicU64::new(0),
+            disk_level: std::sync::Mutex::new(DiskLevel::Ok),
+            disk_free_mb: std::sync::Mutex::new(None),
+            disk_records_dropped: AtomicU64::new(0),
+            peers: PeerDirectory::default(),
+            peer_filter,
+            updates_throughput: std::sync::Mutex::new(ThroughputMeter::default()),
+            events,
+            stopping: AtomicBool::new(false),
+            tasks: std::sync::Mutex::new(Vec::new()),
         });
 
         if service.cfg.enabled {
             service
-                .ensure_updates_writer(Utc::now().timestamp())
+                .ensure_updates_writer(Utc::now().timestamp(), None)
                 .await?;
-            service.spawn_background_tasks();
+            service.spawn_background_tasks(tap_listener);
         }
 
         Ok(service)
     }
 
-    pub fn subscribe_events(&self) -> broadcast::Receiver<EventEnvelope> {
-        self.event_tx.subscribe()
-    }
-
-    pub fn event_sender(&self) -> broadcast::Sender<EventEnvelope> {
-        self.event_tx.clone()
-    }
-
     pub fn destinations(&self) -> Vec<(String, String, String)> {
         self.cfg
             .destinations
@@ -111,88 +229,403 @@ impl ArchiveService {
             .collect()
     }
 
+    /// Queue a record for the writer task so a slow disk never holds up the
+    /// caller. Returns false when the queue was full and the record was shed
+    /// under `ingest_overflow = "drop"`.
+    pub async fn submit(&self, record: IngestRecord) -> bool {
+        if !self.cfg.enabled || self.stopping.load(Ordering::Relaxed) {
+            return true;
+        }
+        if matches!(record, IngestRecord::PeerState(_)) && !self.cfg.include_peer_state_records {
+            return true;
+        }
+
+        let queued = match self.cfg.ingest_overflow {
+            IngestOverflow::Drop => !matches!(
+                self.ingest_tx.try_send(record),
+                Err(mpsc::error::TrySendError::Full(_))
+            ),
+            IngestOverflow::Block => {
+                let _ = self.ingest_tx.send(record).await;
+                true
+            }
+        };
+        if !queued {
+            self.records_shed.fetch_add(1, Ordering::Relaxed);
+        }
+        queued
+    }
+
+    /// `[archive].dedup_window_ms`; zero when duplicate suppression is off.
+    pub fn dedup_window(&self) -> Duration {
+        Duration::from_millis(self.cfg.dedup_window_ms)
+    }
+
+    /// Count an UPDATE the session skipped as a duplicate.
+    pub fn note_duplicate_suppressed(&self) {
+        self.duplicates_suppressed.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Whether the next announcement-only UPDATE falls on the 1-in-N
+    /// `[archive].sample_rate`; counts it as sampled out when not.
+    pub fn sample_update(&self) -> bool {
+        let rate = u64::from(self.cfg.sample_rate.max(1));
+        if self
+            .sample_seq
+            .fetch_add(1, Ordering::Relaxed)
+            .is_multiple_of(rate)
+        {
+            return true;
+        }
+        self.sampled_out.fetch_add(1, Ordering::Relaxed);
+        false
+    }
+
+    /// Whether `peer`'s UPDATEs belong in the updates stream: its own
+    /// `archive.updates` and `[archive.peer_filter]` must both allow it.
+    /// `peer_ip` is `peer.address`, already parsed.
+    pub fn archives_updates(&self, peer: &PeerConfig, peer_ip: IpAddr) -> bool {
+        peer.archive.updates && self.peer_filter.admits(peer, peer_ip)
+    }
+
+    /// Record a peer's current name and state for the peers sidecar.
+    /// `collector_id` is the peer's own stream, if it has one.
+    pub fn note_peer(&self, collector_id: Option<String>, peer: SegmentPeer) {
+        self.peers.note(collector_id, peer);
+    }
+
     pub async fn ingest_update(&self, update: UpdateRecordInput) -> Result<()> {
-        if !self.cfg.enabled {
+        if !self.cfg.enabled || self.stopping.load(Ordering::Relaxed) {
             return Ok(());
         }
+        self.write_update(update).await
+    }
 
-        self.ensure_updates_writer(update.timestamp).await?;
+    async fn write_update(&self, update: UpdateRecordInput) -> Result<()> {
+        if self.updates_paused() || self.updates_backing_off() {
+            return Ok(());
+        }
+        self.ensure_updates_writer(update.timestamp, update.collector_id.as_deref())
+            .await?;
 
-        let record = encode_bgp4mp_message_as4(&update)?;
-        let mut writer_guard = self.updates_writer.lock().await;
-        let writer = writer_guard
-            .as_mut()
+        let mut record = self.record_buffers.get();
+        write_bgp4mp_message_as4(&update, &mut record)?;
+        let series = (
+            update.collector_id,
+            self.cfg.split_address_families.then_some(update.family),
+        );
+        let mut writers = self.updates_writers.lock().await;
+        let writer = writers
+            .get_mut(&series)
             .context("updates writer not initialized")?;
-        writer.write_record(&record)?;
+        if let Err(err) = writer.write_record(&record) {
+            let broken = writers.remove(&series);
+            drop(writers);
+            return Err(self.updates_write_failed(broken, err));
+        }
+        drop(writers);
+        self.writer_succeeded(ArchiveStream::Updates);
+        self.note_throughput(record.len());
+        if let Some(tap) = &self.tap {
+            tap.send(&record);
+        }
 
         Ok(())
     }
 
     pub async fn ingest_peer_state(&self, state: PeerStateRecordInput) -> Result<()> {
-        if !self.cfg.enabled || !self.cfg.include_peer_state_records {
+        if !self.cfg.enabled
+            || !self.cfg.include_peer_state_records
+            || self.stopping.load(Ordering::Relaxed)
+        {
             return Ok(());
         }
+        self.write_peer_state(state).await
+    }
 
-        self.ensure_updates_writer(state.timestamp).await?;
+    async fn write_peer_state(&self, state: PeerStateRecordInput) -> Result<()> {
+        if self.updates_paused() || self.updates_backing_off() {
+            return Ok(());
+        }
+        self.ensure_updates_writer(state.timestamp, state.collector_id.as_deref())
+            .await?;
 
-        let record = encode_bgp4mp_state_change_as4(&state)?;
-        let mut writer_guard = self.updates_writer.lock().await;
-        let writer = writer_guard
-            .as_mut()
-            .context("updates writer not initialized")?;
-        writer.write_record(&record)?;
+        let mut record = self.record_buffers.get();
+        write_bgp4mp_state_change_as4(&state, &mut record)?;
+        // Sessions carry both families, so each series of the peer's stream
+        // gets every state change.
+        let mut writers = self.updates_writers.lock().await;
+        if !writers
+            .keys()
+            .any(|(collector, _)| *collector == state.collector_id)
+        {
+            bail!("updates writer not initialized");
+        }
+        let mut failed = None;
+        for (series, writer) in writers
+            .iter_mut()
+            .filter(|((collector, _), _)| *collector == state.collector_id)
+        {
+            if let Err(err) = writer.write_record(&record) {
+                failed = Some((series.clone(), err));
+                break;
+            }
+        }
+        if let Some((series, err)) = failed {
+            let broken = writers.remove(&series);
+            drop(writers);
+            return Err(self.updates_write_failed(broken, err));
+        }
+        drop(writers);
+        self.writer_succeeded(ArchiveStream::Updates);
+        self.note_throughput(record.len());
+        if let Some(tap) = &self.tap {
+            tap.send(&record);
+        }
 
         Ok(())
     }
 
-    pub async fn snapshot_now(&self, mut input: RibSnapshotInput) -> Result<FinalizedSegment> {
-        if !self.cfg.enabled {
-            anyhow::bail!("archive is disabled");
+    /// Whether `[archive.disk_guard]` has paused updates; counts the record
+    /// being dropped when so.
+    fn updates_paused(&self) -> bool {
+        if self.disk_level() < DiskLevel::PauseUpdates {
+            return false;
         }
+        self.disk_records_dropped.fetch_add(1, Ordering::Relaxed);
+        true
+    }
 
-        if input.collector_bgp_id == Ipv4Addr::UNSPECIFIED {
-            input.collector_bgp_id = self.collector_bgp_id;
+    /// Whether the updates writer is backing off after a failure; counts
+    /// the record being dropped when so.
+    fn updates_backing_off(&self) -> bool {
+        let mut history = self.history(ArchiveStream::Updates);
+        if history.health.ready(Instant::now()) {
+            return false;
         }
+        history.health.note_dropped();
+        true
+    }
 
-        let paths = segment_paths(&self.cfg, ArchiveStream::Ribs, input.timestamp)?;
-        self.emit(Event::ArchiveSegmentOpened {
-            stream: ArchiveStream::Ribs.as_str().to_string(),
-            path: paths.final_path.display().to_string(),
-            start_ts: aligned_epoch(input.timestamp, self.cfg.ribs_interval_secs),
+    /// Quarantine the updates segment `broken` whose write failed, if any,
+    /// and start backing off. Returns `err` for the caller to report.
+    fn updates_write_failed(
+        &self,
+        broken: Option<SegmentWriter>,
+        err: anyhow::Error,
+    ) -> anyhow::Error {
+        let tmp_path = broken.map(|writer| {
+            let tmp_path = writer.tmp_path().to_path_buf();
+            // Dropping the encoder tries to flush; that error is expected.
+            drop(writer);
+            tmp_path
         });
+        let quarantined =
+            tmp_path.and_then(|tmp_path| self.quarantine(ArchiveStream::Updates, &tmp_path));
+        self.writer_failed(ArchiveStream::Updates, quarantined, &err);
+        err
+    }
 
-        let mut writer = SegmentWriter::new(
-            &self.cfg,
-            ArchiveStream::Ribs,
-            aligned_epoch(input.timestamp, self.cfg.ribs_interval_secs),
-            paths,
-        )?;
+    /// Move a broken segment aside; `None` if that failed too.
+    fn quarantine(&self, stream: ArchiveStream, tmp_path: &Path) -> Option<PathBuf> {
+        match quarantine(&self.cfg.tmp_root, tmp_path) {
+            Ok(path) => {
+                self.history(stream).health.note_quarantined();
+                tracing::warn!(stream = stream.as_str(), path=%path.display(), "quarantined broken segment");
+                Some(path)
+            }
+            Err(err) => {
+                tracing::error!(stream = stream.as_str(), path=%tmp_path.display(), error=%format!("{err:#}"), "failed quarantining segment");
+                None
+            }
+        }
+    }
 
-        let records = build_table_dump_v2(&input)?;
-        for rec in records {
-            writer.write_record(&rec)?;
+    /// Start backing off `stream`'s writer. Only the updates stream emits
+    /// `archive_writer_failed`; RIBs already emit `archive_snapshot_failed`.
+    fn writer_failed(
+        &self,
+        stream: ArchiveStream,
+        quarantined: Option<PathBuf>,
+        err: &anyhow::Error,
+    ) {
+        let (consecutive_failures, backoff) = {
+            let mut history = self.history(stream);
+            let backoff = history.health.failed(Instant::now());
+            (history.health.consecutive_failures(), backoff)
+        };
+        tracing::error!(
+            stream = stream.as_str(),
+            consecutive_failures,
+            retry_in_secs = backoff.as_secs(),
+            error = %format!("{err:#}"),
+            "archive writer failed"
+        );
+        if stream == ArchiveStream::Updates {
+            self.emit(Event::ArchiveWriterFailed {
+                stream: stream.as_str().to_string(),
+                error: format!("{err:#}"),
+                quarantined: quarantined.map(|path| path.display().to_string()),
+                consecutive_failures,
+                retry_in_secs: backoff.as_secs(),
+            });
         }
+    }
 
-        let finalized = writer.finalize(input.timestamp)?;
-        self.emit(Event::ArchiveSegmentFinalized {
-            stream: ArchiveStream::Ribs.as_str().to_string(),
-            path: finalized.final_path.display().to_string(),
-            end_ts: finalized.end_ts,
-            records: finalized.record_count,
+    fn writer_succeeded(&self, stream: ArchiveStream) {
+        let Some(failures) = self.history(stream).health.succeeded() else {
+            return;
+        };
+        tracing::info!(
+            stream = stream.as_str(),
+            failures,
+            "archive writer recovered"
+        );
+        self.emit(Event::ArchiveWriterRecovered {
+            stream: stream.as_str().to_string(),
+            failures,
         });
+    }
 
-        if let Some(replicator) = &self.replicator {
-            replicator.enqueue_segment(&finalized)?;
+    fn history(&self, stream: ArchiveStream) -> std::sync::MutexGuard<'_, StreamHistory> {
+        match stream {
+            ArchiveStream::Updates => &self.updates_history,
+            ArchiveStream::Ribs => &self.ribs_history,
         }
+        .lock()
+        .expect("stream history lock poisoned")
+    }
 
-        {
-            let mut last = self.ribs_last.lock().await;
-            *last = Some(finalized.clone());
+    fn note_error(&self, stream: ArchiveStream, err: &anyhow::Error) {
+        self.history(stream).last_error = Some((Utc::now().timestamp(), format!("{err:#}")));
+    }
+
+    fn disk_level(&self) -> DiskLevel {
+        *self.disk_level.lock().expect("disk level lock poisoned")
+    }
+
+    fn note_throughput(&self, bytes: usize) {
+        self.updates_throughput
+            .lock()
+            .expect("throughput lock poisoned")
+            .record(bytes);
+    }
+
+    /// `[archive].rib_view_name`, used when a snapshot request names none.
+    pub fn rib_view_name(&self) -> &str {
+        &self.cfg.rib_view_name
+    }
+
+    /// BGP identifier written as the collector's in peer index tables.
+    pub fn collector_bgp_id(&self) -> Ipv4Addr {
+        self.collector_bgp_id
+    }
+
+    /// Whether a RIB segment already exists for `timestamp`'s bucket.
+    pub fn rib_segment_exists(&self, timestamp: i64) -> Result<bool> {
+        for &series in self.series() {
+            let paths = segment_paths(&self.cfg, ArchiveStream::Ribs, series, timestamp)?;
+            if paths.final_path.exists() {
+                return Ok(true);
+            }
         }
+        Ok(false)
+    }
 
+    pub async fn snapshot_now(&self, mut input: RibSnapshotInput) -> Result<FinalizedSegment> {
+        if !self.cfg.enabled {
+            anyhow::bail!("archive is disabled");
+        }
+
+        if self.disk_level() >= DiskLevel::PauseRibs {
+            bail!("RIB snapshots are paused: archive root is below [archive.disk_guard].pause_ribs_free_mb");
+        }
+
+        if input.collector_bgp_id == Ipv4Addr::UNSPECIFIED {
+            input.collector_bgp_id = self.collector_bgp_id;
+        }
+
+        let mut finalized = None;
+        for &series in self.series() {
+            match self.write_rib_segment(&input, series) {
+                Ok(segment) => {
+                    finalized.get_or_insert(segment);
+                }
+                Err(err) => {
+                    self.note_error(ArchiveStream::Ribs, &err);
+                    self.writer_failed(ArchiveStream::Ribs, None, &err);
+                    self.emit(Event::ArchiveSnapshotFailed {
+                        error: format!("{err:#}"),
+                    });
+                    return Err(err);
+                }
+            }
+        }
+        self.writer_succeeded(ArchiveStream::Ribs);
+        finalized.context("no RIB series configured")
+    }
+
+    fn write_rib_segment(
+        &self,
+        input: &RibSnapshotInput,
+        series: Option<AddressFamily>,
+    ) -> Result<FinalizedSegment> {
+        let start_ts = aligned_epoch(input.timestamp, self.cfg.ribs_interval_secs);
+        let paths = segment_paths(&self.cfg, ArchiveStream::Ribs, series, input.timestamp)?;
+
+        // Each family's RIB gets the full peer index table and the routes of
+        // its own prefixes, numbered from zero.
+        let records = match series {
+            Some(family) => build_table_dump_v2(&RibSnapshotInput {
+                timestamp: input.timestamp,
+                collector_bgp_id: input.collector_bgp_id,
+                view_name: input.view_name.clone(),
+                peers: input.peers.clone(),
+                routes: input
+                    .routes
+                    .iter()
+                    .filter(|route| AddressFamily::of(route.prefix) == family)
+                    .enumerate()
+                    .map(|(sequence, route)| SnapshotRoute {
+                        sequence: sequence as u32,
+                        ..route.clone()
+                    })
+                    .collect(),
+            })?,
+            None => build_table_dump_v2(input)?,
+        };
+
+        let mut writer = SegmentWriter::new(&self.cfg, ArchiveStream::Ribs, start_ts, paths)?;
+        self.emit(Event::ArchiveSegmentOpened {
+            stream: ArchiveStream::Ribs.as_str().to_string(),
+            path: writer.path().display().to_string(),
+            start_ts,
+        });
+        let tmp_path = writer.tmp_path().to_path_buf();
+        let written = records
+            .iter()
+            .try_for_each(|rec| writer.write_record(rec))
+            .and_then(|()| writer.finalize(input.timestamp));
+        let finalized = match written {
+            Ok(finalized) => finalized,
+            Err(err) => {
+                self.quarantine(ArchiveStream::Ribs, &tmp_path);
+                return Err(err);
+            }
+        };
+        self.segment_finalized(&finalized)?;
         Ok(finalized)
     }
 
+    /// Segment series written: both families when split, otherwise one.
+    fn series(&self) -> &'static [Option<AddressFamily>] {
+        if self.cfg.split_address_families {
+            &[Some(AddressFamily::Ipv4), Some(AddressFamily::Ipv6)]
+        } else {
+            &[None]
+        }
+    }
+
     pub async fn rollover(&self, stream: ArchiveStream) -> Result<()> {
         if !self.cfg.enabled {
             return Ok(());
@@ -207,7 +640,7 @@ impl ArchiveService {
                 let snapshot = RibSnapshotInput {
                     timestamp: now,
                     collector_bgp_id: self.collector_bgp_id,
-                    view_name: "main".to_string(),
+                    view_name: self.cfg.rib_view_name.clone(),
                     peers: vec![],
                     routes: vec![],
                 };
@@ -218,6 +651,63 @@ impl ArchiveService {
         Ok(())
     }
 
+    /// Compare finalized segments with what `destination_key` holds; with
+    /// `repair`, queue replication for missing or mismatched segments.
+    pub async fn reconcile(&self, destination_key: &str, repair: bool) -> Result<ReconcileReport> {
+        let replicator = self.replicator.as_ref().context("archive is disabled")?;
+        replicator
+            .reconcile(&self.cfg.root, destination_key, repair)
+            .await
+    }
+
+    /// Move `segment`'s waiting replication jobs ahead of everything else.
+    /// A relative path is taken from the archive root.
+    pub async fn prioritize(&self, segment: &Path) -> Result<usize> {
+        let replicator = self.replicator.as_ref().context("archive is disabled")?;
+        let segment = self.cfg.root.join(segment);
+        let raised = replicator.queue().prioritize(&segment)?;
+        if raised == 0 {
+            bail!("no waiting replication jobs for {}", segment.display());
+        }
+        Ok(raised)
+    }
+
+    /// The finalized segment at `segment`, absolute or relative to the
+    /// archive root. Paths resolving outside the root are refused.
+    pub fn fetch_path(&self, segment: &Path) -> Result<PathBuf> {
+        let root = self
+            .cfg
+            .root
+            .canonicalize()
+            .with_context(|| format!("failed resolving {}", self.cfg.root.display()))?;
+        let path = root
+            .join(segment)
+            .canonicalize()
+            .with_context(|| format!("no segment {}", segment.display()))?;
+        if !path.starts_with(&root) {
+            bail!("{} is outside the archive root", segment.display());
+        }
+        if !path.is_file() {
+            bail!("{} is not a file", segment.display());
+        }
+        Ok(path)
+    }
+
+    /// The finalized `stream` segment covering `timestamp`; the first series
+    /// that has one when address families are split.
+    pub fn segment_at(&self, stream: ArchiveStream, timestamp: i64) -> Result<PathBuf> {
+        for &series in self.series() {
+            let paths = segment_paths(&self.cfg, stream, series, timestamp)?;
+            if paths.final_path.is_file() {
+                return Ok(paths.final_path);
+            }
+        }
+        bail!(
+            "no finalized {} segment covers {timestamp}",
+            stream.as_str()
+        )
+    }
+
     pub async fn retry_failed_replications(&self) -> Result<usize> {
         match &self.replicator {
             Some(rep) => rep.retry_failed(),
@@ -226,44 +716,184 @@ impl ArchiveService {
     }
 
     pub async fn status(&self) -> Result<ArchiveStatus> {
-        let updates_guard = self.updates_writer.lock().await;
-        let ribs_guard = self.ribs_last.lock().await;
+        let now = Utc::now().timestamp();
+        let mut updates = self.history(ArchiveStream::Updates).status();
+        updates.interval_secs = self.cfg.updates_interval_secs;
+        updates.duplicates_suppressed = self.duplicates_suppressed.load(Ordering::Relaxed);
+        updates.sample_rate = self.cfg.sample_rate;
+        updates.sampled_out = self.sampled_out.load(Ordering::Relaxed);
+        (updates.records_per_sec, updates.bytes_per_sec) = self
+            .updates_throughput
+            .lock()
+            .expect("throughput lock poisoned")
+            .rates();
+        {
+            let writers = self.updates_writers.lock().await;
+            // The first series stands for the others in the path and age fields.
+            let first = writers.values().next();
+            let start = first.map(|w| w.start_ts());
+            updates.open_path = first.map(|w| w.path().to_path_buf());
+            updates.open_record_count = writers.values().map(|w| w.record_count()).sum();
+            updates.segment_age_secs = start.map(|start| (now - start).max(0));
+            updates.next_rotation_secs =
+                start.map(|start| (start + i64::from(updates.interval_secs) - now).max(0));
+        }
+        let mut ribs = self.history(ArchiveStream::Ribs).status();
+        ribs.interval_secs = self.cfg.ribs_interval_secs;
+        if self.cfg.enabled {
+            let interval = i64::from(ribs.interval_secs);
+            let delay = rib_snapshot_delay(&self.cfg);
+            ribs.next_rotation_secs =
+                Some(aligned_epoch(now - delay, ribs.interval_secs) + interval + delay - now);
+        }
 
         let queued = match &self.replicator {
             Some(rep) => rep.queue().pending_count()?,
             None => 0,
         };
 
-        let failures = match &self.replicator {
-            Some(rep) => rep.failures(),
-            None => 0,
+        let (failures, deduplicated) = match &self.replicator {
+            Some(rep) => (rep.failures(), rep.deduplicated()),
+            None => (0, 0),
+        };
+
+        let oldest_pending = match &self.replicator {
+            Some(rep) => rep.queue().oldest_pending_created_ts()?,
+            None => None,
         };
 
         Ok(ArchiveStatus {
             enabled: self.cfg.enabled,
             collector_id: self.cfg.collector_id.clone(),
-            updates_interval_secs: self.cfg.updates_interval_secs,
-            ribs_interval_secs: self.cfg.ribs_interval_secs,
-            updates_open_path: updates_guard.as_ref().map(|w| w.path().to_path_buf()),
-            updates_record_count: updates_guard
-                .as_ref()
-                .map(|w| w.record_count())
-                .unwrap_or(0),
-            ribs_last_path: ribs_guard.as_ref().map(|r| r.final_path.clone()),
-            ribs_last_record_count: ribs_guard.as_ref().map(|r| r.record_count).unwrap_or(0),
+            updates,
+            ribs,
             queued_replication_jobs: queued,
             replication_failures: failures,
+            replication_deduplicated: deduplicated,
+            oldest_pending_replication_age_secs: oldest_pending.map(|ts| (now - ts).max(0)),
+            ingest_queue_depth: self.ingest_tx.max_capacity() - self.ingest_tx.capacity(),
+            ingest_records_shed: self.records_shed.load(Ordering::Relaxed),
+            disk_free_mb: *self.disk_free_mb.lock().expect("disk free lock poisoned"),
+            disk_level: self.disk_level(),
+            disk_records_dropped: self.disk_records_dropped.load(Ordering::Relaxed),
+            tap_clients: self.tap.as_ref().map(Tap::clients).unwrap_or(0),
         })
     }
 
-    fn spawn_background_tasks(self: &Arc<Self>) {
+    /// Stop ingest and background tasks, finalize the open updates segment, and
+    /// return in-flight replication jobs to the queue so they resume on restart.
+    pub async fn shutdown(&self) -> Result<()> {
+        self.stopping.store(true, Ordering::Relaxed);
+
+        let tasks = std::mem::take(&mut *self.tasks.lock().expect("archive tasks lock poisoned"));
+        for task in tasks {
+            task.abort();
+        }
+
+        if !self.cfg.enabled {
+            return Ok(());
+        }
+        if let Some(tap) = &self.tap {
+            tap.remove_socket();
+        }
+
+        // Let the ingest task finish its current record and drain the queue.
+        let ingest = self
+            .ingest_task
+            .lock()
+            .expect("archive ingest task lock poisoned")
+            .take();
+        if let Some(task) = ingest {
+            self.ingest_stop.notify_one();
+            if let Err(err) = task.await {
+                tracing::error!(error=%err, "archive ingest task failed");
+            }
+        }
+
+        // A failed series is quarantined; the others are still finalized and
+        // the replication queue is still requeued before reporting it.
+        let now = Utc::now().timestamp();
+        let mut errors = Vec::new();
+        let open_writers = std::mem::take(&mut *self.updates_writers.lock().await);
+        for writer in open_writers.into_values() {
+            if let Err(err) = self.finalize_updates_writer(writer, now) {
+                errors.push(format!("{err:#}"));
+            }
+        }
+
+        if let Some(rep) = &self.replicator {
+            match rep.queue().requeue_in_progress() {
+                Ok(0) => {}
+                Ok(requeued) => {
+                    tracing::info!(requeued, "returned in-progress replication jobs to queue")
+                }
+                Err(err) => errors.push(format!("{err:#}")),
+            }
+        }
+
+        if !errors.is_empty() {
+            bail!("archive shutdown failed: {}", errors.join("; "));
+        }
+        Ok(())
+    }
+
+    fn spawn_background_tasks(self: &Arc<Self>, tap_listener: Option<UnixListener>) {
+        let mut tasks = self.tasks.lock().expect("archive tasks lock poisoned");
+
+        if let (Some(tap), Some(listener)) = (&self.tap, tap_listener) {
+            tasks.push(tap.spawn(listener));
+        }
+
         if let Some(replicator) = &self.replicator {
             let rep = Arc::clone(replicator);
-            rep.spawn();
+            tasks.push(rep.spawn());
+        }
+
+        let service = Arc::clone(self);
+        *self
+            .ingest_task
+            .lock()
+            .expect("archive ingest task lock poisoned") = Some(tokio::spawn(async move {
+            let mut queue = service.ingest_rx.lock().await;
+            loop {
+                tokio::select! {
+                    record = queue.recv() => match record {
+                        Some(record) => service.write(record).await,
+                        None => return,
+                    },
+                    _ = service.ingest_stop.notified() => break,
+                }
+            }
+            // Records accepted before the stop still make it into the final segment.
+            while let Ok(record) = queue.try_recv() {
+                service.write(record).await;
+            }
+        }));
+
+        if self.cfg.audit_interval_secs > 0 {
+            let service = Arc::clone(self);
+            tasks.push(tokio::spawn(async move {
+                let interval = Duration::from_secs(service.cfg.audit_interval_secs);
+                loop {
+                    tokio::time::sleep(interval).await;
+                    service.audit_replicas().await;
+                }
+            }));
+        }
+
+        if self.cfg.disk_guard.enabled() {
+            let service = Arc::clone(self);
+            tasks.push(tokio::spawn(async move {
+                let interval = Duration::from_secs(service.cfg.disk_guard.check_interval_secs);
+                loop {
+                    service.check_disk();
+                    tokio::time::sleep(interval).await;
+                }
+            }));
         }
 
         let service = Arc::clone(self);
-        tokio::spawn(async move {
+        tasks.push(tokio::spawn(async move {
             let mut ticker = tokio::time::interval(Duration::from_secs(5));
             loop {
                 ticker.tick().await;
@@ -271,93 +901,341 @@ impl ArchiveService {
                     tracing::error!(error=%err, "archive scheduler tick failed");
                 }
             }
+        }));
+    }
+
+    /// Reconcile every async replica and queue its gaps.
+    async fn audit_replicas(&self) {
+        let Some(replicator) = &self.replicator else {
+            return;
+        };
+        let replicas = self
+            .cfg
+            .destinations
+            .iter()
+            .filter(|d| d.mode == DestinationMode::AsyncReplica);
+        for destination in replicas {
+            let key = destination.destination_key();
+            match replicator.reconcile(&self.cfg.root, &key, true).await {
+                Ok(report) => {
+                    if report.repair_jobs_queued > 0 {
+                        tracing::warn!(destination=%key, healed = report.repair_jobs_queued, "replica audit queued missing segments");
+                    }
+                    self.emit(Event::ArchiveAuditCompleted {
+                        destination: key,
+                        missing: report.missing.len(),
+                        mismatched: report.mismatched.len(),
+                        healed: report.repair_jobs_queued,
+                    });
+                }
+                Err(err) => {
+                    tracing::error!(destination=%key, error=%err, "replica audit failed")
+                }
+            }
+        }
+    }
+
+    /// Measure free space on the archive root, prune if below
+    /// `prune_free_mb`, and move to the matching [`DiskLevel`].
+    fn check_disk(&self) {
+        let guard = &self.cfg.disk_guard;
+        let root = &self.cfg.root;
+        let mut pruned = 0;
+        let free_mb = match free_space_mb(root) {
+            Ok(free_mb) if guard.prune_free_mb > 0 && free_mb < guard.prune_free_mb => {
+                let queue = self.replicator.as_ref().map(|rep| rep.queue());
+                let lock = self
+                    .cfg
+                    .destinations
+                    .iter()
+                    .find(|d| d.mode == DestinationMode::Primary)
+                    .and_then(|d| d.object_lock.as_ref());
+                let replicas: Vec<String> = self
+                    .cfg
+                    .destinations
+                    .iter()
+                    .filter(|d| d.mode == DestinationMode::AsyncReplica)
+                    .map(|d| d.destination_key())
+                    .collect();
+                match disk_guard::prune(guard, root, queue, &replicas, lock, || free_space_mb(root))
+                {
+                    Ok(count) => pruned = count,
+                    Err(err) => tracing::error!(error=%err, "archive disk pruning failed"),
+                }
+                free_space_mb(root)
+            }
+            other => other,
+        };
+        let free_mb = match free_mb {
+            Ok(free_mb) => free_mb,
+            Err(err) => {
+                tracing::error!(error=%err, "archive disk space check failed");
+                return;
+            }
+        };
+        *self.disk_free_mb.lock().expect("disk free lock poisoned") = Some(free_mb);
+
+        let level = DiskLevel::for_free_mb(guard, free_mb);
+        let previous = std::mem::replace(
+            &mut *self.disk_level.lock().expect("disk level lock poisoned"),
+            level,
+        );
+        if level == previous && pruned == 0 {
+            return;
+        }
+        if level == DiskLevel::Ok {
+            tracing::info!(free_mb, pruned, "archive disk space recovered");
+        } else {
+            tracing::warn!(
+                free_mb,
+                level = level.as_str(),
+                pruned,
+                "archive disk space low"
+            );
+        }
+        self.emit(Event::ArchiveDiskPressure {
+            free_mb,
+            level,
+            pruned,
         });
     }
 
+    async fn write(&self, record: IngestRecord) {
+        let (kind, written) = match record {
+            IngestRecord::Update(update) => ("update", self.write_update(update).await),
+            IngestRecord::PeerState(state) => ("peer state", self.write_peer_state(state).await),
+        };
+        if let Err(err) = written {
+            tracing::error!(record = kind, error=%err, "failed archiving record");
+            self.note_error(ArchiveStream::Updates, &err);
+        }
+    }
+
     async fn tick(&self) -> Result<()> {
         if !self.cfg.enabled {
             return Ok(());
         }
 
         let now = Utc::now().timestamp();
-        self.ensure_updates_writer(now).await?;
+        if self
+            .history(ArchiveStream::Updates)
+            .health
+            .ready(Instant::now())
+        {
+            self.ensure_updates_writer(now, None)
+                .await
+                .inspect_err(|err| self.note_error(ArchiveStream::Updates, err))?;
+        }
 
-        let rib_bucket = aligned_epoch(now, self.cfg.ribs_interval_secs);
+        // A bucket's snapshot is due `rib_snapshot_delay` past its start.
+        let rib_bucket = aligned_epoch(
+            now - rib_snapshot_delay(&self.cfg),
+            self.cfg.ribs_interval_secs,
+        );
         let mut last_rib = self.last_rib_bucket.lock().await;
+        if last_rib.is_none() {
+            // First tick since startup: account for the buckets that passed
+            // while we were down, and skip the current one if it was already
+            // written before the restart.
+            match self.rib_gaps.last_bucket() {
+                Ok(Some(previous)) => {
+                    if let Some(gap) =
+                        self.rib_gaps
+                            .missed(previous, rib_bucket, RibGapReason::Downtime)
+                    {
+                        self.record_rib_gap(gap);
+                    }
+                    *last_rib = Some(previous);
+                }
+                Ok(None) => {}
+                Err(err) => tracing::warn!(error=%err, "failed reading last RIB bucket"),
+            }
+        }
         if last_rib.map(|v| v != rib_bucket).unwrap_or(true) {
+            if !self
+                .history(ArchiveStream::Ribs)
+                .health
+                .ready(Instant::now())
+            {
+                // Retried on a later tick once the backoff has passed.
+                return Ok(());
+            }
+            if self.disk_level() >= DiskLevel::PauseRibs {
+                tracing::warn!(
+                    bucket = rib_bucket,
+                    "skipping RIB snapshot, archive disk space low"
+                );
+                self.record_rib_gap(self.rib_gaps.gap(
+                    rib_bucket,
+                    rib_bucket,
+                    RibGapReason::DiskPressure,
+                ));
+                self.note_rib_bucket(rib_bucket);
+                *last_rib = Some(rib_bucket);
+                return Ok(());
+            }
             let snapshot = RibSnapshotInput {
                 timestamp: now,
                 collector_bgp_id: self.collector_bgp_id,
-                view_name: "main".to_string(),
+                view_name: self.cfg.rib_view_name.clone(),
                 peers: vec![],
                 routes: vec![],
             };
             self.snapshot_now(snapshot).await?;
+            self.note_rib_bucket(rib_bucket);
             *last_rib = Some(rib_bucket);
         }
 
         Ok(())
     }
 
-    async fn ensure_updates_writer(&self, now_ts: i64) -> Result<()> {
+    fn record_rib_gap(&self, gap: RibGap) {
+        tracing::warn!(
+            first_bucket = gap.first_bucket,
+            last_bucket = gap.last_bucket,
+            buckets = gap.buckets,
+            reason = gap.reason.as_str(),
+            "RIB snapshots missed"
+        );
+        if let Err(err) = self.rib_gaps.record(&gap) {
+            tracing::error!(error=%err, "failed recording RIB gap");
+        }
+        self.emit(Event::ArchiveRibGap {
+            first_bucket: gap.first_bucket,
+            last_bucket: gap.last_bucket,
+            buckets: gap.buckets,
+            reason: gap.reason.as_str().to_string(),
+        });
+    }
+
+    fn note_rib_bucket(&self, bucket: i64) {
+        if let Err(err) = self.rib_gaps.set_last_bucket(bucket) {
+            tracing::error!(error=%err, "failed saving last RIB bucket");
+        }
+    }
+
+    /// Open or rotate the collector's series and those of `collector_id`'s
+    /// stream. A peer stream, once opened, rotates with the collector's.
+    async fn ensure_updates_writer(&self, now_ts: i64, collector_id: Option<&str>) -> Result<()> {
         let update_bucket = aligned_epoch(now_ts, self.cfg.updates_interval_secs);
 
-        let mut writer_guard = self.updates_writer.lock().await;
-        let needs_rotate = writer_guard
-            .as_ref()
-            .map(|w| w.start_ts() != update_bucket)
-            .unwrap_or(true);
+        let mut writers = self.updates_writers.lock().await;
+        let mut collectors = writers
+            .keys()
+            .map(|(collector, _)| collector.clone())
+            .collect::<BTreeSet<_>>();
+        collectors.insert(None);
+        collectors.insert(collector_id.map(str::to_string));
+        for collector in collectors {
+            let cfg = self.collector_cfg(collector.as_deref());
+            for &family in self.series() {
+                let series = (collector.clone(), family);
+                let needs_rotate = writers
+                    .get(&series)
+                    .map(|w| w.start_ts() != update_bucket)
+                    .unwrap_or(true);
+                if !needs_rotate {
+                    continue;
+                }
 
-        if needs_rotate {
-            if let Some(old_writer) = writer_guard.take() {
-                let finalized = old_writer.finalize(now_ts)?;
-                self.emit(Event::ArchiveSegmentFinalized {
+                if let Some(old_writer) = writers.remove(&series) {
+                    self.finalize_updates_writer(old_writer, now_ts)?;
+                }
+
+                let paths = segment_paths(&cfg, ArchiveStream::Updates, family, now_ts)?;
+                let writer = SegmentWriter::new(&cfg, ArchiveStream::Updates, update_bucket, paths)
+                    .inspect_err(|err| {
+                        self.writer_failed(ArchiveStream::Updates, None, err);
+                    })?;
+                self.emit(Event::ArchiveSegmentOpened {
                     stream: ArchiveStream::Updates.as_str().to_string(),
-                    path: finalized.final_path.display().to_string(),
-                    end_ts: finalized.end_ts,
-                    records: finalized.record_count,
+                    path: writer.path().display().to_string(),
+                    start_ts: update_bucket,
                 });
-                if let Some(rep) = &self.replicator {
-                    rep.enqueue_segment(&finalized)?;
-                }
+                writers.insert(series, writer);
             }
-
-            let paths = segment_paths(&self.cfg, ArchiveStream::Updates, now_ts)?;
-            self.emit(Event::ArchiveSegmentOpened {
-                stream: ArchiveStream::Updates.as_str().to_string(),
-                path: paths.final_path.display().to_string(),
-                start_ts: update_bucket,
-            });
-            let writer =
-                SegmentWriter::new(&self.cfg, ArchiveStream::Updates, update_bucket, paths)?;
-            *writer_guard = Some(writer);
         }
 
         Ok(())
     }
 
     async fn rotate_updates(&self, now_ts: i64) -> Result<()> {
-        {
-            let mut writer_guard = self.updates_writer.lock().await;
-            if let Some(old_writer) = writer_guard.take() {
-                let finalized = old_writer.finalize(now_ts)?;
-                self.emit(Event::ArchiveSegmentFinalized {
-                    stream: ArchiveStream::Updates.as_str().to_string(),
-                    path: finalized.final_path.display().to_string(),
-                    end_ts: finalized.end_ts,
-                    records: finalized.record_count,
-                });
-                if let Some(rep) = &self.replicator {
-                    rep.enqueue_segment(&finalized)?;
-                }
-            }
+        let old_writers = std::mem::take(&mut *self.updates_writers.lock().await);
+        let collectors = old_writers
+            .keys()
+            .filter_map(|(collector, _)| collector.clone())
+            .collect::<BTreeSet<_>>();
+        for old_writer in old_writers.into_values() {
+            self.finalize_updates_writer(old_writer, now_ts)?;
+        }
+
+        self.ensure_updates_writer(now_ts, None).await?;
+        for collector in collectors {
+            self.ensure_updates_writer(now_ts, Some(&collector)).await?;
+        }
+        Ok(())
+    }
+
+    /// `[archive]` as a peer stream's writers see it: the same layout under
+    /// the stream's collector ID.
+    fn collector_cfg(&self, collector_id: Option<&str>) -> Cow<'_, ArchiveConfig> {
+        match collector_id {
+            Some(collector_id) => Cow::Owned(ArchiveConfig {
+                collector_id: collector_id.to_string(),
+                ..self.cfg.clone()
+            }),
+            None => Cow::Borrowed(&self.cfg),
         }
+    }
 
-        self.ensure_updates_writer(now_ts).await
+    /// Finalize an updates segment, quarantining it if that fails.
+    fn finalize_updates_writer(&self, writer: SegmentWriter, end_ts: i64) -> Result<()> {
+        let tmp_path = writer.tmp_path().to_path_buf();
+        let finalized = match writer.finalize(end_ts) {
+            Ok(finalized) => finalized,
+            Err(err) => {
+                let quarantined = self.quarantine(ArchiveStream::Updates, &tmp_path);
+                self.writer_failed(ArchiveStream::Updates, quarantined, &err);
+                return Err(err);
+            }
+        };
+        self.segment_finalized(&finalized)
+    }
+
+    /// Announce a finalized segment, queue its replication, and index it.
+    fn segment_finalized(&self, finalized: &FinalizedSegment) -> Result<()> {
+        let peer_stream =
+            Some(finalized.collector_id.as_str()).filter(|id| *id != self.cfg.collector_id);
+        if finalized.family != Some(AddressFamily::Ipv6) && peer_stream.is_none() {
+            self.history(finalized.stream).last_finalized = Some(finalized.clone());
+        }
+        if self.cfg.peers_sidecar {
+            PeersSidecar {
+                collector_id: finalized.collector_id.clone(),
+                stream: finalized.stream.as_str().to_string(),
+                start_ts: finalized.start_ts,
+                end_ts: finalized.end_ts,
+                peers: self.peers.snapshot(peer_stream),
+            }
+            .write(&finalized.final_path)?;
+        }
+        self.emit(Event::ArchiveSegmentFinalized {
+            stream: finalized.stream.as_str().to_string(),
+            path: finalized.final_path.display().to_string(),
+            end_ts: finalized.end_ts,
+            records: finalized.record_count,
+        });
+        if let Some(rep) = &self.replicator {
+            rep.enqueue_segment(finalized)?;
+        }
+        if let Some(broker) = &self.broker {
+            broker.publish(finalized);
+        }
+        Ok(())
     }
 
     fn emit(&self, event: Event) {
-        let _ = self.event_tx.send(EventEnvelope::new(event));
+        self.events.publish(event);
     }
 }
 
@@ -0,0 +1,137 @@
+//! Write-once retention for `[archive.destinations.object_lock]`: S3 Object
+//! Lock parameters for uploads, and the local equivalent of refusing to
+//! replace a segment copy until its window has passed.
+
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{bail, Context, Result};
+use aws_sdk_s3::primitives::DateTime;
+use aws_sdk_s3::types::ObjectLockMode as S3ObjectLockMode;
+
+use crate::config::{ObjectLockConfig, ObjectLockMode};
+
+/// Retention end for a segment that closed at `end_ts`.
+pub fn retain_until(lock: &ObjectLockConfig, end_ts: i64) -> DateTime {
+    DateTime::from_secs(end_ts + i64::from(lock.retain_days) * 86_400)
+}
+
+pub fn s3_mode(lock: &ObjectLockConfig) -> S3ObjectLockMode {
+    match lock.mode {
+        ObjectLockMode::Compliance => S3ObjectLockMode::Compliance,
+        ObjectLockMode::Governance => S3ObjectLockMode::Governance,
+    }
+}
+
+fn window(lock: &ObjectLockConfig) -> Duration {
+    Duration::from_secs(u64::from(lock.retain_days) * 86_400)
+}
+
+/// Fail while the local copy at `path` is younger than the retention window
+/// of `lock`. Disk guard pruning, primary finalization, and local replica
+/// copies check this before deleting or replacing a segment.
+pub fn ensure_deletable(path: &Path, lock: Option<&ObjectLockConfig>) -> Result<()> {
+    let Some(lock) = lock else {
+        return Ok(());
+    };
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err).with_context(|| format!("failed to stat {}", path.display())),
+    };
+    let age = SystemTime::now()
+        .duration_since(metadata.modified()?)
+        .unwrap_or_default();
+    if age < window(lock) {
+        bail!(
+            "refusing to delete {} inside its {}-day retention window",
+            path.display(),
+            lock.retain_days
+        );
+    }
+    Ok(())
+}
+
+/// Make an existing local copy writable for replacement, refusing while it
+/// is younger than the retention window.
+pub fn release_for_replace(path: &Path, lock: &ObjectLockConfig) -> Result<()> {
+    ensure_deletable(path, Some(lock))?;
+    let metadata = match fs::metadata(path) {
+        Ok(metadata) => metadata,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+        Err(err) => return Err(err).with_context(|| format!("failed to stat {}", path.display())),
+    };
+    let mut permissions = metadata.permissions();
+    #[allow(clippy::permissions_set_readonly_false)]
+    permissions.set_readonly(false);
+    fs::set_permissions(path, permissions)
+        .with_context(|| format!("failed to unlock {}", path.display()))
+}
+
+/// Whether `to` already holds exactly `from`, as after a retried job whose
+/// earlier attempt copied this file before failing.
+pub fn already_copied(from: &Path, to: &Path) -> Result<bool> {
+    let (Ok(a), Ok(b)) = (fs::metadata(from), fs::metadata(to)) else {
+        return Ok(false);
+    };
+    if a.len() != b.len() {
+        return Ok(false);
+    }
+    let (mut a, mut b) = (fs::File::open(from)?, fs::File::open(to)?);
+    let (mut buf_a, mut buf_b) = (vec![0; 64 * 1024], vec![0; 64 * 1024]);
+    loop {
+        let n = a.read(&mut buf_a)?;
+        if n == 0 {
+            return Ok(true);
+        }
+        b.read_exact(&mut buf_b[..n])?;
+        if buf_a[..n] != buf_b[..n] {
+            return Ok(false);
+        }
+    }
+}
+
+pub fn make_read_only(path: &Path) -> Result<()> {
+    let mut permissions = fs::metadata(path)?.permissions();
+    permissions.set_readonly(true);
+    fs::set_permissions(path, permissions)
+        .with_context(|| format!("failed to lock {}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn refuses_to_replace_copies_inside_the_window() {
+        let lock = ObjectLockConfig {
+            mode: ObjectLockMode::Compliance,
+            retain_days: 7,
+        };
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("updates.20260221.1200.gz");
+        assert!(release_for_replace(&path, &lock).is_ok());
+
+        fs::write(&path, b"segment").unwrap();
+        make_read_only(&path).unwrap();
+        let same = dir.path().join("same");
+        fs::write(&same, b"segment").unwrap();
+        assert!(already_copied(&same, &path).unwrap());
+        assert!(fs::metadata(&path).unwrap().permissions().readonly());
+        assert!(release_for_replace(&path, &lock).is_err());
+        assert!(ensure_deletable(&path, Some(&lock)).is_err());
+        assert!(ensure_deletable(&path, None).is_ok());
+
+        let eight_days_ago = SystemTime::now() - Duration::from_secs(8 * 86_400);
+        fs::File::open(&path)
+            .unwrap()
+            .set_modified(eight_days_ago)
+            .unwrap();
+        release_for_replace(&path, &lock).unwrap();
+        assert!(!fs::metadata(&path).unwrap().permissions().readonly());
+
+        assert_eq!(retain_until(&lock, 0), DateTime::from_secs(7 * 86_400));
+    }
+}
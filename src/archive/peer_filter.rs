@@ -0,0 +1,95 @@
+//! `[archive.peer_filter]`, compiled once so the per-UPDATE check is a few
+//! comparisons.
+
+use std::net::IpAddr;
+
+use anyhow::Result;
+use glob::Pattern;
+
+use crate::config::{ArchivePeerFilterConfig, PeerConfig, PeerSelector};
+
+#[derive(Debug)]
+struct Selector {
+    address: Option<IpAddr>,
+    asn: Option<u32>,
+    name: Option<Pattern>,
+}
+
+#[derive(Debug, Default)]
+pub struct PeerFilter {
+    include: Vec<Selector>,
+    exclude: Vec<Selector>,
+}
+
+impl Selector {
+    fn compile(selector: &PeerSelector) -> Result<Self> {
+        Ok(Self {
+            address: selector.address,
+            asn: selector.asn,
+            name: selector.name.as_deref().map(Pattern::new).transpose()?,
+        })
+    }
+
+    fn matches(&self, peer: &PeerConfig, peer_ip: IpAddr) -> bool {
+        self.address.is_none_or(|address| peer_ip == address)
+            && self.asn.is_none_or(|asn| peer.remote_as == asn)
+            && self.name.as_ref().is_none_or(|pattern| {
+                peer.name
+                    .as_deref()
+                    .is_some_and(|name| pattern.matches(name))
+            })
+    }
+}
+
+impl PeerFilter {
+    pub fn new(cfg: &ArchivePeerFilterConfig) -> Result<Self> {
+        let compile = |selectors: &[PeerSelector]| {
+            selectors
+                .iter()
+                .map(Selector::compile)
+                .collect::<Result<Vec<_>>>()
+        };
+        Ok(Self {
+            include: compile(&cfg.include)?,
+            exclude: compile(&cfg.exclude)?,
+        })
+    }
+
+    /// `peer_ip` is `peer.address`, parsed once by the caller.
+    pub fn admits(&self, peer: &PeerConfig, peer_ip: IpAddr) -> bool {
+        (self.include.is_empty() || self.include.iter().any(|s| s.matches(peer, peer_ip)))
+            && !self.exclude.iter().any(|s| s.matches(peer, peer_ip))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn include_then_exclude_by_address_asn_and_name() {
+        let cfg: ArchivePeerFilterConfig = toml::from_str(
+            r#"
+include = [{ asn = 65001 }, { name = "rs-*" }]
+exclude = [{ address = "192.0.2.3" }, { asn = 65001, name = "*-announce" }]
+"#,
+        )
+        .unwrap();
+        cfg.validate().unwrap();
+        let filter = PeerFilter::new(&cfg).unwrap();
+
+        let admits = |filter: &PeerFilter, address: &str, remote_as, name: Option<&str>| {
+            let mut peer: PeerConfig =
+                toml::from_str(&format!("address = \"{address}\"\nremote_as = {remote_as}"))
+                    .unwrap();
+            peer.name = name.map(str::to_string);
+            filter.admits(&peer, address.parse().unwrap())
+        };
+        assert!(admits(&filter, "192.0.2.1", 65001, None));
+        assert!(admits(&filter, "192.0.2.2", 65009, Some("rs-fra")));
+        assert!(!admits(&filter, "192.0.2.3", 65001, None));
+        assert!(!admits(&filter, "192.0.2.4", 65001, Some("edge-announce")));
+        assert!(!admits(&filter, "192.0.2.5", 65009, Some("transit-a")));
+        assert!(admits(&PeerFilter::default(), "192.0.2.5", 65009, None));
+    }
+}
@@ -0,0 +1,120 @@
+//! Per-segment peers sidecar (`<segment>.peers.json`): the configured name,
+//! ASN, and session state of every archived peer when the segment closed,
+//! so archive consumers can label feeds without the collector config.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::types::PeerState;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SegmentPeer {
+    pub address: String,
+    pub asn: u32,
+    pub name: Option<String>,
+    pub state: PeerState,
+    pub established_at: Option<i64>,
+    /// Set while the peer is in maintenance (`focl peer maintenance`).
+    #[serde(default)]
+    pub maintenance: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeersSidecar {
+    pub collector_id: String,
+    pub stream: String,
+    pub start_ts: i64,
+    pub end_ts: i64,
+    pub peers: Vec<SegmentPeer>,
+}
+
+/// Latest known state of each archived peer, keyed by address, with the
+/// collector stream (`None` for the collector's own) it is archived in.
+#[derive(Debug, Default)]
+pub struct PeerDirectory {
+    peers: Mutex<BTreeMap<String, (Option<String>, SegmentPeer)>>,
+}
+
+impl PeerDirectory {
+    pub fn note(&self, collector_id: Option<String>, peer: SegmentPeer) {
+        self.lock()
+            .insert(peer.address.clone(), (collector_id, peer));
+    }
+
+    /// Peers archived in `collector_id`'s stream.
+    pub fn snapshot(&self, collector_id: Option<&str>) -> Vec<SegmentPeer> {
+        self.lock()
+            .values()
+            .filter(|(collector, _)| collector.as_deref() == collector_id)
+            .map(|(_, peer)| peer.clone())
+            .collect()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, BTreeMap<String, (Option<String>, SegmentPeer)>> {
+        self.peers.lock().expect("peer directory lock poisoned")
+    }
+}
+
+impl PeersSidecar {
+    pub fn write(&self, segment_path: &Path) -> Result<PathBuf> {
+        let path = sidecar_path(segment_path);
+        let json = serde_json::to_vec_pretty(self)?;
+        fs::write(&path, json)
+            .with_context(|| format!("failed to write peers sidecar {}", path.display()))?;
+        Ok(path)
+    }
+}
+
+pub fn sidecar_path(segment_path: &Path) -> PathBuf {
+    PathBuf::from(format!("{}.peers.json", segment_path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_latest_state_per_peer() {
+        let directory = PeerDirectory::default();
+        let peer = |state| SegmentPeer {
+            address: "192.0.2.2".to_string(),
+            asn: 65002,
+            name: Some("transit-a".to_string()),
+            state,
+            established_at: None,
+            maintenance: false,
+        };
+        directory.note(None, peer(PeerState::Active));
+        directory.note(None, peer(PeerState::Established));
+        directory.note(
+            Some("focl01-private".to_string()),
+            SegmentPeer {
+                address: "192.0.2.9".to_string(),
+                ..peer(PeerState::Established)
+            },
+        );
+        assert_eq!(directory.snapshot(Some("focl01-private")).len(), 1);
+
+        let dir = tempfile::tempdir().unwrap();
+        let segment = dir.path().join("updates.20260221.1200.gz");
+        let sidecar = PeersSidecar {
+            collector_id: "focl01".to_string(),
+            stream: "updates".to_string(),
+            start_ts: 100,
+            end_ts: 1000,
+            peers: directory.snapshot(None),
+        };
+        let path = sidecar.write(&segment).unwrap();
+        assert_eq!(path, dir.path().join("updates.20260221.1200.gz.peers.json"));
+
+        let written: PeersSidecar = serde_json::from_slice(&fs::read(&path).unwrap()).unwrap();
+        assert_eq!(written.peers.len(), 1);
+        assert_eq!(written.peers[0].name.as_deref(), Some("transit-a"));
+        assert!(matches!(written.peers[0].state, PeerState::Established));
+    }
+}
@@ -0,0 +1,98 @@
+//! Recycled record buffers, so sustained update ingest encodes MRT records
+//! into warm allocations instead of a fresh `Vec` per record.
+
+use std::ops::{Deref, DerefMut};
+use std::sync::Mutex;
+
+/// Buffers that grew past this (large RIB records) are freed rather than
+/// pinned in the pool.
+const MAX_RETAINED_CAPACITY: usize = 64 * 1024;
+
+pub struct BufferPool {
+    free: Mutex<Vec<Vec<u8>>>,
+    max_pooled: usize,
+}
+
+impl BufferPool {
+    /// Keep at most `max_pooled` idle buffers around.
+    pub fn new(max_pooled: usize) -> Self {
+        Self {
+            free: Mutex::new(Vec::with_capacity(max_pooled)),
+            max_pooled,
+        }
+    }
+
+    /// An empty buffer, returned to the pool when dropped.
+    pub fn get(&self) -> PooledBuffer<'_> {
+        let buf = self
+            .free
+            .lock()
+            .expect("buffer pool lock poisoned")
+            .pop()
+            .unwrap_or_default();
+        PooledBuffer { pool: self, buf }
+    }
+
+    fn put(&self, mut buf: Vec<u8>) {
+        if buf.capacity() == 0 || buf.capacity() > MAX_RETAINED_CAPACITY {
+            return;
+        }
+        buf.clear();
+        let mut free = self.free.lock().expect("buffer pool lock poisoned");
+        if free.len() < self.max_pooled {
+            free.push(buf);
+        }
+    }
+}
+
+pub struct PooledBuffer<'a> {
+    pool: &'a BufferPool,
+    buf: Vec<u8>,
+}
+
+impl Deref for PooledBuffer<'_> {
+    type Target = Vec<u8>;
+
+    fn deref(&self) -> &Vec<u8> {
+        &self.buf
+    }
+}
+
+impl DerefMut for PooledBuffer<'_> {
+    fn deref_mut(&mut self) -> &mut Vec<u8> {
+        &mut self.buf
+    }
+}
+
+impl Drop for PooledBuffer<'_> {
+    fn drop(&mut self) {
+        self.pool.put(std::mem::take(&mut self.buf));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reuses_and_bounds_buffers() {
+        let pool = BufferPool::new(1);
+        let ptr = {
+            let mut buf = pool.get();
+            buf.extend_from_slice(b"record");
+            buf.as_ptr()
+        };
+        let buf = pool.get();
+        assert!(buf.is_empty());
+        assert_eq!(buf.as_ptr(), ptr);
+
+        let mut other = pool.get();
+        other.push(1);
+        drop(buf);
+        drop(other);
+        assert_eq!(pool.free.lock().unwrap().len(), 1);
+
+        pool.get().reserve(MAX_RETAINED_CAPACITY + 1);
+        assert_eq!(pool.free.lock().unwrap().len(), 0);
+    }
+}
@@ -1,13 +1,52 @@
 use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
 use chrono::Utc;
-use rusqlite::{params, Connection};
+use rusqlite::{params, Connection, OptionalExtension};
 
+use crate::archive::types::ArchiveStream;
+
+/// Schema migrations of the queue database, applied in order. Migration `i`
+/// brings the schema to version `i + 1`, recorded in `schema_version`. Append
+/// new migrations; never edit one that has shipped.
+const MIGRATIONS: &[&str] = &[
+    "
+    CREATE TABLE replication_queue (
+        id INTEGER PRIMARY KEY AUTOINCREMENT,
+        segment_path TEXT NOT NULL,
+        manifest_path TEXT NOT NULL,
+        destination_key TEXT NOT NULL,
+        attempts INTEGER NOT NULL DEFAULT 0,
+        max_retries INTEGER NOT NULL DEFAULT 0,
+        next_retry_ts INTEGER NOT NULL,
+        status TEXT NOT NULL,
+        last_error TEXT,
+        created_ts INTEGER NOT NULL,
+        updated_ts INTEGER NOT NULL
+    );
+    CREATE INDEX idx_replication_queue_ready
+    ON replication_queue(status, next_retry_ts);
+    ",
+    "ALTER TABLE replication_queue ADD COLUMN priority INTEGER NOT NULL DEFAULT 0",
+    "ALTER TABLE replication_queue ADD COLUMN lease_expires_ts INTEGER",
+    "
+    CREATE TABLE replicated_segments (
+        segment_path TEXT NOT NULL,
+        destination_key TEXT NOT NULL,
+        replicated_ts INTEGER NOT NULL,
+        PRIMARY KEY (segment_path, destination_key)
+    );
+    ",
+];
+
+/// Persistent replication job queue. One connection is kept open for the
+/// queue's lifetime rather than reopened per operation.
 #[derive(Debug, Clone)]
 pub struct ReplicationQueue {
     db_path: PathBuf,
+    conn: Arc<Mutex<Connection>>,
 }
 
 #[derive(Debug, Clone)]
@@ -18,6 +57,45 @@ pub struct ReplicationJob {
     pub destination_key: String,
     pub attempts: u32,
     pub max_retries: u32,
+    pub priority: ReplicationPriority,
+}
+
+/// Claim order of queued jobs: higher classes go first, oldest first within
+/// a class. RIBs outrank updates because consumers usually need the bview
+/// before the updates that follow it.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ReplicationPriority {
+    #[default]
+    Normal,
+    Ribs,
+    /// Flagged by an operator with `focl archive prioritize`.
+    Urgent,
+}
+
+impl ReplicationPriority {
+    pub fn for_stream(stream: &str) -> Self {
+        if stream == ArchiveStream::Ribs.as_str() {
+            ReplicationPriority::Ribs
+        } else {
+            ReplicationPriority::Normal
+        }
+    }
+
+    fn as_column(self) -> i64 {
+        match self {
+            ReplicationPriority::Normal => 0,
+            ReplicationPriority::Ribs => 10,
+            ReplicationPriority::Urgent => 20,
+        }
+    }
+
+    fn from_column(value: i64) -> Self {
+        match value {
+            v if v >= 20 => ReplicationPriority::Urgent,
+            v if v >= 10 => ReplicationPriority::Ribs,
+            _ => ReplicationPriority::Normal,
+        }
+    }
 }
 
 impl ReplicationQueue {
@@ -28,7 +106,12 @@ impl ReplicationQueue {
                 .with_context(|| format!("failed creating replication dir {}", parent.display()))?;
         }
 
-        let queue = Self { db_path };
+        let conn = Connection::open(&db_path)
+            .with_context(|| format!("failed opening queue db {}", db_path.display()))?;
+        let queue = Self {
+            db_path,
+            conn: Arc::new(Mutex::new(conn)),
+        };
         queue.init()?;
         Ok(queue)
     }
@@ -37,34 +120,19 @@ impl ReplicationQueue {
         &self.db_path
     }
 
-    fn open(&self) -> Result<Connection> {
-        let conn = Connection::open(&self.db_path)
-            .with_context(|| format!("failed opening queue db {}", self.db_path.display()))?;
-        Ok(conn)
+    fn conn(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().expect("replication queue lock poisoned")
     }
 
     fn init(&self) -> Result<()> {
-        let conn = self.open()?;
-        conn.execute_batch(
-            "
-            CREATE TABLE IF NOT EXISTS replication_queue (
-                id INTEGER PRIMARY KEY AUTOINCREMENT,
-                segment_path TEXT NOT NULL,
-                manifest_path TEXT NOT NULL,
-                destination_key TEXT NOT NULL,
-                attempts INTEGER NOT NULL DEFAULT 0,
-                max_retries INTEGER NOT NULL DEFAULT 0,
-                next_retry_ts INTEGER NOT NULL,
-                status TEXT NOT NULL,
-                last_error TEXT,
-                created_ts INTEGER NOT NULL,
-                updated_ts INTEGER NOT NULL
-            );
-            CREATE INDEX IF NOT EXISTS idx_replication_queue_ready
-            ON replication_queue(status, next_retry_ts);
-            ",
-        )?;
-        Ok(())
+        let mut conn = self.conn();
+        migrate(&mut conn)
+            .with_context(|| format!("failed migrating queue db {}", self.db_path.display()))
+    }
+
+    /// The schema version the queue database is at.
+    pub fn schema_version(&self) -> Result<u32> {
+        Ok(current_version(&self.conn())?.unwrap_or(0))
     }
 
     pub fn enqueue(
@@ -73,41 +141,73 @@ impl ReplicationQueue {
         manifest_path: &Path,
         destination_key: &str,
         max_retries: u32,
+        priority: ReplicationPriority,
     ) -> Result<()> {
-        let now = Utc::now().timestamp();
-        let conn = self.open()?;
-        conn.execute(
-            "
-            INSERT INTO replication_queue (
-                segment_path, manifest_path, destination_key, attempts, max_retries,
-                next_retry_ts, status, created_ts, updated_ts
-            ) VALUES (?, ?, ?, 0, ?, ?, 'pending', ?, ?)
-            ",
-            params![
-                segment_path.display().to_string(),
-                manifest_path.display().to_string(),
-                destination_key,
-                max_retries,
-                now,
-                now,
-                now
-            ],
+        self.enqueue_many(
+            segment_path,
+            manifest_path,
+            [(destination_key, max_retries)],
+            priority,
         )?;
         Ok(())
     }
 
-    pub fn claim_ready(&self, limit: usize) -> Result<Vec<ReplicationJob>> {
+    /// Queue one segment for several destinations, given as
+    /// `(destination_key, max_retries)`, in a single transaction.
+    pub fn enqueue_many<'a>(
+        &self,
+        segment_path: &Path,
+        manifest_path: &Path,
+        destinations: impl IntoIterator<Item = (&'a str, u32)>,
+        priority: ReplicationPriority,
+    ) -> Result<usize> {
+        let now = Utc::now().timestamp();
+        let segment_path = segment_path.display().to_string();
+        let manifest_path = manifest_path.display().to_string();
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        let mut queued = 0;
+        {
+            let mut stmt = tx.prepare_cached(
+                "
+                INSERT INTO replication_queue (
+                    segment_path, manifest_path, destination_key, attempts, max_retries,
+                    next_retry_ts, status, created_ts, updated_ts, priority
+                ) VALUES (?, ?, ?, 0, ?, ?, 'pending', ?, ?, ?)
+                ",
+            )?;
+            for (destination_key, max_retries) in destinations {
+                stmt.execute(params![
+                    segment_path,
+                    manifest_path,
+                    destination_key,
+                    max_retries,
+                    now,
+                    now,
+                    now,
+                    priority.as_column()
+                ])?;
+                queued += 1;
+            }
+        }
+        tx.commit()?;
+        Ok(queued)
+    }
+
+    /// Claim up to `limit` ready jobs, each leased for `lease_secs`.
+    pub fn claim_ready(&self, limit: usize, lease_secs: u64) -> Result<Vec<ReplicationJob>> {
         let now = Utc::now().timestamp();
-        let conn = self.open()?;
-        let tx = conn.unchecked_transaction()?;
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
 
         let jobs: Vec<ReplicationJob> = {
             let mut stmt = tx.prepare(
                 "
-                SELECT id, segment_path, manifest_path, destination_key, attempts, max_retries
+                SELECT id, segment_path, manifest_path, destination_key, attempts, max_retries,
+                       priority
                 FROM replication_queue
                 WHERE status = 'pending' AND next_retry_ts <= ?
-                ORDER BY id ASC
+                ORDER BY priority DESC, id ASC
                 LIMIT ?
                 ",
             )?;
@@ -120,6 +220,7 @@ impl ReplicationQueue {
                     destination_key: row.get(3)?,
                     attempts: row.get::<_, u32>(4)?,
                     max_retries: row.get::<_, u32>(5)?,
+                    priority: ReplicationPriority::from_column(row.get(6)?),
                 })
             })?;
 
@@ -128,8 +229,12 @@ impl ReplicationQueue {
 
         for job in &jobs {
             tx.execute(
-                "UPDATE replication_queue SET status = 'in_progress', updated_ts = ? WHERE id = ?",
-                params![now, job.id],
+                "
+                UPDATE replication_queue
+                SET status = 'in_progress', lease_expires_ts = ?, updated_ts = ?
+                WHERE id = ?
+                ",
+                params![now.saturating_add(lease_secs as i64), now, job.id],
             )?;
         }
 
@@ -137,12 +242,23 @@ impl ReplicationQueue {
         Ok(jobs)
     }
 
+    /// Remove a finished job, remembering that its destination holds the
+    /// segment.
     pub fn mark_success(&self, job_id: i64) -> Result<()> {
-        let conn = self.open()?;
-        conn.execute(
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "
+            INSERT OR REPLACE INTO replicated_segments (segment_path, destination_key, replicated_ts)
+            SELECT segment_path, destination_key, ? FROM replication_queue WHERE id = ?
+            ",
+            params![Utc::now().timestamp(), job_id],
+        )?;
+        tx.execute(
             "DELETE FROM replication_queue WHERE id = ?",
             params![job_id],
         )?;
+        tx.commit()?;
         Ok(())
     }
 
@@ -153,7 +269,7 @@ impl ReplicationQueue {
         retry_backoff_secs: u64,
     ) -> Result<()> {
         let now = Utc::now().timestamp();
-        let conn = self.open()?;
+        let conn = self.conn();
         let next_attempt = job.attempts.saturating_add(1);
 
         let exhausted = job.max_retries > 0 && next_attempt >= job.max_retries;
@@ -161,7 +277,8 @@ impl ReplicationQueue {
             conn.execute(
                 "
                 UPDATE replication_queue
-                SET attempts = ?, status = 'failed', last_error = ?, updated_ts = ?
+                SET attempts = ?, status = 'failed', last_error = ?, lease_expires_ts = NULL,
+                    updated_ts = ?
                 WHERE id = ?
                 ",
                 params![next_attempt, error, now, job.id],
@@ -171,7 +288,8 @@ impl ReplicationQueue {
             conn.execute(
                 "
                 UPDATE replication_queue
-                SET attempts = ?, status = 'pending', next_retry_ts = ?, last_error = ?, updated_ts = ?
+                SET attempts = ?, status = 'pending', next_retry_ts = ?, last_error = ?,
+                    lease_expires_ts = NULL, updated_ts = ?
                 WHERE id = ?
                 ",
                 params![next_attempt, next_retry, error, now, job.id],
@@ -182,7 +300,7 @@ impl ReplicationQueue {
     }
 
     pub fn pending_count(&self) -> Result<usize> {
-        let conn = self.open()?;
+        let conn = self.conn();
         let count: i64 = conn.query_row(
             "SELECT COUNT(*) FROM replication_queue WHERE status IN ('pending', 'in_progress')",
             [],
@@ -191,8 +309,107 @@ impl ReplicationQueue {
         Ok(count as usize)
     }
 
+    /// Whether `segment_path` already waits to be replicated to `destination_key`.
+    pub fn has_open_job(&self, segment_path: &Path, destination_key: &str) -> Result<bool> {
+        let conn = self.conn();
+        let open: bool = conn.query_row(
+            "
+            SELECT EXISTS(
+                SELECT 1 FROM replication_queue
+                WHERE segment_path = ? AND destination_key = ?
+                  AND status IN ('pending', 'in_progress')
+            )
+            ",
+            params![segment_path.display().to_string(), destination_key],
+            |row| row.get(0),
+        )?;
+        Ok(open)
+    }
+
+    /// Whether any destination still lacks a copy of `segment_path`.
+    pub fn has_unfinished_jobs(&self, segment_path: &Path) -> Result<bool> {
+        let conn = self.conn();
+        let unfinished: bool = conn.query_row(
+            "
+            SELECT EXISTS(
+                SELECT 1 FROM replication_queue
+                WHERE segment_path = ? AND status IN ('pending', 'in_progress', 'failed')
+            )
+            ",
+            params![segment_path.display().to_string()],
+            |row| row.get(0),
+        )?;
+        Ok(unfinished)
+    }
+
+    /// Whether every one of `destination_keys` has a copy of `segment_path`
+    /// and no job for it is waiting again. False without destinations.
+    pub fn replicated_to_all(
+        &self,
+        segment_path: &Path,
+        destination_keys: &[String],
+    ) -> Result<bool> {
+        if destination_keys.is_empty() || self.has_unfinished_jobs(segment_path)? {
+            return Ok(false);
+        }
+        let conn = self.conn();
+        let mut stmt = conn.prepare_cached(
+            "
+            SELECT EXISTS(
+                SELECT 1 FROM replicated_segments WHERE segment_path = ? AND destination_key = ?
+            )
+            ",
+        )?;
+        let segment_path = segment_path.display().to_string();
+        for key in destination_keys {
+            let replicated: bool = stmt.query_row(params![segment_path, key], |row| row.get(0))?;
+            if !replicated {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Drop the replication record of a segment deleted locally.
+    pub fn forget_segment(&self, segment_path: &Path) -> Result<()> {
+        self.conn().execute(
+            "DELETE FROM replicated_segments WHERE segment_path = ?",
+            params![segment_path.display().to_string()],
+        )?;
+        Ok(())
+    }
+
+    /// Move every waiting job for `segment_path` to the front of the queue;
+    /// returns how many were raised.
+    pub fn prioritize(&self, segment_path: &Path) -> Result<usize> {
+        let conn = self.conn();
+        let raised = conn.execute(
+            "
+            UPDATE replication_queue SET priority = ?, updated_ts = ?
+            WHERE segment_path = ? AND status IN ('pending', 'failed')
+            ",
+            params![
+                ReplicationPriority::Urgent.as_column(),
+                Utc::now().timestamp(),
+                segment_path.display().to_string()
+            ],
+        )?;
+        Ok(raised)
+    }
+
+    /// Creation time of the oldest job still waiting to be replicated.
+    pub fn oldest_pending_created_ts(&self) -> Result<Option<i64>> {
+        let conn = self.conn();
+        let ts: Option<i64> = conn.query_row(
+            "SELECT MIN(created_ts) FROM replication_queue WHERE status IN ('pending', 'in_progress')",
+            [],
+            |row| row.get(0),
+        )?;
+        Ok(ts)
+    }
+
     pub fn failed_count(&self) -> Result<usize> {
-        let conn = self.open()?;
+        let conn = self.conn();
         let count: i64 = conn.query_row(
             "SELECT COUNT(*) FROM replication_queue WHERE status = 'failed'",
             [],
@@ -201,9 +418,41 @@ impl ReplicationQueue {
         Ok(count as usize)
     }
 
+    pub fn requeue_in_progress(&self) -> Result<usize> {
+        let now = Utc::now().timestamp();
+        let conn = self.conn();
+        let updated = conn.execute(
+            "
+            UPDATE replication_queue
+            SET status = 'pending', next_retry_ts = ?, lease_expires_ts = NULL, updated_ts = ?
+            WHERE status = 'in_progress'
+            ",
+            params![now, now],
+        )?;
+        Ok(updated)
+    }
+
+    /// Return in-progress jobs whose lease has expired to the queue, such as
+    /// those left behind by a crash. Jobs claimed before leases existed have
+    /// none and count as expired.
+    pub fn reap_expired_leases(&self) -> Result<usize> {
+        let now = Utc::now().timestamp();
+        let conn = self.conn();
+        let reaped = conn.execute(
+            "
+            UPDATE replication_queue
+            SET status = 'pending', next_retry_ts = ?, lease_expires_ts = NULL, updated_ts = ?
+            WHERE status = 'in_progress'
+              AND (lease_expires_ts IS NULL OR lease_expires_ts <= ?)
+            ",
+            params![now, now, now],
+        )?;
+        Ok(reaped)
+    }
+
     pub fn retry_failed(&self) -> Result<usize> {
         let now = Utc::now().timestamp();
-        let conn = self.open()?;
+        let conn = self.conn();
         let updated = conn.execute(
             "
             UPDATE replication_queue
@@ -216,6 +465,65 @@ impl ReplicationQueue {
     }
 }
 
+/// Bring the database to the latest schema version, one transaction per
+/// migration, so a failed migration leaves the previous version intact.
+fn migrate(conn: &mut Connection) -> Result<()> {
+    conn.execute_batch("CREATE TABLE IF NOT EXISTS schema_version (version INTEGER NOT NULL)")?;
+    let version = match current_version(conn)? {
+        Some(version) => version,
+        None => {
+            let version = unversioned_version(conn)?;
+            conn.execute(
+                "INSERT INTO schema_version (version) VALUES (?1)",
+                [version],
+            )?;
+            version
+        }
+    };
+    let latest = MIGRATIONS.len() as u32;
+    if version > latest {
+        bail!(
+            "schema version {version} is newer than this focld supports ({latest}); \
+             downgrading the queue is not supported"
+        );
+    }
+    for (index, sql) in MIGRATIONS.iter().enumerate().skip(version as usize) {
+        let next = index as u32 + 1;
+        let tx = conn.transaction()?;
+        tx.execute_batch(sql)
+            .with_context(|| format!("migration to schema version {next} failed"))?;
+        tx.execute("UPDATE schema_version SET version = ?1", [next])?;
+        tx.commit()?;
+        tracing::info!(version = next, "migrated replication queue schema");
+    }
+    Ok(())
+}
+
+fn current_version(conn: &Connection) -> Result<Option<u32>> {
+    Ok(conn
+        .query_row("SELECT version FROM schema_version", [], |row| row.get(0))
+        .optional()?)
+}
+
+/// Version of a queue created before `schema_version` existed: such queues
+/// were created whole, with or without the `priority` column.
+fn unversioned_version(conn: &Connection) -> Result<u32> {
+    let has_column = |column: &str| -> Result<bool> {
+        Ok(conn.query_row(
+            "SELECT EXISTS(SELECT 1 FROM pragma_table_info('replication_queue') WHERE name = ?1)",
+            [column],
+            |row| row.get(0),
+        )?)
+    };
+    Ok(if has_column("priority")? {
+        2
+    } else if has_column("id")? {
+        1
+    } else {
+        0
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -231,15 +539,172 @@ mod tests {
                 Path::new("/tmp/segment.gz.json"),
                 "local:/tmp/archive",
                 0,
+                ReplicationPriority::Normal,
             )
             .unwrap();
 
         assert_eq!(queue.pending_count().unwrap(), 1);
+        assert!(queue.oldest_pending_created_ts().unwrap().is_some());
+
+        let segment = Path::new("/tmp/segment.gz");
+        assert!(queue.has_open_job(segment, "local:/tmp/archive").unwrap());
+        assert!(!queue.has_open_job(segment, "local:/mnt/other").unwrap());
 
-        let jobs = queue.claim_ready(10).unwrap();
+        let jobs = queue.claim_ready(10, 60).unwrap();
         assert_eq!(jobs.len(), 1);
+        assert!(queue.has_open_job(segment, "local:/tmp/archive").unwrap());
 
         queue.mark_success(jobs[0].id).unwrap();
+        assert!(!queue.has_open_job(segment, "local:/tmp/archive").unwrap());
         assert_eq!(queue.pending_count().unwrap(), 0);
+        assert_eq!(queue.oldest_pending_created_ts().unwrap(), None);
+    }
+
+    #[test]
+    fn enqueues_segment_for_many_destinations() {
+        let tmp = tempfile::tempdir().unwrap();
+        let queue = ReplicationQueue::new(tmp.path()).unwrap();
+
+        let queued = queue
+            .enqueue_many(
+                Path::new("/tmp/segment.gz"),
+                Path::new("/tmp/segment.gz.json"),
+                [("local:/mnt/a", 0), ("local:/mnt/b", 3), ("s3:bucket", 5)],
+                ReplicationPriority::Normal,
+            )
+            .unwrap();
+        assert_eq!(queued, 3);
+
+        let mut jobs = queue.claim_ready(10, 60).unwrap();
+        jobs.sort_by(|a, b| a.destination_key.cmp(&b.destination_key));
+        let destinations = jobs
+            .iter()
+            .map(|job| (job.destination_key.as_str(), job.max_retries))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            destinations,
+            [("local:/mnt/a", 0), ("local:/mnt/b", 3), ("s3:bucket", 5)]
+        );
+    }
+
+    #[test]
+    fn requeues_in_progress_jobs() {
+        let tmp = tempfile::tempdir().unwrap();
+        let queue = ReplicationQueue::new(tmp.path()).unwrap();
+
+        queue
+            .enqueue(
+                Path::new("/tmp/segment.gz"),
+                Path::new("/tmp/segment.gz.json"),
+                "local:/tmp/archive",
+                0,
+                ReplicationPriority::Normal,
+            )
+            .unwrap();
+
+        assert_eq!(queue.claim_ready(10, 60).unwrap().len(), 1);
+        assert!(queue.claim_ready(10, 60).unwrap().is_empty());
+
+        assert_eq!(queue.requeue_in_progress().unwrap(), 1);
+        assert_eq!(queue.claim_ready(10, 60).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn reaps_jobs_with_expired_leases() {
+        let tmp = tempfile::tempdir().unwrap();
+        let queue = ReplicationQueue::new(tmp.path()).unwrap();
+        let enqueue = |destination| {
+            queue
+                .enqueue(
+                    Path::new("/tmp/segment.gz"),
+                    Path::new("/tmp/segment.gz.json"),
+                    destination,
+                    0,
+                    ReplicationPriority::Normal,
+                )
+                .unwrap();
+        };
+
+        enqueue("local:/mnt/a");
+        assert_eq!(queue.claim_ready(10, 3600).unwrap().len(), 1);
+        assert_eq!(queue.reap_expired_leases().unwrap(), 0);
+
+        // A lease of 0 expires on claim, like a crash long ago.
+        enqueue("local:/mnt/b");
+        assert_eq!(queue.claim_ready(10, 0).unwrap().len(), 1);
+        assert_eq!(queue.reap_expired_leases().unwrap(), 1);
+        let jobs = queue.claim_ready(10, 3600).unwrap();
+        assert_eq!(jobs.len(), 1);
+        assert_eq!(jobs[0].destination_key, "local:/mnt/b");
+    }
+
+    #[test]
+    fn claims_ribs_and_flagged_segments_first() {
+        let tmp = tempfile::tempdir().unwrap();
+        let queue = ReplicationQueue::new(tmp.path()).unwrap();
+        let enqueue = |segment: &str, priority| {
+            queue
+                .enqueue(
+                    Path::new(segment),
+                    Path::new("m"),
+                    "local:/mnt/replica",
+                    0,
+                    priority,
+                )
+                .unwrap()
+        };
+        enqueue("updates.1", ReplicationPriority::Normal);
+        enqueue("updates.2", ReplicationPriority::Normal);
+        enqueue("bview.1", ReplicationPriority::for_stream("ribs"));
+        assert_eq!(queue.prioritize(Path::new("updates.2")).unwrap(), 1);
+
+        let order = queue
+            .claim_ready(10, 60)
+            .unwrap()
+            .into_iter()
+            .map(|job| (job.segment_path.display().to_string(), job.priority))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            order,
+            [
+                ("updates.2".to_string(), ReplicationPriority::Urgent),
+                ("bview.1".to_string(), ReplicationPriority::Ribs),
+                ("updates.1".to_string(), ReplicationPriority::Normal),
+            ]
+        );
+    }
+
+    #[test]
+    fn migrates_unversioned_queues() {
+        let tmp = tempfile::tempdir().unwrap();
+        let db_path = tmp.path().join(".replication").join("queue.sqlite");
+        fs::create_dir_all(db_path.parent().unwrap()).unwrap();
+        {
+            // A queue from before priority classes and schema versions.
+            let conn = Connection::open(&db_path).unwrap();
+            conn.execute_batch(MIGRATIONS[0]).unwrap();
+            conn.execute(
+                "INSERT INTO replication_queue (segment_path, manifest_path, destination_key,
+                 next_retry_ts, status, created_ts, updated_ts)
+                 VALUES ('/tmp/rib.gz', '/tmp/rib.gz.json', 'local:/mnt', 0, 'pending', 0, 0)",
+                [],
+            )
+            .unwrap();
+        }
+
+        let queue = ReplicationQueue::new(tmp.path()).unwrap();
+        assert_eq!(queue.schema_version().unwrap(), MIGRATIONS.len() as u32);
+        assert_eq!(queue.claim_ready(10, 60).unwrap().len(), 1);
+        drop(queue);
+
+        // Reopening applies nothing; a newer schema is refused.
+        let queue = ReplicationQueue::new(tmp.path()).unwrap();
+        queue
+            .conn()
+            .execute("UPDATE schema_version SET version = 99", [])
+            .unwrap();
+        drop(queue);
+        let err = ReplicationQueue::new(tmp.path()).unwrap_err();
+        assert!(format!("{err:#}").contains("newer than this focld supports"));
     }
 }
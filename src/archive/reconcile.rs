@@ -0,0 +1,331 @@
+//! `focl archive reconcile`: compare the segments finalized under the archive
+//! root with what a destination actually holds, and optionally queue
+//! replication jobs for whatever is missing or differs.
+
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+use crate::archive::manifest::SegmentManifest;
+use crate::archive::queue::ReplicationPriority;
+use crate::archive::replicator::{object_key, s3_client, Replicator, SHA256_METADATA};
+use crate::config::{ArchiveDestinationConfig, DestinationType};
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ReconcileReport {
+    pub destination: String,
+    pub local_segments: usize,
+    pub remote_objects: usize,
+    /// Segments whose object or manifest is absent on the destination.
+    pub missing: Vec<String>,
+    /// Segments present on the destination with a different size, or, for
+    /// S3, a different `sha256` object metadata.
+    pub mismatched: Vec<String>,
+    /// Segment objects on the destination with no local manifest.
+    pub remote_only: Vec<String>,
+    /// Repair jobs queued; segments already waiting in the queue are skipped.
+    pub repair_jobs_queued: usize,
+}
+
+/// A finalized segment under the archive root, found through its manifest.
+pub(crate) struct LocalSegment {
+    pub(crate) segment_path: PathBuf,
+    pub(crate) manifest_path: PathBuf,
+    pub(crate) manifest: SegmentManifest,
+}
+
+/// An object on the destination. `sha256` is the object metadata recorded
+/// on S3 uploads; local destinations and older S3 objects compare by size.
+#[derive(Debug, Clone, Default)]
+struct RemoteObject {
+    size: u64,
+    sha256: Option<String>,
+}
+
+impl From<u64> for RemoteObject {
+    fn from(size: u64) -> Self {
+        Self { size, sha256: None }
+    }
+}
+
+impl Replicator {
+    pub async fn reconcile(
+        &self,
+        root: &Path,
+        destination_key: &str,
+        repair: bool,
+    ) -> Result<ReconcileReport> {
+        let destination = self
+            .destination(destination_key)
+            .with_context(|| format!("destination {destination_key} not found"))?;
+        let local = local_segments(root)?;
+        let remote = match destination.destination_type {
+            DestinationType::Local => list_local(
+                destination
+                    .path
+                    .as_ref()
+                    .context("local destination path missing")?,
+            )?
+            .into_iter()
+            .map(|(key, size)| (key, RemoteObject::from(size)))
+            .collect(),
+            DestinationType::S3 => list_s3(destination, &local).await?,
+        };
+
+        let mut report = compare(&local, &remote);
+        report.destination = destination_key.to_string();
+        if repair {
+            for segment in &local {
+                let relative = &segment.manifest.relative_path;
+                let needs_repair =
+                    report.missing.contains(relative) || report.mismatched.contains(relative);
+                if needs_repair
+                    && !self
+                        .queue()
+                        .has_open_job(&segment.segment_path, destination_key)?
+                {
+                    self.queue().enqueue(
+                        &segment.segment_path,
+                        &segment.manifest_path,
+                        destination_key,
+                        destination.max_retries(),
+                        ReplicationPriority::for_stream(&segment.manifest.stream),
+                    )?;
+                    report.repair_jobs_queued += 1;
+                }
+            }
+        }
+        Ok(report)
+    }
+}
+
+fn compare(local: &[LocalSegment], remote: &BTreeMap<String, RemoteObject>) -> ReconcileReport {
+    let mut report = ReconcileReport {
+        local_segments: local.len(),
+        remote_objects: remote.len(),
+        ..ReconcileReport::default()
+    };
+    let mut known = HashSet::new();
+    for segment in local {
+        let relative = segment.manifest.relative_path.trim_start_matches('/');
+        known.insert(relative.to_string());
+        match (
+            remote.get(relative),
+            remote.contains_key(&format!("{relative}.json")),
+        ) {
+            (Some(object), true)
+                if object.size == segment.manifest.bytes
+                    && object
+                        .sha256
+                        .as_ref()
+                        .is_none_or(|sha256| *sha256 == segment.manifest.sha256) => {}
+            (Some(_), true) => report.mismatched.push(relative.to_string()),
+            _ => report.missing.push(relative.to_string()),
+        }
+    }
+    report.remote_only = remote
+        .keys()
+        .filter(|key| !key.ends_with(".json") && !known.contains(*key))
+        .cloned()
+        .collect();
+    report
+}
+
+/// Manifests under `root`, skipping dot-directories such as `.replication`.
+pub(crate) fn local_segments(root: &Path) -> Result<Vec<LocalSegment>> {
+    let mut segments = Vec::new();
+    for (relative, _) in list_local(root)? {
+        let Some(segment) = relative.strip_suffix(".json") else {
+            continue;
+        };
+        if segment.ends_with(".peers") {
+            continue;
+        }
+        let manifest_path = root.join(&relative);
+        let Ok(manifest) = fs::read(&manifest_path)
+            .map_err(anyhow::Error::from)
+            .and_then(|raw| Ok(serde_json::from_slice::<SegmentManifest>(&raw)?))
+        else {
+            continue;
+        };
+        segments.push(LocalSegment {
+            segment_path: root.join(segment),
+            manifest_path,
+            manifest,
+        });
+    }
+    Ok(segments)
+}
+
+/// Files under `base` by `/`-separated relative path, with their sizes.
+fn list_local(base: &Path) -> Result<BTreeMap<String, u64>> {
+    let mut files = BTreeMap::new();
+    let mut dirs = vec![base.to_path_buf()];
+    while let Some(dir) = dirs.pop() {
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(err) => {
+                return Err(err).with_context(|| format!("failed reading {}", dir.display()))
+            }
+        };
+        for entry in entries {
+            let entry = entry?;
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            let metadata = entry.metadata()?;
+            let path = entry.path();
+            if metadata.is_dir() {
+                dirs.push(path);
+            } else if let Ok(relative) = path.strip_prefix(base) {
+                let relative = relative
+                    .components()
+                    .map(|c| c.as_os_str().to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                files.insert(relative, metadata.len());
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Objects under the destination prefix. Segment objects that match a local
+/// segment's size are also fetched with HEAD for their `sha256` metadata.
+async fn list_s3(
+    destination: &ArchiveDestinationConfig,
+    local: &[LocalSegment],
+) -> Result<BTreeMap<String, RemoteObject>> {
+    let bucket = destination.bucket.as_deref().context("s3 bucket missing")?;
+    let prefix = object_key(destination.prefix.as_deref().unwrap_or_default(), "");
+    let client = s3_client(destination).await?;
+
+    let mut objects = BTreeMap::new();
+    let mut pages = client
+        .list_objects_v2()
+        .bucket(bucket)
+        .prefix(&prefix)
+        .into_paginator()
+        .send();
+    while let Some(page) = pages.next().await {
+        let page = page.with_context(|| format!("failed listing s3://{bucket}/{prefix}"))?;
+        for object in page.contents() {
+            let Some(key) = object.key() else {
+                continue;
+            };
+            let relative = key.strip_prefix(&prefix).unwrap_or(key);
+            let size = object.size().unwrap_or_default().max(0) as u64;
+            objects.insert(relative.to_string(), RemoteObject::from(size));
+        }
+    }
+
+    for segment in local {
+        let relative = segment.manifest.relative_path.trim_start_matches('/');
+        let Some(object) = objects.get_mut(relative) else {
+            continue;
+        };
+        if object.size != segment.manifest.bytes {
+            continue;
+        }
+        let key = format!("{prefix}{relative}");
+        let head = client
+            .head_object()
+            .bucket(bucket)
+            .key(&key)
+            .send()
+            .await
+            .with_context(|| format!("failed reading s3://{bucket}/{key}"))?;
+        object.sha256 = head
+            .metadata()
+            .and_then(|metadata| metadata.get(SHA256_METADATA))
+            .cloned();
+    }
+    Ok(objects)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::types::ArchiveStream;
+    use crate::config::{CompressionKind, LayoutProfile};
+
+    fn remote_objects(base: &Path) -> BTreeMap<String, RemoteObject> {
+        list_local(base)
+            .unwrap()
+            .into_iter()
+            .map(|(key, size)| (key, RemoteObject::from(size)))
+            .collect()
+    }
+
+    fn finalize(root: &Path, relative: &str, body: &[u8]) {
+        let segment = root.join(relative);
+        fs::create_dir_all(segment.parent().unwrap()).unwrap();
+        fs::write(&segment, body).unwrap();
+        SegmentManifest::build(
+            "focl01",
+            ArchiveStream::Updates,
+            0,
+            900,
+            1,
+            CompressionKind::Gzip,
+            LayoutProfile::RouteViews,
+            &segment,
+            Path::new(relative),
+        )
+        .unwrap()
+        .write_sidecar(&segment)
+        .unwrap();
+    }
+
+    #[test]
+    fn reports_missing_mismatched_and_remote_only_segments() {
+        let root = tempfile::tempdir().unwrap();
+        let replica = tempfile::tempdir().unwrap();
+        let a = "focl01/2026.02/UPDATES/updates.20260221.1200.gz";
+        let b = "focl01/2026.02/UPDATES/updates.20260221.1215.gz";
+        let c = "focl01/2026.02/UPDATES/updates.20260221.1230.gz";
+        finalize(root.path(), a, b"aaaa");
+        finalize(root.path(), b, b"bbbb");
+        finalize(root.path(), c, b"cccc");
+        fs::create_dir_all(root.path().join(".replication")).unwrap();
+        fs::write(root.path().join(".replication/queue.json"), b"{}").unwrap();
+
+        finalize(replica.path(), a, b"aaaa");
+        finalize(replica.path(), b, b"bb");
+        let stray = "focl01/2026.02/UPDATES/updates.20260221.1100.gz";
+        finalize(replica.path(), stray, b"old");
+
+        let local = local_segments(root.path()).unwrap();
+        let report = compare(&local, &remote_objects(replica.path()));
+        assert_eq!(report.local_segments, 3);
+        assert_eq!(report.missing, vec![c.to_string()]);
+        assert_eq!(report.mismatched, vec![b.to_string()]);
+        assert_eq!(report.remote_only, vec![stray.to_string()]);
+    }
+
+    #[test]
+    fn reports_a_checksum_mismatch_of_the_same_size() {
+        let root = tempfile::tempdir().unwrap();
+        let a = "focl01/2026.02/UPDATES/updates.20260221.1200.gz";
+        let b = "focl01/2026.02/UPDATES/updates.20260221.1215.gz";
+        finalize(root.path(), a, b"aaaa");
+        finalize(root.path(), b, b"bbbb");
+        let local = local_segments(root.path()).unwrap();
+
+        let mut remote = remote_objects(root.path());
+        for segment in &local {
+            remote
+                .get_mut(&segment.manifest.relative_path)
+                .unwrap()
+                .sha256 = Some(segment.manifest.sha256.clone());
+        }
+        remote.get_mut(b).unwrap().sha256 = Some("0".repeat(64));
+        let report = compare(&local, &remote);
+        assert!(report.missing.is_empty());
+        assert_eq!(report.mismatched, vec![b.to_string()]);
+    }
+}
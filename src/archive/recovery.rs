@@ -0,0 +1,141 @@
+//! Failure state of an archive stream's writer. A segment whose write fails
+//! is moved to `<tmp_root>/quarantine`, and no new segment is opened until a
+//! backoff, doubling with each consecutive failure, has passed.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+
+const QUARANTINE_DIR: &str = "quarantine";
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Default)]
+pub(super) struct WriterHealth {
+    consecutive_failures: u64,
+    failures: u64,
+    quarantined: u64,
+    /// Records dropped while waiting out the backoff.
+    dropped: u64,
+    retry_at: Option<Instant>,
+}
+
+impl WriterHealth {
+    /// Whether a segment may be written at `now`.
+    pub fn ready(&self, now: Instant) -> bool {
+        self.retry_at.is_none_or(|at| now >= at)
+    }
+
+    /// Note a failure at `now` and return the backoff before the next try.
+    pub fn failed(&mut self, now: Instant) -> Duration {
+        self.consecutive_failures += 1;
+        self.failures += 1;
+        let doublings = (self.consecutive_failures - 1).min(16) as u32;
+        let backoff = INITIAL_BACKOFF
+            .saturating_mul(1 << doublings)
+            .min(MAX_BACKOFF);
+        self.retry_at = Some(now + backoff);
+        backoff
+    }
+
+    /// Note a successful write; returns the run of failures it ends, if any.
+    pub fn succeeded(&mut self) -> Option<u64> {
+        self.retry_at = None;
+        match std::mem::take(&mut self.consecutive_failures) {
+            0 => None,
+            failures => Some(failures),
+        }
+    }
+
+    pub fn note_quarantined(&mut self) {
+        self.quarantined += 1;
+    }
+
+    pub fn note_dropped(&mut self) {
+        self.dropped += 1;
+    }
+
+    pub fn consecutive_failures(&self) -> u64 {
+        self.consecutive_failures
+    }
+
+    pub fn failures(&self) -> u64 {
+        self.failures
+    }
+
+    pub fn quarantined(&self) -> u64 {
+        self.quarantined
+    }
+
+    pub fn dropped(&self) -> u64 {
+        self.dropped
+    }
+
+    /// Seconds until the next try, while backing off.
+    pub fn retry_in_secs(&self, now: Instant) -> Option<u64> {
+        self.retry_at
+            .filter(|at| *at > now)
+            .map(|at| (at - now).as_secs_f64().ceil() as u64)
+    }
+}
+
+/// Move the tmp file of a broken segment into `<tmp_root>/quarantine`,
+/// suffixed with the Unix time, and return its new path.
+pub(super) fn quarantine(tmp_root: &Path, tmp_path: &Path) -> Result<PathBuf> {
+    let dir = tmp_root.join(QUARANTINE_DIR);
+    fs::create_dir_all(&dir)
+        .with_context(|| format!("failed creating quarantine dir {}", dir.display()))?;
+    let name = tmp_path
+        .file_name()
+        .with_context(|| format!("segment path {} has no file name", tmp_path.display()))?;
+    let target = dir.join(format!(
+        "{}.{}",
+        name.to_string_lossy(),
+        chrono::Utc::now().timestamp()
+    ));
+    fs::rename(tmp_path, &target).with_context(|| {
+        format!(
+            "failed moving {} to {}",
+            tmp_path.display(),
+            target.display()
+        )
+    })?;
+    Ok(target)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backs_off_until_a_write_succeeds_and_quarantines_segments() {
+        let start = Instant::now();
+        let mut health = WriterHealth::default();
+        assert!(health.ready(start));
+
+        assert_eq!(health.failed(start), Duration::from_secs(1));
+        assert_eq!(health.failed(start), Duration::from_secs(2));
+        assert!(!health.ready(start + Duration::from_secs(1)));
+        assert!(health.ready(start + Duration::from_secs(2)));
+        assert_eq!(health.retry_in_secs(start), Some(2));
+        for _ in 0..10 {
+            health.failed(start);
+        }
+        assert_eq!(health.retry_in_secs(start), Some(60));
+
+        assert_eq!(health.succeeded(), Some(12));
+        assert_eq!(health.succeeded(), None);
+        assert!(health.ready(start));
+        assert_eq!((health.consecutive_failures(), health.failures()), (0, 12));
+
+        let tmp = tempfile::tempdir().unwrap();
+        let segment = tmp.path().join("updates.20260221.1200.gz");
+        fs::write(&segment, b"partial").unwrap();
+        let moved = quarantine(tmp.path(), &segment).unwrap();
+        assert!(!segment.exists());
+        assert!(moved.starts_with(tmp.path().join(QUARANTINE_DIR)));
+        assert_eq!(fs::read(moved).unwrap(), b"partial");
+    }
+}
@@ -10,25 +10,31 @@ use aws_sdk_s3::primitives::ByteStream;
 use aws_types::region::Region;
 use tokio::time::sleep;
 
-use crate::archive::manifest::SegmentManifest;
-use crate::archive::queue::{ReplicationJob, ReplicationQueue};
+use crate::archive::lifecycle::{object_tagging, ObjectKind};
+use crate::archive::manifest::{compute_sha256, SegmentManifest};
+use crate::archive::object_lock::{
+    already_copied, make_read_only, release_for_replace, retain_until, s3_mode,
+};
+use crate::archive::peers::sidecar_path;
+use crate::archive::queue::{ReplicationJob, ReplicationPriority, ReplicationQueue};
 use crate::archive::types::FinalizedSegment;
 use crate::config::{ArchiveConfig, ArchiveDestinationConfig, DestinationMode, DestinationType};
-use crate::types::{Event, EventEnvelope};
+use crate::types::{Event, EventBus};
+
+/// User metadata key holding a segment object's SHA-256.
+pub(crate) const SHA256_METADATA: &str = "sha256";
 
 pub struct Replicator {
     queue: ReplicationQueue,
     destinations: HashMap<String, ArchiveDestinationConfig>,
     failures: AtomicU64,
-    event_tx: Option<tokio::sync::broadcast::Sender<EventEnvelope>>,
+    deduplicated: AtomicU64,
+    lease_secs: u64,
+    events: Option<EventBus>,
 }
 
 impl Replicator {
-    pub fn new(
-        cfg: &ArchiveConfig,
-        queue: ReplicationQueue,
-        event_tx: Option<tokio::sync::broadcast::Sender<EventEnvelope>>,
-    ) -> Self {
+    pub fn new(cfg: &ArchiveConfig, queue: ReplicationQueue, events: Option<EventBus>) -> Self {
         let destinations = cfg
             .destinations
             .iter()
@@ -40,7 +46,9 @@ impl Replicator {
             queue,
             destinations,
             failures: AtomicU64::new(0),
-            event_tx,
+            deduplicated: AtomicU64::new(0),
+            lease_secs: cfg.replication_lease_secs,
+            events,
         }
     }
 
@@ -48,22 +56,40 @@ impl Replicator {
         &self.queue
     }
 
+    pub fn destination(&self, key: &str) -> Option<&ArchiveDestinationConfig> {
+        self.destinations.get(key)
+    }
+
     pub fn failures(&self) -> u64 {
         self.failures.load(Ordering::Relaxed)
     }
 
+    pub fn deduplicated(&self) -> u64 {
+        self.deduplicated.load(Ordering::Relaxed)
+    }
+
+    /// Note a segment skipped because `destination` already holds it.
+    fn note_deduplicated(&self, job: &ReplicationJob) {
+        self.deduplicated.fetch_add(1, Ordering::Relaxed);
+        tracing::debug!(
+            destination = %job.destination_key,
+            path = %job.segment_path.display(),
+            "destination already holds segment; skipped upload"
+        );
+    }
+
     pub fn enqueue_segment(&self, segment: &FinalizedSegment) -> Result<()> {
-        for destination in self.destinations.values() {
-            if destination.mode != DestinationMode::AsyncReplica {
-                continue;
-            }
-            self.queue.enqueue(
-                &segment.final_path,
-                &segment.manifest_path,
-                &destination.destination_key(),
-                destination.max_retries(),
-            )?;
-        }
+        let replicas = self
+            .destinations
+            .iter()
+            .filter(|(_, d)| d.mode == DestinationMode::AsyncReplica)
+            .map(|(key, d)| (key.as_str(), d.max_retries()));
+        self.queue.enqueue_many(
+            &segment.final_path,
+            &segment.manifest_path,
+            replicas,
+            ReplicationPriority::for_stream(segment.stream.as_str()),
+        )?;
         Ok(())
     }
 
@@ -79,7 +105,14 @@ impl Replicator {
     }
 
     pub async fn run_once(&self) -> Result<()> {
-        let jobs = self.queue.claim_ready(32)?;
+        let reaped = self.queue.reap_expired_leases()?;
+        if reaped > 0 {
+            tracing::warn!(
+                reaped,
+                "returned replication jobs with expired leases to queue"
+            );
+        }
+        let jobs = self.queue.claim_ready(32, self.lease_secs)?;
         for job in jobs {
             if let Err(err) = self.process_job(&job).await {
                 self.failures.fetch_add(1, Ordering::Relaxed);
@@ -159,20 +192,37 @@ impl Replicator {
                 .with_context(|| format!("failed creating destination dir {}", parent.display()))?;
         }
 
-        fs::copy(&job.segment_path, &target_segment).with_context(|| {
-            format!(
-                "failed copying segment {} -> {}",
-                job.segment_path.display(),
-                target_segment.display()
-            )
-        })?;
-        fs::copy(&job.manifest_path, &target_manifest).with_context(|| {
-            format!(
-                "failed copying manifest {} -> {}",
-                job.manifest_path.display(),
-                target_manifest.display()
-            )
-        })?;
+        let lock = destination.object_lock.as_ref();
+        let copy = |what: &str, from: &Path, to: &Path| -> Result<()> {
+            if let Some(lock) = lock {
+                if already_copied(from, to)? {
+                    return Ok(());
+                }
+                release_for_replace(to, lock)?;
+            }
+            fs::copy(from, to).with_context(|| {
+                format!(
+                    "failed copying {what} {} -> {}",
+                    from.display(),
+                    to.display()
+                )
+            })?;
+            if lock.is_some() {
+                make_read_only(to)?;
+            }
+            Ok(())
+        };
+
+        if holds_digest(&target_segment, &manifest.sha256)? {
+            self.note_deduplicated(job);
+        } else {
+            copy("segment", &job.segment_path, &target_segment)?;
+        }
+        copy("manifest", &job.manifest_path, &target_manifest)?;
+        let peers = sidecar_path(&job.segment_path);
+        if peers.exists() {
+            copy("peers sidecar", &peers, &sidecar_path(&target_segment))?;
+        }
 
         Ok(())
     }
@@ -183,42 +233,45 @@ impl Replicator {
         job: &ReplicationJob,
         manifest: &SegmentManifest,
     ) -> Result<()> {
-        let endpoint = destination
-            .endpoint
-            .as_deref()
-            .context("s3 endpoint missing")?;
         let bucket = destination.bucket.as_deref().context("s3 bucket missing")?;
         let prefix = destination.prefix.as_deref().unwrap_or_default();
-
-        let region = destination
-            .region
-            .clone()
-            .unwrap_or_else(|| "us-east-1".to_string());
-
-        let shared_config = aws_config::defaults(aws_config::BehaviorVersion::latest())
-            .region(Region::new(region))
-            .load()
-            .await;
-
-        let s3_conf = aws_sdk_s3::config::Builder::from(&shared_config)
-            .endpoint_url(endpoint)
-            .force_path_style(true)
-            .build();
-
-        let client = aws_sdk_s3::Client::from_conf(s3_conf);
+        let client = s3_client(destination).await?;
 
         let key = object_key(prefix, &manifest.relative_path);
         let manifest_key = format!("{}.json", key);
 
-        let body = ByteStream::from_path(Path::new(&job.segment_path)).await?;
-        client
-            .put_object()
-            .bucket(bucket)
-            .key(&key)
-            .body(body)
-            .send()
-            .await
-            .with_context(|| format!("failed uploading segment to s3://{bucket}/{key}"))?;
+        let lifecycle = destination.lifecycle.as_ref();
+        let tagging =
+            |kind| lifecycle.map(|lifecycle| object_tagging(lifecycle, kind, &manifest.stream));
+        let lock = destination.object_lock.as_ref();
+        let lock_mode = lock.map(s3_mode);
+        let lock_until = lock.map(|lock| retain_until(lock, manifest.end_ts));
+
+        // Segments are uploaded with their digest as metadata, so one already
+        // there from an earlier attempt or backfill can be recognized. A
+        // failed HEAD just means uploading again.
+        let existing = client.head_object().bucket(bucket).key(&key).send().await;
+        if existing.is_ok_and(|head| {
+            head.metadata()
+                .and_then(|m| m.get(SHA256_METADATA))
+                .is_some_and(|sha256| *sha256 == manifest.sha256)
+        }) {
+            self.note_deduplicated(job);
+        } else {
+            let body = ByteStream::from_path(Path::new(&job.segment_path)).await?;
+            client
+                .put_object()
+                .bucket(bucket)
+                .key(&key)
+                .body(body)
+                .metadata(SHA256_METADATA, &manifest.sha256)
+                .set_tagging(tagging(ObjectKind::Segment))
+                .set_object_lock_mode(lock_mode.clone())
+                .set_object_lock_retain_until_date(lock_until)
+                .send()
+                .await
+                .with_context(|| format!("failed uploading segment to s3://{bucket}/{key}"))?;
+        }
 
         let manifest_body = ByteStream::from_path(Path::new(&job.manifest_path)).await?;
         client
@@ -226,6 +279,9 @@ impl Replicator {
             .bucket(bucket)
             .key(&manifest_key)
             .body(manifest_body)
+            .set_tagging(tagging(ObjectKind::Manifest))
+            .set_object_lock_mode(lock_mode.clone())
+            .set_object_lock_retain_until_date(lock_until)
             .send()
             .await
             .with_context(|| {
@@ -235,17 +291,69 @@ impl Replicator {
                 )
             })?;
 
+        let peers = sidecar_path(&job.segment_path);
+        if peers.exists() {
+            let peers_key = format!("{}.peers.json", key);
+            client
+                .put_object()
+                .bucket(bucket)
+                .key(&peers_key)
+                .body(ByteStream::from_path(&peers).await?)
+                .set_tagging(tagging(ObjectKind::Peers))
+                .set_object_lock_mode(lock_mode)
+                .set_object_lock_retain_until_date(lock_until)
+                .send()
+                .await
+                .with_context(|| {
+                    format!("failed uploading peers sidecar to s3://{bucket}/{peers_key}")
+                })?;
+        }
+
         Ok(())
     }
 
     fn emit(&self, event: Event) {
-        if let Some(tx) = &self.event_tx {
-            let _ = tx.send(EventEnvelope::new(event));
+        if let Some(events) = &self.events {
+            events.publish(event);
         }
     }
 }
 
-fn object_key(prefix: &str, relative: &str) -> String {
+/// Whether `path` exists with SHA-256 `sha256`.
+fn holds_digest(path: &Path, sha256: &str) -> Result<bool> {
+    if !path.is_file() {
+        return Ok(false);
+    }
+    Ok(compute_sha256(path)? == sha256)
+}
+
+/// Client for an S3 destination, path-style against its configured endpoint.
+pub(crate) async fn s3_client(
+    destination: &ArchiveDestinationConfig,
+) -> Result<aws_sdk_s3::Client> {
+    let endpoint = destination
+        .endpoint
+        .as_deref()
+        .context("s3 endpoint missing")?;
+    let region = destination
+        .region
+        .clone()
+        .unwrap_or_else(|| "us-east-1".to_string());
+
+    let shared_config = aws_config::defaults(aws_config::BehaviorVersion::latest())
+        .region(Region::new(region))
+        .load()
+        .await;
+
+    let s3_conf = aws_sdk_s3::config::Builder::from(&shared_config)
+        .endpoint_url(endpoint)
+        .force_path_style(true)
+        .build();
+
+    Ok(aws_sdk_s3::Client::from_conf(s3_conf))
+}
+
+pub(crate) fn object_key(prefix: &str, relative: &str) -> String {
     if prefix.is_empty() {
         return relative.trim_start_matches('/').to_string();
     }
@@ -253,3 +361,75 @@ fn object_key(prefix: &str, relative: &str) -> String {
     let normalized_prefix = prefix.trim_matches('/');
     format!("{}/{}", normalized_prefix, relative.trim_start_matches('/'))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::types::ArchiveStream;
+    use crate::config::{CompressionKind, LayoutProfile};
+
+    #[tokio::test]
+    async fn skips_segments_the_destination_already_holds() {
+        let root = tempfile::tempdir().unwrap();
+        let replica = tempfile::tempdir().unwrap();
+        let relative = "focl01/2026.02/UPDATES/updates.20260221.1200.gz";
+        let segment = root.path().join(relative);
+        fs::create_dir_all(segment.parent().unwrap()).unwrap();
+        fs::write(&segment, b"segment").unwrap();
+        let manifest_path = SegmentManifest::build(
+            "focl01",
+            ArchiveStream::Updates,
+            0,
+            900,
+            1,
+            CompressionKind::Gzip,
+            LayoutProfile::RouteViews,
+            &segment,
+            Path::new(relative),
+        )
+        .unwrap()
+        .write_sidecar(&segment)
+        .unwrap();
+
+        let cfg: ArchiveConfig = toml::from_str(&format!(
+            r#"
+            [[destinations]]
+            type = "local"
+            mode = "async_replica"
+            path = "{}"
+            "#,
+            replica.path().display()
+        ))
+        .unwrap();
+        let queue = ReplicationQueue::new(root.path()).unwrap();
+        let replicator = Replicator::new(&cfg, queue, None);
+        let key = cfg.destinations[0].destination_key();
+        let target = replica.path().join(relative);
+        let replicate = || async {
+            replicator
+                .queue()
+                .enqueue(
+                    &segment,
+                    &manifest_path,
+                    &key,
+                    0,
+                    ReplicationPriority::Normal,
+                )
+                .unwrap();
+            replicator.run_once().await.unwrap();
+        };
+
+        // A backfill of a segment already there only copies its manifest.
+        fs::create_dir_all(target.parent().unwrap()).unwrap();
+        fs::copy(&segment, &target).unwrap();
+        replicate().await;
+        assert_eq!(replicator.deduplicated(), 1);
+        assert!(replica.path().join(format!("{relative}.json")).exists());
+
+        // A different copy is replaced.
+        fs::write(&target, b"corrupt").unwrap();
+        replicate().await;
+        assert_eq!(replicator.deduplicated(), 1);
+        assert_eq!(fs::read(&target).unwrap(), b"segment");
+    }
+}
@@ -10,11 +10,19 @@ use bgpkit_parser::models::{
 use bgpkit_parser::parser::bgp::attributes::parse_attributes;
 use bgpkit_parser::parser::bgp::parse_bgp_message;
 use bytes::Bytes;
-use ipnet::{IpNet, Ipv4Net};
+use ipnet::IpNet;
 
 use crate::archive::types::{PeerStateRecordInput, RibSnapshotInput, UpdateRecordInput};
 
 pub fn encode_bgp4mp_message_as4(input: &UpdateRecordInput) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    write_bgp4mp_message_as4(input, &mut out)?;
+    Ok(out)
+}
+
+/// Like [`encode_bgp4mp_message_as4`], appending the record to `out`.
+pub fn write_bgp4mp_message_as4(input: &UpdateRecordInput, out: &mut Vec<u8>) -> Result<()> {
+    check_families(input.peer_ip, input.local_ip)?;
     let bgp_message = parse_update_message(&input.bgp_message)?;
 
     let msg = Bgp4MpMessage {
@@ -22,21 +30,34 @@ pub fn encode_bgp4mp_message_as4(input: &UpdateRecordInput) -> Result<Vec<u8>> {
         peer_asn: Asn::new_32bit(input.peer_asn),
         local_asn: Asn::new_32bit(input.local_asn),
         interface_index: input.interface_index,
-        peer_ip: IpAddr::V4(input.peer_ip),
-        local_ip: IpAddr::V4(input.local_ip),
+        peer_ip: input.peer_ip,
+        local_ip: input.local_ip,
         bgp_message,
     };
 
     let message = MrtMessage::Bgp4Mp(Bgp4MpEnum::Message(msg));
-    Ok(encode_mrt_message(
+    write_mrt_message(
         input.timestamp as u32,
         EntryType::BGP4MP,
         Bgp4MpType::MessageAs4 as u16,
         message,
-    ))
+        out,
+    );
+    Ok(())
 }
 
 pub fn encode_bgp4mp_state_change_as4(input: &PeerStateRecordInput) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    write_bgp4mp_state_change_as4(input, &mut out)?;
+    Ok(out)
+}
+
+/// Like [`encode_bgp4mp_state_change_as4`], appending the record to `out`.
+pub fn write_bgp4mp_state_change_as4(
+    input: &PeerStateRecordInput,
+    out: &mut Vec<u8>,
+) -> Result<()> {
+    check_families(input.peer_ip, input.local_ip)?;
     let old_state = BgpState::try_from(input.old_state)
         .map_err(|_| anyhow!("invalid old_state value {}", input.old_state))?;
     let new_state = BgpState::try_from(input.new_state)
@@ -47,51 +68,52 @@ pub fn encode_bgp4mp_state_change_as4(input: &PeerStateRecordInput) -> Result<Ve
         peer_asn: Asn::new_32bit(input.peer_asn),
         local_asn: Asn::new_32bit(input.local_asn),
         interface_index: input.interface_index,
-        peer_ip: IpAddr::V4(input.peer_ip),
-        local_addr: IpAddr::V4(input.local_ip),
+        peer_ip: input.peer_ip,
+        local_addr: input.local_ip,
         old_state,
         new_state,
     };
 
     let message = MrtMessage::Bgp4Mp(Bgp4MpEnum::StateChange(state_change));
-    Ok(encode_mrt_message(
+    write_mrt_message(
         input.timestamp as u32,
         EntryType::BGP4MP,
         Bgp4MpType::StateChangeAs4 as u16,
         message,
-    ))
+        out,
+    );
+    Ok(())
+}
+
+/// BGP4MP records carry one AFI for both addresses.
+fn check_families(peer_ip: IpAddr, local_ip: IpAddr) -> Result<()> {
+    if peer_ip.is_ipv4() != local_ip.is_ipv4() {
+        bail!("peer address {peer_ip} and local address {local_ip} differ in address family");
+    }
+    Ok(())
 }
 
 pub fn build_table_dump_v2(snapshot: &RibSnapshotInput) -> Result<Vec<Vec<u8>>> {
     let mut records = Vec::with_capacity(1 + snapshot.routes.len());
-
-    let peer_index_table = build_peer_index_table(snapshot)?;
-    records.push(encode_mrt_message(
-        snapshot.timestamp as u32,
-        EntryType::TABLE_DUMP_V2,
-        TableDumpV2Type::PeerIndexTable as u16,
-        MrtMessage::TableDumpV2Message(TableDumpV2Message::PeerIndexTable(
-            peer_index_table.clone(),
-        )),
-    ));
+    records.push(encode_peer_index_table(snapshot)?);
 
     for route in &snapshot.routes {
-        if route.prefix_len > 32 {
-            bail!("invalid IPv4 prefix length {}", route.prefix_len);
-        }
-
-        if !peer_index_table.id_peer_map.contains_key(&route.peer_index) {
+        if usize::from(route.peer_index) >= snapshot.peers.len() {
             bail!(
                 "route references unknown peer_index {} (peers: {})",
                 route.peer_index,
-                peer_index_table.id_peer_map.len()
+                snapshot.peers.len()
             );
         }
 
-        let ipv4_prefix = Ipv4Net::new(route.prefix, route.prefix_len).with_context(|| {
+        let network = IpNet::new(route.prefix, route.prefix_len).with_context(|| {
             format!("invalid route prefix {}/{}", route.prefix, route.prefix_len)
         })?;
-        let prefix = NetworkPrefix::new(IpNet::V4(ipv4_prefix), None);
+        let rib_type = match network {
+            IpNet::V4(_) => TableDumpV2Type::RibIpv4Unicast,
+            IpNet::V6(_) => TableDumpV2Type::RibIpv6Unicast,
+        };
+        let prefix = NetworkPrefix::new(network, None);
 
         let attributes = parse_attributes(
             Bytes::from(route.path_attributes.clone()),
@@ -101,7 +123,7 @@ pub fn build_table_dump_v2(snapshot: &RibSnapshotInput) -> Result<Vec<Vec<u8>>>
             None,
             None,
         )
-        .with_context(|| format!("failed parsing route attributes for prefix {}", ipv4_prefix))?;
+        .with_context(|| format!("failed parsing route attributes for prefix {}", network))?;
 
         let rib_entry = RibEntry {
             peer_index: route.peer_index,
@@ -111,7 +133,7 @@ pub fn build_table_dump_v2(snapshot: &RibSnapshotInput) -> Result<Vec<Vec<u8>>>
         };
 
         let rib = RibAfiEntries {
-            rib_type: TableDumpV2Type::RibIpv4Unicast,
+            rib_type,
             sequence_number: route.sequence,
             prefix,
             rib_entries: vec![rib_entry],
@@ -120,7 +142,7 @@ pub fn build_table_dump_v2(snapshot: &RibSnapshotInput) -> Result<Vec<Vec<u8>>>
         records.push(encode_mrt_message(
             snapshot.timestamp as u32,
             EntryType::TABLE_DUMP_V2,
-            TableDumpV2Type::RibIpv4Unicast as u16,
+            rib_type as u16,
             MrtMessage::TableDumpV2Message(TableDumpV2Message::RibAfi(rib)),
         ));
     }
@@ -128,6 +150,18 @@ pub fn build_table_dump_v2(snapshot: &RibSnapshotInput) -> Result<Vec<Vec<u8>>>
     Ok(records)
 }
 
+/// The PEER_INDEX_TABLE record that opens a TableDumpV2 RIB, on its own;
+/// `snapshot.routes` is ignored.
+pub fn encode_peer_index_table(snapshot: &RibSnapshotInput) -> Result<Vec<u8>> {
+    let peer_index_table = build_peer_index_table(snapshot)?;
+    Ok(encode_mrt_message(
+        snapshot.timestamp as u32,
+        EntryType::TABLE_DUMP_V2,
+        TableDumpV2Type::PeerIndexTable as u16,
+        MrtMessage::TableDumpV2Message(TableDumpV2Message::PeerIndexTable(peer_index_table)),
+    ))
+}
+
 fn build_peer_index_table(snapshot: &RibSnapshotInput) -> Result<PeerIndexTable> {
     if snapshot.peers.len() > u16::MAX as usize {
         bail!("peer count exceeds TABLE_DUMP_V2 limit");
@@ -181,6 +215,18 @@ fn encode_mrt_message(
     subtype: u16,
     message: MrtMessage,
 ) -> Vec<u8> {
+    let mut out = Vec::new();
+    write_mrt_message(timestamp, entry_type, subtype, message, &mut out);
+    out
+}
+
+fn write_mrt_message(
+    timestamp: u32,
+    entry_type: EntryType,
+    subtype: u16,
+    message: MrtMessage,
+    out: &mut Vec<u8>,
+) {
     let payload = message.encode(subtype);
     let header = CommonHeader {
         timestamp,
@@ -192,10 +238,9 @@ fn encode_mrt_message(
 
     let header_bytes = header.encode();
 
-    let mut out = Vec::with_capacity(header_bytes.len() + payload.len());
+    out.reserve(header_bytes.len() + payload.len());
     out.extend_from_slice(header_bytes.as_ref());
     out.extend_from_slice(payload.as_ref());
-    out
 }
 
 #[cfg(test)]
@@ -216,9 +261,11 @@ mod tests {
             peer_asn: 64496,
             local_asn: 64497,
             interface_index: 0,
-            peer_ip: Ipv4Addr::new(198, 51, 100, 1),
-            local_ip: Ipv4Addr::new(198, 51, 100, 2),
+            peer_ip: Ipv4Addr::new(198, 51, 100, 1).into(),
+            local_ip: Ipv4Addr::new(198, 51, 100, 2).into(),
             bgp_message: valid_update_withdraw_message(),
+            collector_id: None,
+            family: Default::default(),
         };
 
         let bytes = encode_bgp4mp_message_as4(&input).expect("update encoding should succeed");
@@ -239,10 +286,11 @@ mod tests {
             peer_asn: 64496,
             local_asn: 64497,
             interface_index: 0,
-            peer_ip: Ipv4Addr::new(198, 51, 100, 1),
-            local_ip: Ipv4Addr::new(198, 51, 100, 2),
+            peer_ip: Ipv4Addr::new(198, 51, 100, 1).into(),
+            local_ip: Ipv4Addr::new(198, 51, 100, 2).into(),
             old_state: 3,
             new_state: 6,
+            collector_id: None,
         };
 
         let bytes = encode_bgp4mp_state_change_as4(&input).expect("state change encoding");
@@ -255,6 +303,48 @@ mod tests {
         );
     }
 
+    #[test]
+    fn encodes_ipv6_sessions_and_rejects_mixed_families() {
+        let peer_ip: IpAddr = "2001:db8::1".parse().unwrap();
+        let mut input = UpdateRecordInput {
+            timestamp: 1_700_000_000,
+            peer_asn: 64496,
+            local_asn: 64497,
+            interface_index: 0,
+            peer_ip,
+            local_ip: "2001:db8::2".parse().unwrap(),
+            bgp_message: valid_update_withdraw_message(),
+            family: Default::default(),
+            collector_id: None,
+        };
+
+        let bytes = encode_bgp4mp_message_as4(&input).unwrap();
+        // The AFI follows the common header, both ASNs, and the interface index.
+        assert_eq!(bytes[22..24], [0, 2]);
+        let parsed = parse_mrt_record(&mut Cursor::new(bytes)).unwrap();
+        let MrtMessage::Bgp4Mp(Bgp4MpEnum::Message(message)) = parsed.message else {
+            panic!("expected a BGP4MP message");
+        };
+        assert_eq!(message.peer_ip, peer_ip);
+
+        let state = PeerStateRecordInput {
+            timestamp: 1_700_000_000,
+            peer_asn: 64496,
+            local_asn: 64497,
+            interface_index: 0,
+            peer_ip,
+            local_ip: std::net::Ipv6Addr::UNSPECIFIED.into(),
+            old_state: 5,
+            new_state: 6,
+            collector_id: None,
+        };
+        let bytes = encode_bgp4mp_state_change_as4(&state).unwrap();
+        assert_eq!(bytes[22..24], [0, 2]);
+
+        input.local_ip = Ipv4Addr::new(198, 51, 100, 2).into();
+        assert!(encode_bgp4mp_message_as4(&input).is_err());
+    }
+
     #[test]
     fn builds_table_dump_v2_records() {
         let snapshot = RibSnapshotInput {
@@ -268,7 +358,7 @@ mod tests {
             }],
             routes: vec![SnapshotRoute {
                 sequence: 1,
-                prefix: Ipv4Addr::new(203, 0, 113, 0),
+                prefix: Ipv4Addr::new(203, 0, 113, 0).into(),
                 prefix_len: 24,
                 peer_index: 0,
                 originated_time: 1_700_000_000,
@@ -285,6 +375,18 @@ mod tests {
             first_record.common_header.entry_type,
             EntryType::TABLE_DUMP_V2
         );
+        match first_record.message {
+            MrtMessage::TableDumpV2Message(TableDumpV2Message::PeerIndexTable(table)) => {
+                assert_eq!(table.view_name, "main");
+                assert_eq!(
+                    table.id_peer_map[&0].peer_bgp_id,
+                    snapshot.peers[0].peer_bgp_id
+                );
+            }
+            other => panic!("not a peer index table: {other:?}"),
+        }
+        // Exported on its own, the peer index is the RIB's first record.
+        assert_eq!(encode_peer_index_table(&snapshot).unwrap(), records[0]);
 
         let mut second = Cursor::new(records[1].clone());
         let second_record = parse_mrt_record(&mut second).expect("rib entry should parse");
@@ -0,0 +1,121 @@
+//! `[archive.tap]`: a Unix socket that streams updates-stream MRT records to
+//! local consumers as they are written, without waiting for rotation.
+
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use tokio::io::AsyncWriteExt;
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::broadcast;
+
+const SOCKET_MODE: u32 = 0o660;
+
+/// One serialized MRT record.
+pub type TapRecord = Arc<[u8]>;
+
+#[derive(Debug)]
+pub struct Tap {
+    path: PathBuf,
+    tx: broadcast::Sender<TapRecord>,
+}
+
+impl Tap {
+    /// Bind `path`, replacing a stale socket left by a previous run.
+    pub fn bind(path: &Path, buffer_records: usize) -> Result<(Self, UnixListener)> {
+        if path.exists() {
+            std::fs::remove_file(path)
+                .with_context(|| format!("failed removing stale tap socket {}", path.display()))?;
+        }
+        let listener = UnixListener::bind(path)
+            .with_context(|| format!("failed binding tap socket {}", path.display()))?;
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(SOCKET_MODE))?;
+        let (tx, _) = broadcast::channel(buffer_records);
+        let tap = Self {
+            path: path.to_path_buf(),
+            tx,
+        };
+        Ok((tap, listener))
+    }
+
+    /// Connected clients.
+    pub fn clients(&self) -> usize {
+        self.tx.receiver_count()
+    }
+
+    /// Copy `record` to connected clients; free when nobody listens.
+    pub fn send(&self, record: &[u8]) {
+        if self.tx.receiver_count() > 0 {
+            let _ = self.tx.send(Arc::from(record));
+        }
+    }
+
+    /// Accept clients until the task is aborted.
+    pub fn spawn(&self, listener: UnixListener) -> tokio::task::JoinHandle<()> {
+        let tx = self.tx.clone();
+        tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, _)) => {
+                        tokio::spawn(stream_to(stream, tx.subscribe()));
+                    }
+                    Err(err) => tracing::warn!(error=%err, "tap accept failed"),
+                }
+            }
+        })
+    }
+
+    pub fn remove_socket(&self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+async fn stream_to(mut stream: UnixStream, mut rx: broadcast::Receiver<TapRecord>) {
+    tracing::info!("tap client connected");
+    loop {
+        match rx.recv().await {
+            Ok(record) => {
+                if stream.write_all(&record).await.is_err() {
+                    break;
+                }
+            }
+            Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                tracing::warn!(skipped, "tap client fell behind, skipped records");
+            }
+            Err(broadcast::error::RecvError::Closed) => break,
+        }
+    }
+    tracing::info!("tap client disconnected");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    #[tokio::test]
+    async fn streams_records_to_connected_clients() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("tap.sock");
+        let (tap, listener) = Tap::bind(&path, 16).unwrap();
+        let task = tap.spawn(listener);
+
+        // Nothing is buffered before a client connects.
+        tap.send(b"early");
+        let mut client = UnixStream::connect(&path).await.unwrap();
+        while tap.clients() == 0 {
+            tokio::task::yield_now().await;
+        }
+        tap.send(b"mrt-1");
+        tap.send(b"mrt-2");
+
+        let mut buf = [0; 10];
+        client.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"mrt-1mrt-2");
+
+        task.abort();
+        tap.remove_socket();
+        assert!(!path.exists());
+    }
+}
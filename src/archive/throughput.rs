@@ -0,0 +1,80 @@
+//! Rolling records/sec and bytes/sec of the updates stream over 1, 5, and
+//! 15 minute windows, fed by the archive writer task.
+
+use std::time::Instant;
+
+use crate::archive::types::RateWindows;
+use crate::bgp::RateMeter;
+
+const WINDOWS_SECS: [f64; 3] = [60.0, 300.0, 900.0];
+
+#[derive(Debug)]
+pub struct ThroughputMeter {
+    records: [RateMeter; 3],
+    bytes: [RateMeter; 3],
+}
+
+impl Default for ThroughputMeter {
+    fn default() -> Self {
+        Self {
+            records: WINDOWS_SECS.map(RateMeter::new),
+            bytes: WINDOWS_SECS.map(RateMeter::new),
+        }
+    }
+}
+
+impl ThroughputMeter {
+    /// One record of `bytes` (uncompressed MRT) written.
+    pub fn record(&mut self, bytes: usize) {
+        self.record_at(bytes, Instant::now());
+    }
+
+    pub fn record_at(&mut self, bytes: usize, now: Instant) {
+        for meter in &mut self.records {
+            meter.record_at(1, now);
+        }
+        for meter in &mut self.bytes {
+            meter.record_at(bytes as u64, now);
+        }
+    }
+
+    /// `(records_per_sec, bytes_per_sec)`.
+    pub fn rates(&self) -> (RateWindows, RateWindows) {
+        self.rates_at(Instant::now())
+    }
+
+    pub fn rates_at(&self, now: Instant) -> (RateWindows, RateWindows) {
+        let windows = |meters: &[RateMeter; 3]| RateWindows {
+            m1: meters[0].rate_at(now),
+            m5: meters[1].rate_at(now),
+            m15: meters[2].rate_at(now),
+        };
+        (windows(&self.records), windows(&self.bytes))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn short_windows_react_faster() {
+        let mut meter = ThroughputMeter::default();
+        let start = Instant::now();
+        for second in 0..600 {
+            let at = start + Duration::from_secs(second);
+            for _ in 0..10 {
+                meter.record_at(100, at);
+            }
+        }
+        let now = start + Duration::from_secs(600);
+        let (records, bytes) = meter.rates_at(now);
+        assert!((records.m1 - 10.0).abs() < 0.5, "{records:?}");
+        assert!((bytes.m1 - 1000.0).abs() < 50.0, "{bytes:?}");
+        assert!(records.m5 < records.m1 && records.m15 < records.m5);
+
+        let (idle, _) = meter.rates_at(now + Duration::from_secs(120));
+        assert!(idle.m1 < 2.0 && idle.m15 > idle.m1);
+    }
+}
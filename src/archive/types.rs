@@ -4,6 +4,7 @@ use std::path::PathBuf;
 use serde::{Deserialize, Serialize};
 
 use crate::config::CompressionKind;
+use crate::types::DiskLevel;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
@@ -21,11 +22,42 @@ impl ArchiveStream {
     }
 }
 
+/// Address family of a segment series under `[archive].split_address_families`.
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum AddressFamily {
+    #[default]
+    Ipv4,
+    Ipv6,
+}
+
+impl AddressFamily {
+    /// Layout token distinguishing the series: `v4` or `v6`.
+    pub fn token(self) -> &'static str {
+        match self {
+            AddressFamily::Ipv4 => "v4",
+            AddressFamily::Ipv6 => "v6",
+        }
+    }
+
+    /// Family of `addr`.
+    pub fn of(addr: IpAddr) -> Self {
+        match addr {
+            IpAddr::V4(_) => AddressFamily::Ipv4,
+            IpAddr::V6(_) => AddressFamily::Ipv6,
+        }
+    }
+}
+
 #[derive(Debug, Clone)]
 pub struct SegmentPaths {
     pub tmp_path: PathBuf,
     pub final_path: PathBuf,
     pub relative_path: PathBuf,
+    /// `None` unless address families are archived as separate series.
+    pub family: Option<AddressFamily>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -39,6 +71,12 @@ pub struct FinalizedSegment {
     pub final_path: PathBuf,
     pub relative_path: PathBuf,
     pub manifest_path: PathBuf,
+    /// Collector the segment was laid out under; differs from
+    /// `[archive].collector_id` for a peer's own stream.
+    #[serde(default)]
+    pub collector_id: String,
+    #[serde(default)]
+    pub family: Option<AddressFamily>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -47,9 +85,19 @@ pub struct UpdateRecordInput {
     pub peer_asn: u32,
     pub local_asn: u32,
     pub interface_index: u16,
-    pub peer_ip: Ipv4Addr,
-    pub local_ip: Ipv4Addr,
+    /// The AFI of the record follows the peer address; the local address
+    /// must be of the same family.
+    pub peer_ip: IpAddr,
+    pub local_ip: IpAddr,
     pub bgp_message: Vec<u8>,
+    /// The peer's `archive.collector_id`, selecting its own stream; `None`
+    /// for the collector's.
+    #[serde(default)]
+    pub collector_id: Option<String>,
+    /// Family of the NLRI carried, selecting the series when address
+    /// families are split.
+    #[serde(default)]
+    pub family: AddressFamily,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -58,10 +106,13 @@ pub struct PeerStateRecordInput {
     pub peer_asn: u32,
     pub local_asn: u32,
     pub interface_index: u16,
-    pub peer_ip: Ipv4Addr,
-    pub local_ip: Ipv4Addr,
+    pub peer_ip: IpAddr,
+    pub local_ip: IpAddr,
     pub old_state: u16,
     pub new_state: u16,
+    /// As for [`UpdateRecordInput::collector_id`].
+    #[serde(default)]
+    pub collector_id: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -74,7 +125,7 @@ pub struct SnapshotPeer {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SnapshotRoute {
     pub sequence: u32,
-    pub prefix: Ipv4Addr,
+    pub prefix: IpAddr,
     pub prefix_len: u8,
     pub peer_index: u16,
     pub originated_time: u32,
@@ -94,12 +145,91 @@ pub struct RibSnapshotInput {
 pub struct ArchiveStatus {
     pub enabled: bool,
     pub collector_id: String,
-    pub updates_interval_secs: u32,
-    pub ribs_interval_secs: u32,
-    pub updates_open_path: Option<PathBuf>,
-    pub updates_record_count: u64,
-    pub ribs_last_path: Option<PathBuf>,
-    pub ribs_last_record_count: u64,
+    pub updates: StreamStatus,
+    pub ribs: StreamStatus,
     pub queued_replication_jobs: usize,
     pub replication_failures: u64,
+    /// Segments not uploaded because the destination already held them.
+    #[serde(default)]
+    pub replication_deduplicated: u64,
+    pub oldest_pending_replication_age_secs: Option<i64>,
+    /// Records waiting in the ingest queue.
+    #[serde(default)]
+    pub ingest_queue_depth: usize,
+    /// Records shed because the ingest queue was full.
+    #[serde(default)]
+    pub ingest_records_shed: u64,
+    /// Free MiB on the archive root at the last disk guard check.
+    #[serde(default)]
+    pub disk_free_mb: Option<u64>,
+    #[serde(default)]
+    pub disk_level: DiskLevel,
+    /// Records dropped while updates were paused for disk space.
+    #[serde(default)]
+    pub disk_records_dropped: u64,
+    /// Clients connected to `[archive.tap].socket`.
+    #[serde(default)]
+    pub tap_clients: usize,
+}
+
+/// Segment lifecycle of one archive stream. With split address families the
+/// first series stands for the others.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StreamStatus {
+    pub interval_secs: u32,
+    /// The segment being written; RIBs are written in one go and have none.
+    pub open_path: Option<PathBuf>,
+    pub open_record_count: u64,
+    pub segment_age_secs: Option<i64>,
+    pub next_rotation_secs: Option<i64>,
+    pub last_finalized_path: Option<PathBuf>,
+    /// End of the last finalized segment.
+    pub last_finalized_ts: Option<i64>,
+    pub last_finalized_record_count: u64,
+    pub last_error: Option<String>,
+    pub last_error_ts: Option<i64>,
+    /// Failed writes since the last successful one.
+    #[serde(default)]
+    pub consecutive_write_failures: u64,
+    #[serde(default)]
+    pub write_failures: u64,
+    /// Broken segments moved to `<tmp_root>/quarantine`.
+    #[serde(default)]
+    pub quarantined_segments: u64,
+    /// Records dropped while backing off after a failure.
+    #[serde(default)]
+    pub records_dropped_backoff: u64,
+    /// Seconds until a new segment is tried, while backing off.
+    #[serde(default)]
+    pub retry_in_secs: Option<u64>,
+    /// UPDATEs skipped by `[archive].dedup_window_ms`.
+    #[serde(default)]
+    pub duplicates_suppressed: u64,
+    /// `[archive].sample_rate` and the UPDATEs it left out; RIBs are never
+    /// sampled.
+    #[serde(default = "default_sample_rate")]
+    pub sample_rate: u32,
+    #[serde(default)]
+    pub sampled_out: u64,
+    /// Records written, averaged like a load average; zero for RIBs.
+    #[serde(default)]
+    pub records_per_sec: RateWindows,
+    /// Uncompressed MRT bytes written.
+    #[serde(default)]
+    pub bytes_per_sec: RateWindows,
+}
+
+/// A rate averaged over the trailing 1, 5, and 15 minutes.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct RateWindows {
+    #[serde(rename = "1m")]
+    pub m1: f64,
+    #[serde(rename = "5m")]
+    pub m5: f64,
+    #[serde(rename = "15m")]
+    pub m15: f64,
+}
+
+pub(crate) fn default_sample_rate() -> u32 {
+    1
 }
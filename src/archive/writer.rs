@@ -1,5 +1,6 @@
 use std::fs::{self, File};
-use std::io::{BufWriter, Write};
+use std::io::{BufWriter, ErrorKind, Write};
+use std::path::Path;
 
 use anyhow::{Context, Result};
 use bzip2::write::BzEncoder;
@@ -8,8 +9,9 @@ use flate2::Compression;
 use zstd::stream::write::Encoder as ZstdEncoder;
 
 use crate::archive::manifest::SegmentManifest;
+use crate::archive::object_lock::{make_read_only, release_for_replace};
 use crate::archive::types::{ArchiveStream, FinalizedSegment, SegmentPaths};
-use crate::config::{ArchiveConfig, CompressionKind};
+use crate::config::{ArchiveConfig, CompressionKind, DestinationMode};
 
 enum SegmentEncoder {
     Gzip(GzEncoder<BufWriter<File>>),
@@ -98,7 +100,14 @@ impl SegmentWriter {
                 SegmentEncoder::Bzip2(BzEncoder::new(buffered, bzip2::Compression::default()))
             }
             CompressionKind::Zstd => {
-                let enc = ZstdEncoder::new(buffered, 3).context("failed to create zstd encoder")?;
+                let mut enc =
+                    ZstdEncoder::new(buffered, 3).context("failed to create zstd encoder")?;
+                // RIB dumps are written in one burst at snapshot time, so they
+                // are worth spreading across workers; updates trickle in.
+                if stream == ArchiveStream::Ribs && cfg.rib_zstd_workers > 0 {
+                    enc.multithread(cfg.rib_zstd_workers)
+                        .context("failed to enable zstd workers")?;
+                }
                 SegmentEncoder::Zstd(enc)
             }
         };
@@ -123,6 +132,10 @@ impl SegmentWriter {
         &self.paths.final_path
     }
 
+    pub fn tmp_path(&self) -> &std::path::Path {
+        &self.paths.tmp_path
+    }
+
     pub fn record_count(&self) -> u64 {
         self.record_count
     }
@@ -138,7 +151,26 @@ impl SegmentWriter {
         }
         drop(file);
 
-        fs::rename(&self.paths.tmp_path, &self.paths.final_path).with_context(|| {
+        let lock = self
+            .cfg
+            .destinations
+            .iter()
+            .find(|d| d.mode == DestinationMode::Primary)
+            .and_then(|d| d.object_lock.as_ref());
+        if let Some(lock) = lock {
+            release_for_replace(&self.paths.final_path, lock)?;
+            release_for_replace(
+                Path::new(&format!("{}.json", self.paths.final_path.display())),
+                lock,
+            )?;
+        }
+
+        move_into_place(
+            &self.paths.tmp_path,
+            &self.paths.final_path,
+            self.cfg.cross_filesystem_tmp,
+        )
+        .with_context(|| {
             format!(
                 "failed to atomically move {} to {}",
                 self.paths.tmp_path.display(),
@@ -146,7 +178,7 @@ impl SegmentWriter {
             )
         })?;
 
-        let manifest = SegmentManifest::build(
+        let mut manifest = SegmentManifest::build(
             self.cfg.collector_id.clone(),
             self.stream,
             self.start_ts,
@@ -157,8 +189,16 @@ impl SegmentWriter {
             &self.paths.final_path,
             &self.paths.relative_path,
         )?;
+        if self.stream == ArchiveStream::Updates {
+            manifest.sample_rate = self.cfg.sample_rate;
+        }
+        manifest.family = self.paths.family;
 
         let manifest_path = manifest.write_sidecar(&self.paths.final_path)?;
+        if lock.is_some() {
+            make_read_only(&self.paths.final_path)?;
+            make_read_only(&manifest_path)?;
+        }
 
         Ok(FinalizedSegment {
             stream: self.stream,
@@ -170,6 +210,128 @@ impl SegmentWriter {
             final_path: self.paths.final_path,
             relative_path: self.paths.relative_path,
             manifest_path,
+            collector_id: self.cfg.collector_id.clone(),
+            family: self.paths.family,
         })
     }
 }
+
+/// Rename `tmp_path` to `final_path`. When they are on different filesystems
+/// and `cross_filesystem` is set, copy to a sibling of `final_path`, fsync it,
+/// and rename that instead, so readers never see a partial segment.
+fn move_into_place(tmp_path: &Path, final_path: &Path, cross_filesystem: bool) -> Result<()> {
+    match fs::rename(tmp_path, final_path) {
+        Ok(()) => return Ok(()),
+        Err(err) if err.kind() == ErrorKind::CrossesDevices && cross_filesystem => {}
+        Err(err) if err.kind() == ErrorKind::CrossesDevices => {
+            return Err(err).context(
+                "tmp_root is on another filesystem than root; set [archive].cross_filesystem_tmp",
+            );
+        }
+        Err(err) => return Err(err.into()),
+    }
+    copy_into_place(tmp_path, final_path)?;
+    fs::remove_file(tmp_path)
+        .with_context(|| format!("failed to remove {}", tmp_path.display()))?;
+    Ok(())
+}
+
+fn copy_into_place(tmp_path: &Path, final_path: &Path) -> Result<()> {
+    let mut staged = final_path.as_os_str().to_owned();
+    staged.push(".partial");
+    let staged = Path::new(&staged);
+    fs::copy(tmp_path, staged)
+        .with_context(|| format!("failed to copy to {}", staged.display()))?;
+    File::open(staged)
+        .and_then(|file| file.sync_all())
+        .with_context(|| format!("failed to fsync {}", staged.display()))?;
+    fs::rename(staged, final_path)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn multithreaded_zstd_rib_segment_round_trips() {
+        let dir = tempfile::tempdir().unwrap();
+        let cfg = ArchiveConfig {
+            compression: CompressionKind::Zstd,
+            rib_zstd_workers: 2,
+            fsync_on_rotate: false,
+            ..Default::default()
+        };
+        let paths = SegmentPaths {
+            tmp_path: dir.path().join("tmp/bview.zst"),
+            final_path: dir.path().join("bview.zst"),
+            relative_path: PathBuf::from("bview.zst"),
+            family: None,
+        };
+        let record = vec![7u8; 64 * 1024];
+
+        let mut writer = SegmentWriter::new(&cfg, ArchiveStream::Ribs, 0, paths).unwrap();
+        for _ in 0..64 {
+            writer.write_record(&record).unwrap();
+        }
+        let finalized = writer.finalize(0).unwrap();
+
+        let decoded = zstd::decode_all(File::open(&finalized.final_path).unwrap()).unwrap();
+        assert_eq!(decoded.len(), 64 * record.len());
+        assert!(decoded.iter().all(|&b| b == 7));
+    }
+
+    #[test]
+    fn locked_primary_segments_are_not_replaced() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut cfg: ArchiveConfig = toml::from_str(&format!(
+            r#"
+            [[destinations]]
+            type = "local"
+            mode = "primary"
+            path = "{}"
+
+            [destinations.object_lock]
+            retain_days = 7
+            "#,
+            dir.path().display()
+        ))
+        .unwrap();
+        cfg.fsync_on_rotate = false;
+        let finalize = || {
+            let paths = SegmentPaths {
+                tmp_path: dir.path().join("tmp/updates.gz"),
+                final_path: dir.path().join("updates.gz"),
+                relative_path: PathBuf::from("updates.gz"),
+                family: None,
+            };
+            let mut writer = SegmentWriter::new(&cfg, ArchiveStream::Updates, 0, paths)?;
+            writer.write_record(b"record")?;
+            writer.finalize(900)
+        };
+
+        let finalized = finalize().unwrap();
+        assert!(fs::metadata(&finalized.final_path)
+            .unwrap()
+            .permissions()
+            .readonly());
+        assert!(fs::metadata(&finalized.manifest_path)
+            .unwrap()
+            .permissions()
+            .readonly());
+        assert!(finalize().is_err());
+    }
+
+    #[test]
+    fn copies_segment_into_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let tmp_path = dir.path().join("updates.tmp");
+        let final_path = dir.path().join("updates.gz");
+        fs::write(&tmp_path, b"segment").unwrap();
+
+        copy_into_place(&tmp_path, &final_path).unwrap();
+        assert_eq!(fs::read(&final_path).unwrap(), b"segment");
+        assert!(!dir.path().join("updates.gz.partial").exists());
+    }
+}
@@ -0,0 +1,339 @@
+//! `[rib_in]`: the Adj-RIB-In, reduced to the AS path of each (peer, prefix),
+//! plus the origin ASNs seen per prefix since startup. Backs `rib_in`,
+//! `aspath_stats`, and `origin_changed` events for `watch_prefixes`.
+
+use std::collections::{BTreeSet, HashMap};
+use std::sync::{Arc, Mutex};
+
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+
+use crate::config::RibInConfig;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AsPathStats {
+    /// Distinct prefixes across every peer's Adj-RIB-In.
+    pub prefixes: usize,
+    /// Prefixes currently announced with more than one origin ASN.
+    pub moas_prefixes: usize,
+    /// Origins of the requested prefix, or the prefixes with the most origins.
+    pub origins: Vec<PrefixOrigins>,
+    pub peers: Vec<PeerPathStats>,
+    /// Origins first seen at or after `since` for a prefix that already had
+    /// another origin; `None` without `since`.
+    pub new_origins: Option<Vec<NewOrigin>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PrefixOrigins {
+    pub prefix: IpNet,
+    pub origins: Vec<u32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PeerPathStats {
+    pub peer: String,
+    pub prefixes: usize,
+    /// Mean AS path length, prepends included.
+    pub avg_path_len: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct NewOrigin {
+    pub prefix: IpNet,
+    pub origin: u32,
+    /// The peer it was first received from.
+    pub peer: String,
+    pub first_seen: i64,
+}
+
+/// A watched prefix a peer re-announced with a different AS path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct PathChange {
+    pub prefix: IpNet,
+    pub old_path: Arc<[u32]>,
+    pub new_path: Arc<[u32]>,
+}
+
+#[derive(Debug)]
+struct OriginSeen {
+    asn: u32,
+    first_seen: i64,
+    peer: String,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    /// Peer address to prefix to AS path. Prefixes of one UPDATE share a path.
+    peers: HashMap<String, HashMap<IpNet, Arc<[u32]>>>,
+    origins: HashMap<IpNet, Vec<OriginSeen>>,
+}
+
+#[derive(Debug)]
+pub(super) struct AdjRibIn {
+    state: Mutex<State>,
+    watch: Vec<IpNet>,
+}
+
+impl AdjRibIn {
+    /// `None` when `[rib_in]` is disabled.
+    pub fn new(cfg: &RibInConfig) -> Option<Self> {
+        cfg.enabled.then(|| Self {
+            state: Mutex::default(),
+            // Checked by `Config::validate`.
+            watch: cfg.watched_prefixes().unwrap_or_default(),
+        })
+    }
+
+    /// Apply one UPDATE received from `peer` at Unix second `now`, returning
+    /// the watched prefixes whose path from `peer` it changed.
+    pub fn update_at(
+        &self,
+        peer: &str,
+        announced: &[IpNet],
+        as_path: &[u32],
+        withdrawn: &[IpNet],
+        now: i64,
+    ) -> Vec<PathChange> {
+        let mut changes = Vec::new();
+        if announced.is_empty() && withdrawn.is_empty() {
+            return changes;
+        }
+        let mut state = self.lock();
+        let State { peers, origins } = &mut *state;
+        let rib = peers.entry(peer.to_string()).or_default();
+        for prefix in withdrawn {
+            rib.remove(prefix);
+        }
+        if announced.is_empty() {
+            return changes;
+        }
+        let path: Arc<[u32]> = Arc::from(as_path);
+        for prefix in announced {
+            let Some(old_path) = rib.insert(*prefix, Arc::clone(&path)) else {
+                continue;
+            };
+            if old_path != path && self.watch.iter().any(|w| w.contains(prefix)) {
+                changes.push(PathChange {
+                    prefix: *prefix,
+                    old_path,
+                    new_path: Arc::clone(&path),
+                });
+            }
+        }
+        // An empty path is a route originated by the peer's own AS (iBGP).
+        let Some(&origin) = as_path.last() else {
+            return changes;
+        };
+        for prefix in announced {
+            let seen = origins.entry(*prefix).or_default();
+            if seen.iter().all(|o| o.asn != origin) {
+                seen.push(OriginSeen {
+                    asn: origin,
+                    first_seen: now,
+                    peer: peer.to_string(),
+                });
+            }
+        }
+        changes
+    }
+
+    /// Drop the routes of a session that went down.
+    pub fn clear_peer(&self, peer: &str) {
+        self.lock().peers.remove(peer);
+    }
+
+    /// Prefixes received from `peer`, in order.
+    pub fn prefixes(&self, peer: &str) -> Vec<IpNet> {
+        let mut prefixes = self
+            .lock()
+            .peers
+            .get(peer)
+            .map(|rib| rib.keys().copied().collect::<Vec<_>>())
+            .unwrap_or_default();
+        prefixes.sort();
+        prefixes
+    }
+
+    /// Routes received from `peer` with their AS paths, by prefix.
+    pub fn routes(&self, peer: &str) -> Vec<(IpNet, Arc<[u32]>)> {
+        let mut routes = self
+            .lock()
+            .peers
+            .get(peer)
+            .map(|rib| {
+                rib.iter()
+                    .map(|(prefix, path)| (*prefix, Arc::clone(path)))
+                    .collect::<Vec<_>>()
+            })
+            .unwrap_or_default();
+        routes.sort_by_key(|(prefix, _)| *prefix);
+        routes
+    }
+
+    pub fn stats(&self, prefix: Option<IpNet>, since: Option<i64>, limit: usize) -> AsPathStats {
+        let state = self.lock();
+
+        let mut current: HashMap<IpNet, BTreeSet<u32>> = HashMap::new();
+        let mut peers = Vec::with_capacity(state.peers.len());
+        for (peer, rib) in &state.peers {
+            let mut path_len = 0;
+            for (prefix, path) in rib {
+                path_len += path.len();
+                let origins = current.entry(*prefix).or_default();
+                origins.extend(path.last());
+            }
+            peers.push(PeerPathStats {
+                peer: peer.clone(),
+                prefixes: rib.len(),
+                avg_path_len: if rib.is_empty() {
+                    0.0
+                } else {
+                    path_len as f64 / rib.len() as f64
+                },
+            });
+        }
+        peers.sort_by(|a, b| a.peer.cmp(&b.peer));
+
+        let moas_prefixes = current.values().filter(|o| o.len() > 1).count();
+        let mut origins = current
+            .iter()
+            .filter(|(p, o)| match prefix {
+                Some(wanted) => **p == wanted,
+                None => o.len() > 1,
+            })
+            .map(|(p, o)| PrefixOrigins {
+                prefix: *p,
+                origins: o.iter().copied().collect(),
+            })
+            .collect::<Vec<_>>();
+        origins.sort_by(|a, b| {
+            b.origins
+                .len()
+                .cmp(&a.origins.len())
+                .then_with(|| a.prefix.cmp(&b.prefix))
+        });
+        origins.truncate(limit);
+
+        let new_origins = since.map(|since| {
+            let mut found = state
+                .origins
+                .iter()
+                .filter(|(p, _)| prefix.is_none_or(|wanted| **p == wanted))
+                .flat_map(|(p, seen)| {
+                    let earliest = seen.iter().map(|o| o.first_seen).min().unwrap_or(since);
+                    seen.iter()
+                        .filter(move |o| o.first_seen >= since && o.first_seen > earliest)
+                        .map(move |o| NewOrigin {
+                            prefix: *p,
+                            origin: o.asn,
+                            peer: o.peer.clone(),
+                            first_seen: o.first_seen,
+                        })
+                })
+                .collect::<Vec<_>>();
+            found.sort_by(|a, b| {
+                b.first_seen
+                    .cmp(&a.first_seen)
+                    .then_with(|| a.prefix.cmp(&b.prefix))
+            });
+            found.truncate(limit);
+            found
+        });
+
+        AsPathStats {
+            prefixes: current.len(),
+            moas_prefixes,
+            origins,
+            peers,
+            new_origins,
+        }
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().expect("adj-rib-in lock poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn summarizes_origins_path_lengths_and_new_origins() {
+        let rib = AdjRibIn::new(&RibInConfig {
+            enabled: true,
+            ..RibInConfig::default()
+        })
+        .unwrap();
+        let a: IpNet = "203.0.113.0/24".parse().unwrap();
+        let b: IpNet = "198.51.100.0/24".parse().unwrap();
+
+        rib.update_at("192.0.2.2", &[a, b], &[65002, 64500], &[], 100);
+        rib.update_at("192.0.2.3", &[a], &[65003, 65010, 64500], &[], 100);
+        // A second origin for `a` an hour later.
+        rib.update_at("192.0.2.3", &[a], &[65003, 64666], &[], 3700);
+
+        let stats = rib.stats(None, Some(3600), 10);
+        assert_eq!(stats.prefixes, 2);
+        assert_eq!(stats.moas_prefixes, 1);
+        assert_eq!(
+            stats.origins,
+            vec![PrefixOrigins {
+                prefix: a,
+                origins: vec![64500, 64666],
+            }]
+        );
+        assert_eq!(stats.peers[0].avg_path_len, 2.0);
+        assert_eq!(stats.peers[1].prefixes, 1);
+        assert_eq!(
+            stats.new_origins.unwrap(),
+            vec![NewOrigin {
+                prefix: a,
+                origin: 64666,
+                peer: "192.0.2.3".to_string(),
+                first_seen: 3700,
+            }]
+        );
+
+        rib.update_at("192.0.2.2", &[], &[], &[a], 3800);
+        assert_eq!(rib.prefixes("192.0.2.2"), vec![b]);
+        rib.clear_peer("192.0.2.3");
+        let stats = rib.stats(Some(a), None, 10);
+        assert_eq!(stats.prefixes, 1);
+        assert!(stats.origins.is_empty());
+        assert!(stats.new_origins.is_none());
+    }
+
+    #[test]
+    fn reports_path_changes_of_watched_prefixes() {
+        let rib = AdjRibIn::new(&RibInConfig {
+            enabled: true,
+            watch_prefixes: vec!["203.0.113.0/24".to_string()],
+        })
+        .unwrap();
+        let watched: IpNet = "203.0.113.128/25".parse().unwrap();
+        let other: IpNet = "198.51.100.0/24".parse().unwrap();
+
+        // First announcements are not changes.
+        let first = rib.update_at("192.0.2.2", &[watched, other], &[65002, 64500], &[], 100);
+        assert!(first.is_empty());
+        let same = rib.update_at("192.0.2.2", &[watched], &[65002, 64500], &[], 110);
+        assert!(same.is_empty());
+
+        let changes = rib.update_at("192.0.2.2", &[watched, other], &[65002, 64666], &[], 120);
+        assert_eq!(
+            changes,
+            vec![PathChange {
+                prefix: watched,
+                old_path: Arc::from(&[65002, 64500][..]),
+                new_path: Arc::from(&[65002, 64666][..]),
+            }]
+        );
+
+        // A route from another peer is compared with that peer's own.
+        assert!(rib
+            .update_at("192.0.2.3", &[watched], &[65003, 64500], &[], 130)
+            .is_empty());
+    }
+}
@@ -0,0 +1,96 @@
+//! `[global].aggregate_prefixes`: collapse the configured `[[prefixes]]`
+//! into the fewest covering prefixes before they are announced.
+
+use std::collections::{BTreeMap, HashSet};
+use std::net::IpAddr;
+use std::sync::Arc;
+
+use bgpkit_parser::models::{AttributeValue, Origin};
+use ipnet::IpNet;
+
+use super::mrt::RoutePath;
+use super::PrefixEntry;
+
+/// Drop entries covered by another entry and merge adjacent ones into their
+/// supernet. Only entries sharing a next hop are combined. Supernets that
+/// were not configured verbatim carry ATOMIC_AGGREGATE.
+pub(super) fn aggregate(entries: Vec<PrefixEntry>) -> Vec<PrefixEntry> {
+    let mut by_next_hop: BTreeMap<Option<IpAddr>, Vec<IpNet>> = BTreeMap::new();
+    for entry in &entries {
+        by_next_hop
+            .entry(entry.next_hop)
+            .or_default()
+            .push(entry.network);
+    }
+    let configured = entries
+        .iter()
+        .map(|entry| (entry.next_hop, entry.network))
+        .collect::<HashSet<_>>();
+    let atomic = Arc::new(RoutePath {
+        origin: Origin::IGP,
+        as_path: vec![],
+        attributes: vec![AttributeValue::AtomicAggregate],
+    });
+
+    let mut aggregated = Vec::new();
+    for (next_hop, networks) in by_next_hop {
+        for network in IpNet::aggregate(&networks) {
+            let path = (!configured.contains(&(next_hop, network))).then(|| Arc::clone(&atomic));
+            aggregated.push(PrefixEntry {
+                network,
+                next_hop,
+                path,
+                peers: None,
+            });
+        }
+    }
+    aggregated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(network: &str, next_hop: Option<&str>) -> PrefixEntry {
+        PrefixEntry {
+            network: network.parse().unwrap(),
+            next_hop: next_hop.map(|nh| nh.parse().unwrap()),
+            path: None,
+            peers: None,
+        }
+    }
+
+    #[test]
+    fn merges_adjacent_and_drops_covered_prefixes() {
+        let entries = vec![
+            entry("10.0.0.0/24", None),
+            entry("10.0.1.0/24", None),
+            entry("10.0.1.128/25", None),
+            entry("192.0.2.0/24", None),
+            entry("192.0.2.0/24", None),
+            entry("2001:db8::/48", None),
+            entry("2001:db8:1::/48", None),
+            entry("10.0.2.0/24", Some("192.0.2.9")),
+            entry("10.0.3.0/24", None),
+        ];
+
+        let aggregated = aggregate(entries)
+            .into_iter()
+            .map(|e| (e.network.to_string(), e.next_hop, e.path.is_some()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            aggregated,
+            [
+                ("10.0.0.0/23".to_string(), None, true),
+                ("10.0.3.0/24".to_string(), None, false),
+                ("192.0.2.0/24".to_string(), None, false),
+                ("2001:db8::/47".to_string(), None, true),
+                (
+                    "10.0.2.0/24".to_string(),
+                    Some("192.0.2.9".parse().unwrap()),
+                    false
+                ),
+            ]
+        );
+    }
+}
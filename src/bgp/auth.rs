@@ -1,49 +1,15 @@
 use std::net::SocketAddr;
 use std::os::unix::io::AsRawFd;
 
-use anyhow::Result;
+use anyhow::{Context, Result};
 
-/// Set TCP-MD5 signature on a socket for BGP authentication (RFC 2385)
-///
-/// # Safety
-/// This uses libc directly and is marked unsafe due to raw pointer operations.
+/// `peer_addr` as the `sockaddr_storage` the TCP-MD5 and TCP-AO socket
+/// options match peers by.
 #[cfg(target_os = "linux")]
-pub fn set_tcp_md5_signature(socket_fd: i32, peer_addr: &SocketAddr, password: &str) -> Result<()> {
-    use libc::{setsockopt, socklen_t, AF_INET, AF_INET6, IPPROTO_TCP, TCP_MD5SIG};
-    use std::os::raw::c_void;
+fn sockaddr_storage(peer_addr: &SocketAddr) -> libc::sockaddr_storage {
+    use libc::{AF_INET, AF_INET6};
 
-    // TCP_MD5SIG requires a tcp_md5sig struct
-    // struct tcp_md5sig {
-    //     struct sockaddr_storage tcpm_addr;
-    //     __u8 tcpm_flags;
-    //     __u8 tcpm_prefixlen;
-    //     __u16 tcpm_keylen;
-    //     __u32 tcpm_ifindex;
-    //     __u8 tcpm_key[TCP_MD5SIG_MAXKEYLEN];
-    // };
-
-    const TCP_MD5SIG_MAXKEYLEN: usize = 80;
-
-    #[repr(C)]
-    struct TcpMd5Sig {
-        tcpm_addr: libc::sockaddr_storage,
-        tcpm_flags: u8,
-        tcpm_prefixlen: u8,
-        tcpm_keylen: u16,
-        tcpm_ifindex: u32,
-        tcpm_key: [u8; TCP_MD5SIG_MAXKEYLEN],
-    }
-
-    let mut md5sig = TcpMd5Sig {
-        tcpm_addr: unsafe { std::mem::zeroed() },
-        tcpm_flags: 0,
-        tcpm_prefixlen: 0,
-        tcpm_keylen: 0,
-        tcpm_ifindex: 0,
-        tcpm_key: [0; TCP_MD5SIG_MAXKEYLEN],
-    };
-
-    // Set up address
+    let mut storage: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
     match peer_addr {
         SocketAddr::V4(addr) => {
             let sin = libc::sockaddr_in {
@@ -57,11 +23,10 @@ pub fn set_tcp_md5_signature(socket_fd: i32, peer_addr: &SocketAddr, password: &
             unsafe {
                 std::ptr::copy_nonoverlapping(
                     &sin as *const _ as *const u8,
-                    &mut md5sig.tcpm_addr as *mut _ as *mut u8,
+                    &mut storage as *mut _ as *mut u8,
                     std::mem::size_of::<libc::sockaddr_in>(),
                 );
             }
-            md5sig.tcpm_addr.ss_family = AF_INET as u16;
         }
         SocketAddr::V6(addr) => {
             let sin6 = libc::sockaddr_in6 {
@@ -76,13 +41,54 @@ pub fn set_tcp_md5_signature(socket_fd: i32, peer_addr: &SocketAddr, password: &
             unsafe {
                 std::ptr::copy_nonoverlapping(
                     &sin6 as *const _ as *const u8,
-                    &mut md5sig.tcpm_addr as *mut _ as *mut u8,
+                    &mut storage as *mut _ as *mut u8,
                     std::mem::size_of::<libc::sockaddr_in6>(),
                 );
             }
-            md5sig.tcpm_addr.ss_family = AF_INET6 as u16;
         }
     }
+    storage
+}
+
+/// Set TCP-MD5 signature on a socket for BGP authentication (RFC 2385)
+///
+/// # Safety
+/// This uses libc directly and is marked unsafe due to raw pointer operations.
+#[cfg(target_os = "linux")]
+pub fn set_tcp_md5_signature(socket_fd: i32, peer_addr: &SocketAddr, password: &str) -> Result<()> {
+    use libc::{setsockopt, socklen_t, IPPROTO_TCP, TCP_MD5SIG};
+    use std::os::raw::c_void;
+
+    // TCP_MD5SIG requires a tcp_md5sig struct
+    // struct tcp_md5sig {
+    //     struct sockaddr_storage tcpm_addr;
+    //     __u8 tcpm_flags;
+    //     __u8 tcpm_prefixlen;
+    //     __u16 tcpm_keylen;
+    //     __u32 tcpm_ifindex;
+    //     __u8 tcpm_key[TCP_MD5SIG_MAXKEYLEN];
+    // };
+
+    const TCP_MD5SIG_MAXKEYLEN: usize = 80;
+
+    #[repr(C)]
+    struct TcpMd5Sig {
+        tcpm_addr: libc::sockaddr_storage,
+        tcpm_flags: u8,
+        tcpm_prefixlen: u8,
+        tcpm_keylen: u16,
+        tcpm_ifindex: u32,
+        tcpm_key: [u8; TCP_MD5SIG_MAXKEYLEN],
+    }
+
+    let mut md5sig = TcpMd5Sig {
+        tcpm_addr: sockaddr_storage(peer_addr),
+        tcpm_flags: 0,
+        tcpm_prefixlen: 0,
+        tcpm_keylen: 0,
+        tcpm_ifindex: 0,
+        tcpm_key: [0; TCP_MD5SIG_MAXKEYLEN],
+    };
 
     // Set the password
     let password_bytes = password.as_bytes();
@@ -120,26 +126,212 @@ pub fn set_tcp_md5_signature(
     anyhow::bail!("TCP-MD5 authentication is only supported on Linux (RFC 2385)")
 }
 
-/// Extension trait to set TCP-MD5 on tokio TcpSocket
-pub trait TcpSocketExt {
-    fn set_md5_signature(&self, peer_addr: &SocketAddr, password: &str) -> Result<()>;
+/// One TCP-AO master key and the KeyID used as both its SendID and RecvID.
+#[derive(Debug, Clone, Copy)]
+pub struct AoKey<'a> {
+    pub id: u8,
+    pub key: &'a str,
 }
 
-impl TcpSocketExt for tokio::net::TcpSocket {
-    fn set_md5_signature(&self, peer_addr: &SocketAddr, password: &str) -> Result<()> {
-        let fd = self.as_raw_fd();
-        set_tcp_md5_signature(fd, peer_addr, password)
+/// The session keys to put on a socket before it connects or listens.
+#[derive(Debug, Clone)]
+pub enum TcpAuth<'a> {
+    Md5(&'a str),
+    /// Every key is installed; segments are sent with the first one and the
+    /// peer is asked to send with the last one.
+    Ao(Vec<AoKey<'a>>),
+}
+
+impl TcpAuth<'_> {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            TcpAuth::Md5(_) => "TCP-MD5",
+            TcpAuth::Ao(_) => "TCP-AO",
+        }
     }
 }
 
-/// Extension trait to set TCP-MD5 on tokio TcpStream  
-pub trait TcpStreamExt {
-    fn set_md5_signature(&self, peer_addr: &SocketAddr, password: &str) -> Result<()>;
+/// HMAC-SHA-1-96, the mandatory TCP-AO MAC (RFC 5926).
+#[cfg(target_os = "linux")]
+const TCP_AO_ALG: &[u8] = b"hmac(sha1)";
+
+#[cfg(target_os = "linux")]
+const TCP_AO_ADD_KEY: libc::c_int = 38;
+#[cfg(target_os = "linux")]
+const TCP_AO_DEL_KEY: libc::c_int = 39;
+#[cfg(target_os = "linux")]
+const TCP_AO_MAXKEYLEN: usize = 80;
+
+// `set_current:1, set_rnext:1` bitfields of the TCP-AO commands, which
+// start at the low bit on the little-endian targets focl runs on.
+const TCP_AO_SET_CURRENT: u32 = 1;
+const TCP_AO_SET_RNEXT: u32 = 1 << 1;
+
+/// struct tcp_ao_add from linux/tcp.h.
+#[cfg(target_os = "linux")]
+#[repr(C, align(8))]
+struct TcpAoAdd {
+    addr: libc::sockaddr_storage,
+    alg_name: [u8; 64],
+    ifindex: i32,
+    flags: u32,
+    reserved2: u16,
+    prefix: u8,
+    sndid: u8,
+    rcvid: u8,
+    maclen: u8,
+    keyflags: u8,
+    keylen: u8,
+    key: [u8; TCP_AO_MAXKEYLEN],
+}
+
+/// struct tcp_ao_del from linux/tcp.h.
+#[cfg(target_os = "linux")]
+#[repr(C, align(8))]
+struct TcpAoDel {
+    addr: libc::sockaddr_storage,
+    ifindex: i32,
+    flags: u32,
+    reserved2: u16,
+    prefix: u8,
+    sndid: u8,
+    rcvid: u8,
+    current_key: u8,
+    rnext: u8,
+    keyflags: u8,
 }
 
-impl TcpStreamExt for tokio::net::TcpStream {
-    fn set_md5_signature(&self, peer_addr: &SocketAddr, password: &str) -> Result<()> {
+/// Add `key` for `peer_addr` to a socket (RFC 5925). The kernel keeps every
+/// added key and accepts segments signed with any of them. `flags` picks
+/// the key as Current_key and/or RNext_key.
+#[cfg(target_os = "linux")]
+pub fn add_tcp_ao_key(
+    socket_fd: i32,
+    peer_addr: &SocketAddr,
+    key: AoKey<'_>,
+    flags: u32,
+) -> Result<()> {
+    let bytes = key.key.as_bytes();
+    if bytes.len() > TCP_AO_MAXKEYLEN {
+        anyhow::bail!("TCP-AO key too long (max {} bytes)", TCP_AO_MAXKEYLEN);
+    }
+    let mut cmd = TcpAoAdd {
+        // TCP-AO matches on the address only; the port must be zero.
+        addr: sockaddr_storage(&SocketAddr::new(peer_addr.ip(), 0)),
+        alg_name: [0; 64],
+        ifindex: 0,
+        flags,
+        reserved2: 0,
+        prefix: if peer_addr.is_ipv4() { 32 } else { 128 },
+        sndid: key.id,
+        rcvid: key.id,
+        maclen: 0,
+        keyflags: 0,
+        keylen: bytes.len() as u8,
+        key: [0; TCP_AO_MAXKEYLEN],
+    };
+    cmd.alg_name[..TCP_AO_ALG.len()].copy_from_slice(TCP_AO_ALG);
+    cmd.key[..bytes.len()].copy_from_slice(bytes);
+    setsockopt_tcp(socket_fd, TCP_AO_ADD_KEY, &cmd).context("failed to add TCP-AO key")
+}
+
+/// Remove KeyID `id` for `peer_addr` from a socket, moving Current_key and
+/// RNext_key to `next` first, since the kernel refuses to drop a key in use.
+#[cfg(target_os = "linux")]
+pub fn del_tcp_ao_key(socket_fd: i32, peer_addr: &SocketAddr, id: u8, next: u8) -> Result<()> {
+    let cmd = TcpAoDel {
+        addr: sockaddr_storage(&SocketAddr::new(peer_addr.ip(), 0)),
+        ifindex: 0,
+        flags: TCP_AO_SET_CURRENT | TCP_AO_SET_RNEXT,
+        reserved2: 0,
+        prefix: if peer_addr.is_ipv4() { 32 } else { 128 },
+        sndid: id,
+        rcvid: id,
+        current_key: next,
+        rnext: next,
+        keyflags: 0,
+    };
+    setsockopt_tcp(socket_fd, TCP_AO_DEL_KEY, &cmd).context("failed to remove TCP-AO key")
+}
+
+#[cfg(target_os = "linux")]
+fn setsockopt_tcp<T>(socket_fd: i32, name: libc::c_int, value: &T) -> std::io::Result<()> {
+    let ret = unsafe {
+        libc::setsockopt(
+            socket_fd,
+            libc::IPPROTO_TCP,
+            name,
+            value as *const T as *const libc::c_void,
+            std::mem::size_of::<T>() as libc::socklen_t,
+        )
+    };
+    if ret < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn add_tcp_ao_key(
+    _socket_fd: i32,
+    _peer_addr: &SocketAddr,
+    _key: AoKey<'_>,
+    _flags: u32,
+) -> Result<()> {
+    anyhow::bail!("TCP-AO authentication is only supported on Linux (RFC 5925)")
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn del_tcp_ao_key(_socket_fd: i32, _peer_addr: &SocketAddr, _id: u8, _next: u8) -> Result<()> {
+    anyhow::bail!("TCP-AO authentication is only supported on Linux (RFC 5925)")
+}
+
+/// Set TCP-MD5 or TCP-AO keys on a tokio socket, listener, or stream.
+pub trait TcpAuthExt: AsRawFd {
+    fn set_tcp_auth(&self, peer_addr: &SocketAddr, auth: &TcpAuth<'_>) -> Result<()> {
         let fd = self.as_raw_fd();
-        set_tcp_md5_signature(fd, peer_addr, password)
+        match auth {
+            TcpAuth::Md5(password) => set_tcp_md5_signature(fd, peer_addr, password),
+            TcpAuth::Ao(keys) => {
+                let last = keys.len().saturating_sub(1);
+                for (i, key) in keys.iter().enumerate() {
+                    let mut flags = 0;
+                    if i == 0 {
+                        flags |= TCP_AO_SET_CURRENT;
+                    }
+                    if i == last {
+                        flags |= TCP_AO_SET_RNEXT;
+                    }
+                    add_tcp_ao_key(fd, peer_addr, *key, flags)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Add the next rotation key to a live session and ask the peer to
+    /// switch to it.
+    fn add_next_tcp_ao_key(&self, peer_addr: &SocketAddr, key: AoKey<'_>) -> Result<()> {
+        add_tcp_ao_key(self.as_raw_fd(), peer_addr, key, TCP_AO_SET_RNEXT)
+    }
+
+    /// Drop the key `id` from a live session once the rotation window
+    /// closes, sending with `next` from then on.
+    fn retire_tcp_ao_key(&self, peer_addr: &SocketAddr, id: u8, next: u8) -> Result<()> {
+        del_tcp_ao_key(self.as_raw_fd(), peer_addr, id, next)
+    }
+}
+
+impl<T: AsRawFd> TcpAuthExt for T {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn tcp_ao_commands_match_the_kernel_layout() {
+        assert_eq!(std::mem::size_of::<TcpAoAdd>(), 288);
+        assert_eq!(std::mem::size_of::<TcpAoDel>(), 144);
     }
 }
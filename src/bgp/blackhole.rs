@@ -0,0 +1,197 @@
+//! Remotely triggered blackholing: `blackhole_add` originates a prefix with
+//! the BLACKHOLE community (RFC 7999) and the configured discard next hop
+//! toward the `[blackhole].peers` upstreams, optionally expiring it.
+
+use std::net::IpAddr;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use bgpkit_parser::models::{Asn, AttributeValue, Community, Origin};
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+use tokio::task::JoinHandle;
+
+use super::mrt::RoutePath;
+use super::{BgpService, PrefixEntry};
+
+/// The well-known BLACKHOLE community, 65535:666.
+const BLACKHOLE: Community = Community::Custom(Asn::new_16bit(65535), 666);
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Blackhole {
+    pub prefix: IpNet,
+    pub next_hop: IpAddr,
+    /// Peers the blackhole is announced to; empty means every peer.
+    pub peers: Vec<String>,
+    pub added_at: i64,
+    pub expires_at: Option<i64>,
+}
+
+#[derive(Debug)]
+pub(super) struct ActiveBlackhole {
+    info: Blackhole,
+    expiry: Option<JoinHandle<()>>,
+}
+
+impl BgpService {
+    /// Blackhole `prefix`, replacing an existing blackhole for it. Without
+    /// `expires_secs` the `[blackhole].expiry_secs` default applies; 0 never
+    /// expires.
+    pub fn blackhole_add(&self, prefix: IpNet, expires_secs: Option<u64>) -> Result<Blackhole> {
+        let cfg = &self.inner.blackhole;
+        let prefix = prefix.trunc();
+        let (next_hop, min_len) = match prefix {
+            IpNet::V4(_) => (cfg.next_hop.as_deref(), cfg.min_prefix_len_v4),
+            IpNet::V6(_) => (cfg.next_hop_v6.as_deref(), cfg.min_prefix_len_v6),
+        };
+        if prefix.prefix_len() < min_len {
+            bail!("refusing to blackhole {prefix}: shorter than /{min_len}");
+        }
+        let next_hop = next_hop
+            .context("no blackhole next hop configured for this address family")?
+            .parse::<IpAddr>()?;
+
+        let added_at = chrono::Utc::now().timestamp();
+        let expires_secs = expires_secs.unwrap_or(cfg.expiry_secs);
+        let expires_at = (expires_secs > 0).then(|| added_at + expires_secs as i64);
+        let info = Blackhole {
+            prefix,
+            next_hop,
+            peers: cfg.peers.clone(),
+            added_at,
+            expires_at,
+        };
+
+        self.originate(PrefixEntry {
+            network: prefix,
+            next_hop: Some(next_hop),
+            path: Some(Arc::new(RoutePath {
+                origin: Origin::IGP,
+                as_path: vec![],
+                attributes: vec![AttributeValue::Communities(vec![
+                    BLACKHOLE,
+                    Community::NoExport,
+                ])],
+            })),
+            peers: self.blackhole_scope(),
+        });
+
+        let expiry = expires_at.map(|_| {
+            let service = self.clone();
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_secs(expires_secs)).await;
+                tracing::info!(%prefix, "blackhole expired");
+                let _ = service.blackhole_remove(prefix);
+            })
+        });
+        let replaced = self
+            .inner
+            .blackholes
+            .lock()
+            .expect("blackholes lock poisoned")
+            .insert(
+                prefix,
+                ActiveBlackhole {
+                    info: info.clone(),
+                    expiry,
+                },
+            );
+        if let Some(task) = replaced.and_then(|old| old.expiry) {
+            task.abort();
+        }
+        tracing::info!(%prefix, %next_hop, ?expires_at, "blackhole added");
+        Ok(info)
+    }
+
+    /// Withdraw the blackhole for `prefix`.
+    pub fn blackhole_remove(&self, prefix: IpNet) -> Result<()> {
+        let prefix = prefix.trunc();
+        let removed = self
+            .inner
+            .blackholes
+            .lock()
+            .expect("blackholes lock poisoned")
+            .remove(&prefix)
+            .ok_or_else(|| anyhow!("no blackhole for {prefix}"))?;
+        if let Some(task) = removed.expiry {
+            // A no-op when called from the expiry task itself, which is
+            // already finishing.
+            task.abort();
+        }
+        self.withdraw_entry(prefix, &self.blackhole_scope());
+        tracing::info!(%prefix, "blackhole removed");
+        Ok(())
+    }
+
+    pub fn blackhole_list(&self) -> Vec<Blackhole> {
+        self.inner
+            .blackholes
+            .lock()
+            .expect("blackholes lock poisoned")
+            .values()
+            .map(|active| active.info.clone())
+            .collect()
+    }
+
+    fn blackhole_scope(&self) -> Option<Arc<[String]>> {
+        let peers = &self.inner.blackhole.peers;
+        (!peers.is_empty()).then(|| peers.as_slice().into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::FoclConfig;
+    use crate::types::EventBus;
+
+    #[tokio::test]
+    async fn adds_expires_and_removes_blackholes() {
+        let cfg: FoclConfig = toml::from_str(
+            r#"
+[global]
+asn = 65001
+router_id = "192.0.2.1"
+
+[blackhole]
+next_hop = "192.0.2.66"
+"#,
+        )
+        .unwrap();
+        let bgp = BgpService::new(&cfg, EventBus::default(), None)
+            .await
+            .unwrap();
+        let prefix: IpNet = "203.0.113.7/32".parse().unwrap();
+
+        let added = bgp.blackhole_add(prefix, Some(0)).unwrap();
+        assert_eq!(added.next_hop, "192.0.2.66".parse::<IpAddr>().unwrap());
+        assert_eq!(added.expires_at, None);
+        let entry = bgp.inner.prefixes.read().unwrap()[0].clone();
+        assert_eq!(entry.network, prefix);
+        assert_eq!(
+            entry.path.unwrap().attributes,
+            [AttributeValue::Communities(vec![
+                BLACKHOLE,
+                Community::NoExport
+            ])]
+        );
+
+        assert!(bgp
+            .blackhole_add("203.0.0.0/16".parse().unwrap(), None)
+            .is_err());
+        assert!(bgp
+            .blackhole_add("2001:db8::1/128".parse().unwrap(), None)
+            .is_err());
+
+        bgp.blackhole_remove(prefix).unwrap();
+        assert!(bgp.blackhole_list().is_empty());
+        assert!(bgp.inner.prefixes.read().unwrap().is_empty());
+        assert!(bgp.blackhole_remove(prefix).is_err());
+
+        bgp.blackhole_add(prefix, Some(1)).unwrap();
+        assert_eq!(bgp.blackhole_list().len(), 1);
+        tokio::time::sleep(Duration::from_millis(1200)).await;
+        assert!(bgp.blackhole_list().is_empty());
+    }
+}
@@ -0,0 +1,184 @@
+//! Capabilities (RFC 5492) advertised in our OPEN and negotiated with the
+//! peer's: multiprotocol extensions for IPv4 and IPv6 unicast (RFC 4760),
+//! four-octet AS numbers (RFC 6793), and route refresh (RFC 2918).
+
+use std::net::Ipv4Addr;
+
+use bgpkit_parser::models::capabilities::BgpCapabilityType;
+use bgpkit_parser::models::{BgpOpenMessage, CapabilityValue, ParamValue};
+use serde::{Deserialize, Serialize};
+
+use crate::config::PeerConfig;
+
+const MSG_OPEN: u8 = 1;
+const PARAM_CAPABILITIES: u8 = 2;
+const CAP_MULTIPROTOCOL: u8 = 1;
+const CAP_ROUTE_REFRESH: u8 = 2;
+const CAP_FOUR_OCTET_AS: u8 = 65;
+/// Pre-standard route refresh, still sent by some routers.
+const CAP_ROUTE_REFRESH_CISCO: u8 = 128;
+/// AS_TRANS (RFC 6793), My AS in the OPEN of a four-octet ASN.
+const AS_TRANS: u16 = 23456;
+
+/// Address families (AFI/SAFI pairs) both sides can exchange.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AfiSafi {
+    Ipv4Unicast,
+    Ipv6Unicast,
+}
+
+impl AfiSafi {
+    const ALL: [Self; 2] = [Self::Ipv4Unicast, Self::Ipv6Unicast];
+
+    /// AFI and SAFI.
+    pub fn afi_safi(self) -> (u16, u8) {
+        match self {
+            Self::Ipv4Unicast => (1, 1),
+            Self::Ipv6Unicast => (2, 1),
+        }
+    }
+}
+
+/// What a session negotiated, reported by `peer_show` while it is up.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NegotiatedCapabilities {
+    pub address_families: Vec<AfiSafi>,
+    pub four_octet_as: bool,
+    pub route_refresh: bool,
+    /// Codes of every capability in the peer's OPEN, known to us or not.
+    pub peer_capabilities: Vec<u8>,
+}
+
+impl NegotiatedCapabilities {
+    /// Negotiate our capabilities for `peer` with those in its `open`.
+    pub(super) fn negotiate(peer: &PeerConfig, open: &BgpOpenMessage) -> Self {
+        let capabilities: Vec<_> = open
+            .opt_params
+            .iter()
+            .filter_map(|param| match &param.param_value {
+                ParamValue::Capacities(caps) => Some(caps.iter()),
+                ParamValue::Raw(_) => None,
+            })
+            .flatten()
+            .collect();
+        let offered: Vec<(u16, u8)> = capabilities
+            .iter()
+            .filter_map(|cap| match &cap.value {
+                CapabilityValue::MultiprotocolExtensions(mp) => {
+                    Some((mp.afi as u16, mp.safi as u8))
+                }
+                _ => None,
+            })
+            .collect();
+        // A peer without multiprotocol extensions speaks IPv4 unicast only.
+        let address_families = AfiSafi::ALL
+            .into_iter()
+            .filter(|family| {
+                offered.contains(&family.afi_safi())
+                    || (offered.is_empty() && *family == AfiSafi::Ipv4Unicast)
+            })
+            .collect();
+        let has = |ty: BgpCapabilityType| capabilities.iter().any(|cap| cap.ty == ty);
+        Self {
+            address_families,
+            four_octet_as: has(BgpCapabilityType::SUPPORT_FOR_4_OCTET_AS_NUMBER_CAPABILITY),
+            route_refresh: peer.route_refresh
+                && (has(BgpCapabilityType::ROUTE_REFRESH_CAPABILITY_FOR_BGP_4)
+                    || has(BgpCapabilityType::Unknown(CAP_ROUTE_REFRESH_CISCO))),
+            peer_capabilities: capabilities.iter().map(|cap| u8::from(cap.ty)).collect(),
+        }
+    }
+}
+
+/// Our OPEN, header included, advertising every capability we support for
+/// `peer`.
+pub(super) fn encode_open(
+    peer: &PeerConfig,
+    local_as: u32,
+    hold_time: u16,
+    router_id: Ipv4Addr,
+) -> Vec<u8> {
+    let mut capabilities = Vec::new();
+    for family in AfiSafi::ALL {
+        let (afi, safi) = family.afi_safi();
+        capabilities.extend_from_slice(&[CAP_MULTIPROTOCOL, 4]);
+        capabilities.extend_from_slice(&afi.to_be_bytes());
+        capabilities.extend_from_slice(&[0, safi]);
+    }
+    if peer.route_refresh {
+        capabilities.extend_from_slice(&[CAP_ROUTE_REFRESH, 0]);
+    }
+    capabilities.extend_from_slice(&[CAP_FOUR_OCTET_AS, 4]);
+    capabilities.extend_from_slice(&local_as.to_be_bytes());
+
+    let my_as = u16::try_from(local_as).unwrap_or(AS_TRANS);
+    let mut open = vec![0xff; 16];
+    // Length, filled in below.
+    open.extend_from_slice(&[0, 0, MSG_OPEN, 4]);
+    open.extend_from_slice(&my_as.to_be_bytes());
+    open.extend_from_slice(&hold_time.to_be_bytes());
+    open.extend_from_slice(&router_id.octets());
+    open.push(capabilities.len() as u8 + 2);
+    open.extend_from_slice(&[PARAM_CAPABILITIES, capabilities.len() as u8]);
+    open.extend_from_slice(&capabilities);
+    let len = open.len() as u16;
+    open[16..18].copy_from_slice(&len.to_be_bytes());
+    open
+}
+
+/// Whether a received frame is a ROUTE-REFRESH, which bgpkit does not parse.
+pub(super) fn is_route_refresh(frame: &[u8]) -> bool {
+    frame.get(18) == Some(&5)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bgpkit_parser::bgp::parse_bgp_message;
+    use bgpkit_parser::models::{AsnLength, BgpMessage};
+
+    fn parse_open(frame: Vec<u8>) -> BgpOpenMessage {
+        match parse_bgp_message(&mut frame.into(), false, &AsnLength::Bits32).unwrap() {
+            BgpMessage::Open(open) => open,
+            other => panic!("not an OPEN: {other:?}"),
+        }
+    }
+
+    #[test]
+    fn advertises_and_negotiates_capabilities() {
+        let peer: PeerConfig =
+            toml::from_str("address = \"192.0.2.2\"\nremote_as = 65002").unwrap();
+        let router_id = Ipv4Addr::new(192, 0, 2, 1);
+
+        let ours = parse_open(encode_open(&peer, 4_200_000_001, 90, router_id));
+        assert_eq!(ours.asn.to_u32(), u32::from(AS_TRANS));
+        assert_eq!(ours.hold_time, 90);
+        assert_eq!(super::super::open::peer_asn(&ours), 4_200_000_001);
+
+        // Talking to ourselves negotiates everything.
+        let both = NegotiatedCapabilities::negotiate(&peer, &ours);
+        assert_eq!(
+            both.address_families,
+            [AfiSafi::Ipv4Unicast, AfiSafi::Ipv6Unicast]
+        );
+        assert!(both.four_octet_as && both.route_refresh);
+        assert_eq!(both.peer_capabilities, [1, 1, 2, 65]);
+
+        // A bare OPEN is IPv4 unicast with two-octet ASNs.
+        let no_refresh: PeerConfig =
+            toml::from_str("address = \"192.0.2.2\"\nremote_as = 65002\nroute_refresh = false")
+                .unwrap();
+        let bare = parse_open(encode_open(&no_refresh, 65002, 90, router_id));
+        let mut bare_caps = bare.clone();
+        bare_caps.opt_params.clear();
+        let plain = NegotiatedCapabilities::negotiate(&peer, &bare_caps);
+        assert_eq!(plain.address_families, [AfiSafi::Ipv4Unicast]);
+        assert!(!plain.four_octet_as && !plain.route_refresh);
+        // We only use route refresh when we advertised it too.
+        assert!(!NegotiatedCapabilities::negotiate(&no_refresh, &ours).route_refresh);
+        assert!(!NegotiatedCapabilities::negotiate(&peer, &bare).route_refresh);
+
+        assert!(is_route_refresh(&super::super::encode_route_refresh(1, 1)));
+    }
+}
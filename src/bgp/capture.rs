@@ -0,0 +1,389 @@
+//! Per-peer pcap capture (`capture_pcap`). Each BGP message is written as one
+//! synthesized TCP/IP packet so Wireshark decodes the session as if it had
+//! been captured on the wire, without needing capture privileges.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::net::{IpAddr, SocketAddr};
+use std::ops::{Deref, DerefMut};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bytes::{Bytes, BytesMut};
+use tokio::io::AsyncWriteExt;
+use tokio::net::TcpStream;
+
+use super::message_log::MessageLog;
+use super::mirror::Mirror;
+use super::timeline::SessionTimeline;
+use crate::config::PeerConfig;
+
+const PCAP_MAGIC: u32 = 0xa1b2_c3d4;
+const PCAP_HEADER_LEN: u64 = 24;
+const PCAP_SNAPLEN: u32 = 262_144;
+/// LINKTYPE_RAW: packets start at the IPv4 or IPv6 header.
+const LINKTYPE_RAW: u32 = 101;
+
+const TCP_SYN: u8 = 0x02;
+const TCP_PSH: u8 = 0x08;
+const TCP_ACK: u8 = 0x10;
+
+/// Incoming frames are split off this much buffer before it is reallocated.
+const READ_BUFFER_CAPACITY: usize = 64 * 1024;
+
+/// The TCP stream of a BGP session, mirrored into the peer's capture file,
+/// message log, and `mirror_to` endpoints when configured, with its
+/// NOTIFICATIONs noted in the peer's session timeline. Derefs to the underlying
+/// socket for everything that is not message I/O.
+pub(super) struct SessionStream {
+    tcp: TcpStream,
+    read_buf: BytesMut,
+    capture: Option<PcapCapture>,
+    log: Arc<MessageLog>,
+    timeline: Arc<SessionTimeline>,
+    mirror: Option<Mirror>,
+}
+
+impl SessionStream {
+    /// `active` when we opened the connection.
+    pub fn new(
+        tcp: TcpStream,
+        peer: &PeerConfig,
+        log: Arc<MessageLog>,
+        timeline: Arc<SessionTimeline>,
+        active: bool,
+    ) -> Self {
+        let capture = peer
+            .capture_pcap
+            .as_ref()
+            .and_then(|path| match (tcp.local_addr(), tcp.peer_addr()) {
+                (Ok(local), Ok(remote)) => {
+                    let max_bytes = peer.capture_pcap_max_mb.saturating_mul(1024 * 1024);
+                    let opened = PcapCapture::open(
+                        path,
+                        max_bytes,
+                        peer.capture_pcap_keep,
+                        local,
+                        remote,
+                        active,
+                    );
+                    opened
+                        .inspect_err(|err| {
+                            tracing::warn!(peer=%peer.address, path=%path.display(), error=%err, "failed opening pcap capture");
+                        })
+                        .ok()
+                }
+                _ => None,
+            });
+        let mirror =
+            (!peer.mirror_to.is_empty()).then(|| Mirror::start(&peer.address, &peer.mirror_to));
+        Self {
+            tcp,
+            read_buf: BytesMut::with_capacity(READ_BUFFER_CAPACITY),
+            capture,
+            log,
+            timeline,
+            mirror,
+        }
+    }
+
+    /// The socket and the buffer incoming frames are read into.
+    pub fn reader(&mut self) -> (&mut TcpStream, &mut BytesMut) {
+        (&mut self.tcp, &mut self.read_buf)
+    }
+
+    /// Write one complete BGP message.
+    pub async fn write_frame(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.tcp.write_all(bytes).await?;
+        self.record(true, bytes);
+        Ok(())
+    }
+
+    /// Record a BGP message read off the socket.
+    pub fn record_incoming(&mut self, frame: &Bytes) {
+        self.record(false, frame);
+        if let Some(mirror) = &mut self.mirror {
+            mirror.send(frame);
+        }
+    }
+
+    /// Capture errors never affect the session; the capture just stops.
+    fn record(&mut self, outbound: bool, bytes: &[u8]) {
+        self.log.record(outbound, bytes);
+        self.timeline.record_message(outbound, bytes);
+        let Some(capture) = &mut self.capture else {
+            return;
+        };
+        if let Err(err) = capture.data(outbound, bytes) {
+            tracing::warn!(path=%capture.path.display(), error=%err, "pcap capture failed, disabling it");
+            self.capture = None;
+        }
+    }
+}
+
+impl Deref for SessionStream {
+    type Target = TcpStream;
+
+    fn deref(&self) -> &TcpStream {
+        &self.tcp
+    }
+}
+
+impl DerefMut for SessionStream {
+    fn deref_mut(&mut self) -> &mut TcpStream {
+        &mut self.tcp
+    }
+}
+
+/// A pcap file that rotates to `<path>.1`, `<path>.2`, ... once it would
+/// exceed `max_bytes`, keeping at most `keep` rotated files.
+pub(super) struct PcapCapture {
+    path: PathBuf,
+    file: File,
+    written: u64,
+    max_bytes: u64,
+    keep: u32,
+    local: SocketAddr,
+    remote: SocketAddr,
+    local_seq: u32,
+    remote_seq: u32,
+}
+
+impl PcapCapture {
+    /// Open (or append to) the capture and record a synthesized three-way
+    /// handshake, initiated by us when `active`.
+    pub fn open(
+        path: &Path,
+        max_bytes: u64,
+        keep: u32,
+        local: SocketAddr,
+        remote: SocketAddr,
+        active: bool,
+    ) -> io::Result<Self> {
+        if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+            fs::create_dir_all(parent)?;
+        }
+        let (file, written) = open_pcap(path)?;
+        let mut capture = Self {
+            path: path.to_path_buf(),
+            file,
+            written,
+            max_bytes,
+            keep,
+            local,
+            remote,
+            local_seq: 0,
+            remote_seq: 0,
+        };
+        capture.packet(active, TCP_SYN, &[])?;
+        capture.packet(!active, TCP_SYN | TCP_ACK, &[])?;
+        capture.local_seq = 1;
+        capture.remote_seq = 1;
+        capture.packet(active, TCP_ACK, &[])?;
+        Ok(capture)
+    }
+
+    pub fn data(&mut self, outbound: bool, payload: &[u8]) -> io::Result<()> {
+        self.packet(outbound, TCP_PSH | TCP_ACK, payload)?;
+        let seq = if outbound {
+            &mut self.local_seq
+        } else {
+            &mut self.remote_seq
+        };
+        *seq = seq.wrapping_add(payload.len() as u32);
+        Ok(())
+    }
+
+    fn packet(&mut self, outbound: bool, flags: u8, payload: &[u8]) -> io::Result<()> {
+        let (src, dst, seq, ack) = if outbound {
+            (self.local, self.remote, self.local_seq, self.remote_seq)
+        } else {
+            (self.remote, self.local, self.remote_seq, self.local_seq)
+        };
+        let ack = if flags & TCP_ACK != 0 { ack } else { 0 };
+        let packet = ip_packet(src, dst, seq, ack, flags, payload);
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        let mut record = Vec::with_capacity(16 + packet.len());
+        record.extend_from_slice(&(now.as_secs() as u32).to_le_bytes());
+        record.extend_from_slice(&now.subsec_micros().to_le_bytes());
+        record.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+        record.extend_from_slice(&(packet.len() as u32).to_le_bytes());
+        record.extend_from_slice(&packet);
+
+        if self.written > PCAP_HEADER_LEN && self.written + record.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+        self.file.write_all(&record)?;
+        self.written += record.len() as u64;
+        Ok(())
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        let rotated = |n: u32| PathBuf::from(format!("{}.{n}", self.path.display()));
+        if self.keep == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            for n in (1..self.keep).rev() {
+                match fs::rename(rotated(n), rotated(n + 1)) {
+                    Err(err) if err.kind() != io::ErrorKind::NotFound => return Err(err),
+                    _ => {}
+                }
+            }
+            fs::rename(&self.path, rotated(1))?;
+        }
+        (self.file, self.written) = open_pcap(&self.path)?;
+        Ok(())
+    }
+}
+
+/// Open `path` for appending, writing the global header if it is new.
+fn open_pcap(path: &Path) -> io::Result<(File, u64)> {
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    let mut written = file.metadata()?.len();
+    if written == 0 {
+        let mut header = Vec::with_capacity(PCAP_HEADER_LEN as usize);
+        header.extend_from_slice(&PCAP_MAGIC.to_le_bytes());
+        header.extend_from_slice(&2u16.to_le_bytes());
+        header.extend_from_slice(&4u16.to_le_bytes());
+        header.extend_from_slice(&0i32.to_le_bytes());
+        header.extend_from_slice(&0u32.to_le_bytes());
+        header.extend_from_slice(&PCAP_SNAPLEN.to_le_bytes());
+        header.extend_from_slice(&LINKTYPE_RAW.to_le_bytes());
+        file.write_all(&header)?;
+        written = PCAP_HEADER_LEN;
+    }
+    Ok((file, written))
+}
+
+fn ip_packet(
+    src: SocketAddr,
+    dst: SocketAddr,
+    seq: u32,
+    ack: u32,
+    flags: u8,
+    payload: &[u8],
+) -> Vec<u8> {
+    let tcp_len = 20 + payload.len();
+    let mut tcp = Vec::with_capacity(tcp_len);
+    tcp.extend_from_slice(&src.port().to_be_bytes());
+    tcp.extend_from_slice(&dst.port().to_be_bytes());
+    tcp.extend_from_slice(&seq.to_be_bytes());
+    tcp.extend_from_slice(&ack.to_be_bytes());
+    tcp.extend_from_slice(&[5 << 4, flags]);
+    tcp.extend_from_slice(&u16::MAX.to_be_bytes());
+    tcp.extend_from_slice(&[0, 0, 0, 0]);
+    tcp.extend_from_slice(payload);
+
+    let mut packet = Vec::with_capacity(40 + tcp_len);
+    let mut pseudo = Vec::with_capacity(40);
+    match (src.ip(), dst.ip()) {
+        (IpAddr::V4(s), IpAddr::V4(d)) => {
+            packet.extend_from_slice(&[0x45, 0]);
+            packet.extend_from_slice(&((20 + tcp_len) as u16).to_be_bytes());
+            packet.extend_from_slice(&[0, 0, 0x40, 0, 64, 6, 0, 0]);
+            packet.extend_from_slice(&s.octets());
+            packet.extend_from_slice(&d.octets());
+            let sum = checksum(&packet);
+            packet[10..12].copy_from_slice(&sum.to_be_bytes());
+
+            pseudo.extend_from_slice(&s.octets());
+            pseudo.extend_from_slice(&d.octets());
+            pseudo.extend_from_slice(&[0, 6]);
+            pseudo.extend_from_slice(&(tcp_len as u16).to_be_bytes());
+        }
+        (s, d) => {
+            let (s, d) = (to_v6(s), to_v6(d));
+            packet.extend_from_slice(&[0x60, 0, 0, 0]);
+            packet.extend_from_slice(&(tcp_len as u16).to_be_bytes());
+            packet.extend_from_slice(&[6, 64]);
+            packet.extend_from_slice(&s);
+            packet.extend_from_slice(&d);
+
+            pseudo.extend_from_slice(&s);
+            pseudo.extend_from_slice(&d);
+            pseudo.extend_from_slice(&(tcp_len as u32).to_be_bytes());
+            pseudo.extend_from_slice(&[0, 0, 0, 6]);
+        }
+    }
+    pseudo.extend_from_slice(&tcp);
+    let sum = checksum(&pseudo);
+    tcp[16..18].copy_from_slice(&sum.to_be_bytes());
+    packet.extend_from_slice(&tcp);
+    packet
+}
+
+fn to_v6(ip: IpAddr) -> [u8; 16] {
+    match ip {
+        IpAddr::V4(v4) => v4.to_ipv6_mapped().octets(),
+        IpAddr::V6(v6) => v6.octets(),
+    }
+}
+
+/// RFC 1071 Internet checksum.
+fn checksum(bytes: &[u8]) -> u16 {
+    let mut sum = bytes
+        .chunks(2)
+        .map(|c| u32::from(u16::from_be_bytes([c[0], c.get(1).copied().unwrap_or(0)])))
+        .sum::<u32>();
+    while sum > 0xffff {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Split a capture file into its packets.
+    fn packets(path: &Path) -> Vec<Vec<u8>> {
+        let bytes = fs::read(path).unwrap();
+        assert_eq!(&bytes[..4], &PCAP_MAGIC.to_le_bytes());
+        assert_eq!(&bytes[20..24], &LINKTYPE_RAW.to_le_bytes());
+        let mut out = Vec::new();
+        let mut rest = &bytes[24..];
+        while !rest.is_empty() {
+            let len = u32::from_le_bytes(rest[8..12].try_into().unwrap()) as usize;
+            out.push(rest[16..16 + len].to_vec());
+            rest = &rest[16 + len..];
+        }
+        out
+    }
+
+    #[test]
+    fn writes_tcp_stream_and_rotates() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("peer.pcap");
+        let local: SocketAddr = "192.0.2.1:40000".parse().unwrap();
+        let remote: SocketAddr = "192.0.2.2:179".parse().unwrap();
+
+        let mut capture = PcapCapture::open(&path, 400, 1, local, remote, true).unwrap();
+        let keepalive = [[0xffu8; 16].as_slice(), &[0, 19, 4]].concat();
+        capture.data(true, &keepalive).unwrap();
+        capture.data(false, &keepalive).unwrap();
+
+        let written = packets(&path);
+        assert_eq!(written.len(), 5);
+        let data = &written[3];
+        assert_eq!(checksum(&data[..20]), 0, "IPv4 header checksum");
+        assert_eq!(&data[12..16], &[192, 0, 2, 1]);
+        let tcp = &data[20..];
+        assert_eq!(u16::from_be_bytes([tcp[2], tcp[3]]), 179);
+        assert_eq!(u32::from_be_bytes(tcp[4..8].try_into().unwrap()), 1);
+        assert_eq!(tcp[13], TCP_PSH | TCP_ACK);
+        assert_eq!(&tcp[20..], keepalive.as_slice());
+        let reply = &written[4][20..];
+        assert_eq!(u32::from_be_bytes(reply[8..12].try_into().unwrap()), 20);
+
+        for _ in 0..4 {
+            capture.data(true, &keepalive).unwrap();
+        }
+        assert!(dir.path().join("peer.pcap.1").exists());
+        assert!(!dir.path().join("peer.pcap.2").exists());
+        assert!(fs::metadata(&path).unwrap().len() <= 400);
+    }
+}
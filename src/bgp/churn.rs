@@ -0,0 +1,158 @@
+//! `[churn]`: announcement and withdrawal counts per prefix in one-minute
+//! buckets, so `rib_churn` can rank unstable prefixes over any window up to
+//! `window_mins` without offline MRT processing.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use ipnet::IpNet;
+use serde::{Deserialize, Serialize};
+
+use crate::config::ChurnConfig;
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PrefixChurn {
+    pub prefix: IpNet,
+    pub announcements: u64,
+    pub withdrawals: u64,
+}
+
+impl PrefixChurn {
+    fn total(&self) -> u64 {
+        self.announcements + self.withdrawals
+    }
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// Unix minute.
+    minute: i64,
+    counts: HashMap<IpNet, (u32, u32)>,
+}
+
+#[derive(Debug)]
+pub(super) struct ChurnTracker {
+    window_mins: u32,
+    buckets: Mutex<VecDeque<Bucket>>,
+}
+
+impl ChurnTracker {
+    /// `None` when `[churn]` is disabled.
+    pub fn new(cfg: &ChurnConfig) -> Option<Self> {
+        cfg.enabled.then(|| Self {
+            window_mins: cfg.window_mins,
+            buckets: Mutex::new(VecDeque::new()),
+        })
+    }
+
+    pub fn window_mins(&self) -> u32 {
+        self.window_mins
+    }
+
+    /// Count one UPDATE received at Unix second `now`.
+    pub fn record_at(&self, announced: &[IpNet], withdrawn: &[IpNet], now: i64) {
+        if announced.is_empty() && withdrawn.is_empty() {
+            return;
+        }
+        let minute = now.div_euclid(60);
+        let mut buckets = self.lock();
+        if buckets.back().is_none_or(|b| b.minute != minute) {
+            buckets.push_back(Bucket {
+                minute,
+                counts: HashMap::new(),
+            });
+        }
+        while buckets
+            .front()
+            .is_some_and(|b| b.minute <= minute - i64::from(self.window_mins))
+        {
+            buckets.pop_front();
+        }
+        let counts = &mut buckets.back_mut().expect("bucket pushed above").counts;
+        for prefix in announced {
+            let entry = counts.entry(*prefix).or_default();
+            entry.0 = entry.0.saturating_add(1);
+        }
+        for prefix in withdrawn {
+            let entry = counts.entry(*prefix).or_default();
+            entry.1 = entry.1.saturating_add(1);
+        }
+    }
+
+    /// The `limit` prefixes with the most updates in the last `window_mins`
+    /// minutes (including the current one), busiest first.
+    pub fn top_at(&self, window_mins: u32, limit: usize, now: i64) -> Vec<PrefixChurn> {
+        self.top_by_at(window_mins, limit, now, PrefixChurn::total)
+    }
+
+    /// Like [`Self::top_at`], ranked by `key`.
+    pub fn top_by_at(
+        &self,
+        window_mins: u32,
+        limit: usize,
+        now: i64,
+        key: fn(&PrefixChurn) -> u64,
+    ) -> Vec<PrefixChurn> {
+        let since = now.div_euclid(60) - i64::from(window_mins.min(self.window_mins));
+        let mut totals: HashMap<IpNet, PrefixChurn> = HashMap::new();
+        for bucket in self.lock().iter().filter(|b| b.minute > since) {
+            for (prefix, (announcements, withdrawals)) in &bucket.counts {
+                let entry = totals.entry(*prefix).or_insert_with(|| PrefixChurn {
+                    prefix: *prefix,
+                    announcements: 0,
+                    withdrawals: 0,
+                });
+                entry.announcements += u64::from(*announcements);
+                entry.withdrawals += u64::from(*withdrawals);
+            }
+        }
+        let mut top = totals.into_values().collect::<Vec<_>>();
+        top.sort_by(|a, b| key(b).cmp(&key(a)).then_with(|| a.prefix.cmp(&b.prefix)));
+        top.truncate(limit);
+        top
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<Bucket>> {
+        self.buckets.lock().expect("churn buckets lock poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ranks_prefixes_within_the_requested_window() {
+        let tracker = ChurnTracker::new(&ChurnConfig {
+            enabled: true,
+            window_mins: 10,
+        })
+        .unwrap();
+        let a: IpNet = "203.0.113.0/24".parse().unwrap();
+        let b: IpNet = "198.51.100.0/24".parse().unwrap();
+        let start = 1_700_000_000 - 1_700_000_000 % 60;
+
+        tracker.record_at(&[a, b], &[], start);
+        tracker.record_at(&[], &[a], start + 30);
+        tracker.record_at(&[b], &[], start + 300);
+        tracker.record_at(&[b], &[], start + 310);
+
+        let top = tracker.top_at(10, 10, start + 320);
+        assert_eq!(top.len(), 2);
+        assert_eq!(top[0].prefix, b);
+        assert_eq!(top[0].announcements, 3);
+        assert_eq!(top[1].withdrawals, 1);
+
+        // Only the last minute: a's updates are older.
+        let top = tracker.top_at(1, 10, start + 320);
+        assert_eq!(top.len(), 1);
+        assert_eq!(top[0].announcements, 2);
+
+        // Buckets older than window_mins are dropped as time moves on.
+        tracker.record_at(&[a], &[], start + 900);
+        let top = tracker.top_at(60, 10, start + 900);
+        assert_eq!(top.len(), 1);
+        assert_eq!(top[0].prefix, a);
+        assert_eq!(top[0].announcements, 1);
+    }
+}
@@ -0,0 +1,209 @@
+//! Health-check gated announcements: `[prefixes.health_check]` keeps an
+//! anycast prefix announced only while the local service answers.
+
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use tokio::net::TcpStream;
+use tokio::process::Command;
+
+use super::{BgpService, PrefixEntry};
+use crate::config::{HealthCheckKind, PrefixHealthCheck};
+use crate::types::Event;
+
+/// Consecutive pass/fail counting so one flaky probe does not flap a route.
+#[derive(Debug)]
+struct Hysteresis {
+    healthy: bool,
+    streak: u32,
+    rise: u32,
+    fall: u32,
+}
+
+impl Hysteresis {
+    fn new(rise: u32, fall: u32) -> Self {
+        Self {
+            healthy: false,
+            streak: 0,
+            rise,
+            fall,
+        }
+    }
+
+    /// Record one probe result; returns the new state when it flips.
+    fn observe(&mut self, passed: bool) -> Option<bool> {
+        if passed == self.healthy {
+            self.streak = 0;
+            return None;
+        }
+        self.streak += 1;
+        let needed = if passed { self.rise } else { self.fall };
+        if self.streak < needed {
+            return None;
+        }
+        self.healthy = passed;
+        self.streak = 0;
+        Some(passed)
+    }
+}
+
+impl BgpService {
+    pub(super) fn spawn_health_checks(&self, groups: Vec<(Vec<PrefixEntry>, PrefixHealthCheck)>) {
+        let client = match reqwest::Client::builder().build() {
+            Ok(client) => client,
+            Err(err) => {
+                tracing::error!(error=%err, "failed building HTTP client for health checks");
+                return;
+            }
+        };
+        for (entries, check) in groups {
+            let service = self.clone();
+            let client = client.clone();
+            tokio::spawn(async move {
+                tokio::select! {
+                    _ = service.run_health_check(&client, &entries, &check) => {}
+                    _ = service.shutdown_requested() => {}
+                }
+            });
+        }
+    }
+
+    async fn run_health_check(
+        &self,
+        client: &reqwest::Client,
+        entries: &[PrefixEntry],
+        check: &PrefixHealthCheck,
+    ) {
+        let mut state = Hysteresis::new(check.rise, check.fall);
+        loop {
+            let passed = match probe(client, check).await {
+                Ok(()) => true,
+                Err(err) => {
+                    tracing::debug!(target=%check.target, error=%err, "health check failed");
+                    false
+                }
+            };
+            if let Some(healthy) = state.observe(passed) {
+                self.set_prefixes_healthy(entries, healthy, &check.target);
+            }
+            tokio::time::sleep(Duration::from_secs(check.interval_secs)).await;
+        }
+    }
+
+    fn set_prefixes_healthy(&self, entries: &[PrefixEntry], healthy: bool, target: &str) {
+        for entry in entries {
+            if healthy {
+                self.originate(entry.clone());
+            } else {
+                self.withdraw_entry(entry.network, &entry.peers);
+            }
+        }
+        let prefixes = entries
+            .iter()
+            .map(|entry| entry.network.to_string())
+            .collect::<Vec<_>>();
+        if healthy {
+            tracing::info!(%target, ?prefixes, "health check passing, announcing");
+        } else {
+            tracing::warn!(%target, ?prefixes, "health check failing, withdrawing");
+        }
+        self.inner.events.publish(Event::PrefixHealthChanged {
+            prefixes,
+            target: target.to_string(),
+            healthy,
+        });
+    }
+}
+
+async fn probe(client: &reqwest::Client, check: &PrefixHealthCheck) -> Result<()> {
+    let timeout = Duration::from_secs(check.timeout_secs);
+    let target = check.target.as_str();
+    let probe = async {
+        match check.kind {
+            HealthCheckKind::Tcp => {
+                TcpStream::connect(target).await?;
+            }
+            HealthCheckKind::Http => {
+                client.get(target).send().await?.error_for_status()?;
+            }
+            HealthCheckKind::Icmp => {
+                let wait = check.timeout_secs.to_string();
+                exit_ok(Command::new("ping").args(["-c", "1", "-W", &wait, target])).await?;
+            }
+            HealthCheckKind::Script => exit_ok(Command::new("sh").args(["-c", target])).await?,
+        }
+        Ok(())
+    };
+    tokio::time::timeout(timeout, probe)
+        .await
+        .context("timed out")?
+}
+
+async fn exit_ok(command: &mut Command) -> Result<()> {
+    let status = command
+        .stdin(std::process::Stdio::null())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .kill_on_drop(true)
+        .status()
+        .await?;
+    if !status.success() {
+        bail!("exited with {status}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn flips_only_after_rise_or_fall_streaks() {
+        let mut state = Hysteresis::new(2, 3);
+        assert_eq!(state.observe(true), None);
+        assert_eq!(state.observe(false), None);
+        assert_eq!(state.observe(true), None);
+        assert_eq!(state.observe(true), Some(true));
+        assert_eq!(state.observe(true), None);
+
+        assert_eq!(state.observe(false), None);
+        assert_eq!(state.observe(false), None);
+        assert_eq!(state.observe(true), None);
+        assert_eq!(state.observe(false), None);
+        assert_eq!(state.observe(false), None);
+        assert_eq!(state.observe(false), Some(false));
+    }
+
+    #[tokio::test]
+    async fn probes_tcp_and_script_targets() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let check = |kind, target: String| PrefixHealthCheck {
+            kind,
+            target,
+            interval_secs: 1,
+            timeout_secs: 1,
+            rise: 1,
+            fall: 1,
+        };
+        let client = reqwest::Client::new();
+        let open = listener.local_addr().unwrap().to_string();
+        assert!(probe(&client, &check(HealthCheckKind::Tcp, open.clone()))
+            .await
+            .is_ok());
+        drop(listener);
+        assert!(probe(&client, &check(HealthCheckKind::Tcp, open))
+            .await
+            .is_err());
+
+        assert!(
+            probe(&client, &check(HealthCheckKind::Script, "true".into()))
+                .await
+                .is_ok()
+        );
+        assert!(
+            probe(&client, &check(HealthCheckKind::Script, "exit 3".into()))
+                .await
+                .is_err()
+        );
+    }
+}
@@ -0,0 +1,109 @@
+//! `[hijack]`: self-monitoring of our own `[[prefixes]]`. A received route
+//! for one of them, or a more-specific of one, that another AS originates is
+//! a suspected hijack.
+
+use std::collections::HashSet;
+
+use ipnet::IpNet;
+
+use crate::config::HijackConfig;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct SuspectedHijack {
+    /// Our configured prefix.
+    pub prefix: IpNet,
+    /// The announced prefixes it covers.
+    pub announced: Vec<IpNet>,
+    pub origin: u32,
+}
+
+#[derive(Debug, Default)]
+pub(super) struct HijackDetector {
+    prefixes: Vec<IpNet>,
+    allowed_origins: HashSet<u32>,
+}
+
+impl HijackDetector {
+    /// Watches `prefixes` unless `[hijack]` is disabled; `local_as` may
+    /// always originate them.
+    pub fn new(cfg: &HijackConfig, prefixes: Vec<IpNet>, local_as: u32) -> Self {
+        if !cfg.enabled {
+            return Self::default();
+        }
+        let mut allowed_origins = cfg.allowed_origins.iter().copied().collect::<HashSet<_>>();
+        allowed_origins.insert(local_as);
+        Self {
+            prefixes,
+            allowed_origins,
+        }
+    }
+
+    /// Our prefixes covering `announced` when the route's origin, the last
+    /// ASN of `as_path`, is not one of ours. A route with an empty path was
+    /// originated by an iBGP peer and is never flagged.
+    pub fn check(&self, announced: &[IpNet], as_path: &[u32]) -> Vec<SuspectedHijack> {
+        let Some(&origin) = as_path.last() else {
+            return Vec::new();
+        };
+        if self.prefixes.is_empty() || self.allowed_origins.contains(&origin) {
+            return Vec::new();
+        }
+        self.prefixes
+            .iter()
+            .filter_map(|prefix| {
+                let covered = announced
+                    .iter()
+                    .filter(|candidate| prefix.contains(*candidate))
+                    .copied()
+                    .collect::<Vec<_>>();
+                (!covered.is_empty()).then_some(SuspectedHijack {
+                    prefix: *prefix,
+                    announced: covered,
+                    origin,
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn net(s: &str) -> IpNet {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn flags_foreign_origins_of_our_prefixes_and_more_specifics() {
+        let cfg = HijackConfig {
+            enabled: true,
+            allowed_origins: vec![64600],
+        };
+        let detector = HijackDetector::new(&cfg, vec![net("192.0.2.0/24")], 64500);
+
+        let announced = [
+            net("192.0.2.0/25"),
+            net("192.0.0.0/16"),
+            net("198.51.100.0/24"),
+        ];
+        assert_eq!(
+            detector.check(&announced, &[65002, 64666]),
+            vec![SuspectedHijack {
+                prefix: net("192.0.2.0/24"),
+                announced: vec![net("192.0.2.0/25")],
+                origin: 64666,
+            }]
+        );
+        assert!(detector.check(&announced, &[65002, 64500]).is_empty());
+        assert!(detector.check(&announced, &[65002, 64600]).is_empty());
+        assert!(detector.check(&announced, &[]).is_empty());
+
+        let disabled = HijackConfig {
+            enabled: false,
+            ..cfg
+        };
+        let detector = HijackDetector::new(&disabled, vec![net("192.0.2.0/24")], 64500);
+        assert!(detector.check(&announced, &[64666]).is_empty());
+    }
+}
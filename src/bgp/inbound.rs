@@ -0,0 +1,212 @@
+//! `[inbound_limits]`: per-source and global rate limits for connections
+//! accepted by passive peers, and temporary bans for sources that keep
+//! failing the OPEN handshake.
+
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::config::InboundLimitsConfig;
+
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Why a connection was turned away.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum Rejected {
+    SourceRate,
+    GlobalRate,
+    Banned,
+}
+
+impl Rejected {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::SourceRate => "per-source rate limit",
+            Self::GlobalRate => "global rate limit",
+            Self::Banned => "banned",
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+struct Source {
+    accepted: VecDeque<Instant>,
+    failures: VecDeque<Instant>,
+    banned_until: Option<Instant>,
+}
+
+impl Source {
+    fn is_idle(&self, now: Instant) -> bool {
+        self.accepted.is_empty()
+            && self.failures.is_empty()
+            && self.banned_until.is_none_or(|until| until <= now)
+    }
+}
+
+#[derive(Debug, Default)]
+struct State {
+    accepted: VecDeque<Instant>,
+    sources: HashMap<IpAddr, Source>,
+}
+
+#[derive(Debug)]
+pub(super) struct InboundLimiter {
+    cfg: InboundLimitsConfig,
+    state: Mutex<State>,
+    rejected: AtomicU64,
+}
+
+impl InboundLimiter {
+    pub fn new(cfg: InboundLimitsConfig) -> Self {
+        Self {
+            cfg,
+            state: Mutex::default(),
+            rejected: AtomicU64::new(0),
+        }
+    }
+
+    /// Count a connection from `source` if it is within every limit.
+    pub fn admit_at(&self, source: IpAddr, now: Instant) -> Result<(), Rejected> {
+        let mut state = self.lock();
+        let failure_window = Duration::from_secs(self.cfg.failure_window_secs);
+        prune(&mut state.accepted, now, RATE_WINDOW);
+        for entry in state.sources.values_mut() {
+            prune(&mut entry.accepted, now, RATE_WINDOW);
+            prune(&mut entry.failures, now, failure_window);
+        }
+        state.sources.retain(|_, entry| !entry.is_idle(now));
+
+        let entry = state.sources.get(&source);
+        let rejected = if entry
+            .and_then(|e| e.banned_until)
+            .is_some_and(|until| until > now)
+        {
+            Some(Rejected::Banned)
+        } else if self.cfg.per_source_per_min > 0
+            && entry.map_or(0, |e| e.accepted.len()) >= self.cfg.per_source_per_min as usize
+        {
+            Some(Rejected::SourceRate)
+        } else if self.cfg.global_per_min > 0
+            && state.accepted.len() >= self.cfg.global_per_min as usize
+        {
+            Some(Rejected::GlobalRate)
+        } else {
+            None
+        };
+        if let Some(reason) = rejected {
+            self.rejected.fetch_add(1, Ordering::Relaxed);
+            return Err(reason);
+        }
+        state.accepted.push_back(now);
+        state
+            .sources
+            .entry(source)
+            .or_default()
+            .accepted
+            .push_back(now);
+        Ok(())
+    }
+
+    /// Record a failed OPEN handshake. Returns the ban length when this
+    /// failure gets `source` banned.
+    pub fn open_failed_at(&self, source: IpAddr, now: Instant) -> Option<Duration> {
+        if self.cfg.ban_after_failures == 0 {
+            return None;
+        }
+        let mut state = self.lock();
+        let entry = state.sources.entry(source).or_default();
+        prune(
+            &mut entry.failures,
+            now,
+            Duration::from_secs(self.cfg.failure_window_secs),
+        );
+        entry.failures.push_back(now);
+        if entry.failures.len() < self.cfg.ban_after_failures as usize {
+            return None;
+        }
+        let ban = Duration::from_secs(self.cfg.ban_secs);
+        entry.failures.clear();
+        entry.banned_until = Some(now + ban);
+        Some(ban)
+    }
+
+    /// A completed handshake clears the source's failures.
+    pub fn open_succeeded(&self, source: IpAddr) {
+        if let Some(entry) = self.lock().sources.get_mut(&source) {
+            entry.failures.clear();
+        }
+    }
+
+    /// Connections turned away since startup.
+    pub fn rejected(&self) -> u64 {
+        self.rejected.load(Ordering::Relaxed)
+    }
+
+    /// Sources currently banned.
+    pub fn banned(&self) -> usize {
+        let now = Instant::now();
+        self.lock()
+            .sources
+            .values()
+            .filter(|e| e.banned_until.is_some_and(|until| until > now))
+            .count()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, State> {
+        self.state.lock().expect("inbound limiter lock poisoned")
+    }
+}
+
+fn prune(events: &mut VecDeque<Instant>, now: Instant, window: Duration) {
+    while events
+        .front()
+        .is_some_and(|at| now.saturating_duration_since(*at) >= window)
+    {
+        events.pop_front();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn limits_rates_and_bans_failing_sources() {
+        let limiter = InboundLimiter::new(InboundLimitsConfig {
+            per_source_per_min: 2,
+            global_per_min: 3,
+            ban_after_failures: 2,
+            failure_window_secs: 300,
+            ban_secs: 600,
+        });
+        let a: IpAddr = "198.51.100.1".parse().unwrap();
+        let b: IpAddr = "198.51.100.2".parse().unwrap();
+        let c: IpAddr = "198.51.100.3".parse().unwrap();
+        let start = Instant::now();
+        let at = |secs: u64| start + Duration::from_secs(secs);
+
+        assert!(limiter.admit_at(a, at(0)).is_ok());
+        assert!(limiter.admit_at(a, at(1)).is_ok());
+        assert_eq!(limiter.admit_at(a, at(2)), Err(Rejected::SourceRate));
+        assert!(limiter.admit_at(b, at(3)).is_ok());
+        assert_eq!(limiter.admit_at(c, at(4)), Err(Rejected::GlobalRate));
+        // The window slides.
+        assert!(limiter.admit_at(c, at(61)).is_ok());
+        assert_eq!(limiter.rejected(), 2);
+
+        assert_eq!(limiter.open_failed_at(c, at(61)), None);
+        assert_eq!(
+            limiter.open_failed_at(c, at(62)),
+            Some(Duration::from_secs(600))
+        );
+        assert_eq!(limiter.admit_at(c, at(200)), Err(Rejected::Banned));
+        assert!(limiter.admit_at(c, at(663)).is_ok());
+
+        // A successful handshake resets the failure count.
+        assert_eq!(limiter.open_failed_at(b, at(700)), None);
+        limiter.open_succeeded(b);
+        assert_eq!(limiter.open_failed_at(b, at(701)), None);
+    }
+}
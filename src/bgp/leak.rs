@@ -0,0 +1,129 @@
+//! Route-leak heuristics (RFC 7908) driven by the per-peer `role`: routes
+//! that arrive from a customer or lateral peer but have crossed one of our
+//! providers or peers, and RFC 9234 Only-To-Customer violations.
+
+use std::collections::HashSet;
+
+use bgpkit_parser::models::Attributes;
+
+use crate::config::{PeerConfig, PeerRole};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct SuspectedLeak {
+    pub as_path: Vec<u32>,
+    pub reason: String,
+}
+
+#[derive(Debug, Default)]
+pub(super) struct LeakDetector {
+    /// ASNs of peers configured as `provider` or `peer`.
+    upstream_asns: HashSet<u32>,
+}
+
+impl LeakDetector {
+    pub fn new(peers: &[PeerConfig], local_as: u32) -> Self {
+        let upstream_asns = peers
+            .iter()
+            .filter(|p| matches!(p.role, Some(PeerRole::Provider | PeerRole::Peer)))
+            .map(|p| p.remote_as)
+            .filter(|&asn| asn != local_as)
+            .collect();
+        Self { upstream_asns }
+    }
+
+    /// Check the attributes of a route received from a peer with `role`.
+    pub fn check(
+        &self,
+        role: PeerRole,
+        peer_as: u32,
+        attributes: &Attributes,
+    ) -> Option<SuspectedLeak> {
+        let as_path = attributes
+            .as_path()
+            .and_then(|path| path.to_u32_vec_opt(false))
+            .unwrap_or_default();
+
+        let otc = attributes.only_to_customer().map(|asn| asn.to_u32());
+        let reason = match (role, otc) {
+            (PeerRole::Customer | PeerRole::RouteServerClient, Some(otc)) => Some(format!(
+                "only-to-customer AS{otc} on a route from a customer"
+            )),
+            (PeerRole::Peer, Some(otc)) if otc != peer_as => Some(format!(
+                "only-to-customer AS{otc} on a route from peer AS{peer_as}"
+            )),
+            (PeerRole::Customer | PeerRole::Peer, _) => as_path
+                .iter()
+                .find(|&&asn| asn != peer_as && self.upstream_asns.contains(&asn))
+                .map(|asn| {
+                    let from = if role == PeerRole::Customer {
+                        "customer"
+                    } else {
+                        "peer"
+                    };
+                    format!("route from a {from} crosses AS{asn}, a provider or peer of ours")
+                }),
+            _ => None,
+        }?;
+        Some(SuspectedLeak { as_path, reason })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bgpkit_parser::models::{AsPath, Asn, AttributeValue};
+
+    fn peer(remote_as: u32, role: &str) -> PeerConfig {
+        toml::from_str(&format!(
+            "address = \"192.0.2.{}\"\nremote_as = {remote_as}\nrole = \"{role}\"",
+            remote_as % 250
+        ))
+        .unwrap()
+    }
+
+    fn attributes(path: &[u32], otc: Option<u32>) -> Attributes {
+        let mut values = vec![AttributeValue::AsPath {
+            path: AsPath::from_sequence(path),
+            is_as4: false,
+        }];
+        if let Some(otc) = otc {
+            values.push(AttributeValue::OnlyToCustomer(Asn::new_32bit(otc)));
+        }
+        values.into_iter().collect()
+    }
+
+    #[test]
+    fn flags_valleys_and_otc_violations() {
+        let detector = LeakDetector::new(
+            &[
+                peer(64500, "provider"),
+                peer(64510, "peer"),
+                peer(64520, "customer"),
+            ],
+            65001,
+        );
+
+        let clean = attributes(&[64520, 64520, 64999], None);
+        assert_eq!(detector.check(PeerRole::Customer, 64520, &clean), None);
+
+        let valley = attributes(&[64520, 64500, 13335], None);
+        let leak = detector.check(PeerRole::Customer, 64520, &valley).unwrap();
+        assert_eq!(leak.as_path, [64520, 64500, 13335]);
+        assert!(leak.reason.contains("AS64500"));
+        assert!(detector.check(PeerRole::Provider, 64500, &valley).is_none());
+
+        let lateral = attributes(&[64510, 64500], None);
+        assert!(detector.check(PeerRole::Peer, 64510, &lateral).is_some());
+
+        let own_otc = attributes(&[64510, 64999], Some(64510));
+        assert!(detector.check(PeerRole::Peer, 64510, &own_otc).is_none());
+        let foreign_otc = attributes(&[64510, 64999], Some(64777));
+        assert!(detector
+            .check(PeerRole::Peer, 64510, &foreign_otc)
+            .is_some());
+        let customer_otc = attributes(&[64520], Some(64520));
+        assert!(detector
+            .check(PeerRole::Customer, 64520, &customer_otc)
+            .is_some());
+    }
+}
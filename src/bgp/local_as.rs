@@ -0,0 +1,60 @@
+//! AS migration with a per-peer `local_as` (RFC 7705), following Cisco's
+//! `local-as`. A session with a `local_as` other than `global.asn` prepends
+//! both ASNs to routes it sends, unless `local_as_replace_as`, and `local_as`
+//! to the paths received from it, unless `local_as_no_prepend`.
+
+use crate::config::PeerConfig;
+
+/// ASNs prepended to every route sent to `peer`, nearest first.
+pub(super) fn outbound_prepend(peer: &PeerConfig, global_asn: u32) -> Vec<u32> {
+    match migrated_as(peer, global_asn) {
+        Some(local_as) if peer.local_as_replace_as => vec![local_as],
+        Some(local_as) => vec![local_as, global_asn],
+        None => vec![global_asn],
+    }
+}
+
+/// The ASN prepended to paths received from `peer`, if any.
+pub(super) fn inbound_prepend(peer: &PeerConfig, global_asn: u32) -> Option<u32> {
+    migrated_as(peer, global_asn).filter(|_| !peer.local_as_no_prepend)
+}
+
+fn migrated_as(peer: &PeerConfig, global_asn: u32) -> Option<u32> {
+    peer.local_as.filter(|&local_as| local_as != global_asn)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn peer(extra: &str) -> PeerConfig {
+        toml::from_str(&format!(
+            r#"
+            address = "192.0.2.2"
+            remote_as = 65002
+            {extra}
+            "#
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn prepends_like_cisco_local_as() {
+        let plain = peer("");
+        assert_eq!(outbound_prepend(&plain, 65001), vec![65001]);
+        assert_eq!(inbound_prepend(&plain, 65001), None);
+
+        let same = peer("local_as = 65001");
+        assert_eq!(outbound_prepend(&same, 65001), vec![65001]);
+        assert_eq!(inbound_prepend(&same, 65001), None);
+
+        let migrated = peer("local_as = 64999");
+        assert_eq!(outbound_prepend(&migrated, 65001), vec![64999, 65001]);
+        assert_eq!(inbound_prepend(&migrated, 65001), Some(64999));
+
+        let replaced =
+            peer("local_as = 64999\nlocal_as_no_prepend = true\nlocal_as_replace_as = true");
+        assert_eq!(outbound_prepend(&replaced, 65001), vec![64999]);
+        assert_eq!(inbound_prepend(&replaced, 65001), None);
+    }
+}
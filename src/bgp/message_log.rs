@@ -0,0 +1,142 @@
+//! Per-peer hex dumps of every BGP message (`log_messages`), toggled at
+//! runtime with `peer_log_messages`. Lines go to `log_messages_file` when set,
+//! otherwise to the daemon log.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use chrono::{SecondsFormat, Utc};
+
+use super::TYPE_OFFSET;
+use crate::config::PeerConfig;
+
+#[derive(Debug)]
+pub(super) struct MessageLog {
+    peer: String,
+    enabled: AtomicBool,
+    path: Option<PathBuf>,
+    /// Opened on the first message logged to `path`.
+    file: Mutex<Option<File>>,
+}
+
+impl MessageLog {
+    pub fn new(peer: &PeerConfig) -> Self {
+        Self {
+            peer: peer.address.clone(),
+            enabled: AtomicBool::new(peer.log_messages),
+            path: peer.log_messages_file.clone(),
+            file: Mutex::new(None),
+        }
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.enabled.load(Ordering::Relaxed)
+    }
+
+    pub fn set_enabled(&self, enabled: bool) {
+        self.enabled.store(enabled, Ordering::Relaxed);
+        if !enabled {
+            // Reopened on the next message, so the file can be rotated away.
+            *self.file.lock().expect("message log lock poisoned") = None;
+        }
+    }
+
+    pub fn path(&self) -> Option<&Path> {
+        self.path.as_deref()
+    }
+
+    /// Log one complete BGP message, sent by us when `outbound`.
+    pub fn record(&self, outbound: bool, bytes: &[u8]) {
+        if !self.enabled() {
+            return;
+        }
+        let direction = if outbound { "sent" } else { "received" };
+        let kind = message_type(bytes);
+        let Some(path) = &self.path else {
+            tracing::info!(
+                target: "focl::bgp::messages",
+                peer = %self.peer,
+                direction,
+                kind,
+                len = bytes.len(),
+                hex = %hex::encode(bytes),
+                "bgp message"
+            );
+            return;
+        };
+        let line = format!(
+            "{} {} {direction} {kind} {} {}\n",
+            Utc::now().to_rfc3339_opts(SecondsFormat::Micros, true),
+            self.peer,
+            bytes.len(),
+            hex::encode(bytes)
+        );
+        if let Err(err) = self.append(path, line.as_bytes()) {
+            tracing::warn!(peer=%self.peer, path=%path.display(), error=%err, "message log failed, disabling it");
+            self.set_enabled(false);
+        }
+    }
+
+    fn append(&self, path: &Path, line: &[u8]) -> io::Result<()> {
+        let mut file = self.file.lock().expect("message log lock poisoned");
+        if file.is_none() {
+            if let Some(parent) = path.parent().filter(|p| !p.as_os_str().is_empty()) {
+                fs::create_dir_all(parent)?;
+            }
+            *file = Some(OpenOptions::new().create(true).append(true).open(path)?);
+        }
+        file.as_mut().expect("opened above").write_all(line)
+    }
+}
+
+fn message_type(bytes: &[u8]) -> &'static str {
+    match bytes.get(TYPE_OFFSET) {
+        Some(1) => "OPEN",
+        Some(2) => "UPDATE",
+        Some(3) => "NOTIFICATION",
+        Some(4) => "KEEPALIVE",
+        Some(5) => "ROUTE-REFRESH",
+        _ => "UNKNOWN",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn logs_messages_to_file_only_while_enabled() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("peer.log");
+        let mut peer: PeerConfig = toml::from_str(
+            r#"
+            address = "192.0.2.2"
+            remote_as = 65002
+            "#,
+        )
+        .unwrap();
+        peer.log_messages_file = Some(path.clone());
+        let log = MessageLog::new(&peer);
+        let mut keepalive = vec![0xff; 16];
+        keepalive.extend_from_slice(&[0, 19, 4]);
+
+        log.record(true, &keepalive);
+        assert!(!path.exists());
+
+        log.set_enabled(true);
+        log.record(true, &keepalive);
+        log.record(false, &keepalive);
+        log.set_enabled(false);
+        log.record(false, &keepalive);
+
+        let logged = fs::read_to_string(&path).unwrap();
+        let lines = logged.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0]
+            .ends_with(" 192.0.2.2 sent KEEPALIVE 19 ffffffffffffffffffffffffffffffff001304"));
+        assert!(lines[1].contains(" received KEEPALIVE "));
+    }
+}
@@ -0,0 +1,162 @@
+//! Session mirroring (`mirror_to`): every BGP message received from the peer
+//! is forwarded byte for byte to secondary collectors, so a standby focld
+//! sees the same session without peering changes.
+
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use bytes::Bytes;
+use tokio::io::AsyncWriteExt;
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::net::TcpStream;
+use tokio::sync::mpsc;
+use tokio::time::{timeout, Instant};
+
+const BGP_MSG_OPEN: u8 = 1;
+const KEEPALIVE: [u8; 19] = {
+    let mut msg = [0xff; 19];
+    msg[16] = 0;
+    msg[17] = 19;
+    msg[18] = 4;
+    msg
+};
+/// Frames queued per endpoint before new ones are dropped.
+const MIRROR_BUFFER: usize = 1024;
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const RECONNECT_DELAY: Duration = Duration::from_secs(10);
+
+/// Feeds one forwarding task per endpoint. Dropping it closes the mirror
+/// connections, so the receivers see the session end with ours.
+pub(super) struct Mirror {
+    endpoints: Vec<Endpoint>,
+}
+
+struct Endpoint {
+    addr: SocketAddr,
+    tx: mpsc::Sender<Bytes>,
+    /// Set while frames are being dropped, to warn once per overflow.
+    overflowing: bool,
+}
+
+impl Mirror {
+    pub fn start(peer: &str, endpoints: &[SocketAddr]) -> Self {
+        let endpoints = endpoints
+            .iter()
+            .map(|&addr| {
+                let (tx, rx) = mpsc::channel(MIRROR_BUFFER);
+                tokio::spawn(forward(peer.to_string(), addr, rx));
+                Endpoint {
+                    addr,
+                    tx,
+                    overflowing: false,
+                }
+            })
+            .collect();
+        Self { endpoints }
+    }
+
+    /// Queue a received frame; never waits on a slow endpoint.
+    pub fn send(&mut self, frame: &Bytes) {
+        for endpoint in &mut self.endpoints {
+            match endpoint.tx.try_send(frame.clone()) {
+                Ok(()) => endpoint.overflowing = false,
+                Err(_) if !endpoint.overflowing => {
+                    endpoint.overflowing = true;
+                    tracing::warn!(endpoint=%endpoint.addr, "mirror endpoint fell behind, dropping messages");
+                }
+                Err(_) => {}
+            }
+        }
+    }
+}
+
+/// Copy frames to `addr` until the session ends. A reconnect mid-session
+/// replays the peer's OPEN and a KEEPALIVE so the receiver always sees a
+/// well-formed session; messages received while the endpoint is unreachable
+/// are lost.
+async fn forward(peer: String, addr: SocketAddr, mut rx: mpsc::Receiver<Bytes>) {
+    let mut open: Option<Bytes> = None;
+    let mut conn: Option<OwnedWriteHalf> = None;
+    let mut retry_at = Instant::now();
+    while let Some(frame) = rx.recv().await {
+        let is_open = frame.get(18) == Some(&BGP_MSG_OPEN);
+        if is_open {
+            open = Some(frame.clone());
+        }
+        if conn.is_none() && Instant::now() >= retry_at {
+            let replay = open.as_ref().filter(|_| !is_open);
+            match connect(addr, replay).await {
+                Ok(stream) => {
+                    tracing::info!(peer, endpoint=%addr, "mirror connected");
+                    conn = Some(stream);
+                }
+                Err(err) => {
+                    tracing::warn!(peer, endpoint=%addr, error=%err, "mirror connect failed");
+                    retry_at = Instant::now() + RECONNECT_DELAY;
+                }
+            }
+        }
+        let Some(stream) = &mut conn else {
+            continue;
+        };
+        if let Err(err) = stream.write_all(&frame).await {
+            tracing::warn!(peer, endpoint=%addr, error=%err, "mirror write failed");
+            conn = None;
+            retry_at = Instant::now() + RECONNECT_DELAY;
+        }
+    }
+    if let Some(mut stream) = conn {
+        let _ = stream.shutdown().await;
+    }
+}
+
+/// Whatever the receiver sends back (its own OPEN, KEEPALIVEs) is read and
+/// discarded so it never stalls on a full socket buffer.
+async fn connect(addr: SocketAddr, replay: Option<&Bytes>) -> std::io::Result<OwnedWriteHalf> {
+    let stream = timeout(CONNECT_TIMEOUT, TcpStream::connect(addr))
+        .await
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::TimedOut, "connect timed out"))??;
+    stream.set_nodelay(true)?;
+    let (mut read, mut write) = stream.into_split();
+    tokio::spawn(async move {
+        let _ = tokio::io::copy(&mut read, &mut tokio::io::sink()).await;
+    });
+    if let Some(open) = replay {
+        write.write_all(open).await?;
+        write.write_all(&KEEPALIVE).await?;
+    }
+    Ok(write)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+    use tokio::net::TcpListener;
+
+    fn frame(kind: u8, body: &[u8]) -> Bytes {
+        let mut bytes = vec![0xff; 16];
+        bytes.extend_from_slice(&((19 + body.len()) as u16).to_be_bytes());
+        bytes.push(kind);
+        bytes.extend_from_slice(body);
+        bytes.into()
+    }
+
+    #[tokio::test]
+    async fn forwards_received_frames_verbatim() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let open = frame(BGP_MSG_OPEN, &[4, 0xfd, 0xea, 0, 90, 192, 0, 2, 2, 0]);
+        let update = frame(2, &[0, 0, 0, 0]);
+
+        let mut mirror = Mirror::start("192.0.2.2", &[addr]);
+        mirror.send(&open);
+        mirror.send(&update);
+        let (mut stream, _) = listener.accept().await.unwrap();
+        drop(mirror);
+
+        let mut received = Vec::new();
+        stream.read_to_end(&mut received).await.unwrap();
+        assert_eq!(received, [open, update].concat());
+    }
+}
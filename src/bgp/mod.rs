@@ -1,29 +1,176 @@
-use std::collections::HashMap;
-use std::net::{IpAddr, Ipv4Addr, SocketAddr};
-use std::str::FromStr;
+use std::collections::{BTreeMap, HashMap};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
 
 use anyhow::{anyhow, Context, Result};
 use bgpkit_parser::bgp::parse_bgp_message;
 use bgpkit_parser::models::{
-    AsPath, AsnLength, AttributeValue, Attributes, BgpMessage, BgpOpenMessage, BgpUpdateMessage,
-    NetworkPrefix, Origin,
+    Afi, AsPath, AsPathSegment, Asn, AsnLength, Attribute, AttributeValue, Attributes, BgpError,
+    BgpMessage, BgpNotificationMessage, BgpOpenMessage, BgpUpdateMessage, CeaseNotification,
+    FiniteStateMachineError, NetworkPrefix, Nlri, Origin,
 };
 use bytes::Bytes;
 use ipnet::IpNet;
 use serde::{Deserialize, Serialize};
 use tokio::io::{AsyncReadExt, AsyncWriteExt};
 use tokio::net::{TcpListener, TcpSocket, TcpStream};
-use tokio::sync::{broadcast, RwLock};
+use tokio::sync::{broadcast, mpsc, oneshot, watch, RwLock};
 use tokio::task::JoinHandle;
-use tokio::time::{sleep, timeout, Instant};
+use tokio::time::{sleep, sleep_until, timeout, timeout_at, Instant};
 
-use crate::config::{FoclConfig, PeerConfig};
-use crate::types::{Event, EventEnvelope, PeerState};
+use crate::archive::peers::SegmentPeer;
+use crate::archive::types::{AddressFamily, PeerStateRecordInput, SnapshotPeer, UpdateRecordInput};
+use crate::archive::{ArchiveService, IngestRecord};
+use crate::config::{
+    BlackholeConfig, FoclConfig, InvalidOriginations, PeerConfig, PrefixList, RibSource,
+};
+use crate::types::{Event, EventBus, EventTopic, PeerState};
 
+mod adj_rib_in;
+mod aggregate;
 mod auth;
-use auth::{TcpSocketExt, TcpStreamExt};
+mod blackhole;
+mod capabilities;
+mod capture;
+mod churn;
+mod healthcheck;
+mod hijack;
+mod inbound;
+mod leak;
+mod local_as;
+mod message_log;
+mod mirror;
+mod mrt;
+mod open;
+mod pacing;
+mod remote_lists;
+mod rib_snapshot;
+mod rpki;
+mod session_error;
+mod stats;
+mod timeline;
+mod timers;
+use adj_rib_in::AdjRibIn;
+pub use adj_rib_in::{AsPathStats, NewOrigin, PeerPathStats, PrefixOrigins};
+use auth::{AoKey, TcpAuth, TcpAuthExt};
+use blackhole::ActiveBlackhole;
+pub use blackhole::Blackhole;
+use capabilities::{encode_open, is_route_refresh};
+pub use capabilities::{AfiSafi, NegotiatedCapabilities};
+use capture::SessionStream;
+use churn::ChurnTracker;
+pub use churn::PrefixChurn;
+use hijack::HijackDetector;
+use inbound::InboundLimiter;
+use leak::LeakDetector;
+use message_log::MessageLog;
+use mrt::RoutePath;
+use open::{check_open, collision_keeps_inbound};
+use pacing::Outbox;
+pub use rib_snapshot::SnapshotTable;
+use rib_snapshot::{route_attributes, snapshot_peer};
+use session_error::SessionError;
+pub use session_error::{NotificationCodes, PeerError, PeerErrorCode};
+pub(crate) use stats::RateMeter;
+use stats::{DuplicateFilter, FlapHistory, MinuteCounts, RibCounters, ShedNotice};
+use timeline::SessionTimeline;
+pub use timeline::{SessionEvent, SessionEventKind};
+pub use timers::PeerTimers;
+use timers::{keepalive_secs, TimerState};
+
+const UPDATE_RATE_WINDOW_SECS: f64 = 60.0;
+const FLAP_WINDOW_SECS: i64 = 3600;
+const SOFT_RESET_TIMEOUT: Duration = Duration::from_secs(10);
+const BGP_MSG_ROUTE_REFRESH: u8 = 5;
+const ROUTE_CHANGE_BUFFER: usize = 256;
+const RECEIVED_UPDATE_BUFFER: usize = 1024;
+const SHED_EVENT_INTERVAL: Duration = Duration::from_secs(1);
+const BGP_HEADER_LEN: usize = 19;
+/// Offset of the type octet in a BGP message header.
+pub(super) const TYPE_OFFSET: usize = 18;
+const BGP_MAX_MESSAGE_LEN: usize = 4096;
+/// Hold time until the peer's OPEN arrives (RFC 4271 suggests 4 minutes).
+const OPEN_HOLD_TIME: Duration = Duration::from_secs(240);
+/// How long a hard reset waits for the old session to close.
+const PEER_STOP_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// How `peer_reset` treats the session.
+///
+/// `Hard` tears the session down and reconnects; the soft modes keep the
+/// session up and either re-send our announcements (`SoftOut`) or ask the
+/// peer to re-advertise its routes with ROUTE-REFRESH (`SoftIn`).
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum ResetMode {
+    #[default]
+    Hard,
+    SoftOut,
+    SoftIn,
+}
+
+/// A peer put in maintenance with `peer_maintenance`: our announcements are
+/// withdrawn and it no longer counts toward health. With `keep_session` the
+/// session stays up so its routes are still collected; otherwise it is shut
+/// down with a Cease and not reconnected until maintenance ends.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Maintenance {
+    pub keep_session: bool,
+    /// Unix seconds when maintenance started.
+    pub since: i64,
+}
+
+#[derive(Debug)]
+struct SoftReset {
+    mode: ResetMode,
+    reply: oneshot::Sender<Result<()>>,
+}
+
+/// A runtime change to the originated routes, pushed to every live session.
+#[derive(Debug, Clone)]
+enum RouteChange {
+    Announce(PrefixEntry),
+    Withdraw(IpNet),
+    /// Many routes changed at once; re-send the full set.
+    Refresh,
+    /// The named prefix list was replaced; peers exporting through it
+    /// withdraw what `previous` allowed and re-send the full set.
+    PrefixListChanged {
+        name: String,
+        previous: Arc<PrefixList>,
+    },
+}
+
+/// An UPDATE received from a peer, published to [`BgpService::subscribe_received`].
+#[derive(Debug, Clone)]
+pub struct ReceivedUpdate {
+    pub timestamp: f64,
+    pub peer: String,
+    pub peer_as: u32,
+    pub local_as: u32,
+    pub local_address: Option<IpAddr>,
+    /// BGP identifier from the peer's OPEN.
+    pub peer_bgp_id: Ipv4Addr,
+    /// Whether four-octet AS numbers were negotiated; otherwise the AS_PATH
+    /// in `raw` carries two-octet AS numbers.
+    pub four_octet_as: bool,
+    pub update: BgpUpdateMessage,
+    /// The message as received, header included.
+    pub raw: Bytes,
+}
+
+impl ReceivedUpdate {
+    /// Prefixes announced, in the NLRI field or MP_REACH_NLRI.
+    pub fn announced(&self) -> Vec<IpNet> {
+        announced_prefixes(&self.update)
+    }
+
+    /// Prefixes withdrawn, in the withdrawn routes field or MP_UNREACH_NLRI.
+    pub fn withdrawn(&self) -> Vec<IpNet> {
+        withdrawn_prefixes(&self.update)
+    }
+}
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PeerInfo {
@@ -35,16 +182,101 @@ pub struct PeerInfo {
     pub passive: bool,
     pub auth_enabled: bool,
     pub state: PeerState,
-    pub last_error: Option<String>,
+    /// Why the last session attempt failed; cleared once Established.
+    pub last_error: Option<PeerError>,
     pub advertised_prefixes: usize,
     pub established_at: Option<i64>,
+    pub updates_received: u64,
+    pub withdrawals_received: u64,
+    /// Announced prefixes received, including MP_REACH_NLRI.
+    pub prefixes_received: u64,
+    /// Received prefixes that did not match the peer's `import_prefix_list`.
+    pub prefixes_rejected: u64,
+    pub session_flaps: u64,
+    /// Archive records shed because the archive ingest queue was full.
+    #[serde(default)]
+    pub archive_records_shed: u64,
+    /// UPDATEs not archived as duplicates within `[archive].dedup_window_ms`.
+    #[serde(default)]
+    pub archive_duplicates_suppressed: u64,
+    #[serde(default)]
+    pub maintenance: Option<Maintenance>,
+    /// Whether every BGP message is being hex-dumped (`peer_log_messages`).
+    #[serde(default)]
+    pub log_messages: bool,
+    /// Negotiated with the peer; only set while Established.
+    #[serde(default)]
+    pub capabilities: Option<NegotiatedCapabilities>,
+    /// BGP identifier in the peer's last OPEN; kept after the session drops.
+    #[serde(default)]
+    pub remote_id: Option<Ipv4Addr>,
+}
+
+/// A peer's cumulative counters, kept across restarts by `[peer_stats]`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PeerTotals {
+    pub peer: String,
+    pub updates_received: u64,
+    pub withdrawals_received: u64,
+    pub prefixes_received: u64,
+    pub prefixes_rejected: u64,
+    pub session_flaps: u64,
+    pub last_established: Option<i64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct RibSummary {
     pub peers_total: usize,
     pub peers_established: usize,
+    /// Peers in maintenance, left out of `peers_total` and `peers_established`.
+    #[serde(default)]
+    pub peers_maintenance: usize,
     pub advertised_prefixes_total: usize,
+    #[serde(default)]
+    pub prefixes_received_total: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerTalker {
+    pub address: String,
+    pub name: Option<String>,
+    pub updates_per_sec: f64,
+    pub updates_received: u64,
+    pub withdrawals_received: u64,
+    pub flaps_last_hour: usize,
+}
+
+/// A peer's UPDATEs over the window of a `bgp_top` report.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TopPeer {
+    pub address: String,
+    pub name: Option<String>,
+    pub updates: u64,
+    pub withdrawals: u64,
+    pub updates_per_sec: f64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BgpTop {
+    pub minutes: u32,
+    pub peers: Vec<TopPeer>,
+    /// Ranked by announcements; `None` while `[churn]` is disabled. Covers
+    /// at most `[churn].window_mins`.
+    pub prefixes: Option<Vec<PrefixChurn>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BgpStats {
+    pub updates_per_sec: f64,
+    pub updates_received_total: u64,
+    pub withdrawals_received_total: u64,
+    pub session_flaps_last_hour: usize,
+    pub top_talkers: Vec<PeerTalker>,
+    /// Passive-peer connections turned away by `[inbound_limits]`.
+    #[serde(default)]
+    pub inbound_rejected: u64,
+    #[serde(default)]
+    pub inbound_banned_sources: usize,
 }
 
 #[derive(Debug)]
@@ -52,8 +284,26 @@ struct PeerRuntime {
     info: PeerInfo,
     cfg: PeerConfig,
     task: JoinHandle<()>,
+    /// Set to stop the task: its session closes with a Cease and the peer
+    /// goes Idle before the task exits.
+    cancel: watch::Sender<bool>,
+    soft_reset: mpsc::Sender<SoftReset>,
+    maintenance: watch::Sender<Option<Maintenance>>,
+    update_rate: RateMeter,
+    recent_updates: MinuteCounts,
+    flaps: FlapHistory,
+    shed_notice: ShedNotice,
+    /// Our address on the latest session, for archived state changes.
+    local_ip: Option<IpAddr>,
+    timers: Arc<TimerState>,
+    message_log: Arc<MessageLog>,
+    timeline: Arc<SessionTimeline>,
 }
 
+/// One peer's state behind its own lock, so sessions only contend with
+/// readers of the same peer.
+type PeerSlot = Arc<RwLock<PeerRuntime>>;
+
 #[derive(Clone)]
 pub struct BgpService {
     inner: Arc<BgpServiceInner>,
@@ -63,51 +313,195 @@ pub struct BgpService {
 struct PrefixEntry {
     network: IpNet,
     next_hop: Option<IpAddr>,
+    /// Attributes carried over from an MRT file.
+    path: Option<Arc<RoutePath>>,
+    /// Peers this entry is announced to; `None` means every peer.
+    peers: Option<Arc<[String]>>,
+}
+
+impl PrefixEntry {
+    fn announced_to(&self, peer: &PeerConfig) -> bool {
+        self.peers
+            .as_ref()
+            .is_none_or(|peers| peers.contains(&peer.address))
+    }
 }
 
 struct BgpServiceInner {
     global_asn: u32,
     router_id: Ipv4Addr,
-    prefixes: Vec<PrefixEntry>,
-    peers: RwLock<HashMap<String, PeerRuntime>>,
-    event_tx: broadcast::Sender<EventEnvelope>,
+    /// `[archive].rib_view_name`, for peers without an `archive.view_name`.
+    rib_view_name: String,
+    prefixes: std::sync::RwLock<Vec<PrefixEntry>>,
+    /// Swapped wholesale when a `[remote_prefix_lists]` entry is refreshed.
+    prefix_lists: std::sync::RwLock<HashMap<String, Arc<PrefixList>>>,
+    route_tx: broadcast::Sender<RouteChange>,
+    received_tx: broadcast::Sender<ReceivedUpdate>,
+    /// Write-locked only when peers are added; per-peer updates go through
+    /// the slot.
+    peers: RwLock<HashMap<String, PeerSlot>>,
+    events: EventBus,
+    shutdown: watch::Sender<bool>,
+    update_rate: std::sync::Mutex<RateMeter>,
+    rib_counters: RibCounters,
+    archive: Option<Arc<ArchiveService>>,
+    blackhole: BlackholeConfig,
+    blackholes: std::sync::Mutex<BTreeMap<IpNet, ActiveBlackhole>>,
+    leaks: LeakDetector,
+    hijacks: HijackDetector,
+    inbound: InboundLimiter,
+    churn: Option<ChurnTracker>,
+    rib_in: Option<AdjRibIn>,
 }
 
 impl BgpService {
-    pub async fn new(cfg: &FoclConfig, event_tx: broadcast::Sender<EventEnvelope>) -> Result<Self> {
+    pub async fn new(
+        cfg: &FoclConfig,
+        events: EventBus,
+        archive: Option<Arc<ArchiveService>>,
+    ) -> Result<Self> {
         let router_id = cfg
             .global
             .router_id
             .parse::<Ipv4Addr>()
             .context("global.router_id must be IPv4")?;
 
-        let prefixes = cfg
-            .prefixes
-            .iter()
-            .map(|p| {
-                let network = IpNet::from_str(&p.network)
-                    .with_context(|| format!("invalid prefix network: {}", p.network))?;
-                let next_hop = p
-                    .next_hop
+        let mut prefixes = cfg
+            .originated_prefixes()
+            .context("invalid prefix in config")?
+            .into_iter()
+            .map(|(network, next_hop)| {
+                let next_hop = next_hop
                     .as_ref()
                     .map(|nh| nh.parse::<IpAddr>())
                     .transpose()
-                    .with_context(|| format!("invalid next-hop address: {:?}", p.next_hop))?;
-                Ok::<_, anyhow::Error>(PrefixEntry { network, next_hop })
+                    .with_context(|| format!("invalid next-hop address: {:?}", next_hop))?;
+                Ok::<_, anyhow::Error>(PrefixEntry {
+                    network,
+                    next_hop,
+                    path: None,
+                    peers: None,
+                })
             })
             .collect::<Result<Vec<_>, _>>()
             .context("invalid prefix in config")?;
+        if cfg.global.aggregate_prefixes {
+            let configured = prefixes.len();
+            prefixes = aggregate::aggregate(prefixes);
+            tracing::info!(
+                configured,
+                announced = prefixes.len(),
+                "aggregated configured prefixes"
+            );
+        }
+
+        let mut health_checked = cfg
+            .health_checked_prefixes()?
+            .into_iter()
+            .map(|group| {
+                let next_hop = group
+                    .next_hop
+                    .as_ref()
+                    .map(|nh| nh.parse::<IpAddr>())
+                    .transpose()
+                    .with_context(|| format!("invalid next-hop address: {:?}", group.next_hop))?;
+                let entries = group
+                    .networks
+                    .into_iter()
+                    .map(|network| PrefixEntry {
+                        network,
+                        next_hop,
+                        path: None,
+                        peers: None,
+                    })
+                    .collect();
+                Ok::<_, anyhow::Error>((entries, group.check))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let own_networks = prefixes
+            .iter()
+            .chain(health_checked.iter().flat_map(|(entries, _)| entries))
+            .map(|entry| entry.network)
+            .collect();
+
+        if let Some(source) = &cfg.rpki.roa_file {
+            let policy = cfg.rpki.invalid_originations;
+            match rpki::RoaTable::load(source).await {
+                Ok(roas) => {
+                    tracing::info!(source, roas = roas.len(), "loaded ROAs");
+                    let origin = cfg.global.asn;
+                    rpki::check_originations(&roas, &mut prefixes, origin, policy);
+                    for (entries, _) in &mut health_checked {
+                        rpki::check_originations(&roas, entries, origin, policy);
+                    }
+                }
+                Err(err) if policy == InvalidOriginations::Refuse => {
+                    return Err(
+                        err.context("[rpki].invalid_originations = \"refuse\" needs the ROA file")
+                    );
+                }
+                Err(err) => {
+                    tracing::warn!(source, error=%err, "failed loading ROAs, originations not checked")
+                }
+            }
+        }
+
+        let mut mrt_sources: Vec<(&str, Vec<String>)> = Vec::new();
+        for peer in cfg.peers.iter().filter(|p| p.enabled) {
+            let Some(path) = peer.announce_from_mrt.as_deref() else {
+                continue;
+            };
+            match mrt_sources.iter_mut().find(|(p, _)| *p == path) {
+                Some((_, peers)) => peers.push(peer.address.clone()),
+                None => mrt_sources.push((path, vec![peer.address.clone()])),
+            }
+        }
+        for (path, peers) in mrt_sources {
+            let loaded = load_mrt_entries(path, Some(peers.into())).await?;
+            tracing::info!(path, prefixes = loaded.len(), "loaded routes from MRT file");
+            prefixes.extend(loaded);
+        }
+        let mut prefix_lists = cfg
+            .compiled_prefix_lists()?
+            .into_iter()
+            .map(|(name, list)| (name, Arc::new(list)))
+            .collect::<HashMap<_, _>>();
+        let mut remote_etags = BTreeMap::new();
+        for (name, remote) in &cfg.remote_prefix_lists {
+            let (list, etag) = remote_lists::load_cached(name, remote);
+            prefix_lists.insert(name.clone(), Arc::new(list));
+            remote_etags.insert(name.clone(), etag);
+        }
 
         let inner = Arc::new(BgpServiceInner {
             global_asn: cfg.global.asn,
             router_id,
-            prefixes,
+            rib_view_name: cfg.archive.rib_view_name.clone(),
+            prefixes: std::sync::RwLock::new(prefixes),
+            prefix_lists: std::sync::RwLock::new(prefix_lists),
+            route_tx: broadcast::channel(ROUTE_CHANGE_BUFFER).0,
+            received_tx: broadcast::channel(RECEIVED_UPDATE_BUFFER).0,
             peers: RwLock::new(HashMap::new()),
-            event_tx,
+            events,
+            shutdown: watch::Sender::new(false),
+            update_rate: std::sync::Mutex::new(RateMeter::new(UPDATE_RATE_WINDOW_SECS)),
+            rib_counters: RibCounters::default(),
+            archive,
+            blackhole: cfg.blackhole.clone(),
+            blackholes: std::sync::Mutex::new(BTreeMap::new()),
+            leaks: LeakDetector::new(&cfg.peers, cfg.global.asn),
+            hijacks: HijackDetector::new(&cfg.hijack, own_networks, cfg.global.asn),
+            inbound: InboundLimiter::new(cfg.inbound_limits.clone()),
+            churn: ChurnTracker::new(&cfg.churn),
+            rib_in: AdjRibIn::new(&cfg.rib_in),
         });
 
         let service = Self { inner };
         service.start_peers(&cfg.peers).await;
+        service.spawn_remote_prefix_lists(&cfg.remote_prefix_lists, remote_etags);
+        if !health_checked.is_empty() {
+            service.spawn_health_checks(health_checked);
+        }
         Ok(service)
     }
 
@@ -116,16 +510,22 @@ impl BgpService {
             if !peer.enabled {
                 continue;
             }
-            let runtime = self.spawn_peer_task(peer.clone());
+            let runtime = self.spawn_peer_task(peer.clone(), None);
+            self.note_archive_peer(&runtime);
+            self.inner.rib_counters.peer_added();
             self.inner
                 .peers
                 .write()
                 .await
-                .insert(peer.address.clone(), runtime);
+                .insert(peer.address.clone(), Arc::new(RwLock::new(runtime)));
         }
     }
 
-    fn spawn_peer_task(&self, peer_cfg: PeerConfig) -> PeerRuntime {
+    fn spawn_peer_task(
+        &self,
+        peer_cfg: PeerConfig,
+        maintenance: Option<Maintenance>,
+    ) -> PeerRuntime {
         let local_as = peer_cfg.local_as.unwrap_or(self.inner.global_asn);
         let info = PeerInfo {
             address: peer_cfg.address.clone(),
@@ -139,122 +539,643 @@ impl BgpService {
             last_error: None,
             advertised_prefixes: 0,
             established_at: None,
+            updates_received: 0,
+            withdrawals_received: 0,
+            prefixes_received: 0,
+            prefixes_rejected: 0,
+            session_flaps: 0,
+            archive_records_shed: 0,
+            archive_duplicates_suppressed: 0,
+            maintenance,
+            log_messages: peer_cfg.log_messages,
+            capabilities: None,
+            remote_id: None,
         };
 
+        let message_log = Arc::new(MessageLog::new(&peer_cfg));
+        let timeline = Arc::new(SessionTimeline::new(peer_cfg.session_history));
         let service = self.clone();
         let address = peer_cfg.address.clone();
         let peer_for_task = peer_cfg.clone();
+        let (soft_reset, mut soft_reset_rx) = mpsc::channel(4);
+        let (maintenance, mut maintenance_rx) = watch::channel(maintenance);
+        let (cancel, cancel_rx) = watch::channel(false);
+        let timers = Arc::new(TimerState::default());
+        let task_timers = Arc::clone(&timers);
         let task = tokio::spawn(async move {
-            service.peer_loop(peer_for_task).await;
-            let mut peers = service.inner.peers.write().await;
-            if let Some(runtime) = peers.get_mut(&address) {
-                runtime.info.state = PeerState::Idle;
-            }
+            service
+                .peer_loop(
+                    peer_for_task,
+                    &mut soft_reset_rx,
+                    &mut maintenance_rx,
+                    &task_timers,
+                    &cancel_rx,
+                )
+                .await;
+            service
+                .set_peer_state(&address, PeerState::Idle, None, None)
+                .await;
         });
 
         PeerRuntime {
             info,
             cfg: peer_cfg,
             task,
+            cancel,
+            soft_reset,
+            maintenance,
+            update_rate: RateMeter::new(UPDATE_RATE_WINDOW_SECS),
+            recent_updates: MinuteCounts::default(),
+            flaps: FlapHistory::new(FLAP_WINDOW_SECS),
+            shed_notice: ShedNotice::default(),
+            local_ip: None,
+            timers,
+            message_log,
+            timeline,
         }
     }
 
-    async fn peer_loop(&self, peer: PeerConfig) {
+    async fn peer_loop(
+        &self,
+        peer: PeerConfig,
+        soft_reset: &mut mpsc::Receiver<SoftReset>,
+        maintenance: &mut watch::Receiver<Option<Maintenance>>,
+        timers: &TimerState,
+        cancel: &watch::Receiver<bool>,
+    ) {
+        // Index into `md5_keys_at`, advanced after each attempt that did not
+        // reach Established so a rotation window alternates the keys.
+        let mut key_attempt = 0usize;
         loop {
+            if self.is_stopping(cancel) {
+                return;
+            }
+            if maintenance
+                .borrow_and_update()
+                .is_some_and(|m| !m.keep_session)
+            {
+                self.set_peer_state(&peer.address, PeerState::Idle, None, None)
+                    .await;
+                tokio::select! {
+                    lifted = maintenance.wait_for(|m| m.is_none_or(|m| m.keep_session)) => {
+                        if lifted.is_err() {
+                            return;
+                        }
+                    }
+                    _ = self.stop_requested(cancel) => return,
+                }
+            }
+
             self.set_peer_state(&peer.address, PeerState::Connect, None, None)
                 .await;
 
+            let now = chrono::Utc::now().timestamp();
+            let keys = peer.md5_keys_at(now);
+            let index = key_attempt % keys.len().max(1);
+            if keys.len() > 1 {
+                let key = if index == 0 { "next" } else { "current" };
+                tracing::debug!(peer=%peer.address, key, "trying TCP-MD5 key in rotation window");
+            }
+            let ao_keys = peer.ao_keys_at(now);
+            let auth = if ao_keys.is_empty() {
+                keys.get(index).copied().map(TcpAuth::Md5)
+            } else {
+                Some(TcpAuth::Ao(
+                    ao_keys
+                        .into_iter()
+                        .map(|(id, key)| AoKey { id, key })
+                        .collect(),
+                ))
+            };
+            let auth = auth.as_ref();
             let result = if peer.passive {
-                self.run_passive_session(&peer).await
+                self.run_passive_session(&peer, auth, soft_reset, maintenance, timers, cancel)
+                    .await
+            } else if let Some(listen_port) = peer.listen_port {
+                self.run_dual_session(
+                    &peer,
+                    auth,
+                    listen_port,
+                    soft_reset,
+                    maintenance,
+                    timers,
+                    cancel,
+                )
+                .await
             } else {
-                self.run_active_session(&peer).await
+                self.run_active_session(&peer, auth, soft_reset, maintenance, timers, cancel)
+                    .await
             };
+            if !timers.session_up() {
+                key_attempt = key_attempt.wrapping_add(1);
+            }
+            timers.session_down();
+            if let Some(rib_in) = &self.inner.rib_in {
+                rib_in.clear_peer(&peer.address);
+            }
 
             match result {
+                // Stopped on request; the task goes Idle on its way out.
+                Ok(()) if self.is_stopping(cancel) => return,
                 Ok(()) => {
                     self.set_peer_state(&peer.address, PeerState::Active, None, None)
                         .await;
                 }
                 Err(err) => {
-                    self.set_peer_state(
-                        &peer.address,
-                        PeerState::Active,
-                        Some(err.to_string()),
-                        None,
-                    )
-                    .await;
+                    let error = PeerError::from_error(&err, chrono::Utc::now().timestamp());
+                    self.set_peer_state(&peer.address, PeerState::Active, Some(error), None)
+                        .await;
                 }
             }
 
-            sleep(Duration::from_secs(peer.connect_retry_secs as u64)).await;
+            let retry = Duration::from_secs(peer.connect_retry_secs as u64);
+            timers.connect_retry_in(retry);
+            tokio::select! {
+                _ = sleep(retry) => {}
+                _ = self.stop_requested(cancel) => return,
+            }
+            timers.connect_retry_fired();
         }
     }
 
-    async fn run_active_session(&self, peer: &PeerConfig) -> Result<()> {
-        let addr: SocketAddr = format!("{}:{}", peer.address, peer.remote_port)
-            .parse()
-            .with_context(|| {
-                format!("invalid peer socket {}:{}", peer.address, peer.remote_port)
-            })?;
+    async fn shutdown_requested(&self) {
+        let mut rx = self.inner.shutdown.subscribe();
+        let _ = rx.wait_for(|stopping| *stopping).await;
+    }
+
+    /// Whether the service is shutting down or this peer's task was
+    /// cancelled.
+    fn is_stopping(&self, cancel: &watch::Receiver<bool>) -> bool {
+        *self.inner.shutdown.borrow() || *cancel.borrow()
+    }
+
+    /// Cease subcode for a session closed by [`Self::stop_requested`]: a
+    /// cancelled peer task is a hard reset (RFC 4486), anything else is the
+    /// daemon shutting down.
+    fn stop_reason(&self) -> CeaseNotification {
+        if *self.inner.shutdown.borrow() {
+            CeaseNotification::ADMINISTRATIVE_SHUTDOWN
+        } else {
+            CeaseNotification::ADMINISTRATIVE_RESET
+        }
+    }
+
+    /// Resolves once [`Self::is_stopping`] holds. Sessions select on this
+    /// and close with a Cease rather than being aborted mid-message.
+    async fn stop_requested(&self, cancel: &watch::Receiver<bool>) {
+        let mut cancel = cancel.clone();
+        tokio::select! {
+            _ = self.shutdown_requested() => {}
+            _ = cancel.wait_for(|cancelled| *cancelled) => {}
+        }
+    }
+
+    /// Send Cease to every established peer and wait for peer tasks to exit.
+    ///
+    /// Tasks still running when `deadline` elapses are aborted.
+    pub async fn shutdown(&self, deadline: Duration) {
+        self.inner.shutdown.send_replace(true);
+
+        let aborted = join_peer_tasks(&self.peer_slots().await, deadline).await;
+        if aborted > 0 {
+            tracing::warn!(
+                pending = aborted,
+                "peer tasks did not stop before deadline, aborting"
+            );
+        }
+    }
 
-        let mut stream = connect_with_optional_bind(peer, addr).await?;
-        self.run_session(peer, &mut stream).await
+    async fn run_active_session(
+        &self,
+        peer: &PeerConfig,
+        auth: Option<&TcpAuth<'_>>,
+        soft_reset: &mut mpsc::Receiver<SoftReset>,
+        maintenance: &mut watch::Receiver<Option<Maintenance>>,
+        timers: &TimerState,
+        cancel: &watch::Receiver<bool>,
+    ) -> Result<()> {
+        let addr = remote_socket(peer)?;
+        let tcp = tokio::select! {
+            tcp = connect_with_optional_bind(peer, auth, addr) => tcp
+                .map_err(|err| SessionError::tagged(PeerErrorCode::TcpConnect, format!("{err:#}")))?,
+            _ = self.stop_requested(cancel) => return Ok(()),
+        };
+        let stream = self.session_stream(peer, tcp, true).await;
+        self.run_session(peer, stream, soft_reset, maintenance, timers, cancel)
+            .await
     }
 
-    async fn run_passive_session(&self, peer: &PeerConfig) -> Result<()> {
+    async fn run_passive_session(
+        &self,
+        peer: &PeerConfig,
+        auth: Option<&TcpAuth<'_>>,
+        soft_reset: &mut mpsc::Receiver<SoftReset>,
+        maintenance: &mut watch::Receiver<Option<Maintenance>>,
+        timers: &TimerState,
+        cancel: &watch::Receiver<bool>,
+    ) -> Result<()> {
         let listen_addr = peer
             .local_address
             .clone()
             .unwrap_or_else(|| format!("0.0.0.0:{}", peer.remote_port));
-        let listen: SocketAddr = normalize_socket_addr(&listen_addr, peer.remote_port)
-            .with_context(|| format!("invalid passive local_address {}", listen_addr))?;
+        let listen: SocketAddr =
+            normalize_socket_addr(&listen_addr, peer.remote_port).map_err(|_| {
+                SessionError::tagged(
+                    PeerErrorCode::Dns,
+                    format!("invalid passive local_address {listen_addr}"),
+                )
+            })?;
+        let listener = bind_listener(listen).await?;
 
-        let listener = TcpListener::bind(listen)
+        let tcp = tokio::select! {
+            tcp = self.accept_peer(peer, auth, &listener, false) => tcp?,
+            _ = self.stop_requested(cancel) => return Ok(()),
+        };
+        let stream = self.session_stream(peer, tcp, false).await;
+        self.run_session(peer, stream, soft_reset, maintenance, timers, cancel)
             .await
-            .with_context(|| format!("failed binding passive listener {listen}"))?;
+    }
+
+    /// An active peer with `listen_port` set: connect to the peer and accept
+    /// its connection at once. The first connection to receive the peer's
+    /// OPEN goes to OpenConfirm; should the other's OPEN arrive before the
+    /// peer's KEEPALIVE, the collision is resolved per RFC 4271 §6.8 and the
+    /// losing connection is closed with a Cease.
+    #[allow(clippy::too_many_arguments)]
+    async fn run_dual_session(
+        &self,
+        peer: &PeerConfig,
+        auth: Option<&TcpAuth<'_>>,
+        listen_port: u16,
+        soft_reset: &mut mpsc::Receiver<SoftReset>,
+        maintenance: &mut watch::Receiver<Option<Maintenance>>,
+        timers: &TimerState,
+        cancel: &watch::Receiver<bool>,
+    ) -> Result<()> {
+        let addr = remote_socket(peer)?;
+        let listen_ip = match peer.local_address.as_deref() {
+            Some(raw) => normalize_socket_addr(raw, 0)
+                .map_err(|_| {
+                    SessionError::tagged(
+                        PeerErrorCode::Dns,
+                        format!("invalid peer local_address {raw}"),
+                    )
+                })?
+                .ip(),
+            None if addr.is_ipv4() => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            None => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        };
+        let listener = bind_listener(SocketAddr::new(listen_ip, listen_port)).await?;
+
+        let outbound = async {
+            let tcp = connect_with_optional_bind(peer, auth, addr)
+                .await
+                .map_err(|err| {
+                    SessionError::tagged(PeerErrorCode::TcpConnect, format!("{err:#}"))
+                })?;
+            self.open_connection(peer, tcp, true).await
+        };
+        let inbound = async {
+            let tcp = self.accept_peer(peer, auth, &listener, true).await?;
+            self.open_connection(peer, tcp, false).await
+        };
+        tokio::pin!(outbound, inbound);
+        let (mut outbound_done, mut inbound_done) = (false, false);
+        let mut confirming: Option<OpenedConnection> = None;
+        // Only the peer's connection is left to wait for once ours failed,
+        // and that wait is bounded by connect_retry_secs.
+        let mut give_up: Option<Instant> = None;
+        let retry = Duration::from_secs(u64::from(peer.connect_retry_secs));
+
+        let mut winner = loop {
+            let opened = tokio::select! {
+                opened = &mut outbound, if !outbound_done => {
+                    outbound_done = true;
+                    opened
+                }
+                opened = &mut inbound, if !inbound_done => {
+                    inbound_done = true;
+                    opened
+                }
+                confirmed = async {
+                    match confirming.as_mut() {
+                        Some(connection) => {
+                            await_keepalive(&mut connection.stream, connection.confirm_by).await
+                        }
+                        None => std::future::pending().await,
+                    }
+                }, if confirming.is_some() => {
+                    match confirmed {
+                        Ok(()) => break confirming.take().expect("confirming connection"),
+                        Err(err) => {
+                            confirming = None;
+                            Err(err)
+                        }
+                    }
+                }
+                _ = sleep_until_some(give_up), if give_up.is_some() && confirming.is_none() => {
+                    return Err(SessionError::tagged(
+                        PeerErrorCode::TcpConnect,
+                        "no connection to or from the peer came up",
+                    ));
+                }
+                _ = self.stop_requested(cancel) => {
+                    if let Some(connection) = &mut confirming {
+                        return self.cease(&mut connection.stream, self.stop_reason()).await;
+                    }
+                    return Ok(());
+                }
+            };
+            match opened {
+                Ok(opened) => {
+                    confirming = Some(match confirming.take() {
+                        None => {
+                            self.set_peer_state(&peer.address, PeerState::OpenConfirm, None, None)
+                                .await;
+                            opened
+                        }
+                        Some(existing) => self.resolve_collision(peer, existing, opened).await,
+                    });
+                }
+                Err(err) if outbound_done && inbound_done && confirming.is_none() => {
+                    return Err(err)
+                }
+                Err(err) => {
+                    tracing::debug!(peer=%peer.address, error=%format!("{err:#}"), "connection attempt failed");
+                    if outbound_done && !inbound_done && give_up.is_none() {
+                        give_up = Some(Instant::now() + retry);
+                    }
+                }
+            }
+        };
+        self.run_established(
+            peer,
+            &mut winner.stream,
+            &winner.open,
+            soft_reset,
+            maintenance,
+            timers,
+            cancel,
+        )
+        .await
+    }
 
-        let (mut stream, peer_addr) = listener.accept().await?;
+    /// RFC 4271 §6.8: of two connections that both received the peer's OPEN,
+    /// keep the one opened by the side with the higher BGP Identifier and
+    /// close the other with a Cease. Returns the kept connection, already in
+    /// OpenConfirm.
+    async fn resolve_collision(
+        &self,
+        peer: &PeerConfig,
+        existing: OpenedConnection,
+        new: OpenedConnection,
+    ) -> OpenedConnection {
+        let keep_inbound = collision_keeps_inbound(self.inner.router_id, new.open.sender_ip);
+        let (kept, mut closed) = if existing.inbound == keep_inbound {
+            (existing, new)
+        } else {
+            (new, existing)
+        };
+        tracing::info!(
+            peer=%peer.address,
+            remote_id=%kept.open.sender_ip,
+            kept = if kept.inbound { "inbound" } else { "outbound" },
+            "connection collision resolved"
+        );
+        notify(
+            &mut closed.stream,
+            BgpError::CeaseNotification(CeaseNotification::CONNECTION_COLLISION_RESOLUTION),
+            Vec::new(),
+        )
+        .await;
+        kept
+    }
+
+    /// The next connection from `peer` on `listener`, admitted by
+    /// `[inbound_limits]`. With `only_peer`, connections from other
+    /// addresses are dropped.
+    async fn accept_peer(
+        &self,
+        peer: &PeerConfig,
+        auth: Option<&TcpAuth<'_>>,
+        listener: &TcpListener,
+        only_peer: bool,
+    ) -> Result<TcpStream> {
+        let peer_ip = peer.address.parse::<IpAddr>().ok();
+        // TCP-AO keys must be on the listener before the peer's SYN arrives;
+        // accepted connections inherit them.
+        if let (Some(auth @ TcpAuth::Ao(_)), Some(ip)) = (auth, peer_ip) {
+            let ip = match (listener.local_addr()?, ip) {
+                (SocketAddr::V6(_), IpAddr::V4(v4)) => IpAddr::V6(v4.to_ipv6_mapped()),
+                (_, ip) => ip,
+            };
+            listener
+                .set_tcp_auth(&SocketAddr::new(ip, 0), auth)
+                .context("failed to set TCP-AO keys on the listener")?;
+        }
+        let (stream, peer_addr) = loop {
+            let (stream, addr) = listener
+                .accept()
+                .await
+                .map_err(|err| SessionError::tagged(PeerErrorCode::TcpConnect, err))?;
+            if only_peer && Some(addr.ip().to_canonical()) != peer_ip {
+                tracing::debug!(peer=%peer.address, source=%addr, "connection from another address dropped");
+                continue;
+            }
+            match self
+                .inner
+                .inbound
+                .admit_at(addr.ip(), std::time::Instant::now())
+            {
+                Ok(()) => break (stream, addr),
+                // Dropped without a word; logging each one is what scanners
+                // would otherwise churn.
+                Err(reason) => {
+                    tracing::debug!(peer=%peer.address, source=%addr, reason=reason.as_str(), "inbound connection rejected");
+                }
+            }
+        };
 
         // Set TCP-MD5 signature if password is configured
         // Note: For passive mode, the MD5 must be set on the accepted socket
         // with the specific peer address
-        if let Some(password) = &peer.password {
+        if let Some(auth @ TcpAuth::Md5(_)) = auth {
             stream
-                .set_md5_signature(&peer_addr, password)
+                .set_tcp_auth(&peer_addr, auth)
                 .context("failed to set TCP-MD5 signature on accepted connection")?;
         }
+        Ok(stream)
+    }
 
-        self.run_session(peer, &mut stream).await
+    /// Wrap a new connection, noting our address on it for archived state
+    /// changes. `active` when we opened it.
+    async fn session_stream(
+        &self,
+        peer: &PeerConfig,
+        tcp: TcpStream,
+        active: bool,
+    ) -> SessionStream {
+        let (message_log, timeline) = match self.peer_slot(&peer.address).await {
+            Some(slot) => {
+                let mut runtime = slot.write().await;
+                // A dual-stack listener reports IPv4 sessions with mapped addresses.
+                runtime.local_ip = tcp.local_addr().ok().map(|a| a.ip().to_canonical());
+                (
+                    Arc::clone(&runtime.message_log),
+                    Arc::clone(&runtime.timeline),
+                )
+            }
+            None => (
+                Arc::new(MessageLog::new(peer)),
+                Arc::new(SessionTimeline::new(0)),
+            ),
+        };
+        SessionStream::new(tcp, peer, message_log, timeline, active)
     }
 
-    async fn run_session(&self, peer: &PeerConfig, stream: &mut TcpStream) -> Result<()> {
-        self.set_peer_state(&peer.address, PeerState::OpenSent, None, None)
-            .await;
+    /// OpenSent for one side of a dual session: exchange OPENs, then send
+    /// the KEEPALIVE that enters OpenConfirm. The peer stays in Connect
+    /// until one side gets this far, as the other may still be connecting.
+    async fn open_connection(
+        &self,
+        peer: &PeerConfig,
+        tcp: TcpStream,
+        active: bool,
+    ) -> Result<OpenedConnection> {
+        let mut stream = self.session_stream(peer, tcp, active).await;
+        let open = self.exchange_open(peer, &mut stream).await?;
+        write_bgp_message(&mut stream, &BgpMessage::KeepAlive).await?;
+        Ok(OpenedConnection {
+            confirm_by: Instant::now() + confirm_hold(peer, &open),
+            stream,
+            open,
+            inbound: !active,
+        })
+    }
 
+    /// Send our OPEN and receive and check the peer's, within the OpenSent
+    /// hold time.
+    async fn exchange_open(
+        &self,
+        peer: &PeerConfig,
+        stream: &mut SessionStream,
+    ) -> Result<BgpOpenMessage> {
         let local_as = peer.local_as.unwrap_or(self.inner.global_asn);
-        let hold_time = peer.hold_time_secs.max(3);
-
-        let open = BgpMessage::Open(BgpOpenMessage {
-            version: 4,
-            asn: local_as.into(),
-            hold_time,
-            sender_ip: self.inner.router_id,
-            extended_length: false,
-            opt_params: vec![],
-        });
-        write_bgp_message(stream, &open).await?;
+        let open = encode_open(
+            peer,
+            local_as,
+            peer.hold_time_secs.max(3),
+            self.inner.router_id,
+        );
+        timeout(
+            OPEN_HOLD_TIME,
+            open_handshake(stream, &open, peer, local_as, self.inner.router_id),
+        )
+        .await
+        .unwrap_or_else(|_| {
+            Err(SessionError::tagged(
+                PeerErrorCode::HoldExpired,
+                "timed out waiting for OPEN from peer",
+            ))
+        })
+    }
 
-        let incoming = read_bgp_message(stream).await?;
-        if !matches!(incoming, BgpMessage::Open(_)) {
-            return Err(anyhow!("expected OPEN from peer"));
-        }
+    async fn run_session(
+        &self,
+        peer: &PeerConfig,
+        mut stream: SessionStream,
+        soft_reset: &mut mpsc::Receiver<SoftReset>,
+        maintenance: &mut watch::Receiver<Option<Maintenance>>,
+        timers: &TimerState,
+        cancel: &watch::Receiver<bool>,
+    ) -> Result<()> {
+        let stream = &mut stream;
+        self.set_peer_state(&peer.address, PeerState::OpenSent, None, None)
+            .await;
+        let handshake = tokio::select! {
+            handshake = self.exchange_open(peer, stream) => handshake,
+            _ = self.stop_requested(cancel) => return self.cease(stream, self.stop_reason()).await,
+        };
+        // Only connections accepted by passive peers count toward
+        // `[inbound_limits]` bans.
+        let source = stream
+            .peer_addr()
+            .ok()
+            .map(|addr| addr.ip())
+            .filter(|_| peer.passive);
+        let remote_open = match (handshake, source) {
+            (Ok(remote_open), source) => {
+                if let Some(source) = source {
+                    self.inner.inbound.open_succeeded(source);
+                }
+                remote_open
+            }
+            (Err(err), Some(source)) => {
+                if let Some(ban) = self
+                    .inner
+                    .inbound
+                    .open_failed_at(source, std::time::Instant::now())
+                {
+                    tracing::warn!(peer=%peer.address, %source, ban_secs = ban.as_secs(), "banning source after repeated OPEN failures");
+                }
+                return Err(err);
+            }
+            (Err(err), None) => return Err(err),
+        };
 
         write_bgp_message(stream, &BgpMessage::KeepAlive).await?;
-        let incoming = read_bgp_message(stream).await?;
-        if !matches!(incoming, BgpMessage::KeepAlive) {
-            return Err(anyhow!("expected KEEPALIVE from peer after OPEN"));
+        self.set_peer_state(&peer.address, PeerState::OpenConfirm, None, None)
+            .await;
+        let confirm_by = Instant::now() + confirm_hold(peer, &remote_open);
+        tokio::select! {
+            confirmed = await_keepalive(stream, confirm_by) => confirmed?,
+            _ = self.stop_requested(cancel) => return self.cease(stream, self.stop_reason()).await,
         }
 
+        self.run_established(
+            peer,
+            stream,
+            &remote_open,
+            soft_reset,
+            maintenance,
+            timers,
+            cancel,
+        )
+        .await
+    }
+
+    /// Established: announce our prefixes, then exchange UPDATEs and
+    /// KEEPALIVEs until the session ends.
+    #[allow(clippy::too_many_arguments)]
+    async fn run_established(
+        &self,
+        peer: &PeerConfig,
+        stream: &mut SessionStream,
+        remote_open: &BgpOpenMessage,
+        soft_reset: &mut mpsc::Receiver<SoftReset>,
+        maintenance: &mut watch::Receiver<Option<Maintenance>>,
+        timers: &TimerState,
+        cancel: &watch::Receiver<bool>,
+    ) -> Result<()> {
+        let capabilities = NegotiatedCapabilities::negotiate(peer, remote_open);
+        let four_octet_as = capabilities.four_octet_as;
+        let asn_len = if four_octet_as {
+            AsnLength::Bits32
+        } else {
+            AsnLength::Bits16
+        };
+        let route_refresh = capabilities.route_refresh;
+        let refresh_families: Vec<_> = capabilities
+            .address_families
+            .iter()
+            .map(|family| family.afi_safi())
+            .collect();
+        self.set_negotiated(&peer.address, capabilities, remote_open.sender_ip)
+            .await;
+
+        // Requests queued while the session was down are stale.
+        while soft_reset.try_recv().is_ok() {}
+
         self.set_peer_state(
             &peer.address,
             PeerState::Established,
@@ -263,35 +1184,198 @@ impl BgpService {
         )
         .await;
 
-        self.send_prefix_announcements(peer, stream).await?;
+        let mut routes = self.inner.route_tx.subscribe();
+        let mut outbox = Outbox::new(peer.pacing.as_ref(), Instant::now());
+        // Soft-out requests, answered once their UPDATEs are on the wire.
+        let mut soft_out_replies: Vec<oneshot::Sender<Result<()>>> = Vec::new();
+        let current = *maintenance.borrow_and_update();
+        match current {
+            None => self.send_prefix_announcements(peer, &mut outbox),
+            Some(m) if m.keep_session => {}
+            // Maintenance began while the session was coming up.
+            Some(_) => {
+                return self
+                    .cease(stream, CeaseNotification::ADMINISTRATIVE_SHUTDOWN)
+                    .await
+            }
+        }
 
-        let negotiated_hold = Duration::from_secs(hold_time as u64);
-        let keepalive_interval = Duration::from_secs((hold_time as u64 / 3).max(1));
+        let negotiated_secs = negotiated_hold_secs(peer, remote_open);
+        timers.established(negotiated_secs);
+        let negotiated_hold = Duration::from_secs(negotiated_secs as u64);
+        let keepalive_interval = Duration::from_secs(keepalive_secs(negotiated_secs) as u64);
         let mut next_keepalive = Instant::now() + keepalive_interval;
         let mut hold_deadline = Instant::now() + negotiated_hold;
+        timers.keepalive_at(next_keepalive);
+        timers.hold_expires_at(hold_deadline);
+        let mut duplicates = DuplicateFilter::new(
+            self.inner
+                .archive
+                .as_ref()
+                .map(|archive| archive.dedup_window())
+                .unwrap_or_default(),
+        );
+
+        let mut ao_step = ao_rotation_at(peer, chrono::Utc::now().timestamp());
 
         loop {
             let now = Instant::now();
             if now >= next_keepalive {
                 write_bgp_message(stream, &BgpMessage::KeepAlive).await?;
                 next_keepalive = now + keepalive_interval;
+                timers.keepalive_at(next_keepalive);
             }
 
             if now >= hold_deadline {
-                return Err(anyhow!("hold timer expired"));
+                return Err(SessionError::tagged(
+                    PeerErrorCode::HoldExpired,
+                    "hold timer expired",
+                ));
             }
 
             let timeout_dur = std::cmp::min(
                 next_keepalive.saturating_duration_since(now),
                 Duration::from_secs(1),
             );
-            match timeout(timeout_dur, read_bgp_message(stream)).await {
-                Ok(Ok(msg)) => match msg {
-                    BgpMessage::KeepAlive | BgpMessage::Update(_) | BgpMessage::Open(_) => {
+            let read = tokio::select! {
+                read = timeout(timeout_dur, read_bgp_frame(stream)) => read,
+                _ = sleep_until_some(outbox.ready_at(now)) => {
+                    while let Some(update) = outbox.pop_ready(Instant::now()) {
+                        write_bgp_message_as(stream, &update, asn_len).await?;
+                    }
+                    self.set_advertised(&peer.address, outbox.advertised()).await;
+                    if outbox.is_empty() {
+                        for reply in soft_out_replies.drain(..) {
+                            let _ = reply.send(Ok(()));
+                        }
+                    }
+                    continue;
+                }
+                Some(request) = soft_reset.recv() => {
+                    match request.mode {
+                        ResetMode::SoftOut if maintenance.borrow().is_some() => {
+                            let _ = request.reply.send(Err(anyhow!(
+                                "peer {} is in maintenance",
+                                peer.address
+                            )));
+                        }
+                        ResetMode::SoftOut => {
+                            self.send_prefix_announcements(peer, &mut outbox);
+                            if outbox.is_empty() {
+                                let _ = request.reply.send(Ok(()));
+                            } else {
+                                soft_out_replies.push(request.reply);
+                            }
+                        }
+                        ResetMode::SoftIn => match route_refresh {
+                            true => {
+                                let mut result = Ok(());
+                                for &(afi, safi) in &refresh_families {
+                                    result = stream
+                                        .write_frame(&encode_route_refresh(afi, safi))
+                                        .await
+                                        .map_err(anyhow::Error::from);
+                                    if result.is_err() {
+                                        break;
+                                    }
+                                }
+                                let reply = result.as_ref().map(|_| ()).map_err(|e| anyhow!("{e}"));
+                                let _ = request.reply.send(reply);
+                                result?;
+                            }
+                            false if !peer.route_refresh => {
+                                let _ = request.reply.send(Err(anyhow!(
+                                    "route refresh is disabled for peer {}",
+                                    peer.address
+                                )));
+                            }
+                            false => {
+                                let _ = request.reply.send(Err(anyhow!(
+                                    "peer {} did not advertise the route refresh capability",
+                                    peer.address
+                                )));
+                            }
+                        },
+                        ResetMode::Hard => {
+                            let _ = request
+                                .reply
+                                .send(Err(anyhow!("hard reset is not a session request")));
+                        }
+                    }
+                    continue;
+                }
+                _ = sleep_until_some(ao_step), if ao_step.is_some() => {
+                    rotate_ao_keys(peer, stream);
+                    ao_step = ao_rotation_at(peer, chrono::Utc::now().timestamp());
+                    continue;
+                }
+                changed = maintenance.changed() => {
+                    if changed.is_err() {
+                        return Ok(());
+                    }
+                    let current = *maintenance.borrow_and_update();
+                    match current {
+                        None => self.send_prefix_announcements(peer, &mut outbox),
+                        // Closing the session withdraws everything anyway.
+                        Some(m) if !m.keep_session => {
+                            return self
+                                .cease(stream, CeaseNotification::ADMINISTRATIVE_SHUTDOWN)
+                                .await
+                        }
+                        Some(_) => self.withdraw_announcements(peer, &mut outbox),
+                    }
+                    continue;
+                }
+                change = routes.recv() => {
+                    // Nothing is announced to a peer in maintenance.
+                    if maintenance.borrow().is_some() {
+                        continue;
+                    }
+                    match change {
+                        Ok(change) => self.send_route_change(peer, &mut outbox, change),
+                        // Missed changes: re-send the full set instead.
+                        Err(broadcast::error::RecvError::Lagged(_)) => {
+                            self.send_prefix_announcements(peer, &mut outbox)
+                        }
+                        Err(broadcast::error::RecvError::Closed) => {}
+                    }
+                    continue;
+                }
+                _ = self.stop_requested(cancel) => return self.cease(stream, self.stop_reason()).await,
+            };
+            match read {
+                Ok(Ok(frame)) if is_route_refresh(&frame) => {
+                    hold_deadline = Instant::now() + negotiated_hold;
+                    timers.hold_expires_at(hold_deadline);
+                    // The peer asks for our routes again (RFC 2918).
+                    if route_refresh && maintenance.borrow().is_none() {
+                        self.send_prefix_announcements(peer, &mut outbox);
+                    }
+                }
+                Ok(Ok(frame)) => match parse_bgp_frame(frame.clone(), asn_len)? {
+                    BgpMessage::Update(update) => {
+                        hold_deadline = Instant::now() + negotiated_hold;
+                        timers.hold_expires_at(hold_deadline);
+                        self.record_update(peer, &update).await;
+                        self.check_route_leak(peer, &update);
+                        self.check_hijack(peer, &update);
+                        self.publish_received(
+                            peer,
+                            stream,
+                            remote_open.sender_ip,
+                            four_octet_as,
+                            &update,
+                            &frame,
+                        );
+                        self.archive_update(peer, stream, &frame, &update, &mut duplicates)
+                            .await;
+                    }
+                    BgpMessage::KeepAlive | BgpMessage::Open(_) => {
                         hold_deadline = Instant::now() + negotiated_hold;
+                        timers.hold_expires_at(hold_deadline);
                     }
-                    BgpMessage::Notification(_) => {
-                        return Err(anyhow!("received NOTIFICATION from peer"));
+                    BgpMessage::Notification(notification) => {
+                        return Err(SessionError::notification(&notification.error, "from peer"));
                     }
                 },
                 Ok(Err(err)) => return Err(err),
@@ -300,133 +1384,1092 @@ impl BgpService {
         }
     }
 
-    async fn send_prefix_announcements(
-        &self,
-        peer: &PeerConfig,
-        stream: &mut TcpStream,
-    ) -> Result<()> {
-        let local_as = peer.local_as.unwrap_or(self.inner.global_asn);
-        let router_id = self.inner.router_id;
-
-        for prefix_entry in &self.inner.prefixes {
-            let update = build_announce_update(prefix_entry, router_id, local_as);
-            write_bgp_message(stream, &update).await?;
-        }
-
-        let count = self.inner.prefixes.len();
-        let mut peers = self.inner.peers.write().await;
-        if let Some(runtime) = peers.get_mut(&peer.address) {
-            runtime.info.advertised_prefixes = count;
-        }
-
+    /// Close the session with a Cease of `subcode`.
+    async fn cease(&self, stream: &mut SessionStream, subcode: CeaseNotification) -> Result<()> {
+        let cease = BgpMessage::Notification(BgpNotificationMessage {
+            error: BgpError::CeaseNotification(subcode),
+            data: vec![],
+        });
+        write_bgp_message(stream, &cease).await?;
+        let _ = stream.shutdown().await;
         Ok(())
     }
 
-    async fn set_peer_state(
-        &self,
-        address: &str,
-        state: PeerState,
-        last_error: Option<String>,
-        established_at: Option<i64>,
-    ) {
-        let mut peers = self.inner.peers.write().await;
-        if let Some(runtime) = peers.get_mut(address) {
-            runtime.info.state = state;
-            if let Some(err) = last_error {
-                runtime.info.last_error = Some(err);
-            } else if matches!(state, PeerState::Established) {
-                runtime.info.last_error = None;
-            }
-            if let Some(ts) = established_at {
-                runtime.info.established_at = Some(ts);
-            }
-            let _ = self
-                .inner
-                .event_tx
-                .send(EventEnvelope::new(Event::PeerState {
-                    peer: address.to_string(),
-                    state,
-                }));
+    /// Withdraw every route exported to the peer, keeping the session up.
+    fn withdraw_announcements(&self, peer: &PeerConfig, outbox: &mut Outbox) {
+        for entry in self.exported_prefixes(peer) {
+            outbox.withdraw(entry.network, build_withdraw_update(entry.network));
         }
     }
 
-    pub async fn peer_list(&self) -> Vec<PeerInfo> {
+    /// Whether the peer's `export_prefix_list` permits announcing `network`.
+    fn exports(&self, peer: &PeerConfig, network: &IpNet) -> bool {
+        peer.export_prefix_list
+            .as_ref()
+            .and_then(|name| self.prefix_list(name))
+            .is_none_or(|list| list.matches(network))
+    }
+
+    fn prefix_list(&self, name: &str) -> Option<Arc<PrefixList>> {
         self.inner
-            .peers
+            .prefix_lists
             .read()
-            .await
-            .values()
-            .map(|r| r.info.clone())
-            .collect()
+            .expect("prefix lists lock poisoned")
+            .get(name)
+            .cloned()
     }
 
-    pub async fn peer_show(&self, peer: &str) -> Option<PeerInfo> {
+    /// Originated prefixes permitted by the peer's `export_prefix_list`.
+    fn exported_prefixes(&self, peer: &PeerConfig) -> Vec<PrefixEntry> {
         self.inner
-            .peers
+            .prefixes
             .read()
-            .await
-            .get(peer)
-            .map(|r| r.info.clone())
+            .expect("prefixes lock poisoned")
+            .iter()
+            .filter(|p| p.announced_to(peer) && self.exports(peer, &p.network))
+            .cloned()
+            .collect()
+    }
+
+    fn send_route_change(&self, peer: &PeerConfig, outbox: &mut Outbox, change: RouteChange) {
+        let prepend = local_as::outbound_prepend(peer, self.inner.global_asn);
+        match &change {
+            RouteChange::Announce(entry)
+                if entry.announced_to(peer) && self.exports(peer, &entry.network) =>
+            {
+                outbox.announce(
+                    entry.network,
+                    build_announce_update(entry, self.inner.router_id, &prepend),
+                );
+            }
+            RouteChange::Withdraw(network) if self.exports(peer, network) => {
+                outbox.withdraw(*network, build_withdraw_update(*network));
+            }
+            RouteChange::Refresh => self.send_prefix_announcements(peer, outbox),
+            RouteChange::PrefixListChanged { name, previous }
+                if peer.export_prefix_list.as_ref() == Some(name) =>
+            {
+                let dropped = self
+                    .inner
+                    .prefixes
+                    .read()
+                    .expect("prefixes lock poisoned")
+                    .iter()
+                    .filter(|p| p.announced_to(peer) && previous.matches(&p.network))
+                    .filter(|p| !self.exports(peer, &p.network))
+                    .map(|p| p.network)
+                    .collect::<Vec<_>>();
+                for network in dropped {
+                    outbox.withdraw(network, build_withdraw_update(network));
+                }
+                self.send_prefix_announcements(peer, outbox);
+            }
+            _ => {}
+        }
+    }
+
+    /// Originate `network` at runtime, replacing any existing entry for it,
+    /// and announce it to every established peer that exports it.
+    pub fn announce_route(&self, network: IpNet, next_hop: Option<IpAddr>) {
+        self.originate(PrefixEntry {
+            network: network.trunc(),
+            next_hop,
+            path: None,
+            peers: None,
+        });
     }
 
-    pub async fn peer_reset(&self, peer: &str) -> Result<()> {
-        let old = {
-            let mut peers = self.inner.peers.write().await;
-            peers.remove(peer)
+    /// Add `entry`, replacing one for the same prefix and peers, and announce it.
+    fn originate(&self, entry: PrefixEntry) {
+        {
+            let mut prefixes = self.inner.prefixes.write().expect("prefixes lock poisoned");
+            match prefixes
+                .iter_mut()
+                .find(|p| p.network == entry.network && p.peers == entry.peers)
+            {
+                Some(existing) => *existing = entry.clone(),
+                None => prefixes.push(entry.clone()),
+            }
+        }
+        let _ = self.inner.route_tx.send(RouteChange::Announce(entry));
+    }
+
+    /// Remove the entry for `network` and `peers` and withdraw it. Other
+    /// entries for the same prefix are announced again afterwards.
+    fn withdraw_entry(&self, network: IpNet, peers: &Option<Arc<[String]>>) -> bool {
+        let remaining = {
+            let mut prefixes = self.inner.prefixes.write().expect("prefixes lock poisoned");
+            let before = prefixes.len();
+            prefixes.retain(|p| !(p.network == network && &p.peers == peers));
+            if prefixes.len() == before {
+                return false;
+            }
+            prefixes
+                .iter()
+                .filter(|p| p.network == network)
+                .cloned()
+                .collect::<Vec<_>>()
         };
+        let _ = self.inner.route_tx.send(RouteChange::Withdraw(network));
+        for entry in remaining {
+            let _ = self.inner.route_tx.send(RouteChange::Announce(entry));
+        }
+        true
+    }
 
-        let Some(old_runtime) = old else {
-            return Err(anyhow!("peer {} not found", peer));
+    /// Stop originating `network` and withdraw it from established peers.
+    pub fn withdraw_route(&self, network: IpNet) -> Result<()> {
+        let network = network.trunc();
+        {
+            let mut prefixes = self.inner.prefixes.write().expect("prefixes lock poisoned");
+            let before = prefixes.len();
+            prefixes.retain(|p| p.network != network);
+            if prefixes.len() == before {
+                return Err(anyhow!("prefix {} is not originated", network));
+            }
+        }
+        let _ = self.inner.route_tx.send(RouteChange::Withdraw(network));
+        Ok(())
+    }
+
+    /// Originate every route left by the MRT file at `path`, replacing
+    /// earlier entries for the same prefixes and peers, and re-send the
+    /// announcements of established peers. `peers` limits the routes to those
+    /// peers; empty means every peer. Returns the number of routes loaded.
+    pub async fn load_mrt(&self, path: &str, peers: Vec<String>) -> Result<usize> {
+        let peers: Option<Arc<[String]>> = if peers.is_empty() {
+            None
+        } else {
+            let known = self.inner.peers.read().await;
+            if let Some(unknown) = peers.iter().find(|p| !known.contains_key(*p)) {
+                return Err(anyhow!("peer not found: {unknown}"));
+            }
+            Some(peers.into())
+        };
+        let loaded = load_mrt_entries(path, peers.clone()).await?;
+        let count = loaded.len();
+        {
+            let networks = loaded
+                .iter()
+                .map(|p| p.network)
+                .collect::<std::collections::HashSet<_>>();
+            let mut prefixes = self.inner.prefixes.write().expect("prefixes lock poisoned");
+            prefixes.retain(|p| p.peers != peers || !networks.contains(&p.network));
+            prefixes.extend(loaded);
+        }
+        let _ = self.inner.route_tx.send(RouteChange::Refresh);
+        Ok(count)
+    }
+
+    /// Every UPDATE received from any peer, as it arrives.
+    pub fn subscribe_received(&self) -> broadcast::Receiver<ReceivedUpdate> {
+        self.inner.received_tx.subscribe()
+    }
+
+    fn publish_received(
+        &self,
+        peer: &PeerConfig,
+        stream: &TcpStream,
+        peer_bgp_id: Ipv4Addr,
+        four_octet_as: bool,
+        update: &BgpUpdateMessage,
+        raw: &Bytes,
+    ) {
+        if self.inner.received_tx.receiver_count() == 0 {
+            return;
+        }
+        let now = chrono::Utc::now();
+        let _ = self.inner.received_tx.send(ReceivedUpdate {
+            timestamp: now.timestamp_micros() as f64 / 1e6,
+            peer: peer.address.clone(),
+            peer_as: peer.remote_as,
+            local_as: peer.local_as.unwrap_or(self.inner.global_asn),
+            local_address: stream.local_addr().ok().map(|a| a.ip()),
+            peer_bgp_id,
+            four_octet_as,
+            update: update.clone(),
+            raw: raw.clone(),
+        });
+    }
+
+    /// Queue an UPDATE for every prefix exported to the peer.
+    fn send_prefix_announcements(&self, peer: &PeerConfig, outbox: &mut Outbox) {
+        let prepend = local_as::outbound_prepend(peer, self.inner.global_asn);
+        let router_id = self.inner.router_id;
+
+        for prefix_entry in self.exported_prefixes(peer) {
+            outbox.announce(
+                prefix_entry.network,
+                build_announce_update(&prefix_entry, router_id, &prepend),
+            );
+        }
+    }
+
+    /// `count` is what the session has sent; UPDATEs still queued are not in it.
+    async fn set_advertised(&self, address: &str, count: usize) {
+        if let Some(slot) = self.peer_slot(address).await {
+            let mut runtime = slot.write().await;
+            self.inner
+                .rib_counters
+                .advertised_changed(runtime.info.advertised_prefixes, count);
+            runtime.info.advertised_prefixes = count;
+        }
+    }
+
+    async fn set_negotiated(
+        &self,
+        address: &str,
+        capabilities: NegotiatedCapabilities,
+        remote_id: Ipv4Addr,
+    ) {
+        if let Some(slot) = self.peer_slot(address).await {
+            let info = &mut slot.write().await.info;
+            info.capabilities = Some(capabilities);
+            info.remote_id = Some(remote_id);
+        }
+    }
+
+    async fn set_peer_state(
+        &self,
+        address: &str,
+        state: PeerState,
+        last_error: Option<PeerError>,
+        established_at: Option<i64>,
+    ) {
+        if let Some(slot) = self.peer_slot(address).await {
+            let mut runtime = slot.write().await;
+            if matches!(runtime.info.state, PeerState::Established)
+                && !matches!(state, PeerState::Established)
+            {
+                runtime.info.session_flaps += 1;
+                runtime.flaps.record(chrono::Utc::now().timestamp());
+            }
+            let old_state = runtime.info.state;
+            if old_state != state {
+                runtime.timeline.record(SessionEventKind::StateChange {
+                    from: old_state,
+                    to: state,
+                });
+            }
+            self.count_state(&runtime, state);
+            let state_record = if fsm_state_code(old_state) != fsm_state_code(state) {
+                self.peer_state_record(&runtime, old_state, state)
+            } else {
+                None
+            };
+            runtime.info.state = state;
+            if let Some(ts) = established_at {
+                runtime.info.established_at = Some(ts);
+            }
+            self.note_archive_peer(&runtime);
+            if let Some(err) = last_error {
+                runtime.timeline.record((&err).into());
+                runtime.info.last_error = Some(err);
+            } else if matches!(state, PeerState::Established) {
+                runtime.info.last_error = None;
+            }
+            if !matches!(state, PeerState::Established) {
+                runtime.info.capabilities = None;
+            }
+            self.inner.events.publish(Event::PeerState {
+                peer: address.to_string(),
+                state,
+            });
+            drop(runtime);
+
+            // A full ingest queue can make this wait; not under the peer lock.
+            if let (Some(archive), Some(record)) = (&self.inner.archive, state_record) {
+                if !archive.submit(IngestRecord::PeerState(record)).await {
+                    self.note_archive_shed(&mut *slot.write().await);
+                }
+            }
+        }
+    }
+
+    /// Peers in maintenance are kept out of the session counters.
+    fn count_state(&self, runtime: &PeerRuntime, state: PeerState) {
+        if runtime.info.maintenance.is_none() {
+            self.inner
+                .rib_counters
+                .state_changed(runtime.info.state, state);
+        }
+    }
+
+    /// Keep the archive's peers sidecar current; peers kept out of the
+    /// updates stream are left out of it too.
+    fn note_archive_peer(&self, runtime: &PeerRuntime) {
+        let Some(archive) = &self.inner.archive else {
+            return;
+        };
+        let Ok(peer_ip) = runtime.cfg.address.parse() else {
+            return;
+        };
+        if !archive.archives_updates(&runtime.cfg, peer_ip) {
+            return;
+        }
+        archive.note_peer(
+            runtime.cfg.archive.collector_id.clone(),
+            SegmentPeer {
+                address: runtime.info.address.clone(),
+                asn: runtime.info.remote_as,
+                name: runtime.info.name.clone(),
+                state: runtime.info.state,
+                established_at: runtime.info.established_at,
+                maintenance: runtime.info.maintenance.is_some(),
+            },
+        );
+    }
+
+    async fn archive_update(
+        &self,
+        peer: &PeerConfig,
+        stream: &TcpStream,
+        frame: &Bytes,
+        update: &BgpUpdateMessage,
+        duplicates: &mut DuplicateFilter,
+    ) {
+        let Some(archive) = &self.inner.archive else {
+            return;
+        };
+        let (Ok(peer_ip), Ok(local)) = (peer.address.parse::<IpAddr>(), stream.local_addr()) else {
+            return;
+        };
+        if !archive.archives_updates(peer, peer_ip) {
+            return;
+        }
+        if duplicates.is_duplicate_at(frame, std::time::Instant::now()) {
+            archive.note_duplicate_suppressed();
+            if let Some(slot) = self.peer_slot(&peer.address).await {
+                slot.write().await.info.archive_duplicates_suppressed += 1;
+            }
+            return;
+        }
+        if withdrawn_count(update) == 0 && !archive.sample_update() {
+            return;
+        }
+        // A dual-stack listener reports IPv4 sessions with mapped addresses.
+        let local_ip = local.ip().to_canonical();
+        if local_ip.is_ipv4() != peer_ip.is_ipv4() {
+            return;
+        }
+
+        let input = UpdateRecordInput {
+            timestamp: chrono::Utc::now().timestamp(),
+            peer_asn: peer.remote_as,
+            local_asn: peer.local_as.unwrap_or(self.inner.global_asn),
+            interface_index: 0,
+            peer_ip,
+            local_ip,
+            bgp_message: frame.to_vec(),
+            family: update_family(update),
+            collector_id: peer.archive.collector_id.clone(),
         };
+        if archive.submit(IngestRecord::Update(input)).await {
+            return;
+        }
+        if let Some(slot) = self.peer_slot(&peer.address).await {
+            self.note_archive_shed(&mut *slot.write().await);
+        }
+    }
+
+    /// The state change record to archive, if the peer's are recorded.
+    fn peer_state_record(
+        &self,
+        runtime: &PeerRuntime,
+        old: PeerState,
+        new: PeerState,
+    ) -> Option<PeerStateRecordInput> {
+        self.inner.archive.as_ref()?;
+        if !runtime.cfg.archive.state_changes {
+            return None;
+        }
+        let peer_ip = runtime.cfg.address.parse::<IpAddr>().ok()?;
+        // Unspecified until the first session is up.
+        let local_ip = match (peer_ip, runtime.local_ip) {
+            (IpAddr::V4(_), Some(local @ IpAddr::V4(_)))
+            | (IpAddr::V6(_), Some(local @ IpAddr::V6(_))) => local,
+            (IpAddr::V4(_), _) => IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+            (IpAddr::V6(_), _) => IpAddr::V6(Ipv6Addr::UNSPECIFIED),
+        };
+
+        Some(PeerStateRecordInput {
+            timestamp: chrono::Utc::now().timestamp(),
+            peer_asn: runtime.info.remote_as,
+            local_asn: runtime.info.local_as,
+            interface_index: 0,
+            peer_ip,
+            local_ip,
+            old_state: fsm_state_code(old),
+            new_state: fsm_state_code(new),
+            collector_id: runtime.cfg.archive.collector_id.clone(),
+        })
+    }
+
+    fn note_archive_shed(&self, runtime: &mut PeerRuntime) {
+        runtime.info.archive_records_shed += 1;
+        if let Some(shed) = runtime
+            .shed_notice
+            .record_at(std::time::Instant::now(), SHED_EVENT_INTERVAL)
+        {
+            tracing::warn!(peer=%runtime.cfg.address, shed, "archive ingest queue full, records shed");
+            self.inner.events.publish(Event::ArchiveRecordsShed {
+                peer: runtime.cfg.address.clone(),
+                shed,
+            });
+        }
+    }
 
-        old_runtime.task.abort();
+    async fn record_update(&self, peer: &PeerConfig, update: &BgpUpdateMessage) {
+        let address = peer.address.as_str();
+        let withdrawn = withdrawn_count(update);
 
-        let runtime = self.spawn_peer_task(old_runtime.cfg);
         self.inner
-            .peers
-            .write()
+            .update_rate
+            .lock()
+            .expect("update rate lock poisoned")
+            .record(1);
+
+        let announced = announced_prefixes(update);
+        let now = chrono::Utc::now().timestamp();
+        if self.inner.churn.is_some() || self.inner.rib_in.is_some() {
+            let withdrawn_nets = withdrawn_prefixes(update);
+            if let Some(churn) = &self.inner.churn {
+                churn.record_at(&announced, &withdrawn_nets, now);
+            }
+            if let Some(rib_in) = &self.inner.rib_in {
+                let received = update
+                    .attributes
+                    .as_path()
+                    .and_then(|path| path.to_u32_vec_opt(false))
+                    .unwrap_or_default();
+                let as_path = local_as::inbound_prepend(peer, self.inner.global_asn)
+                    .into_iter()
+                    .chain(received)
+                    .collect::<Vec<_>>();
+                let changes = rib_in.update_at(address, &announced, &as_path, &withdrawn_nets, now);
+                for change in changes {
+                    let old_origin = change.old_path.last().copied();
+                    let new_origin = change.new_path.last().copied();
+                    if old_origin != new_origin {
+                        tracing::info!(peer=%address, prefix=%change.prefix, ?old_origin, ?new_origin, "watched prefix changed origin");
+                    }
+                    self.inner.events.publish(Event::OriginChanged {
+                        peer: address.to_string(),
+                        prefix: change.prefix.to_string(),
+                        old_origin,
+                        new_origin,
+                        old_as_path: change.old_path.to_vec(),
+                        new_as_path: change.new_path.to_vec(),
+                    });
+                }
+            }
+        }
+
+        if let Some(slot) = self.peer_slot(address).await {
+            let mut runtime = slot.write().await;
+            let import = runtime
+                .cfg
+                .import_prefix_list
+                .as_ref()
+                .and_then(|name| self.prefix_list(name));
+            let rejected = import
+                .map(|list| announced.iter().filter(|p| !list.matches(p)).count())
+                .unwrap_or(0);
+            runtime.info.updates_received += 1;
+            runtime.info.withdrawals_received += withdrawn as u64;
+            runtime.info.prefixes_received += announced.len() as u64;
+            self.inner
+                .rib_counters
+                .prefixes_received(announced.len() as u64);
+            runtime.info.prefixes_rejected += rejected as u64;
+            runtime.update_rate.record(1);
+            runtime.recent_updates.record_at(withdrawn as u64, now);
+        }
+    }
+
+    fn check_route_leak(&self, peer: &PeerConfig, update: &BgpUpdateMessage) {
+        let Some(role) = peer.role else {
+            return;
+        };
+        let prefixes = announced_prefixes(update);
+        if prefixes.is_empty() {
+            return;
+        }
+        let Some(leak) = self
+            .inner
+            .leaks
+            .check(role, peer.remote_as, &update.attributes)
+        else {
+            return;
+        };
+        tracing::warn!(peer=%peer.address, as_path=?leak.as_path, reason=%leak.reason, "suspected route leak");
+        self.inner.events.publish(Event::RouteLeakSuspected {
+            peer: peer.address.clone(),
+            prefixes: prefixes.iter().map(ToString::to_string).collect(),
+            as_path: leak.as_path,
+            reason: leak.reason,
+        });
+    }
+
+    fn check_hijack(&self, peer: &PeerConfig, update: &BgpUpdateMessage) {
+        let prefixes = announced_prefixes(update);
+        if prefixes.is_empty() {
+            return;
+        }
+        let as_path = update
+            .attributes
+            .as_path()
+            .and_then(|path| path.to_u32_vec_opt(false))
+            .unwrap_or_default();
+        for hijack in self.inner.hijacks.check(&prefixes, &as_path) {
+            tracing::warn!(peer=%peer.address, prefix=%hijack.prefix, origin=hijack.origin, as_path=?as_path, "suspected hijack of our prefix");
+            self.inner.events.publish(Event::PrefixHijackSuspected {
+                peer: peer.address.clone(),
+                prefix: hijack.prefix.to_string(),
+                announced: hijack.announced.iter().map(ToString::to_string).collect(),
+                origin: hijack.origin,
+                as_path: as_path.clone(),
+            });
+        }
+    }
+
+    pub async fn bgp_stats(&self, top: usize) -> BgpStats {
+        let now = chrono::Utc::now().timestamp();
+        let mut talkers = Vec::new();
+        for slot in self.peer_slots().await {
+            let r = slot.read().await;
+            talkers.push(PeerTalker {
+                address: r.info.address.clone(),
+                name: r.info.name.clone(),
+                updates_per_sec: r.update_rate.rate(),
+                updates_received: r.info.updates_received,
+                withdrawals_received: r.info.withdrawals_received,
+                flaps_last_hour: r.flaps.count(now),
+            });
+        }
+
+        let updates_received_total = talkers.iter().map(|t| t.updates_received).sum();
+        let withdrawals_received_total = talkers.iter().map(|t| t.withdrawals_received).sum();
+        let session_flaps_last_hour = talkers.iter().map(|t| t.flaps_last_hour).sum();
+
+        talkers.sort_by(|a, b| {
+            b.updates_per_sec
+                .total_cmp(&a.updates_per_sec)
+                .then_with(|| b.updates_received.cmp(&a.updates_received))
+        });
+        talkers.truncate(top);
+
+        BgpStats {
+            updates_per_sec: self
+                .inner
+                .update_rate
+                .lock()
+                .expect("update rate lock poisoned")
+                .rate(),
+            updates_received_total,
+            withdrawals_received_total,
+            session_flaps_last_hour,
+            top_talkers: talkers,
+            inbound_rejected: self.inner.inbound.rejected(),
+            inbound_banned_sources: self.inner.inbound.banned(),
+        }
+    }
+
+    /// Peers ranked by UPDATEs and prefixes by announcements over the last
+    /// `minutes` minutes (at most [`MinuteCounts::MAX_MINUTES`]).
+    pub async fn bgp_top(&self, minutes: u32, limit: usize) -> BgpTop {
+        let minutes = minutes.clamp(1, MinuteCounts::MAX_MINUTES);
+        let now = chrono::Utc::now().timestamp();
+        let mut peers = Vec::new();
+        for slot in self.peer_slots().await {
+            let r = slot.read().await;
+            let (updates, withdrawals) = r.recent_updates.totals_at(minutes, now);
+            if updates == 0 {
+                continue;
+            }
+            peers.push(TopPeer {
+                address: r.info.address.clone(),
+                name: r.info.name.clone(),
+                updates,
+                withdrawals,
+                updates_per_sec: updates as f64 / f64::from(minutes * 60),
+            });
+        }
+        peers.sort_by(|a, b| {
+            b.updates
+                .cmp(&a.updates)
+                .then_with(|| a.address.cmp(&b.address))
+        });
+        peers.truncate(limit);
+
+        let prefixes = self
+            .inner
+            .churn
+            .as_ref()
+            .map(|churn| churn.top_by_at(minutes, limit, now, |p| p.announcements));
+        BgpTop {
+            minutes,
+            peers,
+            prefixes,
+        }
+    }
+
+    pub fn global_asn(&self) -> u32 {
+        self.inner.global_asn
+    }
+
+    pub fn router_id(&self) -> Ipv4Addr {
+        self.inner.router_id
+    }
+
+    async fn peer_slot(&self, address: &str) -> Option<PeerSlot> {
+        self.inner.peers.read().await.get(address).cloned()
+    }
+
+    async fn peer_slots(&self) -> Vec<PeerSlot> {
+        self.inner.peers.read().await.values().cloned().collect()
+    }
+
+    pub async fn peer_list(&self) -> Vec<PeerInfo> {
+        let mut infos = Vec::new();
+        for slot in self.peer_slots().await {
+            infos.push(slot.read().await.info.clone());
+        }
+        infos
+    }
+
+    pub async fn peer_totals(&self) -> Vec<PeerTotals> {
+        let mut totals = Vec::new();
+        for slot in self.peer_slots().await {
+            let info = &slot.read().await.info;
+            totals.push(PeerTotals {
+                peer: info.address.clone(),
+                updates_received: info.updates_received,
+                withdrawals_received: info.withdrawals_received,
+                prefixes_received: info.prefixes_received,
+                prefixes_rejected: info.prefixes_rejected,
+                session_flaps: info.session_flaps,
+                last_established: info.established_at,
+            });
+        }
+        totals
+    }
+
+    /// Add counters saved by a previous run to the configured peers; saved
+    /// peers no longer configured are skipped.
+    pub async fn restore_peer_totals(&self, saved: &[PeerTotals]) {
+        for totals in saved {
+            let Some(slot) = self.peer_slot(&totals.peer).await else {
+                continue;
+            };
+            let info = &mut slot.write().await.info;
+            info.updates_received += totals.updates_received;
+            info.withdrawals_received += totals.withdrawals_received;
+            info.prefixes_received += totals.prefixes_received;
+            info.prefixes_rejected += totals.prefixes_rejected;
+            info.session_flaps += totals.session_flaps;
+            if info.established_at.is_none() {
+                info.established_at = totals.last_established;
+            }
+            self.inner
+                .rib_counters
+                .prefixes_received(totals.prefixes_received);
+        }
+    }
+
+    /// Zero the cumulative counters of `peer`, or of every peer without
+    /// one. Returns the peers reset.
+    pub async fn reset_peer_totals(&self, peer: Option<&str>) -> Result<Vec<String>> {
+        let slots = match peer {
+            Some(peer) => vec![self
+                .peer_slot(peer)
+                .await
+                .ok_or_else(|| anyhow!("peer {} not found", peer))?],
+            None => self.peer_slots().await,
+        };
+        let mut reset = Vec::with_capacity(slots.len());
+        for slot in slots {
+            let info = &mut slot.write().await.info;
+            self.inner
+                .rib_counters
+                .prefixes_forgotten(info.prefixes_received);
+            info.updates_received = 0;
+            info.withdrawals_received = 0;
+            info.prefixes_received = 0;
+            info.prefixes_rejected = 0;
+            info.session_flaps = 0;
+            reset.push(info.address.clone());
+        }
+        Ok(reset)
+    }
+
+    pub async fn peer_show(&self, peer: &str) -> Option<PeerInfo> {
+        Some(self.peer_slot(peer).await?.read().await.info.clone())
+    }
+
+    /// `peer`'s recent session events, oldest first.
+    pub async fn peer_history(&self, peer: &str) -> Option<Vec<SessionEvent>> {
+        Some(self.peer_slot(peer).await?.read().await.timeline.snapshot())
+    }
+
+    /// Wait up to `timeout` for `peer` to be in `state` and return its info
+    /// from then, or `None` if it did not get there in time.
+    pub async fn peer_wait(
+        &self,
+        peer: &str,
+        state: PeerState,
+        timeout: Duration,
+    ) -> Result<Option<PeerInfo>> {
+        let deadline = Instant::now() + timeout;
+        // Subscribed before the first look, so no transition goes unseen.
+        let mut events = self
+            .inner
+            .events
+            .subscribe("peer_wait", &[EventTopic::Peer]);
+        let info = self
+            .peer_show(peer)
             .await
-            .insert(peer.to_string(), runtime);
+            .ok_or_else(|| anyhow!("peer {} not found", peer))?;
+        if info.state == state {
+            return Ok(Some(info));
+        }
+        loop {
+            match tokio::time::timeout_at(deadline, events.recv()).await {
+                Err(_) | Ok(None) => return Ok(None),
+                Ok(Some(envelope)) => match &envelope.event {
+                    Event::PeerState {
+                        peer: changed,
+                        state: reached,
+                    } if changed == peer && *reached == state => {
+                        return Ok(self.peer_show(peer).await);
+                    }
+                    // Transitions were missed; only the current state is left.
+                    Event::EventsDropped { .. } => {
+                        if let Some(info) = self.peer_show(peer).await.filter(|i| i.state == state)
+                        {
+                            return Ok(Some(info));
+                        }
+                    }
+                    _ => {}
+                },
+            }
+        }
+    }
+
+    /// Timers of `peer`, or of every peer without one.
+    pub async fn peer_timers(&self, peer: Option<&str>) -> Option<Vec<PeerTimers>> {
+        let slots = match peer {
+            Some(peer) => vec![self.peer_slot(peer).await?],
+            None => self.peer_slots().await,
+        };
+        let mut timers = Vec::with_capacity(slots.len());
+        for slot in slots {
+            let runtime = slot.read().await;
+            timers.push(runtime.timers.snapshot(&runtime.cfg));
+        }
+        Some(timers)
+    }
+
+    pub async fn peer_reset(&self, peer: &str, mode: ResetMode) -> Result<()> {
+        if let Some(slot) = self.peer_slot(peer).await {
+            slot.read()
+                .await
+                .timeline
+                .record(SessionEventKind::Reset { mode });
+        }
+        if mode != ResetMode::Hard {
+            return self.soft_reset(peer, mode).await;
+        }
+
+        let slot = self
+            .peer_slot(peer)
+            .await
+            .ok_or_else(|| anyhow!("peer {} not found", peer))?;
+        // The session needs the slot lock to close, so it is not held while
+        // the old task winds down.
+        slot.read().await.cancel.send_replace(true);
+        if join_peer_tasks(std::slice::from_ref(&slot), PEER_STOP_TIMEOUT).await > 0 {
+            tracing::warn!(peer, "peer task did not stop in time, aborted");
+        }
+        let mut current = slot.write().await;
+        if let Some(rib_in) = &self.inner.rib_in {
+            rib_in.clear_peer(peer);
+        }
+
+        // The new task waits on the slot lock until the swap below is done.
+        let mut runtime = self.spawn_peer_task(current.cfg.clone(), current.info.maintenance);
+        let old_runtime = &mut *current;
+        runtime.info.updates_received = old_runtime.info.updates_received;
+        runtime.info.withdrawals_received = old_runtime.info.withdrawals_received;
+        runtime.info.prefixes_received = old_runtime.info.prefixes_received;
+        runtime.info.prefixes_rejected = old_runtime.info.prefixes_rejected;
+        runtime.info.session_flaps = old_runtime.info.session_flaps;
+        runtime.info.archive_records_shed = old_runtime.info.archive_records_shed;
+        runtime.info.archive_duplicates_suppressed = old_runtime.info.archive_duplicates_suppressed;
+        runtime.info.log_messages = old_runtime.info.log_messages;
+        std::mem::swap(&mut runtime.message_log, &mut old_runtime.message_log);
+        std::mem::swap(&mut runtime.timeline, &mut old_runtime.timeline);
+        std::mem::swap(&mut runtime.update_rate, &mut old_runtime.update_rate);
+        std::mem::swap(&mut runtime.recent_updates, &mut old_runtime.recent_updates);
+        std::mem::swap(&mut runtime.flaps, &mut old_runtime.flaps);
+        self.count_state(old_runtime, runtime.info.state);
+        self.inner
+            .rib_counters
+            .advertised_changed(old_runtime.info.advertised_prefixes, 0);
+        *current = runtime;
         Ok(())
     }
 
-    pub async fn rib_summary(&self) -> RibSummary {
-        let peers = self.inner.peers.read().await;
-        let established = peers
-            .values()
-            .filter(|p| matches!(p.info.state, PeerState::Established))
-            .count();
+    /// Put `peer` in maintenance, or take it out with `None`. Its session
+    /// withdraws or re-sends our announcements on its own.
+    pub async fn peer_maintenance(
+        &self,
+        peer: &str,
+        maintenance: Option<Maintenance>,
+    ) -> Result<PeerInfo> {
+        let slot = self
+            .peer_slot(peer)
+            .await
+            .ok_or_else(|| anyhow!("peer {} not found", peer))?;
+        let mut runtime = slot.write().await;
+        if runtime.info.maintenance.is_some() != maintenance.is_some() {
+            self.inner
+                .rib_counters
+                .maintenance_changed(runtime.info.state, maintenance.is_some());
+        }
+        runtime.info.maintenance = maintenance;
+        runtime.maintenance.send_replace(maintenance);
+        self.note_archive_peer(&runtime);
+        tracing::info!(peer, ?maintenance, "peer maintenance changed");
+        Ok(runtime.info.clone())
+    }
 
-        RibSummary {
-            peers_total: peers.len(),
-            peers_established: established,
-            advertised_prefixes_total: peers.values().map(|p| p.info.advertised_prefixes).sum(),
+    /// Start or stop hex-dumping `peer`'s BGP messages; returns where they go,
+    /// `None` for the daemon log.
+    pub async fn peer_log_messages(&self, peer: &str, enabled: bool) -> Result<Option<PathBuf>> {
+        let slot = self
+            .peer_slot(peer)
+            .await
+            .ok_or_else(|| anyhow!("peer {} not found", peer))?;
+        let mut runtime = slot.write().await;
+        runtime.message_log.set_enabled(enabled);
+        runtime.info.log_messages = enabled;
+        tracing::info!(peer, enabled, "peer message logging changed");
+        Ok(runtime.message_log.path().map(Path::to_path_buf))
+    }
+
+    async fn soft_reset(&self, peer: &str, mode: ResetMode) -> Result<()> {
+        let sender = {
+            let slot = self
+                .peer_slot(peer)
+                .await
+                .ok_or_else(|| anyhow!("peer {} not found", peer))?;
+            let runtime = slot.read().await;
+            if !matches!(runtime.info.state, PeerState::Established) {
+                return Err(anyhow!("peer {} is not established", peer));
+            }
+            runtime.soft_reset.clone()
+        };
+
+        let (reply, reply_rx) = oneshot::channel();
+        sender
+            .try_send(SoftReset { mode, reply })
+            .map_err(|_| anyhow!("peer {} session is busy", peer))?;
+        match timeout(SOFT_RESET_TIMEOUT, reply_rx).await {
+            Ok(Ok(result)) => result,
+            Ok(Err(_)) => Err(anyhow!("peer {} session ended before the reset ran", peer)),
+            Err(_) => Err(anyhow!("timed out waiting for peer {} session", peer)),
         }
     }
 
-    pub async fn rib_out(&self, peer: &str) -> Result<Vec<String>> {
-        let peers = self.inner.peers.read().await;
-        if !peers.contains_key(peer) {
-            return Err(anyhow!("peer {} not found", peer));
+    /// The `limit` busiest prefixes over the last `window_mins` minutes, or
+    /// the whole `[churn].window_mins` without one. Returns the window used.
+    pub fn rib_churn(
+        &self,
+        window_mins: Option<u32>,
+        limit: usize,
+    ) -> Result<(u32, Vec<PrefixChurn>)> {
+        let churn = self
+            .inner
+            .churn
+            .as_ref()
+            .ok_or_else(|| anyhow!("churn tracking is disabled; set [churn].enabled"))?;
+        let window = window_mins
+            .unwrap_or(churn.window_mins())
+            .clamp(1, churn.window_mins());
+        let top = churn.top_at(window, limit, chrono::Utc::now().timestamp());
+        Ok((window, top))
+    }
+
+    pub fn rib_summary(&self) -> RibSummary {
+        self.inner.rib_counters.summary()
+    }
+
+    pub async fn rib_out(&self, peer: &str) -> Result<Vec<IpNet>> {
+        let slot = self
+            .peer_slot(peer)
+            .await
+            .ok_or_else(|| anyhow!("peer {} not found", peer))?;
+        let runtime = slot.read().await;
+        if runtime.info.maintenance.is_some() {
+            return Ok(vec![]);
         }
+        let cfg = runtime.cfg.clone();
+        drop(runtime);
         Ok(self
-            .inner
-            .prefixes
-            .iter()
-            .map(|p| p.network.to_string())
+            .exported_prefixes(&cfg)
+            .into_iter()
+            .map(|p| p.network)
             .collect())
     }
 
-    pub async fn rib_in(&self, peer: &str) -> Result<Vec<String>> {
-        let peers = self.inner.peers.read().await;
-        if !peers.contains_key(peer) {
+    /// Peers and routes for a targeted RIB snapshot of `view` taken at `now`:
+    /// the Adj-RIB-In of `peers` (every peer dumped under `view` when `None`),
+    /// or the routes we originate. The Adj-RIB-In keeps only AS paths, so its
+    /// routes carry ORIGIN INCOMPLETE and no NEXT_HOP.
+    pub async fn rib_snapshot(
+        &self,
+        source: RibSource,
+        peers: Option<&[String]>,
+        view: &str,
+        now: i64,
+    ) -> Result<SnapshotTable> {
+        let mut table = SnapshotTable::default();
+        match source {
+            RibSource::AdjRibIn => {
+                let rib_in =
+                    self.inner.rib_in.as_ref().ok_or_else(|| {
+                        anyhow!("the Adj-RIB-In is disabled; set [rib_in].enabled")
+                    })?;
+                let slots = match peers {
+                    Some(peers) => {
+                        let mut slots = Vec::with_capacity(peers.len());
+                        for peer in peers {
+                            let slot = self
+                                .peer_slot(peer)
+                                .await
+                                .ok_or_else(|| anyhow!("peer {} not found", peer))?;
+                            let peer_view = self.peer_view(&slot.read().await.cfg).to_string();
+                            if peer_view != view {
+                                anyhow::bail!("peer {peer} is dumped under view {peer_view}");
+                            }
+                            slots.push(slot);
+                        }
+                        slots
+                    }
+                    None => self.peer_slots().await,
+                };
+                let infos = self.view_peers(slots, view).await;
+
+                for info in infos {
+                    let index = table.add_peer(snapshot_peer(&info)?)?;
+                    let originated = info.established_at.unwrap_or(now) as u32;
+                    for (prefix, as_path) in rib_in.routes(&info.address) {
+                        let attrs = route_attributes(Origin::INCOMPLETE, &as_path, None, &[]);
+                        table.add_route(index, prefix, originated, &attrs);
+                    }
+                }
+            }
+            RibSource::LocRib => {
+                if peers.is_some() {
+                    anyhow::bail!("peers selects Adj-RIB-In routes; not valid with loc_rib");
+                }
+                let index = table.add_peer(SnapshotPeer {
+                    peer_bgp_id: self.inner.router_id,
+                    peer_ip: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+                    peer_asn: self.inner.global_asn,
+                })?;
+                let entries = self
+                    .inner
+                    .prefixes
+                    .read()
+                    .expect("prefixes lock poisoned")
+                    .clone();
+                for entry in entries {
+                    let next_hop = match entry.next_hop {
+                        Some(IpAddr::V4(next_hop)) => next_hop,
+                        _ => self.inner.router_id,
+                    };
+                    let attrs = match &entry.path {
+                        Some(path) => route_attributes(
+                            path.origin,
+                            &path.as_path,
+                            Some(next_hop),
+                            &path.attributes,
+                        ),
+                        None => route_attributes(Origin::IGP, &[], Some(next_hop), &[]),
+                    };
+                    table.add_route(index, entry.network, now as u32, &attrs);
+                }
+            }
+        }
+        Ok(table)
+    }
+
+    /// The peers of `slots` dumped under `view`, ordered by address.
+    async fn view_peers(&self, slots: Vec<PeerSlot>, view: &str) -> Vec<PeerInfo> {
+        let mut infos = Vec::with_capacity(slots.len());
+        for slot in slots {
+            let runtime = slot.read().await;
+            if self.peer_view(&runtime.cfg) == view {
+                infos.push(runtime.info.clone());
+            }
+        }
+        infos.sort_by(|a, b| a.address.cmp(&b.address));
+        infos
+    }
+
+    /// RIB view `peer` is dumped under: its `archive.view_name`, else
+    /// `[archive].rib_view_name`.
+    fn peer_view<'a>(&'a self, peer: &'a PeerConfig) -> &'a str {
+        peer.archive
+            .view_name
+            .as_deref()
+            .unwrap_or(&self.inner.rib_view_name)
+    }
+
+    /// Every peer dumped under `view` as a peer index table entry, ordered by
+    /// address.
+    pub async fn peer_index(&self, view: &str) -> Result<Vec<SnapshotPeer>> {
+        let infos = self.view_peers(self.peer_slots().await, view).await;
+        infos.iter().map(snapshot_peer).collect()
+    }
+
+    /// Empty while `[rib_in]` is disabled.
+    pub async fn rib_in(&self, peer: &str) -> Result<Vec<IpNet>> {
+        if self.peer_slot(peer).await.is_none() {
             return Err(anyhow!("peer {} not found", peer));
         }
-        Ok(vec![])
+        Ok(self
+            .inner
+            .rib_in
+            .as_ref()
+            .map(|rib_in| rib_in.prefixes(peer))
+            .unwrap_or_default())
+    }
+
+    /// Origin ASNs for `prefix` (or the prefixes with several origins), AS
+    /// path length per peer, and origins new since `since`.
+    pub fn aspath_stats(
+        &self,
+        prefix: Option<IpNet>,
+        since: Option<i64>,
+        limit: usize,
+    ) -> Result<AsPathStats> {
+        let rib_in = self
+            .inner
+            .rib_in
+            .as_ref()
+            .ok_or_else(|| anyhow!("the Adj-RIB-In is disabled; set [rib_in].enabled"))?;
+        Ok(rib_in.stats(prefix, since, limit))
     }
 }
 
-async fn connect_with_optional_bind(peer: &PeerConfig, remote: SocketAddr) -> Result<TcpStream> {
+async fn connect_with_optional_bind(
+    peer: &PeerConfig,
+    auth: Option<&TcpAuth<'_>>,
+    remote: SocketAddr,
+) -> Result<TcpStream> {
     let local_bind = match peer.local_address.as_deref() {
         None => None,
         Some(raw) => Some(normalize_socket_addr(raw, 0).context("invalid peer local_address")?),
@@ -437,11 +2480,11 @@ async fn connect_with_optional_bind(peer: &PeerConfig, remote: SocketAddr) -> Re
             let socket = TcpSocket::new_v4()?;
             socket.bind(SocketAddr::V4(local_v4))?;
 
-            // Set TCP-MD5 signature if password is configured
-            if let Some(password) = &peer.password {
+            // Set TCP-MD5 signature or TCP-AO keys if a password is configured
+            if let Some(auth) = auth {
                 socket
-                    .set_md5_signature(&remote, password)
-                    .context("failed to set TCP-MD5 signature")?;
+                    .set_tcp_auth(&remote, auth)
+                    .with_context(|| format!("failed to set {} signature", auth.kind()))?;
             }
 
             socket
@@ -457,22 +2500,36 @@ async fn connect_with_optional_bind(peer: &PeerConfig, remote: SocketAddr) -> Re
             };
             socket.bind(local)?;
 
-            // Set TCP-MD5 signature if password is configured
-            if let Some(password) = &peer.password {
+            // Set TCP-MD5 signature or TCP-AO keys if a password is configured
+            if let Some(auth) = auth {
                 socket
-                    .set_md5_signature(&remote, password)
-                    .context("failed to set TCP-MD5 signature")?;
+                    .set_tcp_auth(&remote, auth)
+                    .with_context(|| format!("failed to set {} signature", auth.kind()))?;
             }
 
             socket.connect(remote).await.map_err(Into::into)
         }
+        // TCP-AO keys must be on the socket before the SYN goes out.
+        (_, None) if matches!(auth, Some(TcpAuth::Ao(_))) => {
+            let socket = if remote.is_ipv4() {
+                TcpSocket::new_v4()?
+            } else {
+                TcpSocket::new_v6()?
+            };
+            if let Some(auth) = auth {
+                socket
+                    .set_tcp_auth(&remote, auth)
+                    .context("failed to set TCP-AO keys")?;
+            }
+            socket.connect(remote).await.map_err(Into::into)
+        }
         (_, None) => {
             // No local bind, set MD5 on connected stream
             let stream = TcpStream::connect(remote).await?;
 
-            if let Some(password) = &peer.password {
+            if let Some(auth) = auth {
                 stream
-                    .set_md5_signature(&remote, password)
+                    .set_tcp_auth(&remote, auth)
                     .context("failed to set TCP-MD5 signature")?;
             }
 
@@ -492,67 +2549,542 @@ fn normalize_socket_addr(raw: &str, default_port: u16) -> Result<SocketAddr> {
     Ok(SocketAddr::new(ip, default_port))
 }
 
-async fn write_bgp_message(stream: &mut TcpStream, msg: &BgpMessage) -> Result<()> {
-    let mut bytes = msg.encode(AsnLength::Bits32).to_vec();
+async fn write_bgp_message(stream: &mut SessionStream, msg: &BgpMessage) -> Result<()> {
+    write_bgp_message_as(stream, msg, AsnLength::Bits32).await
+}
+
+/// Write `msg` with AS numbers `asn_len` wide, as negotiated for UPDATEs.
+async fn write_bgp_message_as(
+    stream: &mut SessionStream,
+    msg: &BgpMessage,
+    asn_len: AsnLength,
+) -> Result<()> {
+    let bytes = encode_bgp_message(msg, asn_len)?;
+    stream.write_frame(&bytes).await?;
+    Ok(())
+}
+
+/// `msg` on the wire; for a 2-octet peer, UPDATE attributes go through
+/// [`as_trans_attributes`].
+fn encode_bgp_message(msg: &BgpMessage, asn_len: AsnLength) -> Result<Vec<u8>> {
+    let translated;
+    let msg = match (msg, asn_len) {
+        (BgpMessage::Update(update), AsnLength::Bits16) => {
+            match as_trans_attributes(&update.attributes) {
+                Some(attributes) => {
+                    translated = BgpMessage::Update(BgpUpdateMessage {
+                        attributes,
+                        ..update.clone()
+                    });
+                    &translated
+                }
+                None => msg,
+            }
+        }
+        _ => msg,
+    };
+    let mut bytes = msg.encode(asn_len).to_vec();
     if bytes.len() < 19 {
         return Err(anyhow!("encoded BGP message too short"));
     }
 
     bytes[0..16].fill(0xff);
-
-    stream.write_all(&bytes).await?;
-    Ok(())
+    Ok(bytes)
 }
 
-async fn read_bgp_message(stream: &mut TcpStream) -> Result<BgpMessage> {
-    let mut header = [0u8; 19];
-    stream.read_exact(&mut header).await?;
+/// RFC 6793 section 4.2.2: towards a 2-octet peer, every 4-octet ASN in
+/// AS_PATH and AGGREGATOR becomes AS_TRANS, and the real values travel in
+/// AS4_PATH and AS4_AGGREGATOR. `None` when nothing needs translating.
+fn as_trans_attributes(attributes: &Attributes) -> Option<Attributes> {
+    let four_octet = |asn: &Asn| asn.required_len().is_four_byte();
+    let path_needs_trans = |path: &AsPath| path.required_asn_length().is_four_byte();
+    let path_trans = attributes.iter().any(|value| {
+        matches!(value, AttributeValue::AsPath { path, is_as4: false } if path_needs_trans(path))
+    });
+    let aggregator_trans = attributes.iter().any(|value| {
+        matches!(value, AttributeValue::Aggregator { asn, is_as4: false, .. } if four_octet(asn))
+    });
+    if !path_trans && !aggregator_trans {
+        return None;
+    }
 
-    if header[0..16] != [0xff; 16] {
-        return Err(anyhow!("invalid BGP marker"));
+    let to_trans = |asns: &[Asn]| -> Vec<Asn> {
+        asns.iter()
+            .map(|asn| {
+                if four_octet(asn) {
+                    Asn::TRANSITION
+                } else {
+                    *asn
+                }
+            })
+            .collect()
+    };
+    let mut translated = Vec::new();
+    for attr in attributes.clone().into_attributes_iter() {
+        match attr.value {
+            AttributeValue::AsPath {
+                ref path,
+                is_as4: false,
+            } if path_needs_trans(path) => {
+                let segments = path
+                    .segments
+                    .iter()
+                    .map(|segment| match segment {
+                        AsPathSegment::AsSequence(asns) => {
+                            AsPathSegment::AsSequence(to_trans(asns))
+                        }
+                        AsPathSegment::AsSet(asns) => AsPathSegment::AsSet(to_trans(asns)),
+                        AsPathSegment::ConfedSequence(asns) => {
+                            AsPathSegment::ConfedSequence(to_trans(asns))
+                        }
+                        AsPathSegment::ConfedSet(asns) => AsPathSegment::ConfedSet(to_trans(asns)),
+                    })
+                    .collect();
+                translated.push(Attribute {
+                    value: AttributeValue::AsPath {
+                        path: AsPath::from_segments(segments),
+                        is_as4: false,
+                    },
+                    ..attr
+                });
+                // AS4_PATH carries no confederation segments.
+                let as4_path = path
+                    .segments
+                    .iter()
+                    .filter(|segment| {
+                        matches!(
+                            segment,
+                            AsPathSegment::AsSequence(_) | AsPathSegment::AsSet(_)
+                        )
+                    })
+                    .cloned()
+                    .collect();
+                translated.push(
+                    AttributeValue::AsPath {
+                        path: AsPath::from_segments(as4_path),
+                        is_as4: true,
+                    }
+                    .into(),
+                );
+            }
+            AttributeValue::Aggregator {
+                asn,
+                id,
+                is_as4: false,
+            } if four_octet(&asn) => {
+                translated.push(Attribute {
+                    value: AttributeValue::Aggregator {
+                        asn: Asn::TRANSITION,
+                        id,
+                        is_as4: false,
+                    },
+                    ..attr
+                });
+                translated.push(
+                    AttributeValue::Aggregator {
+                        asn: Asn::new_32bit(asn.into()),
+                        id,
+                        is_as4: true,
+                    }
+                    .into(),
+                );
+            }
+            // Replaced by the ones derived above.
+            AttributeValue::AsPath { is_as4: true, .. } if path_trans => {}
+            AttributeValue::Aggregator { is_as4: true, .. } if aggregator_trans => {}
+            _ => translated.push(attr),
+        }
     }
+    Some(translated.into())
+}
 
-    let length = u16::from_be_bytes([header[16], header[17]]) as usize;
-    if !(19..=4096).contains(&length) {
-        return Err(anyhow!("invalid BGP message length {}", length));
+/// OpenSent: send our OPEN and receive the peer's. An OPEN that fails
+/// [`check_open`], or any other message in its place, is answered with a
+/// NOTIFICATION.
+async fn open_handshake(
+    stream: &mut SessionStream,
+    open: &[u8],
+    peer: &PeerConfig,
+    local_as: u32,
+    router_id: Ipv4Addr,
+) -> Result<BgpOpenMessage> {
+    stream.write_frame(open).await?;
+    let remote_open = match read_bgp_message(stream).await? {
+        BgpMessage::Open(open) => open,
+        BgpMessage::Notification(notification) => {
+            return Err(SessionError::notification(
+                &notification.error,
+                "instead of OPEN",
+            ));
+        }
+        other => {
+            let error = BgpError::FiniteStateMachineError(
+                FiniteStateMachineError::RECEIVE_UNEXPECTED_MESSAGE_IN_OPENSENT_State,
+            );
+            notify(stream, error, Vec::new()).await;
+            let kind = match other {
+                BgpMessage::Update(_) => "UPDATE",
+                _ => "KEEPALIVE",
+            };
+            return Err(anyhow!("expected OPEN from peer, got {kind}"));
+        }
+    };
+    if let Err(rejection) = check_open(&remote_open, peer, local_as, router_id) {
+        notify(stream, rejection.error, rejection.data).await;
+        return Err(SessionError::tagged(
+            PeerErrorCode::OpenMismatch,
+            format!("rejected OPEN from peer: {}", rejection.reason),
+        ));
     }
+    Ok(remote_open)
+}
 
-    let mut bytes = Vec::with_capacity(length);
-    bytes.extend_from_slice(&header);
+/// OpenConfirm: wait until `deadline` for the KEEPALIVE that brings the
+/// session up. A NOTIFICATION, or any other message, ends the connection.
+async fn await_keepalive(stream: &mut SessionStream, deadline: Instant) -> Result<()> {
+    let message = match timeout_at(deadline, read_bgp_message(stream)).await {
+        Ok(message) => message?,
+        Err(_) => {
+            notify(stream, BgpError::HoldTimerExpired(0), Vec::new()).await;
+            return Err(SessionError::tagged(
+                PeerErrorCode::HoldExpired,
+                "hold timer expired in OpenConfirm",
+            ));
+        }
+    };
+    match message {
+        BgpMessage::KeepAlive => Ok(()),
+        BgpMessage::Notification(notification) => Err(SessionError::notification(
+            &notification.error,
+            "in OpenConfirm",
+        )),
+        other => {
+            let error = BgpError::FiniteStateMachineError(
+                FiniteStateMachineError::RECEIVE_UNEXPECTED_MESSAGE_IN_OPENCONFIRM_STATE,
+            );
+            notify(stream, error, Vec::new()).await;
+            let kind = match other {
+                BgpMessage::Open(_) => "OPEN",
+                _ => "UPDATE",
+            };
+            Err(anyhow!(
+                "expected KEEPALIVE from peer in OpenConfirm, got {kind}"
+            ))
+        }
+    }
+}
+
+/// A connection that has exchanged OPENs and sent its KEEPALIVE, waiting in
+/// OpenConfirm for the peer's.
+struct OpenedConnection {
+    stream: SessionStream,
+    open: BgpOpenMessage,
+    inbound: bool,
+    confirm_by: Instant,
+}
 
-    let payload_len = length - 19;
-    if payload_len > 0 {
-        let mut payload = vec![0u8; payload_len];
-        stream.read_exact(&mut payload).await?;
-        bytes.extend_from_slice(&payload);
+/// The hold time both sides agreed on: the smaller wins. A peer that asks
+/// for no hold timer still gets KEEPALIVEs, and we keep enforcing ours.
+fn negotiated_hold_secs(peer: &PeerConfig, remote_open: &BgpOpenMessage) -> u16 {
+    let hold_time = peer.hold_time_secs.max(3);
+    match remote_open.hold_time {
+        0 => hold_time,
+        remote => hold_time.min(remote.max(3)),
     }
+}
+
+/// How long OpenConfirm waits for the peer's KEEPALIVE: the negotiated hold
+/// time.
+fn confirm_hold(peer: &PeerConfig, remote_open: &BgpOpenMessage) -> Duration {
+    Duration::from_secs(u64::from(negotiated_hold_secs(peer, remote_open)))
+}
 
-    let bytes32 = bytes.clone();
-    let mut raw32 = Bytes::from(bytes32);
-    let parsed = parse_bgp_message(&mut raw32, false, &AsnLength::Bits32)
-        .or_else(|_| {
-            let mut raw16 = Bytes::from(bytes);
-            parse_bgp_message(&mut raw16, false, &AsnLength::Bits16)
+fn remote_socket(peer: &PeerConfig) -> Result<SocketAddr> {
+    format!("{}:{}", peer.address, peer.remote_port)
+        .parse()
+        .map_err(|_| {
+            SessionError::tagged(
+                PeerErrorCode::Dns,
+                format!("invalid peer socket {}:{}", peer.address, peer.remote_port),
+            )
         })
-        .map_err(|e| anyhow!("failed parsing BGP message using bgpkit-parser: {e}"))?;
+}
 
-    Ok(parsed)
+async fn bind_listener(listen: SocketAddr) -> Result<TcpListener> {
+    TcpListener::bind(listen).await.map_err(|err| {
+        SessionError::tagged(
+            PeerErrorCode::TcpConnect,
+            format!("failed binding peer listener {listen}: {err}"),
+        )
+    })
 }
 
+/// When an established TCP-AO session next changes keys: as its rotation
+/// window opens, and again as it closes.
+fn ao_rotation_at(peer: &PeerConfig, now: i64) -> Option<Instant> {
+    peer.tcp_ao_key_id?;
+    let rotation = peer.password_rotation.as_ref()?;
+    let at = [rotation.start, rotation.end()]
+        .into_iter()
+        .find(|&at| at > now)?;
+    Some(Instant::now() + Duration::from_secs(u64::try_from(at - now).unwrap_or(0)))
+}
+
+/// Add the next TCP-AO key to a live session once the rotation window opens
+/// and drop the old one once it closes, so the session outlives the roll.
+fn rotate_ao_keys(peer: &PeerConfig, stream: &SessionStream) {
+    let (Some(id), Some(rotation)) = (peer.tcp_ao_key_id, &peer.password_rotation) else {
+        return;
+    };
+    let (Some(next), Ok(remote)) = (rotation.tcp_ao_key_id, stream.peer_addr()) else {
+        return;
+    };
+    let now = chrono::Utc::now().timestamp();
+    let (step, result) = if now >= rotation.end() {
+        ("retired", stream.retire_tcp_ao_key(&remote, id, next))
+    } else if now >= rotation.start {
+        let key = AoKey {
+            id: next,
+            key: &rotation.password,
+        };
+        ("added", stream.add_next_tcp_ao_key(&remote, key))
+    } else {
+        return;
+    };
+    match result {
+        Ok(()) => {
+            tracing::info!(peer=%peer.address, step, "rotated TCP-AO key on the live session")
+        }
+        Err(err) => {
+            tracing::warn!(peer=%peer.address, step, error=%format!("{err:#}"), "failed rotating TCP-AO key on the live session")
+        }
+    }
+}
+
+async fn sleep_until_some(deadline: Option<Instant>) {
+    match deadline {
+        Some(deadline) => sleep_until(deadline).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Best-effort NOTIFICATION before the session is torn down.
+async fn notify(stream: &mut SessionStream, error: BgpError, data: Vec<u8>) {
+    let notification = BgpMessage::Notification(BgpNotificationMessage { error, data });
+    let _ = write_bgp_message(stream, &notification).await;
+    let _ = stream.shutdown().await;
+}
+
+/// Before Established: OPEN, KEEPALIVE and NOTIFICATION carry no AS path,
+/// so the AS number width does not matter.
+async fn read_bgp_message(stream: &mut SessionStream) -> Result<BgpMessage> {
+    let frame = read_bgp_frame(stream).await?;
+    parse_bgp_frame(frame, AsnLength::Bits32)
+}
+
+/// Read one complete BGP message (header included) off the wire.
+///
+/// Frames are split off the session's read buffer, so one allocation serves
+/// many messages; `reserve` reclaims the buffer once the frames handed out
+/// earlier have all been dropped. Bytes read stay in the buffer until their
+/// frame is complete, so a cancelled read loses nothing: the session loop
+/// races reads against paced UPDATEs and its other work.
+async fn read_bgp_frame(stream: &mut SessionStream) -> Result<Bytes> {
+    let (tcp, buf) = stream.reader();
+    let frame = loop {
+        if buf.len() >= BGP_HEADER_LEN {
+            if buf[0..16] != [0xff; 16] {
+                return Err(anyhow!("invalid BGP marker"));
+            }
+            let length = u16::from_be_bytes([buf[16], buf[17]]) as usize;
+            if !(BGP_HEADER_LEN..=BGP_MAX_MESSAGE_LEN).contains(&length) {
+                return Err(anyhow!("invalid BGP message length {}", length));
+            }
+            if buf.len() >= length {
+                break buf.split_to(length).freeze();
+            }
+        }
+        buf.reserve(BGP_MAX_MESSAGE_LEN);
+        if tcp.read_buf(buf).await? == 0 {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+    };
+
+    stream.record_incoming(&frame);
+    Ok(frame)
+}
+
+/// Parse a frame with AS numbers `asn_len` wide, as negotiated.
+fn parse_bgp_frame(mut bytes: Bytes, asn_len: AsnLength) -> Result<BgpMessage> {
+    parse_bgp_message(&mut bytes, false, &asn_len)
+        .map_err(|e| anyhow!("failed parsing BGP message using bgpkit-parser: {e}"))
+}
+
+/// Wait up to `deadline` for the tasks of `slots`, already told to stop, to
+/// exit, then abort the rest. Returns how many were aborted.
+async fn join_peer_tasks(slots: &[PeerSlot], deadline: Duration) -> usize {
+    let give_up_at = Instant::now() + deadline;
+    loop {
+        let mut pending = Vec::new();
+        for slot in slots {
+            if !slot.read().await.task.is_finished() {
+                pending.push(slot);
+            }
+        }
+        if pending.is_empty() {
+            return 0;
+        }
+        if Instant::now() >= give_up_at {
+            for slot in &pending {
+                slot.read().await.task.abort();
+            }
+            return pending.len();
+        }
+        sleep(Duration::from_millis(50)).await;
+    }
+}
+
+/// Prefixes announced by an UPDATE, in the NLRI field or MP_REACH_NLRI.
+fn announced_prefixes(update: &BgpUpdateMessage) -> Vec<IpNet> {
+    update
+        .announced_prefixes
+        .iter()
+        .chain(
+            update
+                .attributes
+                .get_reachable_nlri()
+                .map(|nlri| nlri.prefixes.iter())
+                .into_iter()
+                .flatten(),
+        )
+        .map(|p| p.prefix)
+        .collect()
+}
+
+/// Prefixes withdrawn by an UPDATE, in the withdrawn routes field or
+/// MP_UNREACH_NLRI.
+fn withdrawn_prefixes(update: &BgpUpdateMessage) -> Vec<IpNet> {
+    update
+        .withdrawn_prefixes
+        .iter()
+        .chain(
+            update
+                .attributes
+                .get_unreachable_nlri()
+                .map(|nlri| nlri.prefixes.iter())
+                .into_iter()
+                .flatten(),
+        )
+        .map(|p| p.prefix)
+        .collect()
+}
+
+fn withdrawn_count(update: &BgpUpdateMessage) -> usize {
+    update.withdrawn_prefixes.len()
+        + update
+            .attributes
+            .get_unreachable_nlri()
+            .map(|nlri| nlri.prefixes.len())
+            .unwrap_or(0)
+}
+
+/// IPv6 when the UPDATE carries IPv6 MP_REACH/MP_UNREACH NLRI, including
+/// an IPv6 End-of-RIB; IPv4 otherwise. An UPDATE mixing IPv4 NLRI with IPv6
+/// MP NLRI is archived once, in the v6 series.
+fn update_family(update: &BgpUpdateMessage) -> AddressFamily {
+    let attributes = &update.attributes;
+    let ipv6 = attributes
+        .get_reachable_nlri()
+        .into_iter()
+        .chain(attributes.get_unreachable_nlri())
+        .any(|nlri| nlri.afi == Afi::Ipv6);
+    if ipv6 {
+        AddressFamily::Ipv6
+    } else {
+        AddressFamily::Ipv4
+    }
+}
+
+/// BGP4MP_STATE_CHANGE state codes (RFC 6396 section 4.4.1).
+fn fsm_state_code(state: PeerState) -> u16 {
+    match state {
+        PeerState::Idle => 1,
+        PeerState::Connect => 2,
+        PeerState::Active => 3,
+        PeerState::OpenSent => 4,
+        PeerState::OpenConfirm => 5,
+        PeerState::Established => 6,
+    }
+}
+
+fn encode_route_refresh(afi: u16, safi: u8) -> [u8; 23] {
+    let mut bytes = [0xffu8; 23];
+    bytes[16..18].copy_from_slice(&23u16.to_be_bytes());
+    bytes[TYPE_OFFSET] = BGP_MSG_ROUTE_REFRESH;
+    bytes[19..21].copy_from_slice(&afi.to_be_bytes());
+    bytes[21] = 0;
+    bytes[22] = safi;
+    bytes
+}
+
+fn build_withdraw_update(network: IpNet) -> BgpMessage {
+    let prefix = NetworkPrefix::new(network, None);
+    let mut attrs = Attributes::default();
+    let withdrawn_prefixes = match network {
+        IpNet::V4(_) => vec![prefix],
+        IpNet::V6(_) => {
+            attrs.add_attr(AttributeValue::MpUnreachNlri(Nlri::new_unreachable(prefix)).into());
+            vec![]
+        }
+    };
+    BgpMessage::Update(BgpUpdateMessage {
+        withdrawn_prefixes,
+        attributes: attrs,
+        announced_prefixes: vec![],
+    })
+}
+
+async fn load_mrt_entries(path: &str, peers: Option<Arc<[String]>>) -> Result<Vec<PrefixEntry>> {
+    let owned = path.to_string();
+    let routes = tokio::task::spawn_blocking(move || mrt::load_routes(&owned))
+        .await
+        .context("MRT loader panicked")??;
+    Ok(routes
+        .into_iter()
+        .map(|(network, path)| PrefixEntry {
+            network,
+            next_hop: None,
+            path: Some(Arc::new(path)),
+            peers: peers.clone(),
+        })
+        .collect())
+}
+
+/// An UPDATE announcing `prefix_entry`, with `prepend` ahead of its AS path.
 fn build_announce_update(
     prefix_entry: &PrefixEntry,
     router_id: Ipv4Addr,
-    local_as: u32,
+    prepend: &[u32],
 ) -> BgpMessage {
+    let mut as_path = prepend.to_vec();
+    let mut origin = Origin::IGP;
+    if let Some(path) = &prefix_entry.path {
+        as_path.extend(&path.as_path);
+        origin = path.origin;
+    }
+
     let mut attrs = Attributes::default();
-    attrs.add_attr(AttributeValue::Origin(Origin::IGP).into());
+    attrs.add_attr(AttributeValue::Origin(origin).into());
     attrs.add_attr(
         AttributeValue::AsPath {
-            path: AsPath::from_sequence([local_as]),
+            path: AsPath::from_sequence(as_path),
             is_as4: false,
         }
         .into(),
     );
+    for attr in prefix_entry.path.iter().flat_map(|p| &p.attributes) {
+        attrs.add_attr(attr.clone().into());
+    }
 
     // Determine next-hop: use configured next-hop or default based on prefix type
     let next_hop = prefix_entry.next_hop.unwrap_or_else(|| {
@@ -576,3 +3108,78 @@ fn build_announce_update(
         announced_prefixes: vec![announced],
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use bgpkit_parser::models::AttrType;
+
+    use super::*;
+
+    #[test]
+    fn encodes_route_refresh_message() {
+        let bytes = encode_route_refresh(2, 1);
+        assert!(bytes[..16].iter().all(|b| *b == 0xff));
+        assert_eq!(u16::from_be_bytes([bytes[16], bytes[17]]), 23);
+        assert_eq!(bytes[TYPE_OFFSET], BGP_MSG_ROUTE_REFRESH);
+        assert_eq!(&bytes[19..], &[0, 2, 0, 1]);
+    }
+
+    #[test]
+    fn sends_as_trans_and_as4_path_to_two_octet_peers() {
+        let attributes: Attributes = vec![
+            AttributeValue::Origin(Origin::IGP),
+            AttributeValue::AsPath {
+                path: AsPath::from_sequence([65001, 4_200_000_000]),
+                is_as4: false,
+            },
+            AttributeValue::NextHop("192.0.2.1".parse().unwrap()),
+        ]
+        .into_iter()
+        .collect();
+        let update = BgpMessage::Update(BgpUpdateMessage {
+            withdrawn_prefixes: vec![],
+            attributes,
+            announced_prefixes: vec![NetworkPrefix::new("203.0.113.0/24".parse().unwrap(), None)],
+        });
+
+        let frame = encode_bgp_message(&update, AsnLength::Bits16).unwrap();
+        let BgpMessage::Update(parsed) =
+            parse_bgp_frame(Bytes::from(frame), AsnLength::Bits16).unwrap()
+        else {
+            panic!("not an UPDATE");
+        };
+        let paths: Vec<_> = parsed
+            .attributes
+            .iter()
+            .filter_map(|value| match value {
+                AttributeValue::AsPath { path, is_as4 } => {
+                    Some((path.to_u32_vec_opt(false), *is_as4))
+                }
+                _ => None,
+            })
+            .collect();
+        assert_eq!(
+            paths,
+            vec![
+                (Some(vec![65001, 23456]), false),
+                (Some(vec![65001, 4_200_000_000]), true),
+            ]
+        );
+
+        // 4-octet peers get the path as is.
+        let frame = encode_bgp_message(&update, AsnLength::Bits32).unwrap();
+        let BgpMessage::Update(parsed) =
+            parse_bgp_frame(Bytes::from(frame), AsnLength::Bits32).unwrap()
+        else {
+            panic!("not an UPDATE");
+        };
+        assert!(parsed.attributes.get_attr(AttrType::AS4_PATH).is_none());
+    }
+
+    #[test]
+    fn reset_mode_uses_kebab_case() {
+        let mode: ResetMode = serde_json::from_str("\"soft-in\"").unwrap();
+        assert_eq!(mode, ResetMode::SoftIn);
+        assert_eq!(ResetMode::default(), ResetMode::Hard);
+    }
+}
@@ -0,0 +1,143 @@
+//! Routes read from MRT files (`announce_from_mrt`, `prefix_load_mrt`) for
+//! lab route generation.
+
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+use bgpkit_parser::models::{AttributeValue, ElemType, MetaCommunity, Origin};
+use bgpkit_parser::BgpkitParser;
+use ipnet::IpNet;
+
+/// Attributes of a loaded route that are re-announced with it. Our ASN is
+/// prepended to `as_path` and the next hop is always our own.
+#[derive(Debug, Clone, PartialEq)]
+pub(super) struct RoutePath {
+    pub origin: Origin,
+    pub as_path: Vec<u32>,
+    /// MED and communities; LOCAL_PREF and other iBGP-only attributes are dropped.
+    pub attributes: Vec<AttributeValue>,
+}
+
+/// Parse a RIB dump or updates file (local path or URL, optionally
+/// compressed) into the routes it leaves behind. Records are applied in file
+/// order, so a later announcement replaces an earlier one and a withdrawal
+/// removes it.
+pub(super) fn load_routes(path: &str) -> Result<BTreeMap<IpNet, RoutePath>> {
+    let parser =
+        BgpkitParser::new(path).map_err(|err| anyhow!("failed opening MRT file {path}: {err}"))?;
+
+    let mut routes = BTreeMap::new();
+    for elem in parser {
+        let network = elem.prefix.prefix.trunc();
+        if elem.elem_type == ElemType::WITHDRAW {
+            routes.remove(&network);
+            continue;
+        }
+
+        let mut attributes = Vec::new();
+        if let Some(med) = elem.med {
+            attributes.push(AttributeValue::MultiExitDiscriminator(med));
+        }
+        let (mut plain, mut extended, mut large) = (vec![], vec![], vec![]);
+        for community in elem.communities.into_iter().flatten() {
+            match community {
+                MetaCommunity::Plain(c) => plain.push(c),
+                MetaCommunity::Extended(c) => extended.push(c),
+                MetaCommunity::Large(c) => large.push(c),
+                MetaCommunity::Ipv6Extended(_) => {}
+            }
+        }
+        if !plain.is_empty() {
+            attributes.push(AttributeValue::Communities(plain));
+        }
+        if !extended.is_empty() {
+            attributes.push(AttributeValue::ExtendedCommunities(extended));
+        }
+        if !large.is_empty() {
+            attributes.push(AttributeValue::LargeCommunities(large));
+        }
+
+        routes.insert(
+            network,
+            RoutePath {
+                origin: elem.origin.unwrap_or(Origin::IGP),
+                as_path: elem
+                    .as_path
+                    .and_then(|path| path.to_u32_vec_opt(false))
+                    .unwrap_or_default(),
+                attributes,
+            },
+        );
+    }
+    Ok(routes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::snapshot::encode_bgp4mp_message_as4;
+    use crate::archive::types::UpdateRecordInput;
+    use crate::bgp::{build_announce_update, build_withdraw_update, PrefixEntry};
+    use bgpkit_parser::models::{AsnLength, BgpMessage, Community};
+    use std::net::Ipv4Addr;
+    use std::sync::Arc;
+
+    fn record(message: &BgpMessage) -> Vec<u8> {
+        let mut bytes = message.encode(AsnLength::Bits32).to_vec();
+        bytes[0..16].fill(0xff);
+        encode_bgp4mp_message_as4(&UpdateRecordInput {
+            timestamp: 1_700_000_000,
+            peer_asn: 65010,
+            local_asn: 65001,
+            interface_index: 0,
+            peer_ip: Ipv4Addr::new(192, 0, 2, 10).into(),
+            local_ip: Ipv4Addr::new(192, 0, 2, 1).into(),
+            bgp_message: bytes,
+            collector_id: None,
+            family: Default::default(),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn loads_updates_file_with_attributes() {
+        let kept: IpNet = "203.0.113.0/24".parse().unwrap();
+        let withdrawn: IpNet = "198.51.100.0/24".parse().unwrap();
+        let source = RoutePath {
+            origin: Origin::EGP,
+            as_path: vec![65010, 65020],
+            attributes: vec![
+                AttributeValue::MultiExitDiscriminator(50),
+                AttributeValue::Communities(vec![Community::Custom(65010.into(), 100)]),
+            ],
+        };
+        let announce = |network| {
+            build_announce_update(
+                &PrefixEntry {
+                    network,
+                    next_hop: Some("192.0.2.10".parse().unwrap()),
+                    path: Some(Arc::new(source.clone())),
+                    peers: None,
+                },
+                Ipv4Addr::new(192, 0, 2, 10),
+                &[65010],
+            )
+        };
+
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("updates.mrt");
+        let mut mrt = record(&announce(kept));
+        mrt.extend(record(&announce(withdrawn)));
+        mrt.extend(record(&build_withdraw_update(withdrawn)));
+        std::fs::write(&file, mrt).unwrap();
+
+        let routes = load_routes(file.to_str().unwrap()).unwrap();
+        assert_eq!(routes.len(), 1);
+        let path = &routes[&kept];
+        assert_eq!(path.origin, Origin::EGP);
+        assert_eq!(path.as_path, [65010, 65010, 65020]);
+        assert_eq!(path.attributes, source.attributes);
+
+        assert!(load_routes(dir.path().join("missing").to_str().unwrap()).is_err());
+    }
+}
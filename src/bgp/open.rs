@@ -0,0 +1,190 @@
+//! Checks on the peer's OPEN (RFC 4271 section 6.2, RFC 6286, RFC 6793).
+//! A rejected OPEN is answered with the matching OPEN Message Error.
+
+use std::net::Ipv4Addr;
+
+use bgpkit_parser::models::{BgpError, BgpOpenMessage, CapabilityValue, OpenError, ParamValue};
+
+use crate::config::PeerConfig;
+
+const BGP_VERSION: u8 = 4;
+
+/// Why an OPEN was refused, and the NOTIFICATION that says so.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct OpenRejection {
+    pub error: BgpError,
+    pub data: Vec<u8>,
+    pub reason: String,
+}
+
+impl OpenRejection {
+    fn new(subcode: OpenError, reason: String) -> Self {
+        Self {
+            error: BgpError::OpenError(subcode),
+            data: Vec::new(),
+            reason,
+        }
+    }
+}
+
+/// The peer's ASN: the four-octet AS capability if present, else My AS.
+pub(super) fn peer_asn(open: &BgpOpenMessage) -> u32 {
+    open.opt_params
+        .iter()
+        .filter_map(|param| match &param.param_value {
+            ParamValue::Capacities(caps) => Some(caps.iter()),
+            ParamValue::Raw(_) => None,
+        })
+        .flatten()
+        .find_map(|cap| match &cap.value {
+            CapabilityValue::FourOctetAs(four) => Some(four.asn),
+            _ => None,
+        })
+        .unwrap_or_else(|| open.asn.to_u32())
+}
+
+/// Validate `open` from `peer`, which we reach as `local_as` with
+/// `router_id`.
+pub(super) fn check_open(
+    open: &BgpOpenMessage,
+    peer: &PeerConfig,
+    local_as: u32,
+    router_id: Ipv4Addr,
+) -> Result<(), OpenRejection> {
+    if open.version != BGP_VERSION {
+        return Err(OpenRejection {
+            // The data is the highest version we support.
+            data: u16::from(BGP_VERSION).to_be_bytes().to_vec(),
+            ..OpenRejection::new(
+                OpenError::UNSUPPORTED_VERSION_NUMBER,
+                format!("unsupported BGP version {}", open.version),
+            )
+        });
+    }
+
+    let asn = peer_asn(open);
+    if asn != peer.remote_as {
+        return Err(OpenRejection::new(
+            OpenError::BAD_PEER_AS,
+            format!("peer AS {asn} does not match remote_as {}", peer.remote_as),
+        ));
+    }
+
+    let id = open.sender_ip;
+    if id.is_unspecified() {
+        return Err(OpenRejection::new(
+            OpenError::BAD_BGP_IDENTIFIER,
+            "BGP identifier 0.0.0.0".to_string(),
+        ));
+    }
+    // Only internal peers must not share our identifier (RFC 6286).
+    if asn == local_as && id == router_id {
+        return Err(OpenRejection::new(
+            OpenError::BAD_BGP_IDENTIFIER,
+            format!("BGP identifier {id} is our own"),
+        ));
+    }
+
+    if matches!(open.hold_time, 1 | 2) {
+        return Err(OpenRejection::new(
+            OpenError::UNACCEPTABLE_HOLD_TIME,
+            format!("hold time {}s is below 3s", open.hold_time),
+        ));
+    }
+    Ok(())
+}
+
+/// Which of two colliding connections survives (RFC 4271 section 6.8): the
+/// one opened by the side with the higher BGP Identifier, so the one we
+/// accepted when the peer's identifier is above ours.
+pub(super) fn collision_keeps_inbound(router_id: Ipv4Addr, remote_id: Ipv4Addr) -> bool {
+    u32::from(router_id) < u32::from(remote_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bgpkit_parser::models::capabilities::{BgpCapabilityType, FourOctetAsCapability};
+    use bgpkit_parser::models::{Capability, OptParam};
+
+    fn open(asn: u32, sender_ip: &str, hold_time: u16) -> BgpOpenMessage {
+        BgpOpenMessage {
+            version: 4,
+            asn: u16::try_from(asn).unwrap_or(23456).into(),
+            hold_time,
+            sender_ip: sender_ip.parse().unwrap(),
+            extended_length: false,
+            opt_params: vec![OptParam {
+                param_type: 2,
+                param_len: 6,
+                param_value: ParamValue::Capacities(vec![Capability {
+                    ty: BgpCapabilityType::SUPPORT_FOR_4_OCTET_AS_NUMBER_CAPABILITY,
+                    value: CapabilityValue::FourOctetAs(FourOctetAsCapability::new(asn)),
+                }]),
+            }],
+        }
+    }
+
+    fn subcode(result: Result<(), OpenRejection>) -> OpenError {
+        match result.unwrap_err().error {
+            BgpError::OpenError(subcode) => subcode,
+            other => panic!("unexpected error {other:?}"),
+        }
+    }
+
+    #[test]
+    fn rejects_mismatched_as_bad_identifier_and_short_hold_time() {
+        let router_id: Ipv4Addr = "192.0.2.1".parse().unwrap();
+        let ebgp: PeerConfig =
+            toml::from_str("address = \"192.0.2.2\"\nremote_as = 4200000002").unwrap();
+        let ibgp: PeerConfig =
+            toml::from_str("address = \"192.0.2.3\"\nremote_as = 65001").unwrap();
+
+        let ebgp_check = |open: BgpOpenMessage| check_open(&open, &ebgp, 65001, router_id);
+
+        let good = open(4_200_000_002, "192.0.2.2", 90);
+        assert_eq!(peer_asn(&good), 4_200_000_002);
+        assert!(ebgp_check(good.clone()).is_ok());
+        // Our identifier is fine on an eBGP session, as is no hold timer.
+        assert!(ebgp_check(open(4_200_000_002, "192.0.2.1", 0)).is_ok());
+
+        assert_eq!(
+            subcode(ebgp_check(open(65003, "192.0.2.2", 90))),
+            OpenError::BAD_PEER_AS
+        );
+        assert_eq!(
+            subcode(ebgp_check(open(4_200_000_002, "0.0.0.0", 90))),
+            OpenError::BAD_BGP_IDENTIFIER
+        );
+        assert_eq!(
+            subcode(check_open(
+                &open(65001, "192.0.2.1", 90),
+                &ibgp,
+                65001,
+                router_id
+            )),
+            OpenError::BAD_BGP_IDENTIFIER
+        );
+        assert_eq!(
+            subcode(ebgp_check(open(4_200_000_002, "192.0.2.2", 2))),
+            OpenError::UNACCEPTABLE_HOLD_TIME
+        );
+
+        let mut old = good;
+        old.version = 3;
+        let rejection = ebgp_check(old).unwrap_err();
+        assert_eq!(rejection.data, [0, 4]);
+    }
+
+    #[test]
+    fn collision_keeps_the_connection_from_the_higher_identifier() {
+        let ours: Ipv4Addr = "192.0.2.10".parse().unwrap();
+        assert!(collision_keeps_inbound(ours, "192.0.2.20".parse().unwrap()));
+        assert!(!collision_keeps_inbound(ours, "192.0.2.9".parse().unwrap()));
+        // Compared as integers, not octet strings.
+        assert!(collision_keeps_inbound(
+            ours,
+            "198.51.100.1".parse().unwrap()
+        ));
+    }
+}
@@ -0,0 +1,204 @@
+//! Outbound UPDATE pacing (`[peers.pacing]`). A session queues the UPDATEs it
+//! sends in an [`Outbox`] and drains it between reads, so a full table sent
+//! at a paced rate never delays KEEPALIVEs or the hold timer.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::time::Duration;
+
+use bgpkit_parser::models::BgpMessage;
+use ipnet::IpNet;
+use tokio::time::Instant;
+
+use crate::config::PacingConfig;
+
+/// Queued UPDATEs, at most one per prefix: a later change to a prefix
+/// replaces the one still waiting, keeping its place in the queue.
+#[derive(Debug)]
+pub(super) struct Outbox {
+    queue: VecDeque<IpNet>,
+    /// The UPDATE waiting for each queued prefix, and whether it announces.
+    pending: HashMap<IpNet, (BgpMessage, bool)>,
+    /// Prefixes whose last UPDATE sent was an announcement.
+    advertised: HashSet<IpNet>,
+    bucket: Option<TokenBucket>,
+}
+
+impl Outbox {
+    /// Unpaced without `pacing`: everything queued goes out at once.
+    pub fn new(pacing: Option<&PacingConfig>, now: Instant) -> Self {
+        Self {
+            queue: VecDeque::new(),
+            pending: HashMap::new(),
+            advertised: HashSet::new(),
+            bucket: pacing.map(|pacing| TokenBucket::new(pacing, now)),
+        }
+    }
+
+    pub fn announce(&mut self, prefix: IpNet, update: BgpMessage) {
+        self.push(prefix, update, true);
+    }
+
+    pub fn withdraw(&mut self, prefix: IpNet, update: BgpMessage) {
+        self.push(prefix, update, false);
+    }
+
+    fn push(&mut self, prefix: IpNet, update: BgpMessage, announces: bool) {
+        if self.pending.insert(prefix, (update, announces)).is_none() {
+            self.queue.push_back(prefix);
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.queue.is_empty()
+    }
+
+    /// Prefixes announced to the peer so far, not counting queued UPDATEs.
+    pub fn advertised(&self) -> usize {
+        self.advertised.len()
+    }
+
+    /// When the next queued UPDATE may be sent, or `None` with nothing queued.
+    pub fn ready_at(&self, now: Instant) -> Option<Instant> {
+        if self.queue.is_empty() {
+            return None;
+        }
+        Some(
+            self.bucket
+                .as_ref()
+                .map_or(now, |bucket| bucket.ready_at(now)),
+        )
+    }
+
+    /// The next UPDATE, if the rate allows sending it now.
+    pub fn pop_ready(&mut self, now: Instant) -> Option<BgpMessage> {
+        if self.queue.is_empty() {
+            return None;
+        }
+        if let Some(bucket) = &mut self.bucket {
+            if !bucket.take(now) {
+                return None;
+            }
+        }
+        let prefix = self.queue.pop_front()?;
+        let (update, announces) = self.pending.remove(&prefix)?;
+        if announces {
+            self.advertised.insert(prefix);
+        } else {
+            self.advertised.remove(&prefix);
+        }
+        Some(update)
+    }
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    per_sec: f64,
+    burst: f64,
+    tokens: f64,
+    refilled: Instant,
+}
+
+impl TokenBucket {
+    fn new(pacing: &PacingConfig, now: Instant) -> Self {
+        let burst = f64::from(pacing.burst());
+        Self {
+            per_sec: f64::from(pacing.updates_per_sec),
+            burst,
+            tokens: burst,
+            refilled: now,
+        }
+    }
+
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+        self.tokens = (self.tokens + elapsed * self.per_sec).min(self.burst);
+        self.refilled = now;
+    }
+
+    fn take(&mut self, now: Instant) -> bool {
+        self.refill(now);
+        if self.tokens < 1.0 {
+            return false;
+        }
+        self.tokens -= 1.0;
+        true
+    }
+
+    fn ready_at(&self, now: Instant) -> Instant {
+        let elapsed = now.saturating_duration_since(self.refilled).as_secs_f64();
+        let missing = 1.0 - (self.tokens + elapsed * self.per_sec);
+        if missing <= 0.0 {
+            return now;
+        }
+        now + Duration::from_secs_f64(missing / self.per_sec)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drain(outbox: &mut Outbox, now: Instant) -> usize {
+        std::iter::from_fn(|| outbox.pop_ready(now)).count()
+    }
+
+    #[test]
+    fn sends_a_burst_then_paces_to_the_rate() {
+        let start = Instant::now();
+        let pacing = PacingConfig {
+            updates_per_sec: 10,
+            burst: Some(3),
+        };
+        let mut outbox = Outbox::new(Some(&pacing), start);
+        assert_eq!(outbox.ready_at(start), None);
+        for prefix in prefixes(10) {
+            outbox.announce(prefix, BgpMessage::KeepAlive);
+        }
+
+        assert_eq!(drain(&mut outbox, start), 3);
+        let next = outbox.ready_at(start).unwrap();
+        assert_eq!(next - start, Duration::from_millis(100));
+
+        // Half a second refills five tokens, not more than the burst.
+        assert_eq!(drain(&mut outbox, start + Duration::from_millis(500)), 3);
+        assert_eq!(drain(&mut outbox, start + Duration::from_millis(600)), 1);
+        assert_eq!(drain(&mut outbox, start + Duration::from_secs(60)), 3);
+        assert_eq!(outbox.ready_at(start), None);
+
+        let mut unpaced = Outbox::new(None, start);
+        for prefix in prefixes(1000) {
+            unpaced.announce(prefix, BgpMessage::KeepAlive);
+        }
+        assert_eq!(unpaced.ready_at(start), Some(start));
+        assert_eq!(drain(&mut unpaced, start), 1000);
+    }
+
+    #[test]
+    fn keeps_the_last_change_per_prefix_and_counts_what_was_sent() {
+        let start = Instant::now();
+        let mut outbox = Outbox::new(None, start);
+        let [a, b] = prefixes(2).try_into().unwrap();
+        outbox.announce(a, BgpMessage::KeepAlive);
+        outbox.announce(b, BgpMessage::KeepAlive);
+        // A full re-send queues nothing new.
+        outbox.announce(a, BgpMessage::KeepAlive);
+        outbox.announce(b, BgpMessage::KeepAlive);
+        outbox.withdraw(a, BgpMessage::KeepAlive);
+        assert_eq!(outbox.advertised(), 0);
+
+        assert_eq!(drain(&mut outbox, start), 2);
+        assert!(outbox.is_empty());
+        assert_eq!(outbox.advertised(), 1);
+
+        outbox.withdraw(b, BgpMessage::KeepAlive);
+        assert_eq!(outbox.advertised(), 1);
+        assert_eq!(drain(&mut outbox, start), 1);
+        assert_eq!(outbox.advertised(), 0);
+    }
+
+    fn prefixes(count: u32) -> Vec<IpNet> {
+        (0..count)
+            .map(|i| IpNet::new(std::net::Ipv4Addr::from(i << 8).into(), 24).unwrap())
+            .collect()
+    }
+}
@@ -0,0 +1,240 @@
+//! `[remote_prefix_lists]`: prefix lists fetched over HTTP(S) and refreshed
+//! on an interval, so a central system can manage collector filters.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use reqwest::header::{ETAG, IF_NONE_MATCH};
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+use super::{BgpService, RouteChange};
+use crate::config::{PrefixList, PrefixListEntry, RemotePrefixListConfig};
+
+const FETCH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// A fetched list body and the ETag to send on the next refresh; also the
+/// format of the cache file.
+#[derive(Serialize, Deserialize)]
+struct Fetched {
+    entries: Vec<PrefixListEntry>,
+    etag: Option<String>,
+}
+
+impl BgpService {
+    /// `etags` holds the ETag of each list loaded from its cache.
+    pub(super) fn spawn_remote_prefix_lists(
+        &self,
+        lists: &BTreeMap<String, RemotePrefixListConfig>,
+        mut etags: BTreeMap<String, Option<String>>,
+    ) {
+        if lists.is_empty() {
+            return;
+        }
+        let client = match reqwest::Client::builder().timeout(FETCH_TIMEOUT).build() {
+            Ok(client) => client,
+            Err(err) => {
+                tracing::error!(error=%err, "failed building HTTP client for remote prefix lists");
+                return;
+            }
+        };
+        for (name, cfg) in lists {
+            let service = self.clone();
+            let client = client.clone();
+            let name = name.clone();
+            let cfg = cfg.clone();
+            let etag = etags.remove(&name).flatten();
+            tokio::spawn(async move {
+                tokio::select! {
+                    _ = service.refresh_prefix_list(&client, &name, &cfg, etag) => {}
+                    _ = service.shutdown_requested() => {}
+                }
+            });
+        }
+    }
+
+    async fn refresh_prefix_list(
+        &self,
+        client: &reqwest::Client,
+        name: &str,
+        cfg: &RemotePrefixListConfig,
+        mut etag: Option<String>,
+    ) {
+        loop {
+            match fetch(client, &cfg.url, etag.as_deref()).await {
+                Ok(None) => tracing::debug!(list = name, "remote prefix list unchanged"),
+                Ok(Some(fetched)) => match PrefixList::compile(name, &fetched.entries) {
+                    Ok(list) => {
+                        tracing::info!(
+                            list = name,
+                            entries = fetched.entries.len(),
+                            "remote prefix list updated"
+                        );
+                        self.replace_prefix_list(name, list);
+                        if let Err(err) = save_cached(name, cfg, &fetched) {
+                            tracing::warn!(list = name, error=%err, "failed caching remote prefix list");
+                        }
+                        etag = fetched.etag;
+                    }
+                    Err(err) => {
+                        tracing::warn!(list = name, error=%err, "rejected remote prefix list, keeping previous")
+                    }
+                },
+                Err(err) => {
+                    tracing::warn!(list = name, url=%cfg.url, error=%err, "failed fetching remote prefix list")
+                }
+            }
+            tokio::time::sleep(Duration::from_secs(cfg.refresh_secs)).await;
+        }
+    }
+
+    fn replace_prefix_list(&self, name: &str, list: PrefixList) {
+        let previous = self
+            .inner
+            .prefix_lists
+            .write()
+            .expect("prefix lists lock poisoned")
+            .insert(name.to_string(), Arc::new(list))
+            .unwrap_or_default();
+        let _ = self.inner.route_tx.send(RouteChange::PrefixListChanged {
+            name: name.to_string(),
+            previous,
+        });
+    }
+}
+
+/// The last good version of `name` saved in its cache, with its ETag. A list
+/// without a usable cache matches nothing until its first fetch.
+pub(super) fn load_cached(
+    name: &str,
+    cfg: &RemotePrefixListConfig,
+) -> (PrefixList, Option<String>) {
+    let path = cache_path(name, cfg);
+    let cached = fs::read(&path)
+        .map_err(anyhow::Error::from)
+        .and_then(|raw| Ok(serde_json::from_slice::<Fetched>(&raw)?))
+        .and_then(|cached| Ok((PrefixList::compile(name, &cached.entries)?, cached.etag)));
+    match cached {
+        Ok(cached) => {
+            tracing::info!(list = name, path=%path.display(), "loaded cached remote prefix list");
+            cached
+        }
+        Err(err) => {
+            tracing::warn!(
+                list = name,
+                path=%path.display(),
+                error=%err,
+                "no cached remote prefix list, matching nothing until the first fetch"
+            );
+            (PrefixList::default(), None)
+        }
+    }
+}
+
+fn save_cached(name: &str, cfg: &RemotePrefixListConfig, fetched: &Fetched) -> Result<()> {
+    let path = cache_path(name, cfg);
+    fs::create_dir_all(&cfg.cache_dir)
+        .with_context(|| format!("cannot create {}", cfg.cache_dir.display()))?;
+    let tmp = path.with_extension("json.tmp");
+    fs::write(&tmp, serde_json::to_vec(fetched)?)?;
+    fs::rename(&tmp, &path)?;
+    Ok(())
+}
+
+fn cache_path(name: &str, cfg: &RemotePrefixListConfig) -> PathBuf {
+    cfg.cache_dir.join(format!("{name}.json"))
+}
+
+/// `None` when the server answers 304 Not Modified for `etag`.
+async fn fetch(client: &reqwest::Client, url: &str, etag: Option<&str>) -> Result<Option<Fetched>> {
+    let mut request = client.get(url);
+    if let Some(etag) = etag {
+        request = request.header(IF_NONE_MATCH, etag);
+    }
+    let response = request.send().await?;
+    if response.status() == StatusCode::NOT_MODIFIED {
+        return Ok(None);
+    }
+    let response = response.error_for_status()?;
+    let etag = response
+        .headers()
+        .get(ETAG)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+    let body = response.bytes().await?;
+    let entries = serde_json::from_slice(&body)
+        .context("remote prefix list must be a JSON array of {prefix, ge, le} entries")?;
+    Ok(Some(Fetched { entries, etag }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    #[tokio::test]
+    async fn fetches_and_revalidates_with_etag() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("http://{}/customers.json", listener.local_addr().unwrap());
+        let server = tokio::spawn(async move {
+            let mut requests = Vec::new();
+            for _ in 0..2 {
+                let (mut conn, _) = listener.accept().await.unwrap();
+                let mut buf = vec![0; 4096];
+                let n = conn.read(&mut buf).await.unwrap();
+                let request = String::from_utf8_lossy(&buf[..n]).to_lowercase();
+                let response = if request.contains("if-none-match: \"v1\"") {
+                    "HTTP/1.1 304 Not Modified\r\netag: \"v1\"\r\ncontent-length: 0\r\n\r\n"
+                        .to_string()
+                } else {
+                    let body = r#"[{"prefix": "198.51.100.0/22", "le": 24}]"#;
+                    format!(
+                        "HTTP/1.1 200 OK\r\netag: \"v1\"\r\ncontent-length: {}\r\n\r\n{body}",
+                        body.len()
+                    )
+                };
+                conn.write_all(response.as_bytes()).await.unwrap();
+                requests.push(request);
+            }
+            requests
+        });
+
+        let client = reqwest::Client::new();
+        let fetched = fetch(&client, &url, None).await.unwrap().unwrap();
+        assert_eq!(fetched.etag.as_deref(), Some("\"v1\""));
+        let list = PrefixList::compile("customers", &fetched.entries).unwrap();
+        assert!(list.matches(&"198.51.101.0/24".parse().unwrap()));
+
+        let revalidated = fetch(&client, &url, fetched.etag.as_deref()).await.unwrap();
+        assert!(revalidated.is_none());
+        assert!(server.await.unwrap()[1].contains("if-none-match"));
+    }
+
+    #[test]
+    fn cached_list_survives_a_restart() {
+        let tmp = tempfile::tempdir().unwrap();
+        let cfg = RemotePrefixListConfig {
+            url: "https://lists.example.net/customers.json".to_string(),
+            refresh_secs: 300,
+            cache_dir: tmp.path().join("remote_prefix_lists"),
+        };
+        let prefix = "198.51.101.0/24".parse().unwrap();
+        let (list, etag) = load_cached("customers", &cfg);
+        assert!(!list.matches(&prefix));
+        assert!(etag.is_none());
+
+        let fetched = Fetched {
+            entries: serde_json::from_str(r#"[{"prefix": "198.51.100.0/22", "le": 24}]"#).unwrap(),
+            etag: Some("\"v1\"".to_string()),
+        };
+        save_cached("customers", &cfg, &fetched).unwrap();
+        let (list, etag) = load_cached("customers", &cfg);
+        assert!(list.matches(&prefix));
+        assert_eq!(etag.as_deref(), Some("\"v1\""));
+    }
+}
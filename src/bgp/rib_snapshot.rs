@@ -0,0 +1,142 @@
+//! Contents of a targeted `archive_snapshot_now`: the Adj-RIB-In of some or
+//! all peers, or the Loc-RIB of routes we originate.
+
+use std::net::{IpAddr, Ipv4Addr};
+
+use anyhow::{bail, Context, Result};
+use bgpkit_parser::models::{AsPath, AsnLength, AttributeValue, Attributes, Origin};
+use ipnet::IpNet;
+
+use super::PeerInfo;
+use crate::archive::types::{SnapshotPeer, SnapshotRoute};
+
+/// Peer index table and routes of a snapshot.
+#[derive(Debug, Default)]
+pub struct SnapshotTable {
+    pub peers: Vec<SnapshotPeer>,
+    pub routes: Vec<SnapshotRoute>,
+}
+
+impl SnapshotTable {
+    /// Add a peer index entry and return its index.
+    pub(super) fn add_peer(&mut self, peer: SnapshotPeer) -> Result<u16> {
+        let Ok(index) = u16::try_from(self.peers.len()) else {
+            bail!("peer count exceeds TABLE_DUMP_V2 limit");
+        };
+        self.peers.push(peer);
+        Ok(index)
+    }
+
+    /// Add a route of peer `peer_index`; IPv6 prefixes become
+    /// RIB_IPV6_UNICAST entries.
+    pub(super) fn add_route(
+        &mut self,
+        peer_index: u16,
+        prefix: IpNet,
+        originated_time: u32,
+        attributes: &Attributes,
+    ) {
+        self.routes.push(SnapshotRoute {
+            sequence: self.routes.len() as u32,
+            prefix: prefix.network(),
+            prefix_len: prefix.prefix_len(),
+            peer_index,
+            originated_time,
+            path_attributes: attributes.encode(AsnLength::Bits32).to_vec(),
+        });
+    }
+}
+
+/// The peer index table entry of `peer`; its BGP identifier stays 0.0.0.0
+/// until it has sent an OPEN.
+pub(super) fn snapshot_peer(peer: &PeerInfo) -> Result<SnapshotPeer> {
+    Ok(SnapshotPeer {
+        peer_bgp_id: peer.remote_id.unwrap_or(Ipv4Addr::UNSPECIFIED),
+        peer_ip: peer
+            .address
+            .parse()
+            .with_context(|| format!("peer address {} is not an IP address", peer.address))?,
+        peer_asn: peer.remote_as,
+    })
+}
+
+/// ORIGIN, AS_PATH, and NEXT_HOP when known, followed by `extra`.
+pub(super) fn route_attributes(
+    origin: Origin,
+    as_path: &[u32],
+    next_hop: Option<Ipv4Addr>,
+    extra: &[AttributeValue],
+) -> Attributes {
+    let mut attrs = Attributes::default();
+    attrs.add_attr(AttributeValue::Origin(origin).into());
+    attrs.add_attr(
+        AttributeValue::AsPath {
+            path: AsPath::from_sequence(as_path),
+            is_as4: false,
+        }
+        .into(),
+    );
+    if let Some(next_hop) = next_hop {
+        attrs.add_attr(AttributeValue::NextHop(IpAddr::V4(next_hop)).into());
+    }
+    for attr in extra {
+        attrs.add_attr(attr.clone().into());
+    }
+    attrs
+}
+
+#[cfg(test)]
+mod tests {
+    use bgpkit_parser::models::{MrtMessage, TableDumpV2Message, TableDumpV2Type};
+    use bgpkit_parser::parse_mrt_record;
+
+    use super::*;
+    use crate::archive::snapshot::build_table_dump_v2;
+    use crate::archive::types::RibSnapshotInput;
+
+    #[test]
+    fn builds_routes_that_round_trip_through_table_dump_v2() {
+        let mut table = SnapshotTable::default();
+        let peer = table
+            .add_peer(SnapshotPeer {
+                peer_bgp_id: Ipv4Addr::UNSPECIFIED,
+                peer_ip: "192.0.2.2".parse().unwrap(),
+                peer_asn: 65002,
+            })
+            .unwrap();
+        let attrs = route_attributes(Origin::INCOMPLETE, &[65002, 64500], None, &[]);
+        table.add_route(
+            peer,
+            "203.0.113.0/24".parse().unwrap(),
+            1_700_000_000,
+            &attrs,
+        );
+        table.add_route(
+            peer,
+            "2001:db8::/32".parse().unwrap(),
+            1_700_000_000,
+            &attrs,
+        );
+        assert_eq!(table.routes.len(), 2);
+
+        let records = build_table_dump_v2(&RibSnapshotInput {
+            timestamp: 1_700_000_000,
+            collector_bgp_id: Ipv4Addr::new(192, 0, 2, 1),
+            view_name: "main".to_string(),
+            peers: table.peers,
+            routes: table.routes,
+        })
+        .unwrap();
+        // Peer index table, then one record per route.
+        assert_eq!(records.len(), 3);
+        let record = parse_mrt_record(&mut std::io::Cursor::new(&records[2])).unwrap();
+        match record.message {
+            MrtMessage::TableDumpV2Message(TableDumpV2Message::RibAfi(rib)) => {
+                assert_eq!(rib.rib_type, TableDumpV2Type::RibIpv6Unicast);
+                assert_eq!(rib.prefix.prefix, "2001:db8::/32".parse::<IpNet>().unwrap());
+                assert_eq!(rib.rib_entries[0].peer_index, peer);
+            }
+            other => panic!("not a RIB entry: {other:?}"),
+        }
+    }
+}
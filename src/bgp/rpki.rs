@@ -0,0 +1,163 @@
+//! RPKI origin validation (RFC 6811) of our own originations against a ROA
+//! export, so the collector never announces a prefix that would be
+//! RPKI-invalid from its ASN.
+
+use anyhow::{bail, Context, Result};
+use ipnet::IpNet;
+use serde::Deserialize;
+use serde_json::Value;
+
+use super::PrefixEntry;
+use crate::config::InvalidOriginations;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) enum RpkiState {
+    Valid,
+    Invalid,
+    NotFound,
+}
+
+#[derive(Debug)]
+struct Roa {
+    prefix: IpNet,
+    max_length: u8,
+    asn: u32,
+}
+
+#[derive(Debug, Default)]
+pub(super) struct RoaTable {
+    roas: Vec<Roa>,
+}
+
+#[derive(Deserialize)]
+struct RoaExport {
+    roas: Vec<RawRoa>,
+}
+
+#[derive(Deserialize)]
+struct RawRoa {
+    prefix: String,
+    #[serde(rename = "maxLength")]
+    max_length: Option<u8>,
+    /// `13335` (rpki-client) or `"AS13335"` (Routinator).
+    asn: Value,
+}
+
+impl RoaTable {
+    /// Load from a path or an `http(s)://` URL.
+    pub async fn load(source: &str) -> Result<Self> {
+        let body = if source.starts_with("https://") || source.starts_with("http://") {
+            reqwest::get(source)
+                .await?
+                .error_for_status()?
+                .bytes()
+                .await?
+                .to_vec()
+        } else {
+            tokio::fs::read(source).await?
+        };
+        Self::from_json(&body).with_context(|| format!("invalid ROA export {source}"))
+    }
+
+    pub fn from_json(body: &[u8]) -> Result<Self> {
+        let export: RoaExport = serde_json::from_slice(body)?;
+        let roas = export
+            .roas
+            .into_iter()
+            .map(|raw| {
+                let prefix = raw
+                    .prefix
+                    .parse::<IpNet>()
+                    .with_context(|| format!("invalid ROA prefix {}", raw.prefix))?;
+                let asn = match &raw.asn {
+                    Value::Number(n) => n.as_u64().and_then(|n| u32::try_from(n).ok()),
+                    Value::String(s) => s.trim_start_matches("AS").parse().ok(),
+                    _ => None,
+                };
+                let Some(asn) = asn else {
+                    bail!("invalid ROA asn {} for {prefix}", raw.asn);
+                };
+                Ok(Roa {
+                    prefix: prefix.trunc(),
+                    max_length: raw.max_length.unwrap_or(prefix.prefix_len()),
+                    asn,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { roas })
+    }
+
+    pub fn len(&self) -> usize {
+        self.roas.len()
+    }
+
+    /// RFC 6811: valid if a covering ROA authorizes `origin` at this
+    /// length, invalid if ROAs cover the prefix but none match.
+    pub fn validate(&self, prefix: &IpNet, origin: u32) -> RpkiState {
+        let mut covered = false;
+        for roa in self.roas.iter().filter(|roa| roa.prefix.contains(prefix)) {
+            covered = true;
+            if roa.asn == origin && prefix.prefix_len() <= roa.max_length {
+                return RpkiState::Valid;
+            }
+        }
+        if covered {
+            RpkiState::Invalid
+        } else {
+            RpkiState::NotFound
+        }
+    }
+}
+
+/// Warn about, or with `Refuse` leave out, entries that would be
+/// RPKI-invalid when originated from `origin`.
+pub(super) fn check_originations(
+    table: &RoaTable,
+    entries: &mut Vec<PrefixEntry>,
+    origin: u32,
+    policy: InvalidOriginations,
+) {
+    entries.retain(|entry| {
+        if table.validate(&entry.network, origin) != RpkiState::Invalid {
+            return true;
+        }
+        match policy {
+            InvalidOriginations::Warn => {
+                tracing::warn!(prefix=%entry.network, origin, "announcing RPKI-invalid origination");
+                true
+            }
+            InvalidOriginations::Refuse => {
+                tracing::error!(prefix=%entry.network, origin, "refusing RPKI-invalid origination");
+                false
+            }
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn validates_origins_against_covering_roas() {
+        let table = RoaTable::from_json(
+            br#"{"roas": [
+                {"prefix": "192.0.2.0/24", "maxLength": 24, "asn": 65001, "ta": "test"},
+                {"prefix": "198.51.100.0/22", "maxLength": 24, "asn": "AS65001"},
+                {"prefix": "2001:db8::/32", "asn": "AS65002"}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(table.len(), 3);
+
+        let check = |prefix: &str, origin| table.validate(&prefix.parse().unwrap(), origin);
+        assert_eq!(check("192.0.2.0/24", 65001), RpkiState::Valid);
+        assert_eq!(check("198.51.101.0/24", 65001), RpkiState::Valid);
+        assert_eq!(check("198.51.101.0/25", 65001), RpkiState::Invalid);
+        assert_eq!(check("192.0.2.0/24", 65009), RpkiState::Invalid);
+        assert_eq!(check("2001:db8::/32", 65001), RpkiState::Invalid);
+        assert_eq!(check("203.0.113.0/24", 65001), RpkiState::NotFound);
+
+        assert!(RoaTable::from_json(br#"{"roas": [{"prefix": "x", "asn": 1}]}"#).is_err());
+    }
+}
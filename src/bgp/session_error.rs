@@ -0,0 +1,137 @@
+//! Why a peer's last session attempt failed. Session errors are raised
+//! tagged with a [`PeerErrorCode`] and kept as `PeerInfo::last_error`, so
+//! tooling can alert on the category instead of matching messages.
+
+use std::fmt;
+
+use bgpkit_parser::models::BgpError;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PeerErrorCode {
+    /// The peer or local address could not be resolved.
+    Dns,
+    /// Connecting, binding, or accepting the TCP connection failed.
+    TcpConnect,
+    /// The peer's OPEN was rejected: AS, BGP identifier, hold time, or
+    /// capabilities did not match.
+    OpenMismatch,
+    /// The hold timer, or the wait for the peer's OPEN, ran out.
+    HoldExpired,
+    /// The peer sent a NOTIFICATION.
+    NotificationReceived,
+    /// Anything else, such as a connection reset or a malformed message.
+    Other,
+}
+
+/// Code and subcode of a received NOTIFICATION.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NotificationCodes {
+    pub code: u8,
+    pub subcode: u8,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PeerError {
+    pub code: PeerErrorCode,
+    pub message: String,
+    /// Unix time the session failed.
+    pub ts: i64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub notification: Option<NotificationCodes>,
+}
+
+impl PeerError {
+    /// Classify `err` by the [`SessionError`] in its chain, if any.
+    pub(super) fn from_error(err: &anyhow::Error, ts: i64) -> Self {
+        let tagged = err
+            .chain()
+            .find_map(|cause| cause.downcast_ref::<SessionError>());
+        Self {
+            code: tagged.map_or(PeerErrorCode::Other, |e| e.code),
+            message: format!("{err:#}"),
+            ts,
+            notification: tagged.and_then(|e| e.notification),
+        }
+    }
+}
+
+impl fmt::Display for PeerError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+/// A session failure tagged with its category, carried through `anyhow`.
+#[derive(Debug, thiserror::Error)]
+#[error("{message}")]
+pub(super) struct SessionError {
+    code: PeerErrorCode,
+    notification: Option<NotificationCodes>,
+    message: String,
+}
+
+impl SessionError {
+    pub fn tagged(code: PeerErrorCode, message: impl fmt::Display) -> anyhow::Error {
+        Self {
+            code,
+            notification: None,
+            message: message.to_string(),
+        }
+        .into()
+    }
+
+    /// The peer sent `error`; `context` says when.
+    pub fn notification(error: &BgpError, context: &str) -> anyhow::Error {
+        let (code, subcode) = error.get_codes();
+        Self {
+            code: PeerErrorCode::NotificationReceived,
+            notification: Some(NotificationCodes { code, subcode }),
+            message: format!("received NOTIFICATION {code}/{subcode} ({error:?}) {context}"),
+        }
+        .into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+    use bgpkit_parser::models::CeaseNotification;
+
+    #[test]
+    fn classifies_tagged_errors_through_context() {
+        let err = Err::<(), _>(SessionError::tagged(
+            PeerErrorCode::TcpConnect,
+            "connection refused",
+        ))
+        .context("connecting to 192.0.2.2")
+        .unwrap_err();
+        let peer_error = PeerError::from_error(&err, 1_700_000_000);
+        assert_eq!(peer_error.code, PeerErrorCode::TcpConnect);
+        assert_eq!(
+            peer_error.message,
+            "connecting to 192.0.2.2: connection refused"
+        );
+        assert_eq!(peer_error.ts, 1_700_000_000);
+
+        let cease = BgpError::CeaseNotification(CeaseNotification::ADMINISTRATIVE_SHUTDOWN);
+        let peer_error = PeerError::from_error(&SessionError::notification(&cease, "from peer"), 0);
+        assert_eq!(peer_error.code, PeerErrorCode::NotificationReceived);
+        assert_eq!(
+            peer_error.notification,
+            Some(NotificationCodes {
+                code: 6,
+                subcode: 2
+            })
+        );
+
+        let other = PeerError::from_error(&anyhow::anyhow!("connection reset"), 0);
+        assert_eq!(
+            (other.code, other.notification),
+            (PeerErrorCode::Other, None)
+        );
+        assert_eq!(other.to_string(), "connection reset");
+    }
+}
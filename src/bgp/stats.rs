@@ -0,0 +1,369 @@
+use std::collections::VecDeque;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::time::Instant;
+
+use super::RibSummary;
+use crate::types::PeerState;
+
+/// Exponentially weighted moving average of an event rate, in events/sec.
+///
+/// Each recorded event contributes `1 / window` and decays with time constant
+/// `window`, so a steady stream converges on its true per-second rate.
+#[derive(Debug, Clone)]
+pub struct RateMeter {
+    window_secs: f64,
+    rate: f64,
+    last: Option<Instant>,
+}
+
+impl RateMeter {
+    pub fn new(window_secs: f64) -> Self {
+        Self {
+            window_secs,
+            rate: 0.0,
+            last: None,
+        }
+    }
+
+    pub fn record(&mut self, count: u64) {
+        self.record_at(count, Instant::now());
+    }
+
+    pub fn record_at(&mut self, count: u64, now: Instant) {
+        self.rate = self.rate_at(now) + count as f64 / self.window_secs;
+        self.last = Some(now);
+    }
+
+    pub fn rate(&self) -> f64 {
+        self.rate_at(Instant::now())
+    }
+
+    pub fn rate_at(&self, now: Instant) -> f64 {
+        match self.last {
+            Some(last) => {
+                let elapsed = now.saturating_duration_since(last).as_secs_f64();
+                self.rate * (-elapsed / self.window_secs).exp()
+            }
+            None => 0.0,
+        }
+    }
+}
+
+/// Timestamps of session drops within a trailing window.
+#[derive(Debug, Clone)]
+pub struct FlapHistory {
+    window_secs: i64,
+    events: VecDeque<i64>,
+}
+
+impl FlapHistory {
+    pub fn new(window_secs: i64) -> Self {
+        Self {
+            window_secs,
+            events: VecDeque::new(),
+        }
+    }
+
+    pub fn record(&mut self, ts: i64) {
+        self.events.push_back(ts);
+        self.prune(ts);
+    }
+
+    pub fn count(&self, now: i64) -> usize {
+        let cutoff = now - self.window_secs;
+        self.events.iter().filter(|ts| **ts > cutoff).count()
+    }
+
+    fn prune(&mut self, now: i64) {
+        let cutoff = now - self.window_secs;
+        while self.events.front().is_some_and(|ts| *ts <= cutoff) {
+            self.events.pop_front();
+        }
+    }
+}
+
+/// UPDATEs and withdrawn prefixes per Unix minute, kept for the last
+/// [`MinuteCounts::MAX_MINUTES`] minutes.
+#[derive(Debug, Clone, Default)]
+pub struct MinuteCounts {
+    buckets: VecDeque<(i64, u64, u64)>,
+}
+
+impl MinuteCounts {
+    pub const MAX_MINUTES: u32 = 60;
+
+    /// Count one UPDATE withdrawing `withdrawn` prefixes at Unix second `now`.
+    pub fn record_at(&mut self, withdrawn: u64, now: i64) {
+        let minute = now.div_euclid(60);
+        match self.buckets.back_mut() {
+            Some((m, updates, withdrawals)) if *m == minute => {
+                *updates += 1;
+                *withdrawals += withdrawn;
+            }
+            _ => self.buckets.push_back((minute, 1, withdrawn)),
+        }
+        while self
+            .buckets
+            .front()
+            .is_some_and(|(m, _, _)| *m <= minute - i64::from(Self::MAX_MINUTES))
+        {
+            self.buckets.pop_front();
+        }
+    }
+
+    /// `(updates, withdrawals)` in the last `minutes` minutes, the current
+    /// one included.
+    pub fn totals_at(&self, minutes: u32, now: i64) -> (u64, u64) {
+        let since = now.div_euclid(60) - i64::from(minutes.min(Self::MAX_MINUTES));
+        self.buckets
+            .iter()
+            .filter(|(m, _, _)| *m > since)
+            .fold((0, 0), |(u, w), (_, updates, withdrawals)| {
+                (u + updates, w + withdrawals)
+            })
+    }
+}
+
+/// Running totals behind `rib_summary`, adjusted wherever peer state
+/// changes so that reading them never walks the peer table.
+#[derive(Debug, Default)]
+pub struct RibCounters {
+    peers_total: AtomicUsize,
+    peers_established: AtomicUsize,
+    peers_maintenance: AtomicUsize,
+    advertised_prefixes: AtomicUsize,
+    prefixes_received: AtomicU64,
+}
+
+impl RibCounters {
+    pub fn peer_added(&self) {
+        self.peers_total.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn state_changed(&self, old: PeerState, new: PeerState) {
+        match (old, new) {
+            (PeerState::Established, PeerState::Established) => {}
+            (PeerState::Established, _) => {
+                self.peers_established.fetch_sub(1, Ordering::Relaxed);
+            }
+            (_, PeerState::Established) => {
+                self.peers_established.fetch_add(1, Ordering::Relaxed);
+            }
+            _ => {}
+        }
+    }
+
+    /// A peer in maintenance is counted apart: it leaves `peers_total` and,
+    /// while its session stays up, `peers_established`. Callers stop passing
+    /// its state changes to [`Self::state_changed`] until it is back.
+    pub fn maintenance_changed(&self, state: PeerState, draining: bool) {
+        let established = usize::from(matches!(state, PeerState::Established));
+        if draining {
+            self.peers_total.fetch_sub(1, Ordering::Relaxed);
+            self.peers_established
+                .fetch_sub(established, Ordering::Relaxed);
+            self.peers_maintenance.fetch_add(1, Ordering::Relaxed);
+        } else {
+            self.peers_total.fetch_add(1, Ordering::Relaxed);
+            self.peers_established
+                .fetch_add(established, Ordering::Relaxed);
+            self.peers_maintenance.fetch_sub(1, Ordering::Relaxed);
+        }
+    }
+
+    pub fn advertised_changed(&self, old: usize, new: usize) {
+        if new >= old {
+            self.advertised_prefixes
+                .fetch_add(new - old, Ordering::Relaxed);
+        } else {
+            self.advertised_prefixes
+                .fetch_sub(old - new, Ordering::Relaxed);
+        }
+    }
+
+    pub fn prefixes_received(&self, count: u64) {
+        self.prefixes_received.fetch_add(count, Ordering::Relaxed);
+    }
+
+    /// A peer's received-prefix count was reset.
+    pub fn prefixes_forgotten(&self, count: u64) {
+        self.prefixes_received.fetch_sub(count, Ordering::Relaxed);
+    }
+
+    pub fn summary(&self) -> RibSummary {
+        RibSummary {
+            peers_total: self.peers_total.load(Ordering::Relaxed),
+            peers_established: self.peers_established.load(Ordering::Relaxed),
+            peers_maintenance: self.peers_maintenance.load(Ordering::Relaxed),
+            advertised_prefixes_total: self.advertised_prefixes.load(Ordering::Relaxed),
+            prefixes_received_total: self.prefixes_received.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Rate limit for announcing shed archive records, so an overloaded archive
+/// does not also flood the event bus.
+#[derive(Debug, Clone, Default)]
+pub struct ShedNotice {
+    unreported: u64,
+    last: Option<Instant>,
+}
+
+impl ShedNotice {
+    /// Count one shed record. Returns the number to announce when a notice
+    /// is due: on the first shed record and then at most once per `interval`.
+    pub fn record_at(&mut self, now: Instant, interval: std::time::Duration) -> Option<u64> {
+        self.unreported += 1;
+        if self
+            .last
+            .is_some_and(|last| now.saturating_duration_since(last) < interval)
+        {
+            return None;
+        }
+        self.last = Some(now);
+        Some(std::mem::take(&mut self.unreported))
+    }
+}
+
+/// Drops an UPDATE that repeats the previous archived one byte for byte
+/// within `window`. The window runs from the last archived copy, so a
+/// steadily repeating UPDATE is still archived once per window.
+#[derive(Debug, Default)]
+pub struct DuplicateFilter {
+    window: std::time::Duration,
+    last: Vec<u8>,
+    last_at: Option<Instant>,
+}
+
+impl DuplicateFilter {
+    /// A zero `window` disables the filter.
+    pub fn new(window: std::time::Duration) -> Self {
+        Self {
+            window,
+            ..Self::default()
+        }
+    }
+
+    pub fn is_duplicate_at(&mut self, frame: &[u8], now: Instant) -> bool {
+        if self.window.is_zero() {
+            return false;
+        }
+        let duplicate = self
+            .last_at
+            .is_some_and(|at| now.saturating_duration_since(at) <= self.window)
+            && self.last == frame;
+        if !duplicate {
+            self.last.clear();
+            self.last.extend_from_slice(frame);
+            self.last_at = Some(now);
+        }
+        duplicate
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn rate_meter_decays_over_window() {
+        let start = Instant::now();
+        let mut meter = RateMeter::new(60.0);
+        meter.record_at(600, start);
+
+        let initial = meter.rate_at(start);
+        assert!((initial - 10.0).abs() < 1e-9);
+
+        let later = meter.rate_at(start + Duration::from_secs(60));
+        assert!((later - 10.0 / std::f64::consts::E).abs() < 1e-6);
+    }
+
+    #[test]
+    fn flap_history_counts_only_recent_events() {
+        let mut flaps = FlapHistory::new(3600);
+        flaps.record(1_000);
+        flaps.record(4_000);
+        flaps.record(4_500);
+        assert_eq!(flaps.count(4_700), 2);
+        assert_eq!(flaps.count(9_000), 0);
+    }
+
+    #[test]
+    fn shed_notices_are_rate_limited() {
+        let interval = std::time::Duration::from_secs(1);
+        let start = Instant::now();
+        let mut notice = ShedNotice::default();
+        assert_eq!(notice.record_at(start, interval), Some(1));
+        assert_eq!(notice.record_at(start, interval), None);
+        assert_eq!(notice.record_at(start + interval / 2, interval), None);
+        assert_eq!(notice.record_at(start + interval, interval), Some(3));
+    }
+
+    #[test]
+    fn minute_counts_sum_recent_minutes() {
+        let start = 1_700_000_000 - 1_700_000_000 % 60;
+        let mut counts = MinuteCounts::default();
+        counts.record_at(0, start);
+        counts.record_at(2, start + 59);
+        counts.record_at(1, start + 120);
+        assert_eq!(counts.totals_at(1, start + 130), (1, 1));
+        assert_eq!(counts.totals_at(5, start + 130), (3, 3));
+
+        counts.record_at(0, start + 3600);
+        assert_eq!(counts.totals_at(60, start + 3600), (2, 1));
+    }
+
+    #[test]
+    fn rib_counters_follow_transitions() {
+        let counters = RibCounters::default();
+        counters.peer_added();
+        counters.peer_added();
+        counters.state_changed(PeerState::OpenSent, PeerState::Established);
+        counters.state_changed(PeerState::Established, PeerState::Established);
+        counters.advertised_changed(0, 5);
+        counters.prefixes_received(7);
+        let summary = counters.summary();
+        assert_eq!(summary.peers_total, 2);
+        assert_eq!(summary.peers_established, 1);
+        assert_eq!(summary.advertised_prefixes_total, 5);
+        assert_eq!(summary.prefixes_received_total, 7);
+
+        counters.state_changed(PeerState::Established, PeerState::Active);
+        counters.advertised_changed(5, 2);
+        let summary = counters.summary();
+        assert_eq!(summary.peers_established, 0);
+        assert_eq!(summary.advertised_prefixes_total, 2);
+
+        counters.state_changed(PeerState::Active, PeerState::Established);
+        counters.maintenance_changed(PeerState::Established, true);
+        let summary = counters.summary();
+        assert_eq!(summary.peers_total, 1);
+        assert_eq!(summary.peers_established, 0);
+        assert_eq!(summary.peers_maintenance, 1);
+
+        counters.maintenance_changed(PeerState::Established, false);
+        let summary = counters.summary();
+        assert_eq!(summary.peers_total, 2);
+        assert_eq!(summary.peers_established, 1);
+        assert_eq!(summary.peers_maintenance, 0);
+    }
+
+    #[test]
+    fn duplicate_filter_drops_repeats_within_window() {
+        let start = Instant::now();
+        let mut filter = DuplicateFilter::new(Duration::from_millis(500));
+        assert!(!filter.is_duplicate_at(b"update-a", start));
+        assert!(filter.is_duplicate_at(b"update-a", start + Duration::from_millis(100)));
+        assert!(filter.is_duplicate_at(b"update-a", start + Duration::from_millis(450)));
+        // Measured from the last archived copy, not the last duplicate.
+        assert!(!filter.is_duplicate_at(b"update-a", start + Duration::from_millis(600)));
+        assert!(!filter.is_duplicate_at(b"update-b", start + Duration::from_millis(700)));
+        assert!(!filter.is_duplicate_at(b"update-a", start + Duration::from_millis(750)));
+
+        let mut off = DuplicateFilter::new(Duration::ZERO);
+        assert!(!off.is_duplicate_at(b"update-a", start));
+        assert!(!off.is_duplicate_at(b"update-a", start));
+    }
+}
@@ -0,0 +1,163 @@
+//! A bounded per-peer timeline of session events (`session_history`): state
+//! changes, NOTIFICATIONs in either direction, resets, and session errors.
+//! Returned by `peer_show` with `history`.
+
+use std::collections::VecDeque;
+use std::sync::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use super::session_error::{PeerError, PeerErrorCode};
+use super::{ResetMode, TYPE_OFFSET};
+use crate::types::PeerState;
+
+const MSG_NOTIFICATION: u8 = 3;
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SessionEvent {
+    /// Unix time of the event.
+    pub ts: i64,
+    #[serde(flatten)]
+    pub kind: SessionEventKind,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum SessionEventKind {
+    StateChange {
+        from: PeerState,
+        to: PeerState,
+    },
+    NotificationSent {
+        code: u8,
+        subcode: u8,
+    },
+    NotificationReceived {
+        code: u8,
+        subcode: u8,
+    },
+    /// A `peer_reset` was requested.
+    Reset {
+        mode: ResetMode,
+    },
+    /// A session attempt failed; the same error becomes `last_error`.
+    Error {
+        code: PeerErrorCode,
+        message: String,
+    },
+}
+
+impl From<&PeerError> for SessionEventKind {
+    fn from(error: &PeerError) -> Self {
+        Self::Error {
+            code: error.code,
+            message: error.message.clone(),
+        }
+    }
+}
+
+/// The last `capacity` events of one peer, oldest first. Shared with the
+/// peer's sessions, which record the NOTIFICATIONs they send and receive.
+#[derive(Debug)]
+pub(super) struct SessionTimeline {
+    capacity: usize,
+    events: Mutex<VecDeque<SessionEvent>>,
+}
+
+impl SessionTimeline {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            events: Mutex::new(VecDeque::with_capacity(capacity)),
+        }
+    }
+
+    pub fn record(&self, kind: SessionEventKind) {
+        self.record_at(chrono::Utc::now().timestamp(), kind);
+    }
+
+    pub fn record_at(&self, ts: i64, kind: SessionEventKind) {
+        if self.capacity == 0 {
+            return;
+        }
+        let mut events = self.lock();
+        if events.len() == self.capacity {
+            events.pop_front();
+        }
+        events.push_back(SessionEvent { ts, kind });
+    }
+
+    /// Note `bytes`, one complete BGP message sent by us when `outbound`, if
+    /// it is a NOTIFICATION.
+    pub fn record_message(&self, outbound: bool, bytes: &[u8]) {
+        if bytes.get(TYPE_OFFSET) != Some(&MSG_NOTIFICATION) {
+            return;
+        }
+        let (code, subcode) = match bytes.get(TYPE_OFFSET + 1..TYPE_OFFSET + 3) {
+            Some(&[code, subcode]) => (code, subcode),
+            _ => return,
+        };
+        self.record(if outbound {
+            SessionEventKind::NotificationSent { code, subcode }
+        } else {
+            SessionEventKind::NotificationReceived { code, subcode }
+        });
+    }
+
+    pub fn snapshot(&self) -> Vec<SessionEvent> {
+        self.lock().iter().cloned().collect()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, VecDeque<SessionEvent>> {
+        self.events.lock().expect("session timeline lock poisoned")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_latest_events_and_notes_notifications() {
+        let timeline = SessionTimeline::new(2);
+        timeline.record_at(
+            1,
+            SessionEventKind::StateChange {
+                from: PeerState::Idle,
+                to: PeerState::Connect,
+            },
+        );
+        timeline.record_at(
+            2,
+            SessionEventKind::Reset {
+                mode: ResetMode::Hard,
+            },
+        );
+        let mut cease = vec![0xff; 16];
+        cease.extend_from_slice(&[0, 21, MSG_NOTIFICATION, 6, 2]);
+        timeline.record_message(true, &cease);
+        // A KEEPALIVE is not a session event.
+        let mut keepalive = vec![0xff; 16];
+        keepalive.extend_from_slice(&[0, 19, 4]);
+        timeline.record_message(false, &keepalive);
+
+        let events = timeline.snapshot();
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].ts, 2);
+        assert_eq!(
+            events[1].kind,
+            SessionEventKind::NotificationSent {
+                code: 6,
+                subcode: 2
+            }
+        );
+        assert_eq!(
+            serde_json::to_value(&events[0]).unwrap(),
+            serde_json::json!({"ts": 2, "event": "reset", "mode": "hard"})
+        );
+
+        let disabled = SessionTimeline::new(0);
+        disabled.record_message(false, &cease);
+        assert!(disabled.snapshot().is_empty());
+    }
+}
@@ -0,0 +1,144 @@
+//! Per-peer timer state behind `peer_timers`. The session task publishes its
+//! deadlines through atomics so neither side takes the peer lock per message.
+
+use std::sync::atomic::{AtomicI64, AtomicU32, Ordering};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::time::Instant;
+
+use crate::config::PeerConfig;
+
+/// Configured and negotiated timers of one peer. `*_at_ms` are Unix
+/// timestamps in milliseconds of the timer's next expiry, absent while the
+/// timer is not running.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerTimers {
+    pub address: String,
+    pub hold_time_secs: u16,
+    pub keepalive_secs: u16,
+    pub connect_retry_secs: u16,
+    pub negotiated_hold_secs: Option<u16>,
+    pub negotiated_keepalive_secs: Option<u16>,
+    pub keepalive_at_ms: Option<i64>,
+    pub hold_expires_at_ms: Option<i64>,
+    pub connect_retry_at_ms: Option<i64>,
+}
+
+/// 0 means "not running" for every field.
+#[derive(Debug, Default)]
+pub(super) struct TimerState {
+    negotiated_hold_secs: AtomicU32,
+    keepalive_at_ms: AtomicI64,
+    hold_expires_at_ms: AtomicI64,
+    connect_retry_at_ms: AtomicI64,
+}
+
+impl TimerState {
+    pub fn established(&self, negotiated_hold_secs: u16) {
+        self.negotiated_hold_secs
+            .store(u32::from(negotiated_hold_secs), Ordering::Relaxed);
+        self.connect_retry_at_ms.store(0, Ordering::Relaxed);
+    }
+
+    pub fn keepalive_at(&self, at: Instant) {
+        self.keepalive_at_ms.store(unix_ms(at), Ordering::Relaxed);
+    }
+
+    pub fn hold_expires_at(&self, at: Instant) {
+        self.hold_expires_at_ms
+            .store(unix_ms(at), Ordering::Relaxed);
+    }
+
+    /// Whether a session reached Established since the last `session_down`.
+    pub fn session_up(&self) -> bool {
+        self.negotiated_hold_secs.load(Ordering::Relaxed) != 0
+    }
+
+    pub fn session_down(&self) {
+        self.negotiated_hold_secs.store(0, Ordering::Relaxed);
+        self.keepalive_at_ms.store(0, Ordering::Relaxed);
+        self.hold_expires_at_ms.store(0, Ordering::Relaxed);
+    }
+
+    pub fn connect_retry_in(&self, after: Duration) {
+        self.connect_retry_at_ms
+            .store(unix_ms(Instant::now() + after), Ordering::Relaxed);
+    }
+
+    pub fn connect_retry_fired(&self) {
+        self.connect_retry_at_ms.store(0, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self, cfg: &PeerConfig) -> PeerTimers {
+        let load = |value: &AtomicI64| Some(value.load(Ordering::Relaxed)).filter(|&ms| ms != 0);
+        let negotiated_hold = self.negotiated_hold_secs.load(Ordering::Relaxed) as u16;
+        let negotiated = (negotiated_hold != 0).then_some(negotiated_hold);
+        PeerTimers {
+            address: cfg.address.clone(),
+            hold_time_secs: cfg.hold_time_secs,
+            keepalive_secs: keepalive_secs(cfg.hold_time_secs),
+            connect_retry_secs: cfg.connect_retry_secs,
+            negotiated_hold_secs: negotiated,
+            negotiated_keepalive_secs: negotiated.map(keepalive_secs),
+            keepalive_at_ms: load(&self.keepalive_at_ms),
+            hold_expires_at_ms: load(&self.hold_expires_at_ms),
+            connect_retry_at_ms: load(&self.connect_retry_at_ms),
+        }
+    }
+}
+
+/// KEEPALIVEs go out at a third of the hold time (RFC 4271 section 10).
+pub(super) fn keepalive_secs(hold_time_secs: u16) -> u16 {
+    (hold_time_secs / 3).max(1)
+}
+
+fn unix_ms(at: Instant) -> i64 {
+    let now = Instant::now();
+    let offset = if at >= now {
+        (at - now).as_millis() as i64
+    } else {
+        -((now - at).as_millis() as i64)
+    };
+    chrono::Utc::now().timestamp_millis() + offset
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reports_running_timers_only() {
+        let cfg: PeerConfig = toml::from_str(
+            r#"
+address = "192.0.2.2"
+remote_as = 65002
+hold_time_secs = 90
+"#,
+        )
+        .unwrap();
+        let state = TimerState::default();
+        let idle = state.snapshot(&cfg);
+        assert_eq!(idle.keepalive_secs, 30);
+        assert_eq!(idle.negotiated_hold_secs, None);
+        assert_eq!(idle.hold_expires_at_ms, None);
+
+        let before = chrono::Utc::now().timestamp_millis();
+        state.established(30);
+        state.keepalive_at(Instant::now() + Duration::from_secs(10));
+        state.hold_expires_at(Instant::now() + Duration::from_secs(30));
+        let up = state.snapshot(&cfg);
+        assert_eq!(up.negotiated_hold_secs, Some(30));
+        assert_eq!(up.negotiated_keepalive_secs, Some(10));
+        let hold_at = up.hold_expires_at_ms.unwrap();
+        assert!(hold_at >= before + 29_000 && hold_at <= before + 31_000);
+        assert!(up.keepalive_at_ms.unwrap() < hold_at);
+
+        state.session_down();
+        state.connect_retry_in(Duration::from_secs(5));
+        let down = state.snapshot(&cfg);
+        assert_eq!(down.negotiated_hold_secs, None);
+        assert_eq!(down.keepalive_at_ms, None);
+        assert!(down.connect_retry_at_ms.is_some());
+    }
+}
@@ -0,0 +1,64 @@
+//! `focl-sim`: play a scripted BGP peer against a collector, for lab tests
+//! of session handling, archive ingest, and failure paths.
+
+use std::net::{Ipv4Addr, SocketAddr};
+use std::path::PathBuf;
+
+use anyhow::{bail, Context, Result};
+use clap::Parser;
+use focl::sim::{SimConfig, SimPeer, Step};
+use serde::Deserialize;
+use tokio::net::TcpListener;
+
+#[derive(Debug, Parser)]
+struct Args {
+    /// TOML file with a `[[steps]]` array, run in order
+    script: PathBuf,
+    /// Connect to a collector that has us configured as a passive peer
+    #[arg(long, conflicts_with = "listen")]
+    connect: Option<SocketAddr>,
+    /// Wait for a collector that has us configured as an active peer
+    #[arg(long)]
+    listen: Option<SocketAddr>,
+    #[arg(long)]
+    asn: u32,
+    #[arg(long, default_value = "192.0.2.2")]
+    router_id: Ipv4Addr,
+    #[arg(long, default_value_t = 90)]
+    hold_time: u16,
+    /// NEXT_HOP of announced routes; defaults to the router ID
+    #[arg(long)]
+    next_hop: Option<Ipv4Addr>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Script {
+    steps: Vec<Step>,
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let args = Args::parse();
+    let raw = std::fs::read_to_string(&args.script)
+        .with_context(|| format!("failed reading {}", args.script.display()))?;
+    let script: Script = toml::from_str(&raw)
+        .with_context(|| format!("failed parsing {}", args.script.display()))?;
+
+    let mut cfg = SimConfig::new(args.asn, args.router_id);
+    cfg.hold_time = args.hold_time;
+    cfg.next_hop = args.next_hop.unwrap_or(args.router_id);
+
+    let mut peer = match (args.connect, args.listen) {
+        (Some(addr), _) => SimPeer::connect(cfg, addr).await?,
+        (None, Some(addr)) => {
+            let listener = TcpListener::bind(addr)
+                .await
+                .with_context(|| format!("failed binding {addr}"))?;
+            SimPeer::accept(cfg, &listener).await?
+        }
+        (None, None) => bail!("one of --connect or --listen is required"),
+    };
+    peer.run(&script.steps).await?;
+    println!("{} steps completed", script.steps.len());
+    Ok(())
+}
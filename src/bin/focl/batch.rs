@@ -0,0 +1,90 @@
+//! `focl batch`: sub-requests for the `batch` control command, read from a
+//! file of JSON objects (one per line, or a single array) with `cmd` and
+//! optional `args` and `id`.
+
+use std::io::Read;
+use std::path::Path;
+
+use anyhow::{bail, Context, Result};
+use focl::types::ControlRequest;
+use serde::Deserialize;
+use serde_json::Value;
+
+#[derive(Deserialize)]
+struct BatchEntry {
+    cmd: String,
+    #[serde(default)]
+    args: Value,
+    #[serde(default)]
+    id: Option<String>,
+}
+
+/// Read the requests in `path`, or stdin for `-`. Entries without an `id`
+/// are numbered from 1 in file order.
+pub fn read_requests(path: &Path) -> Result<Vec<ControlRequest>> {
+    let mut raw = String::new();
+    if path == Path::new("-") {
+        std::io::stdin().read_to_string(&mut raw)?;
+    } else {
+        raw = std::fs::read_to_string(path)
+            .with_context(|| format!("failed reading {}", path.display()))?;
+    }
+    parse_requests(&raw)
+}
+
+fn parse_requests(raw: &str) -> Result<Vec<ControlRequest>> {
+    let entries: Vec<BatchEntry> = if raw.trim_start().starts_with('[') {
+        serde_json::from_str(raw).context("invalid batch array")?
+    } else {
+        raw.lines()
+            .enumerate()
+            .filter(|(_, line)| !line.trim().is_empty())
+            .map(|(n, line)| {
+                serde_json::from_str(line).with_context(|| format!("invalid batch line {}", n + 1))
+            })
+            .collect::<Result<_>>()?
+    };
+    if entries.is_empty() {
+        bail!("batch holds no requests");
+    }
+    Ok(entries
+        .into_iter()
+        .enumerate()
+        .map(|(n, entry)| ControlRequest {
+            version: 1,
+            id: entry.id.unwrap_or_else(|| (n + 1).to_string()),
+            cmd: entry.cmd,
+            args: entry.args,
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_lines_or_an_array_and_numbers_missing_ids() {
+        let lines = r#"
+{"cmd": "peer_show", "args": {"peer": "192.0.2.2"}}
+
+{"cmd": "ping", "id": "first-ping"}
+"#;
+        let requests = parse_requests(lines).unwrap();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(
+            (requests[0].id.as_str(), requests[0].cmd.as_str()),
+            ("1", "peer_show")
+        );
+        assert_eq!(requests[0].args["peer"], "192.0.2.2");
+        assert_eq!(requests[1].id, "first-ping");
+        assert!(requests[1].args.is_null());
+
+        let array = r#"[{"cmd": "ping"}, {"cmd": "health"}]"#;
+        let requests = parse_requests(array).unwrap();
+        assert_eq!(requests[1].id, "2");
+
+        assert!(parse_requests("\n").is_err());
+        assert!(parse_requests("{\"args\": {}}").is_err());
+    }
+}
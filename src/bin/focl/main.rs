@@ -0,0 +1,1098 @@
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+
+use anyhow::{Context, Result};
+use clap::{Args, Parser, Subcommand};
+use focl::types::{ControlRequest, ControlResponse};
+use serde_json::json;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::UnixStream;
+
+mod batch;
+mod mrt;
+mod show;
+mod status;
+mod top;
+
+#[derive(Debug, Parser)]
+#[command(name = "focl", about = "CLI for focld control plane")]
+struct Cli {
+    /// Control socket path (defaults to the instance socket, or /tmp/focld.sock)
+    #[arg(long)]
+    socket: Option<PathBuf>,
+
+    /// Named daemon instance to control
+    #[arg(long, global = true)]
+    instance: Option<String>,
+
+    #[command(subcommand)]
+    command: Commands,
+}
+
+#[derive(Debug, Subcommand)]
+enum Commands {
+    Start {
+        #[arg(short, long, default_value = "focl.toml")]
+        config: PathBuf,
+        /// Run focld attached to this terminal, streaming its logs and forwarding signals
+        #[arg(long, visible_alias = "attach")]
+        foreground: bool,
+    },
+    Stop {
+        /// Block until the daemon has exited
+        #[arg(long)]
+        wait: bool,
+        /// Seconds to wait before giving up (with --wait)
+        #[arg(long, default_value_t = 30)]
+        timeout: u64,
+    },
+    Reload,
+    Health,
+    /// Send many control requests in one round trip, from a file of JSON
+    /// objects with `cmd` and `args` (`-` reads stdin)
+    Batch {
+        file: PathBuf,
+        /// Run the requests concurrently instead of in order
+        #[arg(long)]
+        concurrent: bool,
+    },
+    /// Validate a config file without starting focld
+    Check {
+        #[arg(short, long, default_value = "focl.toml")]
+        config: PathBuf,
+        /// Also probe archive paths, free space, S3 destinations, and the control socket directory
+        #[arg(long)]
+        preflight: bool,
+    },
+    /// Summarize daemon, peer, and archive state in one view
+    Status {
+        /// Print the combined summary as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Live dashboard of peers, update rates, and archive progress
+    Top {
+        /// Refresh interval in seconds
+        #[arg(long, default_value_t = 2)]
+        interval: u64,
+    },
+    Peer {
+        #[command(subcommand)]
+        command: PeerCommands,
+    },
+    Rib {
+        #[command(subcommand)]
+        command: RibCommands,
+    },
+    Bgp {
+        #[command(subcommand)]
+        command: BgpCommands,
+    },
+    Prefix {
+        #[command(subcommand)]
+        command: PrefixCommands,
+    },
+    /// Remotely triggered blackholing toward `[blackhole].peers`
+    Blackhole {
+        #[command(subcommand)]
+        command: BlackholeCommands,
+    },
+    /// FRR-style text views
+    Show {
+        #[command(subcommand)]
+        command: ShowCommands,
+    },
+    Archive {
+        #[command(subcommand)]
+        command: ArchiveCommands,
+    },
+    Events {
+        #[command(subcommand)]
+        command: EventsCommands,
+    },
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommands,
+    },
+    /// Offline tools for MRT files
+    Mrt {
+        #[command(subcommand)]
+        command: MrtCommands,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum MrtCommands {
+    /// Summarize an MRT file: record types, time range, peers, and top prefixes
+    Inspect {
+        file: PathBuf,
+        /// Number of most frequent prefixes to list
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ConfigCommands {
+    /// Print the JSON Schema for focl.toml
+    Schema,
+}
+
+#[derive(Debug, Subcommand)]
+enum PeerCommands {
+    List,
+    Show {
+        peer: String,
+        /// Include the peer's recent session events
+        #[arg(long)]
+        history: bool,
+    },
+    /// Configured and negotiated hold/keepalive/connect-retry timers
+    Timers {
+        peer: Option<String>,
+    },
+    Reset {
+        peer: String,
+        /// Re-send our announcements without dropping the session
+        #[arg(long, conflicts_with = "soft_in")]
+        soft_out: bool,
+        /// Ask the peer to re-advertise via ROUTE-REFRESH without dropping the session
+        #[arg(long)]
+        soft_in: bool,
+    },
+    /// Zero a peer's cumulative counters, in memory and in `[peer_stats]`
+    StatsReset {
+        /// Every peer when omitted
+        peer: Option<String>,
+    },
+    /// Drain a peer: withdraw our routes and leave it out of health checks
+    Maintenance {
+        peer: String,
+        #[arg(value_parser = ["on", "off"])]
+        state: String,
+        /// Keep the session up so the peer's routes are still collected
+        #[arg(long)]
+        keep_session: bool,
+    },
+    /// Hex-dump every BGP message sent to and received from the peer
+    LogMessages {
+        peer: String,
+        #[arg(value_parser = ["on", "off"])]
+        state: String,
+    },
+    /// Block until the peer reaches a state; exits 1 on timeout
+    Wait {
+        peer: String,
+        #[arg(long, default_value = "established", value_parser = [
+            "idle", "connect", "active", "open_sent", "open_confirm", "established",
+        ])]
+        state: String,
+        /// Seconds to wait, at most 3600
+        #[arg(long, default_value_t = 60)]
+        timeout: u64,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum RibCommands {
+    Summary,
+    In(RibQuery),
+    Out(RibQuery),
+    /// Prefixes with the most announcements and withdrawals recently
+    Churn {
+        /// Minutes to look back; defaults to the whole `[churn]` window
+        #[arg(long)]
+        window_mins: Option<u32>,
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+    /// Origin ASNs per prefix, AS path length per peer, and new origins
+    Aspath {
+        /// Origins of this prefix only; by default prefixes with several origins
+        #[arg(long)]
+        prefix: Option<String>,
+        /// Report origins first seen since this Unix timestamp
+        #[arg(long)]
+        since: Option<i64>,
+        #[arg(long, default_value_t = 20)]
+        limit: usize,
+    },
+}
+
+#[derive(Debug, Args)]
+struct RibQuery {
+    peer: String,
+    /// Maximum prefixes per page.
+    #[arg(long)]
+    limit: Option<usize>,
+    /// Resume after this prefix (the `next_cursor` of a previous page).
+    #[arg(long)]
+    cursor: Option<String>,
+    /// Fetch every page in a single request, printing each as it arrives.
+    #[arg(long)]
+    stream: bool,
+}
+
+impl RibQuery {
+    fn to_args(&self) -> serde_json::Value {
+        json!({
+            "peer": self.peer,
+            "limit": self.limit,
+            "cursor": self.cursor,
+            "stream": self.stream,
+        })
+    }
+}
+
+#[derive(Debug, Subcommand)]
+enum BgpCommands {
+    Stats {
+        #[arg(long, default_value_t = 10)]
+        top: usize,
+    },
+    /// Busiest peers and most announced prefixes over the last few minutes
+    Top {
+        #[arg(long, default_value_t = 5)]
+        minutes: u32,
+        #[arg(long, default_value_t = 10)]
+        limit: usize,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum PrefixCommands {
+    /// Originate the routes in an MRT file (RIB dump or updates; path or URL)
+    LoadMrt {
+        path: String,
+        /// Announce only to this peer; repeat for several (default: every peer)
+        #[arg(long = "peer")]
+        peers: Vec<String>,
+    },
+    /// When each peer first and last announced the prefix, and last withdrew it
+    History {
+        prefix: String,
+        /// Include prefixes covered by PREFIX
+        #[arg(long)]
+        more_specifics: bool,
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum BlackholeCommands {
+    /// Announce the prefix with the BLACKHOLE community (65535:666)
+    Add {
+        prefix: String,
+        /// Withdraw automatically after this many seconds; 0 never expires
+        /// (default: `[blackhole].expiry_secs`)
+        #[arg(long)]
+        expires: Option<u64>,
+    },
+    Remove {
+        prefix: String,
+    },
+    List,
+}
+
+#[derive(Debug, Subcommand)]
+enum ShowCommands {
+    Bgp {
+        #[command(subcommand)]
+        command: ShowBgpCommands,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum ShowBgpCommands {
+    /// Like FRR `show bgp summary`
+    Summary,
+    /// Like FRR `show bgp neighbors [<ip>]`
+    Neighbors { peer: Option<String> },
+}
+
+#[derive(Debug, Subcommand)]
+enum ArchiveCommands {
+    Status,
+    Rollover {
+        #[arg(long, value_parser = ["updates", "ribs"])]
+        stream: String,
+    },
+    Snapshot {
+        /// TableDumpV2 view name; defaults to [archive].rib_view_name
+        #[arg(long)]
+        view_name: Option<String>,
+        /// Fill the snapshot from adj_rib_in or loc_rib
+        #[arg(long)]
+        rib_source: Option<String>,
+        /// Only these peers' Adj-RIB-In (repeatable)
+        #[arg(long = "peer")]
+        peers: Vec<String>,
+        /// Unix seconds to stamp the snapshot with; defaults to now
+        #[arg(long)]
+        timestamp: Option<i64>,
+    },
+    /// Export the peer index table (peers, ASNs, BGP IDs) without a RIB dump
+    PeerIndex {
+        /// TableDumpV2 view name; defaults to [archive].rib_view_name
+        #[arg(long)]
+        view_name: Option<String>,
+        /// json, or mrt for a one-record TABLE_DUMP_V2 file
+        #[arg(long, value_parser = ["json", "mrt"], default_value = "json")]
+        format: String,
+        /// Write here instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    Destinations,
+    Retry,
+    /// Compare finalized segments with a destination and report missing or
+    /// mismatched objects
+    Reconcile {
+        /// Destination key, as listed by `focl archive destinations`
+        #[arg(long)]
+        destination: String,
+        /// Queue replication jobs for missing or mismatched segments
+        #[arg(long)]
+        repair: bool,
+    },
+    /// Replicate a segment ahead of everything else in the queue
+    Prioritize {
+        /// Segment path, absolute or relative to the archive root
+        segment: String,
+    },
+    /// Download a finalized segment over the control socket
+    Fetch {
+        /// Segment path, absolute or relative to the archive root
+        segment: Option<String>,
+        /// Fetch the segment of this stream covering --timestamp instead
+        #[arg(long, value_parser = ["updates", "ribs"], requires = "timestamp", conflicts_with = "segment")]
+        stream: Option<String>,
+        /// Unix seconds within the wanted segment's interval
+        #[arg(long, requires = "stream")]
+        timestamp: Option<i64>,
+        /// raw (as stored), mrt (decompressed), or elems (JSONL)
+        #[arg(long, value_parser = ["raw", "mrt", "elems"], default_value = "raw")]
+        format: String,
+        /// Write here instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Print recommended bucket lifecycle rules for S3 destinations with
+    /// `[archive.destinations.lifecycle]`
+    Lifecycle {
+        #[arg(short, long, default_value = "focl.toml")]
+        config: PathBuf,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum EventsCommands {
+    History {
+        #[arg(long)]
+        since: Option<i64>,
+        #[arg(long)]
+        until: Option<i64>,
+        #[arg(long = "type")]
+        event_type: Option<String>,
+        #[arg(long)]
+        peer: Option<String>,
+        #[arg(long)]
+        limit: Option<usize>,
+    },
+}
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let cli = Cli::parse();
+    if let Some(instance) = &cli.instance {
+        focl::config::validate_instance_name(instance)?;
+    }
+    let socket = cli
+        .socket
+        .clone()
+        .unwrap_or_else(|| focl::config::instance_control_socket(cli.instance.as_deref()));
+
+    match cli.command {
+        Commands::Start { config, foreground } => {
+            let focld_bin = locate_focld_binary()?;
+            if foreground {
+                let code = run_foreground(focld_bin, config, cli.instance.as_deref()).await?;
+                std::process::exit(code);
+            }
+            let mut command = std::process::Command::new(focld_bin);
+            command.arg("--config").arg(config);
+            if let Some(instance) = &cli.instance {
+                command.arg("--instance").arg(instance);
+            }
+            let child = command
+                .stdin(Stdio::null())
+                .stdout(Stdio::null())
+                .stderr(Stdio::null())
+                .spawn()
+                .context("failed spawning focld")?;
+            println!("{{\"started\":true,\"pid\":{}}}", child.id());
+        }
+        Commands::Stop { wait, timeout } => {
+            let pid = if wait {
+                fetch_result::<serde_json::Value>(&socket, "daemon_status", json!({}))
+                    .await?
+                    .get("pid")
+                    .and_then(|p| p.as_i64())
+            } else {
+                None
+            };
+            let response = send_control_request(&socket, "shutdown", json!({})).await?;
+            let accepted = response.ok;
+            print_response(response);
+            if wait && accepted {
+                let timeout = std::time::Duration::from_secs(timeout);
+                if !wait_for_exit(&socket, pid, timeout).await {
+                    eprintln!(
+                        "focld did not exit within {}s (socket {})",
+                        timeout.as_secs(),
+                        socket.display()
+                    );
+                    std::process::exit(1);
+                }
+            } else if !accepted {
+                std::process::exit(1);
+            }
+        }
+        Commands::Reload => {
+            let response = send_control_request(&socket, "reload", json!({})).await?;
+            print_response(response);
+        }
+        Commands::Health => {
+            let response = send_control_request(&socket, "health", json!({})).await?;
+            print_response(response);
+        }
+        Commands::Batch { file, concurrent } => {
+            let requests = batch::read_requests(&file)?;
+            let response = send_control_request(
+                &socket,
+                "batch",
+                json!({"requests": requests, "concurrent": concurrent}),
+            )
+            .await?;
+            let failed = response
+                .result
+                .as_ref()
+                .and_then(|result| result["failed"].as_u64())
+                .unwrap_or(0);
+            let ok = response.ok;
+            print_response(response);
+            if !ok || failed > 0 {
+                std::process::exit(1);
+            }
+        }
+        Commands::Status { json } => {
+            if !status::run(&socket, json).await? {
+                std::process::exit(1);
+            }
+        }
+        Commands::Show {
+            command: ShowCommands::Bgp { command },
+        } => match command {
+            ShowBgpCommands::Summary => show::summary(&socket).await?,
+            ShowBgpCommands::Neighbors { peer } => show::neighbors(&socket, peer).await?,
+        },
+        Commands::Top { interval } => {
+            top::run(socket, std::time::Duration::from_secs(interval.max(1))).await?;
+        }
+        Commands::Peer { command } => match command {
+            PeerCommands::List => {
+                let response = send_control_request(&socket, "peer_list", json!({})).await?;
+                print_response(response);
+            }
+            PeerCommands::Show { peer, history } => {
+                let response = send_control_request(
+                    &socket,
+                    "peer_show",
+                    json!({"peer": peer, "history": history}),
+                )
+                .await?;
+                print_response(response);
+            }
+            PeerCommands::Timers { peer } => {
+                let response =
+                    send_control_request(&socket, "peer_timers", json!({"peer": peer})).await?;
+                print_response(response);
+            }
+            PeerCommands::StatsReset { peer } => {
+                let response =
+                    send_control_request(&socket, "peer_stats_reset", json!({"peer": peer}))
+                        .await?;
+                print_response(response);
+            }
+            PeerCommands::Reset {
+                peer,
+                soft_out,
+                soft_in,
+            } => {
+                let mode = if soft_out {
+                    "soft-out"
+                } else if soft_in {
+                    "soft-in"
+                } else {
+                    "hard"
+                };
+                let response = send_control_request(
+                    &socket,
+                    "peer_reset",
+                    json!({"peer": peer, "mode": mode}),
+                )
+                .await?;
+                print_response(response);
+            }
+            PeerCommands::Maintenance {
+                peer,
+                state,
+                keep_session,
+            } => {
+                let response = send_control_request(
+                    &socket,
+                    "peer_maintenance",
+                    json!({"peer": peer, "enabled": state == "on", "keep_session": keep_session}),
+                )
+                .await?;
+                print_response(response);
+            }
+            PeerCommands::LogMessages { peer, state } => {
+                let response = send_control_request(
+                    &socket,
+                    "peer_log_messages",
+                    json!({"peer": peer, "enabled": state == "on"}),
+                )
+                .await?;
+                print_response(response);
+            }
+            PeerCommands::Wait {
+                peer,
+                state,
+                timeout,
+            } => {
+                let response = send_control_request(
+                    &socket,
+                    "peer_wait",
+                    json!({"peer": peer, "state": state, "timeout_secs": timeout}),
+                )
+                .await?;
+                let ok = response.ok;
+                print_response(response);
+                if !ok {
+                    std::process::exit(1);
+                }
+            }
+        },
+        Commands::Rib { command } => match command {
+            RibCommands::Summary => {
+                let response = send_control_request(&socket, "rib_summary", json!({})).await?;
+                print_response(response);
+            }
+            RibCommands::In(query) => run_rib_query(&socket, "rib_in", &query).await?,
+            RibCommands::Out(query) => run_rib_query(&socket, "rib_out", &query).await?,
+            RibCommands::Churn { window_mins, limit } => {
+                let response = send_control_request(
+                    &socket,
+                    "rib_churn",
+                    json!({"window_mins": window_mins, "limit": limit}),
+                )
+                .await?;
+                print_response(response);
+            }
+            RibCommands::Aspath {
+                prefix,
+                since,
+                limit,
+            } => {
+                let response = send_control_request(
+                    &socket,
+                    "aspath_stats",
+                    json!({"prefix": prefix, "since": since, "limit": limit}),
+                )
+                .await?;
+                print_response(response);
+            }
+        },
+        Commands::Bgp { command } => match command {
+            BgpCommands::Stats { top } => {
+                let response =
+                    send_control_request(&socket, "bgp_stats", json!({"top": top})).await?;
+                print_response(response);
+            }
+            BgpCommands::Top { minutes, limit } => {
+                let response = send_control_request(
+                    &socket,
+                    "bgp_top",
+                    json!({"minutes": minutes, "limit": limit}),
+                )
+                .await?;
+                print_response(response);
+            }
+        },
+        Commands::Prefix { command } => match command {
+            PrefixCommands::LoadMrt { path, peers } => {
+                // focld resolves relative paths against its own working directory.
+                let path = match std::fs::canonicalize(&path) {
+                    Ok(absolute) => absolute.to_string_lossy().into_owned(),
+                    Err(_) => path,
+                };
+                let response = send_control_request(
+                    &socket,
+                    "prefix_load_mrt",
+                    json!({"path": path, "peers": peers}),
+                )
+                .await?;
+                print_response(response);
+            }
+            PrefixCommands::History {
+                prefix,
+                more_specifics,
+                limit,
+            } => {
+                let response = send_control_request(
+                    &socket,
+                    "prefix_history",
+                    json!({"prefix": prefix, "more_specifics": more_specifics, "limit": limit}),
+                )
+                .await?;
+                print_response(response);
+            }
+        },
+        Commands::Blackhole { command } => {
+            let response = match command {
+                BlackholeCommands::Add { prefix, expires } => {
+                    send_control_request(
+                        &socket,
+                        "blackhole_add",
+                        json!({"prefix": prefix, "expires_secs": expires}),
+                    )
+                    .await?
+                }
+                BlackholeCommands::Remove { prefix } => {
+                    send_control_request(&socket, "blackhole_remove", json!({"prefix": prefix}))
+                        .await?
+                }
+                BlackholeCommands::List => {
+                    send_control_request(&socket, "blackhole_list", json!({})).await?
+                }
+            };
+            print_response(response);
+        }
+        Commands::Archive { command } => match command {
+            ArchiveCommands::Status => {
+                let response = send_control_request(&socket, "archive_status", json!({})).await?;
+                print_response(response);
+            }
+            ArchiveCommands::Rollover { stream } => {
+                let response =
+                    send_control_request(&socket, "archive_rollover", json!({"stream": stream}))
+                        .await?;
+                print_response(response);
+            }
+            ArchiveCommands::Snapshot {
+                view_name,
+                rib_source,
+                peers,
+                timestamp,
+            } => {
+                let response = send_control_request(
+                    &socket,
+                    "archive_snapshot_now",
+                    json!({
+                        "view_name": view_name,
+                        "rib_source": rib_source,
+                        "peers": (!peers.is_empty()).then_some(peers),
+                        "timestamp": timestamp,
+                    }),
+                )
+                .await?;
+                print_response(response);
+            }
+            ArchiveCommands::PeerIndex {
+                view_name,
+                format,
+                output,
+            } => {
+                let result = fetch_result::<serde_json::Value>(
+                    &socket,
+                    "archive_peer_index",
+                    json!({"view_name": view_name, "format": format}),
+                )
+                .await?;
+                let bytes = match result.get("data").and_then(|d| d.as_str()) {
+                    Some(data) => {
+                        use base64::Engine;
+                        base64::engine::general_purpose::STANDARD
+                            .decode(data)
+                            .context("archive_peer_index data is not base64")?
+                    }
+                    None => format!("{}\n", serde_json::to_string_pretty(&result)?).into_bytes(),
+                };
+                match output {
+                    Some(path) => std::fs::write(&path, bytes)
+                        .with_context(|| format!("failed writing {}", path.display()))?,
+                    None => std::io::Write::write_all(&mut std::io::stdout(), &bytes)?,
+                }
+            }
+            ArchiveCommands::Destinations => {
+                let response =
+                    send_control_request(&socket, "archive_destinations", json!({})).await?;
+                print_response(response);
+            }
+            ArchiveCommands::Retry => {
+                let response =
+                    send_control_request(&socket, "archive_replicator_retry", json!({})).await?;
+                print_response(response);
+            }
+            ArchiveCommands::Reconcile {
+                destination,
+                repair,
+            } => {
+                let response = send_control_request(
+                    &socket,
+                    "archive_reconcile",
+                    json!({"destination": destination, "repair": repair}),
+                )
+                .await?;
+                print_response(response);
+            }
+            ArchiveCommands::Prioritize { segment } => {
+                let response = send_control_request(
+                    &socket,
+                    "archive_prioritize",
+                    json!({"segment": segment}),
+                )
+                .await?;
+                print_response(response);
+            }
+            ArchiveCommands::Fetch {
+                segment,
+                stream,
+                timestamp,
+                format,
+                output,
+            } => {
+                let args = json!({
+                    "segment": segment,
+                    "stream": stream,
+                    "timestamp": timestamp,
+                    "format": format,
+                });
+                run_archive_fetch(&socket, args, output.as_deref()).await?;
+            }
+            ArchiveCommands::Lifecycle { config } => {
+                let mut cfg = focl::config::FoclConfig::load(&config)?;
+                if let Some(instance) = &cli.instance {
+                    cfg.apply_instance(instance)?;
+                }
+                let rules = cfg
+                    .archive
+                    .destinations
+                    .iter()
+                    .filter_map(|destination| {
+                        let rules = focl::archive::lifecycle::recommended_rules(destination)?;
+                        Some(json!({
+                            "destination": destination.destination_key(),
+                            "lifecycle_configuration": rules,
+                        }))
+                    })
+                    .collect::<Vec<_>>();
+                println!("{}", serde_json::to_string_pretty(&rules)?);
+            }
+        },
+        Commands::Check { config, preflight } => {
+            let mut cfg = focl::config::FoclConfig::load(&config)?;
+            if let Some(instance) = &cli.instance {
+                cfg.apply_instance(instance)?;
+            }
+            for warning in &cfg.migration_warnings {
+                eprintln!("warning: {warning}");
+            }
+            println!("config {} ok", config.display());
+            if preflight {
+                let report = focl::preflight::run(&cfg).await;
+                print!("{report}");
+                if !report.passed() {
+                    std::process::exit(1);
+                }
+            }
+        }
+        Commands::Mrt { command } => match command {
+            MrtCommands::Inspect { file, top, json } => {
+                let summary = mrt::inspect(&file, top)?;
+                if json {
+                    println!("{}", serde_json::to_string_pretty(&summary)?);
+                } else {
+                    mrt::print(&summary);
+                }
+            }
+        },
+        Commands::Config { command } => match command {
+            ConfigCommands::Schema => {
+                println!(
+                    "{}",
+                    serde_json::to_string_pretty(&focl::config::FoclConfig::json_schema())?
+                );
+            }
+        },
+        Commands::Events { command } => match command {
+            EventsCommands::History {
+                since,
+                until,
+                event_type,
+                peer,
+                limit,
+            } => {
+                let response = send_control_request(
+                    &socket,
+                    "events_history",
+                    json!({
+                        "since": since,
+                        "until": until,
+                        "type": event_type,
+                        "peer": peer,
+                        "limit": limit,
+                    }),
+                )
+                .await?;
+                print_response(response);
+            }
+        },
+    }
+
+    Ok(())
+}
+
+fn locate_focld_binary() -> Result<PathBuf> {
+    let current = std::env::current_exe().context("failed resolving current executable")?;
+    let sibling = current.with_file_name("focld");
+    if sibling.exists() {
+        return Ok(sibling);
+    }
+    Ok(PathBuf::from("focld"))
+}
+
+async fn run_foreground(
+    focld_bin: PathBuf,
+    config: PathBuf,
+    instance: Option<&str>,
+) -> Result<i32> {
+    let mut command = tokio::process::Command::new(focld_bin);
+    command.arg("--config").arg(config);
+    if let Some(instance) = instance {
+        command.arg("--instance").arg(instance);
+    }
+    let mut child = command
+        .stdin(Stdio::null())
+        .spawn()
+        .context("failed spawning focld")?;
+
+    let mut sigterm = tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate())
+        .context("failed installing SIGTERM handler")?;
+
+    loop {
+        // focld shuts down gracefully on SIGINT, so both signals are forwarded as SIGINT.
+        tokio::select! {
+            status = child.wait() => {
+                let status = status.context("failed waiting for focld")?;
+                return Ok(status.code().unwrap_or(1));
+            }
+            _ = tokio::signal::ctrl_c() => forward_sigint(&child),
+            _ = sigterm.recv() => forward_sigint(&child),
+        }
+    }
+}
+
+fn forward_sigint(child: &tokio::process::Child) {
+    if let Some(pid) = child.id() {
+        unsafe {
+            libc::kill(pid as libc::pid_t, libc::SIGINT);
+        }
+    }
+}
+
+/// Poll until the control socket is gone and, if known, the daemon pid has exited.
+async fn wait_for_exit(socket: &Path, pid: Option<i64>, timeout: std::time::Duration) -> bool {
+    let deadline = tokio::time::Instant::now() + timeout;
+    loop {
+        let pid_alive = pid.is_some_and(|pid| unsafe { libc::kill(pid as libc::pid_t, 0) } == 0);
+        if !pid_alive && !socket.exists() {
+            return true;
+        }
+        if tokio::time::Instant::now() >= deadline {
+            return false;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    }
+}
+
+async fn run_rib_query(socket: &PathBuf, cmd: &str, query: &RibQuery) -> Result<()> {
+    if !query.stream {
+        let response = send_control_request(socket, cmd, query.to_args()).await?;
+        print_response(response);
+        return Ok(());
+    }
+
+    let mut reader = open_control_request(socket, cmd, query.to_args()).await?;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            anyhow::bail!("control socket closed before the {cmd} stream completed");
+        }
+        let response: ControlResponse = serde_json::from_str(line.trim_end())?;
+        let done = !response.ok
+            || response
+                .result
+                .as_ref()
+                .and_then(|r| r.get("done"))
+                .and_then(|d| d.as_bool())
+                .unwrap_or(true);
+        print_response(response);
+        if done {
+            return Ok(());
+        }
+    }
+}
+
+/// Write the chunks of an `archive_fetch` stream to `output` or stdout.
+async fn run_archive_fetch(
+    socket: &PathBuf,
+    args: serde_json::Value,
+    output: Option<&Path>,
+) -> Result<()> {
+    use base64::Engine;
+
+    let mut out: Box<dyn tokio::io::AsyncWrite + Unpin> = match output {
+        Some(path) => Box::new(
+            tokio::fs::File::create(path)
+                .await
+                .with_context(|| format!("failed creating {}", path.display()))?,
+        ),
+        None => Box::new(tokio::io::stdout()),
+    };
+    let mut reader = open_control_request(socket, "archive_fetch", args).await?;
+    let mut expected_seq = 0;
+    loop {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            anyhow::bail!("control socket closed before the archive_fetch stream completed");
+        }
+        let response: ControlResponse = serde_json::from_str(line.trim_end())?;
+        if !response.ok {
+            let msg = response
+                .error
+                .map(|e| format!("{}: {}", e.code, e.message))
+                .unwrap_or_else(|| "unknown error".to_string());
+            anyhow::bail!("archive_fetch failed: {msg}");
+        }
+        let result = response.result.unwrap_or_default();
+        if result.get("done").and_then(|d| d.as_bool()).unwrap_or(true) {
+            out.flush().await?;
+            if output.is_some() {
+                eprintln!("{}", serde_json::to_string(&result)?);
+            }
+            return Ok(());
+        }
+        let seq = result.get("seq").and_then(|s| s.as_u64());
+        if seq != Some(expected_seq) {
+            anyhow::bail!("archive_fetch chunk {seq:?} out of order, expected {expected_seq}");
+        }
+        expected_seq += 1;
+        let data = result
+            .get("data")
+            .and_then(|d| d.as_str())
+            .context("archive_fetch chunk without data")?;
+        let chunk = base64::engine::general_purpose::STANDARD
+            .decode(data)
+            .context("archive_fetch chunk is not base64")?;
+        out.write_all(&chunk).await?;
+    }
+}
+
+async fn open_control_request(
+    socket: &PathBuf,
+    cmd: &str,
+    args: serde_json::Value,
+) -> Result<BufReader<UnixStream>> {
+    let mut stream = UnixStream::connect(socket)
+        .await
+        .with_context(|| format!("failed connecting to {}", socket.display()))?;
+
+    let req = ControlRequest {
+        version: 1,
+        id: uuid_like_id(),
+        cmd: cmd.to_string(),
+        args,
+    };
+
+    let payload = serde_json::to_string(&req)?;
+    stream.write_all(payload.as_bytes()).await?;
+    stream.write_all(b"\n").await?;
+
+    Ok(BufReader::new(stream))
+}
+
+async fn send_control_request(
+    socket: &PathBuf,
+    cmd: &str,
+    args: serde_json::Value,
+) -> Result<ControlResponse> {
+    let mut reader = open_control_request(socket, cmd, args).await?;
+    let mut line = String::new();
+    reader.read_line(&mut line).await?;
+
+    let response: ControlResponse = serde_json::from_str(line.trim_end())?;
+    Ok(response)
+}
+
+#[derive(serde::Deserialize)]
+struct PeerList {
+    peers: Vec<focl::bgp::PeerInfo>,
+}
+
+/// Send a request and decode its `result`, turning control errors into `Err`.
+async fn fetch_result<T: serde::de::DeserializeOwned>(
+    socket: &PathBuf,
+    cmd: &str,
+    args: serde_json::Value,
+) -> Result<T> {
+    let response = send_control_request(socket, cmd, args).await?;
+    decode_result(cmd, response)
+}
+
+/// The `result` of a `cmd` response, decoded, or its control error.
+fn decode_result<T: serde::de::DeserializeOwned>(
+    cmd: &str,
+    response: ControlResponse,
+) -> Result<T> {
+    if !response.ok {
+        let msg = response
+            .error
+            .map(|e| format!("{}: {}", e.code, e.message))
+            .unwrap_or_else(|| "unknown error".to_string());
+        anyhow::bail!("{cmd} failed: {msg}");
+    }
+    let result = response
+        .result
+        .ok_or_else(|| anyhow::anyhow!("{cmd} returned no result"))?;
+    Ok(serde_json::from_value(result)?)
+}
+
+fn uuid_like_id() -> String {
+    format!(
+        "req-{}-{}",
+        std::process::id(),
+        chrono::Utc::now().timestamp_nanos_opt().unwrap_or_default()
+    )
+}
+
+fn print_response(response: ControlResponse) {
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&response).unwrap_or_else(|_| "{}".to_string())
+    );
+}
@@ -0,0 +1,229 @@
+//! `focl mrt inspect`: summarize an MRT file client-side, for spelunking the
+//! archive without the daemon.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::File;
+use std::io::Read;
+use std::net::IpAddr;
+use std::path::Path;
+
+use anyhow::{anyhow, Context, Result};
+use bgpkit_parser::models::{ElemType, MrtMessage};
+use bgpkit_parser::{BgpkitParser, Elementor};
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct MrtSummary {
+    pub path: String,
+    pub records: u64,
+    /// Keyed `ENTRY_TYPE/subtype`, e.g. `BGP4MP/MessageAs4`.
+    pub record_types: BTreeMap<String, u64>,
+    pub first_ts: Option<u32>,
+    pub last_ts: Option<u32>,
+    pub announcements: u64,
+    pub withdrawals: u64,
+    pub peers: Vec<PeerSummary>,
+    pub top_prefixes: Vec<PrefixCount>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PeerSummary {
+    pub address: IpAddr,
+    pub asn: u32,
+    pub announcements: u64,
+    pub withdrawals: u64,
+}
+
+#[derive(Debug, Serialize)]
+pub struct PrefixCount {
+    pub prefix: String,
+    pub elems: u64,
+}
+
+/// Read every record of `path` (plain, gzip, bzip2, or zstd by extension).
+pub fn inspect(path: &Path, top: usize) -> Result<MrtSummary> {
+    let display = path.display().to_string();
+    let parser: BgpkitParser<Box<dyn Read + Send>> =
+        if path.extension().is_some_and(|ext| ext == "zst") {
+            let file = File::open(path).with_context(|| format!("failed opening {display}"))?;
+            BgpkitParser::from_reader(Box::new(zstd::Decoder::new(file)?))
+        } else {
+            BgpkitParser::new(&display)
+                .map_err(|err| anyhow!("failed opening MRT file {display}: {err}"))?
+        };
+
+    let mut summary = MrtSummary {
+        path: display,
+        records: 0,
+        record_types: BTreeMap::new(),
+        first_ts: None,
+        last_ts: None,
+        announcements: 0,
+        withdrawals: 0,
+        peers: Vec::new(),
+        top_prefixes: Vec::new(),
+    };
+    let mut peers: BTreeMap<(IpAddr, u32), (u64, u64)> = BTreeMap::new();
+    let mut prefixes: HashMap<String, u64> = HashMap::new();
+    let mut elementor = Elementor::new();
+
+    for record in parser.into_record_iter() {
+        let header = &record.common_header;
+        summary.records += 1;
+        summary.first_ts = Some(
+            summary
+                .first_ts
+                .map_or(header.timestamp, |ts| ts.min(header.timestamp)),
+        );
+        summary.last_ts = Some(
+            summary
+                .last_ts
+                .map_or(header.timestamp, |ts| ts.max(header.timestamp)),
+        );
+        let kind = match &record.message {
+            MrtMessage::TableDumpMessage(_) => format!("{:?}", header.entry_type),
+            MrtMessage::TableDumpV2Message(message) => {
+                format!("{:?}/{:?}", header.entry_type, message.dump_type())
+            }
+            MrtMessage::Bgp4Mp(message) => {
+                format!("{:?}/{:?}", header.entry_type, message.msg_type())
+            }
+        };
+        *summary.record_types.entry(kind).or_default() += 1;
+
+        for elem in elementor.record_to_elems(record) {
+            let counts = peers
+                .entry((elem.peer_ip, elem.peer_asn.to_u32()))
+                .or_default();
+            if elem.elem_type == ElemType::WITHDRAW {
+                summary.withdrawals += 1;
+                counts.1 += 1;
+            } else {
+                summary.announcements += 1;
+                counts.0 += 1;
+            }
+            *prefixes.entry(elem.prefix.prefix.to_string()).or_default() += 1;
+        }
+    }
+
+    summary.peers = peers
+        .into_iter()
+        .map(
+            |((address, asn), (announcements, withdrawals))| PeerSummary {
+                address,
+                asn,
+                announcements,
+                withdrawals,
+            },
+        )
+        .collect();
+    let mut prefixes = prefixes.into_iter().collect::<Vec<_>>();
+    prefixes.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+    summary.top_prefixes = prefixes
+        .into_iter()
+        .take(top)
+        .map(|(prefix, elems)| PrefixCount { prefix, elems })
+        .collect();
+    Ok(summary)
+}
+
+pub fn print(summary: &MrtSummary) {
+    println!("{}", summary.path);
+    println!("  records: {}", summary.records);
+    for (kind, count) in &summary.record_types {
+        println!("    {kind:<32} {count}");
+    }
+    if let (Some(first), Some(last)) = (summary.first_ts, summary.last_ts) {
+        let time = |ts: u32| {
+            chrono::DateTime::from_timestamp(i64::from(ts), 0)
+                .map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+                .unwrap_or_else(|| ts.to_string())
+        };
+        println!("  time range: {} - {} UTC", time(first), time(last));
+    }
+    println!(
+        "  elems: {} announcements, {} withdrawals",
+        summary.announcements, summary.withdrawals
+    );
+    println!("  peers: {}", summary.peers.len());
+    for peer in &summary.peers {
+        println!(
+            "    {:<39} AS{:<10} {} announced, {} withdrawn",
+            peer.address, peer.asn, peer.announcements, peer.withdrawals
+        );
+    }
+    if !summary.top_prefixes.is_empty() {
+        println!("  top prefixes:");
+        for prefix in &summary.top_prefixes {
+            println!("    {:<43} {}", prefix.prefix, prefix.elems);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use focl::archive::snapshot::write_bgp4mp_message_as4;
+    use focl::archive::types::UpdateRecordInput;
+    use std::io::Write;
+    use std::net::Ipv4Addr;
+
+    fn update(nlri: &[u8]) -> Vec<u8> {
+        let attributes = [
+            0x40, 1, 1, 0, // ORIGIN IGP
+            0x40, 2, 6, 2, 1, 0, 0, 0xfd, 0xea, // AS_PATH 65002
+            0x40, 3, 4, 198, 51, 100, 1, // NEXT_HOP
+        ];
+        let mut msg = vec![0xff; 16];
+        let len = 16 + 2 + 1 + 2 + 2 + attributes.len() + nlri.len();
+        msg.extend_from_slice(&(len as u16).to_be_bytes());
+        msg.push(2);
+        msg.extend_from_slice(&0u16.to_be_bytes());
+        msg.extend_from_slice(&(attributes.len() as u16).to_be_bytes());
+        msg.extend_from_slice(&attributes);
+        msg.extend_from_slice(nlri);
+        msg
+    }
+
+    #[test]
+    fn summarizes_updates_by_type_peer_and_prefix() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("updates.20260221.1200.zst");
+        let mut file = zstd::Encoder::new(File::create(&path).unwrap(), 0).unwrap();
+        for (timestamp, nlri) in [
+            (1_700_000_000, &[24, 203, 0, 113][..]),
+            (1_700_000_060, &[24, 203, 0, 113, 24, 198, 51, 100][..]),
+        ] {
+            let mut record = Vec::new();
+            write_bgp4mp_message_as4(
+                &UpdateRecordInput {
+                    timestamp,
+                    peer_asn: 65002,
+                    local_asn: 65001,
+                    interface_index: 0,
+                    peer_ip: Ipv4Addr::new(198, 51, 100, 1).into(),
+                    local_ip: Ipv4Addr::new(198, 51, 100, 2).into(),
+                    bgp_message: update(nlri),
+                    family: Default::default(),
+                    collector_id: None,
+                },
+                &mut record,
+            )
+            .unwrap();
+            file.write_all(&record).unwrap();
+        }
+        file.finish().unwrap();
+
+        let summary = inspect(&path, 1).unwrap();
+        assert_eq!(summary.records, 2);
+        assert_eq!(summary.record_types["BGP4MP/MessageAs4"], 2);
+        assert_eq!(summary.first_ts, Some(1_700_000_000));
+        assert_eq!(summary.last_ts, Some(1_700_000_060));
+        assert_eq!(summary.announcements, 3);
+        assert_eq!(summary.peers.len(), 1);
+        assert_eq!(summary.peers[0].asn, 65002);
+        assert_eq!(summary.top_prefixes.len(), 1);
+        assert_eq!(summary.top_prefixes[0].prefix, "203.0.113.0/24");
+        assert_eq!(summary.top_prefixes[0].elems, 2);
+    }
+}
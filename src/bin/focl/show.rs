@@ -0,0 +1,319 @@
+//! `focl show bgp ...`: FRR-style text renderings for runbooks and humans.
+
+use std::net::IpAddr;
+use std::path::PathBuf;
+
+use anyhow::Result;
+use focl::bgp::{AfiSafi, PeerInfo};
+use focl::types::PeerState;
+use serde::Deserialize;
+use serde_json::json;
+
+use crate::{fetch_result, PeerList};
+
+#[derive(Debug, Deserialize)]
+struct PeerShown {
+    peer: PeerInfo,
+}
+
+#[derive(Debug, Deserialize)]
+struct Identity {
+    asn: u32,
+    router_id: String,
+}
+
+pub async fn summary(socket: &PathBuf) -> Result<()> {
+    let identity = fetch_result::<Identity>(socket, "daemon_status", json!({})).await?;
+    let mut peers = fetch_result::<PeerList>(socket, "peer_list", json!({}))
+        .await?
+        .peers;
+    peers.sort_by_key(|p| p.address.parse::<IpAddr>().ok());
+    let now = chrono::Utc::now().timestamp();
+    print!(
+        "{}",
+        render_summary(identity.asn, &identity.router_id, &peers, now)
+    );
+    Ok(())
+}
+
+pub async fn neighbors(socket: &PathBuf, peer: Option<String>) -> Result<()> {
+    let identity = fetch_result::<Identity>(socket, "daemon_status", json!({})).await?;
+    let peers = match peer {
+        Some(peer) => {
+            let shown =
+                fetch_result::<PeerShown>(socket, "peer_show", json!({"peer": peer})).await?;
+            vec![shown.peer]
+        }
+        None => {
+            let mut peers = fetch_result::<PeerList>(socket, "peer_list", json!({}))
+                .await?
+                .peers;
+            peers.sort_by_key(|p| p.address.parse::<IpAddr>().ok());
+            peers
+        }
+    };
+    let now = chrono::Utc::now().timestamp();
+    let blocks = peers
+        .iter()
+        .map(|p| render_neighbor(p, &identity.router_id, now))
+        .collect::<Vec<_>>();
+    print!("{}", blocks.join("\n"));
+    Ok(())
+}
+
+/// One `show bgp summary` section per address family, as FRR prints it.
+fn render_summary(asn: u32, router_id: &str, peers: &[PeerInfo], now: i64) -> String {
+    let mut out = String::new();
+    for (family, v6) in [("IPv4", false), ("IPv6", true)] {
+        let members = peers
+            .iter()
+            .filter(|p| p.address.parse::<IpAddr>().is_ok_and(|a| a.is_ipv6() == v6))
+            .collect::<Vec<_>>();
+        if members.is_empty() {
+            continue;
+        }
+        if !out.is_empty() {
+            out.push('\n');
+        }
+        out.push_str(&format!("{family} Unicast Summary:\n"));
+        out.push_str(&format!(
+            "BGP router identifier {router_id}, local AS number {asn} vrf-id 0\n"
+        ));
+        out.push_str(&format!("Peers {}\n\n", members.len()));
+        out.push_str(
+            "Neighbor        V         AS   MsgRcvd   MsgSent   TblVer  InQ OutQ  Up/Down State/PfxRcd   PfxSnt Desc\n",
+        );
+        for peer in &members {
+            let state_or_pfx = if matches!(peer.state, PeerState::Established) {
+                accepted(peer).to_string()
+            } else {
+                state_name(peer.state).to_string()
+            };
+            out.push_str(&format!(
+                "{:<15} 4 {:>10} {:>9} {:>9} {:>8} {:>4} {:>4} {:>8} {:>12} {:>8} {}\n",
+                peer.address,
+                peer.remote_as,
+                peer.updates_received,
+                0,
+                0,
+                0,
+                0,
+                up_down(peer, now),
+                state_or_pfx,
+                peer.advertised_prefixes,
+                peer.name.as_deref().unwrap_or("N/A"),
+            ));
+        }
+        out.push_str(&format!("\nTotal number of neighbors {}\n", members.len()));
+    }
+    if out.is_empty() {
+        out.push_str("% No BGP neighbors found\n");
+    }
+    out
+}
+
+/// A `show bgp neighbors <ip>` block.
+fn render_neighbor(peer: &PeerInfo, router_id: &str, now: i64) -> String {
+    let link = if peer.remote_as == peer.local_as {
+        "internal"
+    } else {
+        "external"
+    };
+    let mut out = format!(
+        "BGP neighbor is {}, remote AS {}, local AS {}, {link} link\n",
+        peer.address, peer.remote_as, peer.local_as
+    );
+    if let Some(name) = &peer.name {
+        out.push_str(&format!(" Description: {name}\n"));
+    }
+    match peer.remote_id {
+        Some(remote_id) => out.push_str(&format!(
+            "  BGP version 4, remote router ID {remote_id}, local router ID {router_id}\n"
+        )),
+        None => out.push_str(&format!("  BGP version 4, local router ID {router_id}\n")),
+    }
+    match (peer.state, peer.established_at) {
+        (PeerState::Established, Some(ts)) => out.push_str(&format!(
+            "  BGP state = Established, up for {}\n",
+            frr_uptime(now.saturating_sub(ts))
+        )),
+        (state, _) => out.push_str(&format!("  BGP state = {}\n", state_name(state))),
+    }
+    if let Some(caps) = &peer.capabilities {
+        out.push_str("  Neighbor capabilities:\n");
+        if caps.four_octet_as {
+            out.push_str("    4 Byte AS: advertised and received\n");
+        }
+        if caps.route_refresh {
+            out.push_str("    Route refresh: advertised and received(new)\n");
+        }
+        for family in &caps.address_families {
+            let name = match family {
+                AfiSafi::Ipv4Unicast => "IPv4 Unicast",
+                AfiSafi::Ipv6Unicast => "IPv6 Unicast",
+            };
+            out.push_str(&format!(
+                "    Address Family {name}: advertised and received\n"
+            ));
+        }
+    }
+    out.push_str("  Message statistics:\n");
+    out.push_str("                         Sent       Rcvd\n");
+    out.push_str(&format!(
+        "    Updates:       {:>10} {:>10}\n",
+        "-", peer.updates_received
+    ));
+    out.push_str(&format!(
+        "    Withdrawals:   {:>10} {:>10}\n",
+        "-", peer.withdrawals_received
+    ));
+    let family = match peer.address.parse::<IpAddr>() {
+        Ok(IpAddr::V6(_)) => "IPv6",
+        _ => "IPv4",
+    };
+    out.push_str(&format!("\n For address family: {family} Unicast\n"));
+    out.push_str(&format!("  {} accepted prefixes\n", accepted(peer)));
+    if peer.prefixes_rejected > 0 {
+        out.push_str(&format!(
+            "  {} prefixes denied by import prefix list\n",
+            peer.prefixes_rejected
+        ));
+    }
+    out.push_str(&format!(
+        "  {} announced prefixes\n\n",
+        peer.advertised_prefixes
+    ));
+    out.push_str(&format!("  Connections dropped {}\n", peer.session_flaps));
+    if let Some(err) = &peer.last_error {
+        out.push_str(&format!("  Last reset due to {err}\n"));
+    }
+    if peer.auth_enabled {
+        out.push_str("  Peer Authentication Enabled\n");
+    }
+    out.push_str(&format!(
+        "Foreign host: {}, Foreign port: {}\n",
+        peer.address, peer.remote_port
+    ));
+    out
+}
+
+fn accepted(peer: &PeerInfo) -> u64 {
+    peer.prefixes_received
+        .saturating_sub(peer.prefixes_rejected)
+}
+
+fn state_name(state: PeerState) -> &'static str {
+    match state {
+        PeerState::Idle => "Idle",
+        PeerState::Connect => "Connect",
+        PeerState::Active => "Active",
+        PeerState::OpenSent => "OpenSent",
+        PeerState::OpenConfirm => "OpenConfirm",
+        PeerState::Established => "Established",
+    }
+}
+
+fn up_down(peer: &PeerInfo, now: i64) -> String {
+    match peer.established_at {
+        Some(ts) => frr_uptime(now.saturating_sub(ts)),
+        None => "never".to_string(),
+    }
+}
+
+/// FRR's peer uptime format: `hh:mm:ss` under a day, `XdYYhZZm` under a
+/// week, `XwYdZZh` beyond.
+fn frr_uptime(secs: i64) -> String {
+    let secs = secs.max(0);
+    let (weeks, days) = (secs / 604_800, (secs / 86_400) % 7);
+    let (hours, mins, secs) = ((secs / 3600) % 24, (secs / 60) % 60, secs % 60);
+    if weeks > 0 {
+        format!("{weeks}w{days}d{hours:02}h")
+    } else if days > 0 {
+        format!("{days}d{hours:02}h{mins:02}m")
+    } else {
+        format!("{hours:02}:{mins:02}:{secs:02}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use focl::bgp::NegotiatedCapabilities;
+
+    fn peer(address: &str, state: PeerState) -> PeerInfo {
+        PeerInfo {
+            address: address.to_string(),
+            name: Some("upstream".to_string()),
+            remote_as: 65002,
+            local_as: 65001,
+            remote_port: 179,
+            passive: false,
+            auth_enabled: false,
+            state,
+            last_error: None,
+            advertised_prefixes: 2,
+            established_at: Some(1_700_000_000 - 312),
+            updates_received: 12,
+            withdrawals_received: 1,
+            prefixes_received: 5,
+            prefixes_rejected: 1,
+            session_flaps: 0,
+            archive_records_shed: 0,
+            archive_duplicates_suppressed: 0,
+            maintenance: None,
+            log_messages: false,
+            capabilities: None,
+            remote_id: None,
+        }
+    }
+
+    #[test]
+    fn renders_frr_summary_and_neighbor() {
+        let mut established = peer("192.0.2.2", PeerState::Established);
+        established.capabilities = Some(NegotiatedCapabilities {
+            address_families: vec![AfiSafi::Ipv4Unicast],
+            four_octet_as: true,
+            route_refresh: false,
+            peer_capabilities: vec![1, 65],
+        });
+        let peers = [established, peer("2001:db8::2", PeerState::Active)];
+        let summary = render_summary(65001, "192.0.2.1", &peers, 1_700_000_000);
+        assert!(summary.starts_with("IPv4 Unicast Summary:\n"));
+        assert!(summary.contains("BGP router identifier 192.0.2.1, local AS number 65001"));
+        let row = summary
+            .lines()
+            .find(|l| l.starts_with("192.0.2.2"))
+            .unwrap();
+        assert_eq!(
+            row.split_whitespace().collect::<Vec<_>>(),
+            [
+                "192.0.2.2",
+                "4",
+                "65002",
+                "12",
+                "0",
+                "0",
+                "0",
+                "0",
+                "00:05:12",
+                "4",
+                "2",
+                "upstream"
+            ]
+        );
+        assert!(summary.contains("IPv6 Unicast Summary:"));
+        assert!(summary.contains(" Active "));
+
+        let neighbor = render_neighbor(&peers[0], "192.0.2.1", 1_700_000_000);
+        assert!(neighbor.starts_with(
+            "BGP neighbor is 192.0.2.2, remote AS 65002, local AS 65001, external link\n"
+        ));
+        assert!(neighbor.contains("BGP state = Established, up for 00:05:12"));
+        assert!(neighbor.contains(
+            "  Neighbor capabilities:\n    4 Byte AS: advertised and received\n    Address Family IPv4 Unicast: advertised and received\n"
+        ));
+
+        assert_eq!(frr_uptime(90_061), "1d01h01m");
+        assert_eq!(frr_uptime(1_300_000), "2w1d01h");
+    }
+}
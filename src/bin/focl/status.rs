@@ -0,0 +1,132 @@
+//! `focl status`: one consolidated summary of daemon, peers, and archive.
+
+use std::path::PathBuf;
+
+use anyhow::Result;
+use focl::control::ArchiveStatusResult;
+use focl::types::PeerState;
+use serde_json::{json, Value};
+
+use crate::top::format_duration;
+use crate::{fetch_result, PeerList};
+
+/// Prints the summary and returns whether everything looks healthy.
+pub async fn run(socket: &PathBuf, as_json: bool) -> Result<bool> {
+    let daemon = fetch_result::<Value>(socket, "daemon_status", json!({})).await?;
+    let peers = fetch_result::<PeerList>(socket, "peer_list", json!({}))
+        .await?
+        .peers;
+    let archive = fetch_result::<ArchiveStatusResult>(socket, "archive_status", json!({})).await?;
+
+    let established = peers
+        .iter()
+        .filter(|p| matches!(p.state, PeerState::Established))
+        .count();
+    let healthy = established == peers.len()
+        && archive.replication_failures == 0
+        && archive.updates.consecutive_write_failures == 0
+        && archive.ribs.consecutive_write_failures == 0;
+
+    if as_json {
+        let summary = json!({
+            "healthy": healthy,
+            "daemon": daemon,
+            "peers": peers,
+            "archive": archive,
+        });
+        println!("{}", serde_json::to_string_pretty(&summary)?);
+        return Ok(healthy);
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    println!(
+        "focld:   {} ({})",
+        if healthy { "ok" } else { "degraded" },
+        socket.display()
+    );
+
+    println!("peers:   {established}/{} established", peers.len());
+    for peer in &peers {
+        let uptime = peer
+            .established_at
+            .map(|ts| format!("up {}", format_duration(now.saturating_sub(ts))))
+            .unwrap_or_else(|| "down".to_string());
+        let name = peer
+            .name
+            .as_deref()
+            .map(|n| format!(" ({n})"))
+            .unwrap_or_default();
+        print!(
+            "  {}{name} AS{} {:?} {uptime}",
+            peer.address, peer.remote_as, peer.state
+        );
+        match &peer.last_error {
+            Some(err) if !matches!(peer.state, PeerState::Established) => {
+                println!(" last error: {err}")
+            }
+            _ => println!(),
+        }
+    }
+
+    if archive.enabled {
+        println!("archive: enabled, collector {}", archive.collector_id);
+        println!(
+            "  updates: {} ({} records)",
+            archive.updates.open_path.as_deref().unwrap_or("-"),
+            archive.updates.open_record_count
+        );
+        println!(
+            "  ribs:    {} ({} records)",
+            archive.ribs.last_finalized_path.as_deref().unwrap_or("-"),
+            archive.ribs.last_finalized_record_count
+        );
+        for (name, stream) in [("updates", &archive.updates), ("ribs", &archive.ribs)] {
+            if let Some(err) = &stream.last_error {
+                println!("  last {name} error: {err}");
+            }
+            if stream.consecutive_write_failures > 0 {
+                println!(
+                    "  {name} writer failing: {} in a row, retry in {}s, {} quarantined",
+                    stream.consecutive_write_failures,
+                    stream.retry_in_secs.unwrap_or(0),
+                    stream.quarantined_segments
+                );
+            }
+        }
+        println!(
+            "  replication: {} queued, {} failures, {} already at destination",
+            archive.queued_replication_jobs,
+            archive.replication_failures,
+            archive.replication_deduplicated
+        );
+        println!(
+            "  ingest: {} queued, {} shed, {} duplicates suppressed",
+            archive.ingest_queue_depth,
+            archive.ingest_records_shed,
+            archive.updates.duplicates_suppressed
+        );
+        if archive.updates.sample_rate > 1 {
+            println!(
+                "  sampling: 1 in {} updates, {} left out",
+                archive.updates.sample_rate, archive.updates.sampled_out
+            );
+        }
+        if let Some(free_mb) = archive.disk_free_mb {
+            println!(
+                "  disk: {free_mb} MiB free ({}), {} records dropped",
+                archive.disk_level.as_str(),
+                archive.disk_records_dropped
+            );
+        }
+        let records = archive.updates.records_per_sec;
+        let bytes = archive.updates.bytes_per_sec;
+        println!(
+            "  throughput: {:.1}/{:.1}/{:.1} records/s, {:.0}/{:.0}/{:.0} B/s (1m/5m/15m)",
+            records.m1, records.m5, records.m15, bytes.m1, bytes.m5, bytes.m15
+        );
+    } else {
+        println!("archive: disabled");
+    }
+
+    Ok(healthy)
+}
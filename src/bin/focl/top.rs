@@ -0,0 +1,295 @@
+//! `focl top`: a live terminal dashboard fed from the control socket.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use focl::bgp::{BgpStats, PeerInfo};
+use focl::control::{ArchiveStatusResult, BgpStatsResult};
+use focl::types::PeerState;
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEventKind, KeyModifiers};
+use ratatui::layout::{Constraint, Layout, Rect};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, Cell, Gauge, Paragraph, Row, Table};
+use ratatui::{DefaultTerminal, Frame};
+use serde_json::json;
+
+use crate::{fetch_result, PeerList};
+
+#[derive(Debug, Default)]
+struct Snapshot {
+    peers: Vec<PeerInfo>,
+    stats: Option<BgpStats>,
+    archive: Option<ArchiveStatusResult>,
+    error: Option<String>,
+    fetched_at: i64,
+}
+
+pub async fn run(socket: PathBuf, refresh: Duration) -> Result<()> {
+    let mut terminal = ratatui::init();
+    let result = run_loop(&mut terminal, &socket, refresh).await;
+    ratatui::restore();
+    result
+}
+
+async fn run_loop(
+    terminal: &mut DefaultTerminal,
+    socket: &PathBuf,
+    refresh: Duration,
+) -> Result<()> {
+    loop {
+        let snapshot = fetch_snapshot(socket).await;
+        terminal.draw(|frame| render(frame, socket, &snapshot))?;
+
+        let deadline = Instant::now() + refresh;
+        while let Some(remaining) = deadline.checked_duration_since(Instant::now()) {
+            if !event::poll(remaining.min(Duration::from_millis(100)))? {
+                continue;
+            }
+            match event::read()? {
+                Event::Key(key) if key.kind == KeyEventKind::Press => match key.code {
+                    KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+                    KeyCode::Char('c') if key.modifiers.contains(KeyModifiers::CONTROL) => {
+                        return Ok(())
+                    }
+                    KeyCode::Char('r') => break,
+                    _ => {}
+                },
+                Event::Resize(_, _) => {
+                    terminal.draw(|frame| render(frame, socket, &snapshot))?;
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+async fn fetch_snapshot(socket: &PathBuf) -> Snapshot {
+    let mut snapshot = Snapshot {
+        fetched_at: chrono::Utc::now().timestamp(),
+        ..Default::default()
+    };
+
+    let peers = fetch_result::<PeerList>(socket, "peer_list", json!({})).await;
+    match peers {
+        Ok(list) => snapshot.peers = list.peers,
+        Err(err) => {
+            snapshot.error = Some(err.to_string());
+            return snapshot;
+        }
+    }
+
+    let top = snapshot.peers.len().max(1);
+    match fetch_result::<BgpStatsResult>(socket, "bgp_stats", json!({"top": top})).await {
+        Ok(result) => snapshot.stats = Some(result.stats),
+        Err(err) => snapshot.error = Some(err.to_string()),
+    }
+    match fetch_result::<ArchiveStatusResult>(socket, "archive_status", json!({})).await {
+        Ok(status) => snapshot.archive = Some(status),
+        Err(err) => snapshot.error = Some(err.to_string()),
+    }
+    snapshot
+}
+
+fn render(frame: &mut Frame, socket: &Path, snapshot: &Snapshot) {
+    let [header, peers, archive, footer] = Layout::vertical([
+        Constraint::Length(3),
+        Constraint::Min(5),
+        Constraint::Length(6),
+        Constraint::Length(1),
+    ])
+    .areas(frame.area());
+
+    render_header(frame, header, socket, snapshot);
+    render_peers(frame, peers, snapshot);
+    render_archive(frame, archive, snapshot);
+
+    let footer_text = match &snapshot.error {
+        Some(err) => Line::styled(format!(" error: {err}"), Style::new().fg(Color::Red)),
+        None => Line::styled(" q: quit  r: refresh", Style::new().fg(Color::DarkGray)),
+    };
+    frame.render_widget(Paragraph::new(footer_text), footer);
+}
+
+fn render_header(frame: &mut Frame, area: Rect, socket: &Path, snapshot: &Snapshot) {
+    let established = snapshot
+        .peers
+        .iter()
+        .filter(|p| matches!(p.state, PeerState::Established))
+        .count();
+    let (rate, flaps) = snapshot
+        .stats
+        .as_ref()
+        .map(|s| (s.updates_per_sec, s.session_flaps_last_hour))
+        .unwrap_or_default();
+    let text = format!(
+        " peers {established}/{} established   updates {rate:.1}/s   flaps (1h) {flaps}",
+        snapshot.peers.len()
+    );
+    let block = Block::default()
+        .borders(Borders::ALL)
+        .title(format!(" focl top — {} ", socket.display()));
+    frame.render_widget(Paragraph::new(text).block(block), area);
+}
+
+fn render_peers(frame: &mut Frame, area: Rect, snapshot: &Snapshot) {
+    let rates: HashMap<&str, f64> = snapshot
+        .stats
+        .iter()
+        .flat_map(|s| s.top_talkers.iter())
+        .map(|t| (t.address.as_str(), t.updates_per_sec))
+        .collect();
+
+    let header = Row::new([
+        "PEER", "NAME", "AS", "STATE", "UPTIME", "UPD/S", "UPDATES", "WDRAW", "FLAPS",
+    ])
+    .style(Style::new().add_modifier(Modifier::BOLD));
+    let rows = snapshot.peers.iter().map(|peer| {
+        let state = format!("{:?}", peer.state);
+        let state_style = if matches!(peer.state, PeerState::Established) {
+            Style::new().fg(Color::Green)
+        } else {
+            Style::new().fg(Color::Yellow)
+        };
+        let uptime = peer
+            .established_at
+            .map(|ts| format_duration(snapshot.fetched_at.saturating_sub(ts)))
+            .unwrap_or_else(|| "-".to_string());
+        let rate = rates.get(peer.address.as_str()).copied().unwrap_or(0.0);
+        Row::new([
+            Cell::from(peer.address.clone()),
+            Cell::from(peer.name.clone().unwrap_or_default()),
+            Cell::from(peer.remote_as.to_string()),
+            Cell::from(state).style(state_style),
+            Cell::from(uptime),
+            Cell::from(format!("{rate:.1}")),
+            Cell::from(peer.updates_received.to_string()),
+            Cell::from(peer.withdrawals_received.to_string()),
+            Cell::from(peer.session_flaps.to_string()),
+        ])
+    });
+
+    let widths = [
+        Constraint::Min(16),
+        Constraint::Min(10),
+        Constraint::Length(10),
+        Constraint::Length(12),
+        Constraint::Length(10),
+        Constraint::Length(8),
+        Constraint::Length(10),
+        Constraint::Length(8),
+        Constraint::Length(6),
+    ];
+    let table = Table::new(rows, widths)
+        .header(header)
+        .block(Block::default().borders(Borders::ALL).title(" Peers "));
+    frame.render_widget(table, area);
+}
+
+fn render_archive(frame: &mut Frame, area: Rect, snapshot: &Snapshot) {
+    let block = Block::default().borders(Borders::ALL).title(" Archive ");
+    let inner = block.inner(area);
+    frame.render_widget(block, area);
+
+    let Some(status) = &snapshot.archive else {
+        frame.render_widget(Paragraph::new(" archive status unavailable"), inner);
+        return;
+    };
+    if !status.enabled {
+        frame.render_widget(Paragraph::new(" archive disabled"), inner);
+        return;
+    }
+
+    let [gauge_area, details] =
+        Layout::vertical([Constraint::Length(1), Constraint::Min(1)]).areas(inner);
+
+    let progress = segment_progress(snapshot.fetched_at, status.updates.interval_secs);
+    let gauge = Gauge::default()
+        .gauge_style(Style::new().fg(Color::Cyan))
+        .ratio(progress)
+        .label(format!(
+            "updates segment {:.0}% ({} records)",
+            progress * 100.0,
+            status.updates.open_record_count
+        ));
+    frame.render_widget(gauge, gauge_area);
+
+    let lines = vec![
+        Line::from(format!(
+            " open: {}",
+            status.updates.open_path.as_deref().unwrap_or("-")
+        )),
+        Line::from(format!(
+            " last rib: {} ({} records)",
+            status.ribs.last_finalized_path.as_deref().unwrap_or("-"),
+            status.ribs.last_finalized_record_count
+        )),
+        Line::from(format!(
+            " replication backlog: {} queued, {} failures",
+            status.queued_replication_jobs, status.replication_failures
+        )),
+    ];
+    frame.render_widget(Paragraph::new(lines), details);
+}
+
+/// Fraction of the current aligned updates interval that has elapsed.
+fn segment_progress(now: i64, interval_secs: u32) -> f64 {
+    if interval_secs == 0 {
+        return 0.0;
+    }
+    let interval = i64::from(interval_secs);
+    now.rem_euclid(interval) as f64 / interval as f64
+}
+
+pub(crate) fn format_duration(secs: i64) -> String {
+    let secs = secs.max(0);
+    let (days, rem) = (secs / 86_400, secs % 86_400);
+    let (hours, rem) = (rem / 3600, rem % 3600);
+    let (mins, secs) = (rem / 60, rem % 60);
+    if days > 0 {
+        format!("{days}d{hours:02}h")
+    } else if hours > 0 {
+        format!("{hours}h{mins:02}m")
+    } else {
+        format!("{mins}m{secs:02}s")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_the_bgp_stats_response() {
+        let stats = BgpStats {
+            updates_per_sec: 12.5,
+            updates_received_total: 40,
+            withdrawals_received_total: 2,
+            session_flaps_last_hour: 1,
+            top_talkers: vec![],
+            inbound_rejected: 0,
+            inbound_banned_sources: 0,
+        };
+        // What focld writes for `bgp_stats`, read back as `focl top` does.
+        let response = focl::types::ControlResponse::ok(
+            "1",
+            serde_json::to_value(BgpStatsResult { stats }).unwrap(),
+        );
+        let line = serde_json::to_string(&response).unwrap();
+        let result: BgpStatsResult =
+            crate::decode_result("bgp_stats", serde_json::from_str(&line).unwrap()).unwrap();
+        assert_eq!(result.stats.updates_per_sec, 12.5);
+        assert_eq!(result.stats.updates_received_total, 40);
+    }
+
+    #[test]
+    fn formats_progress_and_uptime() {
+        assert_eq!(segment_progress(1_699_999_950, 300), 0.5);
+        assert_eq!(segment_progress(1_700_000_000, 0), 0.0);
+        assert_eq!(format_duration(59), "0m59s");
+        assert_eq!(format_duration(3_725), "1h02m");
+        assert_eq!(format_duration(90_000), "1d01h");
+    }
+}
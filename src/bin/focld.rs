@@ -1,31 +1,88 @@
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 
 use anyhow::{Context, Result};
 use clap::Parser;
-use focl::archive::types::ArchiveStream;
+use focl::archive::types::{ArchiveStream, FinalizedSegment, RibSnapshotInput};
 use focl::archive::ArchiveService;
-use focl::bgp::BgpService;
-use focl::config::FoclConfig;
-use focl::control::{ArchiveRolloverArgs, ArchiveStatusResult, CommandKind, PeerKeyArgs};
-use focl::types::{ControlRequest, ControlResponse};
+use focl::bgp::{BgpService, Maintenance, SnapshotTable};
+use focl::config::{ConfigOverrides, FoclConfig};
+use focl::control::{
+    page_prefixes, paginate_prefixes, ArchiveFetchArgs, ArchivePeerIndexArgs,
+    ArchivePrioritizeArgs, ArchiveReconcileArgs, ArchiveRolloverArgs, ArchiveSnapshotNowArgs,
+    ArchiveStatusResult, AspathStatsArgs, BatchArgs, BgpStatsArgs, BgpStatsResult, BgpTopArgs,
+    BlackholeArgs, CommandKind, EventsSubscribeArgs, FetchTarget, PeerIndexFormat,
+    PeerLogMessagesArgs, PeerMaintenanceArgs, PeerResetArgs, PeerShowArgs, PeerStatsResetArgs,
+    PeerTimersArgs, PeerWaitArgs, PrefixHistoryArgs, PrefixLoadMrtArgs, RibChurnArgs, RibQueryArgs,
+};
+use focl::events::{alerts, hooks, rules, EventQuery, EventStore};
+use focl::health::HealthMonitor;
+use focl::peer_stats::{self, PeerStatsStore};
+use focl::prefix_history::{self, PrefixHistory};
+use focl::types::{ControlRequest, ControlResponse, EventBus, EventTopic};
 use serde_json::json;
-use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
-use tokio::net::{UnixListener, UnixStream};
-use tokio::sync::broadcast;
+use tokio::io::{AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, UnixListener, UnixStream};
+use tokio::sync::{broadcast, oneshot, Semaphore};
+
+/// How long shutdown waits for the event recorder to persist what it holds.
+const EVENT_DRAIN_TIMEOUT: Duration = Duration::from_secs(2);
 
 #[derive(Debug, Parser)]
 struct Args {
-    #[arg(short, long, default_value = "focl.toml")]
+    #[arg(short, long, default_value = "focl.toml", env = "FOCL_CONFIG")]
     config: PathBuf,
+    /// Named instance; derives distinct default socket, pid file, and archive paths
+    #[arg(long, env = "FOCL_INSTANCE")]
+    instance: Option<String>,
+    /// Override [global].asn
+    #[arg(long, env = "FOCL_ASN")]
+    asn: Option<u32>,
+    /// Override [global].router_id
+    #[arg(long, env = "FOCL_ROUTER_ID")]
+    router_id: Option<String>,
+    /// Override [global].control_socket
+    #[arg(long, env = "FOCL_CONTROL_SOCKET")]
+    control_socket: Option<PathBuf>,
+    /// Override [archive].root
+    #[arg(long, env = "FOCL_ARCHIVE_ROOT")]
+    archive_root: Option<PathBuf>,
+    /// Validate the config, run preflight, print the effective config, and
+    /// exit without opening sockets
+    #[arg(long)]
+    dry_run: bool,
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let args = Args::parse();
 
-    let cfg = FoclConfig::load(&args.config)?;
-    init_tracing(&cfg.global.log_level);
+    let overrides = ConfigOverrides {
+        asn: args.asn,
+        router_id: args.router_id.clone(),
+        control_socket: args.control_socket.clone(),
+        archive_root: args.archive_root.clone(),
+    };
+    let mut cfg = FoclConfig::load_with_overrides(&args.config, &overrides)?;
+    if let Some(instance) = &args.instance {
+        cfg.apply_instance(instance)?;
+    }
+    if args.dry_run {
+        return dry_run(&args.config, &cfg).await;
+    }
+    focl::logging::init(&cfg.global.log_level, cfg.global.log_target)?;
+    for warning in &cfg.migration_warnings {
+        tracing::warn!(config=%args.config.display(), "{warning}");
+    }
+
+    if cfg.global.preflight {
+        let report = focl::preflight::run(&cfg).await;
+        if !report.passed() {
+            anyhow::bail!("{report}");
+        }
+        tracing::info!(checks = report.checks.len(), "preflight checks passed");
+    }
 
     let collector_bgp_id = cfg
         .global
@@ -33,26 +90,156 @@ async fn main() -> Result<()> {
         .parse::<std::net::Ipv4Addr>()
         .context("global.router_id must be valid IPv4")?;
 
-    let archive = ArchiveService::new(cfg.archive.clone(), collector_bgp_id).await?;
-    let events_tx = archive.event_sender();
-    let bgp = BgpService::new(&cfg, events_tx).await?;
+    let bus = EventBus::new(cfg.events.buffer_size);
+    // Subscribe before any service starts, so history and hooks see the
+    // events of startup too.
+    let (events, events_task) = if cfg.events.history {
+        let store = EventStore::new(&cfg.events.history_path, cfg.events.history_max_events)?;
+        let (stop, stop_rx) = oneshot::channel();
+        let task = store
+            .clone()
+            .spawn_recorder(bus.subscribe("event_history", &[]), stop_rx);
+        (Some(Arc::new(store)), Some((stop, task)))
+    } else {
+        (None, None)
+    };
+
+    let hooks_task = (!cfg.hooks.is_empty())
+        .then(|| hooks::spawn(cfg.hooks.clone(), bus.subscribe("hooks", &[])));
+    let alerts_task = cfg.alerts.enabled().then(|| {
+        alerts::spawn(
+            cfg.alerts.clone(),
+            cfg.archive.collector_id.clone(),
+            bus.subscribe("alerts", &[]),
+        )
+    });
+    // Rules also watch received routes, so they start once BGP is up.
+    let rules_events = (!cfg.rules.is_empty()).then(|| bus.subscribe("rules", &[]));
+
+    let archive = ArchiveService::new(cfg.archive.clone(), collector_bgp_id, bus.clone()).await?;
+    let bgp = BgpService::new(&cfg, bus.clone(), Some(Arc::clone(&archive))).await?;
+
+    let exabgp_tasks = if cfg.exabgp.enabled {
+        tracing::info!(
+            input=%cfg.exabgp.input.display(),
+            output=%cfg.exabgp.output.display(),
+            "exabgp api enabled"
+        );
+        focl::exabgp::spawn(&cfg.exabgp, bgp.clone())
+    } else {
+        Vec::new()
+    };
+
+    let openbmp_task = cfg.openbmp.enabled.then(|| {
+        let admin_id = cfg
+            .openbmp
+            .admin_id
+            .as_deref()
+            .unwrap_or(&cfg.archive.collector_id);
+        tracing::info!(
+            output=?cfg.openbmp.output,
+            admin_id,
+            "openbmp output enabled"
+        );
+        focl::openbmp::spawn(&cfg.openbmp, admin_id, &bgp)
+    });
+
+    let pid_path = cfg.global.pid_file.clone();
+    write_pid_file(&pid_path)?;
 
     let socket_path = cfg.global.control_socket.clone();
     cleanup_socket(&socket_path)?;
 
     let listener = UnixListener::bind(&socket_path)
         .with_context(|| format!("failed binding control socket {}", socket_path.display()))?;
+    apply_socket_permissions(&socket_path, &cfg.global)?;
+
+    let (prefix_history, prefix_history_task) = if cfg.prefix_history.enabled {
+        let store = Arc::new(PrefixHistory::new(&cfg.prefix_history.path)?);
+        let task = prefix_history::spawn(
+            Arc::clone(&store),
+            &bgp,
+            Duration::from_secs(cfg.prefix_history.flush_interval_secs),
+        );
+        (Some(store), Some(task))
+    } else {
+        (None, None)
+    };
 
-    tracing::info!(socket=%socket_path.display(), "focld started");
+    let (peer_stats, peer_stats_task) = if cfg.peer_stats.enabled {
+        let store = Arc::new(PeerStatsStore::new(&cfg.peer_stats.path)?);
+        bgp.restore_peer_totals(&store.load()?).await;
+        let task = peer_stats::spawn(
+            Arc::clone(&store),
+            &bgp,
+            Duration::from_secs(cfg.peer_stats.save_interval_secs),
+        );
+        (Some(store), Some(task))
+    } else {
+        (None, None)
+    };
+
+    let rules_task = match rules_events {
+        Some(events) => Some(rules::spawn(
+            cfg.rules.clone(),
+            cfg.archive.collector_id.clone(),
+            events,
+            &bgp,
+        )?),
+        None => None,
+    };
+
+    let health = HealthMonitor::new(cfg.health.clone(), Arc::clone(&archive), bgp.clone());
+    health.set_control_server_up(true);
+
+    let health_task = match &cfg.health.http_listen {
+        Some(addr) => {
+            let http_listener = TcpListener::bind(addr)
+                .await
+                .with_context(|| format!("failed binding health listener {addr}"))?;
+            tracing::info!(listen=%addr, "health probes enabled");
+            let health = Arc::clone(&health);
+            Some(tokio::spawn(async move {
+                if let Err(err) = health.serve_http(http_listener).await {
+                    tracing::error!(error=%err, "health probe server failed");
+                }
+            }))
+        }
+        None => None,
+    };
+
+    tracing::info!(
+        socket=%socket_path.display(),
+        instance=args.instance.as_deref().unwrap_or("default"),
+        "focld started"
+    );
 
     let (shutdown_tx, _) = broadcast::channel::<()>(8);
     let mut shutdown_rx = shutdown_tx.subscribe();
 
+    let ctx = ControlContext {
+        archive: Arc::clone(&archive),
+        bgp: bgp.clone(),
+        health: Arc::clone(&health),
+        events,
+        prefix_history,
+        peer_stats: peer_stats.clone(),
+        bus,
+        shutdown_tx: shutdown_tx.clone(),
+        limits: ControlLimits {
+            read_timeout: Duration::from_secs(cfg.global.control_read_timeout_secs),
+            max_request_bytes: cfg.global.control_max_request_bytes,
+            max_connections: cfg.global.control_max_connections,
+        },
+    };
+
     let accept_task = {
-        let archive = Arc::clone(&archive);
-        let bgp = bgp.clone();
-        let shutdown_tx = shutdown_tx.clone();
-        tokio::spawn(async move { run_control_server(listener, archive, bgp, shutdown_tx).await })
+        let ctx = ctx.clone();
+        tokio::spawn(async move {
+            let result = run_control_server(listener, ctx.clone()).await;
+            ctx.health.set_control_server_up(false);
+            result
+        })
     };
 
     tokio::select! {
@@ -65,21 +252,103 @@ async fn main() -> Result<()> {
     }
 
     let _ = shutdown_tx.send(());
-    accept_task.abort();
-    cleanup_socket(&socket_path)?;
+    let deadline = Duration::from_secs(cfg.global.shutdown_timeout_secs);
+    let sequence = async {
+        accept_task.abort();
+        health.set_control_server_up(false);
+        if let Some(task) = health_task {
+            task.abort();
+        }
+        cleanup_socket(&socket_path)?;
+        tracing::info!("control server stopped");
 
-    Ok(())
+        for task in &exabgp_tasks {
+            task.abort();
+        }
+        if let Some(task) = &openbmp_task {
+            task.abort();
+        }
+        // Peers get half the budget; the rest is for finalizing the archive.
+        bgp.shutdown(deadline / 2).await;
+        tracing::info!("peer sessions closed");
+        if let Some(task) = peer_stats_task {
+            task.abort();
+        }
+        if let Some(store) = &peer_stats {
+            if let Err(err) = store.save(&bgp.peer_totals().await) {
+                tracing::error!(error=%err, "failed saving peer stats");
+            }
+        }
+
+        archive.shutdown().await?;
+        tracing::info!("archive finalized");
+
+        if let Some((stop, task)) = events_task {
+            // The recorder persists the shutdown events already published, then ends.
+            let _ = stop.send(());
+            if tokio::time::timeout(EVENT_DRAIN_TIMEOUT, task)
+                .await
+                .is_err()
+            {
+                tracing::warn!("event history did not drain before shutdown");
+            }
+        }
+        if let Some(task) = prefix_history_task {
+            task.abort();
+        }
+        if let Some(task) = hooks_task {
+            task.abort();
+        }
+        if let Some(task) = rules_task {
+            task.abort();
+        }
+        if let Some(task) = alerts_task {
+            task.abort();
+        }
+        Ok::<_, anyhow::Error>(())
+    };
+
+    let result = match tokio::time::timeout(deadline, sequence).await {
+        Ok(result) => result,
+        Err(_) => {
+            tracing::warn!(
+                timeout_secs = cfg.global.shutdown_timeout_secs,
+                "shutdown deadline exceeded, exiting"
+            );
+            cleanup_socket(&socket_path)
+        }
+    };
+    remove_pid_file(&pid_path);
+
+    result
 }
 
-fn init_tracing(level: &str) {
-    let env_filter = tracing_subscriber::EnvFilter::try_new(level)
-        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new("info"));
+fn write_pid_file(path: &Path) -> Result<()> {
+    if let Ok(raw) = std::fs::read_to_string(path) {
+        if let Ok(pid) = raw.trim().parse::<i32>() {
+            let alive = unsafe { libc::kill(pid, 0) } == 0;
+            if alive && pid != std::process::id() as i32 {
+                anyhow::bail!(
+                    "focld already running with pid {} (pid file {})",
+                    pid,
+                    path.display()
+                );
+            }
+        }
+    }
 
-    tracing_subscriber::fmt()
-        .with_env_filter(env_filter)
-        .with_target(false)
-        .json()
-        .init();
+    if let Some(parent) = path.parent() {
+        std::fs::create_dir_all(parent)
+            .with_context(|| format!("failed creating pid file dir {}", parent.display()))?;
+    }
+    std::fs::write(path, format!("{}\n", std::process::id()))
+        .with_context(|| format!("failed writing pid file {}", path.display()))
+}
+
+fn remove_pid_file(path: &Path) {
+    if let Err(err) = std::fs::remove_file(path) {
+        tracing::warn!(error=%err, path=%path.display(), "failed removing pid file");
+    }
 }
 
 fn cleanup_socket(path: &Path) -> Result<()> {
@@ -90,42 +359,245 @@ fn cleanup_socket(path: &Path) -> Result<()> {
     Ok(())
 }
 
-async fn run_control_server(
-    listener: UnixListener,
+#[derive(Clone)]
+struct ControlContext {
     archive: Arc<ArchiveService>,
     bgp: BgpService,
+    health: Arc<HealthMonitor>,
+    events: Option<Arc<EventStore>>,
+    prefix_history: Option<Arc<PrefixHistory>>,
+    peer_stats: Option<Arc<PeerStatsStore>>,
+    bus: EventBus,
     shutdown_tx: broadcast::Sender<()>,
-) -> Result<()> {
+    limits: ControlLimits,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct ControlLimits {
+    read_timeout: Duration,
+    max_request_bytes: usize,
+    max_connections: usize,
+}
+
+fn apply_socket_permissions(path: &Path, global: &focl::config::GlobalConfig) -> Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+
+    if let Some(mode) = global.control_socket_mode_bits()? {
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+            .with_context(|| format!("failed setting mode {mode:o} on {}", path.display()))?;
+    }
+
+    if let Some(group) = &global.control_socket_group {
+        let gid = resolve_group(group)?;
+        std::os::unix::fs::chown(path, None, Some(gid))
+            .with_context(|| format!("failed changing group of {} to {group}", path.display()))?;
+    }
+
+    Ok(())
+}
+
+/// `archive_snapshot_now`; an error comes with its control error code.
+async fn take_snapshot(
+    archive: &ArchiveService,
+    bgp: &BgpService,
+    args: ArchiveSnapshotNowArgs,
+) -> Result<FinalizedSegment, (&'static str, anyhow::Error)> {
+    let now = chrono::Utc::now().timestamp();
+    let timestamp = match args.timestamp {
+        None => now,
+        Some(ts) if !(0..=now).contains(&ts) => {
+            return Err((
+                "invalid_args",
+                anyhow::anyhow!("timestamp {ts} is not between 0 and now"),
+            ));
+        }
+        // A past bucket most likely has its scheduled snapshot already.
+        Some(ts) => match archive.rib_segment_exists(ts) {
+            Ok(false) => ts,
+            Ok(true) => {
+                return Err((
+                    "invalid_args",
+                    anyhow::anyhow!("a RIB segment already exists for the bucket of {ts}"),
+                ));
+            }
+            Err(err) => return Err(("archive_snapshot_failed", err)),
+        },
+    };
+    let view_name = args
+        .view_name
+        .clone()
+        .unwrap_or_else(|| archive.rib_view_name().to_string());
+    let table = match args.source() {
+        Some(source) => bgp
+            .rib_snapshot(source, args.peers.as_deref(), &view_name, timestamp)
+            .await
+            .map_err(|err| ("archive_snapshot_failed", err))?,
+        None => SnapshotTable::default(),
+    };
+
+    let snapshot = RibSnapshotInput {
+        timestamp,
+        collector_bgp_id: std::net::Ipv4Addr::UNSPECIFIED,
+        view_name,
+        peers: table.peers,
+        routes: table.routes,
+    };
+    archive
+        .snapshot_now(snapshot)
+        .await
+        .map_err(|err| ("archive_snapshot_failed", err))
+}
+
+/// `archive_peer_index`: the PEER_INDEX_TABLE a RIB snapshot taken now would
+/// start with, as JSON or as a one-record MRT file.
+async fn peer_index(
+    archive: &ArchiveService,
+    bgp: &BgpService,
+    args: ArchivePeerIndexArgs,
+) -> Result<serde_json::Value> {
+    use base64::Engine;
+
+    let view_name = args
+        .view_name
+        .unwrap_or_else(|| archive.rib_view_name().to_string());
+    let snapshot = RibSnapshotInput {
+        timestamp: chrono::Utc::now().timestamp(),
+        collector_bgp_id: archive.collector_bgp_id(),
+        peers: bgp.peer_index(&view_name).await?,
+        view_name,
+        routes: vec![],
+    };
+    Ok(match args.format {
+        PeerIndexFormat::Json => {
+            let peers = snapshot
+                .peers
+                .iter()
+                .enumerate()
+                .map(|(index, peer)| {
+                    json!({
+                        "index": index,
+                        "peer_ip": peer.peer_ip,
+                        "peer_asn": peer.peer_asn,
+                        "peer_bgp_id": peer.peer_bgp_id,
+                    })
+                })
+                .collect::<Vec<_>>();
+            json!({
+                "timestamp": snapshot.timestamp,
+                "collector_bgp_id": snapshot.collector_bgp_id,
+                "view_name": snapshot.view_name,
+                "peers": peers,
+            })
+        }
+        PeerIndexFormat::Mrt => {
+            let record = focl::archive::snapshot::encode_peer_index_table(&snapshot)?;
+            json!({
+                "timestamp": snapshot.timestamp,
+                "peers": snapshot.peers.len(),
+                "data": base64::engine::general_purpose::STANDARD.encode(record),
+            })
+        }
+    })
+}
+
+/// `--dry-run`: reports go to stderr and the effective config to stdout, so
+/// the latter can be redirected to a file.
+async fn dry_run(path: &Path, cfg: &FoclConfig) -> Result<()> {
+    for warning in &cfg.migration_warnings {
+        eprintln!("warning: {warning}");
+    }
+    eprintln!("config {} ok", path.display());
+
+    let mut report = focl::preflight::resolve_peers(cfg);
+    report.checks.extend(focl::preflight::run(cfg).await.checks);
+    eprint!("{report}");
+
+    print!("{}", cfg.effective_toml()?);
+    if !report.passed() {
+        anyhow::bail!("dry run failed");
+    }
+    Ok(())
+}
+
+fn resolve_group(group: &str) -> Result<u32> {
+    if let Ok(gid) = group.parse::<u32>() {
+        return Ok(gid);
+    }
+    let name = std::ffi::CString::new(group).context("group name contains a NUL byte")?;
+    let entry = unsafe { libc::getgrnam(name.as_ptr()) };
+    if entry.is_null() {
+        anyhow::bail!("unknown group {group}");
+    }
+    Ok(unsafe { (*entry).gr_gid })
+}
+
+async fn run_control_server(listener: UnixListener, ctx: ControlContext) -> Result<()> {
+    let slots = Arc::new(Semaphore::new(ctx.limits.max_connections));
+
     loop {
         let (stream, _addr) = listener.accept().await?;
-        let archive = Arc::clone(&archive);
-        let bgp = bgp.clone();
-        let shutdown_tx = shutdown_tx.clone();
 
+        let Ok(permit) = Arc::clone(&slots).try_acquire_owned() else {
+            tracing::warn!(
+                max_connections = ctx.limits.max_connections,
+                "rejecting control connection: too many open connections"
+            );
+            tokio::spawn(async move {
+                let (_read_half, mut write_half) = stream.into_split();
+                let resp = ControlResponse::err(
+                    "unknown",
+                    "too_many_connections",
+                    "control connection limit reached",
+                );
+                let _ = write_response(&mut write_half, &resp).await;
+            });
+            continue;
+        };
+
+        let ctx = ctx.clone();
         tokio::spawn(async move {
-            if let Err(err) = handle_client(stream, archive, bgp, shutdown_tx).await {
+            let _permit = permit;
+            if let Err(err) = handle_client(stream, ctx).await {
                 tracing::warn!(error=%err, "control connection failed");
             }
         });
     }
 }
 
-async fn handle_client(
-    stream: UnixStream,
-    archive: Arc<ArchiveService>,
-    bgp: BgpService,
-    shutdown_tx: broadcast::Sender<()>,
-) -> Result<()> {
+async fn handle_client(stream: UnixStream, ctx: ControlContext) -> Result<()> {
+    let limits = ctx.limits;
     let (read_half, mut write_half) = stream.into_split();
     let mut reader = BufReader::new(read_half);
     let mut line = String::new();
 
     loop {
         line.clear();
-        let bytes = reader.read_line(&mut line).await?;
+        let read = tokio::time::timeout(
+            limits.read_timeout,
+            (&mut reader)
+                .take(limits.max_request_bytes as u64 + 1)
+                .read_line(&mut line),
+        )
+        .await;
+        let bytes = match read {
+            Ok(result) => result?,
+            Err(_) => {
+                tracing::debug!("closing idle control connection");
+                return Ok(());
+            }
+        };
         if bytes == 0 {
             return Ok(());
         }
+        if bytes > limits.max_request_bytes {
+            let resp = ControlResponse::err(
+                "unknown",
+                "request_too_large",
+                format!("request exceeds {} bytes", limits.max_request_bytes),
+            );
+            write_response(&mut write_half, &resp).await?;
+            return Ok(());
+        }
 
         let req = match serde_json::from_str::<ControlRequest>(line.trim_end()) {
             Ok(req) => req,
@@ -136,209 +608,848 @@ async fn handle_client(
             }
         };
 
-        let cmd = CommandKind::from_request(&req);
-        let response = match cmd {
-            CommandKind::Ping => ControlResponse::ok(req.id, json!({"pong": true})),
-            CommandKind::DaemonStatus => {
-                let status = archive.status().await?;
-                let rib = bgp.rib_summary().await;
-                ControlResponse::ok(
-                    req.id,
-                    json!({
-                        "daemon": "focld",
-                        "archive_enabled": status.enabled,
-                        "queued_replication_jobs": status.queued_replication_jobs,
-                        "peers_total": rib.peers_total,
-                        "peers_established": rib.peers_established,
-                    }),
-                )
+        let reply = if CommandKind::from_request(&req) == CommandKind::Batch {
+            Reply::Response(run_batch(&ctx, req).await)
+        } else {
+            execute(&ctx, req).await?
+        };
+        match reply {
+            Reply::Response(response) => write_response(&mut write_half, &response).await?,
+            Reply::RibStream { id, args, prefixes } => {
+                write_rib_stream(&mut write_half, &id, &args, prefixes).await?
             }
-            CommandKind::Reload => ControlResponse::ok(req.id, json!({"reloaded": true})),
-            CommandKind::Shutdown => {
-                let _ = shutdown_tx.send(());
-                ControlResponse::ok(req.id, json!({"shutting_down": true}))
-            }
-            CommandKind::ArchiveStatus => {
-                let status = archive.status().await?;
-                let result = ArchiveStatusResult {
-                    enabled: status.enabled,
-                    collector_id: status.collector_id,
-                    updates_interval_secs: status.updates_interval_secs,
-                    ribs_interval_secs: status.ribs_interval_secs,
-                    updates_open_path: status.updates_open_path.map(|p| p.display().to_string()),
-                    updates_record_count: status.updates_record_count,
-                    ribs_last_path: status.ribs_last_path.map(|p| p.display().to_string()),
-                    ribs_last_record_count: status.ribs_last_record_count,
-                    queued_replication_jobs: status.queued_replication_jobs,
-                    replication_failures: status.replication_failures,
-                };
-                ControlResponse::ok(req.id, result.as_value())
-            }
-            CommandKind::ArchiveRollover => {
-                let args = match ArchiveRolloverArgs::from_json(&req.args) {
-                    Ok(args) => args,
-                    Err(err) => {
-                        let response = ControlResponse::err(
-                            req.id,
-                            "invalid_args",
-                            format!("archive_rollover args error: {err}"),
-                        );
-                        write_response(&mut write_half, &response).await?;
-                        continue;
-                    }
-                };
-                if args.stream == focl::control::ArchiveStream::Updates {
-                    archive.rollover(ArchiveStream::Updates).await?;
-                } else {
-                    archive.rollover(ArchiveStream::Ribs).await?;
-                }
-                ControlResponse::ok(req.id, json!({"ok": true}))
-            }
-            CommandKind::ArchiveSnapshotNow => {
-                let snapshot = focl::archive::types::RibSnapshotInput {
-                    timestamp: chrono::Utc::now().timestamp(),
-                    collector_bgp_id: std::net::Ipv4Addr::UNSPECIFIED,
-                    view_name: "main".to_string(),
-                    peers: vec![],
-                    routes: vec![],
-                };
-                let result = archive.snapshot_now(snapshot).await?;
-                ControlResponse::ok(
+            Reply::FetchStream { id, args, path } => {
+                write_fetch_stream(&mut write_half, &id, &args, path).await?
+            }
+            Reply::Subscribe { id, topics } => {
+                let resp = ControlResponse::ok(
+                    id,
+                    json!({"subscribed": true, "topics": topics, "last_seq": ctx.bus.last_seq()}),
+                );
+                write_response(&mut write_half, &resp).await?;
+                let mut subscription = ctx.bus.subscribe("events_subscribe", &topics);
+                while let Some(event) = subscription.recv().await {
+                    let payload = serde_json::to_string(&event)?;
+                    write_half.write_all(payload.as_bytes()).await?;
+                    write_half.write_all(b"\n").await?;
+                }
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// What a control command produced. Streamed results are written by the
+/// connection itself, so they cannot be part of a `batch`.
+enum Reply {
+    Response(ControlResponse),
+    RibStream {
+        id: String,
+        args: RibQueryArgs,
+        prefixes: Vec<ipnet::IpNet>,
+    },
+    FetchStream {
+        id: String,
+        args: ArchiveFetchArgs,
+        path: PathBuf,
+    },
+    Subscribe {
+        id: String,
+        topics: Vec<EventTopic>,
+    },
+}
+
+async fn execute(ctx: &ControlContext, req: ControlRequest) -> Result<Reply> {
+    let ControlContext {
+        archive,
+        bgp,
+        health,
+        events,
+        prefix_history,
+        peer_stats,
+        bus,
+        shutdown_tx,
+        limits: _,
+    } = ctx;
+    let cmd = CommandKind::from_request(&req);
+    let response = match cmd {
+        CommandKind::Ping => ControlResponse::ok(req.id, json!({"pong": true})),
+        CommandKind::DaemonStatus => {
+            let status = archive.status().await?;
+            let rib = bgp.rib_summary();
+            ControlResponse::ok(
+                req.id,
+                json!({
+                    "daemon": "focld",
+                    "pid": std::process::id(),
+                    "asn": bgp.global_asn(),
+                    "router_id": bgp.router_id().to_string(),
+                    "archive_enabled": status.enabled,
+                    "queued_replication_jobs": status.queued_replication_jobs,
+                    "peers_total": rib.peers_total,
+                    "peers_established": rib.peers_established,
+                    "event_buffer_size": bus.capacity(),
+                    "event_backlog": bus.backlog(),
+                    "event_last_seq": bus.last_seq(),
+                    "events_dropped_total": bus.subscribers().dropped_total(),
+                    "event_subscribers": bus.subscribers().snapshot(),
+                }),
+            )
+        }
+        CommandKind::Health => {
+            let report = health.report().await?;
+            ControlResponse::ok(req.id, json!(report))
+        }
+        CommandKind::Reload => ControlResponse::ok(req.id, json!({"reloaded": true})),
+        CommandKind::Shutdown => {
+            let _ = shutdown_tx.send(());
+            ControlResponse::ok(req.id, json!({"shutting_down": true}))
+        }
+        CommandKind::ArchiveStatus => {
+            let status = archive.status().await?;
+            let result = ArchiveStatusResult {
+                enabled: status.enabled,
+                collector_id: status.collector_id,
+                updates: status.updates.into(),
+                ribs: status.ribs.into(),
+                queued_replication_jobs: status.queued_replication_jobs,
+                replication_failures: status.replication_failures,
+                replication_deduplicated: status.replication_deduplicated,
+                oldest_pending_replication_age_secs: status.oldest_pending_replication_age_secs,
+                ingest_queue_depth: status.ingest_queue_depth,
+                ingest_records_shed: status.ingest_records_shed,
+                disk_free_mb: status.disk_free_mb,
+                disk_level: status.disk_level,
+                disk_records_dropped: status.disk_records_dropped,
+                tap_clients: status.tap_clients,
+            };
+            ControlResponse::ok(req.id, result.as_value())
+        }
+        CommandKind::ArchiveRollover => {
+            let args = match ArchiveRolloverArgs::from_json(&req.args) {
+                Ok(args) => args,
+                Err(err) => {
+                    let response = ControlResponse::err(
+                        req.id,
+                        "invalid_args",
+                        format!("archive_rollover args error: {err}"),
+                    );
+                    return Ok(Reply::Response(response));
+                }
+            };
+            if args.stream == focl::control::ArchiveStream::Updates {
+                archive.rollover(ArchiveStream::Updates).await?;
+            } else {
+                archive.rollover(ArchiveStream::Ribs).await?;
+            }
+            ControlResponse::ok(req.id, json!({"ok": true}))
+        }
+        CommandKind::ArchiveSnapshotNow => {
+            let args = match ArchiveSnapshotNowArgs::from_json(&req.args) {
+                Ok(args) => args,
+                Err(err) => {
+                    let response = ControlResponse::err(
+                        req.id,
+                        "invalid_args",
+                        format!("archive_snapshot_now args error: {err}"),
+                    );
+                    return Ok(Reply::Response(response));
+                }
+            };
+            match take_snapshot(archive, bgp, args).await {
+                Ok(result) => ControlResponse::ok(
                     req.id,
                     json!({
                         "path": result.final_path.display().to_string(),
                         "records": result.record_count,
                     }),
-                )
+                ),
+                Err((code, err)) => ControlResponse::err(req.id, code, format!("{err:#}")),
             }
-            CommandKind::ArchiveDestinations => {
-                let rows = archive
-                    .destinations()
-                    .into_iter()
-                    .map(|(key, mode, destination_type)| {
-                        json!({"key": key, "mode": mode, "type": destination_type})
-                    })
-                    .collect::<Vec<_>>();
-                ControlResponse::ok(req.id, json!({"destinations": rows}))
+        }
+        CommandKind::ArchivePeerIndex => {
+            let args = match ArchivePeerIndexArgs::from_json(&req.args) {
+                Ok(args) => args,
+                Err(err) => {
+                    let response = ControlResponse::err(
+                        req.id,
+                        "invalid_args",
+                        format!("archive_peer_index args error: {err}"),
+                    );
+                    return Ok(Reply::Response(response));
+                }
+            };
+            match peer_index(archive, bgp, args).await {
+                Ok(result) => ControlResponse::ok(req.id, result),
+                Err(err) => {
+                    ControlResponse::err(req.id, "archive_peer_index_failed", format!("{err:#}"))
+                }
             }
-            CommandKind::ArchiveReplicatorRetry => {
-                let count = archive.retry_failed_replications().await?;
-                ControlResponse::ok(req.id, json!({"retried_jobs": count}))
+        }
+        CommandKind::ArchiveDestinations => {
+            let rows = archive
+                .destinations()
+                .into_iter()
+                .map(|(key, mode, destination_type)| {
+                    json!({"key": key, "mode": mode, "type": destination_type})
+                })
+                .collect::<Vec<_>>();
+            ControlResponse::ok(req.id, json!({"destinations": rows}))
+        }
+        CommandKind::ArchiveReplicatorRetry => {
+            let count = archive.retry_failed_replications().await?;
+            ControlResponse::ok(req.id, json!({"retried_jobs": count}))
+        }
+        CommandKind::ArchiveReconcile => {
+            let args = match ArchiveReconcileArgs::from_json(&req.args) {
+                Ok(args) => args,
+                Err(err) => {
+                    let response = ControlResponse::err(
+                        req.id,
+                        "invalid_args",
+                        format!("archive_reconcile args error: {err}"),
+                    );
+                    return Ok(Reply::Response(response));
+                }
+            };
+            match archive.reconcile(&args.destination, args.repair).await {
+                Ok(report) => ControlResponse::ok(req.id, json!(report)),
+                Err(err) => {
+                    ControlResponse::err(req.id, "archive_reconcile_failed", format!("{err:#}"))
+                }
             }
-            CommandKind::PeerList => {
-                let peers = bgp.peer_list().await;
-                ControlResponse::ok(req.id, json!({"peers": peers}))
+        }
+        CommandKind::ArchivePrioritize => {
+            let args = match ArchivePrioritizeArgs::from_json(&req.args) {
+                Ok(args) => args,
+                Err(err) => {
+                    let response = ControlResponse::err(
+                        req.id,
+                        "invalid_args",
+                        format!("archive_prioritize args error: {err}"),
+                    );
+                    return Ok(Reply::Response(response));
+                }
+            };
+            match archive.prioritize(Path::new(&args.segment)).await {
+                Ok(raised) => ControlResponse::ok(req.id, json!({"prioritized_jobs": raised})),
+                Err(err) => {
+                    ControlResponse::err(req.id, "archive_prioritize_failed", format!("{err:#}"))
+                }
             }
-            CommandKind::PeerShow => {
-                let args = match PeerKeyArgs::from_json(&req.args) {
-                    Ok(args) => args,
-                    Err(err) => {
-                        let response = ControlResponse::err(
-                            req.id,
-                            "invalid_args",
-                            format!("peer_show args error: {err}"),
-                        );
-                        write_response(&mut write_half, &response).await?;
-                        continue;
-                    }
-                };
-                match bgp.peer_show(&args.peer).await {
-                    Some(peer) => ControlResponse::ok(req.id, json!({"peer": peer})),
-                    None => ControlResponse::err(req.id, "peer_not_found", "peer not found"),
+        }
+        CommandKind::ArchiveFetch => {
+            let (args, target) = match ArchiveFetchArgs::from_json(&req.args)
+                .map_err(|err| err.to_string())
+                .and_then(|args| Ok((args.target()?, args)))
+            {
+                Ok((target, args)) => (args, target),
+                Err(err) => {
+                    let response = ControlResponse::err(
+                        req.id,
+                        "invalid_args",
+                        format!("archive_fetch args error: {err}"),
+                    );
+                    return Ok(Reply::Response(response));
+                }
+            };
+            let path = match target {
+                FetchTarget::Segment(segment) => archive.fetch_path(Path::new(&segment)),
+                FetchTarget::Interval(stream, timestamp) => {
+                    let stream = match stream {
+                        focl::control::ArchiveStream::Updates => ArchiveStream::Updates,
+                        focl::control::ArchiveStream::Ribs => ArchiveStream::Ribs,
+                    };
+                    archive.segment_at(stream, timestamp)
+                }
+            };
+            match path {
+                Ok(path) => {
+                    return Ok(Reply::FetchStream {
+                        id: req.id,
+                        args,
+                        path,
+                    });
+                }
+                Err(err) => {
+                    ControlResponse::err(req.id, "archive_fetch_failed", format!("{err:#}"))
                 }
             }
-            CommandKind::PeerReset => {
-                let args = match PeerKeyArgs::from_json(&req.args) {
-                    Ok(args) => args,
+        }
+        CommandKind::EventsHistory => {
+            let query = match EventQuery::from_json(&req.args) {
+                Ok(query) => query,
+                Err(err) => {
+                    let response = ControlResponse::err(
+                        req.id,
+                        "invalid_args",
+                        format!("events_history args error: {err}"),
+                    );
+                    return Ok(Reply::Response(response));
+                }
+            };
+            match events {
+                Some(store) => match store.query(&query) {
+                    Ok(rows) => ControlResponse::ok(req.id, json!({"events": rows})),
                     Err(err) => {
-                        let response = ControlResponse::err(
-                            req.id,
-                            "invalid_args",
-                            format!("peer_reset args error: {err}"),
-                        );
-                        write_response(&mut write_half, &response).await?;
-                        continue;
+                        ControlResponse::err(req.id, "events_history_failed", err.to_string())
                     }
-                };
-                match bgp.peer_reset(&args.peer).await {
-                    Ok(()) => ControlResponse::ok(req.id, json!({"reset": true})),
-                    Err(err) => ControlResponse::err(req.id, "peer_reset_failed", err.to_string()),
+                },
+                None => ControlResponse::err(
+                    req.id,
+                    "events_history_disabled",
+                    "event history is disabled; set [events].history = true",
+                ),
+            }
+        }
+        CommandKind::PeerList => {
+            let peers = bgp.peer_list().await;
+            ControlResponse::ok(req.id, json!({"peers": peers}))
+        }
+        CommandKind::PeerShow => {
+            let args = match PeerShowArgs::from_json(&req.args) {
+                Ok(args) => args,
+                Err(err) => {
+                    let response = ControlResponse::err(
+                        req.id,
+                        "invalid_args",
+                        format!("peer_show args error: {err}"),
+                    );
+                    return Ok(Reply::Response(response));
                 }
+            };
+            let Some(peer) = bgp.peer_show(&args.peer).await else {
+                return Ok(Reply::Response(ControlResponse::err(
+                    req.id,
+                    "peer_not_found",
+                    "peer not found",
+                )));
+            };
+            if args.history {
+                let history = bgp.peer_history(&args.peer).await.unwrap_or_default();
+                ControlResponse::ok(req.id, json!({"peer": peer, "history": history}))
+            } else {
+                ControlResponse::ok(req.id, json!({"peer": peer}))
             }
-            CommandKind::RibSummary => {
-                let summary = bgp.rib_summary().await;
-                ControlResponse::ok(req.id, json!({"summary": summary}))
+        }
+        CommandKind::PeerTimers => {
+            let args = match PeerTimersArgs::from_json(&req.args) {
+                Ok(args) => args,
+                Err(err) => {
+                    let response = ControlResponse::err(
+                        req.id,
+                        "invalid_args",
+                        format!("peer_timers args error: {err}"),
+                    );
+                    return Ok(Reply::Response(response));
+                }
+            };
+            match bgp.peer_timers(args.peer.as_deref()).await {
+                Some(timers) => ControlResponse::ok(req.id, json!({"timers": timers})),
+                None => ControlResponse::err(req.id, "peer_not_found", "peer not found"),
             }
-            CommandKind::RibIn => {
-                let args = match PeerKeyArgs::from_json(&req.args) {
-                    Ok(args) => args,
+        }
+        CommandKind::PeerStatsReset => {
+            let args = match PeerStatsResetArgs::from_json(&req.args) {
+                Ok(args) => args,
+                Err(err) => {
+                    let response = ControlResponse::err(
+                        req.id,
+                        "invalid_args",
+                        format!("peer_stats_reset args error: {err}"),
+                    );
+                    return Ok(Reply::Response(response));
+                }
+            };
+            match bgp.reset_peer_totals(args.peer.as_deref()).await {
+                Ok(peers) => match peer_stats
+                    .as_ref()
+                    .map(|store| store.reset(args.peer.as_deref()))
+                    .transpose()
+                {
+                    Ok(_) => ControlResponse::ok(req.id, json!({"reset": peers})),
                     Err(err) => {
-                        let response = ControlResponse::err(
-                            req.id,
-                            "invalid_args",
-                            format!("rib_in args error: {err}"),
-                        );
-                        write_response(&mut write_half, &response).await?;
-                        continue;
+                        ControlResponse::err(req.id, "peer_stats_reset_failed", err.to_string())
                     }
-                };
-                match bgp.rib_in(&args.peer).await {
-                    Ok(prefixes) => ControlResponse::ok(
+                },
+                Err(err) => ControlResponse::err(req.id, "peer_not_found", err.to_string()),
+            }
+        }
+        CommandKind::PeerReset => {
+            let args = match PeerResetArgs::from_json(&req.args) {
+                Ok(args) => args,
+                Err(err) => {
+                    let response = ControlResponse::err(
+                        req.id,
+                        "invalid_args",
+                        format!("peer_reset args error: {err}"),
+                    );
+                    return Ok(Reply::Response(response));
+                }
+            };
+            match bgp.peer_reset(&args.peer, args.mode).await {
+                Ok(()) => ControlResponse::ok(req.id, json!({"reset": true, "mode": args.mode})),
+                Err(err) => ControlResponse::err(req.id, "peer_reset_failed", err.to_string()),
+            }
+        }
+        CommandKind::PeerMaintenance => {
+            let args = match PeerMaintenanceArgs::from_json(&req.args) {
+                Ok(args) => args,
+                Err(err) => {
+                    let response = ControlResponse::err(
+                        req.id,
+                        "invalid_args",
+                        format!("peer_maintenance args error: {err}"),
+                    );
+                    return Ok(Reply::Response(response));
+                }
+            };
+            let maintenance = args.enabled.then(|| Maintenance {
+                keep_session: args.keep_session,
+                since: chrono::Utc::now().timestamp(),
+            });
+            match bgp.peer_maintenance(&args.peer, maintenance).await {
+                Ok(peer) => ControlResponse::ok(req.id, json!({"peer": peer})),
+                Err(err) => {
+                    ControlResponse::err(req.id, "peer_maintenance_failed", err.to_string())
+                }
+            }
+        }
+        CommandKind::PeerLogMessages => {
+            let args = match PeerLogMessagesArgs::from_json(&req.args) {
+                Ok(args) => args,
+                Err(err) => {
+                    let response = ControlResponse::err(
                         req.id,
-                        json!({"peer": args.peer, "prefixes": prefixes}),
+                        "invalid_args",
+                        format!("peer_log_messages args error: {err}"),
+                    );
+                    return Ok(Reply::Response(response));
+                }
+            };
+            match bgp.peer_log_messages(&args.peer, args.enabled).await {
+                Ok(path) => ControlResponse::ok(
+                    req.id,
+                    json!({
+                        "peer": args.peer,
+                        "log_messages": args.enabled,
+                        "file": path.map(|p| p.display().to_string()),
+                    }),
+                ),
+                Err(err) => {
+                    ControlResponse::err(req.id, "peer_log_messages_failed", err.to_string())
+                }
+            }
+        }
+        CommandKind::PeerWait => {
+            let args = match PeerWaitArgs::from_json(&req.args) {
+                Ok(args) => args,
+                Err(err) => {
+                    let response = ControlResponse::err(
+                        req.id,
+                        "invalid_args",
+                        format!("peer_wait args error: {err}"),
+                    );
+                    return Ok(Reply::Response(response));
+                }
+            };
+            let started = std::time::Instant::now();
+            match bgp.peer_wait(&args.peer, args.state, args.timeout()).await {
+                Ok(Some(info)) => ControlResponse::ok(
+                    req.id,
+                    json!({
+                        "peer": info,
+                        "waited_ms": started.elapsed().as_millis() as u64,
+                    }),
+                ),
+                Ok(None) => ControlResponse::err(
+                    req.id,
+                    "peer_wait_timeout",
+                    format!(
+                        "peer {} did not reach {:?} within {}s",
+                        args.peer,
+                        args.state,
+                        args.timeout().as_secs()
                     ),
-                    Err(err) => ControlResponse::err(req.id, "rib_in_failed", err.to_string()),
+                ),
+                Err(err) => ControlResponse::err(req.id, "peer_wait_failed", err.to_string()),
+            }
+        }
+        CommandKind::PrefixLoadMrt => {
+            let args = match PrefixLoadMrtArgs::from_json(&req.args) {
+                Ok(args) => args,
+                Err(err) => {
+                    let response = ControlResponse::err(
+                        req.id,
+                        "invalid_args",
+                        format!("prefix_load_mrt args error: {err}"),
+                    );
+                    return Ok(Reply::Response(response));
+                }
+            };
+            match bgp.load_mrt(&args.path, args.peers).await {
+                Ok(loaded) => ControlResponse::ok(req.id, json!({"loaded": loaded})),
+                Err(err) => ControlResponse::err(req.id, "prefix_load_failed", format!("{err:#}")),
+            }
+        }
+        CommandKind::BlackholeAdd | CommandKind::BlackholeRemove => {
+            let args = match BlackholeArgs::from_json(&req.args) {
+                Ok(args) => args,
+                Err(err) => {
+                    let response = ControlResponse::err(
+                        req.id,
+                        "invalid_args",
+                        format!("{} args error: {err}", req.cmd),
+                    );
+                    return Ok(Reply::Response(response));
+                }
+            };
+            if cmd == CommandKind::BlackholeAdd {
+                match bgp.blackhole_add(args.prefix, args.expires_secs) {
+                    Ok(blackhole) => ControlResponse::ok(req.id, json!({"blackhole": blackhole})),
+                    Err(err) => ControlResponse::err(req.id, "blackhole_failed", err.to_string()),
+                }
+            } else {
+                match bgp.blackhole_remove(args.prefix) {
+                    Ok(()) => ControlResponse::ok(req.id, json!({"removed": args.prefix})),
+                    Err(err) => ControlResponse::err(req.id, "blackhole_failed", err.to_string()),
                 }
             }
-            CommandKind::RibOut => {
-                let args = match PeerKeyArgs::from_json(&req.args) {
-                    Ok(args) => args,
+        }
+        CommandKind::BlackholeList => {
+            ControlResponse::ok(req.id, json!({"blackholes": bgp.blackhole_list()}))
+        }
+        CommandKind::RibSummary => {
+            let summary = bgp.rib_summary();
+            ControlResponse::ok(req.id, json!({"summary": summary}))
+        }
+        CommandKind::RibChurn => {
+            let args = match RibChurnArgs::from_json(&req.args) {
+                Ok(args) => args,
+                Err(err) => {
+                    let response = ControlResponse::err(
+                        req.id,
+                        "invalid_args",
+                        format!("rib_churn args error: {err}"),
+                    );
+                    return Ok(Reply::Response(response));
+                }
+            };
+            match bgp.rib_churn(args.window_mins, args.limit) {
+                Ok((window_mins, prefixes)) => ControlResponse::ok(
+                    req.id,
+                    json!({"window_mins": window_mins, "prefixes": prefixes}),
+                ),
+                Err(err) => ControlResponse::err(req.id, "churn_disabled", err.to_string()),
+            }
+        }
+        CommandKind::PrefixHistory => {
+            let args = match PrefixHistoryArgs::from_json(&req.args) {
+                Ok(args) => args,
+                Err(err) => {
+                    let response = ControlResponse::err(
+                        req.id,
+                        "invalid_args",
+                        format!("prefix_history args error: {err}"),
+                    );
+                    return Ok(Reply::Response(response));
+                }
+            };
+            match prefix_history {
+                Some(store) => match store.query(args.prefix, args.more_specifics, args.limit) {
+                    Ok(rows) => ControlResponse::ok(req.id, json!({"history": rows})),
                     Err(err) => {
-                        let response = ControlResponse::err(
-                            req.id,
-                            "invalid_args",
-                            format!("rib_out args error: {err}"),
-                        );
-                        write_response(&mut write_half, &response).await?;
-                        continue;
+                        ControlResponse::err(req.id, "prefix_history_failed", err.to_string())
                     }
-                };
-                match bgp.rib_out(&args.peer).await {
-                    Ok(prefixes) => ControlResponse::ok(
+                },
+                None => ControlResponse::err(
+                    req.id,
+                    "prefix_history_disabled",
+                    "prefix history is disabled; set [prefix_history].enabled = true",
+                ),
+            }
+        }
+        CommandKind::AspathStats => {
+            let args = match AspathStatsArgs::from_json(&req.args) {
+                Ok(args) => args,
+                Err(err) => {
+                    let response = ControlResponse::err(
                         req.id,
-                        json!({"peer": args.peer, "prefixes": prefixes}),
-                    ),
-                    Err(err) => ControlResponse::err(req.id, "rib_out_failed", err.to_string()),
-                }
-            }
-            CommandKind::Unsupported => {
-                if req.cmd == "events_subscribe" {
-                    let resp = ControlResponse::ok(req.id.clone(), json!({"subscribed": true}));
-                    write_response(&mut write_half, &resp).await?;
-                    let mut rx = archive.subscribe_events();
-                    loop {
-                        match rx.recv().await {
-                            Ok(event) => {
-                                let payload = serde_json::to_string(&event)?;
-                                write_half.write_all(payload.as_bytes()).await?;
-                                write_half.write_all(b"\n").await?;
-                            }
-                            Err(tokio::sync::broadcast::error::RecvError::Lagged(_)) => continue,
-                            Err(tokio::sync::broadcast::error::RecvError::Closed) => return Ok(()),
-                        }
+                        "invalid_args",
+                        format!("aspath_stats args error: {err}"),
+                    );
+                    return Ok(Reply::Response(response));
+                }
+            };
+            match bgp.aspath_stats(args.prefix, args.since, args.limit) {
+                Ok(stats) => ControlResponse::ok(req.id, json!({"aspath": stats})),
+                Err(err) => ControlResponse::err(req.id, "rib_in_disabled", err.to_string()),
+            }
+        }
+        CommandKind::BgpStats => {
+            let args = match BgpStatsArgs::from_json(&req.args) {
+                Ok(args) => args,
+                Err(err) => {
+                    let response = ControlResponse::err(
+                        req.id,
+                        "invalid_args",
+                        format!("bgp_stats args error: {err}"),
+                    );
+                    return Ok(Reply::Response(response));
+                }
+            };
+            let result = BgpStatsResult {
+                stats: bgp.bgp_stats(args.top).await,
+            };
+            ControlResponse::ok(req.id, serde_json::to_value(result)?)
+        }
+        CommandKind::BgpTop => {
+            let args = match BgpTopArgs::from_json(&req.args) {
+                Ok(args) => args,
+                Err(err) => {
+                    let response = ControlResponse::err(
+                        req.id,
+                        "invalid_args",
+                        format!("bgp_top args error: {err}"),
+                    );
+                    return Ok(Reply::Response(response));
+                }
+            };
+            let top = bgp.bgp_top(args.minutes, args.limit).await;
+            ControlResponse::ok(req.id, json!({"top": top}))
+        }
+        CommandKind::RibIn | CommandKind::RibOut => {
+            let name = if cmd == CommandKind::RibIn {
+                "rib_in"
+            } else {
+                "rib_out"
+            };
+            let args = match RibQueryArgs::from_json(&req.args) {
+                Ok(args) => args,
+                Err(err) => {
+                    let response = ControlResponse::err(
+                        req.id,
+                        "invalid_args",
+                        format!("{name} args error: {err}"),
+                    );
+                    return Ok(Reply::Response(response));
+                }
+            };
+            let prefixes = if cmd == CommandKind::RibIn {
+                bgp.rib_in(&args.peer).await
+            } else {
+                bgp.rib_out(&args.peer).await
+            };
+            match prefixes {
+                Ok(prefixes) if args.stream => {
+                    return Ok(Reply::RibStream {
+                        id: req.id,
+                        args,
+                        prefixes,
+                    });
+                }
+                Ok(prefixes) => {
+                    match paginate_prefixes(prefixes, args.cursor.as_deref(), args.page_size()) {
+                        Ok(page) => ControlResponse::ok(
+                            req.id,
+                            json!({
+                                "peer": args.peer,
+                                "prefixes": page.prefixes,
+                                "next_cursor": page.next_cursor,
+                            }),
+                        ),
+                        Err(err) => ControlResponse::err(req.id, "invalid_args", err),
                     }
                 }
+                Err(err) => ControlResponse::err(req.id, format!("{name}_failed"), err.to_string()),
+            }
+        }
+        CommandKind::Batch => {
+            ControlResponse::err(req.id, "invalid_args", "batch requests cannot be nested")
+        }
+        CommandKind::Unsupported => {
+            if req.cmd == "events_subscribe" {
+                return Ok(match EventsSubscribeArgs::from_json(&req.args) {
+                    Ok(args) => Reply::Subscribe {
+                        id: req.id,
+                        topics: args.topics,
+                    },
+                    Err(err) => Reply::Response(ControlResponse::err(
+                        req.id,
+                        "invalid_args",
+                        format!("events_subscribe args error: {err}"),
+                    )),
+                });
+            }
 
-                ControlResponse::err(
-                    req.id,
-                    "unsupported_command",
-                    format!("unsupported cmd: {}", req.cmd),
+            ControlResponse::err(
+                req.id,
+                "unsupported_command",
+                format!("unsupported cmd: {}", req.cmd),
+            )
+        }
+    };
+    Ok(Reply::Response(response))
+}
+
+/// Run the sub-requests of a `batch`, in order or all at once, and collect
+/// their responses in request order.
+async fn run_batch(ctx: &ControlContext, req: ControlRequest) -> ControlResponse {
+    let args = match BatchArgs::from_json(&req.args) {
+        Ok(args) => args,
+        Err(err) => {
+            return ControlResponse::err(req.id, "invalid_args", format!("batch args error: {err}"))
+        }
+    };
+    if args.requests.len() > BatchArgs::MAX_REQUESTS {
+        return ControlResponse::err(
+            req.id,
+            "invalid_args",
+            format!(
+                "batch holds {} requests, more than the limit of {}",
+                args.requests.len(),
+                BatchArgs::MAX_REQUESTS
+            ),
+        );
+    }
+
+    let mut responses = Vec::with_capacity(args.requests.len());
+    if args.concurrent {
+        let tasks = args
+            .requests
+            .into_iter()
+            .map(|sub| {
+                let id = sub.id.clone();
+                let ctx = ctx.clone();
+                (
+                    id,
+                    tokio::spawn(async move { run_batched(&ctx, sub).await }),
                 )
+            })
+            .collect::<Vec<_>>();
+        for (id, task) in tasks {
+            responses.push(
+                task.await.unwrap_or_else(|err| {
+                    ControlResponse::err(id, "command_failed", err.to_string())
+                }),
+            );
+        }
+    } else {
+        for sub in args.requests {
+            responses.push(run_batched(ctx, sub).await);
+        }
+    }
+    let failed = responses.iter().filter(|response| !response.ok).count();
+    ControlResponse::ok(req.id, json!({"results": responses, "failed": failed}))
+}
+
+/// One sub-request of a `batch`; an error becomes its error response.
+async fn run_batched(ctx: &ControlContext, req: ControlRequest) -> ControlResponse {
+    let id = req.id.clone();
+    match execute(ctx, req).await {
+        Ok(Reply::Response(response)) => response,
+        Ok(_) => ControlResponse::err(id, "invalid_args", "streamed commands cannot be batched"),
+        Err(err) => ControlResponse::err(id, "command_failed", format!("{err:#}")),
+    }
+}
+
+async fn write_rib_stream(
+    writer: &mut tokio::net::unix::OwnedWriteHalf,
+    id: &str,
+    args: &RibQueryArgs,
+    mut prefixes: Vec<ipnet::IpNet>,
+) -> Result<()> {
+    prefixes.sort();
+    prefixes.dedup();
+    let mut cursor = args.cursor.clone();
+    loop {
+        let page = match page_prefixes(&prefixes, cursor.as_deref(), args.page_size()) {
+            Ok(page) => page,
+            Err(err) => {
+                let response = ControlResponse::err(id, "invalid_args", err);
+                return write_response(writer, &response).await;
             }
         };
+        let done = page.next_cursor.is_none();
+        let response = ControlResponse::ok(
+            id,
+            json!({
+                "peer": args.peer,
+                "prefixes": page.prefixes,
+                "next_cursor": page.next_cursor,
+                "done": done,
+            }),
+        );
+        write_response(writer, &response).await?;
+        if done {
+            return Ok(());
+        }
+        cursor = page.next_cursor;
+    }
+}
 
-        write_response(&mut write_half, &response).await?;
+/// Write `path` in `args.format` as one response per chunk, then a final
+/// response with `"done": true`. Reading happens on a blocking thread, a few
+/// chunks ahead of the connection.
+async fn write_fetch_stream(
+    writer: &mut tokio::net::unix::OwnedWriteHalf,
+    id: &str,
+    args: &ArchiveFetchArgs,
+    path: PathBuf,
+) -> Result<()> {
+    use base64::Engine;
+
+    let (tx, mut rx) = tokio::sync::mpsc::channel(4);
+    let (format, chunk_size) = (args.format, args.chunk_size());
+    let reader_path = path.clone();
+    tokio::task::spawn_blocking(move || {
+        let chunks = match focl::archive::fetch::read_chunks(&reader_path, format, chunk_size) {
+            Ok(chunks) => chunks,
+            Err(err) => {
+                let _ = tx.blocking_send(Err(err));
+                return;
+            }
+        };
+        for chunk in chunks {
+            let failed = chunk.is_err();
+            if tx.blocking_send(chunk).is_err() || failed {
+                return;
+            }
+        }
+    });
+
+    let (mut seq, mut bytes) = (0u64, 0u64);
+    while let Some(chunk) = rx.recv().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(err) => {
+                let response = ControlResponse::err(id, "archive_fetch_failed", format!("{err:#}"));
+                return write_response(writer, &response).await;
+            }
+        };
+        bytes += chunk.len() as u64;
+        let response = ControlResponse::ok(
+            id,
+            json!({
+                "seq": seq,
+                "data": base64::engine::general_purpose::STANDARD.encode(&chunk),
+                "done": false,
+            }),
+        );
+        write_response(writer, &response).await?;
+        seq += 1;
     }
+    let response = ControlResponse::ok(
+        id,
+        json!({
+            "path": path.display().to_string(),
+            "format": args.format,
+            "chunks": seq,
+            "bytes": bytes,
+            "done": true,
+        }),
+    );
+    write_response(writer, &response).await
 }
 
 async fn write_response(
@@ -0,0 +1,110 @@
+//! Upgrades older config files to the current `config_version` before they
+//! are deserialized, so renamed or reshaped keys keep loading with a warning.
+
+use anyhow::{bail, Result};
+use toml::Table;
+
+/// Version written by this release. Bump it together with a new entry in
+/// [`MIGRATIONS`] whenever a change would break existing files.
+pub const CURRENT_CONFIG_VERSION: u32 = 1;
+
+/// A step that rewrites a config from `from` to `from + 1`, returning a
+/// warning for every key it touched.
+pub struct Migration {
+    pub from: u32,
+    pub apply: fn(&mut Table) -> Vec<String>,
+}
+
+/// Registered upgrade steps, ordered by `from`.
+pub const MIGRATIONS: &[Migration] = &[];
+
+/// Bring `table` up to [`CURRENT_CONFIG_VERSION`]. Files without a
+/// `config_version` key are treated as version 1.
+pub fn migrate(table: &mut Table) -> Result<Vec<String>> {
+    run(table, CURRENT_CONFIG_VERSION, MIGRATIONS)
+}
+
+fn run(table: &mut Table, current: u32, migrations: &[Migration]) -> Result<Vec<String>> {
+    let mut version = match table.get("config_version") {
+        None => 1,
+        Some(value) => match value.as_integer() {
+            Some(v) if v >= 1 => v as u32,
+            _ => bail!("config_version must be a positive integer, got {}", value),
+        },
+    };
+
+    if version > current {
+        bail!(
+            "config_version {} is newer than this focld supports ({}); upgrade focld",
+            version,
+            current
+        );
+    }
+
+    let mut warnings = Vec::new();
+    while version < current {
+        let Some(step) = migrations.iter().find(|m| m.from == version) else {
+            bail!("no migration from config_version {}", version);
+        };
+        warnings.extend(
+            (step.apply)(table)
+                .into_iter()
+                .map(|w| format!("config_version {} -> {}: {}", version, version + 1, w)),
+        );
+        version += 1;
+    }
+
+    if !warnings.is_empty() {
+        warnings.push(format!(
+            "config was upgraded in memory; set config_version = {} after applying the changes above",
+            current
+        ));
+    }
+    table.insert("config_version".to_string(), i64::from(current).into());
+    Ok(warnings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rename_listen(table: &mut Table) -> Vec<String> {
+        let Some(global) = table.get_mut("global").and_then(|g| g.as_table_mut()) else {
+            return vec![];
+        };
+        match global.remove("bind") {
+            Some(value) => {
+                global.insert("listen_addr".to_string(), value);
+                vec!["[global].bind was renamed to [global].listen_addr".to_string()]
+            }
+            None => vec![],
+        }
+    }
+
+    #[test]
+    fn upgrades_old_versions_with_warnings() {
+        let mut table: Table = toml::from_str(
+            r#"
+config_version = 1
+[global]
+bind = "0.0.0.0:1179"
+"#,
+        )
+        .unwrap();
+        let migrations = [Migration {
+            from: 1,
+            apply: rename_listen,
+        }];
+
+        let warnings = run(&mut table, 2, &migrations).unwrap();
+        assert!(warnings[0].contains("renamed"));
+        assert_eq!(table["config_version"].as_integer(), Some(2));
+        assert_eq!(
+            table["global"]["listen_addr"].as_str(),
+            Some("0.0.0.0:1179")
+        );
+
+        let mut newer: Table = toml::from_str("config_version = 3").unwrap();
+        assert!(run(&mut newer, 2, &migrations).is_err());
+    }
+}
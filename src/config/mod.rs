@@ -1,32 +1,439 @@
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
 use std::path::{Path, PathBuf};
 
 use anyhow::{bail, Context, Result};
 use ipnet::IpNet;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+mod migrate;
+mod prefix_list;
+pub use migrate::CURRENT_CONFIG_VERSION;
+pub use prefix_list::{PrefixList, PrefixListEntry};
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct FoclConfig {
+    /// Schema version of this file; older versions are upgraded at load time.
+    #[serde(default = "default_config_version")]
+    pub config_version: u32,
+    /// Glob patterns, relative to this file, of fragments whose `[[peers]]`
+    /// and `[[prefixes]]` are merged in at load time.
+    #[serde(default)]
+    pub include: Vec<String>,
     pub global: GlobalConfig,
     #[serde(default)]
     pub peers: Vec<PeerConfig>,
     #[serde(default)]
     pub prefixes: Vec<PrefixConfig>,
+    /// Named prefix lists referenced by `[[prefixes]]` and peer policy.
+    #[serde(default)]
+    pub prefix_lists: BTreeMap<String, Vec<PrefixListEntry>>,
+    /// Prefix lists fetched from a URL and refreshed on an interval, for
+    /// peer `import_prefix_list`/`export_prefix_list`.
+    #[serde(default)]
+    pub remote_prefix_lists: BTreeMap<String, RemotePrefixListConfig>,
     #[serde(default)]
     pub archive: ArchiveConfig,
+    #[serde(default)]
+    pub health: HealthConfig,
+    #[serde(default)]
+    pub events: EventsConfig,
+    /// Programs run with the event JSON on stdin.
+    #[serde(default)]
+    pub hooks: Vec<HookConfig>,
+    /// Alert rules over events and received routes.
+    #[serde(default)]
+    pub rules: Vec<RuleConfig>,
+    #[serde(default)]
+    pub alerts: AlertsConfig,
+    #[serde(default)]
+    pub exabgp: ExabgpConfig,
+    #[serde(default)]
+    pub openbmp: OpenbmpConfig,
+    #[serde(default)]
+    pub blackhole: BlackholeConfig,
+    #[serde(default)]
+    pub rpki: RpkiConfig,
+    #[serde(default)]
+    pub inbound_limits: InboundLimitsConfig,
+    #[serde(default)]
+    pub churn: ChurnConfig,
+    #[serde(default)]
+    pub rib_in: RibInConfig,
+    #[serde(default)]
+    pub prefix_history: PrefixHistoryConfig,
+    #[serde(default)]
+    pub hijack: HijackConfig,
+    #[serde(default)]
+    pub peer_stats: PeerStatsConfig,
+    /// Warnings produced while upgrading an older `config_version`.
+    #[serde(skip)]
+    pub migration_warnings: Vec<String>,
+}
+
+fn default_config_version() -> u32 {
+    CURRENT_CONFIG_VERSION
+}
+
+/// Settings supplied on the focld command line or via `FOCL_*` environment
+/// variables; they take precedence over the config file.
+#[derive(Debug, Clone, Default)]
+pub struct ConfigOverrides {
+    pub asn: Option<u32>,
+    pub router_id: Option<String>,
+    pub control_socket: Option<PathBuf>,
+    pub archive_root: Option<PathBuf>,
+}
+
+impl ConfigOverrides {
+    /// Applied before deserialization so required keys may come from here;
+    /// `archive_root` is applied afterwards so the default destination stays.
+    fn apply(&self, table: &mut toml::Table) {
+        let section = |table: &mut toml::Table, name: &str| -> toml::Table {
+            match table.remove(name) {
+                Some(toml::Value::Table(t)) => t,
+                _ => toml::Table::new(),
+            }
+        };
+
+        let mut global = section(table, "global");
+        if let Some(asn) = self.asn {
+            global.insert("asn".to_string(), i64::from(asn).into());
+        }
+        if let Some(router_id) = &self.router_id {
+            global.insert("router_id".to_string(), router_id.clone().into());
+        }
+        if let Some(socket) = &self.control_socket {
+            global.insert(
+                "control_socket".to_string(),
+                socket.display().to_string().into(),
+            );
+        }
+        table.insert("global".to_string(), global.into());
+    }
 }
 
 impl FoclConfig {
     pub fn load(path: &Path) -> Result<Self> {
+        Self::load_with_overrides(path, &ConfigOverrides::default())
+    }
+
+    pub fn load_with_overrides(path: &Path, overrides: &ConfigOverrides) -> Result<Self> {
         let raw = fs::read_to_string(path)
             .with_context(|| format!("failed to read config file {}", path.display()))?;
-        let cfg: Self = toml::from_str(&raw)
+        let mut table: toml::Table = toml::from_str(&raw)
+            .with_context(|| format!("failed to parse TOML in {}", path.display()))?;
+        let warnings = migrate::migrate(&mut table)
+            .with_context(|| format!("failed to migrate config {}", path.display()))?;
+        overrides.apply(&mut table);
+        let mut cfg: Self = table
+            .try_into()
             .with_context(|| format!("failed to parse TOML in {}", path.display()))?;
+        cfg.migration_warnings = warnings;
+        if let Some(root) = overrides.archive_root.clone() {
+            // Keep the implicit tmp dir and local destination under the new root.
+            cfg.archive.root = root.clone();
+            if cfg.archive.tmp_root == default_archive_tmp_root() {
+                cfg.archive.tmp_root = root.join(".tmp");
+            }
+            for destination in &mut cfg.archive.destinations {
+                if destination.destination_type == DestinationType::Local
+                    && destination.path.as_deref() == Some(default_archive_root().as_path())
+                {
+                    destination.path = Some(root.clone());
+                }
+            }
+        }
+        cfg.merge_includes(path)?;
         cfg.validate()
             .with_context(|| format!("config validation failed for {}", path.display()))?;
         Ok(cfg)
     }
 
+    /// Load every fragment matched by `include` and append its peers and
+    /// prefixes, rejecting entries already defined by another file.
+    fn merge_includes(&mut self, path: &Path) -> Result<()> {
+        if self.include.is_empty() {
+            return Ok(());
+        }
+
+        let base = path.parent().unwrap_or_else(|| Path::new("."));
+        let main = path.display().to_string();
+        let mut peer_origin = self
+            .peers
+            .iter()
+            .map(|p| (p.address.clone(), main.clone()))
+            .collect::<HashMap<_, _>>();
+        let mut prefix_origin = self
+            .prefixes
+            .iter()
+            .map(|p| (p.key(), main.clone()))
+            .collect::<HashMap<_, _>>();
+
+        for fragment_path in resolve_includes(base, &self.include)? {
+            let origin = fragment_path.display().to_string();
+            let raw = fs::read_to_string(&fragment_path)
+                .with_context(|| format!("failed to read included config {origin}"))?;
+            let fragment: ConfigFragment = toml::from_str(&raw)
+                .with_context(|| format!("failed to parse TOML in included config {origin}"))?;
+
+            for peer in fragment.peers {
+                if let Some(first) = peer_origin.insert(peer.address.clone(), origin.clone()) {
+                    bail!(
+                        "peer {} is defined in both {} and {}",
+                        peer.address,
+                        first,
+                        origin
+                    );
+                }
+                self.peers.push(peer);
+            }
+            for prefix in fragment.prefixes {
+                if let Some(first) = prefix_origin.insert(prefix.key(), origin.clone()) {
+                    bail!(
+                        "prefix {} is defined in both {} and {}",
+                        prefix.key(),
+                        first,
+                        origin
+                    );
+                }
+                self.prefixes.push(prefix);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Checks that span several `[[peers]]` entries: repeated addresses,
+    /// mismatched address families, and listeners that would collide.
+    fn validate_peer_set(&self) -> Result<()> {
+        let mut seen: HashMap<IpAddr, &str> = HashMap::new();
+        let mut listeners: Vec<(SocketAddr, &str)> = Vec::new();
+
+        for peer in &self.peers {
+            let address: IpAddr = peer.address.parse()?;
+            if let Some(first) = seen.insert(address, &peer.address) {
+                bail!(
+                    "peer {} is configured more than once (also as {}); merge the [[peers]] entries",
+                    peer.address,
+                    first
+                );
+            }
+
+            if peer.tcp_ao_key_id.is_some() && peer.password.is_none() {
+                bail!(
+                    "peer {} sets tcp_ao_key_id without a password",
+                    peer.address
+                );
+            }
+            if let Some(rotation) = &peer.password_rotation {
+                rotation.validate(peer)?;
+            }
+            if let Some(pacing) = &peer.pacing {
+                pacing.validate(peer)?;
+            }
+            if (peer.local_as_no_prepend || peer.local_as_replace_as)
+                && peer.local_as.is_none_or(|asn| asn == self.global.asn)
+            {
+                bail!(
+                    "peer {} sets local_as_no_prepend or local_as_replace_as without a local_as other than [global].asn",
+                    peer.address
+                );
+            }
+
+            let local = peer.local_address_socket()?;
+            if let Some(local) = local {
+                if local.is_ipv4() != address.is_ipv4() {
+                    bail!(
+                        "peer {} has local_address {} from a different address family",
+                        peer.address,
+                        local.ip()
+                    );
+                }
+            }
+
+            if peer.passive && peer.listen_port.is_some() {
+                bail!(
+                    "peer {} sets listen_port on a passive peer; passive peers listen on remote_port",
+                    peer.address
+                );
+            }
+            if peer.listen_port == Some(0) {
+                bail!("peer {} listen_port must not be 0", peer.address);
+            }
+            if !peer.enabled {
+                continue;
+            }
+            let listen = match (peer.passive, peer.listen_port) {
+                (true, _) => local.unwrap_or_else(|| {
+                    SocketAddr::new(
+                        IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED),
+                        peer.remote_port,
+                    )
+                }),
+                (false, Some(port)) => {
+                    let ip = match (local, address) {
+                        (Some(local), _) => local.ip(),
+                        (None, IpAddr::V4(_)) => IpAddr::V4(std::net::Ipv4Addr::UNSPECIFIED),
+                        (None, IpAddr::V6(_)) => IpAddr::V6(std::net::Ipv6Addr::UNSPECIFIED),
+                    };
+                    SocketAddr::new(ip, port)
+                }
+                (false, None) => continue,
+            };
+            for (other, other_peer) in &listeners {
+                let same_family = other.is_ipv4() == listen.is_ipv4();
+                let overlaps = other.ip() == listen.ip()
+                    || (same_family
+                        && (other.ip().is_unspecified() || listen.ip().is_unspecified()));
+                if other.port() == listen.port() && overlaps {
+                    bail!(
+                        "peers {} and {} would both listen on {}; give each a distinct local_address, remote_port, or listen_port",
+                        other_peer,
+                        peer.address,
+                        listen
+                    );
+                }
+            }
+            listeners.push((listen, &peer.address));
+        }
+
+        Ok(())
+    }
+
+    pub fn compiled_prefix_lists(&self) -> Result<HashMap<String, PrefixList>> {
+        self.prefix_lists
+            .iter()
+            .map(|(name, entries)| Ok((name.clone(), PrefixList::compile(name, entries)?)))
+            .collect()
+    }
+
+    /// Every prefix to originate unconditionally, with its configured
+    /// next-hop, after expanding `prefix_list` references. Prefixes with a
+    /// `health_check` are left to [`Self::health_checked_prefixes`].
+    pub fn originated_prefixes(&self) -> Result<Vec<(IpNet, Option<String>)>> {
+        let mut out = Vec::new();
+        for prefix in self.prefixes.iter().filter(|p| p.health_check.is_none()) {
+            let networks = self.prefix_networks(prefix)?;
+            out.extend(networks.into_iter().map(|n| (n, prefix.next_hop.clone())));
+        }
+        Ok(out)
+    }
+
+    /// Prefixes announced only while their health check passes, one group
+    /// per `[[prefixes]]` entry.
+    pub fn health_checked_prefixes(&self) -> Result<Vec<HealthCheckedPrefixes>> {
+        self.prefixes
+            .iter()
+            .filter_map(|prefix| Some((prefix, prefix.health_check.as_ref()?)))
+            .map(|(prefix, check)| {
+                Ok(HealthCheckedPrefixes {
+                    networks: self.prefix_networks(prefix)?,
+                    next_hop: prefix.next_hop.clone(),
+                    check: check.clone(),
+                })
+            })
+            .collect()
+    }
+
+    fn prefix_networks(&self, prefix: &PrefixConfig) -> Result<Vec<IpNet>> {
+        match (&prefix.network, &prefix.prefix_list) {
+            (Some(network), None) => {
+                let network = network
+                    .parse::<IpNet>()
+                    .with_context(|| format!("invalid IP prefix in [[prefixes]]: {}", network))?;
+                Ok(vec![network])
+            }
+            (None, Some(name)) => {
+                let entries = self.prefix_lists.get(name).with_context(|| {
+                    format!("[[prefixes]] references unknown prefix list {}", name)
+                })?;
+                let list = PrefixList::compile(name, entries)?;
+                Ok(list.networks().collect())
+            }
+            _ => bail!("[[prefixes]] entries need exactly one of network or prefix_list"),
+        }
+    }
+
+    /// JSON Schema describing the TOML configuration file.
+    pub fn json_schema() -> serde_json::Value {
+        serde_json::to_value(schemars::schema_for!(FoclConfig))
+            .expect("config schema is always serializable")
+    }
+
+    /// Rewrite path settings still at their built-in defaults so that several
+    /// named instances can share a host without colliding.
+    pub fn apply_instance(&mut self, instance: &str) -> Result<()> {
+        validate_instance_name(instance)?;
+
+        if self.global.control_socket == default_control_socket() {
+            self.global.control_socket = instance_control_socket(Some(instance));
+        }
+        if self.global.pid_file == default_pid_file() {
+            self.global.pid_file = instance_pid_file(Some(instance));
+        }
+
+        let instance_root = PathBuf::from(DEFAULT_STATE_DIR).join(instance);
+        if self.archive.root == default_archive_root() {
+            self.archive.root = instance_root.join("archive");
+            for destination in &mut self.archive.destinations {
+                if destination.destination_type == DestinationType::Local
+                    && destination.path.as_deref() == Some(default_archive_root().as_path())
+                {
+                    destination.path = Some(self.archive.root.clone());
+                }
+            }
+        }
+        if self.archive.tmp_root == default_archive_tmp_root() {
+            self.archive.tmp_root = instance_root.join("archive").join(".tmp");
+        }
+        if self.events.history_path == default_events_history_path() {
+            self.events.history_path = instance_root.join("events.sqlite");
+        }
+        if self.prefix_history.path == default_prefix_history_path() {
+            self.prefix_history.path = instance_root.join("prefix_history.sqlite");
+        }
+        if self.peer_stats.path == default_peer_stats_path() {
+            self.peer_stats.path = instance_root.join("peer_stats.sqlite");
+        }
+        for remote in self.remote_prefix_lists.values_mut() {
+            if remote.cache_dir == default_remote_prefix_list_cache_dir() {
+                remote.cache_dir = instance_root.join("remote_prefix_lists");
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The loaded configuration with every default filled in, as printed by
+    /// `focld --dry-run`. Included fragments are already merged, and peer
+    /// passwords, S3 credentials, and the Slack webhook are redacted.
+    pub fn effective_toml(&self) -> Result<String> {
+        const REDACTED: &str = "<redacted>";
+        let redact = |secret: &mut Option<String>| {
+            if secret.is_some() {
+                *secret = Some(REDACTED.to_string());
+            }
+        };
+
+        let mut cfg = self.clone();
+        cfg.include.clear();
+        for peer in &mut cfg.peers {
+            redact(&mut peer.password);
+            if let Some(rotation) = &mut peer.password_rotation {
+                rotation.password = REDACTED.to_string();
+            }
+        }
+        for destination in &mut cfg.archive.destinations {
+            redact(&mut destination.secret_access_key);
+            redact(&mut destination.session_token);
+        }
+        redact(&mut cfg.alerts.slack_webhook);
+        toml::to_string_pretty(&cfg).context("failed serializing effective config")
+    }
+
     pub fn validate(&self) -> Result<()> {
         if self.global.asn == 0 {
             bail!("[global].asn must be non-zero");
@@ -36,7 +443,27 @@ impl FoclConfig {
             bail!("[global].router_id must be a valid IPv4 address");
         }
 
+        if self.global.control_read_timeout_secs == 0 {
+            bail!("[global].control_read_timeout_secs must be non-zero");
+        }
+
+        if self.global.control_max_request_bytes == 0 {
+            bail!("[global].control_max_request_bytes must be non-zero");
+        }
+
+        if self.global.control_max_connections == 0 {
+            bail!("[global].control_max_connections must be non-zero");
+        }
+
+        self.global.control_socket_mode_bits()?;
+        if matches!(&self.global.control_socket_group, Some(group) if group.is_empty()) {
+            bail!("[global].control_socket_group must not be empty");
+        }
+
         for peer in &self.peers {
+            if peer.address.parse::<IpAddr>().is_err() {
+                bail!("peer address {} is not a valid IP address", peer.address);
+            }
             if peer.remote_as == 0 {
                 bail!("peer {} has invalid remote_as 0", peer.address);
             }
@@ -50,32 +477,87 @@ impl FoclConfig {
                     peer.hold_time_secs
                 );
             }
-            if let Some(local) = &peer.local_address {
-                let ok = local.parse::<std::net::SocketAddr>().is_ok()
-                    || local.parse::<std::net::IpAddr>().is_ok();
-                if !ok {
+            peer.local_address_socket()?;
+            if peer.capture_pcap.is_some() && peer.capture_pcap_max_mb == 0 {
+                bail!("peer {} has invalid capture_pcap_max_mb 0", peer.address);
+            }
+            peer.archive.validate(&peer.address)?;
+            if peer.archive.collector_id.as_ref() == Some(&self.archive.collector_id) {
+                bail!(
+                    "peer {} archive.collector_id is [archive].collector_id; leave it unset",
+                    peer.address
+                );
+            }
+            if let Some(addr) = peer.mirror_to.iter().find(|a| a.port() == 0) {
+                bail!("peer {} has mirror_to {addr} without a port", peer.address);
+            }
+        }
+
+        self.validate_peer_set()?;
+
+        let lists = self.compiled_prefix_lists()?;
+        for (name, remote) in &self.remote_prefix_lists {
+            if lists.contains_key(name) {
+                bail!("prefix list {name} is defined both locally and in [remote_prefix_lists]");
+            }
+            remote.validate(name)?;
+        }
+        for peer in &self.peers {
+            for name in [&peer.import_prefix_list, &peer.export_prefix_list]
+                .into_iter()
+                .flatten()
+            {
+                if !lists.contains_key(name) && !self.remote_prefix_lists.contains_key(name) {
                     bail!(
-                        "peer {} has invalid local_address {}; expected IP or socket address",
+                        "peer {} references unknown prefix list {}",
                         peer.address,
-                        local
+                        name
                     );
                 }
             }
         }
-
+        self.originated_prefixes()?;
+        self.health_checked_prefixes()?;
         for prefix in &self.prefixes {
-            prefix.network.parse::<IpNet>().with_context(|| {
-                format!("invalid IP prefix in [[prefixes]]: {}", prefix.network)
-            })?;
+            if let Some(check) = &prefix.health_check {
+                check.validate(&prefix.key())?;
+            }
         }
 
         self.archive.validate()?;
+        self.health.validate()?;
+        self.events.validate()?;
+        for hook in &self.hooks {
+            hook.validate()?;
+        }
+        for rule in &self.rules {
+            rule.validate()?;
+        }
+        if let Some(name) = self
+            .rules
+            .iter()
+            .enumerate()
+            .find(|(i, rule)| self.rules[..*i].iter().any(|r| r.name == rule.name))
+            .map(|(_, rule)| &rule.name)
+        {
+            bail!("[[rules]] name {name} is used more than once");
+        }
+        self.alerts.validate()?;
+        self.exabgp.validate(self.global.log_target)?;
+        self.openbmp.validate()?;
+        self.blackhole.validate(&self.peers)?;
+        self.rpki.validate()?;
+        self.inbound_limits.validate()?;
+        self.churn.validate()?;
+        self.rib_in.validate()?;
+        self.prefix_history.validate()?;
+        self.peer_stats.validate()?;
 
         Ok(())
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct GlobalConfig {
     pub asn: u32,
     pub router_id: String,
@@ -85,32 +567,996 @@ pub struct GlobalConfig {
     pub listen_addr: String,
     #[serde(default = "default_control_socket")]
     pub control_socket: PathBuf,
+    /// Octal permission bits applied to the control socket after bind, e.g. "0660".
+    #[serde(default)]
+    pub control_socket_mode: Option<String>,
+    /// Group name or numeric gid the control socket is chowned to after bind.
+    #[serde(default)]
+    pub control_socket_group: Option<String>,
+    #[serde(default = "default_pid_file")]
+    pub pid_file: PathBuf,
     #[serde(default = "default_log_level")]
     pub log_level: String,
+    #[serde(default)]
+    pub log_target: LogTarget,
+    #[serde(default = "default_control_read_timeout")]
+    pub control_read_timeout_secs: u64,
+    #[serde(default = "default_control_max_request_bytes")]
+    pub control_max_request_bytes: usize,
+    #[serde(default = "default_control_max_connections")]
+    pub control_max_connections: usize,
+    #[serde(default = "default_shutdown_timeout")]
+    pub shutdown_timeout_secs: u64,
+    /// Check archive paths, destinations, and the control socket directory
+    /// before starting, and refuse to start if any check fails.
+    #[serde(default = "default_true")]
+    pub preflight: bool,
+    /// Collapse `[[prefixes]]` into covering supernets before announcing.
+    #[serde(default)]
+    pub aggregate_prefixes: bool,
+}
+
+impl GlobalConfig {
+    /// Parsed `control_socket_mode`, if set.
+    pub fn control_socket_mode_bits(&self) -> Result<Option<u32>> {
+        let Some(raw) = &self.control_socket_mode else {
+            return Ok(None);
+        };
+        let digits = raw.strip_prefix("0o").unwrap_or(raw);
+        match u32::from_str_radix(digits, 8) {
+            Ok(bits) if bits <= 0o777 => Ok(Some(bits)),
+            _ => bail!(
+                "[global].control_socket_mode must be octal permission bits like \"0660\", got {}",
+                raw
+            ),
+        }
+    }
+}
+
+/// Contents allowed in an included fragment file.
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct ConfigFragment {
+    #[serde(default)]
+    peers: Vec<PeerConfig>,
+    #[serde(default)]
+    prefixes: Vec<PrefixConfig>,
+}
+
+/// Expand include patterns in order; matches within a pattern are sorted.
+/// A pattern without wildcards must name an existing file.
+fn resolve_includes(base: &Path, patterns: &[String]) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    // Only the pattern is a glob; the directory it is relative to may itself
+    // contain `[`, `*`, or `?`.
+    let escaped_base = PathBuf::from(glob::Pattern::escape(&base.to_string_lossy()));
+    for pattern in patterns {
+        let full = base.join(pattern);
+        let full = full.to_string_lossy();
+        let mut matches = glob::glob(&escaped_base.join(pattern).to_string_lossy())
+            .with_context(|| format!("invalid include pattern {pattern}"))?
+            .collect::<Result<Vec<_>, _>>()
+            .with_context(|| format!("failed expanding include pattern {pattern}"))?;
+        matches.sort();
+
+        let literal = !pattern.contains(['*', '?', '[']);
+        if literal && matches.is_empty() {
+            bail!("included config {} does not exist", full);
+        }
+        for path in matches {
+            if !files.contains(&path) {
+                files.push(path);
+            }
+        }
+    }
+    Ok(files)
+}
+
+fn default_listen() -> bool {
+    true
+}
+
+fn default_listen_addr() -> String {
+    "0.0.0.0:179".to_string()
+}
+
+const DEFAULT_STATE_DIR: &str = "/var/lib/focld";
+
+fn default_control_socket() -> PathBuf {
+    instance_control_socket(None)
+}
+
+fn default_pid_file() -> PathBuf {
+    instance_pid_file(None)
+}
+
+/// Default control socket path for an optional named instance.
+pub fn instance_control_socket(instance: Option<&str>) -> PathBuf {
+    match instance {
+        Some(name) => PathBuf::from(format!("/tmp/focld-{name}.sock")),
+        None => PathBuf::from("/tmp/focld.sock"),
+    }
+}
+
+/// Default pid file path for an optional named instance.
+pub fn instance_pid_file(instance: Option<&str>) -> PathBuf {
+    match instance {
+        Some(name) => PathBuf::from(format!("/tmp/focld-{name}.pid")),
+        None => PathBuf::from("/tmp/focld.pid"),
+    }
+}
+
+pub fn validate_instance_name(instance: &str) -> Result<()> {
+    let valid = !instance.is_empty()
+        && instance
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if !valid {
+        bail!(
+            "invalid instance name {:?}; use letters, digits, '-' or '_'",
+            instance
+        );
+    }
+    Ok(())
+}
+
+fn default_log_level() -> String {
+    "info".to_string()
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum LogTarget {
+    #[default]
+    Stdout,
+    Syslog,
+    Journald,
+}
+
+fn default_control_read_timeout() -> u64 {
+    60
+}
+
+fn default_control_max_request_bytes() -> usize {
+    1024 * 1024
+}
+
+fn default_control_max_connections() -> usize {
+    64
+}
+
+fn default_shutdown_timeout() -> u64 {
+    30
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct HealthConfig {
+    #[serde(default)]
+    pub http_listen: Option<String>,
+    #[serde(default = "default_max_replication_backlog")]
+    pub max_replication_backlog: usize,
+    #[serde(default)]
+    pub min_peers_established_ratio: f64,
+}
+
+impl Default for HealthConfig {
+    fn default() -> Self {
+        Self {
+            http_listen: None,
+            max_replication_backlog: default_max_replication_backlog(),
+            min_peers_established_ratio: 0.0,
+        }
+    }
+}
+
+impl HealthConfig {
+    pub fn validate(&self) -> Result<()> {
+        if let Some(listen) = &self.http_listen {
+            if listen.parse::<std::net::SocketAddr>().is_err() {
+                bail!(
+                    "[health].http_listen must be a socket address, got {}",
+                    listen
+                );
+            }
+        }
+        if !(0.0..=1.0).contains(&self.min_peers_established_ratio) {
+            bail!(
+                "[health].min_peers_established_ratio must be between 0 and 1, got {}",
+                self.min_peers_established_ratio
+            );
+        }
+        Ok(())
+    }
+}
+
+fn default_max_replication_backlog() -> usize {
+    1000
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct EventsConfig {
+    #[serde(default)]
+    pub history: bool,
+    #[serde(default = "default_events_history_path")]
+    pub history_path: PathBuf,
+    #[serde(default = "default_events_history_max")]
+    pub history_max_events: u64,
+    /// Capacity of the in-process event broadcast channel. Subscribers that
+    /// fall further behind than this miss events and receive a gap notice.
+    #[serde(default = "default_events_buffer_size")]
+    pub buffer_size: usize,
+}
+
+impl Default for EventsConfig {
+    fn default() -> Self {
+        Self {
+            history: false,
+            history_path: default_events_history_path(),
+            history_max_events: default_events_history_max(),
+            buffer_size: default_events_buffer_size(),
+        }
+    }
+}
+
+impl EventsConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.history && self.history_max_events == 0 {
+            bail!("[events].history_max_events must be non-zero when history is enabled");
+        }
+        if self.buffer_size == 0 {
+            bail!("[events].buffer_size must be non-zero");
+        }
+        Ok(())
+    }
+}
+
+/// Event names accepted by `[[hooks]].events`; `peer_up` and `peer_down`
+/// are `peer_state` entering and leaving `established`.
+pub const HOOK_EVENTS: &[&str] = &[
+    "peer_up",
+    "peer_down",
+    "peer_state",
+    "archive_segment_opened",
+    "archive_segment_finalized",
+    "archive_replication_succeeded",
+    "archive_replication_failed",
+    "archive_records_shed",
+    "archive_audit_completed",
+    "archive_disk_pressure",
+    "archive_snapshot_failed",
+    "archive_rib_gap",
+    "archive_writer_failed",
+    "archive_writer_recovered",
+    "route_leak_suspected",
+    "prefix_hijack_suspected",
+    "origin_changed",
+    "prefix_health_changed",
+    "events_dropped",
+];
+
+/// `[alerts]`: post to a Slack webhook and/or send email when the collector
+/// needs attention. Nothing is sent until a destination is configured.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct AlertsConfig {
+    /// Slack incoming webhook URL.
+    #[serde(default)]
+    pub slack_webhook: Option<String>,
+    #[serde(default)]
+    pub email: Option<EmailAlertConfig>,
+    /// Alert when this many replications fail within
+    /// `replication_window_mins`; 0 disables.
+    #[serde(default)]
+    pub replication_failures: u32,
+    #[serde(default = "default_alert_replication_window")]
+    pub replication_window_mins: u64,
+    /// Alert when a peer stays down this long after leaving Established;
+    /// 0 disables.
+    #[serde(default)]
+    pub peer_down_mins: u64,
+    #[serde(default = "default_true")]
+    pub snapshot_failed: bool,
+    /// Alert on `prefix_hijack_suspected`.
+    #[serde(default = "default_true")]
+    pub hijack: bool,
+    /// The same alert is not sent again within this many minutes.
+    #[serde(default = "default_alert_repeat")]
+    pub repeat_mins: u64,
+}
+
+impl Default for AlertsConfig {
+    fn default() -> Self {
+        Self {
+            slack_webhook: None,
+            email: None,
+            replication_failures: 0,
+            replication_window_mins: default_alert_replication_window(),
+            peer_down_mins: 0,
+            snapshot_failed: true,
+            hijack: true,
+            repeat_mins: default_alert_repeat(),
+        }
+    }
+}
+
+/// Mail is handed to a sendmail-compatible program, as `sendmail -t`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct EmailAlertConfig {
+    pub to: Vec<String>,
+    pub from: String,
+    #[serde(default = "default_sendmail")]
+    pub sendmail: PathBuf,
+}
+
+fn default_alert_replication_window() -> u64 {
+    60
+}
+
+fn default_alert_repeat() -> u64 {
+    60
+}
+
+fn default_sendmail() -> PathBuf {
+    PathBuf::from("/usr/sbin/sendmail")
+}
+
+impl AlertsConfig {
+    pub fn enabled(&self) -> bool {
+        self.slack_webhook.is_some() || self.email.is_some()
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if let Some(url) = &self.slack_webhook {
+            if !url.starts_with("https://") {
+                bail!("[alerts].slack_webhook must be an https:// URL");
+            }
+        }
+        if let Some(email) = &self.email {
+            if email.to.is_empty() {
+                bail!("[alerts.email].to needs at least one address");
+            }
+            if email.from.is_empty() {
+                bail!("[alerts.email].from must not be empty");
+            }
+        }
+        if self.replication_window_mins == 0 {
+            bail!("[alerts].replication_window_mins must be greater than 0");
+        }
+        Ok(())
+    }
+}
+
+/// `[inbound_limits]`: admission control for connections accepted by passive
+/// peers, so a public port 179 does not run a session for every scanner.
+/// Every limit is off at 0.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct InboundLimitsConfig {
+    /// Connections accepted from one source address per minute.
+    #[serde(default)]
+    pub per_source_per_min: u32,
+    /// Connections accepted from all sources per minute.
+    #[serde(default)]
+    pub global_per_min: u32,
+    /// Ban a source after this many failed OPEN handshakes within
+    /// `failure_window_secs`.
+    #[serde(default)]
+    pub ban_after_failures: u32,
+    #[serde(default = "default_inbound_failure_window")]
+    pub failure_window_secs: u64,
+    #[serde(default = "default_inbound_ban")]
+    pub ban_secs: u64,
+}
+
+impl Default for InboundLimitsConfig {
+    fn default() -> Self {
+        Self {
+            per_source_per_min: 0,
+            global_per_min: 0,
+            ban_after_failures: 0,
+            failure_window_secs: default_inbound_failure_window(),
+            ban_secs: default_inbound_ban(),
+        }
+    }
+}
+
+fn default_inbound_failure_window() -> u64 {
+    600
+}
+
+fn default_inbound_ban() -> u64 {
+    3600
+}
+
+impl InboundLimitsConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.ban_after_failures > 0 && self.failure_window_secs == 0 {
+            bail!("[inbound_limits].failure_window_secs must be greater than 0");
+        }
+        if self.ban_after_failures > 0 && self.ban_secs == 0 {
+            bail!("[inbound_limits].ban_secs must be greater than 0");
+        }
+        Ok(())
+    }
+}
+
+/// `[churn]`: per-prefix announcement and withdrawal counts behind
+/// `rib_churn`. Memory grows with the number of distinct prefixes updated
+/// within the window, so it is off by default.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ChurnConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Longest window `rib_churn` can report, in minutes.
+    #[serde(default = "default_churn_window")]
+    pub window_mins: u32,
+}
+
+impl Default for ChurnConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            window_mins: default_churn_window(),
+        }
+    }
+}
+
+fn default_churn_window() -> u32 {
+    60
+}
+
+impl ChurnConfig {
+    pub fn validate(&self) -> Result<()> {
+        if !(1..=1440).contains(&self.window_mins) {
+            bail!(
+                "[churn].window_mins must be between 1 and 1440, got {}",
+                self.window_mins
+            );
+        }
+        Ok(())
+    }
+}
+
+/// `[rib_in]`: keep the AS path of every route received, per peer, behind
+/// `rib_in` and `aspath_stats`. Memory grows with the size of the peers'
+/// tables, so it is off by default.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct RibInConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Prefixes, more-specifics included, whose AS path changes are emitted
+    /// as `origin_changed` events.
+    #[serde(default)]
+    pub watch_prefixes: Vec<String>,
+}
+
+impl RibInConfig {
+    pub fn watched_prefixes(&self) -> Result<Vec<IpNet>> {
+        self.watch_prefixes
+            .iter()
+            .map(|prefix| {
+                prefix
+                    .parse::<IpNet>()
+                    .with_context(|| format!("[rib_in].watch_prefixes has invalid prefix {prefix}"))
+            })
+            .collect()
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if !self.watched_prefixes()?.is_empty() && !self.enabled {
+            bail!("[rib_in].watch_prefixes requires [rib_in].enabled");
+        }
+        Ok(())
+    }
+}
+
+/// `[prefix_history]`: first-seen, last-seen, and last-withdrawn times per
+/// (prefix, peer) in SQLite, behind `prefix_history`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PrefixHistoryConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_prefix_history_path")]
+    pub path: PathBuf,
+    /// Received updates are batched in memory and written this often.
+    #[serde(default = "default_prefix_history_flush")]
+    pub flush_interval_secs: u64,
+}
+
+impl Default for PrefixHistoryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: default_prefix_history_path(),
+            flush_interval_secs: default_prefix_history_flush(),
+        }
+    }
+}
+
+fn default_prefix_history_path() -> PathBuf {
+    PathBuf::from(DEFAULT_STATE_DIR).join("prefix_history.sqlite")
+}
+
+fn default_prefix_history_flush() -> u64 {
+    5
+}
+
+impl PrefixHistoryConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.enabled && self.flush_interval_secs == 0 {
+            bail!("[prefix_history].flush_interval_secs must be greater than 0");
+        }
+        Ok(())
+    }
+}
+
+/// `[peer_stats]`: per-peer update, prefix, and flap totals saved in SQLite
+/// and added back at startup, so a restart does not zero them.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PeerStatsConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_peer_stats_path")]
+    pub path: PathBuf,
+    /// Totals are also saved at shutdown.
+    #[serde(default = "default_peer_stats_save")]
+    pub save_interval_secs: u64,
+}
+
+impl Default for PeerStatsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: default_peer_stats_path(),
+            save_interval_secs: default_peer_stats_save(),
+        }
+    }
+}
+
+fn default_peer_stats_path() -> PathBuf {
+    PathBuf::from(DEFAULT_STATE_DIR).join("peer_stats.sqlite")
+}
+
+fn default_peer_stats_save() -> u64 {
+    60
+}
+
+impl PeerStatsConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.enabled && self.save_interval_secs == 0 {
+            bail!("[peer_stats].save_interval_secs must be greater than 0");
+        }
+        Ok(())
+    }
+}
+
+/// `[hijack]`: flag received routes for our `[[prefixes]]`, or
+/// more-specifics of them, whose origin is neither `global.asn` nor one of
+/// `allowed_origins`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct HijackConfig {
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+    /// Other ASNs that may originate our prefixes, such as a DDoS
+    /// mitigation provider.
+    #[serde(default)]
+    pub allowed_origins: Vec<u32>,
+}
+
+impl Default for HijackConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            allowed_origins: Vec::new(),
+        }
+    }
+}
+
+/// `[[hooks]]`: run `command` for each matching event, with the event
+/// envelope as JSON on stdin.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct HookConfig {
+    pub events: Vec<String>,
+    pub command: PathBuf,
+    #[serde(default)]
+    pub args: Vec<String>,
+    /// The program is killed if it runs longer than this.
+    #[serde(default = "default_hook_timeout")]
+    pub timeout_secs: u64,
+}
+
+fn default_hook_timeout() -> u64 {
+    10
+}
+
+impl HookConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.command.as_os_str().is_empty() {
+            bail!("[[hooks]].command must not be empty");
+        }
+        if self.events.is_empty() {
+            bail!(
+                "[[hooks]] {} needs at least one event",
+                self.command.display()
+            );
+        }
+        if let Some(unknown) = self
+            .events
+            .iter()
+            .find(|e| !HOOK_EVENTS.contains(&e.as_str()))
+        {
+            bail!(
+                "[[hooks]] {} has unknown event {unknown}; expected one of {}",
+                self.command.display(),
+                HOOK_EVENTS.join(", ")
+            );
+        }
+        if self.timeout_secs == 0 {
+            bail!("[[hooks]].timeout_secs must be greater than 0");
+        }
+        Ok(())
+    }
+}
+
+/// Events accepted by `[[rules]].events` besides [`HOOK_EVENTS`]: one per
+/// UPDATE received, covering its announced or withdrawn prefixes.
+pub const ROUTE_EVENTS: &[&str] = &["route_announced", "route_withdrawn"];
+
+/// `[[rules]]`: when an event or received route matches every condition
+/// set, run `action`. Conditions left unset match anything.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RuleConfig {
+    pub name: String,
+    /// Any of [`HOOK_EVENTS`] or [`ROUTE_EVENTS`].
+    pub events: Vec<String>,
+    #[serde(default)]
+    pub peer: Option<String>,
+    #[serde(default)]
+    pub prefix: Option<String>,
+    #[serde(default)]
+    pub prefix_match: PrefixMatch,
+    /// The origin ASN is one of these.
+    #[serde(default)]
+    pub origin: Vec<u32>,
+    /// The origin ASN is none of these.
+    #[serde(default)]
+    pub origin_not: Vec<u32>,
+    pub action: RuleAction,
+    /// POST target for `webhook`.
+    #[serde(default)]
+    pub url: Option<String>,
+    /// Program for `command`, run with the match as JSON on stdin.
+    #[serde(default)]
+    pub command: Option<PathBuf>,
+    #[serde(default)]
+    pub args: Vec<String>,
+    #[serde(default = "default_hook_timeout")]
+    pub timeout_secs: u64,
+    /// The same rule does not fire again for a peer and prefix within this
+    /// many seconds.
+    #[serde(default = "default_rule_repeat")]
+    pub repeat_secs: u64,
+}
+
+/// How `[[rules]].prefix` compares with a route's prefix.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PrefixMatch {
+    Exact,
+    /// The prefix or any more-specific of it.
+    #[default]
+    OrLonger,
+    /// Only more-specifics of the prefix.
+    Longer,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RuleAction {
+    Log,
+    Webhook,
+    Command,
+}
+
+fn default_rule_repeat() -> u64 {
+    300
+}
+
+impl RuleConfig {
+    pub fn prefix_net(&self) -> Result<Option<IpNet>> {
+        self.prefix
+            .as_deref()
+            .map(|prefix| {
+                prefix
+                    .parse::<IpNet>()
+                    .with_context(|| format!("[[rules]] {} has invalid prefix {prefix}", self.name))
+            })
+            .transpose()
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.name.is_empty() {
+            bail!("[[rules]].name must not be empty");
+        }
+        if self.events.is_empty() {
+            bail!("[[rules]] {} needs at least one event", self.name);
+        }
+        if let Some(unknown) = self
+            .events
+            .iter()
+            .find(|e| !HOOK_EVENTS.contains(&e.as_str()) && !ROUTE_EVENTS.contains(&e.as_str()))
+        {
+            bail!(
+                "[[rules]] {} has unknown event {unknown}; expected one of {}, {}",
+                self.name,
+                HOOK_EVENTS.join(", "),
+                ROUTE_EVENTS.join(", ")
+            );
+        }
+        self.prefix_net()?;
+        match self.action {
+            RuleAction::Log => {}
+            RuleAction::Webhook => match self.url.as_deref() {
+                Some(url) if url.starts_with("https://") || url.starts_with("http://") => {}
+                _ => bail!(
+                    "[[rules]] {} has action webhook and needs an http(s) url",
+                    self.name
+                ),
+            },
+            RuleAction::Command => {
+                if self
+                    .command
+                    .as_ref()
+                    .is_none_or(|command| command.as_os_str().is_empty())
+                {
+                    bail!(
+                        "[[rules]] {} has action command and needs a command",
+                        self.name
+                    );
+                }
+            }
+        }
+        if self.timeout_secs == 0 {
+            bail!("[[rules]].timeout_secs must be greater than 0");
+        }
+        Ok(())
+    }
+}
+
+fn default_events_history_path() -> PathBuf {
+    PathBuf::from(DEFAULT_STATE_DIR).join("events.sqlite")
+}
+
+fn default_events_history_max() -> u64 {
+    100_000
+}
+
+fn default_events_buffer_size() -> usize {
+    512
+}
+
+/// ExaBGP-compatible process API: text commands in, JSON updates out.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ExabgpConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Where `announce`/`withdraw` commands are read from; `-` is stdin,
+    /// anything else is a FIFO (or file) that is reopened when writers close it.
+    #[serde(default = "default_exabgp_stream")]
+    pub input: PathBuf,
+    /// Where received updates are written as ExaBGP JSON; `-` is stdout.
+    #[serde(default = "default_exabgp_stream")]
+    pub output: PathBuf,
+}
+
+impl Default for ExabgpConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            input: default_exabgp_stream(),
+            output: default_exabgp_stream(),
+        }
+    }
+}
+
+impl ExabgpConfig {
+    pub fn validate(&self, log_target: LogTarget) -> Result<()> {
+        if self.enabled && self.output == Path::new("-") && matches!(log_target, LogTarget::Stdout)
+        {
+            bail!(
+                "[exabgp].output = \"-\" shares stdout with logs; set [global].log_target to syslog or journald, or write output to a file"
+            );
+        }
+        Ok(())
+    }
+}
+
+fn default_exabgp_stream() -> PathBuf {
+    PathBuf::from("-")
+}
+
+/// RPKI origin validation of our own originations (`[rpki]`).
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct RpkiConfig {
+    /// ROA export in the rpki-client / Routinator JSON format (path or
+    /// http(s) URL), read at startup.
+    #[serde(default)]
+    pub roa_file: Option<String>,
+    /// What to do with configured prefixes that would be RPKI-invalid from
+    /// `[global].asn`.
+    #[serde(default)]
+    pub invalid_originations: InvalidOriginations,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum InvalidOriginations {
+    /// Log a warning and announce anyway.
+    #[default]
+    Warn,
+    /// Log an error and leave the prefix out.
+    Refuse,
+}
+
+impl RpkiConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self
+            .roa_file
+            .as_deref()
+            .is_some_and(|file| file.trim().is_empty())
+        {
+            bail!("[rpki].roa_file must not be empty");
+        }
+        Ok(())
+    }
+}
+
+/// Remotely triggered blackholing (`focl blackhole`, RFC 7999).
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct BlackholeConfig {
+    /// Next hop for IPv4 blackholes, usually one the upstream discards.
+    #[serde(default)]
+    pub next_hop: Option<String>,
+    #[serde(default)]
+    pub next_hop_v6: Option<String>,
+    /// Upstream peers that receive blackholes; empty means every peer.
+    #[serde(default)]
+    pub peers: Vec<String>,
+    /// Expiry for blackholes added without their own; 0 keeps them until removed.
+    #[serde(default = "default_blackhole_expiry_secs")]
+    pub expiry_secs: u64,
+    /// Shortest IPv4 prefix that may be blackholed.
+    #[serde(default = "default_blackhole_min_len_v4")]
+    pub min_prefix_len_v4: u8,
+    /// Shortest IPv6 prefix that may be blackholed.
+    #[serde(default = "default_blackhole_min_len_v6")]
+    pub min_prefix_len_v6: u8,
+}
+
+impl Default for BlackholeConfig {
+    fn default() -> Self {
+        Self {
+            next_hop: None,
+            next_hop_v6: None,
+            peers: Vec::new(),
+            expiry_secs: default_blackhole_expiry_secs(),
+            min_prefix_len_v4: default_blackhole_min_len_v4(),
+            min_prefix_len_v6: default_blackhole_min_len_v6(),
+        }
+    }
+}
+
+impl BlackholeConfig {
+    pub fn validate(&self, peers: &[PeerConfig]) -> Result<()> {
+        if let Some(next_hop) = &self.next_hop {
+            if next_hop.parse::<Ipv4Addr>().is_err() {
+                bail!("[blackhole].next_hop {next_hop} is not an IPv4 address");
+            }
+        }
+        if let Some(next_hop) = &self.next_hop_v6 {
+            if next_hop.parse::<Ipv6Addr>().is_err() {
+                bail!("[blackhole].next_hop_v6 {next_hop} is not an IPv6 address");
+            }
+        }
+        if self.min_prefix_len_v4 > 32 {
+            bail!("[blackhole].min_prefix_len_v4 must be at most 32");
+        }
+        if self.min_prefix_len_v6 > 128 {
+            bail!("[blackhole].min_prefix_len_v6 must be at most 128");
+        }
+        for peer in &self.peers {
+            if !peers.iter().any(|p| &p.address == peer) {
+                bail!("[blackhole].peers references unknown peer {peer}");
+            }
+        }
+        Ok(())
+    }
+}
+
+fn default_blackhole_expiry_secs() -> u64 {
+    3600
+}
+
+fn default_blackhole_min_len_v4() -> u8 {
+    24
+}
+
+fn default_blackhole_min_len_v6() -> u8 {
+    48
+}
+
+/// Received updates as OpenBMP message bus (`openbmp.bmp_raw`) records.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct OpenbmpConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// File the records are appended to.
+    #[serde(default)]
+    pub output: Option<PathBuf>,
+    /// Collector admin ID in the OpenBMP header; defaults to `[archive].collector_id`.
+    #[serde(default)]
+    pub admin_id: Option<String>,
+    #[serde(default)]
+    pub router_group: Option<String>,
 }
 
-fn default_listen() -> bool {
-    true
+impl OpenbmpConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.enabled && self.output.is_none() {
+            bail!("[openbmp].output is required when [openbmp] is enabled");
+        }
+        Ok(())
+    }
 }
 
-fn default_listen_addr() -> String {
-    "0.0.0.0:179".to_string()
+/// `[remote_prefix_lists.NAME]`: a prefix list served as a JSON array of
+/// `{prefix, ge, le}` entries. The last good body is kept in `cache_dir` and
+/// loaded at startup; a list that was never fetched matches nothing.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RemotePrefixListConfig {
+    /// `http://` or `https://` URL; refreshes send `If-None-Match` with the
+    /// last ETag.
+    pub url: String,
+    #[serde(default = "default_remote_prefix_list_refresh")]
+    pub refresh_secs: u64,
+    /// Directory holding `NAME.json` with the last good body and its ETag.
+    #[serde(default = "default_remote_prefix_list_cache_dir")]
+    pub cache_dir: PathBuf,
 }
 
-fn default_control_socket() -> PathBuf {
-    PathBuf::from("/tmp/focld.sock")
+fn default_remote_prefix_list_refresh() -> u64 {
+    300
 }
 
-fn default_log_level() -> String {
-    "info".to_string()
+fn default_remote_prefix_list_cache_dir() -> PathBuf {
+    PathBuf::from(DEFAULT_STATE_DIR).join("remote_prefix_lists")
+}
+
+impl RemotePrefixListConfig {
+    pub fn validate(&self, name: &str) -> Result<()> {
+        if !(self.url.starts_with("https://") || self.url.starts_with("http://")) {
+            bail!(
+                "[remote_prefix_lists.{name}].url must be an http:// or https:// URL, got {}",
+                self.url
+            );
+        }
+        if self.refresh_secs == 0 {
+            bail!("[remote_prefix_lists.{name}].refresh_secs must be greater than 0");
+        }
+        Ok(())
+    }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct PeerConfig {
     pub address: String,
     pub remote_as: u32,
     #[serde(default)]
     pub local_as: Option<u32>,
+    /// Leave `local_as` off the AS paths received from the peer.
+    #[serde(default)]
+    pub local_as_no_prepend: bool,
+    /// Send routes with `local_as` in place of `global.asn` in the AS path.
+    #[serde(default)]
+    pub local_as_replace_as: bool,
     #[serde(default = "default_hold_time")]
     pub hold_time_secs: u16,
     #[serde(default = "default_connect_retry")]
@@ -123,12 +1569,283 @@ pub struct PeerConfig {
     pub enabled: bool,
     #[serde(default)]
     pub passive: bool,
+    /// Also accept the peer's own connection on this port while connecting
+    /// out, resolving a collision per RFC 4271 section 6.8. Active peers only.
+    #[serde(default)]
+    pub listen_port: Option<u16>,
     #[serde(default = "default_true")]
     pub route_refresh: bool,
     #[serde(default)]
     pub name: Option<String>,
     #[serde(default)]
     pub password: Option<String>,
+    /// Use `password` as a TCP-AO (RFC 5925) key with this SendID and
+    /// RecvID instead of as a TCP-MD5 key.
+    #[serde(default)]
+    pub tcp_ao_key_id: Option<u8>,
+    /// A next key, accepted alongside `password` during a window.
+    #[serde(default)]
+    pub password_rotation: Option<PasswordRotationConfig>,
+    #[serde(default)]
+    pub archive: PeerArchiveConfig,
+    /// Only count received prefixes matching this prefix list as accepted.
+    #[serde(default)]
+    pub import_prefix_list: Option<String>,
+    /// Only announce originated prefixes matching this prefix list.
+    #[serde(default)]
+    pub export_prefix_list: Option<String>,
+    /// Also announce the routes in this MRT file (RIB dump or updates; path
+    /// or URL), keeping their AS path, origin, MED, and communities.
+    #[serde(default)]
+    pub announce_from_mrt: Option<String>,
+    /// Write this peer's BGP messages, both directions, to a pcap file.
+    #[serde(default)]
+    pub capture_pcap: Option<PathBuf>,
+    /// Rotate the capture file once it reaches this size.
+    #[serde(default = "default_capture_pcap_max_mb")]
+    pub capture_pcap_max_mb: u64,
+    /// Rotated capture files to keep (`<file>.1` is the newest).
+    #[serde(default = "default_capture_pcap_keep")]
+    pub capture_pcap_keep: u32,
+    /// Session events kept for `peer_show` with `history`; 0 keeps none.
+    #[serde(default = "default_session_history")]
+    pub session_history: usize,
+    /// Hex-dump every BGP message sent and received from startup; toggled at
+    /// runtime with `peer_log_messages`.
+    #[serde(default)]
+    pub log_messages: bool,
+    /// Append the dumps to this file instead of the daemon log.
+    #[serde(default)]
+    pub log_messages_file: Option<PathBuf>,
+    /// The peer's relationship to us, used to flag suspected route leaks.
+    #[serde(default)]
+    pub role: Option<PeerRole>,
+    /// Forward every message received from this peer, byte for byte, to
+    /// these TCP endpoints (e.g. a passive peer on a standby focld).
+    #[serde(default)]
+    pub mirror_to: Vec<SocketAddr>,
+    /// Pace the UPDATEs sent to the peer instead of writing them all at once.
+    #[serde(default)]
+    pub pacing: Option<PacingConfig>,
+}
+
+/// `[peers.pacing]`: a token bucket for outbound UPDATEs. Up to `burst`
+/// go out back to back, then `updates_per_sec`; the rest wait their turn
+/// without holding up KEEPALIVEs or received messages.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema)]
+pub struct PacingConfig {
+    pub updates_per_sec: u32,
+    /// Defaults to one second's worth, `updates_per_sec`.
+    #[serde(default)]
+    pub burst: Option<u32>,
+}
+
+impl PacingConfig {
+    pub fn validate(&self, peer: &PeerConfig) -> Result<()> {
+        if self.updates_per_sec == 0 {
+            bail!(
+                "peer {} pacing.updates_per_sec must be greater than 0",
+                peer.address
+            );
+        }
+        if self.burst == Some(0) {
+            bail!("peer {} pacing.burst must be greater than 0", peer.address);
+        }
+        Ok(())
+    }
+
+    pub fn burst(&self) -> u32 {
+        self.burst.unwrap_or(self.updates_per_sec)
+    }
+}
+
+/// `[peers.password_rotation]`: roll the session key without downtime. From
+/// `start` for `window_secs` either key is accepted; after the window only
+/// `password` here is used. With TCP-AO both keys sit on the socket at once,
+/// and an established session takes the next key when the window opens. With
+/// TCP-MD5 a socket holds one key, so connection attempts alternate keys.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PasswordRotationConfig {
+    /// The next key.
+    pub password: String,
+    /// Unix time the window opens.
+    pub start: i64,
+    #[serde(default = "default_password_rotation_window")]
+    pub window_secs: u64,
+    /// TCP-AO KeyID of the next key; required when the peer sets
+    /// `tcp_ao_key_id`.
+    #[serde(default)]
+    pub tcp_ao_key_id: Option<u8>,
+}
+
+fn default_password_rotation_window() -> u64 {
+    86_400
+}
+
+impl PasswordRotationConfig {
+    pub fn validate(&self, peer: &PeerConfig) -> Result<()> {
+        if peer.password.is_none() {
+            bail!(
+                "peer {} sets password_rotation without a password to rotate from",
+                peer.address
+            );
+        }
+        if self.password.is_empty() || self.password.len() > TCP_MD5_MAX_KEY_LEN {
+            bail!(
+                "peer {} password_rotation.password must be 1 to {TCP_MD5_MAX_KEY_LEN} bytes",
+                peer.address
+            );
+        }
+        if self.window_secs == 0 {
+            bail!(
+                "peer {} password_rotation.window_secs must be greater than 0",
+                peer.address
+            );
+        }
+        match (peer.tcp_ao_key_id, self.tcp_ao_key_id) {
+            (Some(current), Some(next)) if current == next => bail!(
+                "peer {} password_rotation.tcp_ao_key_id must differ from tcp_ao_key_id {current}",
+                peer.address
+            ),
+            (Some(_), None) => bail!(
+                "peer {} uses TCP-AO; password_rotation.tcp_ao_key_id is required",
+                peer.address
+            ),
+            (None, Some(_)) => bail!(
+                "peer {} sets password_rotation.tcp_ao_key_id without tcp_ao_key_id; TCP-MD5 holds one key per socket and rotates by alternating keys between connection attempts",
+                peer.address
+            ),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Unix time the window closes.
+    pub fn end(&self) -> i64 {
+        self.start
+            .saturating_add(i64::try_from(self.window_secs).unwrap_or(i64::MAX))
+    }
+}
+
+/// TCP_MD5SIG_MAXKEYLEN, which is also TCP_AO_MAXKEYLEN.
+const TCP_MD5_MAX_KEY_LEN: usize = 80;
+
+/// What the peer is to us (RFC 9234 roles, seen from our side).
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PeerRole {
+    Provider,
+    Customer,
+    Peer,
+    RouteServer,
+    RouteServerClient,
+}
+
+/// Per-peer archive policy (`[peers.archive]`), for peers that must stay out
+/// of the public archive.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PeerArchiveConfig {
+    /// Write this peer's UPDATE messages to the updates stream.
+    #[serde(default = "default_true")]
+    pub updates: bool,
+    /// Record this peer's session state changes; `[archive].include_peer_state_records`
+    /// must also be enabled.
+    #[serde(default = "default_true")]
+    pub state_changes: bool,
+    /// Archive this peer's UPDATEs and state changes as a separate stream,
+    /// laid out under this collector ID instead of `[archive].collector_id`.
+    #[serde(default)]
+    pub collector_id: Option<String>,
+    /// RIB view this peer's routes are dumped under; snapshots of any other
+    /// view leave the peer out. Defaults to `[archive].rib_view_name`.
+    #[serde(default)]
+    pub view_name: Option<String>,
+}
+
+impl Default for PeerArchiveConfig {
+    fn default() -> Self {
+        Self {
+            updates: true,
+            state_changes: true,
+            collector_id: None,
+            view_name: None,
+        }
+    }
+}
+
+impl PeerConfig {
+    /// `local_address` as a socket address, using `remote_port` when only an
+    /// IP is given.
+    pub fn local_address_socket(&self) -> Result<Option<SocketAddr>> {
+        let Some(local) = &self.local_address else {
+            return Ok(None);
+        };
+        if let Ok(addr) = local.parse::<SocketAddr>() {
+            return Ok(Some(addr));
+        }
+        let ip = local.parse::<IpAddr>().with_context(|| {
+            format!(
+                "peer {} has invalid local_address {}; expected IP or socket address",
+                self.address, local
+            )
+        })?;
+        Ok(Some(SocketAddr::new(ip, self.remote_port)))
+    }
+
+    /// The TCP-AO keys valid at Unix time `now` as `(KeyID, key)`, the key
+    /// to send with first: the current key inside a rotation window, then the
+    /// next one. Empty for TCP-MD5 peers.
+    pub fn ao_keys_at(&self, now: i64) -> Vec<(u8, &str)> {
+        let Some(id) = self.tcp_ao_key_id else {
+            return Vec::new();
+        };
+        let current = self.password.as_deref().map(|key| (id, key));
+        let next = self
+            .password_rotation
+            .as_ref()
+            .and_then(|r| Some((r.tcp_ao_key_id?, r.password.as_str())));
+        match &self.password_rotation {
+            Some(rotation) if now >= rotation.end() => next.into_iter().collect(),
+            Some(rotation) if now >= rotation.start => current.into_iter().chain(next).collect(),
+            _ => current.into_iter().collect(),
+        }
+    }
+
+    /// The TCP-MD5 keys valid at Unix time `now`, the one to try first
+    /// leading: the next key inside a rotation window, then the current one.
+    /// Empty for TCP-AO peers.
+    pub fn md5_keys_at(&self, now: i64) -> Vec<&str> {
+        if self.tcp_ao_key_id.is_some() {
+            return Vec::new();
+        }
+        let current = self.password.as_deref();
+        let Some(rotation) = &self.password_rotation else {
+            return current.into_iter().collect();
+        };
+        if now < rotation.start {
+            current.into_iter().collect()
+        } else if now < rotation.end() {
+            std::iter::once(rotation.password.as_str())
+                .chain(current)
+                .collect()
+        } else {
+            vec![rotation.password.as_str()]
+        }
+    }
+}
+
+impl PeerArchiveConfig {
+    fn validate(&self, peer: &str) -> Result<()> {
+        if matches!(&self.collector_id, Some(id) if id.trim().is_empty()) {
+            bail!("peer {peer} has an empty archive.collector_id");
+        }
+        if let Some(view) = &self.view_name {
+            if view.len() > usize::from(u16::MAX) {
+                bail!("peer {peer} archive.view_name must be at most 65535 bytes");
+            }
+        }
+        Ok(())
+    }
 }
 
 fn default_true() -> bool {
@@ -147,14 +1864,127 @@ fn default_remote_port() -> u16 {
     179
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+fn default_capture_pcap_max_mb() -> u64 {
+    64
+}
+
+fn default_capture_pcap_keep() -> u32 {
+    4
+}
+
+fn default_session_history() -> usize {
+    64
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct PrefixConfig {
-    pub network: String,
+    /// Single prefix to originate; set this or `prefix_list`.
+    #[serde(default)]
+    pub network: Option<String>,
+    /// Originate the base prefix of every entry in a named prefix list.
+    #[serde(default)]
+    pub prefix_list: Option<String>,
+    #[serde(default)]
+    pub next_hop: Option<String>,
+    /// Announce only while this check passes.
     #[serde(default)]
+    pub health_check: Option<PrefixHealthCheck>,
+}
+
+/// `[prefixes.health_check]`: gates an anycast prefix on a local service.
+/// The prefix is announced after `rise` consecutive passes and withdrawn
+/// after `fall` consecutive failures; it starts withdrawn.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PrefixHealthCheck {
+    pub kind: HealthCheckKind,
+    /// `host:port` for `tcp`, a URL for `http`, a host for `icmp`, or a
+    /// shell command for `script` (exit status 0 passes).
+    pub target: String,
+    #[serde(default = "default_health_check_interval")]
+    pub interval_secs: u64,
+    #[serde(default = "default_health_check_timeout")]
+    pub timeout_secs: u64,
+    #[serde(default = "default_health_check_rise")]
+    pub rise: u32,
+    #[serde(default = "default_health_check_fall")]
+    pub fall: u32,
+}
+
+/// The expanded networks of one health-checked `[[prefixes]]` entry.
+#[derive(Debug, Clone)]
+pub struct HealthCheckedPrefixes {
+    pub networks: Vec<IpNet>,
     pub next_hop: Option<String>,
+    pub check: PrefixHealthCheck,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum HealthCheckKind {
+    Tcp,
+    /// Passes on any 2xx response.
+    Http,
+    /// One echo request via the system `ping`.
+    Icmp,
+    Script,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+fn default_health_check_interval() -> u64 {
+    5
+}
+
+fn default_health_check_timeout() -> u64 {
+    2
+}
+
+fn default_health_check_rise() -> u32 {
+    3
+}
+
+fn default_health_check_fall() -> u32 {
+    2
+}
+
+impl PrefixHealthCheck {
+    pub fn validate(&self, prefix: &str) -> Result<()> {
+        if self.interval_secs == 0 || self.timeout_secs == 0 {
+            bail!("[[prefixes]] {prefix} health_check interval_secs and timeout_secs must be greater than 0");
+        }
+        if self.rise == 0 || self.fall == 0 {
+            bail!("[[prefixes]] {prefix} health_check rise and fall must be at least 1");
+        }
+        match self.kind {
+            HealthCheckKind::Http
+                if !(self.target.starts_with("http://") || self.target.starts_with("https://")) =>
+            {
+                bail!(
+                    "[[prefixes]] {prefix} health_check target must be an http:// or https:// URL, got {}",
+                    self.target
+                )
+            }
+            HealthCheckKind::Tcp if !self.target.contains(':') => bail!(
+                "[[prefixes]] {prefix} health_check target must be host:port, got {}",
+                self.target
+            ),
+            _ if self.target.trim().is_empty() => {
+                bail!("[[prefixes]] {prefix} health_check target must not be empty")
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+impl PrefixConfig {
+    fn key(&self) -> String {
+        match (&self.network, &self.prefix_list) {
+            (Some(network), _) => network.clone(),
+            (None, Some(list)) => format!("prefix_list:{list}"),
+            (None, None) => String::new(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ArchiveConfig {
     #[serde(default)]
     pub enabled: bool,
@@ -166,12 +1996,29 @@ pub struct ArchiveConfig {
     pub updates_interval_secs: u32,
     #[serde(default = "default_ribs_interval")]
     pub ribs_interval_secs: u32,
+    /// Periodic RIB snapshots are taken this long after each bucket boundary.
+    #[serde(default)]
+    pub rib_offset_secs: u32,
+    /// Delays periodic RIB snapshots by a further `[0, rib_spread_secs)`,
+    /// fixed per `collector_id` and `rib_view_name`, so collectors sharing a
+    /// host do not all dump at once.
+    #[serde(default)]
+    pub rib_spread_secs: u32,
     #[serde(default)]
     pub compression: CompressionKind,
     #[serde(default = "default_archive_root")]
     pub root: PathBuf,
     #[serde(default = "default_archive_tmp_root")]
     pub tmp_root: PathBuf,
+    /// `tmp_root` may sit on another filesystem than `root`: finished
+    /// segments that cannot be renamed across are copied, fsynced, and
+    /// renamed into place instead.
+    #[serde(default)]
+    pub cross_filesystem_tmp: bool,
+    /// Free space preflight requires on the archive root, tmp root, and local
+    /// destinations.
+    #[serde(default = "default_min_free_mb")]
+    pub min_free_mb: u64,
     #[serde(default = "default_true")]
     pub fsync_on_rotate: bool,
     #[serde(default = "default_true")]
@@ -182,6 +2029,47 @@ pub struct ArchiveConfig {
     pub custom_templates: Option<CustomLayoutTemplates>,
     #[serde(default)]
     pub destinations: Vec<ArchiveDestinationConfig>,
+    #[serde(default)]
+    pub broker: BrokerIndexConfig,
+    #[serde(default)]
+    pub peer_filter: ArchivePeerFilterConfig,
+    #[serde(default)]
+    pub disk_guard: DiskGuardConfig,
+    #[serde(default)]
+    pub tap: ArchiveTapConfig,
+    /// Records buffered between BGP sessions and the archive writer.
+    #[serde(default = "default_ingest_queue_len")]
+    pub ingest_queue_len: usize,
+    #[serde(default)]
+    pub ingest_overflow: IngestOverflow,
+    /// zstd worker threads for RIB segments; 0 compresses on the calling thread.
+    #[serde(default = "default_rib_zstd_workers")]
+    pub rib_zstd_workers: u32,
+    /// Skip archiving an UPDATE byte-identical to the same peer's previous
+    /// archived UPDATE within this many milliseconds; 0 disables.
+    #[serde(default)]
+    pub dedup_window_ms: u64,
+    /// Archive one in every `sample_rate` UPDATEs; UPDATEs carrying
+    /// withdrawals and peer state changes are always kept. 1 keeps all.
+    #[serde(default = "default_sample_rate")]
+    pub sample_rate: u32,
+    /// Seconds between audits that reconcile each async replica against the
+    /// local segments and queue what is missing; 0 disables.
+    #[serde(default)]
+    pub audit_interval_secs: u64,
+    /// Seconds a claimed replication job may stay in progress before it is
+    /// returned to the queue, e.g. after a crash mid-upload.
+    #[serde(default = "default_replication_lease_secs")]
+    pub replication_lease_secs: u64,
+    /// Write IPv4 and IPv6 updates and RIBs as separate `v4`/`v6` file series.
+    #[serde(default)]
+    pub split_address_families: bool,
+    /// Write `<segment>.peers.json` with the name and state of each archived peer.
+    #[serde(default = "default_true")]
+    pub peers_sidecar: bool,
+    /// View name written in each RIB snapshot's TableDumpV2 peer index table.
+    #[serde(default = "default_rib_view_name")]
+    pub rib_view_name: String,
 }
 
 impl Default for ArchiveConfig {
@@ -192,13 +2080,31 @@ impl Default for ArchiveConfig {
             layout_profile: LayoutProfile::RouteViews,
             updates_interval_secs: default_updates_interval(),
             ribs_interval_secs: default_ribs_interval(),
+            rib_offset_secs: 0,
+            rib_spread_secs: 0,
             compression: CompressionKind::Gzip,
             root: default_archive_root(),
             tmp_root: default_archive_tmp_root(),
+            cross_filesystem_tmp: false,
+            min_free_mb: default_min_free_mb(),
             fsync_on_rotate: true,
             include_peer_state_records: true,
             rib_source: RibSource::AdjRibIn,
             custom_templates: None,
+            broker: BrokerIndexConfig::default(),
+            peer_filter: ArchivePeerFilterConfig::default(),
+            disk_guard: DiskGuardConfig::default(),
+            tap: ArchiveTapConfig::default(),
+            ingest_queue_len: default_ingest_queue_len(),
+            ingest_overflow: IngestOverflow::Drop,
+            rib_zstd_workers: default_rib_zstd_workers(),
+            dedup_window_ms: 0,
+            sample_rate: default_sample_rate(),
+            split_address_families: false,
+            audit_interval_secs: 0,
+            replication_lease_secs: default_replication_lease_secs(),
+            peers_sidecar: true,
+            rib_view_name: default_rib_view_name(),
             destinations: vec![ArchiveDestinationConfig {
                 destination_type: DestinationType::Local,
                 mode: DestinationMode::Primary,
@@ -214,6 +2120,8 @@ impl Default for ArchiveConfig {
                 access_key_id: None,
                 secret_access_key: None,
                 session_token: None,
+                lifecycle: None,
+                object_lock: None,
             }],
         }
     }
@@ -246,57 +2154,321 @@ impl ArchiveConfig {
             );
         }
 
-        if self.destinations.is_empty() {
-            bail!("[archive].destinations must include at least one destination");
+        if self.rib_offset_secs >= self.ribs_interval_secs
+            || u64::from(self.rib_offset_secs) + u64::from(self.rib_spread_secs)
+                > u64::from(self.ribs_interval_secs)
+        {
+            bail!(
+                "[archive].rib_offset_secs must be below ribs_interval_secs ({}) and rib_offset_secs + rib_spread_secs must not exceed it, got {} + {}",
+                self.ribs_interval_secs,
+                self.rib_offset_secs,
+                self.rib_spread_secs
+            );
+        }
+
+        if self.rib_zstd_workers > MAX_ZSTD_WORKERS {
+            bail!(
+                "[archive].rib_zstd_workers must be at most {MAX_ZSTD_WORKERS}, got {}",
+                self.rib_zstd_workers
+            );
+        }
+
+        if self.ingest_queue_len == 0 {
+            bail!("[archive].ingest_queue_len must be >0");
+        }
+
+        if self.sample_rate == 0 {
+            bail!("[archive].sample_rate must be >0");
+        }
+
+        if self.rib_view_name.len() > usize::from(u16::MAX) {
+            bail!("[archive].rib_view_name must be at most 65535 bytes");
+        }
+
+        if self.audit_interval_secs != 0 && self.audit_interval_secs < MIN_AUDIT_INTERVAL_SECS {
+            bail!(
+                "[archive].audit_interval_secs must be 0 or at least {MIN_AUDIT_INTERVAL_SECS}, got {}",
+                self.audit_interval_secs
+            );
+        }
+
+        if self.replication_lease_secs == 0 {
+            bail!("[archive].replication_lease_secs must be >0");
+        }
+
+        if self.destinations.is_empty() {
+            bail!("[archive].destinations must include at least one destination");
+        }
+
+        let primary_count = self
+            .destinations
+            .iter()
+            .filter(|d| d.mode == DestinationMode::Primary)
+            .count();
+
+        if primary_count == 0 {
+            bail!("[archive].destinations must include at least one mode=primary destination");
+        }
+
+        if self.layout_profile == LayoutProfile::Custom {
+            let templates = self
+                .custom_templates
+                .as_ref()
+                .context("[archive].layout_profile=custom requires [archive.custom_templates]")?;
+            templates.validate()?;
+            for (name, value) in [("updates", &templates.updates), ("ribs", &templates.ribs)] {
+                if self.split_address_families != value.contains("{family}") {
+                    bail!(
+                        "[archive.custom_templates].{name} must contain {{family}} exactly when split_address_families is enabled"
+                    );
+                }
+            }
+        }
+
+        for destination in &self.destinations {
+            destination.validate()?;
+        }
+        self.broker.validate()?;
+        self.peer_filter.validate()?;
+        self.disk_guard.validate()?;
+        self.tap.validate()?;
+
+        Ok(())
+    }
+}
+
+fn default_collector_id() -> String {
+    "focl01".to_string()
+}
+
+fn default_updates_interval() -> u32 {
+    900
+}
+
+fn default_ribs_interval() -> u32 {
+    7200
+}
+
+fn default_rib_view_name() -> String {
+    "main".to_string()
+}
+
+fn default_replication_lease_secs() -> u64 {
+    900
+}
+
+fn default_archive_root() -> PathBuf {
+    PathBuf::from(DEFAULT_STATE_DIR).join("archive")
+}
+
+fn default_archive_tmp_root() -> PathBuf {
+    default_archive_root().join(".tmp")
+}
+
+fn default_min_free_mb() -> u64 {
+    1024
+}
+
+fn default_ingest_queue_len() -> usize {
+    8192
+}
+
+/// zstd's own limit on compression workers.
+const MAX_ZSTD_WORKERS: u32 = 200;
+/// Each audit lists every replica, so keep it from running back to back.
+const MIN_AUDIT_INTERVAL_SECS: u64 = 60;
+
+fn default_rib_zstd_workers() -> u32 {
+    4
+}
+
+fn default_sample_rate() -> u32 {
+    1
+}
+
+/// BGPKIT Broker-compatible metadata for finalized segments.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct BrokerIndexConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Public URL the archive root is served from; segment URLs are this plus
+    /// the segment's relative path. Without it URLs are the relative paths.
+    #[serde(default)]
+    pub url_base: Option<String>,
+    /// NDJSON index file, one item per segment. Defaults to
+    /// `<root>/broker-index.jsonl`.
+    #[serde(default)]
+    pub index_path: Option<PathBuf>,
+    /// HTTP(S) endpoint each item is POSTed to as JSON.
+    #[serde(default)]
+    pub endpoint: Option<String>,
+}
+
+impl BrokerIndexConfig {
+    pub fn validate(&self) -> Result<()> {
+        if let Some(endpoint) = &self.endpoint {
+            if !endpoint.starts_with("http://") && !endpoint.starts_with("https://") {
+                bail!(
+                    "[archive.broker].endpoint must be an http:// or https:// URL, got {}",
+                    endpoint
+                );
+            }
         }
+        Ok(())
+    }
+}
 
-        let primary_count = self
-            .destinations
-            .iter()
-            .filter(|d| d.mode == DestinationMode::Primary)
-            .count();
+/// `[archive.peer_filter]`: which peers' UPDATEs reach the updates stream,
+/// on top of each peer's own `archive.updates`. An empty `include` admits
+/// every peer; `exclude` wins over `include`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct ArchivePeerFilterConfig {
+    #[serde(default)]
+    pub include: Vec<PeerSelector>,
+    #[serde(default)]
+    pub exclude: Vec<PeerSelector>,
+}
 
-        if primary_count == 0 {
-            bail!("[archive].destinations must include at least one mode=primary destination");
-        }
+/// Matches a peer when every field given matches.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(deny_unknown_fields)]
+pub struct PeerSelector {
+    #[serde(default)]
+    pub address: Option<IpAddr>,
+    #[serde(default)]
+    pub asn: Option<u32>,
+    /// Glob over the peer's configured `name`, e.g. `"rs-*"`.
+    #[serde(default)]
+    pub name: Option<String>,
+}
 
-        if self.layout_profile == LayoutProfile::Custom {
-            let templates = self
-                .custom_templates
-                .as_ref()
-                .context("[archive].layout_profile=custom requires [archive.custom_templates]")?;
-            templates.validate()?;
+impl ArchivePeerFilterConfig {
+    pub fn validate(&self) -> Result<()> {
+        for (list, selectors) in [("include", &self.include), ("exclude", &self.exclude)] {
+            for selector in selectors {
+                if selector.address.is_none() && selector.asn.is_none() && selector.name.is_none() {
+                    bail!("[archive.peer_filter].{list} entries need an address, asn, or name");
+                }
+                if let Some(name) = &selector.name {
+                    glob::Pattern::new(name).with_context(|| {
+                        format!("[archive.peer_filter].{list} has invalid name glob {name}")
+                    })?;
+                }
+            }
         }
+        Ok(())
+    }
+}
 
-        for destination in &self.destinations {
-            destination.validate()?;
+/// `[archive.tap]`: stream the raw MRT records of the updates stream, as
+/// they are written, to every client connected to `socket`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ArchiveTapConfig {
+    #[serde(default)]
+    pub socket: Option<PathBuf>,
+    /// Records buffered per client; a client further behind skips records.
+    #[serde(default = "default_tap_buffer_records")]
+    pub buffer_records: usize,
+}
+
+impl Default for ArchiveTapConfig {
+    fn default() -> Self {
+        Self {
+            socket: None,
+            buffer_records: default_tap_buffer_records(),
         }
+    }
+}
+
+fn default_tap_buffer_records() -> usize {
+    4096
+}
 
+impl ArchiveTapConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.buffer_records == 0 {
+            bail!("[archive.tap].buffer_records must be greater than 0");
+        }
         Ok(())
     }
 }
 
-fn default_collector_id() -> String {
-    "focl01".to_string()
+/// `[archive.disk_guard]`: free-space thresholds on the archive root, in
+/// MiB. Each is 0 (off) or an escalating response: warn, stop writing RIB
+/// snapshots, then drop updates too. `prune_free_mb` deletes old segments
+/// per `prune_policy` until that much space is free again.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DiskGuardConfig {
+    #[serde(default = "default_disk_check_interval")]
+    pub check_interval_secs: u64,
+    #[serde(default)]
+    pub warn_free_mb: u64,
+    #[serde(default)]
+    pub pause_ribs_free_mb: u64,
+    #[serde(default)]
+    pub pause_updates_free_mb: u64,
+    #[serde(default)]
+    pub prune_free_mb: u64,
+    #[serde(default)]
+    pub prune_policy: PrunePolicy,
 }
 
-fn default_updates_interval() -> u32 {
-    900
+impl Default for DiskGuardConfig {
+    fn default() -> Self {
+        Self {
+            check_interval_secs: default_disk_check_interval(),
+            warn_free_mb: 0,
+            pause_ribs_free_mb: 0,
+            pause_updates_free_mb: 0,
+            prune_free_mb: 0,
+            prune_policy: PrunePolicy::Replicated,
+        }
+    }
 }
 
-fn default_ribs_interval() -> u32 {
-    7200
+impl DiskGuardConfig {
+    pub fn enabled(&self) -> bool {
+        self.warn_free_mb > 0
+            || self.pause_ribs_free_mb > 0
+            || self.pause_updates_free_mb > 0
+            || self.prune_free_mb > 0
+    }
+
+    pub fn validate(&self) -> Result<()> {
+        if self.check_interval_secs == 0 {
+            bail!("[archive.disk_guard].check_interval_secs must be > 0");
+        }
+        if self.pause_updates_free_mb > 0
+            && self.pause_ribs_free_mb > 0
+            && self.pause_updates_free_mb > self.pause_ribs_free_mb
+        {
+            bail!(
+                "[archive.disk_guard].pause_updates_free_mb ({}) must not exceed pause_ribs_free_mb ({}); ribs pause first",
+                self.pause_updates_free_mb,
+                self.pause_ribs_free_mb
+            );
+        }
+        Ok(())
+    }
 }
 
-fn default_archive_root() -> PathBuf {
-    PathBuf::from("/var/lib/focld/archive")
+fn default_disk_check_interval() -> u64 {
+    30
 }
 
-fn default_archive_tmp_root() -> PathBuf {
-    PathBuf::from("/var/lib/focld/archive/.tmp")
+/// Which segments `[archive.disk_guard]` may delete under pressure.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum PrunePolicy {
+    /// Only segments with no replication job still pending or failed; never
+    /// prunes when there are no replicas.
+    #[default]
+    Replicated,
+    /// Oldest segments first, replicated or not.
+    Oldest,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq, Default)]
 pub enum LayoutProfile {
     #[serde(rename = "routeviews", alias = "route_views")]
     #[default]
@@ -307,7 +2479,100 @@ pub enum LayoutProfile {
     Custom,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// `[archive.destinations.object_lock]`: write-once retention. S3 objects
+/// are uploaded with Object Lock retention; local copies are made read-only
+/// and never overwritten inside the window.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ObjectLockConfig {
+    #[serde(default)]
+    pub mode: ObjectLockMode,
+    /// Retention measured from the end of each segment.
+    pub retain_days: u32,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ObjectLockMode {
+    /// Nobody, including the root account, can shorten retention.
+    #[default]
+    Compliance,
+    /// Users with `s3:BypassGovernanceRetention` can lift retention.
+    Governance,
+}
+
+impl ObjectLockConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.retain_days == 0 {
+            bail!("[archive.destinations.object_lock].retain_days must be >0");
+        }
+        Ok(())
+    }
+}
+
+/// `[archive.destinations.lifecycle]`: tags on uploaded objects,
+/// plus the tiering `focl archive lifecycle` turns into bucket rules.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct S3LifecycleConfig {
+    /// Extra object tags on every uploaded object.
+    #[serde(default)]
+    pub tags: BTreeMap<String, String>,
+    /// Days before updates segments move to `storage_class`.
+    #[serde(default)]
+    pub updates_transition_days: Option<u32>,
+    /// Days before RIB segments move to `storage_class`.
+    #[serde(default)]
+    pub ribs_transition_days: Option<u32>,
+    #[serde(default = "default_lifecycle_storage_class")]
+    pub storage_class: String,
+    /// Days after which segments and their sidecars expire. Only the
+    /// recommended bucket rule deletes them; the `Expires` header is a cache
+    /// hint and is not set.
+    #[serde(default)]
+    pub expire_days: Option<u32>,
+}
+
+/// Tags focl sets itself; see `archive::lifecycle`.
+const RESERVED_TAG_PREFIX: &str = "focl-";
+/// S3 allows ten tags per object, two of which focl uses.
+const MAX_LIFECYCLE_TAGS: usize = 8;
+
+impl S3LifecycleConfig {
+    pub fn validate(&self) -> Result<()> {
+        if self.tags.len() > MAX_LIFECYCLE_TAGS {
+            bail!(
+                "[archive.destinations.lifecycle].tags allows at most {MAX_LIFECYCLE_TAGS} tags, got {}",
+                self.tags.len()
+            );
+        }
+        for (key, value) in &self.tags {
+            if key.is_empty() || key.len() > 128 || value.len() > 256 {
+                bail!("[archive.destinations.lifecycle].tags has invalid tag {key}={value}");
+            }
+            if key.starts_with(RESERVED_TAG_PREFIX) {
+                bail!("[archive.destinations.lifecycle].tags must not use the reserved {RESERVED_TAG_PREFIX} prefix, got {key}");
+            }
+        }
+        if self.storage_class.trim().is_empty() {
+            bail!("[archive.destinations.lifecycle].storage_class must not be empty");
+        }
+        if let Some(expire) = self.expire_days {
+            if expire == 0 {
+                bail!("[archive.destinations.lifecycle].expire_days must be >0");
+            }
+            let transitions = [self.updates_transition_days, self.ribs_transition_days];
+            if transitions.into_iter().flatten().any(|days| days >= expire) {
+                bail!("[archive.destinations.lifecycle].expire_days must be later than the transition days");
+            }
+        }
+        Ok(())
+    }
+}
+
+fn default_lifecycle_storage_class() -> String {
+    "GLACIER".to_string()
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct CustomLayoutTemplates {
     pub updates: String,
     pub ribs: String,
@@ -333,7 +2598,7 @@ impl CustomLayoutTemplates {
     }
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq, Default)]
 #[serde(rename_all = "snake_case")]
 pub enum CompressionKind {
     #[default]
@@ -352,7 +2617,18 @@ impl CompressionKind {
     }
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+/// What happens to archive records when the ingest queue is full.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum IngestOverflow {
+    /// Shed the record and count it against its peer; sessions never stall.
+    #[default]
+    Drop,
+    /// Wait for room, stalling the session that produced the record.
+    Block,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq, Default)]
 #[serde(rename_all = "snake_case")]
 pub enum RibSource {
     #[default]
@@ -360,7 +2636,7 @@ pub enum RibSource {
     LocRib,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ArchiveDestinationConfig {
     #[serde(rename = "type")]
     pub destination_type: DestinationType,
@@ -389,6 +2665,10 @@ pub struct ArchiveDestinationConfig {
     pub secret_access_key: Option<String>,
     #[serde(default)]
     pub session_token: Option<String>,
+    #[serde(default)]
+    pub lifecycle: Option<S3LifecycleConfig>,
+    #[serde(default)]
+    pub object_lock: Option<ObjectLockConfig>,
 }
 
 impl ArchiveDestinationConfig {
@@ -398,11 +2678,33 @@ impl ArchiveDestinationConfig {
                 if self.path.is_none() {
                     bail!("archive destination type=local requires path");
                 }
+                if self.lifecycle.is_some() {
+                    bail!("archive destination type=local does not support lifecycle");
+                }
+                if self
+                    .object_lock
+                    .as_ref()
+                    .is_some_and(|lock| lock.mode == ObjectLockMode::Governance)
+                {
+                    bail!(
+                        "archive destination type=local only supports object_lock.mode=compliance"
+                    );
+                }
             }
             DestinationType::S3 => {
                 if self.endpoint.is_none() || self.bucket.is_none() {
                     bail!("archive destination type=s3 requires endpoint and bucket");
                 }
+                if let Some(lifecycle) = &self.lifecycle {
+                    lifecycle.validate()?;
+                }
+            }
+        }
+        if let Some(lock) = &self.object_lock {
+            lock.validate()?;
+            let expire = self.lifecycle.as_ref().and_then(|l| l.expire_days);
+            if expire.is_some_and(|days| days < lock.retain_days) {
+                bail!("[archive.destinations.lifecycle].expire_days must not be shorter than object_lock.retain_days");
             }
         }
         Ok(())
@@ -438,14 +2740,14 @@ impl ArchiveDestinationConfig {
     }
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum DestinationType {
     Local,
     S3,
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema, PartialEq, Eq)]
 #[serde(rename_all = "snake_case")]
 pub enum DestinationMode {
     Primary,
@@ -476,6 +2778,32 @@ mod tests {
         assert!(cfg.validate().is_err());
     }
 
+    #[test]
+    fn rejects_zero_sample_rate() {
+        let cfg = ArchiveConfig {
+            enabled: true,
+            sample_rate: 0,
+            ..ArchiveConfig::default()
+        };
+        assert!(cfg.validate().is_err());
+    }
+
+    #[test]
+    fn rejects_rib_offset_past_the_interval() {
+        let mut cfg = ArchiveConfig {
+            enabled: true,
+            ribs_interval_secs: 7200,
+            rib_offset_secs: 7200,
+            ..ArchiveConfig::default()
+        };
+        assert!(cfg.validate().is_err());
+        cfg.rib_offset_secs = 3600;
+        cfg.rib_spread_secs = 3600;
+        assert!(cfg.validate().is_ok());
+        cfg.rib_spread_secs = 3601;
+        assert!(cfg.validate().is_err());
+    }
+
     #[test]
     fn rejects_custom_without_templates() {
         let cfg = ArchiveConfig {
@@ -502,4 +2830,433 @@ layout_profile = "routeviews"
         let cfg: FoclConfig = toml::from_str(raw).expect("toml should parse");
         assert_eq!(cfg.archive.layout_profile, LayoutProfile::RouteViews);
     }
+
+    #[test]
+    fn instance_derives_distinct_default_paths() {
+        let raw = r#"
+[global]
+asn = 65001
+router_id = "192.0.2.1"
+"#;
+
+        let mut cfg: FoclConfig = toml::from_str(raw).expect("toml should parse");
+        cfg.apply_instance("v6").unwrap();
+        assert_eq!(
+            cfg.global.control_socket,
+            PathBuf::from("/tmp/focld-v6.sock")
+        );
+        assert_eq!(cfg.global.pid_file, PathBuf::from("/tmp/focld-v6.pid"));
+        assert_eq!(cfg.archive.root, PathBuf::from("/var/lib/focld/v6/archive"));
+        assert_eq!(
+            cfg.archive.destinations[0].path,
+            Some(PathBuf::from("/var/lib/focld/v6/archive"))
+        );
+        assert!(cfg.apply_instance("../etc").is_err());
+    }
+
+    #[test]
+    fn parses_control_socket_mode() {
+        let raw = r#"
+[global]
+asn = 65001
+router_id = "192.0.2.1"
+control_socket_mode = "0660"
+"#;
+
+        let mut cfg: FoclConfig = toml::from_str(raw).expect("toml should parse");
+        assert_eq!(cfg.global.control_socket_mode_bits().unwrap(), Some(0o660));
+        cfg.global.control_socket_mode = Some("0999".to_string());
+        assert!(cfg.validate().is_err());
+    }
+
+    #[test]
+    fn merges_included_peer_fragments() {
+        let tmp = tempfile::tempdir().unwrap();
+        let peers_dir = tmp.path().join("peers.d");
+        fs::create_dir_all(&peers_dir).unwrap();
+        fs::write(
+            peers_dir.join("a.toml"),
+            "[[peers]]\naddress = \"192.0.2.2\"\nremote_as = 65002\n",
+        )
+        .unwrap();
+        fs::write(
+            peers_dir.join("b.toml"),
+            "[[peers]]\naddress = \"192.0.2.3\"\nremote_as = 65003\n",
+        )
+        .unwrap();
+        let main = tmp.path().join("focl.toml");
+        fs::write(
+            &main,
+            "include = [\"peers.d/*.toml\"]\n[global]\nasn = 65001\nrouter_id = \"192.0.2.1\"\n",
+        )
+        .unwrap();
+
+        let cfg = FoclConfig::load(&main).unwrap();
+        let addresses = cfg
+            .peers
+            .iter()
+            .map(|p| p.address.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(addresses, vec!["192.0.2.2", "192.0.2.3"]);
+
+        fs::write(
+            peers_dir.join("c.toml"),
+            "[[peers]]\naddress = \"192.0.2.2\"\nremote_as = 65009\n",
+        )
+        .unwrap();
+        let err = format!("{:#}", FoclConfig::load(&main).unwrap_err());
+        assert!(err.contains("a.toml") && err.contains("c.toml"), "{err}");
+    }
+
+    #[test]
+    fn includes_relative_to_a_directory_with_glob_characters() {
+        let tmp = tempfile::tempdir().unwrap();
+        let dir = tmp.path().join("site[1]");
+        fs::create_dir_all(dir.join("peers.d")).unwrap();
+        fs::write(
+            dir.join("peers.d/a.toml"),
+            "[[peers]]\naddress = \"192.0.2.2\"\nremote_as = 65002\n",
+        )
+        .unwrap();
+        let main = dir.join("focl.toml");
+        fs::write(
+            &main,
+            "include = [\"peers.d/*.toml\"]\n[global]\nasn = 65001\nrouter_id = \"192.0.2.1\"\n",
+        )
+        .unwrap();
+
+        let cfg = FoclConfig::load(&main).unwrap();
+        assert_eq!(cfg.peers.len(), 1);
+        assert_eq!(cfg.peers[0].address, "192.0.2.2");
+    }
+
+    #[test]
+    fn parses_peer_archive_overrides() {
+        let raw = r#"
+[global]
+asn = 65001
+router_id = "192.0.2.1"
+
+[[peers]]
+address = "192.0.2.2"
+remote_as = 65002
+
+[[peers]]
+address = "192.0.2.9"
+remote_as = 64512
+
+[peers.archive]
+updates = false
+
+[[peers]]
+address = "192.0.2.10"
+remote_as = 64513
+
+[peers.archive]
+collector_id = "focl01-private"
+view_name = "private"
+"#;
+
+        let cfg: FoclConfig = toml::from_str(raw).expect("toml should parse");
+        assert!(cfg.peers[0].archive.updates);
+        assert!(!cfg.peers[1].archive.updates);
+        assert!(cfg.peers[1].archive.state_changes);
+        assert_eq!(
+            cfg.peers[2].archive.collector_id.as_deref(),
+            Some("focl01-private")
+        );
+        assert_eq!(cfg.peers[2].archive.view_name.as_deref(), Some("private"));
+        cfg.validate().expect("overrides should validate");
+
+        let mut empty = cfg.clone();
+        empty.peers[2].archive.collector_id = Some(" ".to_string());
+        assert!(empty.validate().is_err());
+    }
+
+    #[test]
+    fn rejects_duplicate_and_conflicting_peers() {
+        let base = r#"
+[global]
+asn = 65001
+router_id = "192.0.2.1"
+"#;
+        let load = |peers: &str| -> Result<FoclConfig> {
+            let cfg: FoclConfig = toml::from_str(&format!("{base}{peers}"))?;
+            cfg.validate()?;
+            Ok(cfg)
+        };
+
+        let dup = load(
+            r#"
+[[peers]]
+address = "2001:db8::1"
+remote_as = 65002
+
+[[peers]]
+address = "2001:db8:0::1"
+remote_as = 65003
+"#,
+        );
+        assert!(format!("{:#}", dup.unwrap_err()).contains("more than once"));
+
+        let listeners = load(
+            r#"
+[[peers]]
+address = "192.0.2.2"
+remote_as = 65002
+passive = true
+
+[[peers]]
+address = "192.0.2.3"
+remote_as = 65003
+passive = true
+local_address = "192.0.2.1"
+"#,
+        );
+        assert!(format!("{:#}", listeners.unwrap_err()).contains("both listen"));
+
+        let listen_port = load(
+            r#"
+[[peers]]
+address = "192.0.2.2"
+remote_as = 65002
+passive = true
+
+[[peers]]
+address = "192.0.2.3"
+remote_as = 65003
+listen_port = 179
+"#,
+        );
+        assert!(format!("{:#}", listen_port.unwrap_err()).contains("both listen"));
+        let passive_listen_port = load(
+            r#"
+[[peers]]
+address = "192.0.2.2"
+remote_as = 65002
+passive = true
+listen_port = 1179
+"#,
+        );
+        assert!(format!("{:#}", passive_listen_port.unwrap_err()).contains("passive peer"));
+
+        let family = load(
+            r#"
+[[peers]]
+address = "192.0.2.2"
+remote_as = 65002
+local_address = "2001:db8::1"
+"#,
+        );
+        assert!(family.is_err());
+
+        let ok = load(
+            r#"
+[[peers]]
+address = "192.0.2.2"
+remote_as = 65002
+passive = true
+local_address = "192.0.2.1:1179"
+
+[[peers]]
+address = "192.0.2.3"
+remote_as = 65003
+passive = true
+"#,
+        );
+        assert!(ok.is_ok());
+    }
+
+    #[test]
+    fn expands_prefix_list_references() {
+        let raw = r#"
+[global]
+asn = 65001
+router_id = "192.0.2.1"
+
+[[prefix_lists.customers]]
+prefix = "203.0.113.0/24"
+
+[[prefix_lists.customers]]
+prefix = "198.51.100.0/22"
+le = 24
+
+[[prefixes]]
+prefix_list = "customers"
+
+[[prefixes]]
+network = "192.0.2.0/24"
+
+[[peers]]
+address = "192.0.2.2"
+remote_as = 65002
+export_prefix_list = "customers"
+"#;
+
+        let mut cfg: FoclConfig = toml::from_str(raw).expect("toml should parse");
+        cfg.validate().unwrap();
+        let networks = cfg
+            .originated_prefixes()
+            .unwrap()
+            .into_iter()
+            .map(|(n, _)| n.to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            networks,
+            vec!["203.0.113.0/24", "198.51.100.0/22", "192.0.2.0/24"]
+        );
+
+        cfg.peers[0].import_prefix_list = Some("missing".to_string());
+        assert!(cfg.validate().is_err());
+
+        cfg.remote_prefix_lists.insert(
+            "missing".to_string(),
+            RemotePrefixListConfig {
+                url: "https://lists.example.net/missing.json".to_string(),
+                refresh_secs: 300,
+                cache_dir: default_remote_prefix_list_cache_dir(),
+            },
+        );
+        cfg.validate().unwrap();
+        cfg.remote_prefix_lists.get_mut("missing").unwrap().url = "ftp://x".to_string();
+        assert!(cfg.validate().is_err());
+    }
+
+    #[test]
+    fn overrides_replace_file_settings() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("focl.toml");
+        fs::write(&path, "# everything comes from overrides\n").unwrap();
+
+        let overrides = ConfigOverrides {
+            asn: Some(65010),
+            router_id: Some("192.0.2.10".to_string()),
+            control_socket: Some(PathBuf::from("/run/focld.sock")),
+            archive_root: Some(PathBuf::from("/data/archive")),
+        };
+        let cfg = FoclConfig::load_with_overrides(&path, &overrides).unwrap();
+        assert_eq!(cfg.global.asn, 65010);
+        assert_eq!(cfg.global.router_id, "192.0.2.10");
+        assert_eq!(cfg.global.control_socket, PathBuf::from("/run/focld.sock"));
+        assert_eq!(cfg.archive.root, PathBuf::from("/data/archive"));
+        assert_eq!(cfg.archive.tmp_root, PathBuf::from("/data/archive/.tmp"));
+        assert_eq!(
+            cfg.archive.destinations[0].path,
+            Some(PathBuf::from("/data/archive"))
+        );
+        assert!(FoclConfig::load(&path).is_err());
+    }
+
+    #[test]
+    fn exports_json_schema() {
+        let schema = FoclConfig::json_schema();
+        assert_eq!(schema["title"], "FoclConfig");
+        assert!(schema["properties"]["global"].is_object());
+        assert!(schema["required"]
+            .as_array()
+            .unwrap()
+            .contains(&serde_json::json!("global")));
+    }
+
+    #[test]
+    fn effective_toml_expands_defaults_and_redacts_secrets() {
+        let cfg: FoclConfig = toml::from_str(
+            r#"
+            [global]
+            asn = 65001
+            router_id = "192.0.2.1"
+
+            [[peers]]
+            address = "192.0.2.2"
+            remote_as = 65002
+            password = "hunter2"
+
+            [peers.password_rotation]
+            password = "swordfish"
+            start = 1767225600
+            "#,
+        )
+        .unwrap();
+
+        let effective = cfg.effective_toml().unwrap();
+        assert!(!effective.contains("hunter2"), "{effective}");
+        assert!(!effective.contains("swordfish"), "{effective}");
+        assert!(effective.contains("ribs_interval_secs"));
+        let reloaded: FoclConfig = toml::from_str(&effective).unwrap();
+        reloaded.validate().unwrap();
+        assert_eq!(reloaded.peers[0].password.as_deref(), Some("<redacted>"));
+        assert_eq!(
+            reloaded.archive.ribs_interval_secs,
+            cfg.archive.ribs_interval_secs
+        );
+    }
+
+    #[test]
+    fn rotates_md5_keys_through_the_window() {
+        let peer: PeerConfig = toml::from_str(
+            r#"
+            address = "192.0.2.2"
+            remote_as = 65002
+            password = "old"
+
+            [password_rotation]
+            password = "new"
+            start = 1000
+            window_secs = 600
+            "#,
+        )
+        .unwrap();
+        peer.password_rotation
+            .as_ref()
+            .unwrap()
+            .validate(&peer)
+            .unwrap();
+
+        assert_eq!(peer.md5_keys_at(999), vec!["old"]);
+        assert_eq!(peer.md5_keys_at(1000), vec!["new", "old"]);
+        assert_eq!(peer.md5_keys_at(1599), vec!["new", "old"]);
+        assert_eq!(peer.md5_keys_at(1600), vec!["new"]);
+
+        let unkeyed = PeerConfig {
+            password: None,
+            ..peer
+        };
+        assert!(unkeyed
+            .password_rotation
+            .as_ref()
+            .unwrap()
+            .validate(&unkeyed)
+            .is_err());
+    }
+
+    #[test]
+    fn rotates_tcp_ao_keys_through_the_window() {
+        let mut peer: PeerConfig = toml::from_str(
+            r#"
+            address = "192.0.2.2"
+            remote_as = 65002
+            password = "old"
+            tcp_ao_key_id = 1
+
+            [password_rotation]
+            password = "new"
+            start = 1000
+            window_secs = 600
+            tcp_ao_key_id = 2
+            "#,
+        )
+        .unwrap();
+        let rotation = peer.password_rotation.clone().unwrap();
+        rotation.validate(&peer).unwrap();
+
+        assert!(peer.md5_keys_at(1000).is_empty());
+        assert_eq!(peer.ao_keys_at(999), vec![(1, "old")]);
+        assert_eq!(peer.ao_keys_at(1000), vec![(1, "old"), (2, "new")]);
+        assert_eq!(peer.ao_keys_at(1600), vec![(2, "new")]);
+
+        peer.tcp_ao_key_id = Some(2);
+        assert!(rotation.validate(&peer).is_err());
+        peer.tcp_ao_key_id = None;
+        assert!(rotation.validate(&peer).is_err());
+    }
 }
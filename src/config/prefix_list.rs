@@ -0,0 +1,107 @@
+use anyhow::{bail, Context, Result};
+use ipnet::IpNet;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+
+/// One `[[prefix_lists.NAME]]` entry. Without `ge`/`le` only the exact prefix
+/// matches; with them, any more-specific whose length falls in `ge..=le`.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PrefixListEntry {
+    pub prefix: String,
+    #[serde(default)]
+    pub ge: Option<u8>,
+    #[serde(default)]
+    pub le: Option<u8>,
+}
+
+/// A compiled prefix list ready for matching.
+#[derive(Debug, Clone, Default)]
+pub struct PrefixList {
+    entries: Vec<CompiledEntry>,
+}
+
+#[derive(Debug, Clone)]
+struct CompiledEntry {
+    network: IpNet,
+    min_len: u8,
+    max_len: u8,
+}
+
+impl PrefixList {
+    pub fn compile(name: &str, entries: &[PrefixListEntry]) -> Result<Self> {
+        let entries = entries
+            .iter()
+            .map(|entry| {
+                let network = entry.prefix.parse::<IpNet>().with_context(|| {
+                    format!("prefix list {name} has invalid prefix {}", entry.prefix)
+                })?;
+                let len = network.prefix_len();
+                let max = network.max_prefix_len();
+                let min_len = entry.ge.unwrap_or(len);
+                let max_len = entry
+                    .le
+                    .unwrap_or(if entry.ge.is_some() { max } else { len });
+                if !(len <= min_len && min_len <= max_len && max_len <= max) {
+                    bail!(
+                        "prefix list {name} entry {} needs {len} <= ge <= le <= {max}",
+                        entry.prefix
+                    );
+                }
+                Ok(CompiledEntry {
+                    network: network.trunc(),
+                    min_len,
+                    max_len,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { entries })
+    }
+
+    pub fn matches(&self, prefix: &IpNet) -> bool {
+        self.entries.iter().any(|entry| {
+            let len = prefix.prefix_len();
+            entry.network.contains(prefix) && (entry.min_len..=entry.max_len).contains(&len)
+        })
+    }
+
+    /// The base prefix of every entry, used when a list is originated.
+    pub fn networks(&self) -> impl Iterator<Item = IpNet> + '_ {
+        self.entries.iter().map(|entry| entry.network)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(prefix: &str, ge: Option<u8>, le: Option<u8>) -> PrefixListEntry {
+        PrefixListEntry {
+            prefix: prefix.to_string(),
+            ge,
+            le,
+        }
+    }
+
+    #[test]
+    fn matches_exact_and_ranges() {
+        let list = PrefixList::compile(
+            "test",
+            &[
+                entry("203.0.113.0/24", None, None),
+                entry("198.51.100.0/22", None, Some(24)),
+                entry("2001:db8::/32", Some(48), None),
+            ],
+        )
+        .unwrap();
+
+        let m = |p: &str| list.matches(&p.parse().unwrap());
+        assert!(m("203.0.113.0/24"));
+        assert!(!m("203.0.113.0/25"));
+        assert!(m("198.51.101.0/24"));
+        assert!(!m("198.51.100.0/25"));
+        assert!(m("2001:db8:1::/48"));
+        assert!(!m("2001:db8::/40"));
+
+        assert!(PrefixList::compile("bad", &[entry("10.0.0.0/16", Some(8), None)]).is_err());
+    }
+}
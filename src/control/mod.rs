@@ -1,25 +1,54 @@
+use std::time::Duration;
+
+use ipnet::IpNet;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 
-use crate::types::ControlRequest;
+use crate::archive::fetch::FetchFormat;
+use crate::archive::types::{default_sample_rate, RateWindows, StreamStatus};
+use crate::bgp::{BgpStats, ResetMode};
+use crate::config::RibSource;
+use crate::types::DiskLevel;
+use crate::types::{ControlRequest, EventTopic, PeerState};
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum CommandKind {
     Ping,
     DaemonStatus,
+    Health,
     Shutdown,
     Reload,
     PeerList,
     PeerShow,
     PeerReset,
+    PeerMaintenance,
+    PeerLogMessages,
+    PeerWait,
+    PeerTimers,
+    PeerStatsReset,
     RibSummary,
+    BgpStats,
+    BgpTop,
     RibIn,
     RibOut,
+    RibChurn,
+    AspathStats,
+    PrefixLoadMrt,
+    PrefixHistory,
+    BlackholeAdd,
+    BlackholeRemove,
+    BlackholeList,
     ArchiveStatus,
     ArchiveRollover,
     ArchiveSnapshotNow,
+    ArchivePeerIndex,
     ArchiveDestinations,
     ArchiveReplicatorRetry,
+    ArchiveReconcile,
+    ArchivePrioritize,
+    ArchiveFetch,
+    EventsHistory,
+    Batch,
     Unsupported,
 }
 
@@ -28,35 +57,389 @@ impl CommandKind {
         match req.cmd.as_str() {
             "ping" => Self::Ping,
             "daemon_status" => Self::DaemonStatus,
+            "health" => Self::Health,
             "shutdown" => Self::Shutdown,
             "reload" => Self::Reload,
             "peer_list" => Self::PeerList,
             "peer_show" => Self::PeerShow,
             "peer_reset" => Self::PeerReset,
+            "peer_maintenance" => Self::PeerMaintenance,
+            "peer_log_messages" => Self::PeerLogMessages,
+            "peer_wait" => Self::PeerWait,
+            "peer_timers" => Self::PeerTimers,
+            "peer_stats_reset" => Self::PeerStatsReset,
             "rib_summary" => Self::RibSummary,
+            "bgp_stats" => Self::BgpStats,
+            "bgp_top" => Self::BgpTop,
             "rib_in" => Self::RibIn,
             "rib_out" => Self::RibOut,
+            "rib_churn" => Self::RibChurn,
+            "aspath_stats" => Self::AspathStats,
+            "prefix_load_mrt" => Self::PrefixLoadMrt,
+            "prefix_history" => Self::PrefixHistory,
+            "blackhole_add" => Self::BlackholeAdd,
+            "blackhole_remove" => Self::BlackholeRemove,
+            "blackhole_list" => Self::BlackholeList,
             "archive_status" => Self::ArchiveStatus,
             "archive_rollover" => Self::ArchiveRollover,
             "archive_snapshot_now" => Self::ArchiveSnapshotNow,
+            "archive_peer_index" => Self::ArchivePeerIndex,
             "archive_destinations" => Self::ArchiveDestinations,
             "archive_replicator_retry" => Self::ArchiveReplicatorRetry,
+            "archive_reconcile" => Self::ArchiveReconcile,
+            "archive_prioritize" => Self::ArchivePrioritize,
+            "archive_fetch" => Self::ArchiveFetch,
+            "events_history" => Self::EventsHistory,
+            "batch" => Self::Batch,
             _ => Self::Unsupported,
         }
     }
 }
 
+/// Arguments for `peer_show`; `history` adds the peer's session timeline.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerShowArgs {
+    pub peer: String,
+    #[serde(default)]
+    pub history: bool,
+}
+
+impl PeerShowArgs {
+    pub fn from_json(value: &Value) -> Result<Self, serde_json::Error> {
+        serde_json::from_value(value.clone())
+    }
+}
+
+/// Arguments for `peer_timers`; without `peer` every peer is reported.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PeerTimersArgs {
+    #[serde(default)]
+    pub peer: Option<String>,
+}
+
+impl PeerTimersArgs {
+    pub fn from_json(value: &Value) -> Result<Self, serde_json::Error> {
+        if value.is_null() {
+            return Ok(Self::default());
+        }
+        serde_json::from_value(value.clone())
+    }
+}
+
+/// Arguments for `peer_stats_reset`; without `peer` every peer is reset.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PeerStatsResetArgs {
+    #[serde(default)]
+    pub peer: Option<String>,
+}
+
+impl PeerStatsResetArgs {
+    pub fn from_json(value: &Value) -> Result<Self, serde_json::Error> {
+        if value.is_null() {
+            return Ok(Self::default());
+        }
+        serde_json::from_value(value.clone())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerResetArgs {
+    pub peer: String,
+    #[serde(default)]
+    pub mode: ResetMode,
+}
+
+impl PeerResetArgs {
+    pub fn from_json(value: &Value) -> Result<Self, serde_json::Error> {
+        serde_json::from_value(value.clone())
+    }
+}
+
+/// Arguments for `peer_maintenance`; `keep_session` only applies when
+/// `enabled` is set.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerMaintenanceArgs {
+    pub peer: String,
+    pub enabled: bool,
+    #[serde(default)]
+    pub keep_session: bool,
+}
+
+impl PeerMaintenanceArgs {
+    pub fn from_json(value: &Value) -> Result<Self, serde_json::Error> {
+        serde_json::from_value(value.clone())
+    }
+}
+
+/// Arguments for `peer_log_messages`.
 #[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct PeerKeyArgs {
+pub struct PeerLogMessagesArgs {
     pub peer: String,
+    pub enabled: bool,
 }
 
-impl PeerKeyArgs {
+impl PeerLogMessagesArgs {
     pub fn from_json(value: &Value) -> Result<Self, serde_json::Error> {
         serde_json::from_value(value.clone())
     }
 }
 
+/// Arguments for `peer_wait`: block until `peer` is in `state`, for at most
+/// `timeout_secs`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PeerWaitArgs {
+    pub peer: String,
+    #[serde(default = "default_wait_state")]
+    pub state: PeerState,
+    #[serde(default)]
+    pub timeout_secs: Option<u64>,
+}
+
+fn default_wait_state() -> PeerState {
+    PeerState::Established
+}
+
+impl PeerWaitArgs {
+    pub const DEFAULT_TIMEOUT_SECS: u64 = 60;
+    pub const MAX_TIMEOUT_SECS: u64 = 3600;
+
+    pub fn from_json(value: &Value) -> Result<Self, serde_json::Error> {
+        serde_json::from_value(value.clone())
+    }
+
+    pub fn timeout(&self) -> Duration {
+        Duration::from_secs(
+            self.timeout_secs
+                .unwrap_or(Self::DEFAULT_TIMEOUT_SECS)
+                .min(Self::MAX_TIMEOUT_SECS),
+        )
+    }
+}
+
+/// Arguments for `events_subscribe`: stream only `topics`, or every event.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EventsSubscribeArgs {
+    #[serde(default)]
+    pub topics: Vec<EventTopic>,
+}
+
+impl EventsSubscribeArgs {
+    pub fn from_json(value: &Value) -> Result<Self, serde_json::Error> {
+        if value.is_null() {
+            return Ok(Self::default());
+        }
+        serde_json::from_value(value.clone())
+    }
+}
+
+/// Arguments for `bgp_top`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BgpTopArgs {
+    #[serde(default = "default_top_minutes")]
+    pub minutes: u32,
+    #[serde(default = "default_top")]
+    pub limit: usize,
+}
+
+fn default_top_minutes() -> u32 {
+    5
+}
+
+impl BgpTopArgs {
+    pub fn from_json(value: &Value) -> Result<Self, serde_json::Error> {
+        if value.is_null() {
+            return serde_json::from_value(json!({}));
+        }
+        serde_json::from_value(value.clone())
+    }
+}
+
+/// Arguments for `rib_churn`; without `window_mins` the full `[churn]`
+/// window is used.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RibChurnArgs {
+    #[serde(default)]
+    pub window_mins: Option<u32>,
+    #[serde(default = "default_churn_limit")]
+    pub limit: usize,
+}
+
+fn default_churn_limit() -> usize {
+    20
+}
+
+impl RibChurnArgs {
+    pub fn from_json(value: &Value) -> Result<Self, serde_json::Error> {
+        if value.is_null() {
+            return serde_json::from_value(json!({}));
+        }
+        serde_json::from_value(value.clone())
+    }
+}
+
+/// Arguments for `aspath_stats`. `prefix` narrows the origin listing (and
+/// new-origin detections) to one prefix; `since` is a Unix timestamp.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AspathStatsArgs {
+    #[serde(default)]
+    pub prefix: Option<IpNet>,
+    #[serde(default)]
+    pub since: Option<i64>,
+    #[serde(default = "default_churn_limit")]
+    pub limit: usize,
+}
+
+impl AspathStatsArgs {
+    pub fn from_json(value: &Value) -> Result<Self, serde_json::Error> {
+        if value.is_null() {
+            return serde_json::from_value(json!({}));
+        }
+        serde_json::from_value(value.clone())
+    }
+}
+
+/// Arguments for `rib_in` / `rib_out`.
+///
+/// Results are ordered by prefix; `cursor` is the last prefix of the previous
+/// page (as returned in `next_cursor`). With `stream`, every page is written
+/// as its own response line and the last one carries `"done": true`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RibQueryArgs {
+    pub peer: String,
+    #[serde(default)]
+    pub cursor: Option<String>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub stream: bool,
+}
+
+impl RibQueryArgs {
+    pub const DEFAULT_PAGE_SIZE: usize = 1000;
+    pub const MAX_PAGE_SIZE: usize = 10_000;
+
+    pub fn from_json(value: &Value) -> Result<Self, serde_json::Error> {
+        serde_json::from_value(value.clone())
+    }
+
+    pub fn page_size(&self) -> usize {
+        self.limit
+            .unwrap_or(Self::DEFAULT_PAGE_SIZE)
+            .clamp(1, Self::MAX_PAGE_SIZE)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RibPage {
+    pub prefixes: Vec<IpNet>,
+    pub next_cursor: Option<String>,
+}
+
+pub fn paginate_prefixes(
+    mut prefixes: Vec<IpNet>,
+    cursor: Option<&str>,
+    limit: usize,
+) -> Result<RibPage, String> {
+    prefixes.sort();
+    prefixes.dedup();
+    page_prefixes(&prefixes, cursor, limit)
+}
+
+/// One page of `prefixes`, which must already be sorted and deduplicated;
+/// the cursor is found by binary search.
+pub fn page_prefixes(
+    prefixes: &[IpNet],
+    cursor: Option<&str>,
+    limit: usize,
+) -> Result<RibPage, String> {
+    let start = match cursor {
+        Some(raw) => {
+            let after = raw
+                .parse::<IpNet>()
+                .map_err(|e| format!("invalid cursor {raw}: {e}"))?;
+            prefixes.partition_point(|p| *p <= after)
+        }
+        None => 0,
+    };
+    let end = start.saturating_add(limit).min(prefixes.len());
+    let page = prefixes[start..end].to_vec();
+    let next_cursor = if end < prefixes.len() {
+        page.last().map(|p| p.to_string())
+    } else {
+        None
+    };
+
+    Ok(RibPage {
+        prefixes: page,
+        next_cursor,
+    })
+}
+
+/// Arguments for `prefix_load_mrt`; an empty `peers` announces to every peer.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrefixLoadMrtArgs {
+    pub path: String,
+    #[serde(default)]
+    pub peers: Vec<String>,
+}
+
+impl PrefixLoadMrtArgs {
+    pub fn from_json(value: &Value) -> Result<Self, serde_json::Error> {
+        serde_json::from_value(value.clone())
+    }
+}
+
+/// Arguments for `prefix_history`; `more_specifics` also returns prefixes
+/// covered by `prefix`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PrefixHistoryArgs {
+    pub prefix: IpNet,
+    #[serde(default)]
+    pub more_specifics: bool,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+impl PrefixHistoryArgs {
+    pub fn from_json(value: &Value) -> Result<Self, serde_json::Error> {
+        serde_json::from_value(value.clone())
+    }
+}
+
+/// Arguments for `blackhole_add` / `blackhole_remove`. `expires_secs`
+/// overrides `[blackhole].expiry_secs` on add; 0 never expires.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BlackholeArgs {
+    pub prefix: IpNet,
+    #[serde(default)]
+    pub expires_secs: Option<u64>,
+}
+
+impl BlackholeArgs {
+    pub fn from_json(value: &Value) -> Result<Self, serde_json::Error> {
+        serde_json::from_value(value.clone())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BgpStatsArgs {
+    #[serde(default = "default_top")]
+    pub top: usize,
+}
+
+fn default_top() -> usize {
+    10
+}
+
+impl BgpStatsArgs {
+    pub fn from_json(value: &Value) -> Result<Self, serde_json::Error> {
+        if value.is_null() {
+            return serde_json::from_value(json!({}));
+        }
+        serde_json::from_value(value.clone())
+    }
+}
+
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[serde(rename_all = "snake_case")]
 pub enum ArchiveStream {
@@ -64,6 +447,73 @@ pub enum ArchiveStream {
     Ribs,
 }
 
+/// Arguments for `archive_snapshot_now`; `view_name` defaults to
+/// `[archive].rib_view_name`. With `rib_source` or `peers` the snapshot
+/// carries routes, from the Adj-RIB-In unless `rib_source` says otherwise;
+/// without either it holds the peer index table only, like scheduled ones.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ArchiveSnapshotNowArgs {
+    #[serde(default)]
+    pub view_name: Option<String>,
+    #[serde(default)]
+    pub rib_source: Option<RibSource>,
+    /// Peer addresses whose Adj-RIB-In to include; every peer when unset.
+    #[serde(default)]
+    pub peers: Option<Vec<String>>,
+    /// Unix seconds to stamp the snapshot with, and so the bucket it is
+    /// filed under; defaults to now.
+    #[serde(default)]
+    pub timestamp: Option<i64>,
+}
+
+impl ArchiveSnapshotNowArgs {
+    pub fn from_json(value: &Value) -> Result<Self, serde_json::Error> {
+        if value.is_null() {
+            return Ok(Self::default());
+        }
+        serde_json::from_value(value.clone())
+    }
+
+    /// Where the routes come from, or `None` for a peer index table only.
+    pub fn source(&self) -> Option<RibSource> {
+        match (self.rib_source, &self.peers) {
+            (Some(source), _) => Some(source),
+            (None, Some(_)) => Some(RibSource::AdjRibIn),
+            (None, None) => None,
+        }
+    }
+}
+
+/// How `archive_peer_index` returns the peer index table.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PeerIndexFormat {
+    /// The collector and its peers as JSON.
+    #[default]
+    Json,
+    /// One TABLE_DUMP_V2 PEER_INDEX_TABLE record, base64 in `data`.
+    Mrt,
+}
+
+/// Arguments for `archive_peer_index`; `view_name` defaults to
+/// `[archive].rib_view_name`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ArchivePeerIndexArgs {
+    #[serde(default)]
+    pub view_name: Option<String>,
+    #[serde(default)]
+    pub format: PeerIndexFormat,
+}
+
+impl ArchivePeerIndexArgs {
+    pub fn from_json(value: &Value) -> Result<Self, serde_json::Error> {
+        if value.is_null() {
+            return Ok(Self::default());
+        }
+        serde_json::from_value(value.clone())
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ArchiveRolloverArgs {
     pub stream: ArchiveStream,
@@ -75,18 +525,196 @@ impl ArchiveRolloverArgs {
     }
 }
 
+/// Arguments for `archive_reconcile`; `destination` is a key from
+/// `archive_destinations`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchiveReconcileArgs {
+    pub destination: String,
+    #[serde(default)]
+    pub repair: bool,
+}
+
+impl ArchiveReconcileArgs {
+    pub fn from_json(value: &Value) -> Result<Self, serde_json::Error> {
+        serde_json::from_value(value.clone())
+    }
+}
+
+/// Arguments for `archive_fetch`: either `segment`, a path under the archive
+/// root, or the `stream` segment covering `timestamp`. The segment is written
+/// back in `format` as responses carrying base64 `data` of at most
+/// `chunk_size` bytes each; the last one carries `"done": true`.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ArchiveFetchArgs {
+    #[serde(default)]
+    pub segment: Option<String>,
+    #[serde(default)]
+    pub stream: Option<ArchiveStream>,
+    #[serde(default)]
+    pub timestamp: Option<i64>,
+    #[serde(default)]
+    pub format: FetchFormat,
+    #[serde(default)]
+    pub chunk_size: Option<usize>,
+}
+
+impl ArchiveFetchArgs {
+    pub const DEFAULT_CHUNK_SIZE: usize = 256 * 1024;
+    pub const MAX_CHUNK_SIZE: usize = 4 * 1024 * 1024;
+
+    pub fn from_json(value: &Value) -> Result<Self, serde_json::Error> {
+        serde_json::from_value(value.clone())
+    }
+
+    pub fn chunk_size(&self) -> usize {
+        self.chunk_size
+            .unwrap_or(Self::DEFAULT_CHUNK_SIZE)
+            .clamp(1, Self::MAX_CHUNK_SIZE)
+    }
+
+    /// The segment to fetch; exactly one of `segment` and `stream` with
+    /// `timestamp` must be set.
+    pub fn target(&self) -> Result<FetchTarget, String> {
+        match (&self.segment, &self.stream, self.timestamp) {
+            (Some(segment), None, None) => Ok(FetchTarget::Segment(segment.clone())),
+            (None, Some(stream), Some(timestamp)) => {
+                Ok(FetchTarget::Interval(stream.clone(), timestamp))
+            }
+            (None, None, None) => Err("segment or stream and timestamp required".to_string()),
+            (Some(_), _, _) => Err("segment excludes stream and timestamp".to_string()),
+            (None, _, _) => Err("stream and timestamp go together".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FetchTarget {
+    Segment(String),
+    Interval(ArchiveStream, i64),
+}
+
+/// Arguments for `batch`: `requests` run one after another, or all at once
+/// with `concurrent`, and their responses come back in the same order.
+/// Commands that stream their results cannot be batched.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BatchArgs {
+    pub requests: Vec<ControlRequest>,
+    #[serde(default)]
+    pub concurrent: bool,
+}
+
+impl BatchArgs {
+    pub const MAX_REQUESTS: usize = 1000;
+
+    pub fn from_json(value: &Value) -> Result<Self, serde_json::Error> {
+        serde_json::from_value(value.clone())
+    }
+}
+
+/// Arguments for `archive_prioritize`; `segment` is a path under the
+/// archive root, absolute or relative to it.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ArchivePrioritizeArgs {
+    pub segment: String,
+}
+
+impl ArchivePrioritizeArgs {
+    pub fn from_json(value: &Value) -> Result<Self, serde_json::Error> {
+        serde_json::from_value(value.clone())
+    }
+}
+
+/// Result of `bgp_stats`.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BgpStatsResult {
+    pub stats: BgpStats,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ArchiveStatusResult {
     pub enabled: bool,
     pub collector_id: String,
-    pub updates_interval_secs: u32,
-    pub ribs_interval_secs: u32,
-    pub updates_open_path: Option<String>,
-    pub updates_record_count: u64,
-    pub ribs_last_path: Option<String>,
-    pub ribs_last_record_count: u64,
+    pub updates: StreamStatusResult,
+    pub ribs: StreamStatusResult,
     pub queued_replication_jobs: usize,
     pub replication_failures: u64,
+    #[serde(default)]
+    pub replication_deduplicated: u64,
+    #[serde(default)]
+    pub oldest_pending_replication_age_secs: Option<i64>,
+    #[serde(default)]
+    pub ingest_queue_depth: usize,
+    #[serde(default)]
+    pub ingest_records_shed: u64,
+    #[serde(default)]
+    pub disk_free_mb: Option<u64>,
+    #[serde(default)]
+    pub disk_level: DiskLevel,
+    #[serde(default)]
+    pub disk_records_dropped: u64,
+    #[serde(default)]
+    pub tap_clients: usize,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StreamStatusResult {
+    pub interval_secs: u32,
+    pub open_path: Option<String>,
+    pub open_record_count: u64,
+    pub segment_age_secs: Option<i64>,
+    pub next_rotation_secs: Option<i64>,
+    pub last_finalized_path: Option<String>,
+    pub last_finalized_ts: Option<i64>,
+    pub last_finalized_record_count: u64,
+    pub last_error: Option<String>,
+    pub last_error_ts: Option<i64>,
+    #[serde(default)]
+    pub consecutive_write_failures: u64,
+    #[serde(default)]
+    pub write_failures: u64,
+    #[serde(default)]
+    pub quarantined_segments: u64,
+    #[serde(default)]
+    pub records_dropped_backoff: u64,
+    #[serde(default)]
+    pub retry_in_secs: Option<u64>,
+    #[serde(default)]
+    pub duplicates_suppressed: u64,
+    #[serde(default = "default_sample_rate")]
+    pub sample_rate: u32,
+    #[serde(default)]
+    pub sampled_out: u64,
+    #[serde(default)]
+    pub records_per_sec: RateWindows,
+    #[serde(default)]
+    pub bytes_per_sec: RateWindows,
+}
+
+impl From<StreamStatus> for StreamStatusResult {
+    fn from(status: StreamStatus) -> Self {
+        Self {
+            interval_secs: status.interval_secs,
+            open_path: status.open_path.map(|p| p.display().to_string()),
+            open_record_count: status.open_record_count,
+            segment_age_secs: status.segment_age_secs,
+            next_rotation_secs: status.next_rotation_secs,
+            last_finalized_path: status.last_finalized_path.map(|p| p.display().to_string()),
+            last_finalized_ts: status.last_finalized_ts,
+            last_finalized_record_count: status.last_finalized_record_count,
+            last_error: status.last_error,
+            last_error_ts: status.last_error_ts,
+            consecutive_write_failures: status.consecutive_write_failures,
+            write_failures: status.write_failures,
+            quarantined_segments: status.quarantined_segments,
+            records_dropped_backoff: status.records_dropped_backoff,
+            retry_in_secs: status.retry_in_secs,
+            duplicates_suppressed: status.duplicates_suppressed,
+            sample_rate: status.sample_rate,
+            sampled_out: status.sampled_out,
+            records_per_sec: status.records_per_sec,
+            bytes_per_sec: status.bytes_per_sec,
+        }
+    }
 }
 
 impl ArchiveStatusResult {
@@ -113,3 +741,48 @@ impl ArchiveDestinationsResult {
         json!(self)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot_args_pick_a_rib_source() {
+        let args = ArchiveSnapshotNowArgs::from_json(&Value::Null).unwrap();
+        assert_eq!(args.source(), None);
+
+        let args = ArchiveSnapshotNowArgs::from_json(&json!({"peers": ["192.0.2.2"]})).unwrap();
+        assert_eq!(args.source(), Some(RibSource::AdjRibIn));
+
+        let args = ArchiveSnapshotNowArgs::from_json(
+            &json!({"rib_source": "loc_rib", "timestamp": 1_700_000_000}),
+        )
+        .unwrap();
+        assert_eq!(args.source(), Some(RibSource::LocRib));
+        assert_eq!(args.timestamp, Some(1_700_000_000));
+
+        let args = ArchivePeerIndexArgs::from_json(&Value::Null).unwrap();
+        assert_eq!((args.view_name, args.format), (None, PeerIndexFormat::Json));
+        let args = ArchivePeerIndexArgs::from_json(&json!({"format": "mrt"})).unwrap();
+        assert_eq!(args.format, PeerIndexFormat::Mrt);
+    }
+
+    #[test]
+    fn paginates_prefixes_with_cursor() {
+        let prefixes = ["10.0.2.0/24", "10.0.0.0/24", "10.0.1.0/24"]
+            .iter()
+            .map(|p| p.parse::<IpNet>().unwrap())
+            .collect::<Vec<_>>();
+
+        let first = paginate_prefixes(prefixes.clone(), None, 2).unwrap();
+        assert_eq!(first.prefixes.len(), 2);
+        assert_eq!(first.next_cursor.as_deref(), Some("10.0.1.0/24"));
+
+        let second = paginate_prefixes(prefixes, first.next_cursor.as_deref(), 2).unwrap();
+        assert_eq!(
+            second.prefixes,
+            vec!["10.0.2.0/24".parse::<IpNet>().unwrap()]
+        );
+        assert_eq!(second.next_cursor, None);
+    }
+}
@@ -0,0 +1,282 @@
+//! `[alerts]`: a small notification sink that turns event-bus traffic into
+//! Slack or email alerts for the failures an operator must act on.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Context, Result};
+use reqwest::header::CONTENT_TYPE;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::config::{AlertsConfig, EmailAlertConfig};
+use crate::types::{Event, PeerState, Subscription};
+
+const SEND_TIMEOUT: Duration = Duration::from_secs(30);
+const PEER_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// One notification; `key` identifies it for repeat suppression.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Alert {
+    key: String,
+    text: String,
+}
+
+/// Applies the `[alerts]` rules to events as they arrive.
+#[derive(Debug)]
+struct Rules {
+    cfg: AlertsConfig,
+    failures: VecDeque<Instant>,
+    up: HashSet<String>,
+    down_since: HashMap<String, Instant>,
+    alerted_down: HashSet<String>,
+    last_sent: HashMap<String, Instant>,
+}
+
+impl Rules {
+    fn new(cfg: AlertsConfig) -> Self {
+        Self {
+            cfg,
+            failures: VecDeque::new(),
+            up: HashSet::new(),
+            down_since: HashMap::new(),
+            alerted_down: HashSet::new(),
+            last_sent: HashMap::new(),
+        }
+    }
+
+    fn observe(&mut self, event: &Event, now: Instant) -> Option<Alert> {
+        match event {
+            Event::ArchiveReplicationFailed {
+                destination, error, ..
+            } if self.cfg.replication_failures > 0 => {
+                let window = Duration::from_secs(self.cfg.replication_window_mins * 60);
+                self.failures.push_back(now);
+                while self
+                    .failures
+                    .front()
+                    .is_some_and(|at| now.duration_since(*at) > window)
+                {
+                    self.failures.pop_front();
+                }
+                if self.failures.len() < self.cfg.replication_failures as usize {
+                    return None;
+                }
+                let text = format!(
+                    "{} replication failures in the last {} min; latest to {destination}: {error}",
+                    self.failures.len(),
+                    self.cfg.replication_window_mins
+                );
+                self.throttled("replication_failures".to_string(), text, now)
+            }
+            Event::ArchiveSnapshotFailed { error } if self.cfg.snapshot_failed => self.throttled(
+                "snapshot_failed".to_string(),
+                format!("RIB snapshot failed: {error}"),
+                now,
+            ),
+            Event::PrefixHijackSuspected {
+                peer,
+                prefix,
+                announced,
+                origin,
+                as_path,
+            } if self.cfg.hijack => self.throttled(
+                format!("hijack:{prefix}:{origin}"),
+                format!(
+                    "possible hijack of {prefix}: {} originated by AS{origin} via peer {peer}, path {as_path:?}",
+                    announced.join(", ")
+                ),
+                now,
+            ),
+            Event::PeerState { peer, state } if self.cfg.peer_down_mins > 0 => {
+                if matches!(state, PeerState::Established) {
+                    self.up.insert(peer.clone());
+                    self.down_since.remove(peer);
+                    // Recovery notices are never throttled.
+                    return self.alerted_down.remove(peer).then(|| Alert {
+                        key: format!("peer_up:{peer}"),
+                        text: format!("peer {peer} is back up"),
+                    });
+                }
+                if self.up.remove(peer) {
+                    self.down_since.insert(peer.clone(), now);
+                }
+                None
+            }
+            _ => None,
+        }
+    }
+
+    /// Peers down longer than `peer_down_mins`, each reported once per outage.
+    fn check_peers(&mut self, now: Instant) -> Vec<Alert> {
+        let threshold = Duration::from_secs(self.cfg.peer_down_mins * 60);
+        let expired = self
+            .down_since
+            .iter()
+            .filter(|(_, since)| now.duration_since(**since) >= threshold)
+            .map(|(peer, _)| peer.clone())
+            .collect::<Vec<_>>();
+        expired
+            .into_iter()
+            .map(|peer| {
+                self.down_since.remove(&peer);
+                self.alerted_down.insert(peer.clone());
+                Alert {
+                    key: format!("peer_down:{peer}"),
+                    text: format!(
+                        "peer {peer} has been down for over {} min",
+                        self.cfg.peer_down_mins
+                    ),
+                }
+            })
+            .collect()
+    }
+
+    fn throttled(&mut self, key: String, text: String, now: Instant) -> Option<Alert> {
+        let repeat = Duration::from_secs(self.cfg.repeat_mins * 60);
+        if let Some(last) = self.last_sent.get(&key) {
+            if now.duration_since(*last) < repeat {
+                return None;
+            }
+        }
+        self.last_sent.insert(key.clone(), now);
+        Some(Alert { key, text })
+    }
+}
+
+/// Send alerts for events on `subscription` until the bus closes. `source`
+/// names this collector in every message.
+pub fn spawn(
+    cfg: AlertsConfig,
+    source: String,
+    mut subscription: Subscription,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let client = match reqwest::Client::builder().timeout(SEND_TIMEOUT).build() {
+            Ok(client) => client,
+            Err(err) => {
+                tracing::error!(error=%err, "failed building HTTP client for alerts");
+                return;
+            }
+        };
+        let mut rules = Rules::new(cfg.clone());
+        let mut ticker = tokio::time::interval(PEER_CHECK_INTERVAL);
+        loop {
+            let alerts = tokio::select! {
+                envelope = subscription.recv() => match envelope {
+                    Some(envelope) => rules.observe(&envelope.event, Instant::now()).into_iter().collect(),
+                    None => break,
+                },
+                _ = ticker.tick() => rules.check_peers(Instant::now()),
+            };
+            for alert in alerts {
+                let cfg = cfg.clone();
+                let client = client.clone();
+                let text = format!("[{source}] {}", alert.text);
+                tokio::spawn(async move {
+                    if let Err(err) = deliver(&cfg, &client, &text).await {
+                        tracing::warn!(alert = alert.key, error=%err, "failed sending alert");
+                    }
+                });
+            }
+        }
+    })
+}
+
+async fn deliver(cfg: &AlertsConfig, client: &reqwest::Client, text: &str) -> Result<()> {
+    if let Some(url) = &cfg.slack_webhook {
+        let body = serde_json::to_vec(&serde_json::json!({ "text": text }))?;
+        client
+            .post(url)
+            .header(CONTENT_TYPE, "application/json")
+            .body(body)
+            .send()
+            .await?
+            .error_for_status()
+            .context("slack webhook")?;
+    }
+    if let Some(email) = &cfg.email {
+        tokio::time::timeout(SEND_TIMEOUT, send_mail(email, text))
+            .await
+            .context("sendmail timed out")??;
+    }
+    Ok(())
+}
+
+async fn send_mail(email: &EmailAlertConfig, text: &str) -> Result<()> {
+    let message = format!(
+        "From: {}\nTo: {}\nSubject: focl alert: {}\n\n{text}\n",
+        email.from,
+        email.to.join(", "),
+        text.lines().next().unwrap_or_default()
+    );
+    let mut child = Command::new(&email.sendmail)
+        .arg("-t")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()
+        .with_context(|| format!("failed running {}", email.sendmail.display()))?;
+    let mut stdin = child.stdin.take().expect("sendmail stdin is piped");
+    stdin.write_all(message.as_bytes()).await?;
+    drop(stdin);
+    let status = child.wait().await?;
+    if !status.success() {
+        bail!("{} exited with {status}", email.sendmail.display());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alerts_on_failure_bursts_and_long_peer_outages() {
+        let cfg = AlertsConfig {
+            replication_failures: 2,
+            peer_down_mins: 5,
+            ..AlertsConfig::default()
+        };
+        let mut rules = Rules::new(cfg);
+        let start = Instant::now();
+        let at = |mins: u64| start + Duration::from_secs(mins * 60);
+        let failed = Event::ArchiveReplicationFailed {
+            destination: "s3:archive".to_string(),
+            path: "updates.gz".to_string(),
+            error: "timeout".to_string(),
+        };
+
+        // Two failures more than the window apart do not alert.
+        assert!(rules.observe(&failed, at(0)).is_none());
+        assert!(rules.observe(&failed, at(61)).is_none());
+        let alert = rules.observe(&failed, at(62)).unwrap();
+        assert!(alert.text.starts_with("2 replication failures"));
+        // Repeats are held back for repeat_mins.
+        assert!(rules.observe(&failed, at(63)).is_none());
+        assert!(rules.observe(&failed, at(130)).is_none());
+        assert!(rules.observe(&failed, at(131)).is_some());
+
+        let peer = |state| Event::PeerState {
+            peer: "192.0.2.2".to_string(),
+            state,
+        };
+        // A peer that never came up is not reported.
+        assert!(rules.observe(&peer(PeerState::Active), at(0)).is_none());
+        assert!(rules.check_peers(at(10)).is_empty());
+
+        rules.observe(&peer(PeerState::Established), at(10));
+        rules.observe(&peer(PeerState::Idle), at(11));
+        assert!(rules.check_peers(at(15)).is_empty());
+        let down = rules.check_peers(at(16));
+        assert_eq!(down.len(), 1);
+        assert_eq!(down[0].key, "peer_down:192.0.2.2");
+        assert!(rules.check_peers(at(30)).is_empty());
+
+        let up = rules
+            .observe(&peer(PeerState::Established), at(31))
+            .unwrap();
+        assert_eq!(up.text, "peer 192.0.2.2 is back up");
+    }
+}
@@ -0,0 +1,174 @@
+//! `[[hooks]]`: external programs run on selected events, for pagers and
+//! custom automation that do not want a full control-socket client.
+
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+
+use crate::config::HookConfig;
+use crate::types::{Event, EventEnvelope, PeerState, Subscription};
+
+/// Run every hook subscribed to each event on `subscription` until the bus
+/// closes. Hooks run concurrently and never block the bus.
+pub fn spawn(
+    hooks: Vec<HookConfig>,
+    mut subscription: Subscription,
+) -> tokio::task::JoinHandle<()> {
+    tokio::spawn(async move {
+        let mut established = HashMap::new();
+        while let Some(envelope) = subscription.recv().await {
+            let names = hook_names(&envelope, &mut established);
+            for hook in &hooks {
+                let Some(name) = names
+                    .iter()
+                    .rev()
+                    .copied()
+                    .find(|name| hook.events.iter().any(|e| e == name))
+                else {
+                    continue;
+                };
+                let hook = hook.clone();
+                let envelope = envelope.clone();
+                tokio::spawn(async move {
+                    if let Err(err) = run(&hook, name, &envelope).await {
+                        tracing::warn!(command=%hook.command.display(), error=%err, "event hook failed");
+                    }
+                });
+            }
+        }
+    })
+}
+
+/// The hook names an event answers to: its own type, plus `peer_up` or
+/// `peer_down` when a peer enters or leaves Established.
+pub(super) fn hook_names(
+    envelope: &EventEnvelope,
+    established: &mut HashMap<String, bool>,
+) -> Vec<&'static str> {
+    let mut names = vec![event_name(&envelope.event)];
+    if let Event::PeerState { peer, state } = &envelope.event {
+        let up = matches!(state, PeerState::Established);
+        let was_up = established.insert(peer.clone(), up).unwrap_or(false);
+        match (was_up, up) {
+            (false, true) => names.push("peer_up"),
+            (true, false) => names.push("peer_down"),
+            _ => {}
+        }
+    }
+    names
+}
+
+fn event_name(event: &Event) -> &'static str {
+    match event {
+        Event::PeerState { .. } => "peer_state",
+        Event::ArchiveSegmentOpened { .. } => "archive_segment_opened",
+        Event::ArchiveSegmentFinalized { .. } => "archive_segment_finalized",
+        Event::ArchiveReplicationSucceeded { .. } => "archive_replication_succeeded",
+        Event::ArchiveReplicationFailed { .. } => "archive_replication_failed",
+        Event::ArchiveRecordsShed { .. } => "archive_records_shed",
+        Event::ArchiveAuditCompleted { .. } => "archive_audit_completed",
+        Event::ArchiveDiskPressure { .. } => "archive_disk_pressure",
+        Event::ArchiveSnapshotFailed { .. } => "archive_snapshot_failed",
+        Event::ArchiveRibGap { .. } => "archive_rib_gap",
+        Event::ArchiveWriterFailed { .. } => "archive_writer_failed",
+        Event::ArchiveWriterRecovered { .. } => "archive_writer_recovered",
+        Event::RouteLeakSuspected { .. } => "route_leak_suspected",
+        Event::PrefixHijackSuspected { .. } => "prefix_hijack_suspected",
+        Event::OriginChanged { .. } => "origin_changed",
+        Event::PrefixHealthChanged { .. } => "prefix_health_changed",
+        Event::EventsDropped { .. } => "events_dropped",
+    }
+}
+
+/// `name` is the hook event that matched, passed as `FOCL_EVENT`.
+async fn run(hook: &HookConfig, name: &str, envelope: &EventEnvelope) -> Result<()> {
+    let input = serde_json::to_vec(envelope)?;
+    run_program(
+        Command::new(&hook.command)
+            .args(&hook.args)
+            .env("FOCL_EVENT", name),
+        &input,
+        hook.timeout_secs,
+    )
+    .await
+}
+
+/// Run `command` with `input` on stdin, killing it after `timeout_secs`.
+pub(super) async fn run_program(
+    command: &mut Command,
+    input: &[u8],
+    timeout_secs: u64,
+) -> Result<()> {
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .kill_on_drop(true)
+        .spawn()?;
+    let mut stdin = child.stdin.take().expect("program stdin is piped");
+    let timeout = Duration::from_secs(timeout_secs);
+    let status = tokio::time::timeout(timeout, async {
+        // A program that ignores stdin may exit before reading it all.
+        let _ = stdin.write_all(input).await;
+        drop(stdin);
+        child.wait().await
+    })
+    .await
+    .map_err(|_| anyhow!("timed out after {timeout_secs}s"))??;
+    if !status.success() {
+        bail!("exited with {status}");
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::EventSubscribers;
+    use tokio::sync::broadcast;
+
+    #[tokio::test]
+    async fn runs_hooks_for_peer_up_and_down_with_event_on_stdin() {
+        let tmp = tempfile::tempdir().unwrap();
+        let out = tmp.path().join("hook.log");
+        let hook = HookConfig {
+            events: vec!["peer_up".to_string(), "peer_down".to_string()],
+            command: "sh".into(),
+            args: vec![
+                "-c".to_string(),
+                format!(
+                    "{{ printf '%s ' \"$FOCL_EVENT\"; cat; echo; }} >> {}",
+                    out.display()
+                ),
+            ],
+            timeout_secs: 5,
+        };
+        let (tx, rx) = broadcast::channel(16);
+        let task = spawn(
+            vec![hook],
+            EventSubscribers::new().subscribe("hooks", rx, &[]),
+        );
+
+        for state in [PeerState::Active, PeerState::Established, PeerState::Idle] {
+            tx.send(EventEnvelope::new(Event::PeerState {
+                peer: "192.0.2.2".to_string(),
+                state,
+            }))
+            .unwrap();
+            tokio::time::sleep(Duration::from_millis(200)).await;
+        }
+        drop(tx);
+        task.await.unwrap();
+
+        let log = std::fs::read_to_string(&out).unwrap();
+        let lines = log.lines().collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with("peer_up {"));
+        assert!(lines[0].contains("\"established\""));
+        assert!(lines[1].starts_with("peer_down {"));
+        assert!(lines[1].contains("\"idle\""));
+    }
+}
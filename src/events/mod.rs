@@ -0,0 +1,315 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use anyhow::{Context, Result};
+use chrono::Utc;
+use rusqlite::{params_from_iter, Connection, ToSql};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use tokio::sync::oneshot;
+
+use crate::types::{EventEnvelope, Subscription};
+
+pub mod alerts;
+pub mod hooks;
+pub mod rules;
+
+const PRUNE_EVERY: u64 = 100;
+
+/// Bounded SQLite log of every event published on the daemon event bus.
+#[derive(Debug, Clone)]
+pub struct EventStore {
+    db_path: PathBuf,
+    max_events: u64,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct EventQuery {
+    #[serde(default)]
+    pub since: Option<i64>,
+    #[serde(default)]
+    pub until: Option<i64>,
+    #[serde(default, rename = "type")]
+    pub event_type: Option<String>,
+    #[serde(default)]
+    pub peer: Option<String>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+impl EventQuery {
+    pub fn from_json(value: &Value) -> Result<Self, serde_json::Error> {
+        if value.is_null() {
+            return Ok(Self::default());
+        }
+        serde_json::from_value(value.clone())
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StoredEvent {
+    /// Row in the history store. It keeps increasing across restarts, unlike
+    /// the bus `seq` inside `event`, which matches what live subscribers saw.
+    pub id: i64,
+    pub ts: i64,
+    pub event: Value,
+}
+
+impl EventStore {
+    pub const DEFAULT_LIMIT: usize = 100;
+    pub const MAX_LIMIT: usize = 10_000;
+
+    pub fn new(db_path: &Path, max_events: u64) -> Result<Self> {
+        if let Some(parent) = db_path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed creating events dir {}", parent.display()))?;
+        }
+
+        let store = Self {
+            db_path: db_path.to_path_buf(),
+            max_events,
+        };
+        store.init()?;
+        Ok(store)
+    }
+
+    fn open(&self) -> Result<Connection> {
+        let conn = Connection::open(&self.db_path)
+            .with_context(|| format!("failed opening events db {}", self.db_path.display()))?;
+        Ok(conn)
+    }
+
+    fn init(&self) -> Result<()> {
+        let conn = self.open()?;
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS events (
+                seq INTEGER PRIMARY KEY AUTOINCREMENT,
+                ts INTEGER NOT NULL,
+                event_type TEXT NOT NULL,
+                peer TEXT,
+                payload TEXT NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS idx_events_ts ON events(ts);
+            CREATE INDEX IF NOT EXISTS idx_events_type ON events(event_type, ts);
+            ",
+        )?;
+        Ok(())
+    }
+
+    pub fn record(&self, envelope: &EventEnvelope) -> Result<i64> {
+        let payload = serde_json::to_value(envelope)?;
+        let event_type = payload
+            .get("event")
+            .and_then(Value::as_str)
+            .unwrap_or("unknown")
+            .to_string();
+        let peer = payload
+            .get("payload")
+            .and_then(|p| p.get("peer"))
+            .and_then(Value::as_str)
+            .map(str::to_string);
+
+        let conn = self.open()?;
+        conn.execute(
+            "INSERT INTO events (ts, event_type, peer, payload) VALUES (?, ?, ?, ?)",
+            rusqlite::params![
+                Utc::now().timestamp(),
+                event_type,
+                peer,
+                payload.to_string()
+            ],
+        )?;
+        let seq = conn.last_insert_rowid();
+
+        if (seq as u64).is_multiple_of(PRUNE_EVERY) {
+            conn.execute(
+                "DELETE FROM events WHERE seq <= ?",
+                rusqlite::params![seq - self.max_events as i64],
+            )?;
+        }
+
+        Ok(seq)
+    }
+
+    pub fn query(&self, query: &EventQuery) -> Result<Vec<StoredEvent>> {
+        let mut sql = String::from("SELECT seq, ts, payload FROM events WHERE 1 = 1");
+        let mut args: Vec<Box<dyn ToSql>> = Vec::new();
+
+        if let Some(since) = query.since {
+            sql.push_str(" AND ts >= ?");
+            args.push(Box::new(since));
+        }
+        if let Some(until) = query.until {
+            sql.push_str(" AND ts <= ?");
+            args.push(Box::new(until));
+        }
+        if let Some(event_type) = &query.event_type {
+            sql.push_str(" AND event_type = ?");
+            args.push(Box::new(event_type.clone()));
+        }
+        if let Some(peer) = &query.peer {
+            sql.push_str(" AND peer = ?");
+            args.push(Box::new(peer.clone()));
+        }
+
+        let limit = query
+            .limit
+            .unwrap_or(Self::DEFAULT_LIMIT)
+            .min(Self::MAX_LIMIT);
+        sql.push_str(" ORDER BY seq DESC LIMIT ?");
+        args.push(Box::new(limit as i64));
+
+        let conn = self.open()?;
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(params_from_iter(args.iter()), |row| {
+            Ok((
+                row.get::<_, i64>(0)?,
+                row.get::<_, i64>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?;
+
+        let mut events = rows
+            .map(|row| {
+                let (id, ts, payload) = row?;
+                Ok(StoredEvent {
+                    id,
+                    ts,
+                    event: serde_json::from_str(&payload)?,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        events.reverse();
+        Ok(events)
+    }
+
+    /// Persist every event received on `subscription` until the channel
+    /// closes or `stop` fires; on `stop`, the events already buffered are
+    /// persisted before the task ends. Gaps are persisted as `events_dropped`
+    /// notices.
+    pub fn spawn_recorder(
+        self,
+        mut subscription: Subscription,
+        mut stop: oneshot::Receiver<()>,
+    ) -> tokio::task::JoinHandle<()> {
+        tokio::spawn(async move {
+            loop {
+                tokio::select! {
+                    biased;
+                    envelope = subscription.recv() => match envelope {
+                        Some(envelope) => self.record_or_log(&envelope),
+                        None => return,
+                    },
+                    _ = &mut stop => break,
+                }
+            }
+            while let Some(envelope) = subscription.try_recv() {
+                self.record_or_log(&envelope);
+            }
+        })
+    }
+
+    fn record_or_log(&self, envelope: &EventEnvelope) {
+        if let Err(err) = self.record(envelope) {
+            tracing::error!(error=%err, "failed persisting event");
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Event, PeerState};
+
+    #[test]
+    fn records_and_filters_events() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = EventStore::new(&tmp.path().join("events.sqlite"), 1000).unwrap();
+
+        store
+            .record(&EventEnvelope::new(Event::PeerState {
+                peer: "192.0.2.2".to_string(),
+                state: PeerState::Established,
+            }))
+            .unwrap();
+        store
+            .record(&EventEnvelope::new(Event::ArchiveReplicationSucceeded {
+                destination: "local:/tmp".to_string(),
+                path: "/tmp/a.gz".to_string(),
+            }))
+            .unwrap();
+
+        let all = store.query(&EventQuery::default()).unwrap();
+        assert_eq!(all.len(), 2);
+        assert!(all[0].id < all[1].id);
+
+        let peer_only = store
+            .query(&EventQuery {
+                peer: Some("192.0.2.2".to_string()),
+                ..EventQuery::default()
+            })
+            .unwrap();
+        assert_eq!(peer_only.len(), 1);
+
+        let typed = store
+            .query(&EventQuery {
+                event_type: Some("archive_replication_succeeded".to_string()),
+                ..EventQuery::default()
+            })
+            .unwrap();
+        assert_eq!(typed.len(), 1);
+    }
+
+    #[test]
+    fn prunes_beyond_capacity() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = EventStore::new(&tmp.path().join("events.sqlite"), 10).unwrap();
+
+        for _ in 0..PRUNE_EVERY {
+            store
+                .record(&EventEnvelope::new(Event::PeerState {
+                    peer: "192.0.2.2".to_string(),
+                    state: PeerState::Active,
+                }))
+                .unwrap();
+        }
+
+        let all = store
+            .query(&EventQuery {
+                limit: Some(EventStore::MAX_LIMIT),
+                ..EventQuery::default()
+            })
+            .unwrap();
+        assert_eq!(all.len(), 10);
+    }
+
+    #[tokio::test]
+    async fn recorder_drains_buffered_events_on_stop() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = EventStore::new(&tmp.path().join("events.sqlite"), 1000).unwrap();
+        let bus = crate::types::EventBus::default();
+        let (stop, stop_rx) = oneshot::channel();
+        let task = store
+            .clone()
+            .spawn_recorder(bus.subscribe("event_history", &[]), stop_rx);
+
+        for _ in 0..100 {
+            bus.publish(Event::PeerState {
+                peer: "192.0.2.2".to_string(),
+                state: PeerState::Idle,
+            });
+        }
+        stop.send(()).unwrap();
+        task.await.unwrap();
+
+        let all = store
+            .query(&EventQuery {
+                limit: Some(EventStore::MAX_LIMIT),
+                ..EventQuery::default()
+            })
+            .unwrap();
+        assert_eq!(all.len(), 100);
+        assert_eq!(all[0].event["seq"], 1);
+    }
+}
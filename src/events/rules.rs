@@ -0,0 +1,463 @@
+//! `[[rules]]`: operator-defined alert rules matched against the event bus
+//! and every received UPDATE, e.g. "a more-specific of our prefix with a
+//! foreign origin", each firing a log line, a webhook, or a command.
+
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+use anyhow::{Context, Result};
+use ipnet::IpNet;
+use reqwest::header::CONTENT_TYPE;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tokio::process::Command;
+use tokio::sync::broadcast;
+
+use super::hooks::{hook_names, run_program};
+use crate::bgp::{BgpService, ReceivedUpdate};
+use crate::config::{PrefixMatch, RuleAction, RuleConfig, ROUTE_EVENTS};
+use crate::types::{Event, EventEnvelope, Subscription};
+
+/// Repeat-suppression entries kept before expired ones are pruned.
+const PRUNE_ABOVE: usize = 10_000;
+
+/// What rules are matched against: one event, or the announced or withdrawn
+/// prefixes of one received UPDATE.
+#[derive(Debug, Clone)]
+struct Subject {
+    /// Every event name this answers to, as for hooks.
+    names: Vec<&'static str>,
+    peer: Option<String>,
+    prefixes: Vec<IpNet>,
+    origin: Option<u32>,
+    detail: Value,
+}
+
+/// A rule match, as logged, posted, or written to the command's stdin.
+#[derive(Debug, Clone, Serialize)]
+struct Fired {
+    rule: String,
+    collector: String,
+    event: &'static str,
+    peer: Option<String>,
+    /// The subject's prefixes that matched the rule.
+    prefixes: Vec<IpNet>,
+    origin: Option<u32>,
+    /// The event envelope, or the route's AS path.
+    detail: Value,
+}
+
+#[derive(Debug)]
+struct Rule {
+    cfg: RuleConfig,
+    prefix: Option<IpNet>,
+}
+
+impl Rule {
+    fn matches_prefix(&self, candidate: &IpNet) -> bool {
+        let Some(prefix) = &self.prefix else {
+            return true;
+        };
+        match self.cfg.prefix_match {
+            PrefixMatch::Exact => candidate == prefix,
+            PrefixMatch::OrLonger => prefix.contains(candidate),
+            PrefixMatch::Longer => prefix.contains(candidate) && candidate != prefix,
+        }
+    }
+
+    fn matches_origin(&self, origin: Option<u32>) -> bool {
+        if self.cfg.origin.is_empty() && self.cfg.origin_not.is_empty() {
+            return true;
+        }
+        origin.is_some_and(|asn| {
+            (self.cfg.origin.is_empty() || self.cfg.origin.contains(&asn))
+                && !self.cfg.origin_not.contains(&asn)
+        })
+    }
+}
+
+#[derive(Debug)]
+struct Engine {
+    rules: Vec<Rule>,
+    collector: String,
+    established: HashMap<String, bool>,
+    /// Keyed by rule index, peer, and prefix.
+    last_fired: HashMap<(usize, Option<String>, Option<IpNet>), Instant>,
+}
+
+impl Engine {
+    fn new(rules: Vec<RuleConfig>, collector: String) -> Result<Self> {
+        let rules = rules
+            .into_iter()
+            .map(|cfg| {
+                Ok(Rule {
+                    prefix: cfg.prefix_net()?,
+                    cfg,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self {
+            rules,
+            collector,
+            established: HashMap::new(),
+            last_fired: HashMap::new(),
+        })
+    }
+
+    /// Whether any rule needs every received UPDATE.
+    fn wants_routes(&self) -> bool {
+        self.rules.iter().any(|rule| {
+            rule.cfg
+                .events
+                .iter()
+                .any(|e| ROUTE_EVENTS.contains(&e.as_str()))
+        })
+    }
+
+    fn event_subject(&mut self, envelope: &EventEnvelope) -> Subject {
+        let (peer, prefixes, origin) = match &envelope.event {
+            Event::PeerState { peer, .. } | Event::ArchiveRecordsShed { peer, .. } => {
+                (Some(peer.clone()), vec![], None)
+            }
+            Event::RouteLeakSuspected {
+                peer,
+                prefixes,
+                as_path,
+                ..
+            } => (
+                Some(peer.clone()),
+                parse_prefixes(prefixes),
+                as_path.last().copied(),
+            ),
+            Event::PrefixHijackSuspected {
+                peer,
+                announced,
+                origin,
+                ..
+            } => (Some(peer.clone()), parse_prefixes(announced), Some(*origin)),
+            Event::OriginChanged {
+                peer,
+                prefix,
+                new_origin,
+                ..
+            } => (
+                Some(peer.clone()),
+                prefix.parse().into_iter().collect(),
+                *new_origin,
+            ),
+            Event::PrefixHealthChanged { prefixes, .. } => (None, parse_prefixes(prefixes), None),
+            _ => (None, vec![], None),
+        };
+        Subject {
+            names: hook_names(envelope, &mut self.established),
+            peer,
+            prefixes,
+            origin,
+            detail: serde_json::to_value(envelope).unwrap_or(Value::Null),
+        }
+    }
+
+    fn route_subjects(update: &ReceivedUpdate) -> Vec<Subject> {
+        let as_path = update
+            .update
+            .attributes
+            .as_path()
+            .and_then(|path| path.to_u32_vec_opt(false))
+            .unwrap_or_default();
+        let mut subjects = Vec::with_capacity(2);
+        let announced = update.announced();
+        if !announced.is_empty() {
+            subjects.push(Subject {
+                names: vec!["route_announced"],
+                peer: Some(update.peer.clone()),
+                prefixes: announced,
+                origin: as_path.last().copied(),
+                detail: json!({ "as_path": as_path }),
+            });
+        }
+        let withdrawn = update.withdrawn();
+        if !withdrawn.is_empty() {
+            subjects.push(Subject {
+                names: vec!["route_withdrawn"],
+                peer: Some(update.peer.clone()),
+                prefixes: withdrawn,
+                origin: None,
+                detail: Value::Null,
+            });
+        }
+        subjects
+    }
+
+    /// The rules `subject` fires, with the prefixes each one matched. A rule
+    /// with a prefix condition fires only for matching prefixes not already
+    /// reported within its `repeat_secs`.
+    fn evaluate(&mut self, subject: &Subject, now: Instant) -> Vec<(usize, Fired)> {
+        if self.last_fired.len() > PRUNE_ABOVE {
+            let rules = &self.rules;
+            self.last_fired.retain(|(index, _, _), at| {
+                now.duration_since(*at) < Duration::from_secs(rules[*index].cfg.repeat_secs)
+            });
+        }
+
+        let mut fired = Vec::new();
+        for (index, rule) in self.rules.iter().enumerate() {
+            let Some(event) = subject
+                .names
+                .iter()
+                .rev()
+                .copied()
+                .find(|name| rule.cfg.events.iter().any(|e| e == name))
+            else {
+                continue;
+            };
+            if rule
+                .cfg
+                .peer
+                .as_ref()
+                .is_some_and(|peer| subject.peer.as_ref() != Some(peer))
+                || !rule.matches_origin(subject.origin)
+            {
+                continue;
+            }
+            let keys = if rule.prefix.is_some() {
+                subject
+                    .prefixes
+                    .iter()
+                    .filter(|p| rule.matches_prefix(p))
+                    .map(|p| Some(*p))
+                    .collect::<Vec<_>>()
+            } else {
+                vec![None]
+            };
+
+            let repeat = Duration::from_secs(rule.cfg.repeat_secs);
+            let mut matched = Vec::new();
+            let mut any = false;
+            for key in keys {
+                let key = (index, subject.peer.clone(), key);
+                if self
+                    .last_fired
+                    .get(&key)
+                    .is_some_and(|at| now.duration_since(*at) < repeat)
+                {
+                    continue;
+                }
+                any = true;
+                matched.extend(key.2);
+                self.last_fired.insert(key, now);
+            }
+            if !any {
+                continue;
+            }
+            fired.push((
+                index,
+                Fired {
+                    rule: rule.cfg.name.clone(),
+                    collector: self.collector.clone(),
+                    event,
+                    peer: subject.peer.clone(),
+                    prefixes: if rule.prefix.is_some() {
+                        matched
+                    } else {
+                        subject.prefixes.clone()
+                    },
+                    origin: subject.origin,
+                    detail: subject.detail.clone(),
+                },
+            ));
+        }
+        fired
+    }
+}
+
+fn parse_prefixes(prefixes: &[String]) -> Vec<IpNet> {
+    prefixes.iter().filter_map(|p| p.parse().ok()).collect()
+}
+
+/// Evaluate `rules` against events on `subscription`, and against every
+/// UPDATE `bgp` receives when a rule asks for route events, until the bus
+/// closes. Actions run concurrently and never block evaluation.
+pub fn spawn(
+    rules: Vec<RuleConfig>,
+    collector: String,
+    mut subscription: Subscription,
+    bgp: &BgpService,
+) -> Result<tokio::task::JoinHandle<()>> {
+    let mut engine = Engine::new(rules, collector)?;
+    let mut updates = engine.wants_routes().then(|| bgp.subscribe_received());
+    let client = reqwest::Client::builder()
+        .build()
+        .context("failed building HTTP client for rules")?;
+    Ok(tokio::spawn(async move {
+        loop {
+            let subjects = tokio::select! {
+                envelope = subscription.recv() => match envelope {
+                    Some(envelope) => vec![engine.event_subject(&envelope)],
+                    None => break,
+                },
+                received = async {
+                    match &mut updates {
+                        Some(updates) => updates.recv().await,
+                        None => std::future::pending().await,
+                    }
+                } => match received {
+                    Ok(update) => Engine::route_subjects(&update),
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        tracing::warn!(missed, "rules fell behind, updates not evaluated");
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => {
+                        updates = None;
+                        continue;
+                    }
+                },
+            };
+            for subject in subjects {
+                for (index, fired) in engine.evaluate(&subject, Instant::now()) {
+                    let rule = engine.rules[index].cfg.clone();
+                    let client = client.clone();
+                    tokio::spawn(async move {
+                        if let Err(err) = act(&rule, &client, &fired).await {
+                            tracing::warn!(rule = rule.name, error=%err, "rule action failed");
+                        }
+                    });
+                }
+            }
+        }
+    }))
+}
+
+async fn act(rule: &RuleConfig, client: &reqwest::Client, fired: &Fired) -> Result<()> {
+    match rule.action {
+        RuleAction::Log => {
+            tracing::warn!(
+                rule = fired.rule,
+                event = fired.event,
+                peer = fired.peer,
+                prefixes = ?fired.prefixes,
+                origin = fired.origin,
+                "rule matched"
+            );
+        }
+        RuleAction::Webhook => {
+            let url = rule.url.as_deref().context("webhook rule without url")?;
+            client
+                .post(url)
+                .timeout(Duration::from_secs(rule.timeout_secs))
+                .header(CONTENT_TYPE, "application/json")
+                .body(serde_json::to_vec(fired)?)
+                .send()
+                .await?
+                .error_for_status()?;
+        }
+        RuleAction::Command => {
+            let command = rule
+                .command
+                .as_ref()
+                .context("command rule without command")?;
+            run_program(
+                Command::new(command)
+                    .args(&rule.args)
+                    .env("FOCL_RULE", &rule.name)
+                    .env("FOCL_EVENT", fired.event),
+                &serde_json::to_vec(fired)?,
+                rule.timeout_secs,
+            )
+            .await
+            .with_context(|| command.display().to_string())?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::PeerState;
+
+    fn rule(toml: &str) -> RuleConfig {
+        toml::from_str(toml).unwrap()
+    }
+
+    #[test]
+    fn matches_more_specifics_with_foreign_origin_once_per_repeat() {
+        let mut engine = Engine::new(
+            vec![
+                rule(
+                    r#"
+                    name = "hijack"
+                    events = ["route_announced"]
+                    prefix = "192.0.2.0/24"
+                    prefix_match = "longer"
+                    origin_not = [64500]
+                    action = "log"
+                    "#,
+                ),
+                rule(
+                    r#"
+                    name = "peer-down"
+                    events = ["peer_down"]
+                    peer = "198.51.100.1"
+                    action = "log"
+                    "#,
+                ),
+            ],
+            "rrc00".to_string(),
+        )
+        .unwrap();
+        assert!(engine.wants_routes());
+        let start = Instant::now();
+        let route = |prefixes: &[&str], origin| Subject {
+            names: vec!["route_announced"],
+            peer: Some("198.51.100.1".to_string()),
+            prefixes: prefixes.iter().map(|p| p.parse().unwrap()).collect(),
+            origin: Some(origin),
+            detail: Value::Null,
+        };
+
+        // The covering prefix itself, and the right origin, do not match.
+        assert!(engine
+            .evaluate(&route(&["192.0.2.0/24"], 64666), start)
+            .is_empty());
+        assert!(engine
+            .evaluate(&route(&["192.0.2.0/25"], 64500), start)
+            .is_empty());
+
+        let fired = engine.evaluate(
+            &route(&["192.0.2.0/25", "192.0.2.128/25", "203.0.113.0/24"], 64666),
+            start,
+        );
+        assert_eq!(fired.len(), 1);
+        assert_eq!(fired[0].0, 0);
+        assert_eq!(fired[0].1.prefixes.len(), 2);
+        // Held back until repeat_secs passes.
+        assert!(engine
+            .evaluate(
+                &route(&["192.0.2.0/25"], 64666),
+                start + Duration::from_secs(60)
+            )
+            .is_empty());
+        assert_eq!(
+            engine
+                .evaluate(
+                    &route(&["192.0.2.0/25"], 64666),
+                    start + Duration::from_secs(301)
+                )
+                .len(),
+            1
+        );
+
+        let peer = |state| {
+            EventEnvelope::new(Event::PeerState {
+                peer: "198.51.100.1".to_string(),
+                state,
+            })
+        };
+        let up = engine.event_subject(&peer(PeerState::Established));
+        assert!(engine.evaluate(&up, start).is_empty());
+        let down = engine.event_subject(&peer(PeerState::Idle));
+        let fired = engine.evaluate(&down, start);
+        assert_eq!(fired.len(), 1);
+        assert_eq!(fired[0].1.event, "peer_down");
+    }
+}
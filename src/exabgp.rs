@@ -0,0 +1,497 @@
+//! ExaBGP process-API compatibility: `announce`/`withdraw route` commands are
+//! read from stdin or a FIFO, and received updates are written out in
+//! ExaBGP's JSON encoding, so existing ExaBGP scripts can drive focld.
+
+use std::net::IpAddr;
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use bgpkit_parser::models::{Afi, Community, MetaCommunity, Origin};
+use ipnet::IpNet;
+use serde_json::{json, Map, Value};
+use tokio::io::{AsyncBufReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+use crate::bgp::{BgpService, ReceivedUpdate};
+use crate::config::ExabgpConfig;
+
+/// Version reported in the `exabgp` field; scripts check the major version.
+const EXABGP_VERSION: &str = "4.0.1";
+const REOPEN_DELAY: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Command {
+    Announce {
+        network: IpNet,
+        next_hop: Option<IpAddr>,
+    },
+    Withdraw {
+        network: IpNet,
+    },
+}
+
+/// A parsed command plus the route attributes focld accepted but ignores.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedCommand {
+    pub command: Command,
+    pub ignored: Vec<String>,
+}
+
+/// Parse one ExaBGP API line. Blank lines and `#` comments yield `None`.
+///
+/// Supported: `announce route PREFIX [next-hop IP|self] [...]` and
+/// `withdraw route PREFIX [...]`. Other attributes are skipped and reported
+/// in [`ParsedCommand::ignored`].
+pub fn parse_command(line: &str) -> Result<Option<ParsedCommand>> {
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+        return Ok(None);
+    }
+
+    let mut tokens = line.split_whitespace();
+    let action = tokens.next().unwrap_or_default();
+    if action == "neighbor" {
+        bail!("neighbor-scoped commands are not supported; routes go to every peer");
+    }
+    if !matches!(action, "announce" | "withdraw") || tokens.next() != Some("route") {
+        bail!("unsupported command: {line}");
+    }
+    let prefix = tokens.next().context("missing prefix")?;
+    let network = prefix
+        .parse::<IpNet>()
+        .or_else(|_| prefix.parse::<IpAddr>().map(IpNet::from))
+        .with_context(|| format!("invalid prefix {prefix}"))?;
+
+    let mut next_hop = None;
+    let mut ignored = Vec::new();
+    while let Some(key) = tokens.next() {
+        let value = tokens
+            .next()
+            .with_context(|| format!("missing value for {key}"))?;
+        if value.starts_with('[') && !value.ends_with(']') {
+            // Bracketed lists such as `as-path [ 65001 65002 ]` span tokens.
+            for token in tokens.by_ref() {
+                if token.ends_with(']') {
+                    break;
+                }
+            }
+        }
+        match key {
+            "next-hop" if value == "self" => next_hop = None,
+            "next-hop" => {
+                next_hop = Some(
+                    value
+                        .parse::<IpAddr>()
+                        .with_context(|| format!("invalid next-hop {value}"))?,
+                )
+            }
+            _ => ignored.push(key.to_string()),
+        }
+    }
+
+    let command = match action {
+        "announce" => Command::Announce { network, next_hop },
+        _ => Command::Withdraw { network },
+    };
+    Ok(Some(ParsedCommand { command, ignored }))
+}
+
+/// Start the command reader and the update writer.
+pub fn spawn(cfg: &ExabgpConfig, bgp: BgpService) -> Vec<JoinHandle<()>> {
+    let updates = bgp.subscribe_received();
+    let input = cfg.input.clone();
+    let output = cfg.output.clone();
+    vec![
+        tokio::spawn(read_commands(input, bgp)),
+        tokio::spawn(write_updates(output, updates)),
+    ]
+}
+
+async fn read_commands(input: PathBuf, bgp: BgpService) {
+    if input == Path::new("-") {
+        read_stdin_commands(bgp).await;
+        return;
+    }
+    loop {
+        let is_fifo = tokio::fs::metadata(&input)
+            .await
+            .map(|m| m.file_type().is_fifo())
+            .unwrap_or(false);
+        // Opening a FIFO blocks until a writer connects.
+        let file = match tokio::fs::File::open(&input).await {
+            Ok(file) => file,
+            Err(err) => {
+                tracing::error!(input=%input.display(), error=%err, "failed opening exabgp input");
+                tokio::time::sleep(REOPEN_DELAY).await;
+                continue;
+            }
+        };
+
+        let mut lines = BufReader::new(file).lines();
+        loop {
+            match lines.next_line().await {
+                Ok(Some(line)) => apply_command(&bgp, &line),
+                Ok(None) => break,
+                Err(err) => {
+                    tracing::warn!(error=%err, "failed reading exabgp input");
+                    break;
+                }
+            }
+        }
+
+        // A FIFO hits EOF whenever its last writer closes; wait for the next.
+        if !is_fifo {
+            tracing::info!(input=%input.display(), "exabgp input closed");
+            return;
+        }
+    }
+}
+
+/// Stdin is read on a plain thread: a blocked tokio stdin read would keep
+/// the runtime from shutting down.
+async fn read_stdin_commands(bgp: BgpService) {
+    let (tx, mut rx) = tokio::sync::mpsc::channel::<String>(64);
+    std::thread::spawn(move || {
+        for line in std::io::stdin().lines() {
+            let Ok(line) = line else { break };
+            if tx.blocking_send(line).is_err() {
+                break;
+            }
+        }
+    });
+    while let Some(line) = rx.recv().await {
+        apply_command(&bgp, &line);
+    }
+    tracing::info!("exabgp input closed");
+}
+
+fn apply_command(bgp: &BgpService, line: &str) {
+    let parsed = match parse_command(line) {
+        Ok(Some(parsed)) => parsed,
+        Ok(None) => return,
+        Err(err) => {
+            tracing::warn!(command=%line, error=%err, "rejected exabgp command");
+            return;
+        }
+    };
+    if !parsed.ignored.is_empty() {
+        tracing::warn!(command=%line, ignored=?parsed.ignored, "ignoring unsupported route attributes");
+    }
+    match parsed.command {
+        Command::Announce { network, next_hop } => {
+            bgp.announce_route(network, next_hop);
+            tracing::info!(prefix=%network, "exabgp announce");
+        }
+        Command::Withdraw { network } => match bgp.withdraw_route(network) {
+            Ok(()) => tracing::info!(prefix=%network, "exabgp withdraw"),
+            Err(err) => tracing::warn!(prefix=%network, error=%err, "exabgp withdraw failed"),
+        },
+    }
+}
+
+async fn write_updates(output: PathBuf, mut updates: broadcast::Receiver<ReceivedUpdate>) {
+    let stdout = output == Path::new("-");
+    let host = hostname();
+    let mut counter = 0u64;
+    loop {
+        let mut writer: Box<dyn AsyncWrite + Unpin + Send> = if stdout {
+            Box::new(tokio::io::stdout())
+        } else {
+            let opened = tokio::fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&output)
+                .await;
+            match opened {
+                Ok(file) => Box::new(file),
+                Err(err) => {
+                    tracing::error!(output=%output.display(), error=%err, "failed opening exabgp output");
+                    tokio::time::sleep(REOPEN_DELAY).await;
+                    continue;
+                }
+            }
+        };
+
+        loop {
+            let update = match updates.recv().await {
+                Ok(update) => update,
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    tracing::warn!(missed, "exabgp output fell behind, updates skipped");
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            };
+            counter += 1;
+            let mut line = encode_update(&update, counter, &host).to_string();
+            line.push('\n');
+            let written = match writer.write_all(line.as_bytes()).await {
+                Ok(()) => writer.flush().await,
+                Err(err) => Err(err),
+            };
+            if let Err(err) = written {
+                tracing::warn!(output=%output.display(), error=%err, "failed writing exabgp output");
+                break;
+            }
+        }
+        if stdout {
+            return;
+        }
+        tokio::time::sleep(REOPEN_DELAY).await;
+    }
+}
+
+/// Render a received UPDATE the way ExaBGP's JSON encoder does.
+pub fn encode_update(received: &ReceivedUpdate, counter: u64, host: &str) -> Value {
+    let update = &received.update;
+    let attrs = &update.attributes;
+
+    let message = if update.is_end_of_rib() {
+        let afi = attrs
+            .get_unreachable_nlri()
+            .map(|nlri| nlri.afi)
+            .unwrap_or(Afi::Ipv4);
+        json!({ "eor": { "afi": afi_name(afi), "safi": "unicast" } })
+    } else {
+        let mut body = Map::new();
+        body.insert("attribute".to_string(), encode_attributes(received));
+
+        let mut announce: Map<String, Value> = Map::new();
+        let classic_next_hop = attrs.next_hop().map(|nh| nh.to_string());
+        let reach = attrs.get_reachable_nlri();
+        let announced = update
+            .announced_prefixes
+            .iter()
+            .map(|p| (p.prefix, classic_next_hop.clone()))
+            .chain(reach.into_iter().flat_map(|nlri| {
+                let next_hop = nlri.next_hop.as_ref().map(|nh| nh.to_string());
+                nlri.prefixes
+                    .iter()
+                    .map(move |p| (p.prefix, next_hop.clone()))
+            }));
+        for (prefix, next_hop) in announced {
+            let family = announce
+                .entry(family_name(&prefix))
+                .or_insert_with(|| Value::Object(Map::new()));
+            let next_hop = next_hop.unwrap_or_else(|| "null".to_string());
+            if let Value::Object(by_next_hop) = family {
+                let routes = by_next_hop
+                    .entry(next_hop)
+                    .or_insert_with(|| Value::Array(vec![]));
+                if let Value::Array(routes) = routes {
+                    routes.push(json!({ "nlri": prefix.to_string() }));
+                }
+            }
+        }
+        if !announce.is_empty() {
+            body.insert("announce".to_string(), Value::Object(announce));
+        }
+
+        let mut withdraw: Map<String, Value> = Map::new();
+        let unreach = attrs.get_unreachable_nlri();
+        let withdrawn = update
+            .withdrawn_prefixes
+            .iter()
+            .chain(unreach.into_iter().flat_map(|nlri| nlri.prefixes.iter()));
+        for prefix in withdrawn {
+            let family = withdraw
+                .entry(family_name(&prefix.prefix))
+                .or_insert_with(|| Value::Array(vec![]));
+            if let Value::Array(routes) = family {
+                routes.push(json!({ "nlri": prefix.prefix.to_string() }));
+            }
+        }
+        if !withdraw.is_empty() {
+            body.insert("withdraw".to_string(), Value::Object(withdraw));
+        }
+        json!({ "update": body })
+    };
+
+    json!({
+        "exabgp": EXABGP_VERSION,
+        "time": received.timestamp,
+        "host": host,
+        "pid": std::process::id(),
+        "ppid": unsafe { libc::getppid() },
+        "counter": counter,
+        "type": "update",
+        "neighbor": {
+            "address": {
+                "local": received.local_address.map(|a| a.to_string()),
+                "peer": received.peer,
+            },
+            "asn": { "local": received.local_as, "peer": received.peer_as },
+            "direction": "receive",
+            "message": message,
+        },
+    })
+}
+
+fn encode_attributes(received: &ReceivedUpdate) -> Value {
+    let attrs = &received.update.attributes;
+    let mut out = Map::new();
+
+    let origin = match attrs.origin() {
+        Origin::IGP => "igp",
+        Origin::EGP => "egp",
+        Origin::INCOMPLETE => "incomplete",
+    };
+    out.insert("origin".to_string(), json!(origin));
+    if let Some(path) = attrs.as_path().and_then(|p| p.to_u32_vec_opt(false)) {
+        out.insert("as-path".to_string(), json!(path));
+    }
+    if let Some(med) = attrs.multi_exit_discriminator() {
+        out.insert("med".to_string(), json!(med));
+    }
+    if let Some(local_pref) = attrs.local_preference() {
+        out.insert("local-preference".to_string(), json!(local_pref));
+    }
+    if attrs.atomic_aggregate() {
+        out.insert("atomic-aggregate".to_string(), json!(true));
+    }
+
+    let mut communities = Vec::new();
+    let mut large = Vec::new();
+    for community in attrs.iter_communities() {
+        match community {
+            MetaCommunity::Plain(plain) => communities.push(match plain {
+                Community::NoExport => json!([65535, 65281]),
+                Community::NoAdvertise => json!([65535, 65282]),
+                Community::NoExportSubConfed => json!([65535, 65283]),
+                Community::Custom(asn, value) => json!([u32::from(asn), value]),
+            }),
+            MetaCommunity::Large(c) => {
+                large.push(json!([c.global_admin, c.local_data[0], c.local_data[1]]))
+            }
+            _ => {}
+        }
+    }
+    if !communities.is_empty() {
+        out.insert("community".to_string(), Value::Array(communities));
+    }
+    if !large.is_empty() {
+        out.insert("large-community".to_string(), Value::Array(large));
+    }
+
+    Value::Object(out)
+}
+
+fn family_name(prefix: &IpNet) -> String {
+    match prefix {
+        IpNet::V4(_) => "ipv4 unicast".to_string(),
+        IpNet::V6(_) => "ipv6 unicast".to_string(),
+    }
+}
+
+fn afi_name(afi: Afi) -> &'static str {
+    match afi {
+        Afi::Ipv6 => "ipv6",
+        _ => "ipv4",
+    }
+}
+
+fn hostname() -> String {
+    let mut buf = [0u8; 256];
+    // SAFETY: `buf` is writable for its full length.
+    if unsafe { libc::gethostname(buf.as_mut_ptr().cast(), buf.len()) } != 0 {
+        return "localhost".to_string();
+    }
+    let end = buf.iter().position(|b| *b == 0).unwrap_or(buf.len());
+    String::from_utf8_lossy(&buf[..end]).into_owned()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bgpkit_parser::models::{
+        AsPath, AttributeValue, Attributes, BgpUpdateMessage, NetworkPrefix,
+    };
+
+    #[test]
+    fn parses_announce_and_withdraw() {
+        let parsed = parse_command(
+            "announce route 203.0.113.0/24 next-hop 192.0.2.1 as-path [ 65001 65002 ] med 10",
+        )
+        .unwrap()
+        .unwrap();
+        assert_eq!(
+            parsed.command,
+            Command::Announce {
+                network: "203.0.113.0/24".parse().unwrap(),
+                next_hop: Some("192.0.2.1".parse().unwrap()),
+            }
+        );
+        assert_eq!(parsed.ignored, vec!["as-path", "med"]);
+
+        let parsed = parse_command("withdraw route 2001:db8::/32 next-hop self")
+            .unwrap()
+            .unwrap();
+        assert_eq!(
+            parsed.command,
+            Command::Withdraw {
+                network: "2001:db8::/32".parse().unwrap()
+            }
+        );
+
+        assert!(parse_command("  # comment").unwrap().is_none());
+        assert!(parse_command("neighbor 192.0.2.2 announce route 10.0.0.0/8").is_err());
+        assert!(parse_command("announce flow route { }").is_err());
+    }
+
+    #[test]
+    fn encodes_updates_like_exabgp() {
+        let mut attributes = Attributes::default();
+        attributes.add_attr(AttributeValue::Origin(Origin::IGP).into());
+        attributes.add_attr(
+            AttributeValue::AsPath {
+                path: AsPath::from_sequence([65002, 65003]),
+                is_as4: true,
+            }
+            .into(),
+        );
+        attributes.add_attr(AttributeValue::NextHop("192.0.2.2".parse().unwrap()).into());
+        attributes.add_attr(AttributeValue::MultiExitDiscriminator(50).into());
+        let received = ReceivedUpdate {
+            timestamp: 1_700_000_000.5,
+            peer: "192.0.2.2".to_string(),
+            peer_as: 65002,
+            local_as: 65001,
+            local_address: Some("192.0.2.1".parse().unwrap()),
+            peer_bgp_id: "192.0.2.2".parse().unwrap(),
+            four_octet_as: true,
+            update: BgpUpdateMessage {
+                withdrawn_prefixes: vec![NetworkPrefix::new(
+                    "198.51.100.0/24".parse().unwrap(),
+                    None,
+                )],
+                attributes,
+                announced_prefixes: vec![NetworkPrefix::new(
+                    "203.0.113.0/24".parse().unwrap(),
+                    None,
+                )],
+            },
+            raw: bytes::Bytes::new(),
+        };
+
+        let json = encode_update(&received, 7, "collector");
+        assert_eq!(json["type"], "update");
+        assert_eq!(json["counter"], 7);
+        let neighbor = &json["neighbor"];
+        assert_eq!(neighbor["address"]["peer"], "192.0.2.2");
+        assert_eq!(neighbor["asn"]["local"], 65001);
+        let update = &neighbor["message"]["update"];
+        assert_eq!(update["attribute"]["origin"], "igp");
+        assert_eq!(update["attribute"]["as-path"], json!([65002, 65003]));
+        assert_eq!(update["attribute"]["med"], 50);
+        assert_eq!(
+            update["announce"]["ipv4 unicast"]["192.0.2.2"][0]["nlri"],
+            "203.0.113.0/24"
+        );
+        assert_eq!(
+            update["withdraw"]["ipv4 unicast"][0]["nlri"],
+            "198.51.100.0/24"
+        );
+    }
+}
@@ -0,0 +1,274 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+use crate::archive::ArchiveService;
+use crate::bgp::BgpService;
+use crate::config::HealthConfig;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ComponentStatus {
+    Ok,
+    Degraded,
+    Down,
+    Disabled,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ComponentHealth {
+    pub name: String,
+    pub status: ComponentStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl ComponentHealth {
+    fn new(name: &str, status: ComponentStatus, detail: Option<String>) -> Self {
+        Self {
+            name: name.to_string(),
+            status,
+            detail,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthReport {
+    pub live: bool,
+    pub ready: bool,
+    pub components: Vec<ComponentHealth>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct HealthInputs {
+    pub control_server_up: bool,
+    pub archive_enabled: bool,
+    pub archive_writer_open: bool,
+    pub queued_replication_jobs: usize,
+    pub peers_total: usize,
+    pub peers_established: usize,
+    /// Peers in maintenance, counted in neither of the above.
+    pub peers_maintenance: usize,
+}
+
+/// Evaluate component states against the configured thresholds.
+///
+/// The daemon is live while the control server accepts connections, and ready
+/// when every enabled component reports `ok`.
+pub fn evaluate(cfg: &HealthConfig, inputs: &HealthInputs) -> HealthReport {
+    let mut components = Vec::with_capacity(4);
+
+    components.push(if inputs.control_server_up {
+        ComponentHealth::new("control_server", ComponentStatus::Ok, None)
+    } else {
+        ComponentHealth::new(
+            "control_server",
+            ComponentStatus::Down,
+            Some("control server is not accepting connections".to_string()),
+        )
+    });
+
+    components.push(if !inputs.archive_enabled {
+        ComponentHealth::new("archive_writer", ComponentStatus::Disabled, None)
+    } else if inputs.archive_writer_open {
+        ComponentHealth::new("archive_writer", ComponentStatus::Ok, None)
+    } else {
+        ComponentHealth::new(
+            "archive_writer",
+            ComponentStatus::Down,
+            Some("no open updates segment".to_string()),
+        )
+    });
+
+    components.push(if !inputs.archive_enabled {
+        ComponentHealth::new("replication", ComponentStatus::Disabled, None)
+    } else if inputs.queued_replication_jobs > cfg.max_replication_backlog {
+        ComponentHealth::new(
+            "replication",
+            ComponentStatus::Degraded,
+            Some(format!(
+                "{} queued jobs exceeds threshold {}",
+                inputs.queued_replication_jobs, cfg.max_replication_backlog
+            )),
+        )
+    } else {
+        ComponentHealth::new("replication", ComponentStatus::Ok, None)
+    });
+
+    let ratio = if inputs.peers_total == 0 {
+        1.0
+    } else {
+        inputs.peers_established as f64 / inputs.peers_total as f64
+    };
+    let mut peers_detail = format!(
+        "{}/{} established",
+        inputs.peers_established, inputs.peers_total
+    );
+    if inputs.peers_maintenance > 0 {
+        peers_detail.push_str(&format!(", {} in maintenance", inputs.peers_maintenance));
+    }
+    let peers_detail = Some(peers_detail);
+    components.push(if ratio >= cfg.min_peers_established_ratio {
+        ComponentHealth::new("peers", ComponentStatus::Ok, peers_detail)
+    } else {
+        ComponentHealth::new("peers", ComponentStatus::Degraded, peers_detail)
+    });
+
+    let ready = components
+        .iter()
+        .all(|c| matches!(c.status, ComponentStatus::Ok | ComponentStatus::Disabled));
+
+    HealthReport {
+        live: inputs.control_server_up,
+        ready,
+        components,
+    }
+}
+
+pub struct HealthMonitor {
+    cfg: HealthConfig,
+    archive: Arc<ArchiveService>,
+    bgp: BgpService,
+    control_server_up: AtomicBool,
+}
+
+impl HealthMonitor {
+    pub fn new(cfg: HealthConfig, archive: Arc<ArchiveService>, bgp: BgpService) -> Arc<Self> {
+        Arc::new(Self {
+            cfg,
+            archive,
+            bgp,
+            control_server_up: AtomicBool::new(false),
+        })
+    }
+
+    pub fn set_control_server_up(&self, up: bool) {
+        self.control_server_up.store(up, Ordering::Relaxed);
+    }
+
+    pub async fn report(&self) -> Result<HealthReport> {
+        let status = self.archive.status().await?;
+        let rib = self.bgp.rib_summary();
+
+        let inputs = HealthInputs {
+            control_server_up: self.control_server_up.load(Ordering::Relaxed),
+            archive_enabled: status.enabled,
+            archive_writer_open: status.updates.open_path.is_some(),
+            queued_replication_jobs: status.queued_replication_jobs,
+            peers_total: rib.peers_total,
+            peers_established: rib.peers_established,
+            peers_maintenance: rib.peers_maintenance,
+        };
+
+        Ok(evaluate(&self.cfg, &inputs))
+    }
+
+    /// Serve `/healthz` (liveness) and `/readyz` (readiness) over plain HTTP/1.1.
+    pub async fn serve_http(self: Arc<Self>, listener: TcpListener) -> Result<()> {
+        loop {
+            let (stream, _addr) = listener.accept().await?;
+            let monitor = Arc::clone(&self);
+            tokio::spawn(async move {
+                if let Err(err) = monitor.handle_http(stream).await {
+                    tracing::debug!(error=%err, "health probe connection failed");
+                }
+            });
+        }
+    }
+
+    async fn handle_http(&self, mut stream: TcpStream) -> Result<()> {
+        let mut buf = [0u8; 1024];
+        let read = stream.read(&mut buf).await?;
+        let request = String::from_utf8_lossy(&buf[..read]);
+        let path = request
+            .lines()
+            .next()
+            .and_then(|line| line.split_whitespace().nth(1))
+            .unwrap_or("/");
+
+        let (status_line, body) = match path {
+            "/healthz" | "/readyz" => {
+                let report = self.report().await?;
+                let healthy = if path == "/healthz" {
+                    report.live
+                } else {
+                    report.ready
+                };
+                let status_line = if healthy {
+                    "200 OK"
+                } else {
+                    "503 Service Unavailable"
+                };
+                (status_line, serde_json::to_string(&report)?)
+            }
+            _ => ("404 Not Found", "{\"error\":\"not found\"}".to_string()),
+        };
+
+        let response = format!(
+            "HTTP/1.1 {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            status_line,
+            body.len(),
+            body
+        );
+        stream.write_all(response.as_bytes()).await?;
+        stream.shutdown().await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn healthy_inputs() -> HealthInputs {
+        HealthInputs {
+            control_server_up: true,
+            archive_enabled: true,
+            archive_writer_open: true,
+            queued_replication_jobs: 0,
+            peers_total: 2,
+            peers_established: 2,
+            peers_maintenance: 0,
+        }
+    }
+
+    #[test]
+    fn ready_when_all_components_ok() {
+        let report = evaluate(&HealthConfig::default(), &healthy_inputs());
+        assert!(report.live);
+        assert!(report.ready);
+    }
+
+    #[test]
+    fn not_ready_when_backlog_exceeds_threshold() {
+        let cfg = HealthConfig {
+            max_replication_backlog: 10,
+            ..HealthConfig::default()
+        };
+        let inputs = HealthInputs {
+            queued_replication_jobs: 11,
+            ..healthy_inputs()
+        };
+        let report = evaluate(&cfg, &inputs);
+        assert!(report.live);
+        assert!(!report.ready);
+    }
+
+    #[test]
+    fn not_ready_below_established_ratio() {
+        let cfg = HealthConfig {
+            min_peers_established_ratio: 0.75,
+            ..HealthConfig::default()
+        };
+        let inputs = HealthInputs {
+            peers_established: 1,
+            ..healthy_inputs()
+        };
+        assert!(!evaluate(&cfg, &inputs).ready);
+    }
+}
@@ -2,6 +2,15 @@ pub mod archive;
 pub mod bgp;
 pub mod config;
 pub mod control;
+pub mod events;
+pub mod exabgp;
+pub mod health;
+pub mod logging;
+pub mod openbmp;
+pub mod peer_stats;
+pub mod prefix_history;
+pub mod preflight;
+pub mod sim;
 pub mod types;
 
 pub use config::FoclConfig;
@@ -0,0 +1,162 @@
+use std::io::Write;
+use std::os::unix::net::UnixDatagram;
+use std::path::Path;
+use std::sync::Arc;
+
+use anyhow::{Context, Result};
+use tracing::{Level, Metadata};
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::EnvFilter;
+
+use crate::config::LogTarget;
+
+const SYSLOG_SOCKET: &str = "/dev/log";
+const SYSLOG_FACILITY_DAEMON: u8 = 3;
+
+/// Install the global tracing subscriber for the selected log target.
+///
+/// Stdout and syslog both carry JSON-formatted events so structured fields
+/// survive; journald receives fields natively via its own layer.
+pub fn init(level: &str, target: LogTarget) -> Result<()> {
+    let env_filter = EnvFilter::try_new(level).unwrap_or_else(|_| EnvFilter::new("info"));
+
+    match target {
+        LogTarget::Stdout => {
+            tracing_subscriber::fmt()
+                .with_env_filter(env_filter)
+                .with_target(false)
+                .json()
+                .init();
+        }
+        LogTarget::Syslog => {
+            let writer = SyslogMakeWriter::connect(Path::new(SYSLOG_SOCKET), "focld")?;
+            tracing_subscriber::fmt()
+                .with_env_filter(env_filter)
+                .with_target(false)
+                .with_ansi(false)
+                .without_time()
+                .json()
+                .with_writer(writer)
+                .init();
+        }
+        LogTarget::Journald => {
+            let layer = tracing_journald::layer().context("failed connecting to journald")?;
+            tracing_subscriber::registry()
+                .with(env_filter)
+                .with(layer)
+                .init();
+        }
+    }
+
+    Ok(())
+}
+
+struct SyslogMakeWriter {
+    socket: Arc<UnixDatagram>,
+    ident: String,
+    pid: u32,
+}
+
+impl SyslogMakeWriter {
+    fn connect(path: &Path, ident: &str) -> Result<Self> {
+        let socket = UnixDatagram::unbound().context("failed creating syslog socket")?;
+        socket
+            .connect(path)
+            .with_context(|| format!("failed connecting to syslog socket {}", path.display()))?;
+        Ok(Self {
+            socket: Arc::new(socket),
+            ident: ident.to_string(),
+            pid: std::process::id(),
+        })
+    }
+
+    fn writer(&self, severity: u8) -> SyslogWriter {
+        SyslogWriter {
+            socket: Arc::clone(&self.socket),
+            header: format!(
+                "<{}>{}[{}]: ",
+                SYSLOG_FACILITY_DAEMON * 8 + severity,
+                self.ident,
+                self.pid
+            ),
+            buf: Vec::new(),
+        }
+    }
+}
+
+impl<'a> MakeWriter<'a> for SyslogMakeWriter {
+    type Writer = SyslogWriter;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.writer(syslog_severity(&Level::INFO))
+    }
+
+    fn make_writer_for(&'a self, meta: &Metadata<'_>) -> Self::Writer {
+        self.writer(syslog_severity(meta.level()))
+    }
+}
+
+/// Buffers one formatted event and sends it as a single datagram on drop.
+struct SyslogWriter {
+    socket: Arc<UnixDatagram>,
+    header: String,
+    buf: Vec<u8>,
+}
+
+impl Write for SyslogWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.buf.extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+impl Drop for SyslogWriter {
+    fn drop(&mut self) {
+        let body = String::from_utf8_lossy(&self.buf);
+        let body = body.trim_end();
+        if body.is_empty() {
+            return;
+        }
+        let message = format!("{}{}", self.header, body);
+        let _ = self.socket.send(message.as_bytes());
+    }
+}
+
+fn syslog_severity(level: &Level) -> u8 {
+    match *level {
+        Level::ERROR => 3,
+        Level::WARN => 4,
+        Level::INFO => 6,
+        Level::DEBUG | Level::TRACE => 7,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn syslog_writer_sends_one_datagram_per_event() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("log.sock");
+        let server = UnixDatagram::bind(&path).unwrap();
+
+        let make_writer = SyslogMakeWriter::connect(&path, "focld").unwrap();
+        {
+            let mut writer = make_writer.writer(syslog_severity(&Level::WARN));
+            writer.write_all(b"{\"msg\":\"hello\"}\n").unwrap();
+        }
+
+        let mut buf = [0u8; 256];
+        let len = server.recv(&mut buf).unwrap();
+        let received = String::from_utf8_lossy(&buf[..len]);
+        assert!(received.starts_with("<28>focld["));
+        assert!(received.ends_with("{\"msg\":\"hello\"}"));
+    }
+}
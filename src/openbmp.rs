@@ -0,0 +1,296 @@
+//! OpenBMP message bus output: every received UPDATE becomes a BMP (RFC 7854)
+//! Route Monitoring message wrapped in the OpenBMP v1.7 binary header, the
+//! record format of the `openbmp.bmp_raw` Kafka topic.
+
+use std::net::{IpAddr, Ipv4Addr};
+use std::path::PathBuf;
+use std::time::Duration;
+
+use tokio::io::AsyncWriteExt;
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+use crate::bgp::{BgpService, ReceivedUpdate};
+use crate::config::OpenbmpConfig;
+
+const OBMP_MAGIC: &[u8; 4] = b"OBMP";
+const OBMP_VERSION: (u8, u8) = (1, 7);
+const OBMP_FLAG_ROUTER_MSG: u8 = 0x80;
+const OBMP_FLAG_ROUTER_IPV6: u8 = 0x40;
+const OBMP_TYPE_BMP_RAW: u8 = 12;
+
+const BMP_VERSION: u8 = 3;
+const BMP_ROUTE_MONITORING: u8 = 0;
+const BMP_INITIATION: u8 = 4;
+const BMP_INFO_SYS_DESCR: u16 = 1;
+const BMP_INFO_SYS_NAME: u16 = 2;
+const BMP_PEER_FLAG_IPV6: u8 = 0x80;
+/// The UPDATE uses the legacy two-octet AS_PATH format.
+const BMP_PEER_FLAG_AS2: u8 = 0x20;
+
+const REOPEN_DELAY: Duration = Duration::from_secs(1);
+
+/// Builds OpenBMP records on behalf of one collector. focld itself is the
+/// "router" whose peers are being monitored.
+pub struct Encoder {
+    admin_id: String,
+    collector_hash: [u8; 16],
+    router_ip: IpAddr,
+    router_hash: [u8; 16],
+    router_group: String,
+}
+
+impl Encoder {
+    pub fn new(admin_id: &str, router_ip: IpAddr, router_group: &str) -> Self {
+        let collector_hash = md5::compute(admin_id).0;
+        let router_hash = md5::compute(format!("{router_ip}{}", hex::encode(collector_hash))).0;
+        Self {
+            admin_id: admin_id.to_string(),
+            collector_hash,
+            router_ip,
+            router_hash,
+            router_group: router_group.to_string(),
+        }
+    }
+
+    /// BMP Initiation, sent once at the start of every output stream.
+    pub fn initiation(&self, timestamp: f64, sys_name: &str, sys_descr: &str) -> Vec<u8> {
+        let mut body = Vec::new();
+        for (kind, value) in [
+            (BMP_INFO_SYS_DESCR, sys_descr),
+            (BMP_INFO_SYS_NAME, sys_name),
+        ] {
+            body.extend_from_slice(&kind.to_be_bytes());
+            body.extend_from_slice(&(value.len() as u16).to_be_bytes());
+            body.extend_from_slice(value.as_bytes());
+        }
+        self.wrap(timestamp, &bmp_message(BMP_INITIATION, &body))
+    }
+
+    pub fn route_monitoring(&self, received: &ReceivedUpdate) -> Vec<u8> {
+        let peer_ip = received.peer.parse::<IpAddr>().ok();
+        let mut body = Vec::with_capacity(42 + received.raw.len());
+        body.push(0); // global instance peer
+        let mut flags = match peer_ip {
+            Some(IpAddr::V6(_)) => BMP_PEER_FLAG_IPV6,
+            _ => 0,
+        };
+        if !received.four_octet_as {
+            flags |= BMP_PEER_FLAG_AS2;
+        }
+        body.push(flags);
+        body.extend_from_slice(&[0; 8]);
+        body.extend_from_slice(&bmp_address(
+            peer_ip.unwrap_or(Ipv4Addr::UNSPECIFIED.into()),
+        ));
+        body.extend_from_slice(&received.peer_as.to_be_bytes());
+        body.extend_from_slice(&received.peer_bgp_id.octets());
+        let (secs, usecs) = split_timestamp(received.timestamp);
+        body.extend_from_slice(&secs.to_be_bytes());
+        body.extend_from_slice(&usecs.to_be_bytes());
+        body.extend_from_slice(&received.raw);
+        self.wrap(
+            received.timestamp,
+            &bmp_message(BMP_ROUTE_MONITORING, &body),
+        )
+    }
+
+    fn wrap(&self, timestamp: f64, bmp: &[u8]) -> Vec<u8> {
+        let mut header = Vec::with_capacity(96);
+        header.extend_from_slice(OBMP_MAGIC);
+        header.extend_from_slice(&[OBMP_VERSION.0, OBMP_VERSION.1]);
+        header.extend_from_slice(&[0, 0]); // header length, filled in below
+        header.extend_from_slice(&(bmp.len() as u32).to_be_bytes());
+        let mut flags = OBMP_FLAG_ROUTER_MSG;
+        if self.router_ip.is_ipv6() {
+            flags |= OBMP_FLAG_ROUTER_IPV6;
+        }
+        header.extend_from_slice(&[flags, OBMP_TYPE_BMP_RAW]);
+        let (secs, usecs) = split_timestamp(timestamp);
+        header.extend_from_slice(&secs.to_be_bytes());
+        header.extend_from_slice(&usecs.to_be_bytes());
+        header.extend_from_slice(&self.collector_hash);
+        header.extend_from_slice(&(self.admin_id.len() as u16).to_be_bytes());
+        header.extend_from_slice(self.admin_id.as_bytes());
+        header.extend_from_slice(&self.router_hash);
+        // IPv4 router addresses fill the first four bytes.
+        let mut router_ip = [0u8; 16];
+        match self.router_ip {
+            IpAddr::V4(v4) => router_ip[..4].copy_from_slice(&v4.octets()),
+            IpAddr::V6(v6) => router_ip = v6.octets(),
+        }
+        header.extend_from_slice(&router_ip);
+        header.extend_from_slice(&(self.router_group.len() as u16).to_be_bytes());
+        header.extend_from_slice(self.router_group.as_bytes());
+        header.extend_from_slice(&1u32.to_be_bytes()); // row count
+        let len = header.len() as u16;
+        header[6..8].copy_from_slice(&len.to_be_bytes());
+
+        header.extend_from_slice(bmp);
+        header
+    }
+}
+
+fn bmp_message(kind: u8, body: &[u8]) -> Vec<u8> {
+    let mut message = Vec::with_capacity(6 + body.len());
+    message.push(BMP_VERSION);
+    message.extend_from_slice(&((6 + body.len()) as u32).to_be_bytes());
+    message.push(kind);
+    message.extend_from_slice(body);
+    message
+}
+
+/// RFC 7854 peer addresses are 16 bytes with IPv4 in the last four.
+fn bmp_address(ip: IpAddr) -> [u8; 16] {
+    match ip {
+        IpAddr::V4(v4) => {
+            let mut bytes = [0u8; 16];
+            bytes[12..].copy_from_slice(&v4.octets());
+            bytes
+        }
+        IpAddr::V6(v6) => v6.octets(),
+    }
+}
+
+fn split_timestamp(timestamp: f64) -> (u32, u32) {
+    let secs = timestamp.trunc();
+    (secs as u32, ((timestamp - secs) * 1e6).round() as u32)
+}
+
+pub fn spawn(cfg: &OpenbmpConfig, admin_id: &str, bgp: &BgpService) -> JoinHandle<()> {
+    let encoder = Encoder::new(
+        admin_id,
+        IpAddr::V4(bgp.router_id()),
+        cfg.router_group.as_deref().unwrap_or_default(),
+    );
+    let output = cfg.output.clone().unwrap_or_default();
+    tokio::spawn(write_records(output, encoder, bgp.subscribe_received()))
+}
+
+async fn write_records(
+    output: PathBuf,
+    encoder: Encoder,
+    mut updates: broadcast::Receiver<ReceivedUpdate>,
+) {
+    let sys_descr = format!("focl {}", env!("CARGO_PKG_VERSION"));
+    loop {
+        let opened = tokio::fs::OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&output)
+            .await;
+        let mut file = match opened {
+            Ok(file) => file,
+            Err(err) => {
+                tracing::error!(output=%output.display(), error=%err, "failed opening openbmp output");
+                tokio::time::sleep(REOPEN_DELAY).await;
+                continue;
+            }
+        };
+
+        let now = chrono::Utc::now().timestamp_micros() as f64 / 1e6;
+        let mut written = file
+            .write_all(&encoder.initiation(now, &encoder.admin_id, &sys_descr))
+            .await;
+        while written.is_ok() {
+            let update = match updates.recv().await {
+                Ok(update) => update,
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    tracing::warn!(missed, "openbmp output fell behind, updates skipped");
+                    continue;
+                }
+                Err(broadcast::error::RecvError::Closed) => return,
+            };
+            written = file.write_all(&encoder.route_monitoring(&update)).await;
+        }
+        if let Err(err) = written {
+            tracing::warn!(output=%output.display(), error=%err, "failed writing openbmp output");
+        }
+        tokio::time::sleep(REOPEN_DELAY).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bgpkit_parser::models::{Attributes, BgpUpdateMessage};
+
+    #[test]
+    fn wraps_route_monitoring_in_openbmp_header() {
+        let encoder = Encoder::new("focl01", "192.0.2.1".parse().unwrap(), "lab");
+        let raw = [[0xffu8; 16].as_slice(), &[0, 23, 2, 0, 0, 0, 0]].concat();
+        let received = ReceivedUpdate {
+            timestamp: 1_700_000_000.25,
+            peer: "2001:db8::2".to_string(),
+            peer_as: 65002,
+            local_as: 65001,
+            local_address: None,
+            peer_bgp_id: "192.0.2.2".parse().unwrap(),
+            four_octet_as: true,
+            update: BgpUpdateMessage {
+                withdrawn_prefixes: vec![],
+                attributes: Attributes::default(),
+                announced_prefixes: vec![],
+            },
+            raw: raw.clone().into(),
+        };
+
+        let record = encoder.route_monitoring(&received);
+        assert_eq!(&record[..6], b"OBMP\x01\x07");
+        let header_len = u16::from_be_bytes([record[6], record[7]]) as usize;
+        let bmp_len = u32::from_be_bytes(record[8..12].try_into().unwrap()) as usize;
+        assert_eq!(header_len + bmp_len, record.len());
+        assert_eq!(record[12], OBMP_FLAG_ROUTER_MSG);
+        assert_eq!(record[13], OBMP_TYPE_BMP_RAW);
+        assert_eq!(&record[22..38], &md5::compute("focl01").0);
+        assert_eq!(&record[40..46], b"focl01");
+        assert_eq!(&record[62..66], &[192, 0, 2, 1]);
+        assert_eq!(&record[80..83], b"lab");
+
+        let bmp = &record[header_len..];
+        assert_eq!(bmp[0], BMP_VERSION);
+        assert_eq!(
+            u32::from_be_bytes(bmp[1..5].try_into().unwrap()) as usize,
+            bmp.len()
+        );
+        assert_eq!(bmp[5], BMP_ROUTE_MONITORING);
+        let peer = &bmp[6..48];
+        assert_eq!(peer[1], BMP_PEER_FLAG_IPV6);
+        assert_eq!(
+            &peer[10..26],
+            &"2001:db8::2"
+                .parse::<std::net::Ipv6Addr>()
+                .unwrap()
+                .octets()
+        );
+        assert_eq!(&peer[26..30], &65002u32.to_be_bytes());
+        assert_eq!(&peer[30..34], &[192, 0, 2, 2]);
+        assert_eq!(&peer[38..42], &250_000u32.to_be_bytes());
+        assert_eq!(&bmp[48..], raw.as_slice());
+    }
+
+    #[test]
+    fn flags_two_octet_as_peers() {
+        let encoder = Encoder::new("focl01", "192.0.2.1".parse().unwrap(), "lab");
+        let received = ReceivedUpdate {
+            timestamp: 1_700_000_000.0,
+            peer: "192.0.2.2".to_string(),
+            peer_as: 65002,
+            local_as: 65001,
+            local_address: None,
+            peer_bgp_id: "192.0.2.2".parse().unwrap(),
+            four_octet_as: false,
+            update: BgpUpdateMessage {
+                withdrawn_prefixes: vec![],
+                attributes: Attributes::default(),
+                announced_prefixes: vec![],
+            },
+            raw: bytes::Bytes::new(),
+        };
+
+        let record = encoder.route_monitoring(&received);
+        let header_len = u16::from_be_bytes([record[6], record[7]]) as usize;
+        let peer_flags = record[header_len + 7];
+        assert_eq!(peer_flags, BMP_PEER_FLAG_AS2);
+    }
+}
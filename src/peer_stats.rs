@@ -0,0 +1,177 @@
+//! `[peer_stats]`: per-peer cumulative counters in SQLite. They are added
+//! back to the peers at startup, so dashboards reading `peer_show` or
+//! `bgp_stats` do not see totals drop to zero across a restart.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use rusqlite::{params, Connection};
+use tokio::task::JoinHandle;
+
+use crate::bgp::{BgpService, PeerTotals};
+
+#[derive(Debug, Clone)]
+pub struct PeerStatsStore {
+    db_path: PathBuf,
+}
+
+impl PeerStatsStore {
+    pub fn new(db_path: &Path) -> Result<Self> {
+        if let Some(parent) = db_path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("failed creating peer stats dir {}", parent.display()))?;
+        }
+
+        let store = Self {
+            db_path: db_path.to_path_buf(),
+        };
+        store.init()?;
+        Ok(store)
+    }
+
+    fn open(&self) -> Result<Connection> {
+        let conn = Connection::open(&self.db_path)
+            .with_context(|| format!("failed opening peer stats db {}", self.db_path.display()))?;
+        Ok(conn)
+    }
+
+    fn init(&self) -> Result<()> {
+        let conn = self.open()?;
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS peer_stats (
+                peer TEXT PRIMARY KEY,
+                updates_received INTEGER NOT NULL,
+                withdrawals_received INTEGER NOT NULL,
+                prefixes_received INTEGER NOT NULL,
+                prefixes_rejected INTEGER NOT NULL,
+                session_flaps INTEGER NOT NULL,
+                last_established INTEGER,
+                saved_at INTEGER NOT NULL
+            );
+            ",
+        )?;
+        Ok(())
+    }
+
+    pub fn load(&self) -> Result<Vec<PeerTotals>> {
+        let conn = self.open()?;
+        let mut stmt = conn.prepare(
+            "SELECT peer, updates_received, withdrawals_received, prefixes_received,
+                    prefixes_rejected, session_flaps, last_established
+             FROM peer_stats ORDER BY peer",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok(PeerTotals {
+                peer: row.get(0)?,
+                updates_received: row.get::<_, i64>(1)? as u64,
+                withdrawals_received: row.get::<_, i64>(2)? as u64,
+                prefixes_received: row.get::<_, i64>(3)? as u64,
+                prefixes_rejected: row.get::<_, i64>(4)? as u64,
+                session_flaps: row.get::<_, i64>(5)? as u64,
+                last_established: row.get(6)?,
+            })
+        })?;
+        Ok(rows.collect::<Result<Vec<_>, _>>()?)
+    }
+
+    /// Replace the saved totals of each peer in `totals`.
+    pub fn save(&self, totals: &[PeerTotals]) -> Result<()> {
+        let saved_at = chrono::Utc::now().timestamp();
+        let mut conn = self.open()?;
+        let tx = conn.transaction()?;
+        {
+            let mut stmt = tx.prepare(
+                "INSERT OR REPLACE INTO peer_stats (peer, updates_received, withdrawals_received,
+                    prefixes_received, prefixes_rejected, session_flaps, last_established, saved_at)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+            )?;
+            for peer in totals {
+                stmt.execute(params![
+                    peer.peer,
+                    peer.updates_received as i64,
+                    peer.withdrawals_received as i64,
+                    peer.prefixes_received as i64,
+                    peer.prefixes_rejected as i64,
+                    peer.session_flaps as i64,
+                    peer.last_established,
+                    saved_at
+                ])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Forget the saved totals of `peer`, or of every peer without one.
+    pub fn reset(&self, peer: Option<&str>) -> Result<usize> {
+        let conn = self.open()?;
+        let removed = match peer {
+            Some(peer) => conn.execute("DELETE FROM peer_stats WHERE peer = ?", [peer])?,
+            None => conn.execute("DELETE FROM peer_stats", [])?,
+        };
+        Ok(removed)
+    }
+}
+
+/// Save `bgp`'s peer totals every `interval`. The caller saves once more
+/// after the sessions have closed.
+pub fn spawn(store: Arc<PeerStatsStore>, bgp: &BgpService, interval: Duration) -> JoinHandle<()> {
+    let bgp = bgp.clone();
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.tick().await;
+        loop {
+            ticker.tick().await;
+            let totals = bgp.peer_totals().await;
+            let store = Arc::clone(&store);
+            match tokio::task::spawn_blocking(move || store.save(&totals)).await {
+                Ok(Ok(())) => {}
+                Ok(Err(err)) => tracing::error!(error=%err, "failed saving peer stats"),
+                Err(err) => tracing::error!(error=%err, "peer stats save panicked"),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn totals(peer: &str, updates: u64) -> PeerTotals {
+        PeerTotals {
+            peer: peer.to_string(),
+            updates_received: updates,
+            session_flaps: 2,
+            last_established: Some(1_700_000_000),
+            ..PeerTotals::default()
+        }
+    }
+
+    #[test]
+    fn saves_replaces_and_resets_totals() {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("peer_stats.sqlite");
+        let store = PeerStatsStore::new(&path).unwrap();
+
+        store
+            .save(&[totals("192.0.2.2", 10), totals("192.0.2.3", 5)])
+            .unwrap();
+        store.save(&[totals("192.0.2.2", 25)]).unwrap();
+
+        // A fresh handle, as after a restart.
+        let store = PeerStatsStore::new(&path).unwrap();
+        assert_eq!(
+            store.load().unwrap(),
+            vec![totals("192.0.2.2", 25), totals("192.0.2.3", 5)]
+        );
+
+        assert_eq!(store.reset(Some("192.0.2.3")).unwrap(), 1);
+        assert_eq!(store.load().unwrap(), vec![totals("192.0.2.2", 25)]);
+        assert_eq!(store.reset(None).unwrap(), 1);
+        assert!(store.load().unwrap().is_empty());
+    }
+}
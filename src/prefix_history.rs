@@ -0,0 +1,318 @@
+//! `[prefix_history]`: first-seen, last-seen, and last-withdrawn timestamps
+//! per (prefix, peer) in SQLite, so "when did we first see this prefix" is a
+//! `prefix_history` query instead of a pass over the MRT archive.
+
+use std::collections::HashMap;
+use std::fs;
+use std::net::IpAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{Context, Result};
+use ipnet::IpNet;
+use rusqlite::{params, params_from_iter, Connection, ToSql};
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use tokio::task::JoinHandle;
+
+use crate::bgp::{BgpService, ReceivedUpdate};
+
+#[derive(Debug, Clone)]
+pub struct PrefixHistory {
+    db_path: PathBuf,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PrefixSighting {
+    pub prefix: IpNet,
+    pub peer: String,
+    pub first_seen: i64,
+    pub last_seen: i64,
+    /// Most recent withdrawal, if any since the prefix was first seen.
+    pub last_withdrawn: Option<i64>,
+}
+
+/// Updates received since the last flush, keyed by (prefix, peer).
+#[derive(Debug, Default)]
+struct Pending {
+    /// First and last announcement.
+    announced: HashMap<(IpNet, String), (i64, i64)>,
+    /// Last withdrawal.
+    withdrawn: HashMap<(IpNet, String), i64>,
+}
+
+impl Pending {
+    fn add(&mut self, update: &ReceivedUpdate) {
+        let ts = update.timestamp as i64;
+        for prefix in update.announced() {
+            self.announced
+                .entry((prefix, update.peer.clone()))
+                .and_modify(|(first, last)| {
+                    *first = (*first).min(ts);
+                    *last = (*last).max(ts);
+                })
+                .or_insert((ts, ts));
+        }
+        for prefix in update.withdrawn() {
+            self.withdrawn
+                .entry((prefix, update.peer.clone()))
+                .and_modify(|last| *last = (*last).max(ts))
+                .or_insert(ts);
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        self.announced.is_empty() && self.withdrawn.is_empty()
+    }
+}
+
+impl PrefixHistory {
+    pub const DEFAULT_LIMIT: usize = 100;
+    pub const MAX_LIMIT: usize = 10_000;
+
+    pub fn new(db_path: &Path) -> Result<Self> {
+        if let Some(parent) = db_path.parent() {
+            fs::create_dir_all(parent).with_context(|| {
+                format!("failed creating prefix history dir {}", parent.display())
+            })?;
+        }
+
+        let store = Self {
+            db_path: db_path.to_path_buf(),
+        };
+        store.init()?;
+        Ok(store)
+    }
+
+    fn open(&self) -> Result<Connection> {
+        let conn = Connection::open(&self.db_path).with_context(|| {
+            format!(
+                "failed opening prefix history db {}",
+                self.db_path.display()
+            )
+        })?;
+        Ok(conn)
+    }
+
+    /// `start` and `end` are the network's first and last address in network
+    /// byte order, so covered prefixes are a range scan on `start`.
+    fn init(&self) -> Result<()> {
+        let conn = self.open()?;
+        conn.execute_batch(
+            "
+            CREATE TABLE IF NOT EXISTS prefix_history (
+                prefix TEXT NOT NULL,
+                peer TEXT NOT NULL,
+                start BLOB NOT NULL,
+                end BLOB NOT NULL,
+                first_seen INTEGER NOT NULL,
+                last_seen INTEGER NOT NULL,
+                last_withdrawn INTEGER,
+                PRIMARY KEY (prefix, peer)
+            );
+            CREATE INDEX IF NOT EXISTS idx_prefix_history_start ON prefix_history(start);
+            ",
+        )?;
+        Ok(())
+    }
+
+    fn flush(&self, pending: &Pending) -> Result<()> {
+        let mut conn = self.open()?;
+        let tx = conn.transaction()?;
+        {
+            let mut announce = tx.prepare(
+                "INSERT INTO prefix_history (prefix, peer, start, end, first_seen, last_seen)
+                 VALUES (?, ?, ?, ?, ?, ?)
+                 ON CONFLICT (prefix, peer) DO UPDATE SET
+                    first_seen = MIN(first_seen, excluded.first_seen),
+                    last_seen = MAX(last_seen, excluded.last_seen)",
+            )?;
+            for ((prefix, peer), (first, last)) in &pending.announced {
+                announce.execute(params![
+                    prefix.to_string(),
+                    peer,
+                    addr_bytes(prefix.network()),
+                    addr_bytes(prefix.broadcast()),
+                    first,
+                    last
+                ])?;
+            }
+            // A withdrawal for a prefix never announced to us is not history.
+            let mut withdraw = tx.prepare(
+                "UPDATE prefix_history SET last_withdrawn = MAX(COALESCE(last_withdrawn, 0), ?)
+                 WHERE prefix = ? AND peer = ?",
+            )?;
+            for ((prefix, peer), ts) in &pending.withdrawn {
+                withdraw.execute(params![ts, prefix.to_string(), peer])?;
+            }
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    /// Sightings of `prefix`, and of prefixes it covers when
+    /// `more_specifics` is set, earliest first.
+    pub fn query(
+        &self,
+        prefix: IpNet,
+        more_specifics: bool,
+        limit: Option<usize>,
+    ) -> Result<Vec<PrefixSighting>> {
+        let prefix = prefix.trunc();
+        let mut sql = String::from(
+            "SELECT prefix, peer, first_seen, last_seen, last_withdrawn FROM prefix_history",
+        );
+        let mut args: Vec<Box<dyn ToSql>> = Vec::new();
+        if more_specifics {
+            let start = addr_bytes(prefix.network());
+            sql.push_str(" WHERE start >= ? AND end <= ? AND length(start) = ?");
+            let len = start.len() as i64;
+            args.push(Box::new(start));
+            args.push(Box::new(addr_bytes(prefix.broadcast())));
+            args.push(Box::new(len));
+        } else {
+            sql.push_str(" WHERE prefix = ?");
+            args.push(Box::new(prefix.to_string()));
+        }
+        sql.push_str(" ORDER BY first_seen, prefix, peer LIMIT ?");
+        args.push(Box::new(
+            limit.unwrap_or(Self::DEFAULT_LIMIT).min(Self::MAX_LIMIT) as i64,
+        ));
+
+        let conn = self.open()?;
+        let mut stmt = conn.prepare(&sql)?;
+        let rows = stmt.query_map(params_from_iter(args.iter()), |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, i64>(2)?,
+                row.get::<_, i64>(3)?,
+                row.get::<_, Option<i64>>(4)?,
+            ))
+        })?;
+        rows.map(|row| {
+            let (prefix, peer, first_seen, last_seen, last_withdrawn) = row?;
+            Ok(PrefixSighting {
+                prefix: prefix
+                    .parse()
+                    .with_context(|| format!("invalid prefix {prefix} in prefix history"))?,
+                peer,
+                first_seen,
+                last_seen,
+                last_withdrawn,
+            })
+        })
+        .collect()
+    }
+}
+
+fn addr_bytes(addr: IpAddr) -> Vec<u8> {
+    match addr {
+        IpAddr::V4(v4) => v4.octets().to_vec(),
+        IpAddr::V6(v6) => v6.octets().to_vec(),
+    }
+}
+
+/// Record every UPDATE received by `bgp`, flushing every `flush_interval`.
+pub fn spawn(
+    store: Arc<PrefixHistory>,
+    bgp: &BgpService,
+    flush_interval: Duration,
+) -> JoinHandle<()> {
+    let mut updates = bgp.subscribe_received();
+    tokio::spawn(async move {
+        let mut pending = Pending::default();
+        let mut flush = tokio::time::interval(flush_interval);
+        loop {
+            tokio::select! {
+                received = updates.recv() => match received {
+                    Ok(update) => pending.add(&update),
+                    Err(broadcast::error::RecvError::Lagged(missed)) => {
+                        tracing::warn!(missed, "prefix history fell behind, updates skipped");
+                    }
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+                _ = flush.tick() => {
+                    if pending.is_empty() {
+                        continue;
+                    }
+                    let batch = std::mem::take(&mut pending);
+                    let store = Arc::clone(&store);
+                    match tokio::task::spawn_blocking(move || store.flush(&batch)).await {
+                        Ok(Ok(())) => {}
+                        Ok(Err(err)) => tracing::error!(error=%err, "failed persisting prefix history"),
+                        Err(err) => tracing::error!(error=%err, "prefix history flush panicked"),
+                    }
+                }
+            }
+        }
+        if !pending.is_empty() {
+            if let Err(err) = store.flush(&pending) {
+                tracing::error!(error=%err, "failed persisting prefix history");
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn net(s: &str) -> IpNet {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn keeps_first_last_and_withdrawn_per_peer() {
+        let tmp = tempfile::tempdir().unwrap();
+        let store = PrefixHistory::new(&tmp.path().join("prefix_history.sqlite")).unwrap();
+        let peer = "192.0.2.2".to_string();
+
+        let mut batch = Pending::default();
+        batch
+            .announced
+            .insert((net("203.0.113.0/24"), peer.clone()), (100, 150));
+        batch
+            .announced
+            .insert((net("203.0.113.128/25"), peer.clone()), (120, 120));
+        batch
+            .announced
+            .insert((net("2001:db8::/32"), peer.clone()), (130, 130));
+        // Never announced: ignored.
+        batch
+            .withdrawn
+            .insert((net("198.51.100.0/24"), peer.clone()), 140);
+        store.flush(&batch).unwrap();
+
+        let mut batch = Pending::default();
+        batch
+            .announced
+            .insert((net("203.0.113.0/24"), peer.clone()), (200, 200));
+        batch
+            .withdrawn
+            .insert((net("203.0.113.0/24"), peer.clone()), 210);
+        store.flush(&batch).unwrap();
+
+        let rows = store.query(net("203.0.113.0/24"), false, None).unwrap();
+        assert_eq!(
+            rows,
+            vec![PrefixSighting {
+                prefix: net("203.0.113.0/24"),
+                peer: peer.clone(),
+                first_seen: 100,
+                last_seen: 200,
+                last_withdrawn: Some(210),
+            }]
+        );
+
+        let covered = store.query(net("203.0.0.0/16"), true, None).unwrap();
+        assert_eq!(covered.len(), 2);
+        assert_eq!(covered[1].prefix, net("203.0.113.128/25"));
+        assert!(store
+            .query(net("198.51.100.0/24"), false, None)
+            .unwrap()
+            .is_empty());
+        assert_eq!(store.query(net("::/0"), true, None).unwrap().len(), 1);
+    }
+}
@@ -0,0 +1,363 @@
+//! Startup checks that surface misconfigured paths and destinations up front,
+//! instead of at the first segment rotation or replication attempt.
+
+use std::ffi::CString;
+use std::fmt;
+use std::fs;
+use std::net::{Ipv4Addr, SocketAddr};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::Path;
+use std::time::Duration;
+
+use anyhow::{bail, Context, Result};
+use aws_sdk_s3::primitives::ByteStream;
+use serde::{Deserialize, Serialize};
+
+use crate::archive::replicator::{object_key, s3_client};
+use crate::config::{ArchiveDestinationConfig, DestinationType, FoclConfig, PeerConfig};
+
+const S3_TIMEOUT: Duration = Duration::from_secs(10);
+const PROBE_NAME: &str = ".focl-preflight";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PreflightCheck {
+    pub name: String,
+    pub ok: bool,
+    pub detail: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PreflightReport {
+    pub checks: Vec<PreflightCheck>,
+}
+
+impl PreflightReport {
+    pub fn passed(&self) -> bool {
+        self.checks.iter().all(|c| c.ok)
+    }
+
+    fn record(&mut self, name: String, result: Result<String>) {
+        let (ok, detail) = match result {
+            Ok(detail) => (true, detail),
+            Err(err) => (false, format!("{err:#}")),
+        };
+        self.checks.push(PreflightCheck { name, ok, detail });
+    }
+}
+
+impl fmt::Display for PreflightReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let failed = self.checks.iter().filter(|c| !c.ok).count();
+        if failed == 0 {
+            writeln!(f, "preflight: all {} checks passed", self.checks.len())?;
+        } else {
+            writeln!(
+                f,
+                "preflight: {failed} of {} checks failed",
+                self.checks.len()
+            )?;
+        }
+        for check in &self.checks {
+            let status = if check.ok { "ok  " } else { "FAIL" };
+            writeln!(f, "  {status} {}: {}", check.name, check.detail)?;
+        }
+        Ok(())
+    }
+}
+
+/// Run every check that applies to `cfg`. Failures are collected rather than
+/// returned early so the report covers everything at once.
+pub async fn run(cfg: &FoclConfig) -> PreflightReport {
+    let mut report = PreflightReport::default();
+
+    let socket_dir = cfg
+        .global
+        .control_socket
+        .parent()
+        .filter(|p| !p.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    report.record(
+        format!("control socket directory {}", socket_dir.display()),
+        check_socket_dir(socket_dir),
+    );
+
+    if !cfg.archive.enabled {
+        return report;
+    }
+
+    let min_free = cfg.archive.min_free_mb;
+    report.record(
+        format!("archive root {}", cfg.archive.root.display()),
+        check_writable_dir(&cfg.archive.root, min_free),
+    );
+    report.record(
+        format!("archive tmp root {}", cfg.archive.tmp_root.display()),
+        check_tmp_root(cfg),
+    );
+
+    for destination in &cfg.archive.destinations {
+        let name = format!("destination {}", destination.destination_key());
+        let result = match destination.destination_type {
+            DestinationType::Local => match &destination.path {
+                Some(path) => check_writable_dir(path, min_free),
+                None => Err(anyhow::anyhow!("local destination path missing")),
+            },
+            DestinationType::S3 => {
+                match tokio::time::timeout(S3_TIMEOUT, check_s3(destination)).await {
+                    Ok(result) => result,
+                    Err(_) => Err(anyhow::anyhow!(
+                        "no response within {}s",
+                        S3_TIMEOUT.as_secs()
+                    )),
+                }
+            }
+        };
+        report.record(name, result);
+    }
+
+    report
+}
+
+/// Where focld would dial or listen for each peer, without opening a socket.
+pub fn resolve_peers(cfg: &FoclConfig) -> PreflightReport {
+    let mut report = PreflightReport::default();
+    for peer in &cfg.peers {
+        let name = match &peer.name {
+            Some(name) => format!("peer {} ({name})", peer.address),
+            None => format!("peer {}", peer.address),
+        };
+        report.record(name, resolve_peer(peer, cfg.global.asn));
+    }
+    report
+}
+
+fn resolve_peer(peer: &PeerConfig, global_asn: u32) -> Result<String> {
+    if !peer.enabled {
+        return Ok("disabled".to_string());
+    }
+    let remote = SocketAddr::new(peer.address.parse()?, peer.remote_port);
+    let local = peer.local_address_socket()?;
+    let local_as = peer.local_as.unwrap_or(global_asn);
+    let session = if peer.passive {
+        let listen = local
+            .unwrap_or_else(|| SocketAddr::new(Ipv4Addr::UNSPECIFIED.into(), peer.remote_port));
+        format!("listens on {listen} for {}", remote.ip())
+    } else {
+        match local {
+            Some(local) => format!("dials {remote} from {}", local.ip()),
+            None => format!("dials {remote}"),
+        }
+    };
+    let auth = match (&peer.password, peer.tcp_ao_key_id, &peer.password_rotation) {
+        (Some(_), Some(id), Some(rotation)) => format!(
+            ", TCP-AO key {id} rotating to key {} over {}..{}",
+            rotation.tcp_ao_key_id.unwrap_or_default(),
+            rotation.start,
+            rotation.end()
+        ),
+        (Some(_), Some(id), None) => format!(", TCP-AO key {id}"),
+        (Some(_), None, Some(rotation)) => format!(
+            ", TCP-MD5 rotating over {}..{}, alternating keys per connection attempt",
+            rotation.start,
+            rotation.end()
+        ),
+        (Some(_), None, None) => ", TCP-MD5".to_string(),
+        (None, _, _) => String::new(),
+    };
+    Ok(format!(
+        "{session}, AS {local_as} to AS {}{auth}",
+        peer.remote_as
+    ))
+}
+
+fn check_socket_dir(dir: &Path) -> Result<String> {
+    if !dir.is_dir() {
+        bail!("directory does not exist");
+    }
+    probe_write(dir)?;
+    Ok("exists and is writable".to_string())
+}
+
+fn check_writable_dir(dir: &Path, min_free_mb: u64) -> Result<String> {
+    fs::create_dir_all(dir).with_context(|| format!("cannot create {}", dir.display()))?;
+    probe_write(dir)?;
+    let free_mb = free_space_mb(dir)?;
+    if free_mb < min_free_mb {
+        bail!("only {free_mb} MiB free, need at least {min_free_mb} MiB (min_free_mb)");
+    }
+    Ok(format!("writable, {free_mb} MiB free"))
+}
+
+/// A tmp root on another filesystem than the root cannot have its segments
+/// renamed into place, which fails with EXDEV at the first rotation.
+fn check_tmp_root(cfg: &FoclConfig) -> Result<String> {
+    let archive = &cfg.archive;
+    let detail = check_writable_dir(&archive.tmp_root, archive.min_free_mb)?;
+    if same_filesystem(&archive.tmp_root, &archive.root)? {
+        return Ok(detail);
+    }
+    if !archive.cross_filesystem_tmp {
+        bail!(
+            "on a different filesystem than archive root {}, so segments cannot be renamed \
+             into place (EXDEV); move tmp_root under the root, or set \
+             [archive].cross_filesystem_tmp = true to copy each segment into the root, \
+             fsync it, and rename it there instead",
+            archive.root.display()
+        );
+    }
+    Ok(format!(
+        "{detail}, on a different filesystem than the root (segments are copied into place)"
+    ))
+}
+
+fn same_filesystem(a: &Path, b: &Path) -> Result<bool> {
+    let dev = |dir: &Path| {
+        fs::metadata(dir)
+            .map(|m| m.dev())
+            .with_context(|| format!("cannot stat {}", dir.display()))
+    };
+    Ok(dev(a)? == dev(b)?)
+}
+
+fn probe_write(dir: &Path) -> Result<()> {
+    let probe = dir.join(format!("{PROBE_NAME}-{}", std::process::id()));
+    fs::write(&probe, b"focl").with_context(|| format!("cannot write to {}", dir.display()))?;
+    let _ = fs::remove_file(&probe);
+    Ok(())
+}
+
+pub(crate) fn free_space_mb(dir: &Path) -> Result<u64> {
+    let path = CString::new(dir.as_os_str().as_bytes())
+        .with_context(|| format!("invalid path {}", dir.display()))?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `path` is NUL-terminated and `stat` is a valid out pointer.
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return Err(std::io::Error::last_os_error())
+            .with_context(|| format!("statvfs failed for {}", dir.display()));
+    }
+    Ok((stat.f_bavail as u64).saturating_mul(stat.f_frsize as u64) / (1024 * 1024))
+}
+
+/// Confirms the bucket answers and that we may write and delete objects
+/// under the configured prefix.
+async fn check_s3(destination: &ArchiveDestinationConfig) -> Result<String> {
+    let bucket = destination.bucket.as_deref().context("s3 bucket missing")?;
+    let prefix = destination.prefix.as_deref().unwrap_or_default();
+    let client = s3_client(destination).await?;
+
+    client
+        .head_bucket()
+        .bucket(bucket)
+        .send()
+        .await
+        .with_context(|| format!("bucket {bucket} is not reachable"))?;
+
+    let key = object_key(prefix, &format!("{PROBE_NAME}-{}", std::process::id()));
+    client
+        .put_object()
+        .bucket(bucket)
+        .key(&key)
+        .body(ByteStream::from_static(b"focl"))
+        .send()
+        .await
+        .with_context(|| format!("cannot write s3://{bucket}/{key}"))?;
+    client
+        .delete_object()
+        .bucket(bucket)
+        .key(&key)
+        .send()
+        .await
+        .with_context(|| format!("cannot delete s3://{bucket}/{key}"))?;
+
+    Ok(format!("bucket {bucket} reachable and writable"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(root: &Path, socket: &Path) -> FoclConfig {
+        let raw = format!(
+            r#"
+[global]
+asn = 65001
+router_id = "192.0.2.1"
+control_socket = "{}"
+
+[archive]
+enabled = true
+root = "{}"
+tmp_root = "{}/.tmp"
+
+[[archive.destinations]]
+type = "local"
+mode = "primary"
+path = "{}"
+"#,
+            socket.display(),
+            root.display(),
+            root.display(),
+            root.display()
+        );
+        toml::from_str(&raw).unwrap()
+    }
+
+    #[test]
+    fn resolves_active_and_passive_peers() {
+        let tmp = tempfile::tempdir().unwrap();
+        let mut cfg = config(tmp.path(), &tmp.path().join("focld.sock"));
+        cfg.peers = toml::from_str::<toml::Table>(
+            r#"
+            [[peers]]
+            address = "192.0.2.2"
+            remote_as = 65002
+            local_address = "192.0.2.1"
+
+            [[peers]]
+            address = "192.0.2.3"
+            remote_as = 65003
+            passive = true
+            name = "rs1"
+            "#,
+        )
+        .unwrap()["peers"]
+            .clone()
+            .try_into()
+            .unwrap();
+
+        let report = resolve_peers(&cfg);
+        assert!(report.passed(), "{report}");
+        assert_eq!(
+            report.checks[0].detail,
+            "dials 192.0.2.2:179 from 192.0.2.1, AS 65001 to AS 65002"
+        );
+        assert_eq!(report.checks[1].name, "peer 192.0.2.3 (rs1)");
+        assert_eq!(
+            report.checks[1].detail,
+            "listens on 0.0.0.0:179 for 192.0.2.3, AS 65001 to AS 65003"
+        );
+    }
+
+    #[tokio::test]
+    async fn reports_every_failed_check() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path().join("archive");
+
+        let mut cfg = config(&root, &tmp.path().join("focld.sock"));
+        cfg.archive.min_free_mb = 0;
+        let report = run(&cfg).await;
+        assert!(report.passed(), "{report}");
+        assert_eq!(report.checks.len(), 4);
+        assert!(root.join(".tmp").is_dir());
+
+        let mut cfg = config(&root, &tmp.path().join("missing/focld.sock"));
+        cfg.archive.min_free_mb = u64::MAX;
+        let report = run(&cfg).await;
+        let failed: Vec<_> = report.checks.iter().filter(|c| !c.ok).collect();
+        assert_eq!(failed.len(), 4);
+        assert!(failed[0].detail.contains("does not exist"));
+        assert!(failed[1].detail.contains("min_free_mb"));
+        assert!(report.to_string().contains("4 of 4 checks failed"));
+    }
+}
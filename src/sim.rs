@@ -0,0 +1,388 @@
+//! A scripted remote BGP speaker for integration tests and lab work (the
+//! `focl-sim` binary). It speaks just enough BGP to drive focld's session
+//! FSM: OPEN, KEEPALIVE, IPv4 UPDATEs, and NOTIFICATIONs, each on cue.
+
+use std::net::{Ipv4Addr, SocketAddr};
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Context, Result};
+use bytes::Bytes;
+use ipnet::Ipv4Net;
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+
+const HEADER_LEN: usize = 19;
+const MAX_MESSAGE_LEN: usize = 4096;
+const EXPECT_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageType {
+    Open = 1,
+    Update = 2,
+    Notification = 3,
+    Keepalive = 4,
+    RouteRefresh = 5,
+}
+
+impl MessageType {
+    fn from_code(code: u8) -> Option<Self> {
+        Some(match code {
+            1 => Self::Open,
+            2 => Self::Update,
+            3 => Self::Notification,
+            4 => Self::Keepalive,
+            5 => Self::RouteRefresh,
+            _ => return None,
+        })
+    }
+}
+
+/// One scripted action. In TOML scripts each is a `[[steps]]` table named by
+/// its `step` key.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "step", rename_all = "snake_case")]
+pub enum Step {
+    /// Send OPEN, wait for the collector's, and exchange KEEPALIVEs.
+    Handshake,
+    Open,
+    Keepalive,
+    Update {
+        #[serde(default)]
+        announce: Vec<Ipv4Net>,
+        #[serde(default)]
+        withdraw: Vec<Ipv4Net>,
+        /// Defaults to the simulator's own ASN.
+        #[serde(default)]
+        as_path: Vec<u32>,
+    },
+    /// `count` UPDATEs, each announcing the next prefix of `prefix`'s length
+    /// after `prefix`.
+    BulkUpdates {
+        prefix: Ipv4Net,
+        count: u32,
+        #[serde(default)]
+        as_path: Vec<u32>,
+    },
+    Notification {
+        code: u8,
+        #[serde(default)]
+        subcode: u8,
+    },
+    /// Ask the collector to re-send its routes; IPv4 unicast by default.
+    RouteRefresh {
+        #[serde(default = "default_afi")]
+        afi: u16,
+        #[serde(default = "default_safi")]
+        safi: u8,
+    },
+    /// Wait for a message of this type, skipping KEEPALIVEs and UPDATEs.
+    Expect {
+        message: MessageType,
+    },
+    Sleep {
+        ms: u64,
+    },
+    /// Close the TCP connection without a NOTIFICATION.
+    Close,
+}
+
+/// The simulated peer's identity.
+#[derive(Debug, Clone)]
+pub struct SimConfig {
+    pub asn: u32,
+    pub router_id: Ipv4Addr,
+    pub hold_time: u16,
+    /// NEXT_HOP of announced routes.
+    pub next_hop: Ipv4Addr,
+}
+
+impl SimConfig {
+    pub fn new(asn: u32, router_id: Ipv4Addr) -> Self {
+        Self {
+            asn,
+            router_id,
+            hold_time: 90,
+            next_hop: router_id,
+        }
+    }
+}
+
+/// A message read from the collector.
+#[derive(Debug, Clone)]
+pub struct Received {
+    pub message: MessageType,
+    /// The frame, header included.
+    pub frame: Bytes,
+}
+
+pub struct SimPeer {
+    cfg: SimConfig,
+    stream: TcpStream,
+}
+
+impl SimPeer {
+    /// Connect to a collector that has this peer configured as passive.
+    pub async fn connect(cfg: SimConfig, addr: SocketAddr) -> Result<Self> {
+        let stream = TcpStream::connect(addr)
+            .await
+            .with_context(|| format!("failed connecting to {addr}"))?;
+        Ok(Self { cfg, stream })
+    }
+
+    /// Wait for a collector that connects to `listener` (an active peer).
+    pub async fn accept(cfg: SimConfig, listener: &TcpListener) -> Result<Self> {
+        let (stream, _) = listener.accept().await?;
+        Ok(Self { cfg, stream })
+    }
+
+    pub async fn run(&mut self, steps: &[Step]) -> Result<()> {
+        for (index, step) in steps.iter().enumerate() {
+            self.step(step)
+                .await
+                .with_context(|| format!("step {} ({step:?}) failed", index + 1))?;
+        }
+        Ok(())
+    }
+
+    pub async fn step(&mut self, step: &Step) -> Result<()> {
+        match step {
+            Step::Handshake => self.handshake().await,
+            Step::Open => self.send(&self.open()).await,
+            Step::Keepalive => self.send(&message(MessageType::Keepalive, &[])).await,
+            Step::Update {
+                announce,
+                withdraw,
+                as_path,
+            } => {
+                let update = self.update(announce, withdraw, as_path);
+                self.send(&update).await
+            }
+            Step::BulkUpdates {
+                prefix,
+                count,
+                as_path,
+            } => {
+                let step = 1u32
+                    .checked_shl(32 - u32::from(prefix.prefix_len()))
+                    .unwrap_or(0);
+                let base = u32::from(prefix.network());
+                for i in 0..*count {
+                    let network = base.wrapping_add(i.wrapping_mul(step));
+                    let net = Ipv4Net::new(network.into(), prefix.prefix_len())?;
+                    let update = self.update(&[net], &[], as_path);
+                    self.stream.write_all(&update).await?;
+                }
+                Ok(())
+            }
+            Step::Notification { code, subcode } => {
+                self.send(&message(MessageType::Notification, &[*code, *subcode]))
+                    .await
+            }
+            Step::RouteRefresh { afi, safi } => {
+                let [afi_hi, afi_lo] = afi.to_be_bytes();
+                self.send(&message(
+                    MessageType::RouteRefresh,
+                    &[afi_hi, afi_lo, 0, *safi],
+                ))
+                .await
+            }
+            Step::Expect { message } => self.expect(*message).await.map(|_| ()),
+            Step::Sleep { ms } => {
+                tokio::time::sleep(Duration::from_millis(*ms)).await;
+                Ok(())
+            }
+            Step::Close => {
+                self.stream.shutdown().await?;
+                Ok(())
+            }
+        }
+    }
+
+    pub async fn handshake(&mut self) -> Result<()> {
+        self.send(&self.open()).await?;
+        self.expect(MessageType::Open).await?;
+        self.send(&message(MessageType::Keepalive, &[])).await?;
+        self.expect(MessageType::Keepalive).await?;
+        Ok(())
+    }
+
+    /// Next message of type `wanted`. KEEPALIVEs and UPDATEs in between are
+    /// skipped; a NOTIFICATION or the connection closing is an error.
+    pub async fn expect(&mut self, wanted: MessageType) -> Result<Received> {
+        tokio::time::timeout(EXPECT_TIMEOUT, async {
+            loop {
+                let received = self.recv().await?;
+                if received.message == wanted {
+                    return Ok(received);
+                }
+                if received.message == MessageType::Notification {
+                    bail!(
+                        "got NOTIFICATION {}/{} waiting for {wanted:?}",
+                        received.frame.get(HEADER_LEN).copied().unwrap_or_default(),
+                        received
+                            .frame
+                            .get(HEADER_LEN + 1)
+                            .copied()
+                            .unwrap_or_default()
+                    );
+                }
+            }
+        })
+        .await
+        .map_err(|_| anyhow!("timed out waiting for {wanted:?}"))?
+    }
+
+    pub async fn recv(&mut self) -> Result<Received> {
+        let mut frame = vec![0; HEADER_LEN];
+        self.stream.read_exact(&mut frame).await?;
+        if frame[..16] != [0xff; 16] {
+            bail!("invalid BGP marker");
+        }
+        let len = usize::from(u16::from_be_bytes([frame[16], frame[17]]));
+        if !(HEADER_LEN..=MAX_MESSAGE_LEN).contains(&len) {
+            bail!("invalid BGP message length {len}");
+        }
+        frame.resize(len, 0);
+        self.stream.read_exact(&mut frame[HEADER_LEN..]).await?;
+        let message = MessageType::from_code(frame[18])
+            .ok_or_else(|| anyhow!("unknown BGP message type {}", frame[18]))?;
+        Ok(Received {
+            message,
+            frame: frame.into(),
+        })
+    }
+
+    async fn send(&mut self, frame: &[u8]) -> Result<()> {
+        self.stream.write_all(frame).await?;
+        Ok(())
+    }
+
+    /// OPEN with the four-octet AS capability, so AS paths are encoded with
+    /// four-octet ASNs.
+    fn open(&self) -> Vec<u8> {
+        let as2 = u16::try_from(self.cfg.asn).unwrap_or(23456);
+        let mut body = vec![4];
+        body.extend_from_slice(&as2.to_be_bytes());
+        body.extend_from_slice(&self.cfg.hold_time.to_be_bytes());
+        body.extend_from_slice(&self.cfg.router_id.octets());
+        // One capabilities parameter holding route refresh (2) and
+        // four-octet AS (65).
+        body.extend_from_slice(&[10, 2, 8, 2, 0, 65, 4]);
+        body.extend_from_slice(&self.cfg.asn.to_be_bytes());
+        message(MessageType::Open, &body)
+    }
+
+    fn update(&self, announce: &[Ipv4Net], withdraw: &[Ipv4Net], as_path: &[u32]) -> Vec<u8> {
+        let withdrawn = encode_nlri(withdraw);
+        let mut attributes = Vec::new();
+        if !announce.is_empty() {
+            let path = if as_path.is_empty() {
+                vec![self.cfg.asn]
+            } else {
+                as_path.to_vec()
+            };
+            // ORIGIN IGP
+            attributes.extend_from_slice(&[0x40, 1, 1, 0]);
+            // AS_PATH: one AS_SEQUENCE, extended length for long paths.
+            let segment_len = 2 + 4 * path.len();
+            attributes.extend_from_slice(&[0x50, 2]);
+            attributes.extend_from_slice(&(segment_len as u16).to_be_bytes());
+            attributes.extend_from_slice(&[2, path.len() as u8]);
+            for asn in path {
+                attributes.extend_from_slice(&asn.to_be_bytes());
+            }
+            // NEXT_HOP
+            attributes.extend_from_slice(&[0x40, 3, 4]);
+            attributes.extend_from_slice(&self.cfg.next_hop.octets());
+        }
+
+        let mut body = Vec::new();
+        body.extend_from_slice(&(withdrawn.len() as u16).to_be_bytes());
+        body.extend_from_slice(&withdrawn);
+        body.extend_from_slice(&(attributes.len() as u16).to_be_bytes());
+        body.extend_from_slice(&attributes);
+        body.extend_from_slice(&encode_nlri(announce));
+        message(MessageType::Update, &body)
+    }
+}
+
+fn default_afi() -> u16 {
+    1
+}
+
+fn default_safi() -> u8 {
+    1
+}
+
+fn message(kind: MessageType, body: &[u8]) -> Vec<u8> {
+    let mut frame = vec![0xff; 16];
+    frame.extend_from_slice(&((HEADER_LEN + body.len()) as u16).to_be_bytes());
+    frame.push(kind as u8);
+    frame.extend_from_slice(body);
+    frame
+}
+
+fn encode_nlri(prefixes: &[Ipv4Net]) -> Vec<u8> {
+    let mut out = Vec::new();
+    for prefix in prefixes {
+        let len = prefix.prefix_len();
+        out.push(len);
+        out.extend_from_slice(&prefix.network().octets()[..usize::from(len.div_ceil(8))]);
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn scripted_steps_reach_the_other_side_in_order() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let cfg = SimConfig::new(65002, Ipv4Addr::new(192, 0, 2, 2));
+        let remote = SimConfig::new(65001, Ipv4Addr::new(192, 0, 2, 1));
+
+        let collector = tokio::spawn(async move {
+            let mut collector = SimPeer::accept(remote, &listener).await.unwrap();
+            collector.handshake().await.unwrap();
+            let mut types = Vec::new();
+            while let Ok(received) = collector.recv().await {
+                types.push(received.message);
+            }
+            types
+        });
+
+        let mut sim = SimPeer::connect(cfg, addr).await.unwrap();
+        let steps: Vec<Step> = toml::from_str::<toml::Table>(
+            r#"
+            steps = [
+                { step = "handshake" },
+                { step = "bulk_updates", prefix = "10.0.0.0/24", count = 3 },
+                { step = "update", withdraw = ["10.0.1.0/24"] },
+                { step = "notification", code = 6, subcode = 2 },
+                { step = "close" },
+            ]
+            "#,
+        )
+        .unwrap()["steps"]
+            .clone()
+            .try_into()
+            .unwrap();
+        sim.run(&steps).await.unwrap();
+
+        let types = collector.await.unwrap();
+        assert_eq!(
+            types,
+            [
+                MessageType::Update,
+                MessageType::Update,
+                MessageType::Update,
+                MessageType::Update,
+                MessageType::Notification,
+            ]
+        );
+    }
+}
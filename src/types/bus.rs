@@ -0,0 +1,340 @@
+//! The daemon event bus. Services publish onto one shared [`EventBus`], which
+//! numbers every event, and every consumer subscribes through it, optionally
+//! to a subset of [`EventTopic`]s.
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use super::{Event, EventEnvelope};
+
+/// Event capacity of [`EventBus::default`].
+pub const DEFAULT_EVENT_BUFFER: usize = 512;
+
+/// Coarse grouping of events that subscribers filter on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EventTopic {
+    /// BGP session state.
+    Peer,
+    /// Received routes: leaks, hijacks, origin changes, prefix health.
+    Routing,
+    /// Archive segments, replication, disk, and snapshots.
+    Archive,
+    /// The bus itself; gap notices are delivered on every subscription.
+    Bus,
+}
+
+impl Event {
+    pub fn topic(&self) -> EventTopic {
+        match self {
+            Event::PeerState { .. } => EventTopic::Peer,
+            Event::RouteLeakSuspected { .. }
+            | Event::PrefixHijackSuspected { .. }
+            | Event::OriginChanged { .. }
+            | Event::PrefixHealthChanged { .. } => EventTopic::Routing,
+            Event::ArchiveSegmentOpened { .. }
+            | Event::ArchiveSegmentFinalized { .. }
+            | Event::ArchiveReplicationSucceeded { .. }
+            | Event::ArchiveReplicationFailed { .. }
+            | Event::ArchiveRecordsShed { .. }
+            | Event::ArchiveAuditCompleted { .. }
+            | Event::ArchiveDiskPressure { .. }
+            | Event::ArchiveSnapshotFailed { .. }
+            | Event::ArchiveWriterFailed { .. }
+            | Event::ArchiveWriterRecovered { .. }
+            | Event::ArchiveRibGap { .. } => EventTopic::Archive,
+            Event::EventsDropped { .. } => EventTopic::Bus,
+        }
+    }
+}
+
+/// Cheap to clone; all clones publish to and subscribe from the same bus.
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    inner: Arc<EventBusInner>,
+}
+
+#[derive(Debug)]
+struct EventBusInner {
+    tx: broadcast::Sender<EventEnvelope>,
+    capacity: usize,
+    /// Held across the send so subscribers see `seq` in order.
+    last_seq: Mutex<u64>,
+    subscribers: Arc<EventSubscribers>,
+}
+
+impl EventBus {
+    /// A bus buffering `capacity` events for its slowest subscriber.
+    pub fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(1);
+        Self {
+            inner: Arc::new(EventBusInner {
+                tx: broadcast::channel(capacity).0,
+                capacity,
+                last_seq: Mutex::new(0),
+                subscribers: EventSubscribers::new(),
+            }),
+        }
+    }
+
+    /// Number `event` and deliver it to current subscribers; returns its
+    /// sequence number, counted from 1.
+    pub fn publish(&self, event: Event) -> u64 {
+        let mut last_seq = self.lock_seq();
+        *last_seq += 1;
+        let mut envelope = EventEnvelope::new(event);
+        envelope.seq = *last_seq;
+        let _ = self.inner.tx.send(envelope);
+        *last_seq
+    }
+
+    /// Subscribe `kind` to `topics`, or to every topic when empty.
+    pub fn subscribe(&self, kind: &str, topics: &[EventTopic]) -> Subscription {
+        self.inner
+            .subscribers
+            .subscribe(kind, self.inner.tx.subscribe(), topics)
+    }
+
+    pub fn subscribers(&self) -> &Arc<EventSubscribers> {
+        &self.inner.subscribers
+    }
+
+    /// Sequence number of the most recent event, or 0 before the first.
+    pub fn last_seq(&self) -> u64 {
+        *self.lock_seq()
+    }
+
+    pub fn capacity(&self) -> usize {
+        self.inner.capacity
+    }
+
+    /// Events still buffered for the slowest subscriber.
+    pub fn backlog(&self) -> usize {
+        self.inner.tx.len()
+    }
+
+    fn lock_seq(&self) -> std::sync::MutexGuard<'_, u64> {
+        self.inner.last_seq.lock().expect("event bus lock poisoned")
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new(DEFAULT_EVENT_BUFFER)
+    }
+}
+
+/// Registry of live [`EventBus`] subscribers and how many events each missed.
+#[derive(Debug, Default)]
+pub struct EventSubscribers {
+    next_id: AtomicU64,
+    entries: Mutex<HashMap<u64, SubscriberStats>>,
+    /// Events dropped across all subscribers, including departed ones.
+    dropped_total: AtomicU64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SubscriberStats {
+    pub id: u64,
+    pub kind: String,
+    pub since: i64,
+    pub delivered: u64,
+    pub dropped: u64,
+    /// Times this subscriber fell behind the buffer.
+    #[serde(default)]
+    pub lagged: u64,
+}
+
+impl EventSubscribers {
+    pub fn new() -> Arc<Self> {
+        Arc::new(Self::default())
+    }
+
+    /// Register `rx` as `kind`, delivering only `topics` unless empty.
+    pub fn subscribe(
+        self: &Arc<Self>,
+        kind: &str,
+        rx: broadcast::Receiver<EventEnvelope>,
+        topics: &[EventTopic],
+    ) -> Subscription {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed) + 1;
+        self.lock().insert(
+            id,
+            SubscriberStats {
+                id,
+                kind: kind.to_string(),
+                since: Utc::now().timestamp(),
+                delivered: 0,
+                dropped: 0,
+                lagged: 0,
+            },
+        );
+        Subscription {
+            id,
+            rx,
+            topics: topics.to_vec(),
+            registry: Arc::clone(self),
+        }
+    }
+
+    pub fn snapshot(&self) -> Vec<SubscriberStats> {
+        let mut subscribers = self.lock().values().cloned().collect::<Vec<_>>();
+        subscribers.sort_by_key(|s| s.id);
+        subscribers
+    }
+
+    pub fn dropped_total(&self) -> u64 {
+        self.dropped_total.load(Ordering::Relaxed)
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, HashMap<u64, SubscriberStats>> {
+        self.entries
+            .lock()
+            .expect("event subscribers lock poisoned")
+    }
+}
+
+/// A registered event bus receiver; unregisters itself on drop.
+#[derive(Debug)]
+pub struct Subscription {
+    id: u64,
+    rx: broadcast::Receiver<EventEnvelope>,
+    topics: Vec<EventTopic>,
+    registry: Arc<EventSubscribers>,
+}
+
+impl Subscription {
+    /// Next event on the subscribed topics, or an `events_dropped` gap notice
+    /// if this subscriber fell behind the broadcast buffer. `None` once the
+    /// bus is closed.
+    pub async fn recv(&mut self) -> Option<EventEnvelope> {
+        loop {
+            match self.rx.recv().await {
+                Ok(envelope) if !self.wants(envelope.event.topic()) => {}
+                Ok(envelope) => return Some(self.delivered(envelope)),
+                Err(broadcast::error::RecvError::Lagged(dropped)) => {
+                    return Some(self.lagged(dropped))
+                }
+                Err(broadcast::error::RecvError::Closed) => return None,
+            }
+        }
+    }
+
+    /// Like [`recv`](Self::recv), but `None` as soon as nothing is buffered.
+    pub fn try_recv(&mut self) -> Option<EventEnvelope> {
+        loop {
+            match self.rx.try_recv() {
+                Ok(envelope) if !self.wants(envelope.event.topic()) => {}
+                Ok(envelope) => return Some(self.delivered(envelope)),
+                Err(broadcast::error::TryRecvError::Lagged(dropped)) => {
+                    return Some(self.lagged(dropped))
+                }
+                Err(_) => return None,
+            }
+        }
+    }
+
+    fn delivered(&self, envelope: EventEnvelope) -> EventEnvelope {
+        if let Some(stats) = self.registry.lock().get_mut(&self.id) {
+            stats.delivered += 1;
+        }
+        envelope
+    }
+
+    fn lagged(&self, dropped: u64) -> EventEnvelope {
+        self.registry
+            .dropped_total
+            .fetch_add(dropped, Ordering::Relaxed);
+        let kind = match self.registry.lock().get_mut(&self.id) {
+            Some(stats) => {
+                stats.dropped += dropped;
+                stats.lagged += 1;
+                stats.kind.clone()
+            }
+            None => String::new(),
+        };
+        tracing::warn!(
+            subscriber = self.id,
+            kind,
+            dropped,
+            "event subscriber lagged"
+        );
+        EventEnvelope::new(Event::EventsDropped { dropped })
+    }
+
+    fn wants(&self, topic: EventTopic) -> bool {
+        topic == EventTopic::Bus || self.topics.is_empty() || self.topics.contains(&topic)
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.registry.lock().remove(&self.id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::PeerState;
+
+    #[tokio::test]
+    async fn numbers_events_and_filters_subscriptions_by_topic() {
+        let bus = EventBus::new(16);
+        let mut all = bus.subscribe("all", &[]);
+        let mut peers = bus.subscribe("peers", &[EventTopic::Peer]);
+
+        assert_eq!(
+            bus.publish(Event::ArchiveSnapshotFailed {
+                error: "disk full".to_string(),
+            }),
+            1
+        );
+        bus.publish(Event::PeerState {
+            peer: "192.0.2.2".to_string(),
+            state: PeerState::Established,
+        });
+        assert_eq!(bus.last_seq(), 2);
+
+        let first = all.recv().await.unwrap();
+        assert_eq!((first.seq, first.event.topic()), (1, EventTopic::Archive));
+        assert_eq!(all.recv().await.unwrap().seq, 2);
+
+        let peer = peers.recv().await.unwrap();
+        assert_eq!(peer.seq, 2);
+        assert!(matches!(peer.event, Event::PeerState { .. }));
+
+        let stats = bus.subscribers().snapshot();
+        assert_eq!(stats.len(), 2);
+        assert_eq!((stats[1].kind.as_str(), stats[1].delivered), ("peers", 1));
+    }
+
+    #[tokio::test]
+    async fn lagged_subscriber_gets_gap_notice() {
+        let (tx, rx) = broadcast::channel(2);
+        let subscribers = EventSubscribers::new();
+        let mut sub = subscribers.subscribe("test", rx, &[]);
+
+        for i in 0..5 {
+            tx.send(EventEnvelope::new(Event::EventsDropped { dropped: i }))
+                .unwrap();
+        }
+
+        let notice = sub.recv().await.unwrap();
+        assert!(matches!(notice.event, Event::EventsDropped { dropped: 3 }));
+        assert!(sub.recv().await.is_some());
+        let stats = subscribers.snapshot();
+        assert_eq!(stats[0].dropped, 3);
+        assert_eq!(stats[0].delivered, 1);
+        assert_eq!(stats[0].lagged, 1);
+
+        drop(sub);
+        assert!(subscribers.snapshot().is_empty());
+        assert_eq!(subscribers.dropped_total(), 3);
+    }
+}
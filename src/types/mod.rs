@@ -0,0 +1,232 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+pub mod bus;
+
+pub use bus::{EventBus, EventSubscribers, EventTopic, SubscriberStats, Subscription};
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ControlRequest {
+    pub version: u16,
+    pub id: String,
+    pub cmd: String,
+    #[serde(default)]
+    pub args: Value,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ControlResponse {
+    pub version: u16,
+    pub id: String,
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<ControlError>,
+}
+
+impl ControlResponse {
+    pub fn ok(id: impl Into<String>, result: Value) -> Self {
+        Self {
+            version: 1,
+            id: id.into(),
+            ok: true,
+            result: Some(result),
+            error: None,
+        }
+    }
+
+    pub fn err(id: impl Into<String>, code: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            version: 1,
+            id: id.into(),
+            ok: false,
+            result: None,
+            error: Some(ControlError {
+                code: code.into(),
+                message: message.into(),
+            }),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ControlError {
+    pub code: String,
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PeerState {
+    Idle,
+    Connect,
+    Active,
+    OpenSent,
+    OpenConfirm,
+    Established,
+}
+
+/// Escalating responses to low free space, ordered by severity.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DiskLevel {
+    #[default]
+    Ok,
+    Warn,
+    /// RIB snapshots are skipped.
+    PauseRibs,
+    /// RIB snapshots are skipped and updates are dropped.
+    PauseUpdates,
+}
+
+impl DiskLevel {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            DiskLevel::Ok => "ok",
+            DiskLevel::Warn => "warn",
+            DiskLevel::PauseRibs => "pause_ribs",
+            DiskLevel::PauseUpdates => "pause_updates",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "event", content = "payload")]
+pub enum Event {
+    #[serde(rename = "peer_state")]
+    PeerState { peer: String, state: PeerState },
+    #[serde(rename = "archive_segment_opened")]
+    ArchiveSegmentOpened {
+        stream: String,
+        path: String,
+        start_ts: i64,
+    },
+    #[serde(rename = "archive_segment_finalized")]
+    ArchiveSegmentFinalized {
+        stream: String,
+        path: String,
+        end_ts: i64,
+        records: u64,
+    },
+    #[serde(rename = "archive_replication_succeeded")]
+    ArchiveReplicationSucceeded { destination: String, path: String },
+    #[serde(rename = "archive_replication_failed")]
+    ArchiveReplicationFailed {
+        destination: String,
+        path: String,
+        error: String,
+    },
+    /// The archive ingest queue was full and `shed` of this peer's records
+    /// were dropped since the previous notice.
+    #[serde(rename = "archive_records_shed")]
+    ArchiveRecordsShed { peer: String, shed: u64 },
+    /// A received UPDATE looks like a route leak given the configured peer
+    /// roles; `as_path` is the offending path.
+    #[serde(rename = "route_leak_suspected")]
+    RouteLeakSuspected {
+        peer: String,
+        prefixes: Vec<String>,
+        as_path: Vec<u32>,
+        reason: String,
+    },
+    /// A received UPDATE announces `prefix`, one of ours, or more-specifics
+    /// of it with a foreign origin; `as_path` is the offending path.
+    #[serde(rename = "prefix_hijack_suspected")]
+    PrefixHijackSuspected {
+        peer: String,
+        prefix: String,
+        announced: Vec<String>,
+        origin: u32,
+        as_path: Vec<u32>,
+    },
+    /// `peer` re-announced a `[rib_in].watch_prefixes` prefix with another
+    /// AS path. Origins are `None` for an empty (iBGP) path.
+    #[serde(rename = "origin_changed")]
+    OriginChanged {
+        peer: String,
+        prefix: String,
+        old_origin: Option<u32>,
+        new_origin: Option<u32>,
+        old_as_path: Vec<u32>,
+        new_as_path: Vec<u32>,
+    },
+    /// A `[prefixes.health_check]` changed state and its prefixes were
+    /// announced (`healthy`) or withdrawn.
+    #[serde(rename = "prefix_health_changed")]
+    PrefixHealthChanged {
+        prefixes: Vec<String>,
+        target: String,
+        healthy: bool,
+    },
+    /// A periodic replica audit of `destination` finished; `healed` of the
+    /// missing or mismatched segments were queued for replication.
+    #[serde(rename = "archive_audit_completed")]
+    ArchiveAuditCompleted {
+        destination: String,
+        missing: usize,
+        mismatched: usize,
+        healed: usize,
+    },
+    /// Free space on the archive root crossed a `[archive.disk_guard]`
+    /// threshold; `pruned` segments were deleted to recover space.
+    #[serde(rename = "archive_disk_pressure")]
+    ArchiveDiskPressure {
+        free_mb: u64,
+        level: DiskLevel,
+        pruned: usize,
+    },
+    /// Writing a RIB snapshot failed.
+    #[serde(rename = "archive_snapshot_failed")]
+    ArchiveSnapshotFailed { error: String },
+    /// Writing the `stream` segment failed. The broken segment was moved to
+    /// `quarantined`, if it existed, and a new one is tried in
+    /// `retry_in_secs`.
+    #[serde(rename = "archive_writer_failed")]
+    ArchiveWriterFailed {
+        stream: String,
+        error: String,
+        quarantined: Option<String>,
+        consecutive_failures: u64,
+        retry_in_secs: u64,
+    },
+    /// A write succeeded after `failures` consecutive failures.
+    #[serde(rename = "archive_writer_recovered")]
+    ArchiveWriterRecovered { stream: String, failures: u64 },
+    /// No RIB snapshot exists for `buckets` intervals from `first_bucket`
+    /// through `last_bucket`, because focld was down or disk space was low.
+    #[serde(rename = "archive_rib_gap")]
+    ArchiveRibGap {
+        first_bucket: i64,
+        last_bucket: i64,
+        buckets: u64,
+        reason: String,
+    },
+    /// Gap notice: this subscriber fell behind and `dropped` events were lost.
+    #[serde(rename = "events_dropped")]
+    EventsDropped { dropped: u64 },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EventEnvelope {
+    pub version: u16,
+    #[serde(rename = "type")]
+    pub envelope_type: String,
+    /// Position on the [`EventBus`], from 1; 0 for gap notices, which are
+    /// made per subscriber.
+    #[serde(default)]
+    pub seq: u64,
+    #[serde(flatten)]
+    pub event: Event,
+}
+
+impl EventEnvelope {
+    pub fn new(event: Event) -> Self {
+        Self {
+            version: 1,
+            envelope_type: "event".to_string(),
+            seq: 0,
+            event,
+        }
+    }
+}
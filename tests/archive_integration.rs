@@ -1,66 +1,36 @@
 use std::net::Ipv4Addr;
 
-use focl::archive::types::UpdateRecordInput;
-use focl::archive::ArchiveService;
+use bgpkit_parser::models::{MrtMessage, TableDumpV2Message, TableDumpV2Type};
+use bgpkit_parser::BgpkitParser;
+use focl::archive::types::{RibSnapshotInput, SnapshotPeer, SnapshotRoute, UpdateRecordInput};
+use focl::archive::{ArchiveService, IngestRecord};
 use focl::config::{
     ArchiveConfig, ArchiveDestinationConfig, CompressionKind, DestinationMode, DestinationType,
 };
+use focl::types::{Event, EventBus};
 
 #[tokio::test]
 async fn writes_updates_segment_and_manifest_on_rollover() {
     let tmp = tempfile::tempdir().unwrap();
     let root = tmp.path().join("archive");
-    let tmp_root = root.join(".tmp");
+    let cfg = archive_config(&root);
 
-    let mut cfg = ArchiveConfig {
-        enabled: true,
-        root: root.clone(),
-        tmp_root,
-        compression: CompressionKind::Gzip,
-        ..ArchiveConfig::default()
-    };
-
-    cfg.destinations = vec![ArchiveDestinationConfig {
-        destination_type: DestinationType::Local,
-        mode: DestinationMode::Primary,
-        path: Some(root.clone()),
-        required: Some(true),
-        endpoint: None,
-        bucket: None,
-        prefix: None,
-        upload_concurrency: Some(1),
-        retry_backoff_secs: Some(1),
-        max_retries: Some(0),
-        region: None,
-        access_key_id: None,
-        secret_access_key: None,
-        session_token: None,
-    }];
-
-    cfg.validate().unwrap();
-
-    let service = ArchiveService::new(cfg, Ipv4Addr::new(192, 0, 2, 1))
+    let service = ArchiveService::new(cfg, Ipv4Addr::new(192, 0, 2, 1), EventBus::default())
         .await
         .unwrap();
 
-    service
-        .ingest_update(UpdateRecordInput {
-            timestamp: 1_700_000_001,
-            peer_asn: 64512,
-            local_asn: 64513,
-            interface_index: 0,
-            peer_ip: Ipv4Addr::new(198, 51, 100, 1),
-            local_ip: Ipv4Addr::new(198, 51, 100, 2),
-            bgp_message: valid_update_withdraw_message(),
-        })
-        .await
-        .unwrap();
+    service.ingest_update(update(None)).await.unwrap();
 
     service
         .rollover(focl::archive::types::ArchiveStream::Updates)
         .await
         .unwrap();
 
+    let status = service.status().await.unwrap();
+    assert_eq!(status.updates.last_finalized_record_count, 1);
+    assert!(status.updates.last_finalized_ts.is_some());
+    assert!(status.updates.last_error.is_none());
+
     let mut found_segment = false;
     let mut found_manifest = false;
 
@@ -81,6 +51,321 @@ async fn writes_updates_segment_and_manifest_on_rollover() {
     assert!(found_manifest, "expected at least one segment manifest");
 }
 
+#[tokio::test]
+async fn writes_a_peer_stream_under_its_own_collector() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path().join("archive");
+    let service = ArchiveService::new(
+        archive_config(&root),
+        Ipv4Addr::new(192, 0, 2, 1),
+        EventBus::default(),
+    )
+    .await
+    .unwrap();
+
+    service.ingest_update(update(None)).await.unwrap();
+    service
+        .ingest_update(update(Some("focl01-private")))
+        .await
+        .unwrap();
+    service
+        .ingest_update(update(Some("focl01-private")))
+        .await
+        .unwrap();
+    service
+        .rollover(focl::archive::types::ArchiveStream::Updates)
+        .await
+        .unwrap();
+
+    let records = |collector: &str| -> u64 {
+        walkdir::WalkDir::new(root.join(collector))
+            .into_iter()
+            .map(|entry| entry.unwrap().into_path())
+            .filter(|path| path.to_string_lossy().ends_with(".gz.json"))
+            .map(|path| {
+                let manifest: serde_json::Value =
+                    serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap();
+                assert_eq!(manifest["collector_id"], collector);
+                manifest["record_count"].as_u64().unwrap()
+            })
+            .sum()
+    };
+    assert_eq!(records("focl01"), 1);
+    assert_eq!(records("focl01-private"), 2);
+    // Status reports the collector's own stream.
+    let status = service.status().await.unwrap();
+    assert_eq!(status.updates.last_finalized_record_count, 1);
+}
+
+#[tokio::test]
+async fn failed_segment_open_is_not_announced() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path().join("archive");
+    let cfg = archive_config(&root);
+    let tmp_root = cfg.tmp_root.clone();
+    let bus = EventBus::default();
+    let mut events = bus.subscribe("test", &[]);
+    let service = ArchiveService::new(cfg, Ipv4Addr::new(192, 0, 2, 1), bus)
+        .await
+        .unwrap();
+    // The peer stream's tmp directory cannot be created.
+    std::fs::write(tmp_root.join("focl01-private"), b"").unwrap();
+
+    assert!(service
+        .ingest_update(update(Some("focl01-private")))
+        .await
+        .is_err());
+
+    let mut failed = false;
+    while let Some(envelope) = events.try_recv() {
+        match envelope.event {
+            Event::ArchiveSegmentOpened { path, .. } => {
+                assert!(!path.contains("focl01-private"), "announced {path}");
+            }
+            Event::ArchiveWriterFailed { .. } => failed = true,
+            _ => {}
+        }
+    }
+    assert!(failed);
+}
+
+#[tokio::test]
+async fn splits_rib_routes_by_family() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path().join("archive");
+    let mut cfg = archive_config(&root);
+    cfg.split_address_families = true;
+    let service = ArchiveService::new(cfg, Ipv4Addr::new(192, 0, 2, 1), EventBus::default())
+        .await
+        .unwrap();
+
+    let route = |sequence, prefix: &str, prefix_len| SnapshotRoute {
+        sequence,
+        prefix: prefix.parse().unwrap(),
+        prefix_len,
+        peer_index: 0,
+        originated_time: 1_700_000_000,
+        path_attributes: vec![],
+    };
+    service
+        .snapshot_now(RibSnapshotInput {
+            timestamp: 1_700_000_000,
+            collector_bgp_id: Ipv4Addr::UNSPECIFIED,
+            view_name: "main".to_string(),
+            peers: vec![SnapshotPeer {
+                peer_bgp_id: Ipv4Addr::new(198, 51, 100, 1),
+                peer_ip: "2001:db8::1".parse().unwrap(),
+                peer_asn: 64512,
+            }],
+            routes: vec![
+                route(0, "203.0.113.0", 24),
+                route(1, "2001:db8:1::", 48),
+                route(2, "2001:db8:2::", 48),
+            ],
+        })
+        .await
+        .unwrap();
+
+    let rib = |token: &str| -> Vec<(TableDumpV2Type, u32, String)> {
+        let segment = walkdir::WalkDir::new(&root)
+            .into_iter()
+            .map(Result::unwrap)
+            .find(|entry| {
+                let name = entry.file_name().to_string_lossy();
+                name.starts_with(&format!("rib.{token}.")) && name.ends_with(".gz")
+            })
+            .expect("a RIB segment");
+        BgpkitParser::new(segment.path().to_str().unwrap())
+            .unwrap()
+            .into_record_iter()
+            .filter_map(|record| match record.message {
+                MrtMessage::TableDumpV2Message(TableDumpV2Message::RibAfi(rib)) => Some((
+                    rib.rib_type,
+                    rib.sequence_number,
+                    rib.prefix.prefix.to_string(),
+                )),
+                _ => None,
+            })
+            .collect()
+    };
+    assert_eq!(
+        rib("v4"),
+        vec![(
+            TableDumpV2Type::RibIpv4Unicast,
+            0,
+            "203.0.113.0/24".to_string()
+        )]
+    );
+    assert_eq!(
+        rib("v6"),
+        vec![
+            (
+                TableDumpV2Type::RibIpv6Unicast,
+                0,
+                "2001:db8:1::/48".to_string()
+            ),
+            (
+                TableDumpV2Type::RibIpv6Unicast,
+                1,
+                "2001:db8:2::/48".to_string()
+            ),
+        ]
+    );
+}
+
+#[tokio::test]
+async fn shutdown_writes_every_submitted_record() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path().join("archive");
+    let service = ArchiveService::new(
+        archive_config(&root),
+        Ipv4Addr::new(192, 0, 2, 1),
+        EventBus::default(),
+    )
+    .await
+    .unwrap();
+
+    for _ in 0..500 {
+        assert!(service.submit(IngestRecord::Update(update(None))).await);
+    }
+    service.shutdown().await.unwrap();
+
+    let records: u64 = walkdir::WalkDir::new(&root)
+        .into_iter()
+        .map(|entry| entry.unwrap().into_path())
+        .filter(|path| {
+            let name = path.file_name().unwrap().to_string_lossy();
+            name.starts_with("updates.") && name.ends_with(".gz.json")
+        })
+        .map(|path| {
+            let manifest: serde_json::Value =
+                serde_json::from_slice(&std::fs::read(path).unwrap()).unwrap();
+            manifest["record_count"].as_u64().unwrap()
+        })
+        .sum();
+    assert_eq!(records, 500);
+}
+
+#[tokio::test]
+async fn shutdown_finalizes_the_other_series_when_one_fails() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path().join("archive");
+    let cfg = archive_config(&root);
+    let tmp_root = cfg.tmp_root.clone();
+    let service = ArchiveService::new(cfg, Ipv4Addr::new(192, 0, 2, 1), EventBus::default())
+        .await
+        .unwrap();
+    service.ingest_update(update(None)).await.unwrap();
+    service
+        .ingest_update(update(Some("focl01-private")))
+        .await
+        .unwrap();
+
+    // The collector's own series, finalized first, loses its tmp file.
+    std::fs::remove_dir_all(tmp_root.join("focl01")).unwrap();
+    assert!(service.shutdown().await.is_err());
+
+    let manifests = walkdir::WalkDir::new(root.join("focl01-private"))
+        .into_iter()
+        .map(|entry| entry.unwrap().into_path())
+        .filter(|path| path.to_string_lossy().ends_with(".gz.json"))
+        .count();
+    assert_eq!(manifests, 1);
+}
+
+#[tokio::test]
+async fn restart_within_a_rib_bucket_keeps_its_snapshot() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path().join("archive");
+    let rib_segments = || -> Vec<std::path::PathBuf> {
+        walkdir::WalkDir::new(&root)
+            .into_iter()
+            .map(|entry| entry.unwrap().into_path())
+            .filter(|path| {
+                let name = path.file_name().unwrap().to_string_lossy();
+                name.starts_with("rib.") && name.ends_with(".gz")
+            })
+            .collect()
+    };
+
+    // The scheduler's first tick snapshots the current bucket.
+    let service = ArchiveService::new(
+        archive_config(&root),
+        Ipv4Addr::new(192, 0, 2, 1),
+        EventBus::default(),
+    )
+    .await
+    .unwrap();
+    for _ in 0..100 {
+        if !rib_segments().is_empty() {
+            break;
+        }
+        tokio::time::sleep(std::time::Duration::from_millis(50)).await;
+    }
+    assert_eq!(rib_segments().len(), 1);
+    service.shutdown().await.unwrap();
+    drop(service);
+
+    let service = ArchiveService::new(
+        archive_config(&root),
+        Ipv4Addr::new(192, 0, 2, 1),
+        EventBus::default(),
+    )
+    .await
+    .unwrap();
+    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+    assert_eq!(rib_segments().len(), 1);
+    let status = service.status().await.unwrap();
+    assert!(status.ribs.last_finalized_ts.is_none());
+    service.shutdown().await.unwrap();
+}
+
+fn archive_config(root: &std::path::Path) -> ArchiveConfig {
+    let mut cfg = ArchiveConfig {
+        enabled: true,
+        root: root.to_path_buf(),
+        tmp_root: root.join(".tmp"),
+        compression: CompressionKind::Gzip,
+        ..ArchiveConfig::default()
+    };
+
+    cfg.destinations = vec![ArchiveDestinationConfig {
+        destination_type: DestinationType::Local,
+        mode: DestinationMode::Primary,
+        path: Some(root.to_path_buf()),
+        required: Some(true),
+        endpoint: None,
+        bucket: None,
+        prefix: None,
+        upload_concurrency: Some(1),
+        retry_backoff_secs: Some(1),
+        max_retries: Some(0),
+        region: None,
+        access_key_id: None,
+        secret_access_key: None,
+        session_token: None,
+        lifecycle: None,
+        object_lock: None,
+    }];
+
+    cfg.validate().unwrap();
+    cfg
+}
+
+fn update(collector_id: Option<&str>) -> UpdateRecordInput {
+    UpdateRecordInput {
+        timestamp: 1_700_000_001,
+        peer_asn: 64512,
+        local_asn: 64513,
+        interface_index: 0,
+        peer_ip: Ipv4Addr::new(198, 51, 100, 1).into(),
+        local_ip: Ipv4Addr::new(198, 51, 100, 2).into(),
+        bgp_message: valid_update_withdraw_message(),
+        family: Default::default(),
+        collector_id: collector_id.map(str::to_string),
+    }
+}
+
 fn valid_update_withdraw_message() -> Vec<u8> {
     let mut msg = vec![0xff; 16];
     msg.extend_from_slice(&24u16.to_be_bytes());
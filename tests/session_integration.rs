@@ -0,0 +1,524 @@
+use std::net::{IpAddr, Ipv4Addr};
+use std::time::Duration;
+
+use bgpkit_parser::bgp::parse_bgp_message;
+use bgpkit_parser::models::{AsnLength, Bgp4MpEnum, BgpMessage, BgpState, MrtMessage, ParamValue};
+use bgpkit_parser::BgpkitParser;
+use focl::archive::types::ArchiveStream;
+use focl::archive::ArchiveService;
+use focl::bgp::{
+    AfiSafi, BgpService, NotificationCodes, PeerErrorCode, ResetMode, SessionEventKind,
+};
+use focl::config::RibSource;
+use focl::sim::{MessageType, SimConfig, SimPeer, Step};
+use focl::types::Subscription;
+use focl::types::{Event, EventBus, EventTopic, PeerState};
+use focl::FoclConfig;
+use tokio::net::TcpListener;
+
+const SIM_ASN: u32 = 65002;
+
+/// A collector with one active peer, the simulator listening on `listener`.
+async fn collector(listener: &TcpListener) -> (BgpService, Subscription) {
+    let bus = EventBus::new(64);
+    let events = bus.subscribe("test", &[EventTopic::Peer]);
+    let bgp = BgpService::new(&config(listener, ""), bus, None)
+        .await
+        .unwrap();
+    (bgp, events)
+}
+
+/// Collector config with `extra` appended.
+fn config(listener: &TcpListener, extra: &str) -> FoclConfig {
+    let port = listener.local_addr().unwrap().port();
+    let cfg: FoclConfig = toml::from_str(&format!(
+        r#"
+        [global]
+        asn = 65001
+        router_id = "192.0.2.1"
+
+        [rib_in]
+        enabled = true
+
+        [[peers]]
+        address = "127.0.0.1"
+        remote_as = {SIM_ASN}
+        remote_port = {port}
+        connect_retry_secs = 60
+
+        {extra}
+        "#
+    ))
+    .unwrap();
+    cfg.validate().unwrap();
+    cfg
+}
+
+async fn wait_for_state(events: &mut Subscription, wanted: PeerState) {
+    tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let envelope = events.recv().await.unwrap();
+            if let Event::PeerState { state, .. } = envelope.event {
+                if std::mem::discriminant(&state) == std::mem::discriminant(&wanted) {
+                    return;
+                }
+            }
+        }
+    })
+    .await
+    .unwrap();
+}
+
+/// focld dials the simulator, which plays a short session: a burst of
+/// announcements, a withdrawal, then a Cease.
+#[tokio::test]
+async fn ingests_scripted_session_and_handles_notification() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let (bgp, mut events) = collector(&listener).await;
+    let mut received = bgp.subscribe_received();
+
+    let sim_cfg = SimConfig::new(SIM_ASN, "192.0.2.2".parse().unwrap());
+    let mut sim = SimPeer::accept(sim_cfg, &listener).await.unwrap();
+    sim.run(&[
+        Step::Handshake,
+        Step::BulkUpdates {
+            prefix: "10.0.0.0/24".parse().unwrap(),
+            count: 100,
+            as_path: vec![SIM_ASN, 64500],
+        },
+        Step::Update {
+            announce: vec![],
+            withdraw: vec!["10.0.0.0/24".parse().unwrap()],
+            as_path: vec![],
+        },
+        Step::Keepalive,
+    ])
+    .await
+    .unwrap();
+
+    for _ in 0..101 {
+        tokio::time::timeout(Duration::from_secs(5), received.recv())
+            .await
+            .unwrap()
+            .unwrap();
+    }
+    let info = bgp.peer_show("127.0.0.1").await.unwrap();
+    assert!(matches!(info.state, PeerState::Established));
+    assert_eq!(info.updates_received, 101);
+    assert_eq!(info.prefixes_received, 100);
+    assert_eq!(info.withdrawals_received, 1);
+    assert_eq!(bgp.rib_in("127.0.0.1").await.unwrap().len(), 99);
+    let table = bgp
+        .rib_snapshot(RibSource::AdjRibIn, None, "main", 1_700_000_000)
+        .await
+        .unwrap();
+    assert_eq!(table.peers.len(), 1);
+    assert_eq!(table.routes.len(), 99);
+
+    sim.step(&Step::Notification {
+        code: 6,
+        subcode: 2,
+    })
+    .await
+    .unwrap();
+    wait_for_state(&mut events, PeerState::Active).await;
+    let info = bgp.peer_show("127.0.0.1").await.unwrap();
+    let error = info.last_error.unwrap();
+    assert_eq!(error.code, PeerErrorCode::NotificationReceived);
+    assert_eq!(
+        error.notification,
+        Some(NotificationCodes {
+            code: 6,
+            subcode: 2
+        })
+    );
+    // The session's routes go with it.
+    assert!(bgp.rib_in("127.0.0.1").await.unwrap().is_empty());
+    assert!(sim.expect(MessageType::Keepalive).await.is_err());
+
+    bgp.shutdown(Duration::from_secs(1)).await;
+}
+
+/// A hard reset closes the session with a Cease and goes Idle before the
+/// peer reconnects, instead of dropping the connection mid-stream.
+#[tokio::test]
+async fn hard_reset_closes_session_with_cease() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let (bgp, mut events) = collector(&listener).await;
+
+    let sim_cfg = SimConfig::new(SIM_ASN, "192.0.2.2".parse().unwrap());
+    let mut sim = SimPeer::accept(sim_cfg.clone(), &listener).await.unwrap();
+    sim.handshake().await.unwrap();
+    wait_for_state(&mut events, PeerState::Established).await;
+
+    bgp.peer_reset("127.0.0.1", ResetMode::Hard).await.unwrap();
+    let cease = sim.expect(MessageType::Notification).await.unwrap();
+    assert_eq!(cease.frame[19..21], [6, 4]);
+    wait_for_state(&mut events, PeerState::Idle).await;
+
+    let mut sim = SimPeer::accept(sim_cfg, &listener).await.unwrap();
+    sim.handshake().await.unwrap();
+    wait_for_state(&mut events, PeerState::Established).await;
+
+    // The timeline tells the story: reset, Cease, and the way back up.
+    let history: Vec<_> = bgp
+        .peer_history("127.0.0.1")
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|event| event.kind)
+        .collect();
+    let reset = history
+        .iter()
+        .position(|kind| {
+            *kind
+                == SessionEventKind::Reset {
+                    mode: ResetMode::Hard,
+                }
+        })
+        .unwrap();
+    assert_eq!(
+        history[reset + 1],
+        SessionEventKind::NotificationSent {
+            code: 6,
+            subcode: 4
+        }
+    );
+    assert!(matches!(
+        history.last(),
+        Some(SessionEventKind::StateChange {
+            from: PeerState::OpenConfirm,
+            to: PeerState::Established
+        })
+    ));
+
+    bgp.shutdown(Duration::from_secs(1)).await;
+}
+
+/// An OPEN from the wrong AS is refused with OPEN Message Error / Bad Peer AS.
+#[tokio::test]
+async fn rejects_open_from_wrong_as() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let (bgp, mut events) = collector(&listener).await;
+
+    let sim_cfg = SimConfig::new(SIM_ASN + 1, "192.0.2.2".parse().unwrap());
+    let mut sim = SimPeer::accept(sim_cfg, &listener).await.unwrap();
+    sim.run(&[
+        Step::Open,
+        Step::Expect {
+            message: MessageType::Open,
+        },
+    ])
+    .await
+    .unwrap();
+    let notification = sim.expect(MessageType::Notification).await.unwrap();
+    assert_eq!(notification.frame[19..21], [2, 2]);
+
+    wait_for_state(&mut events, PeerState::Active).await;
+    let info = bgp.peer_show("127.0.0.1").await.unwrap();
+    let error = info.last_error.unwrap();
+    assert_eq!(error.code, PeerErrorCode::OpenMismatch);
+    assert!(error.message.contains("does not match remote_as"));
+
+    bgp.shutdown(Duration::from_secs(1)).await;
+}
+
+/// `peer_wait` returns once the session is up, and gives up at its timeout.
+#[tokio::test]
+async fn peer_wait_blocks_until_established() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let (bgp, _events) = collector(&listener).await;
+
+    let short = Duration::from_millis(100);
+    assert!(bgp
+        .peer_wait("127.0.0.1", PeerState::Established, short)
+        .await
+        .unwrap()
+        .is_none());
+    assert!(bgp
+        .peer_wait("192.0.2.99", PeerState::Established, short)
+        .await
+        .is_err());
+
+    let waiter = {
+        let bgp = bgp.clone();
+        tokio::spawn(async move {
+            bgp.peer_wait("127.0.0.1", PeerState::Established, Duration::from_secs(5))
+                .await
+        })
+    };
+    let sim_cfg = SimConfig::new(SIM_ASN, "192.0.2.2".parse().unwrap());
+    let mut sim = SimPeer::accept(sim_cfg, &listener).await.unwrap();
+    sim.handshake().await.unwrap();
+    let info = waiter.await.unwrap().unwrap().unwrap();
+    assert_eq!(info.state, PeerState::Established);
+
+    // Already there: no waiting.
+    assert!(bgp
+        .peer_wait("127.0.0.1", PeerState::Established, short)
+        .await
+        .unwrap()
+        .is_some());
+
+    bgp.shutdown(Duration::from_secs(1)).await;
+}
+
+/// Every UPDATE and state change of a live session lands in the updates
+/// stream, with the session's addresses.
+#[tokio::test]
+async fn archives_session_updates_and_state_changes() {
+    let tmp = tempfile::tempdir().unwrap();
+    let root = tmp.path().join("archive");
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let cfg = config(
+        &listener,
+        &format!(
+            r#"
+            [archive]
+            enabled = true
+            root = "{root}"
+            tmp_root = "{root}/.tmp"
+
+            [[archive.destinations]]
+            type = "local"
+            mode = "primary"
+            path = "{root}"
+            "#,
+            root = root.display()
+        ),
+    );
+    let bus = EventBus::new(64);
+    let mut events = bus.subscribe("test", &[EventTopic::Peer]);
+    let archive = ArchiveService::new(
+        cfg.archive.clone(),
+        Ipv4Addr::new(192, 0, 2, 1),
+        bus.clone(),
+    )
+    .await
+    .unwrap();
+    let bgp = BgpService::new(&cfg, bus, Some(archive.clone()))
+        .await
+        .unwrap();
+
+    let sim_cfg = SimConfig::new(SIM_ASN, "192.0.2.2".parse().unwrap());
+    let mut sim = SimPeer::accept(sim_cfg, &listener).await.unwrap();
+    sim.run(&[
+        Step::Handshake,
+        Step::BulkUpdates {
+            prefix: "10.0.0.0/24".parse().unwrap(),
+            count: 10,
+            as_path: vec![SIM_ASN, 64500],
+        },
+        Step::Notification {
+            code: 6,
+            subcode: 2,
+        },
+    ])
+    .await
+    .unwrap();
+    wait_for_state(&mut events, PeerState::Active).await;
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while archive.status().await.unwrap().ingest_queue_depth > 0 {
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+    })
+    .await
+    .unwrap();
+    bgp.shutdown(Duration::from_secs(1)).await;
+    archive.rollover(ArchiveStream::Updates).await.unwrap();
+
+    let segment = walkdir::WalkDir::new(&root)
+        .into_iter()
+        .map(Result::unwrap)
+        .find(|entry| {
+            let name = entry.file_name().to_string_lossy();
+            name.starts_with("updates.") && name.ends_with(".gz")
+        })
+        .expect("an updates segment");
+    let loopback = IpAddr::V4(Ipv4Addr::LOCALHOST);
+    let (mut updates, mut states) = (0, Vec::new());
+    for record in BgpkitParser::new(segment.path().to_str().unwrap())
+        .unwrap()
+        .into_record_iter()
+    {
+        match record.message {
+            MrtMessage::Bgp4Mp(Bgp4MpEnum::Message(message)) => {
+                assert_eq!(message.peer_ip, loopback);
+                updates += 1;
+            }
+            MrtMessage::Bgp4Mp(Bgp4MpEnum::StateChange(change)) => {
+                assert_eq!(change.peer_ip, loopback);
+                states.push((change.local_addr, change.new_state));
+            }
+            other => panic!("unexpected record {other:?}"),
+        }
+    }
+    assert_eq!(updates, 10);
+    assert!(states.contains(&(loopback, BgpState::Established)));
+    assert!(states.contains(&(loopback, BgpState::Active)));
+}
+
+/// With `listen_port`, the peer's own connection is accepted alongside ours.
+/// Both reach OpenConfirm; the simulator's higher BGP identifier means its
+/// connection is kept and ours is closed with a collision Cease.
+#[tokio::test]
+async fn resolves_connection_collision() {
+    let listen_port = TcpListener::bind("127.0.0.1:0")
+        .await
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port();
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let bus = EventBus::new(64);
+    let mut events = bus.subscribe("test", &[EventTopic::Peer]);
+    let cfg = config(&listener, &format!("listen_port = {listen_port}"));
+    let bgp = BgpService::new(&cfg, bus, None).await.unwrap();
+
+    let sim_cfg = SimConfig::new(SIM_ASN, "192.0.2.2".parse().unwrap());
+    let mut ours = SimPeer::accept(sim_cfg.clone(), &listener).await.unwrap();
+    ours.run(&[
+        Step::Open,
+        Step::Expect {
+            message: MessageType::Open,
+        },
+    ])
+    .await
+    .unwrap();
+    wait_for_state(&mut events, PeerState::OpenConfirm).await;
+
+    let mut theirs = SimPeer::connect(sim_cfg, ([127, 0, 0, 1], listen_port).into())
+        .await
+        .unwrap();
+    theirs
+        .run(&[
+            Step::Open,
+            Step::Expect {
+                message: MessageType::Open,
+            },
+        ])
+        .await
+        .unwrap();
+    let cease = ours.expect(MessageType::Notification).await.unwrap();
+    assert_eq!(cease.frame[19..21], [6, 7]);
+
+    theirs.step(&Step::Keepalive).await.unwrap();
+    theirs.expect(MessageType::Keepalive).await.unwrap();
+    wait_for_state(&mut events, PeerState::Established).await;
+    assert!(bgp
+        .peer_show("127.0.0.1")
+        .await
+        .unwrap()
+        .last_error
+        .is_none());
+
+    bgp.shutdown(Duration::from_secs(1)).await;
+}
+
+/// With `[peers.pacing]`, the initial announcements go out as one burst
+/// and then at the configured rate.
+#[tokio::test]
+async fn paces_initial_announcements() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let prefixes: String = (0..8)
+        .map(|i| format!("[[prefixes]]\nnetwork = \"198.51.100.{}/32\"\n", i))
+        .collect();
+    let cfg = config(
+        &listener,
+        &format!("[peers.pacing]\nupdates_per_sec = 4\nburst = 2\n\n{prefixes}"),
+    );
+    let bgp = BgpService::new(&cfg, EventBus::new(64), None)
+        .await
+        .unwrap();
+
+    let sim_cfg = SimConfig::new(SIM_ASN, "192.0.2.2".parse().unwrap());
+    let mut sim = SimPeer::accept(sim_cfg, &listener).await.unwrap();
+    sim.handshake().await.unwrap();
+    let start = tokio::time::Instant::now();
+    let mut arrivals = Vec::new();
+    for _ in 0..8 {
+        sim.expect(MessageType::Update).await.unwrap();
+        arrivals.push(start.elapsed());
+    }
+    // The burst goes out at once; the other six take 1.5s at 4/s.
+    assert!(arrivals[1] < Duration::from_millis(200), "{arrivals:?}");
+    assert!(arrivals[7] >= Duration::from_millis(1300), "{arrivals:?}");
+    assert_eq!(
+        bgp.peer_show("127.0.0.1")
+            .await
+            .unwrap()
+            .advertised_prefixes,
+        8
+    );
+
+    bgp.shutdown(Duration::from_secs(1)).await;
+}
+
+/// Our OPEN advertises our capabilities, `peer_show` reports what was
+/// negotiated, and ROUTE-REFRESH works in both directions.
+#[tokio::test]
+async fn negotiates_capabilities_and_route_refresh() {
+    let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let cfg = config(&listener, "[[prefixes]]\nnetwork = \"198.51.100.0/24\"");
+    let bus = EventBus::new(64);
+    let mut events = bus.subscribe("test", &[EventTopic::Peer]);
+    let bgp = BgpService::new(&cfg, bus, None).await.unwrap();
+
+    let sim_cfg = SimConfig::new(SIM_ASN, "192.0.2.2".parse().unwrap());
+    let mut sim = SimPeer::accept(sim_cfg, &listener).await.unwrap();
+    sim.step(&Step::Open).await.unwrap();
+    let open = sim.expect(MessageType::Open).await.unwrap();
+    let Ok(BgpMessage::Open(open)) =
+        parse_bgp_message(&mut open.frame.clone(), false, &AsnLength::Bits32)
+    else {
+        panic!("unparsable OPEN");
+    };
+    let codes: Vec<u8> = open
+        .opt_params
+        .iter()
+        .flat_map(|param| match &param.param_value {
+            ParamValue::Capacities(caps) => caps.iter().map(|cap| u8::from(cap.ty)).collect(),
+            ParamValue::Raw(_) => Vec::new(),
+        })
+        .collect();
+    assert_eq!(codes, [1, 1, 2, 65]);
+    sim.step(&Step::Keepalive).await.unwrap();
+    wait_for_state(&mut events, PeerState::Established).await;
+
+    let caps = bgp
+        .peer_show("127.0.0.1")
+        .await
+        .unwrap()
+        .capabilities
+        .unwrap();
+    assert_eq!(caps.address_families, [AfiSafi::Ipv4Unicast]);
+    assert!(caps.four_octet_as && caps.route_refresh);
+    assert_eq!(caps.peer_capabilities, [2, 65]);
+
+    sim.expect(MessageType::Update).await.unwrap();
+    sim.step(&Step::RouteRefresh { afi: 1, safi: 1 })
+        .await
+        .unwrap();
+    sim.expect(MessageType::Update).await.unwrap();
+
+    bgp.peer_reset("127.0.0.1", ResetMode::SoftIn)
+        .await
+        .unwrap();
+    let refresh = sim.expect(MessageType::RouteRefresh).await.unwrap();
+    assert_eq!(refresh.frame[19..23], [0, 1, 0, 1]);
+
+    bgp.shutdown(Duration::from_secs(1)).await;
+    assert!(bgp
+        .peer_show("127.0.0.1")
+        .await
+        .unwrap()
+        .capabilities
+        .is_none());
+    // The peer index keeps the BGP identifier from the peer's last OPEN.
+    let index = bgp.peer_index("main").await.unwrap();
+    assert_eq!(index.len(), 1);
+    assert_eq!(
+        (index[0].peer_bgp_id, index[0].peer_asn),
+        ("192.0.2.2".parse().unwrap(), SIM_ASN)
+    );
+}